@@ -226,7 +226,7 @@ def run_inspect(repo_dir, base_sha, head_sha):
 
     findings = []
     for e in data.get("entity_reviews", []):
-        if e.get("risk_level") not in ("High", "Critical"):
+        if e.get("risk_level", "").lower() not in ("high", "critical"):
             continue
 
         content = e.get("after_content") or e.get("before_content") or ""
@@ -266,11 +266,11 @@ def run_inspect_llm(repo_dir, base_sha, head_sha, model="gpt-4o"):
     all_entities = data.get("entity_reviews", [])
     # Phase 1: High/Critical/Medium entities for primary coverage
     hcm_entities = [e for e in all_entities
-                    if e.get("risk_level") in ("High", "Critical", "Medium")]
+                    if e.get("risk_level", "").lower() in ("high", "critical", "medium")]
 
     # Phase 2: Low-risk entities for file coverage gaps
     low_entities = [e for e in all_entities
-                    if e.get("risk_level") == "Low"]
+                    if e.get("risk_level", "").lower() == "low"]
 
     if not hcm_entities and not low_entities:
         return []
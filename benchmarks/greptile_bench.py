@@ -851,7 +851,7 @@ def judge_inspect_only(golden_comment, inspect_data):
         return "miss", "inspect found no entities"
 
     entities = inspect_data.get("entity_reviews", [])
-    hc = [e for e in entities if e.get("risk_level") in ("High", "Critical")]
+    hc = [e for e in entities if e.get("risk_level", "").lower() in ("high", "critical")]
     golden_idents = extract_identifiers(golden_comment)
 
     # HC entity name match
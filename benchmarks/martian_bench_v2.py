@@ -21706,7 +21706,7 @@ def entity_triage_score_v3(e: dict) -> float:
         score += min(changed_conds, 5) * 0.04
     # classif_syntax (8.2x) + classif_multi (8.1x)
     classif = e.get("classification", "")
-    if "Syntax" in classif:
+    if "syntax" in classif.lower():
         score += 0.2
     if len(classif) > 12:
         score += 0.15
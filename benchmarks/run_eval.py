@@ -153,8 +153,8 @@ def main():
 
             # Get High/Critical entities
             entities = inspect_result.get("entity_reviews", [])
-            hc_entities = [e for e in entities if e["risk_level"] in ("High", "Critical")]
-            medium_entities = [e for e in entities if e["risk_level"] == "Medium"]
+            hc_entities = [e for e in entities if e["risk_level"].lower() in ("high", "critical")]
+            medium_entities = [e for e in entities if e["risk_level"].lower() == "medium"]
 
             # Build entity summaries
             hc_summary = []
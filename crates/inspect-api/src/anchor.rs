@@ -0,0 +1,262 @@
+//! Place review findings on diff lines, checking what the model cited.
+
+use inspect_core::github::parse_raw_diff;
+use inspect_core::patch::{commentable_lines, entity_anchor_line, parse_patch, DiffHunk};
+use inspect_core::types::EntityReview;
+
+use crate::openai::Finding;
+
+/// Shortest evidence line worth searching for; shorter ones (`}`, `x += 1`)
+/// match all over a diff.
+const MIN_EVIDENCE_CHARS: usize = 8;
+
+/// One file of the diff, keyed by its post-change path.
+struct FilePatch {
+    path: String,
+    hunks: Vec<DiffHunk>,
+}
+
+/// The diff file a finding names: the exact path, else one ending in it.
+fn find_file<'a>(files: &'a [FilePatch], file: &str) -> Option<&'a FilePatch> {
+    files
+        .iter()
+        .find(|f| f.path == file)
+        .or_else(|| files.iter().find(|f| f.path.ends_with(file)))
+}
+
+fn is_added_line(hunks: &[DiffHunk], line: u64) -> bool {
+    hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .any(|l| l.kind == "add" && l.new_line == Some(line))
+}
+
+/// The evidence's code lines, without diff markers, long enough to search for.
+fn evidence_lines(evidence: &str) -> Vec<&str> {
+    evidence
+        .lines()
+        .map(|l| l.strip_prefix(['+', '-']).unwrap_or(l).trim())
+        .filter(|l| l.len() >= MIN_EVIDENCE_CHARS)
+        .collect()
+}
+
+/// Whether `text` mentions `name` as a whole identifier, so an entity `a`
+/// isn't found in every word containing the letter.
+fn mentions(text: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    !name.is_empty()
+        && text.match_indices(name).any(|(at, _)| {
+            !text[..at].chars().next_back().is_some_and(is_ident)
+                && !text[at + name.len()..].chars().next().is_some_and(is_ident)
+        })
+}
+
+/// Where the evidence shows up on the new side of the diff. Added lines beat
+/// context lines, the cited file beats the others, and the line nearest the
+/// cited one breaks ties.
+fn locate_evidence<'a>(
+    files: &'a [FilePatch],
+    cited: Option<&FilePatch>,
+    cited_line: Option<u64>,
+    evidence: &str,
+) -> Option<(&'a str, u64)> {
+    let needles = evidence_lines(evidence);
+    if needles.is_empty() {
+        return None;
+    }
+    files
+        .iter()
+        .flat_map(|f| f.hunks.iter().flat_map(|h| &h.lines).map(move |l| (f, l)))
+        .filter(|(_, l)| needles.iter().any(|n| l.content.contains(n)))
+        .filter_map(|(f, l)| Some((f, l.new_line?, l.kind == "add")))
+        .min_by_key(|&(f, line, added)| {
+            let elsewhere = cited.is_none_or(|c| c.path != f.path);
+            let distance = cited_line.map_or(0, |cited| cited.abs_diff(line));
+            (!added, elsewhere, distance, line)
+        })
+        .map(|(f, line, _)| (f.path.as_str(), line))
+}
+
+/// Check each finding's location against the diff and set `file`, `line`
+/// and `anchored`:
+///
+/// 1. a cited line that the cited file's patch adds is kept;
+/// 2. else the line the evidence quotes, in the cited file or any other;
+/// 3. else the anchor line of the changed entity the finding names.
+///
+/// Findings none of these place keep a best guess for `line` (the riskiest
+/// changed entity in their file, else its first diff line) with `anchored`
+/// false; they aren't dropped.
+pub fn anchor_findings(findings: &mut [Finding], reviews: &[EntityReview], raw_diff: &str) {
+    let mut files: Vec<FilePatch> = parse_raw_diff(raw_diff)
+        .into_iter()
+        .map(|(path, patch)| FilePatch {
+            hunks: parse_patch(&patch),
+            path,
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for finding in findings {
+        finding.anchored = false;
+        let cited = finding.file.as_deref().and_then(|file| find_file(&files, file));
+
+        if let (Some(file), Some(line)) = (cited, finding.line) {
+            if is_added_line(&file.hunks, line) {
+                finding.file = Some(file.path.clone());
+                finding.anchored = true;
+                continue;
+            }
+        }
+
+        let evidence = finding.evidence.as_deref().unwrap_or("");
+        if let Some((path, line)) = locate_evidence(&files, cited, finding.line, evidence) {
+            finding.file = Some(path.to_string());
+            finding.line = Some(line);
+            finding.anchored = true;
+            continue;
+        }
+
+        let Some(file) = cited else {
+            finding.line = None;
+            continue;
+        };
+        finding.file = Some(file.path.clone());
+
+        // Reviews are sorted by risk, so the first in the file is the riskiest
+        let in_file: Vec<&EntityReview> = reviews.iter().filter(|r| r.file_path == file.path).collect();
+        let named = in_file.iter().find(|r| mentions(&finding.issue, &r.entity_name));
+        if let Some(line) = named.and_then(|r| entity_anchor_line(&file.hunks, r.start_line, r.end_line)) {
+            finding.line = Some(line);
+            finding.anchored = true;
+            continue;
+        }
+        finding.line = in_file
+            .first()
+            .and_then(|r| entity_anchor_line(&file.hunks, r.start_line, r.end_line))
+            .or_else(|| commentable_lines(&file.hunks).first().copied());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::types::RiskLevel;
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn a() {
+-    old();
++    new();
+@@ -20,3 +20,4 @@
+ fn b() {
+     keep();
++    added();
+ }
+diff --git a/src/auth.rs b/src/auth.rs
+--- a/src/auth.rs
++++ b/src/auth.rs
+@@ -10,2 +10,3 @@
+ fn check(token: &str) -> bool {
++    let expected = load_secret();
+     token == expected
+";
+
+    fn review(name: &str, file: &str, start_line: usize, end_line: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file, name),
+            entity_name: name.into(),
+            file_path: file.into(),
+            risk_score: 0.5,
+            risk_level: RiskLevel::Medium,
+            start_line,
+            end_line,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn finding(issue: &str, file: Option<&str>, line: Option<u64>, evidence: Option<&str>) -> Finding {
+        Finding {
+            issue: issue.into(),
+            evidence: evidence.map(String::from),
+            severity: None,
+            file: file.map(String::from),
+            line,
+            anchored: false,
+        }
+    }
+
+    fn location(f: &Finding) -> (Option<&str>, Option<u64>, bool) {
+        (f.file.as_deref(), f.line, f.anchored)
+    }
+
+    #[test]
+    fn findings_anchor_to_their_entity() {
+        let reviews = vec![review("a", "src/lib.rs", 1, 3), review("b", "src/lib.rs", 20, 23)];
+        let mut findings = vec![
+            finding("b() never checks the result", Some("src/lib.rs"), None, None),
+            finding("something off here", Some("lib.rs"), None, None),
+            finding("no file given", None, None, None),
+            finding("not in the diff", Some("src/other.rs"), None, None),
+        ];
+        anchor_findings(&mut findings, &reviews, DIFF);
+        assert_eq!(location(&findings[0]), (Some("src/lib.rs"), Some(22), true));
+        // Unnamed entity: the riskiest in the file, matched by path suffix
+        assert_eq!(location(&findings[1]), (Some("src/lib.rs"), Some(2), false));
+        assert_eq!(location(&findings[2]), (None, None, false));
+        assert_eq!(location(&findings[3]), (Some("src/other.rs"), None, false));
+    }
+
+    #[test]
+    fn entity_names_match_whole_identifiers() {
+        // `a` is the riskier entity, and its letter appears all over the issue
+        let reviews = vec![review("a", "src/lib.rs", 1, 3), review("b", "src/lib.rs", 20, 23)];
+        let mut findings = vec![
+            finding("the handler drops a_b's value in b()", Some("src/lib.rs"), None, None),
+            finding("data race", Some("src/lib.rs"), None, None),
+        ];
+        anchor_findings(&mut findings, &reviews, DIFF);
+        assert_eq!(location(&findings[0]), (Some("src/lib.rs"), Some(22), true));
+        assert_eq!(location(&findings[1]), (Some("src/lib.rs"), Some(2), false));
+        assert!(mentions("call a() first", "a"));
+        assert!(!mentions("a_b and ab", "a"));
+    }
+
+    #[test]
+    fn cited_added_lines_are_kept() {
+        let mut findings = vec![finding("new() is wrong", Some("lib.rs"), Some(2), None)];
+        anchor_findings(&mut findings, &[], DIFF);
+        assert_eq!(location(&findings[0]), (Some("src/lib.rs"), Some(2), true));
+    }
+
+    #[test]
+    fn evidence_relocates_wrong_lines_and_files() {
+        let mut findings = vec![
+            // Right file, line off by a few
+            finding("added() result ignored", Some("src/lib.rs"), Some(25), Some("+    added();")),
+            // Wrong file altogether
+            finding("secret compared in variable time", Some("src/lib.rs"), Some(11), Some("let expected = load_secret();")),
+            // No location at all
+            finding("loads the secret per call", None, None, Some("load_secret()")),
+        ];
+        anchor_findings(&mut findings, &[], DIFF);
+        assert_eq!(location(&findings[0]), (Some("src/lib.rs"), Some(22), true));
+        assert_eq!(location(&findings[1]), (Some("src/auth.rs"), Some(11), true));
+        assert_eq!(location(&findings[2]), (Some("src/auth.rs"), Some(11), true));
+    }
+
+    #[test]
+    fn unmatched_findings_are_kept_unanchored() {
+        let mut findings = vec![
+            finding("hallucinated", Some("src/lib.rs"), Some(99), Some("this_code_is_nowhere();")),
+            // Context lines aren't changes, and short evidence isn't searched
+            finding("context", Some("src/auth.rs"), Some(12), Some("}")),
+        ];
+        anchor_findings(&mut findings, &[], DIFF);
+        assert_eq!(location(&findings[0]), (Some("src/lib.rs"), Some(1), false));
+        assert_eq!(location(&findings[1]), (Some("src/auth.rs"), Some(10), false));
+    }
+}
@@ -0,0 +1,321 @@
+//! Completion callbacks: POST a finished job to the URL its creator gave.
+
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::Sha256;
+
+use crate::state::{CallbackDelivery, ReviewJob};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when the job has a secret.
+pub const SIGNATURE_HEADER: &str = "X-Inspect-Signature-256";
+
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often to try a callback and how long to wait in between.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Tries after the first one
+    pub retries: u32,
+    /// Wait before the first retry; doubles for each one after
+    pub base_delay: Duration,
+}
+
+pub const DEFAULT_RETRY: RetryPolicy = RetryPolicy {
+    retries: 3,
+    base_delay: Duration::from_secs(1),
+};
+
+/// Check a callback URL when the job is created. Unless `allow_insecure`,
+/// it must be https and must not name a loopback, link-local or private
+/// host. Hostnames are checked again after DNS, at delivery.
+pub fn validate_url(raw: &str, allow_insecure: bool) -> Result<Url, String> {
+    let url = Url::parse(raw).map_err(|e| format!("invalid callback_url: {e}"))?;
+    if allow_insecure {
+        return match url.scheme() {
+            "https" | "http" => Ok(url),
+            other => Err(format!("callback_url must be http(s), not {other}")),
+        };
+    }
+    if url.scheme() != "https" {
+        return Err("callback_url must use https".into());
+    }
+    let Some(host) = url.host_str() else {
+        return Err("callback_url has no host".into());
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let forbidden = match host.parse::<IpAddr>() {
+        Ok(ip) => is_forbidden(ip),
+        Err(_) => host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost"),
+    };
+    if forbidden {
+        return Err("callback_url must not point at a loopback, link-local or private address".into());
+    }
+    Ok(url)
+}
+
+/// Addresses a callback may not reach: anything that isn't the public
+/// internet from the server's point of view.
+fn is_forbidden(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback() || v4.is_link_local() || v4.is_private() || v4.is_unspecified() || v4.is_broadcast()
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_forbidden(IpAddr::V4(v4)),
+            None => {
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_unicast_link_local()
+                    || v6.is_unique_local()
+            }
+        },
+    }
+}
+
+/// `sha256=` plus the hex HMAC-SHA256 of `body` keyed with `secret`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// A client that won't follow redirects and, unless `allow_insecure`, can
+/// only connect to the address the URL's host resolved to just now, after
+/// that address passed [`is_forbidden`]. Pinning it stops a DNS answer that
+/// changes between the check and the request.
+async fn client_for(url: &Url, allow_insecure: bool) -> Result<reqwest::Client, String> {
+    let builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(ATTEMPT_TIMEOUT);
+    if allow_insecure {
+        return builder.build().map_err(|e| e.to_string());
+    }
+    let host = url.host_str().ok_or("callback_url has no host")?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+        .await
+        .map_err(|e| format!("resolving {host}: {e}"))?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|a| is_forbidden(a.ip())) {
+        return Err(format!("{host} resolves to a loopback, link-local or private address"));
+    }
+    builder.resolve(host, addrs[0]).build().map_err(|e| e.to_string())
+}
+
+/// POST `job` as JSON to its `callback_url`, signed with its secret if it
+/// has one, retrying non-2xx responses and errors per `policy`.
+pub async fn deliver(job: &ReviewJob, allow_insecure: bool, policy: RetryPolicy) -> CallbackDelivery {
+    let mut delivery = CallbackDelivery {
+        delivered: false,
+        attempts: 0,
+        status_code: None,
+        error: None,
+        finished_at: Utc::now(),
+    };
+    let prepared = match job.callback_url.as_deref() {
+        Some(raw) => match validate_url(raw, allow_insecure) {
+            Ok(url) => client_for(&url, allow_insecure).await.map(|client| (client, url)),
+            Err(e) => Err(e),
+        },
+        None => Err("job has no callback_url".into()),
+    };
+    let (client, url) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            delivery.error = Some(e);
+            return delivery;
+        }
+    };
+
+    let body = serde_json::to_vec(job).expect("jobs always serialize");
+    let mut delay = policy.base_delay;
+    for attempt in 1..=policy.retries + 1 {
+        if attempt > 1 {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        delivery.attempts = attempt;
+        let mut request = client
+            .post(url.clone())
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "inspect-api")
+            .body(body.clone());
+        if let Some(ref secret) = job.callback_secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+        match request.send().await {
+            Ok(resp) if resp.status().is_success() => {
+                delivery.delivered = true;
+                delivery.status_code = Some(resp.status().as_u16());
+                delivery.error = None;
+                break;
+            }
+            Ok(resp) => {
+                delivery.status_code = Some(resp.status().as_u16());
+                delivery.error = Some(format!("HTTP {}", resp.status()));
+            }
+            Err(e) => {
+                delivery.status_code = None;
+                delivery.error = Some(e.to_string());
+            }
+        }
+    }
+    delivery.finished_at = Utc::now();
+    delivery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+
+    use crate::state::JobStatus;
+
+    /// What the mock receiver saw, and the statuses it answers with in turn
+    /// (200 once they run out).
+    #[derive(Default)]
+    struct Receiver {
+        requests: Vec<(HeaderMap, Bytes)>,
+        statuses: Vec<u16>,
+    }
+
+    async fn receive(State(receiver): State<Arc<Mutex<Receiver>>>, headers: HeaderMap, body: Bytes) -> StatusCode {
+        let mut receiver = receiver.lock().unwrap();
+        receiver.requests.push((headers, body));
+        let status = if receiver.statuses.is_empty() { 200 } else { receiver.statuses.remove(0) };
+        StatusCode::from_u16(status).unwrap()
+    }
+
+    /// Serve the mock receiver on a local port; returns its URL.
+    async fn serve(receiver: Arc<Mutex<Receiver>>) -> String {
+        let app = axum::Router::new()
+            .route("/hook", axum::routing::post(receive))
+            .with_state(receiver);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}/hook")
+    }
+
+    fn job(callback_url: &str) -> ReviewJob {
+        ReviewJob {
+            id: "job-1".into(),
+            status: JobStatus::Complete,
+            repo: "owner/repo".into(),
+            pr_number: 7,
+            strategy: None,
+            result: None,
+            error: None,
+            created_at: Utc::now(),
+            callback_url: Some(callback_url.into()),
+            callback_secret: Some("s3cret".into()),
+            callback: None,
+            model: None,
+            head_sha: None,
+            queued_position: None,
+        }
+    }
+
+    const FAST: RetryPolicy = RetryPolicy {
+        retries: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    #[tokio::test]
+    async fn delivers_a_signed_job() {
+        let receiver = Arc::new(Mutex::new(Receiver::default()));
+        let job = job(&serve(receiver.clone()).await);
+
+        let delivery = deliver(&job, true, FAST).await;
+        assert!(delivery.delivered, "{:?}", delivery);
+        assert_eq!((delivery.attempts, delivery.status_code), (1, Some(200)));
+
+        let receiver = receiver.lock().unwrap();
+        let (headers, body) = &receiver.requests[0];
+        let sent = headers[SIGNATURE_HEADER].to_str().unwrap();
+        let hex_sig = sent.strip_prefix("sha256=").unwrap();
+        let mut mac = HmacSha256::new_from_slice(b"s3cret").unwrap();
+        mac.update(body);
+        mac.verify_slice(&hex::decode(hex_sig).unwrap()).expect("signature matches the body");
+
+        let sent: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(sent["id"], "job-1");
+        assert_eq!(sent["status"], "complete");
+        assert!(sent.get("callback_secret").is_none());
+    }
+
+    #[tokio::test]
+    async fn retries_until_a_2xx() {
+        let receiver = Arc::new(Mutex::new(Receiver {
+            statuses: vec![500, 503],
+            ..Default::default()
+        }));
+        let job = job(&serve(receiver.clone()).await);
+
+        let delivery = deliver(&job, true, FAST).await;
+        assert!(delivery.delivered);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(receiver.lock().unwrap().requests.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_retry() {
+        let receiver = Arc::new(Mutex::new(Receiver {
+            statuses: vec![500; 10],
+            ..Default::default()
+        }));
+        let job = job(&serve(receiver.clone()).await);
+
+        let delivery = deliver(&job, true, FAST).await;
+        assert!(!delivery.delivered);
+        assert_eq!((delivery.attempts, delivery.status_code), (4, Some(500)));
+        assert_eq!(delivery.error.as_deref(), Some("HTTP 500 Internal Server Error"));
+        assert_eq!(receiver.lock().unwrap().requests.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn local_receivers_need_insecure_callbacks() {
+        let receiver = Arc::new(Mutex::new(Receiver::default()));
+        let job = job(&serve(receiver.clone()).await);
+
+        let delivery = deliver(&job, false, FAST).await;
+        assert!(!delivery.delivered);
+        assert_eq!(delivery.attempts, 0);
+        assert!(receiver.lock().unwrap().requests.is_empty());
+    }
+
+    #[test]
+    fn urls_are_validated() {
+        assert!(validate_url("https://bot.example.com/hook", false).is_ok());
+        for bad in [
+            "http://bot.example.com/hook",
+            "ftp://bot.example.com/hook",
+            "https://127.0.0.1/hook",
+            "https://localhost:8443/hook",
+            "https://api.localhost/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://10.0.0.5/hook",
+            "https://192.168.1.1/hook",
+            "https://[::1]/hook",
+            "https://[fe80::1]/hook",
+            "https://[fdaa::3]/hook",
+            "https://[::ffff:127.0.0.1]/hook",
+            "https://0.0.0.0/hook",
+            "not a url",
+        ] {
+            assert!(validate_url(bad, false).is_err(), "{bad}");
+        }
+        assert!(validate_url("http://127.0.0.1:9000/hook", true).is_ok());
+        assert!(validate_url("file:///etc/passwd", true).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! File pairs rebuilt from a unified diff alone, for callers without GitHub.
+
+use inspect_core::github::FilePair;
+use inspect_core::patch::{parse_patch_info, DiffHunk};
+
+/// One file's part of the diff.
+#[derive(Default)]
+struct Section<'a> {
+    /// From `--- ` (or the `diff --git` line until one turns up); None for /dev/null
+    old_path: Option<String>,
+    new_path: Option<String>,
+    added: bool,
+    deleted: bool,
+    rename_from: Option<&'a str>,
+    patch: Vec<&'a str>,
+}
+
+/// A `---`/`+++` path without its `a/`/`b/` prefix or trailing timestamp.
+fn marker_path(path: &str, prefix: &str) -> Option<String> {
+    let path = path.split('\t').next().unwrap_or(path).trim_end();
+    (path != "/dev/null").then(|| path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// Old and new line counts from a `@@ -a,b +c,d @@` header.
+fn hunk_counts(header: &str) -> (u64, u64) {
+    let count = |range: Option<&str>| {
+        range
+            .and_then(|r| r.split_once(',').map_or(Some("1"), |(_, count)| Some(count)))
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0)
+    };
+    let mut ranges = header.trim_start_matches('@').split_whitespace();
+    let old = ranges.next().and_then(|r| r.strip_prefix('-'));
+    let new = ranges.next().and_then(|r| r.strip_prefix('+'));
+    (count(old), count(new))
+}
+
+impl Section<'_> {
+    fn into_pair(self) -> Option<FilePair> {
+        let info = parse_patch_info(&self.patch.join("\n"));
+        if info.is_binary {
+            return None;
+        }
+        let added = self.added || (self.old_path.is_none() && self.new_path.is_some());
+        let deleted = self.deleted || (self.new_path.is_none() && self.old_path.is_some());
+        let filename = self.new_path.or_else(|| self.old_path.clone())?;
+        let previous_filename = match self.rename_from {
+            Some(from) => Some(from.to_string()),
+            None => self.old_path.filter(|old| !added && *old != filename),
+        };
+        let status = if added {
+            "added"
+        } else if deleted {
+            "removed"
+        } else if previous_filename.is_some() {
+            "renamed"
+        } else {
+            "modified"
+        };
+        Some(FilePair {
+            filename,
+            status: status.to_string(),
+            previous_filename,
+            before_content: (!added).then(|| side(&info.hunks, |l| l.old_line)),
+            after_content: (!deleted).then(|| side(&info.hunks, |l| l.new_line)),
+        })
+    }
+}
+
+/// One side of the file as far as the hunks show it. Lines outside every
+/// hunk are left blank, so entities keep their real line numbers.
+fn side(hunks: &[DiffHunk], line_number: fn(&inspect_core::patch::DiffLine) -> Option<u64>) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in hunks.iter().flat_map(|h| &h.lines) {
+        let Some(index) = line_number(line).filter(|&n| n > 0).map(|n| n as usize - 1) else {
+            continue;
+        };
+        if lines.len() <= index {
+            lines.resize(index + 1, "");
+        }
+        lines[index] = &line.content;
+    }
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+/// Split a unified diff (git's, or plain `diff -u` output) into file pairs.
+/// Binary files are skipped, since their patches carry no content.
+pub fn file_pairs_from_diff(raw: &str) -> Vec<FilePair> {
+    let mut sections: Vec<Section> = Vec::new();
+    // Lines still to come in the current hunk, old side and new side
+    let mut remaining = (0u64, 0u64);
+    for line in raw.lines() {
+        if remaining != (0, 0) {
+            match line.as_bytes().first() {
+                Some(b'-') => remaining.0 = remaining.0.saturating_sub(1),
+                Some(b'+') => remaining.1 = remaining.1.saturating_sub(1),
+                Some(b'\\') => {}
+                _ => remaining = (remaining.0.saturating_sub(1), remaining.1.saturating_sub(1)),
+            }
+            if let Some(section) = sections.last_mut() {
+                section.patch.push(line);
+            }
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // Pure renames and mode changes have no ---/+++ lines to name them
+            let (old, new) = header.split_once(" b/").unwrap_or((header, header));
+            sections.push(Section {
+                old_path: marker_path(old, "a/"),
+                new_path: Some(new.to_string()),
+                ..Section::default()
+            });
+        } else if let Some(old) = line.strip_prefix("--- ") {
+            // In a plain unified diff, --- is the first line of each file
+            if sections.last().is_none_or(|s| !s.patch.is_empty()) {
+                sections.push(Section::default());
+            }
+            sections.last_mut().unwrap().old_path = marker_path(old, "a/");
+        } else if let Some(section) = sections.last_mut() {
+            if let Some(new) = line.strip_prefix("+++ ") {
+                section.new_path = marker_path(new, "b/");
+            } else if line.starts_with("@@") {
+                remaining = hunk_counts(line);
+                section.patch.push(line);
+            } else if line.starts_with("new file mode") {
+                section.added = true;
+                section.old_path = None;
+            } else if line.starts_with("deleted file mode") {
+                section.deleted = true;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                section.rename_from = Some(from);
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                section.patch.push(line);
+            }
+        }
+    }
+    sections.into_iter().filter_map(Section::into_pair).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIT_DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,3 +2,3 @@
+ fn a() {
+--- not a file header, a deleted line
++    new();
+ }
+diff --git a/src/new.rs b/src/new.rs
+new file mode 100644
+--- /dev/null
++++ b/src/new.rs
+@@ -0,0 +1,2 @@
++fn made() {}
++fn up() {}
+diff --git a/src/gone.rs b/src/gone.rs
+deleted file mode 100644
+--- a/src/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn gone() {}
+diff --git a/old/name.rs b/new/name.rs
+similarity index 100%
+rename from old/name.rs
+rename to new/name.rs
+diff --git a/logo.png b/logo.png
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn git_diffs_become_file_pairs() {
+        let pairs = file_pairs_from_diff(GIT_DIFF);
+        let summary: Vec<_> = pairs
+            .iter()
+            .map(|p| (p.filename.as_str(), p.status.as_str(), p.previous_filename.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("src/lib.rs", "modified", None),
+                ("src/new.rs", "added", None),
+                ("src/gone.rs", "removed", None),
+                ("new/name.rs", "renamed", Some("old/name.rs")),
+            ]
+        );
+
+        // Lines keep their numbers; the rest of the file is blank
+        assert_eq!(pairs[0].before_content.as_deref(), Some("\nfn a() {\n-- not a file header, a deleted line\n}\n"));
+        assert_eq!(pairs[0].after_content.as_deref(), Some("\nfn a() {\n    new();\n}\n"));
+        assert_eq!(pairs[1].before_content, None);
+        assert_eq!(pairs[1].after_content.as_deref(), Some("fn made() {}\nfn up() {}\n"));
+        assert_eq!(pairs[2].before_content.as_deref(), Some("fn gone() {}\n"));
+        assert_eq!(pairs[2].after_content, None);
+    }
+
+    #[test]
+    fn plain_unified_diffs_split_on_their_headers() {
+        let diff = "\
+--- a.py\t2026-01-01 00:00:00
++++ a.py\t2026-01-02 00:00:00
+@@ -1 +1 @@
+-x = 1
++x = 2
+--- b.py
++++ b.py
+@@ -1,2 +1,2 @@
+ def f():
+-    pass
++    return 1
+";
+        let pairs = file_pairs_from_diff(diff);
+        assert_eq!(pairs.len(), 2);
+        assert_eq!((pairs[0].filename.as_str(), pairs[0].status.as_str()), ("a.py", "modified"));
+        assert_eq!(pairs[0].after_content.as_deref(), Some("x = 2\n"));
+        assert_eq!(pairs[1].filename, "b.py");
+        assert_eq!(pairs[1].before_content.as_deref(), Some("def f():\n    pass\n"));
+    }
+}
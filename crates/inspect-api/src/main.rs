@@ -1,19 +1,41 @@
+mod anchor;
 mod auth;
+mod callback;
+mod diff;
 mod openai;
+#[cfg(test)]
+mod mock;
 mod prompts;
+mod queue;
 mod routes;
 mod state;
+mod store;
+mod webhook;
 
-use std::collections::HashMap;
 use std::sync::Arc;
 
+use axum::extract::DefaultBodyLimit;
 use axum::Router;
-use tokio::sync::RwLock;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
-use tracing::info;
+use tracing::{info, warn};
 
+use queue::ReviewQueue;
 use state::AppState;
+use store::{JobStore, SqliteJobStore};
+
+/// How long finished jobs are kept when `JOB_TTL_HOURS` isn't set.
+const DEFAULT_JOB_TTL_HOURS: u64 = 72;
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// Per-call OpenAI timeout when `OPENAI_TIMEOUT_SECS` isn't set.
+const DEFAULT_OPENAI_TIMEOUT_SECS: u64 = 120;
+/// Reviews run at once when `MAX_CONCURRENT_REVIEWS` isn't set.
+const DEFAULT_MAX_CONCURRENT_REVIEWS: usize = 3;
+/// Jobs allowed to wait when `MAX_QUEUED_REVIEWS` isn't set; more get a 429.
+const DEFAULT_MAX_QUEUED_REVIEWS: usize = 100;
+/// `POST /v1/analyze` limits when `MAX_ANALYZE_BYTES`/`MAX_ANALYZE_FILES` aren't set.
+const DEFAULT_MAX_ANALYZE_BYTES: usize = 10 * 1024 * 1024;
+const DEFAULT_MAX_ANALYZE_FILES: usize = 500;
 
 #[tokio::main]
 async fn main() {
@@ -32,6 +54,14 @@ async fn main() {
         .unwrap_or(3000);
     let openai_model =
         std::env::var("OPENAI_MODEL").unwrap_or_else(|_| "gpt-5.2".to_string());
+    let openai_base_url = std::env::var("OPENAI_BASE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| "https://api.openai.com/v1".to_string());
+    let openai_timeout_secs: u64 = std::env::var("OPENAI_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(DEFAULT_OPENAI_TIMEOUT_SECS);
     let anthropic_api_key = std::env::var("ANTHROPIC_API_KEY").ok();
     let anthropic_model =
         std::env::var("ANTHROPIC_MODEL").unwrap_or_else(|_| "claude-sonnet-4-5-20250929".to_string());
@@ -39,27 +69,86 @@ async fn main() {
     let supabase_key =
         std::env::var("SUPABASE_SERVICE_ROLE_KEY").expect("SUPABASE_SERVICE_ROLE_KEY required");
 
+    if std::env::var_os("INSPECT_DB_PATH").is_none() {
+        warn!("INSPECT_DB_PATH not set; review jobs are kept in memory and lost on restart");
+    }
+    let job_ttl_hours: u64 = std::env::var("JOB_TTL_HOURS")
+        .ok()
+        .and_then(|h| h.parse().ok())
+        .unwrap_or(DEFAULT_JOB_TTL_HOURS);
+    let max_concurrent: usize = std::env::var("MAX_CONCURRENT_REVIEWS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_REVIEWS);
+    let max_queued: usize = std::env::var("MAX_QUEUED_REVIEWS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_MAX_QUEUED_REVIEWS);
+    info!("Running up to {max_concurrent} reviews at once, {max_queued} more queued");
+    let max_analyze_bytes: usize = std::env::var("MAX_ANALYZE_BYTES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_MAX_ANALYZE_BYTES);
+    let max_analyze_files: usize = std::env::var("MAX_ANALYZE_FILES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_MAX_ANALYZE_FILES);
+    let github_webhook_secret = std::env::var("GITHUB_WEBHOOK_SECRET").ok().filter(|s| !s.is_empty());
+    if github_webhook_secret.is_none() {
+        info!("GITHUB_WEBHOOK_SECRET not set; /v1/webhooks/github is disabled");
+    }
+    let review_drafts = std::env::var("REVIEW_DRAFTS").is_ok_and(|v| v == "1");
+    let jobs = SqliteJobStore::from_env().expect("failed to open job store");
+    let recovered = jobs.recover().expect("failed to recover jobs");
+    let allow_insecure_callbacks = std::env::var("ALLOW_INSECURE_CALLBACKS").is_ok_and(|v| v == "1");
+    if allow_insecure_callbacks {
+        warn!("ALLOW_INSECURE_CALLBACKS=1; callbacks may use http and reach local addresses");
+    }
+
     let state = Arc::new(AppState {
         port,
         openai_api_key,
         openai_model: openai_model.clone(),
+        openai_base_url,
+        openai_timeout: std::time::Duration::from_secs(openai_timeout_secs),
         anthropic_api_key,
         anthropic_model,
         github_token,
+        github_api_url: inspect_core::github::api_url_from_env(),
         http: reqwest::Client::new(),
-        jobs: Arc::new(RwLock::new(HashMap::new())),
+        jobs: Arc::new(jobs),
+        queue: Arc::new(ReviewQueue::new(max_concurrent, max_queued)),
         supabase_url,
         supabase_key,
+        allow_insecure_callbacks,
+        max_analyze_bytes,
+        max_analyze_files,
+        github_webhook_secret,
+        review_drafts,
     });
 
-    let app = Router::new()
-        .route("/v1/review", axum::routing::post(routes::create_review))
-        .route("/v1/review/{id}", axum::routing::get(routes::get_review))
-        .route("/v1/triage", axum::routing::post(routes::create_triage))
-        .route("/health", axum::routing::get(routes::health))
-        .layer(CorsLayer::permissive())
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+    // Jobs accepted but never started before the last shutdown. Per-request
+    // GitHub tokens are never stored, so these run on GITHUB_TOKEN.
+    for job in recovered.pending {
+        info!("Resuming job {} ({}#{})", job.id, job.repo, job.pr_number);
+        state.queue.enqueue(&job.id);
+        tokio::spawn(routes::run_review(state.clone(), job.id, None));
+    }
+    // Jobs cut off mid-review failed; tell whoever was waiting
+    for job in recovered.interrupted {
+        let state = state.clone();
+        tokio::spawn(async move { routes::notify(&state, &job.id).await });
+    }
+
+    if job_ttl_hours > 0 {
+        info!("Pruning finished jobs older than {job_ttl_hours}h (JOB_TTL_HOURS)");
+        tokio::spawn(prune_jobs(state.jobs.clone(), job_ttl_hours));
+    } else {
+        info!("JOB_TTL_HOURS=0; finished jobs are kept forever");
+    }
+
+    let app = app(state);
 
     let addr = format!("0.0.0.0:{port}");
     info!("inspect-api listening on {addr} (model: {openai_model})");
@@ -67,3 +156,39 @@ async fn main() {
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
+
+fn app(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route(
+            "/v1/review",
+            axum::routing::post(routes::create_review).get(routes::list_reviews),
+        )
+        .route(
+            "/v1/review/{id}",
+            axum::routing::get(routes::get_review).delete(routes::delete_review),
+        )
+        .route("/v1/triage", axum::routing::post(routes::create_triage))
+        .route(
+            "/v1/analyze",
+            axum::routing::post(routes::analyze).layer(DefaultBodyLimit::max(state.max_analyze_bytes)),
+        )
+        .route("/v1/webhooks/github", axum::routing::post(webhook::handle_github))
+        .route("/health", axum::routing::get(routes::health))
+        .layer(CorsLayer::permissive())
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+/// Hourly, delete finished jobs created more than `ttl_hours` ago.
+async fn prune_jobs(jobs: Arc<dyn JobStore>, ttl_hours: u64) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(ttl_hours as i64);
+        match jobs.prune(cutoff) {
+            Ok(0) => {}
+            Ok(n) => info!("Pruned {n} jobs older than {ttl_hours}h"),
+            Err(e) => warn!("Failed to prune jobs: {e}"),
+        }
+    }
+}
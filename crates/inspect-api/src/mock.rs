@@ -0,0 +1,127 @@
+//! A local stand-in for GitHub and OpenAI, for tests of outbound requests.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::queue::ReviewQueue;
+use crate::state::AppState;
+use crate::store::SqliteJobStore;
+
+/// A request the server received.
+pub struct Recorded {
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+/// One canned answer.
+#[derive(Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub retry_after: Option<u64>,
+    pub body: serde_json::Value,
+}
+
+impl Reply {
+    pub fn ok(body: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            retry_after: None,
+            body,
+        }
+    }
+
+    /// An error status asking to be retried right away.
+    pub fn retry_now(status: u16) -> Self {
+        Self {
+            status,
+            retry_after: Some(0),
+            body: serde_json::json!({"error": {"message": "try again"}}),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Shared {
+    requests: Arc<Mutex<Vec<Recorded>>>,
+    replies: Arc<Mutex<VecDeque<Reply>>>,
+}
+
+/// Answers requests with its replies in turn, repeating the last one, and
+/// keeps them.
+pub struct MockServer {
+    pub url: String,
+    shared: Shared,
+}
+
+async fn record(State(shared): State<Shared>, uri: Uri, headers: HeaderMap, body: Bytes) -> Response {
+    shared.requests.lock().unwrap().push(Recorded { uri, headers, body });
+    let reply = {
+        let mut replies = shared.replies.lock().unwrap();
+        if replies.len() > 1 {
+            replies.pop_front().unwrap()
+        } else {
+            replies[0].clone()
+        }
+    };
+    let mut resp = (StatusCode::from_u16(reply.status).unwrap(), Json(reply.body)).into_response();
+    if let Some(secs) = reply.retry_after {
+        resp.headers_mut().insert(RETRY_AFTER, secs.into());
+    }
+    resp
+}
+
+impl MockServer {
+    /// Answer every request with 200 and `response`.
+    pub async fn start(response: serde_json::Value) -> Self {
+        Self::scripted(vec![Reply::ok(response)]).await
+    }
+
+    pub async fn scripted(replies: Vec<Reply>) -> Self {
+        let shared = Shared {
+            requests: Arc::default(),
+            replies: Arc::new(Mutex::new(replies.into())),
+        };
+        let app = axum::Router::new().fallback(record).with_state(shared.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { url, shared }
+    }
+
+    pub fn requests(&self) -> std::sync::MutexGuard<'_, Vec<Recorded>> {
+        self.shared.requests.lock().unwrap()
+    }
+
+    /// App state that sends GitHub and OpenAI requests here.
+    pub fn state(&self) -> AppState {
+        AppState {
+            port: 0,
+            openai_api_key: "sk-default".into(),
+            openai_model: "gpt-default".into(),
+            openai_base_url: self.url.clone(),
+            github_token: "ghp-default".into(),
+            github_api_url: self.url.clone(),
+            http: reqwest::Client::new(),
+            openai_timeout: std::time::Duration::from_secs(5),
+            jobs: Arc::new(SqliteJobStore::in_memory().unwrap()),
+            queue: Arc::new(ReviewQueue::new(3, 100)),
+            anthropic_api_key: None,
+            anthropic_model: "claude-default".into(),
+            supabase_url: self.url.clone(),
+            supabase_key: "supabase-default".into(),
+            allow_insecure_callbacks: true,
+            max_analyze_bytes: 1 << 20,
+            max_analyze_files: 100,
+            github_webhook_secret: None,
+            review_drafts: false,
+        }
+    }
+}
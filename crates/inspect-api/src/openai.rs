@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use inspect_core::types::EntityReview;
 use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
@@ -14,6 +16,14 @@ pub struct Finding {
     pub severity: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<String>,
+    /// New-file line: as the model cited it, then after review one GitHub
+    /// accepts an inline comment on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+    /// Whether `file` and `line` were confirmed against the diff, rather
+    /// than guessed
+    #[serde(default)]
+    pub anchored: bool,
 }
 
 #[derive(Deserialize)]
@@ -92,6 +102,67 @@ struct EvidenceReport {
     verdict: String, // "rescue" or "reject"
 }
 
+/// Retries after the first attempt when OpenAI answers 429 or 5xx.
+const OPENAI_RETRIES: u32 = 3;
+/// Wait before the first retry when there's no `Retry-After`; doubles after.
+const OPENAI_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest `Retry-After` honored; longer asks are cut to this.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+fn attempts(n: u32) -> String {
+    if n == 1 {
+        "1 attempt".to_string()
+    } else {
+        format!("{n} attempts")
+    }
+}
+
+/// `Retry-After` given in seconds. The HTTP-date form isn't used by OpenAI.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let secs: u64 = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+/// POST `body` to the OpenAI API at `path` under the state's timeout,
+/// retrying 429 and 5xx answers. Errors start with `api` and say how many
+/// attempts were made.
+async fn post_openai(
+    state: &AppState,
+    api: &str,
+    path: &str,
+    body: &serde_json::Value,
+) -> Result<reqwest::Response, String> {
+    let url = format!("{}{path}", state.openai_base_url);
+    let mut backoff = OPENAI_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let resp = state
+            .http
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", state.openai_api_key))
+            .timeout(state.openai_timeout)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| format!("{api} request failed after {}: {e}", attempts(attempt)))?;
+
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !retryable || attempt > OPENAI_RETRIES {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("{api} error {status} after {}: {text}", attempts(attempt)));
+        }
+        let wait = retry_after(resp.headers()).unwrap_or(backoff).min(MAX_RETRY_AFTER);
+        warn!("{api} returned {status}, retrying in {wait:?} ({} so far)", attempts(attempt));
+        tokio::time::sleep(wait).await;
+        backoff *= 2;
+    }
+}
+
 /// Call OpenAI chat completions API.
 async fn call_openai(
     state: &AppState,
@@ -107,25 +178,14 @@ async fn call_openai(
             {"role": "user", "content": prompt},
         ],
         "temperature": temperature,
+        // Every system prompt asks for JSON, which this mode requires
+        "response_format": {"type": "json_object"},
     });
     if let Some(s) = seed {
         body["seed"] = serde_json::json!(s);
     }
 
-    let resp = state
-        .http
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", state.openai_api_key))
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("request failed: {e}"))?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(format!("OpenAI API error {status}: {text}"));
-    }
+    let resp = post_openai(state, "OpenAI API", "/chat/completions", &body).await?;
 
     let chat: ChatResponse = resp.json().await.map_err(|e| format!("parse failed: {e}"))?;
     let content = chat
@@ -189,6 +249,37 @@ async fn call_anthropic(
     Ok(content)
 }
 
+/// Merge the findings of parallel lenses, dropping repeats. A failed lens
+/// is a warning; all of them failing is an error.
+fn merge_lenses(results: Vec<Result<String, String>>) -> Result<Vec<Finding>, String> {
+    let total = results.len();
+    let mut all_findings: Vec<Finding> = Vec::new();
+    let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
+    let mut errors = Vec::new();
+
+    for result in results {
+        match result {
+            Ok(text) => {
+                for f in parse_issues(&text) {
+                    let key: String = f.issue.to_lowercase().chars().take(80).collect();
+                    if seen.insert(key) {
+                        all_findings.push(f);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Lens failed: {e}");
+                errors.push(e);
+            }
+        }
+    }
+
+    if errors.len() == total {
+        return Err(format!("all {total} review lenses failed; first error: {}", errors[0]));
+    }
+    Ok(all_findings)
+}
+
 /// Strip markdown code fences and parse JSON issues.
 fn parse_issues(text: &str) -> Vec<Finding> {
     let cleaned = strip_code_fences(text);
@@ -238,6 +329,8 @@ fn extract_findings(resp: IssuesResponse) -> Vec<Finding> {
                 evidence: None,
                 severity: None,
                 file: None,
+                line: None,
+                anchored: false,
             }),
             serde_json::Value::Object(map) => {
                 let issue = map
@@ -253,6 +346,8 @@ fn extract_findings(resp: IssuesResponse) -> Vec<Finding> {
                     evidence: map.get("evidence").and_then(|v| v.as_str()).map(String::from),
                     severity: map.get("severity").and_then(|v| v.as_str()).map(String::from),
                     file: map.get("file").and_then(|v| v.as_str()).map(String::from),
+                    line: map.get("line").and_then(|v| v.as_u64()),
+                    anchored: false,
                 })
             }
             _ => None,
@@ -355,7 +450,29 @@ async fn validate_findings_seeded(
 
     let prompt = prompts::format_validate_prompt(pr_title, diff, &candidates_text);
     let text = call_openai(state, prompts::SYSTEM_VALIDATE, &prompt, 0.0, seed).await?;
-    Ok(parse_issues(&text))
+    Ok(restore_details(parse_issues(&text), candidates))
+}
+
+/// Validation answers with bare issue strings; give each one back the
+/// evidence, severity and location of the candidate it repeats.
+fn restore_details(validated: Vec<Finding>, candidates: &[Finding]) -> Vec<Finding> {
+    validated
+        .into_iter()
+        .map(|f| {
+            let key = f.issue.trim().to_lowercase();
+            match candidates.iter().find(|c| c.issue.trim().to_lowercase() == key) {
+                Some(candidate) => Finding {
+                    issue: f.issue,
+                    evidence: f.evidence.or_else(|| candidate.evidence.clone()),
+                    severity: f.severity.or_else(|| candidate.severity.clone()),
+                    file: f.file.or_else(|| candidate.file.clone()),
+                    line: f.line.or(candidate.line),
+                    anchored: false,
+                },
+                None => f,
+            }
+        })
+        .collect()
 }
 
 pub async fn review_raw_lenses(
@@ -364,8 +481,8 @@ pub async fn review_raw_lenses(
     diff: &str,
     triage_section: &str,
     max_findings: usize,
-) -> Vec<Finding> {
-    let truncated = prompts::truncate_diff(diff, 65_000);
+) -> Result<Vec<Finding>, String> {
+    let truncated = prompts::truncate_diff(diff, prompts::MAX_PROMPT_DIFF_CHARS);
     let diff_files = extract_diff_files(diff);
 
     let p_data = prompts::format_lens_prompt(prompts::PROMPT_LENS_DATA, pr_title, triage_section, &truncated);
@@ -388,19 +505,7 @@ pub async fn review_raw_lenses(
         call_anthropic(state, prompts::SYSTEM_REVIEW, &p_gen, 0.1),
     );
 
-    let mut all_findings: Vec<Finding> = Vec::new();
-    let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
-
-    for result in [r1, r2, r3, r4, r5, r6, r7, r8, r9] {
-        if let Ok(text) = result {
-            for f in parse_issues(&text) {
-                let key: String = f.issue.to_lowercase().chars().take(80).collect();
-                if seen.insert(key) {
-                    all_findings.push(f);
-                }
-            }
-        }
-    }
+    let mut all_findings = merge_lenses(vec![r1, r2, r3, r4, r5, r6, r7, r8, r9])?;
 
     let pre_filter = all_findings.len();
     all_findings = structural_file_filter(all_findings, &diff_files);
@@ -409,7 +514,7 @@ pub async fn review_raw_lenses(
         pre_filter, all_findings.len()
     );
 
-    all_findings.into_iter().take(max_findings).collect()
+    Ok(all_findings.into_iter().take(max_findings).collect())
 }
 
 /// 9 parallel lenses + structural filter + blind validation.
@@ -419,8 +524,8 @@ async fn review_hybrid_inner(
     diff: &str,
     triage_section: &str,
     max_findings: usize,
-) -> Vec<Finding> {
-    let truncated = prompts::truncate_diff(diff, 65_000);
+) -> Result<Vec<Finding>, String> {
+    let truncated = prompts::truncate_diff(diff, prompts::MAX_PROMPT_DIFF_CHARS);
     let diff_files = extract_diff_files(diff);
 
     // Build 6 specialized lens prompts
@@ -448,26 +553,12 @@ async fn review_hybrid_inner(
     );
 
     // Merge + dedup
-    let mut all_findings: Vec<Finding> = Vec::new();
-    let mut seen: std::collections::HashSet<String> = std::collections::HashSet::new();
-
-    for result in [r1, r2, r3, r4, r5, r6, r7, r8, r9] {
-        if let Ok(text) = result {
-            for f in parse_issues(&text) {
-                let key: String = f.issue.to_lowercase().chars().take(80).collect();
-                if seen.insert(key) {
-                    all_findings.push(f);
-                }
-            }
-        } else {
-            warn!("Lens failed: {:?}", result.err());
-        }
-    }
+    let mut all_findings = merge_lenses(vec![r1, r2, r3, r4, r5, r6, r7, r8, r9])?;
 
     info!("FUNNEL merge: {} findings from 9 lenses", all_findings.len());
 
     if all_findings.is_empty() {
-        return Vec::new();
+        return Ok(Vec::new());
     }
 
     // Structural file filter
@@ -479,18 +570,18 @@ async fn review_hybrid_inner(
     );
 
     if all_findings.is_empty() {
-        return Vec::new();
+        return Ok(Vec::new());
     }
 
     // Skip validation if few findings
     if all_findings.len() <= 2 {
         info!("FUNNEL skip validation (<= 2 findings), returning {} as-is", all_findings.len());
-        return all_findings;
+        return Ok(all_findings);
     }
 
     // Validation pass with seed=42
     let pre_validation = all_findings.len();
-    match validate_findings_seeded(state, pr_title, &truncated, &all_findings, Some(42)).await {
+    Ok(match validate_findings_seeded(state, pr_title, &truncated, &all_findings, Some(42)).await {
         Ok(validated) => {
             let post_validation = validated.len();
             let final_count = post_validation.min(max_findings);
@@ -509,7 +600,7 @@ async fn review_hybrid_inner(
             warn!("Validation failed: {e}");
             all_findings.into_iter().take(max_findings).collect()
         }
-    }
+    })
 }
 
 // --- Responses API for agentic review ---
@@ -540,20 +631,7 @@ async fn call_responses_api(
         body["input"] = serde_json::json!(inp);
     }
 
-    let resp = state
-        .http
-        .post("https://api.openai.com/v1/responses")
-        .header("Authorization", format!("Bearer {}", state.openai_api_key))
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("responses API request failed: {e}"))?;
-
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(format!("Responses API error {status}: {text}"));
-    }
+    let resp = post_openai(state, "Responses API", "/responses", &body).await?;
 
     resp.json::<ResponsesResponse>()
         .await
@@ -610,6 +688,8 @@ fn parse_agent_step(output: &[ResponseOutput]) -> AgentStep {
                                                 .get("file")
                                                 .and_then(|f| f.as_str())
                                                 .map(String::from),
+                                            line: v.get("line").and_then(|l| l.as_u64()),
+                                            anchored: false,
                                         })
                                     })
                                     .collect()
@@ -858,8 +938,8 @@ async fn execute_read_file(
 
     let encoded_path = urlencoding::encode(path);
     let url = format!(
-        "https://api.github.com/repos/{}/contents/{}?ref={}",
-        ctx.repo, encoded_path, sha
+        "{}/repos/{}/contents/{}?ref={}",
+        state.github_api_url, ctx.repo, encoded_path, sha
     );
 
     let resp = match state
@@ -940,7 +1020,8 @@ async fn execute_search_code(
     }
 
     let url = format!(
-        "https://api.github.com/search/code?q={}&per_page=20",
+        "{}/search/code?q={}&per_page=20",
+        state.github_api_url,
         urlencoding::encode(&search_q)
     );
 
@@ -1200,12 +1281,12 @@ pub async fn review_hybrid_v20(
     triage_section: &str,
     max_findings: usize,
     ctx: &AgentContext,
-) -> (Vec<Finding>, usize, usize) {
+) -> Result<(Vec<Finding>, usize, usize), String> {
     // Step 1: Run v10 pipeline
-    let v10_results = review_hybrid_inner(state, pr_title, diff, triage_section, max_findings).await;
+    let v10_results = review_hybrid_inner(state, pr_title, diff, triage_section, max_findings).await?;
 
     if v10_results.is_empty() {
-        return (v10_results, 0, 0);
+        return Ok((v10_results, 0, 0));
     }
 
     info!("v20: v10 produced {} findings, sending to agentic challenge", v10_results.len());
@@ -1218,7 +1299,7 @@ pub async fn review_hybrid_v20(
         v10_results.len(), challenged.len(), v10_results.len() - challenged.len(), iterations, tool_calls
     );
 
-    (challenged, iterations, tool_calls)
+    Ok((challenged, iterations, tool_calls))
 }
 
 #[cfg(test)]
@@ -1242,6 +1323,26 @@ mod tests {
         assert_eq!(findings[0].evidence.as_deref(), Some("if (x)"));
     }
 
+    #[test]
+    fn test_parse_issues_with_location() {
+        let input = r#"{"issues": [{"issue": "off by one", "evidence": "i <= n", "file": "src/a.rs", "line": 12}]}"#;
+        let findings = parse_issues(input);
+        assert_eq!(findings[0].file.as_deref(), Some("src/a.rs"));
+        assert_eq!(findings[0].line, Some(12));
+        assert!(!findings[0].anchored);
+    }
+
+    #[test]
+    fn test_validation_keeps_candidate_details() {
+        let candidates = parse_issues(
+            r#"{"issues": [{"issue": "Off by one", "evidence": "i <= n", "file": "src/a.rs", "line": 12}, {"issue": "other"}]}"#,
+        );
+        let validated = restore_details(parse_issues(r#"{"issues": ["off by one ", "new"]}"#), &candidates);
+        assert_eq!(validated[0].evidence.as_deref(), Some("i <= n"));
+        assert_eq!((validated[0].file.as_deref(), validated[0].line), (Some("src/a.rs"), Some(12)));
+        assert_eq!(validated[1].file, None);
+    }
+
     #[test]
     fn test_parse_issues_with_code_fence() {
         let input = "```json\n{\"issues\": [\"bug\"]}\n```";
@@ -1249,6 +1350,84 @@ mod tests {
         assert_eq!(findings.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_call_openai_uses_the_state_model() {
+        let server = crate::mock::MockServer::start(serde_json::json!({
+            "choices": [{"message": {"content": "ok"}}],
+        }))
+        .await;
+        let state = server.state().with_overrides(None, Some("gpt-4o-mini".into()));
+
+        let reply = call_openai(&state, "system", "prompt", 0.0, None).await.unwrap();
+        assert_eq!(reply, "ok");
+
+        let requests = server.requests();
+        assert_eq!(requests[0].uri.path(), "/chat/completions");
+        assert_eq!(requests[0].headers["authorization"], "Bearer sk-default");
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["model"], "gpt-4o-mini");
+    }
+
+    #[tokio::test]
+    async fn test_call_openai_retries_then_succeeds() {
+        use crate::mock::{MockServer, Reply};
+        let server = MockServer::scripted(vec![
+            Reply::retry_now(500),
+            Reply::retry_now(429),
+            Reply::ok(serde_json::json!({"choices": [{"message": {"content": "{\"issues\": []}"}}]})),
+        ])
+        .await;
+
+        let reply = call_openai(&server.state(), "system", "prompt", 0.0, Some(42)).await.unwrap();
+        assert_eq!(reply, "{\"issues\": []}");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let body: serde_json::Value = serde_json::from_slice(&requests[2].body).unwrap();
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert_eq!(body["seed"], 42);
+    }
+
+    #[tokio::test]
+    async fn test_call_openai_gives_up_with_the_attempt_count() {
+        use crate::mock::{MockServer, Reply};
+        let server = MockServer::scripted(vec![Reply::retry_now(503)]).await;
+
+        let err = call_openai(&server.state(), "system", "prompt", 0.0, None).await.unwrap_err();
+        assert!(err.starts_with("OpenAI API error 503 Service Unavailable after 4 attempts"), "{err}");
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_call_openai_does_not_retry_client_errors() {
+        use crate::mock::{MockServer, Reply};
+        let server = MockServer::scripted(vec![Reply {
+            status: 400,
+            retry_after: None,
+            body: serde_json::json!({"error": {"message": "bad request"}}),
+        }])
+        .await;
+
+        let err = call_openai(&server.state(), "system", "prompt", 0.0, None).await.unwrap_err();
+        assert!(err.contains("after 1 attempt:"), "{err}");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_merge_lenses_fails_only_when_every_lens_did() {
+        let ok = |text: &str| Ok(text.to_string());
+        let merged = merge_lenses(vec![
+            ok(r#"{"issues": ["bug 1", "Bug 1"]}"#),
+            Err("OpenAI API error 500".into()),
+            ok(r#"{"issues": ["bug 2"]}"#),
+        ])
+        .unwrap();
+        assert_eq!(merged.iter().map(|f| f.issue.as_str()).collect::<Vec<_>>(), ["bug 1", "bug 2"]);
+
+        let err = merge_lenses(vec![Err("first".into()), Err("second".into())]).unwrap_err();
+        assert_eq!(err, "all 2 review lenses failed; first error: first");
+    }
+
     #[test]
     fn test_strip_code_fences() {
         assert_eq!(strip_code_fences("```json\n{}\n```"), "{}");
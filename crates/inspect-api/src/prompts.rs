@@ -1,5 +1,5 @@
 use crate::openai::AgentContext;
-use inspect_core::types::EntityReview;
+use inspect_core::types::{EntityReview, RiskLevel};
 use sem_core::model::change::ChangeType;
 
 pub const SYSTEM_REVIEW: &str = "You are a precise code reviewer. Only report real bugs you are confident about. Always respond with valid JSON.";
@@ -23,7 +23,7 @@ PR Diff:
 
 Focus ONLY on: wrong translations, wrong constants/mappings/enum values, copy-paste errors, wrong key/field references, case sensitivity in comparisons, incorrect regex.
 Rules: ONLY concrete data issues. Be specific. Max 5 issues.
-Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code"}}]}}"#;
+Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_LENS_CONCURRENCY: &str = r#"You are a code reviewer specializing in CONCURRENCY and STATE bugs.
 
@@ -34,7 +34,7 @@ PR Diff:
 
 Focus ONLY on: race conditions, missing locks/transactions, stale reads, process lifecycle bugs, cache inconsistency, feature flag inconsistency.
 Rules: ONLY issues with evidence in the diff. Be specific. Max 5 issues.
-Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code"}}]}}"#;
+Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_LENS_CONTRACTS: &str = r#"You are a code reviewer specializing in API CONTRACT violations.
 
@@ -45,7 +45,7 @@ PR Diff:
 
 Focus ONLY on: missing abstract method implementations, wrong signatures/types, API breaking changes, wrong parameter order, key mismatches, missing React keys, import errors, method name typos breaking interfaces.
 Rules: ONLY verifiable issues. Be specific. Max 5 issues.
-Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code"}}]}}"#;
+Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_LENS_SECURITY: &str = r#"You are a security-focused code reviewer.
 
@@ -56,7 +56,7 @@ PR Diff:
 
 Focus ONLY on: SSRF, XSS, injection, auth bypass, origin/referrer bypass, case sensitivity bypass in security comparisons, frame options misconfig, hardcoded secrets.
 Rules: ONLY real exploitable vulnerabilities. Be specific. Max 5 issues.
-Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code"}}]}}"#;
+Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_LENS_TYPOS: &str = r#"You are a code reviewer with exceptional attention to character-level detail.
 
@@ -74,7 +74,7 @@ Focus ONLY on:
 - Property/key name mismatches
 
 Rules: Character-level precision. Only if it causes runtime failure. Max 5 issues.
-Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code"}}]}}"#;
+Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_LENS_RUNTIME: &str = r#"You are a code reviewer focused on RUNTIME FAILURES.
 
@@ -95,7 +95,7 @@ Focus ONLY on:
 - Missing React keys
 
 Rules: RUNTIME behavior only. Only actual failures. Max 5 issues.
-Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code"}}]}}"#;
+Respond with ONLY: {{"issues": [{{"issue": "desc", "evidence": "code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_DEEP: &str = r#"You are a world-class code reviewer. Review this PR and find ONLY real, concrete bugs.
 
@@ -126,10 +126,10 @@ Rules:
 - Do NOT report issues about code that was only deleted/removed
 - Maximum 10 issues. Quality over quantity.
 
-For each issue, provide it as a JSON object with "issue" (description) and "evidence" (quote the specific code lines from the diff that prove this is a bug).
+For each issue, provide it as a JSON object with "issue" (description), "evidence" (quote the specific code lines from the diff that prove this is a bug), "file" (the file's path as the diff shows it) and "line" (the line number of the evidence in the new version of the file).
 
 Respond with ONLY a JSON object:
-{{"issues": [{{"issue": "description", "evidence": "the specific code"}}]}}"#;
+{{"issues": [{{"issue": "description", "evidence": "the specific code", "file": "path/in/diff", "line": 42}}]}}"#;
 
 pub const PROMPT_VALIDATE: &str = r#"You are a senior code reviewer doing final validation. You have the PR diff and candidate issues.
 
@@ -153,8 +153,86 @@ Return ONLY the issues that are verified real bugs with evidence in the diff.
 Respond with ONLY a JSON object:
 {{"issues": ["verified issue 1", "verified issue 2", ...]}}"#;
 
+/// Diff characters the lens prompts get.
+pub const MAX_PROMPT_DIFF_CHARS: usize = 65_000;
+
+/// Weight of a file's riskiest entity in its section score: a section with
+/// a 1.0-risk entity counts five times its size.
+const RISK_BOOST: f64 = 4.0;
+
+/// Size-based score of one `diff --git` section (without that prefix),
+/// lowered for tests, docs, lockfiles and configs.
+fn section_score(part: &str) -> f64 {
+    let adds = part.matches("\n+").count().saturating_sub(part.matches("\n+++").count());
+    let dels = part.matches("\n-").count().saturating_sub(part.matches("\n---").count());
+    let mod_bonus = adds.min(dels) * 2;
+    let mut score = (adds + dels + mod_bonus) as f64;
+
+    let first_line = part.lines().next().unwrap_or("").to_lowercase();
+
+    // Deprioritize test files
+    if ["test", "spec", "mock", "__test__", "fixture"]
+        .iter()
+        .any(|kw| first_line.contains(kw))
+    {
+        score *= 0.3;
+    }
+    // Deprioritize docs
+    if [".md", ".adoc", ".txt", ".rst", "changelog", "readme"]
+        .iter()
+        .any(|kw| first_line.contains(kw))
+    {
+        score *= 0.2;
+    }
+    // Deprioritize snapshots/lockfiles
+    if [".snap", ".lock", "package-lock", "yarn.lock"]
+        .iter()
+        .any(|kw| first_line.contains(kw))
+    {
+        score *= 0.1;
+    }
+    // Deprioritize config files
+    if [".json", ".yaml", ".yml", ".toml", ".xml"]
+        .iter()
+        .any(|kw| first_line.contains(kw))
+    {
+        score *= 0.5;
+    }
+    score
+}
+
+/// The new-side path from a section's `a/x b/y` first line.
+fn section_path(part: &str) -> &str {
+    let first_line = part.lines().next().unwrap_or("");
+    first_line.split_once(" b/").map_or(first_line, |(_, new)| new).trim_end()
+}
+
+/// As much of `section` as fits in `budget`: its header plus whole hunks
+/// from the top. None if not even the first hunk fits.
+fn fit_hunks(section: &str, budget: usize) -> Option<String> {
+    let mut starts: Vec<usize> = section.match_indices("\n@@").map(|(i, _)| i + 1).collect();
+    let header_end = *starts.first()?;
+    starts.push(section.len());
+
+    let mut end = header_end;
+    for &next in &starts[1..] {
+        if next > budget {
+            break;
+        }
+        end = next;
+    }
+    (end > header_end).then(|| section[..end].to_string())
+}
+
 /// Smart diff truncation that deprioritizes tests, docs, configs.
 pub fn truncate_diff(diff: &str, max_chars: usize) -> String {
+    truncate_diff_with_triage(diff, max_chars, &[])
+}
+
+/// [`truncate_diff`], guided by triage: each file's score is boosted by
+/// its riskiest entity, and files holding a Critical or High entity go in
+/// before any others, cut down to the hunks that fit if they must be.
+pub fn truncate_diff_with_triage(diff: &str, max_chars: usize, entity_reviews: &[EntityReview]) -> String {
     if diff.len() <= max_chars {
         return diff.to_string();
     }
@@ -164,61 +242,40 @@ pub fn truncate_diff(diff: &str, max_chars: usize) -> String {
         return diff[..max_chars].to_string();
     }
 
-    let mut scored: Vec<(f64, &str)> = Vec::new();
+    // (urgent, score, section)
+    let mut scored: Vec<(bool, f64, &str)> = Vec::new();
     for part in &parts {
         let trimmed = part.trim();
         if trimmed.is_empty() {
             continue;
         }
 
-        let adds = part.matches("\n+").count().saturating_sub(part.matches("\n+++").count());
-        let dels = part.matches("\n-").count().saturating_sub(part.matches("\n---").count());
-        let mod_bonus = adds.min(dels) * 2;
-        let mut score = (adds + dels + mod_bonus) as f64;
+        let path = section_path(part);
+        let in_file = || entity_reviews.iter().filter(|e| e.file_path == path);
+        let max_risk = in_file().map(|e| e.risk_score).fold(0.0_f64, f64::max);
+        let urgent = in_file().any(|e| matches!(e.risk_level, RiskLevel::Critical | RiskLevel::High));
 
-        let first_line = part.lines().next().unwrap_or("").to_lowercase();
-
-        // Deprioritize test files
-        if ["test", "spec", "mock", "__test__", "fixture"]
-            .iter()
-            .any(|kw| first_line.contains(kw))
-        {
-            score *= 0.3;
-        }
-        // Deprioritize docs
-        if [".md", ".adoc", ".txt", ".rst", "changelog", "readme"]
-            .iter()
-            .any(|kw| first_line.contains(kw))
-        {
-            score *= 0.2;
-        }
-        // Deprioritize snapshots/lockfiles
-        if [".snap", ".lock", "package-lock", "yarn.lock"]
-            .iter()
-            .any(|kw| first_line.contains(kw))
-        {
-            score *= 0.1;
-        }
-        // Deprioritize config files
-        if [".json", ".yaml", ".yml", ".toml", ".xml"]
-            .iter()
-            .any(|kw| first_line.contains(kw))
-        {
-            score *= 0.5;
-        }
-
-        scored.push((score, part));
+        scored.push((urgent, section_score(part) * (1.0 + RISK_BOOST * max_risk), part));
     }
 
-    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    scored.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal))
+    });
 
     let mut result = String::new();
-    for (_, part) in &scored {
+    for (urgent, _, part) in &scored {
         let candidate = format!("diff --git {}", part);
-        if result.len() + candidate.len() > max_chars {
+        if result.len() + candidate.len() <= max_chars {
+            result.push_str(&candidate);
+        } else if *urgent {
+            // Keep what hunks fit, and still try the other urgent files
+            if let Some(cut) = fit_hunks(&candidate, max_chars - result.len()) {
+                result.push_str(&cut);
+            }
+        } else {
             break;
         }
-        result.push_str(&candidate);
     }
 
     if result.is_empty() {
@@ -488,3 +545,87 @@ pub fn build_code_triage(entities: &[EntityReview]) -> String {
 
     lines.join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn review(file: &str, risk_score: f64, risk_level: RiskLevel) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{file}::f"),
+            file_path: file.into(),
+            risk_score,
+            risk_level,
+            end_line: 3,
+            ..EntityReview::test_default()
+        }
+    }
+
+    /// A section for `path` with `hunks` hunks of `lines` added lines each.
+    fn section(path: &str, hunks: usize, lines: usize) -> String {
+        let mut s = format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n");
+        for h in 0..hunks {
+            s.push_str(&format!("@@ -{0},1 +{0},{1} @@\n", h * 100 + 1, lines + 1));
+            s.push_str(" context\n");
+            for i in 0..lines {
+                s.push_str(&format!("+    let value_{h}_{i} = compute({i});\n"));
+            }
+        }
+        s
+    }
+
+    #[test]
+    fn small_critical_file_beats_large_noisy_file() {
+        let noisy = section("src/helpers.rs", 1, 60);
+        let critical = section("src/auth.rs", 1, 3);
+        let diff = format!("{noisy}{critical}");
+        let max = noisy.len() + 10;
+
+        // By size alone the noisy file wins the budget
+        let plain = truncate_diff(&diff, max);
+        assert!(plain.contains("src/helpers.rs") && !plain.contains("src/auth.rs"));
+
+        let reviews = [
+            review("src/helpers.rs", 0.1, RiskLevel::Low),
+            review("src/auth.rs", 0.9, RiskLevel::Critical),
+        ];
+        let triaged = truncate_diff_with_triage(&diff, max, &reviews);
+        assert!(triaged.starts_with("diff --git a/src/auth.rs"), "{triaged}");
+        assert!(!triaged.contains("src/helpers.rs"));
+    }
+
+    #[test]
+    fn risk_boosts_the_score_of_ordinary_files() {
+        let big = section("src/big.rs", 1, 10);
+        let small = section("src/small.rs", 1, 5);
+        let diff = format!("{big}{small}");
+        let reviews = [review("src/small.rs", 0.8, RiskLevel::Medium)];
+        let triaged = truncate_diff_with_triage(&diff, big.len() + 10, &reviews);
+        assert!(triaged.contains("src/small.rs") && !triaged.contains("src/big.rs"));
+    }
+
+    #[test]
+    fn urgent_files_are_cut_at_hunk_boundaries() {
+        let critical = section("src/auth.rs", 3, 10);
+        let other = section("src/other.rs", 1, 10);
+        let diff = format!("{other}{critical}");
+        let reviews = [review("src/auth.rs", 0.7, RiskLevel::High)];
+
+        // Room for the header and two of the three hunks
+        let first_hunk = critical.find("\n@@").unwrap() + 1;
+        let hunk_len = (critical.len() - first_hunk) / 3;
+        let max = first_hunk + 2 * hunk_len + hunk_len / 2;
+
+        let triaged = truncate_diff_with_triage(&diff, max, &reviews);
+        assert!(triaged.len() <= max);
+        assert_eq!(triaged.matches("\n@@").count(), 2);
+        assert!(triaged.starts_with("diff --git a/src/auth.rs"));
+        assert!(triaged.ends_with("compute(9);\n"));
+    }
+
+    #[test]
+    fn diffs_within_the_limit_are_untouched() {
+        let diff = section("src/lib.rs", 2, 2);
+        assert_eq!(truncate_diff_with_triage(&diff, diff.len(), &[]), diff);
+    }
+}
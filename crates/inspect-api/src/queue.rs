@@ -0,0 +1,180 @@
+//! Bounded review concurrency: at most N reviews run at once, the rest wait
+//! their turn in the order they were accepted.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+/// The queue already holds its maximum number of waiting jobs.
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueueFull;
+
+pub struct ReviewQueue {
+    permits: Arc<Semaphore>,
+    waiting: Mutex<VecDeque<String>>,
+    max_depth: usize,
+    /// Woken whenever a slot frees up or the line moves
+    changed: Notify,
+}
+
+/// A running review's hold on one of the slots; frees it when dropped.
+pub struct Slot {
+    permit: Option<OwnedSemaphorePermit>,
+    queue: Arc<ReviewQueue>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        self.queue.changed.notify_waiters();
+    }
+}
+
+impl ReviewQueue {
+    pub fn new(max_concurrent: usize, max_depth: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            waiting: Mutex::new(VecDeque::new()),
+            max_depth,
+            changed: Notify::new(),
+        }
+    }
+
+    fn waiting(&self) -> std::sync::MutexGuard<'_, VecDeque<String>> {
+        self.waiting.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Put a new job at the back of the line, unless the line is full.
+    pub fn try_enqueue(&self, id: &str) -> Result<(), QueueFull> {
+        let mut waiting = self.waiting();
+        if waiting.len() >= self.max_depth {
+            return Err(QueueFull);
+        }
+        waiting.push_back(id.to_string());
+        Ok(())
+    }
+
+    /// Put a job at the back of the line however long it is, for jobs
+    /// resumed at startup.
+    pub fn enqueue(&self, id: &str) {
+        self.waiting().push_back(id.to_string());
+    }
+
+    /// Take a job out of the line, e.g. because it was deleted.
+    pub fn remove(&self, id: &str) {
+        self.waiting().retain(|waiting| waiting != id);
+        self.changed.notify_waiters();
+    }
+
+    /// Where a waiting job stands: 1 when it's next to start.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        self.waiting().iter().position(|waiting| waiting == id).map(|i| i + 1)
+    }
+
+    /// Wait until `id` is at the front of the line and a slot is free, then
+    /// take both. None if the job left the line while it waited.
+    pub async fn acquire(self: &Arc<Self>, id: &str) -> Option<Slot> {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            // Register before looking, so a release in between isn't missed
+            changed.as_mut().enable();
+            {
+                let mut waiting = self.waiting();
+                match waiting.front() {
+                    Some(front) if front == id => {
+                        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+                            waiting.pop_front();
+                            drop(waiting);
+                            // The next job may be able to start too
+                            self.changed.notify_waiters();
+                            return Some(Slot {
+                                permit: Some(permit),
+                                queue: self.clone(),
+                            });
+                        }
+                    }
+                    _ if !waiting.iter().any(|waiting| waiting == id) => return None,
+                    _ => {}
+                }
+            }
+            changed.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use tokio::sync::{mpsc, oneshot};
+
+    #[tokio::test]
+    async fn jobs_beyond_the_limit_start_in_order() {
+        let queue = Arc::new(ReviewQueue::new(2, 10));
+        let ids = ["a", "b", "c", "d", "e"];
+        for id in ids {
+            queue.try_enqueue(id).unwrap();
+        }
+
+        // Start the waiters in reverse, so spawn order can't be what orders them
+        let (started_tx, mut started) = mpsc::unbounded_channel();
+        let mut finish = Vec::new();
+        for &id in ids.iter().rev() {
+            let (done_tx, done) = oneshot::channel::<()>();
+            finish.push(done_tx);
+            let (queue, started_tx) = (queue.clone(), started_tx.clone());
+            tokio::spawn(async move {
+                let _slot = queue.acquire(id).await.unwrap();
+                started_tx.send(id).unwrap();
+                let _ = done.await;
+            });
+        }
+        finish.reverse();
+
+        let mut order = vec![started.recv().await.unwrap(), started.recv().await.unwrap()];
+        order.sort();
+        assert_eq!(order, ["a", "b"]);
+        assert_eq!(queue.position("c"), Some(1));
+        assert_eq!(queue.position("e"), Some(3));
+        assert_eq!(queue.position("a"), None);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(started.try_recv().is_err(), "only two run at once");
+
+        for done in finish.drain(..3) {
+            done.send(()).unwrap();
+            order.push(started.recv().await.unwrap());
+        }
+        assert_eq!(order, ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn a_full_queue_turns_jobs_away() {
+        let queue = ReviewQueue::new(1, 2);
+        queue.try_enqueue("a").unwrap();
+        queue.try_enqueue("b").unwrap();
+        assert_eq!(queue.try_enqueue("c"), Err(QueueFull));
+        // Resumed jobs go in regardless
+        queue.enqueue("d");
+        assert_eq!(queue.position("d"), Some(3));
+    }
+
+    #[tokio::test]
+    async fn removed_jobs_stop_waiting() {
+        let queue = Arc::new(ReviewQueue::new(1, 10));
+        queue.try_enqueue("a").unwrap();
+        queue.try_enqueue("b").unwrap();
+        let slot = queue.acquire("a").await.unwrap();
+
+        let waiter = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.acquire("b").await.is_none() }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        queue.remove("b");
+        assert!(waiter.await.unwrap());
+        drop(slot);
+    }
+}
@@ -1,29 +1,63 @@
 use std::sync::Arc;
 use std::time::Instant;
 
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Path, Query, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Deserialize;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use inspect_core::analyze::analyze_remote;
-use inspect_core::github::GitHubClient;
+use inspect_core::filter::parse_risk_level;
+use inspect_core::github::{FilePair, GitHubClient};
 use inspect_core::noise::is_noise_file;
 use inspect_core::risk::suggest_verdict;
+use inspect_core::types::RiskLevel;
 
+use crate::anchor::anchor_findings;
 use crate::auth::ApiKey;
+use crate::callback;
+use crate::diff::file_pairs_from_diff;
 use crate::openai;
 use crate::prompts;
 use crate::state::*;
+use crate::store::JobFilter;
+
+/// Header a request may carry its own GitHub token in, instead of the body.
+const GITHUB_TOKEN_HEADER: &str = "x-github-token";
+
+/// Seconds a client turned away by a full queue is told to wait.
+pub const QUEUE_FULL_RETRY_AFTER_SECS: u64 = 30;
+
+/// Page size of `GET /v1/review` when no limit is given, and the most it allows.
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
 
 #[derive(Deserialize)]
 pub struct ReviewRequest {
     pub repo: String,
     pub pr_number: u64,
     pub strategy: Option<String>,
+    /// POST the job here when it completes or fails
+    pub callback_url: Option<String>,
+    /// Signs the callback body (`X-Inspect-Signature-256`)
+    pub callback_secret: Option<String>,
+    /// Fetch the PR with this instead of `GITHUB_TOKEN`; never stored
+    pub github_token: Option<String>,
+    /// Review with this OpenAI model instead of `OPENAI_MODEL`
+    pub model: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    pub status: Option<String>,
+    pub repo: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
 }
 
 #[derive(Deserialize)]
@@ -31,15 +65,63 @@ pub struct TriageRequest {
     pub repo: String,
     pub pr_number: u64,
     pub min_risk: Option<String>,
+    /// Fetch the PR with this instead of `GITHUB_TOKEN`
+    pub github_token: Option<String>,
+}
+
+/// A changed file as the caller has it, for `POST /v1/analyze`.
+#[derive(Deserialize)]
+pub struct AnalyzeFile {
+    pub filename: String,
+    /// `added`, `removed`, `renamed` or `modified`
+    pub status: String,
+    pub previous_filename: Option<String>,
+    pub before_content: Option<String>,
+    pub after_content: Option<String>,
+}
+
+/// Either the files themselves or a unified diff to rebuild them from.
+#[derive(Deserialize)]
+pub struct AnalyzeRequest {
+    pub files: Option<Vec<AnalyzeFile>>,
+    pub diff: Option<String>,
+    pub min_risk: Option<String>,
+}
+
+/// The request's own GitHub token: the body field, else `X-GitHub-Token`.
+fn request_github_token(body: Option<String>, headers: &HeaderMap) -> Option<String> {
+    body.or_else(|| {
+        headers
+            .get(GITHUB_TOKEN_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    })
+    .filter(|token| !token.is_empty())
 }
 
 // POST /v1/review
 pub async fn create_review(
     State(state): State<Arc<AppState>>,
     _api_key: ApiKey,
+    headers: HeaderMap,
     Json(req): Json<ReviewRequest>,
-) -> impl IntoResponse {
+) -> Response {
+    if let Some(ref url) = req.callback_url {
+        if let Err(e) = callback::validate_url(url, state.allow_insecure_callbacks) {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))).into_response();
+        }
+    }
+
     let id = Uuid::new_v4().to_string();
+    if state.queue.try_enqueue(&id).is_err() {
+        warn!("Review queue full, turning away {}#{}", req.repo, req.pr_number);
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, QUEUE_FULL_RETRY_AFTER_SECS.to_string())],
+            Json(serde_json::json!({"error": "review queue is full"})),
+        )
+            .into_response();
+    }
 
     let job = ReviewJob {
         id: id.clone(),
@@ -50,18 +132,30 @@ pub async fn create_review(
         result: None,
         error: None,
         created_at: chrono::Utc::now(),
+        callback_url: req.callback_url.clone(),
+        callback_secret: req.callback_secret.clone(),
+        callback: None,
+        model: Some(req.model.clone().unwrap_or_else(|| state.openai_model.clone())),
+        head_sha: None,
+        queued_position: None,
     };
 
-    {
-        let mut jobs = state.jobs.write().await;
-        jobs.insert(id.clone(), job.clone());
+    if let Err(e) = state.jobs.insert(&job) {
+        error!("Failed to store job {}: {}", id, e);
+        state.queue.remove(&id);
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"error": "failed to store job"})),
+        )
+            .into_response();
     }
 
     // Spawn background review
     let state_clone = state.clone();
     let id_clone = id.clone();
+    let github_token = request_github_token(req.github_token, &headers);
     tokio::spawn(async move {
-        run_review(state_clone, id_clone).await;
+        run_review(state_clone, id_clone, github_token).await;
     });
 
     (
@@ -69,8 +163,10 @@ pub async fn create_review(
         Json(serde_json::json!({
             "id": id,
             "status": "pending",
+            "queued_position": state.queue.position(&id),
         })),
     )
+        .into_response()
 }
 
 // GET /v1/review/:id
@@ -79,25 +175,117 @@ pub async fn get_review(
     _api_key: ApiKey,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    let jobs = state.jobs.read().await;
-    match jobs.get(&id) {
-        Some(job) => (StatusCode::OK, Json(serde_json::to_value(job).unwrap())),
-        None => (
+    match state.jobs.get(&id) {
+        Ok(Some(mut job)) => {
+            job.queued_position = state.queue.position(&id);
+            (StatusCode::OK, Json(serde_json::to_value(job).unwrap()))
+        }
+        Ok(None) => (
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({"error": "job not found"})),
         ),
+        Err(e) => {
+            error!("Failed to read job {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": "failed to read job"})),
+            )
+        }
+    }
+}
+
+// GET /v1/review?status=complete&repo=owner/repo&limit=50&offset=0
+pub async fn list_reviews(
+    State(state): State<Arc<AppState>>,
+    _api_key: ApiKey,
+    Query(query): Query<ListQuery>,
+) -> impl IntoResponse {
+    let status = match query.status.as_deref().map(|s| (s, JobStatus::parse(s))) {
+        None => None,
+        Some((_, Some(status))) => Some(status),
+        Some((s, None)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": format!("unknown status '{s}'")})),
+            )
+        }
+    };
+    let filter = JobFilter {
+        status,
+        repo: query.repo,
+        limit: query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE),
+        offset: query.offset.unwrap_or(0),
+    };
+    match state.jobs.list(&filter) {
+        Ok(page) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "jobs": page.jobs,
+                "total": page.total,
+                "limit": filter.limit,
+                "offset": filter.offset,
+            })),
+        ),
+        Err(e) => {
+            error!("Failed to list jobs: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": "failed to list jobs"})),
+            )
+        }
+    }
+}
+
+// DELETE /v1/review/:id
+pub async fn delete_review(
+    State(state): State<Arc<AppState>>,
+    _api_key: ApiKey,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let deleted = match state.jobs.get(&id) {
+        Ok(Some(job)) if matches!(job.status, JobStatus::Analyzing | JobStatus::Reviewing) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({"error": "job is still running"})),
+            )
+        }
+        Ok(_) => {
+            state.queue.remove(&id);
+            state.jobs.delete(&id)
+        }
+        Err(e) => Err(e),
+    };
+    match deleted {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({"id": id, "deleted": true}))),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "job not found"})),
+        ),
+        Err(e) => {
+            error!("Failed to delete job {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": "failed to delete job"})),
+            )
+        }
     }
 }
 
 // POST /v1/triage
 pub async fn create_triage(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     _api_key: ApiKey,
+    headers: HeaderMap,
     Json(req): Json<TriageRequest>,
 ) -> impl IntoResponse {
     let start = Instant::now();
+    let min_risk = match req.min_risk.as_deref().map(parse_risk_level).transpose() {
+        Ok(level) => level,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))),
+    };
+    let state = state.with_overrides(request_github_token(req.github_token, &headers), None);
 
-    let client = match GitHubClient::new() {
+    let client = match GitHubClient::with_base_url(&state.github_token, &state.github_api_url) {
         Ok(c) => c,
         Err(e) => {
             return (
@@ -124,11 +312,20 @@ pub async fn create_triage(
         .cloned()
         .collect();
 
-    let file_pairs = client
+    let file_pairs = match client
         .get_file_pairs(&req.repo, &visible_files, &pr.base_sha, &pr.head_sha)
-        .await;
+        .await
+    {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({"error": format!("Failed to fetch files: {e}")})),
+            );
+        }
+    };
 
-    let result = match analyze_remote(&file_pairs) {
+    let result = match analyze_remote(&file_pairs, &[]) {
         Ok(r) => r,
         Err(e) => {
             return (
@@ -138,24 +335,89 @@ pub async fn create_triage(
         }
     };
 
-    let verdict = suggest_verdict(&result);
-    let entities = build_entity_json(&result, req.min_risk.as_deref());
     let elapsed = start.elapsed().as_millis() as u64;
+    (StatusCode::OK, Json(triage_json(&result, min_risk, elapsed)))
+}
 
-    let resp = serde_json::json!({
-        "verdict": format!("{}", verdict),
+// POST /v1/analyze
+pub async fn analyze(
+    State(state): State<Arc<AppState>>,
+    _api_key: ApiKey,
+    req: Result<Json<AnalyzeRequest>, JsonRejection>,
+) -> impl IntoResponse {
+    let start = Instant::now();
+
+    // Bodies over max_analyze_bytes are rejected here with a 413
+    let req = match req {
+        Ok(Json(req)) => req,
+        Err(rejection) => {
+            return (
+                rejection.status(),
+                Json(serde_json::json!({"error": rejection.body_text()})),
+            );
+        }
+    };
+
+    let min_risk = match req.min_risk.as_deref().map(parse_risk_level).transpose() {
+        Ok(level) => level,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": e}))),
+    };
+    let file_pairs: Vec<FilePair> = match (req.files, req.diff) {
+        (Some(files), None) => files
+            .into_iter()
+            .map(|f| FilePair {
+                filename: f.filename,
+                status: f.status,
+                previous_filename: f.previous_filename,
+                before_content: f.before_content,
+                after_content: f.after_content,
+            })
+            .collect(),
+        (None, Some(diff)) => file_pairs_from_diff(&diff),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": "give exactly one of `files` or `diff`"})),
+            );
+        }
+    };
+    if file_pairs.len() > state.max_analyze_files {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "error": format!("{} files; at most {} allowed", file_pairs.len(), state.max_analyze_files),
+            })),
+        );
+    }
+
+    let result = match analyze_remote(&file_pairs, &[]) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": format!("Analysis failed: {e}")})),
+            );
+        }
+    };
+
+    let elapsed = start.elapsed().as_millis() as u64;
+    (StatusCode::OK, Json(triage_json(&result, min_risk, elapsed)))
+}
+
+/// The `/v1/triage` and `/v1/analyze` response body.
+fn triage_json(result: &inspect_core::types::ReviewResult, min_risk: Option<RiskLevel>, elapsed_ms: u64) -> serde_json::Value {
+    serde_json::json!({
+        "verdict": format!("{}", suggest_verdict(result)),
         "total_entities": result.stats.total_entities,
-        "entities": entities,
+        "entities": build_entity_json(result, min_risk),
         "stats": {
             "critical": result.stats.by_risk.critical,
             "high": result.stats.by_risk.high,
             "medium": result.stats.by_risk.medium,
             "low": result.stats.by_risk.low,
         },
-        "timing_ms": elapsed,
-    });
-
-    (StatusCode::OK, Json(resp))
+        "timing_ms": elapsed_ms,
+    })
 }
 
 // GET /health
@@ -163,29 +425,14 @@ pub async fn health() -> impl IntoResponse {
     Json(serde_json::json!({"status": "ok"}))
 }
 
-fn parse_risk_level(s: &str) -> inspect_core::types::RiskLevel {
-    match s.to_lowercase().as_str() {
-        "critical" => inspect_core::types::RiskLevel::Critical,
-        "high" => inspect_core::types::RiskLevel::High,
-        "medium" => inspect_core::types::RiskLevel::Medium,
-        _ => inspect_core::types::RiskLevel::Low,
-    }
-}
-
 fn build_entity_json(
     result: &inspect_core::types::ReviewResult,
-    min_risk: Option<&str>,
+    min_risk: Option<RiskLevel>,
 ) -> Vec<serde_json::Value> {
     result
         .entity_reviews
         .iter()
-        .filter(|r| {
-            if let Some(min) = min_risk {
-                r.risk_level >= parse_risk_level(min)
-            } else {
-                true
-            }
-        })
+        .filter(|r| min_risk.is_none_or(|min| r.risk_level >= min))
         .map(|r| {
             let is_cosmetic = r.structural_change == Some(false);
             let mut obj = serde_json::json!({
@@ -240,16 +487,31 @@ fn build_entity_json(
         .collect()
 }
 
-/// Background job: run full review pipeline.
-async fn run_review(state: Arc<AppState>, job_id: String) {
+/// Background job: run full review pipeline, on the job's model and with
+/// `github_token` if the request gave one.
+pub async fn run_review(state: Arc<AppState>, job_id: String, github_token: Option<String>) {
+    // Wait for a free slot; the job may have been deleted meanwhile
+    let Some(_slot) = state.queue.acquire(&job_id).await else {
+        return;
+    };
     let total_start = Instant::now();
 
     // Update status to analyzing
     update_status(&state, &job_id, JobStatus::Analyzing).await;
 
+    let (repo, pr_number, strategy, model) = match state.jobs.get(&job_id) {
+        Ok(Some(job)) => (job.repo, job.pr_number, job.strategy, job.model),
+        Ok(None) => return,
+        Err(e) => {
+            fail_job(&state, &job_id, format!("Failed to read job: {e}")).await;
+            return;
+        }
+    };
+    let state = state.with_overrides(github_token, model);
+
     // Step 1: Fetch PR and run triage
     let triage_start = Instant::now();
-    let client = match GitHubClient::new() {
+    let client = match GitHubClient::with_base_url(&state.github_token, &state.github_api_url) {
         Ok(c) => c,
         Err(e) => {
             fail_job(&state, &job_id, format!("GitHub auth failed: {e}")).await;
@@ -257,12 +519,6 @@ async fn run_review(state: Arc<AppState>, job_id: String) {
         }
     };
 
-    let (repo, pr_number, strategy) = {
-        let jobs = state.jobs.read().await;
-        let job = jobs.get(&job_id).unwrap();
-        (job.repo.clone(), job.pr_number, job.strategy.clone())
-    };
-
     let pr = match client.get_pr(&repo, pr_number).await {
         Ok(pr) => pr,
         Err(e) => {
@@ -283,11 +539,18 @@ async fn run_review(state: Arc<AppState>, job_id: String) {
         .cloned()
         .collect();
 
-    let file_pairs = client
+    let file_pairs = match client
         .get_file_pairs(&repo, &visible_files, &pr.base_sha, &pr.head_sha)
-        .await;
+        .await
+    {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            fail_job(&state, &job_id, format!("Failed to fetch files: {e}")).await;
+            return;
+        }
+    };
 
-    let result = match analyze_remote(&file_pairs) {
+    let result = match analyze_remote(&file_pairs, &[]) {
         Ok(r) => r,
         Err(e) => {
             fail_job(&state, &job_id, format!("Analysis failed: {e}")).await;
@@ -299,8 +562,33 @@ async fn run_review(state: Arc<AppState>, job_id: String) {
     let triage_ms = triage_start.elapsed().as_millis() as u64;
     info!("Triage complete in {}ms: {} entities", triage_ms, result.stats.total_entities);
 
+    // Store the triage now, so pollers have it while the LLM works
+    let mut response = ReviewResponse {
+        findings: None,
+        triage: TriageResponse {
+            verdict: format!("{}", verdict),
+            total_entities: result.stats.total_entities,
+            entities: build_entity_json(&result, None),
+            stats: serde_json::json!({
+                "critical": result.stats.by_risk.critical,
+                "high": result.stats.by_risk.high,
+                "medium": result.stats.by_risk.medium,
+                "low": result.stats.by_risk.low,
+            }),
+        },
+        timing: TimingInfo {
+            triage_ms,
+            review_ms: None,
+            total_ms: None,
+            agent_iterations: None,
+            agent_tool_calls: None,
+        },
+    };
+    if let Err(e) = state.jobs.set_triage(&job_id, &response) {
+        error!("Failed to store triage of job {}: {}", job_id, e);
+    }
+
     // Step 2: Fetch raw diff for LLM review
-    update_status(&state, &job_id, JobStatus::Reviewing).await;
 
     let review_start = Instant::now();
     let diff = match fetch_pr_diff(&state, &repo, pr_number).await {
@@ -313,14 +601,21 @@ async fn run_review(state: Arc<AppState>, job_id: String) {
 
     // Build triage context with entity code snippets
     let triage_section = prompts::build_code_triage(&result.entity_reviews);
+    // Cut the diff to the prompt budget, keeping the files triage flagged
+    let prompt_diff =
+        prompts::truncate_diff_with_triage(&diff, prompts::MAX_PROMPT_DIFF_CHARS, &result.entity_reviews);
 
     // Step 3: LLM review
-    let (findings, agent_iterations, agent_tool_calls) = match strategy.as_deref() {
+    let (mut findings, agent_iterations, agent_tool_calls) = match strategy.as_deref() {
         Some("raw_lenses") => {
             info!("Using raw_lenses strategy (no validation, no challenge)");
-            let findings =
-                openai::review_raw_lenses(&state, &pr.title, &diff, &triage_section, 50).await;
-            (findings, None, None)
+            match openai::review_raw_lenses(&state, &pr.title, &prompt_diff, &triage_section, 50).await {
+                Ok(findings) => (findings, None, None),
+                Err(e) => {
+                    fail_job(&state, &job_id, format!("Review failed: {e}")).await;
+                    return;
+                }
+            }
         }
         _ => {
             info!("Using hybrid_v20 strategy (9 lenses + validation + agentic challenge)");
@@ -332,54 +627,68 @@ async fn run_review(state: Arc<AppState>, job_id: String) {
                 pr_title: pr.title.clone(),
                 triage_section: triage_section.clone(),
             };
-            let (findings, iters, calls) =
-                openai::review_hybrid_v20(&state, &pr.title, &diff, &triage_section, 7, &ctx).await;
-            (findings, Some(iters), Some(calls))
+            match openai::review_hybrid_v20(&state, &pr.title, &prompt_diff, &triage_section, 7, &ctx).await {
+                Ok((findings, iters, calls)) => (findings, Some(iters), Some(calls)),
+                Err(e) => {
+                    fail_job(&state, &job_id, format!("Review failed: {e}")).await;
+                    return;
+                }
+            }
         }
     };
     let review_ms = review_start.elapsed().as_millis() as u64;
     info!("Review complete in {}ms: {} findings", review_ms, findings.len());
+    anchor_findings(&mut findings, &result.entity_reviews, &diff);
 
-    // Build response
-    let entities = build_entity_json(&result, None);
     let total_ms = total_start.elapsed().as_millis() as u64;
-
-    let response = ReviewResponse {
-        findings,
-        triage: TriageResponse {
-            verdict: format!("{}", verdict),
-            total_entities: result.stats.total_entities,
-            entities,
-            stats: serde_json::json!({
-                "critical": result.stats.by_risk.critical,
-                "high": result.stats.by_risk.high,
-                "medium": result.stats.by_risk.medium,
-                "low": result.stats.by_risk.low,
-            }),
-        },
-        timing: TimingInfo {
-            triage_ms,
-            review_ms,
-            total_ms,
-            agent_iterations,
-            agent_tool_calls,
-        },
+    response.findings = Some(findings);
+    response.timing = TimingInfo {
+        triage_ms,
+        review_ms: Some(review_ms),
+        total_ms: Some(total_ms),
+        agent_iterations,
+        agent_tool_calls,
     };
 
     // Store result
-    {
-        let mut jobs = state.jobs.write().await;
-        if let Some(job) = jobs.get_mut(&job_id) {
-            job.status = JobStatus::Complete;
-            job.result = Some(response);
-        }
+    if let Err(e) = state.jobs.complete(&job_id, &response) {
+        error!("Failed to store result of job {}: {}", job_id, e);
+        return;
     }
 
     info!("Job {} complete in {}ms", job_id, total_ms);
+    notify(&state, &job_id).await;
+}
+
+/// Send a finished job to its callback URL, if it has one, and record how
+/// that went.
+pub async fn notify(state: &AppState, job_id: &str) {
+    let job = match state.jobs.get(job_id) {
+        Ok(Some(job)) if job.callback_url.is_some() => job,
+        Ok(_) => return,
+        Err(e) => {
+            error!("Failed to read job {} for its callback: {}", job_id, e);
+            return;
+        }
+    };
+    let delivery = callback::deliver(&job, state.allow_insecure_callbacks, callback::DEFAULT_RETRY).await;
+    if delivery.delivered {
+        info!("Delivered callback for job {} in {} attempts", job_id, delivery.attempts);
+    } else {
+        warn!(
+            "Callback for job {} failed after {} attempts: {}",
+            job_id,
+            delivery.attempts,
+            delivery.error.as_deref().unwrap_or("unknown error")
+        );
+    }
+    if let Err(e) = state.jobs.set_callback(job_id, &delivery) {
+        error!("Failed to record callback of job {}: {}", job_id, e);
+    }
 }
 
 async fn fetch_pr_diff(state: &AppState, repo: &str, pr_number: u64) -> Result<String, String> {
-    let url = format!("https://api.github.com/repos/{repo}/pulls/{pr_number}");
+    let url = format!("{}/repos/{repo}/pulls/{pr_number}", state.github_api_url);
     let resp = state
         .http
         .get(&url)
@@ -400,17 +709,291 @@ async fn fetch_pr_diff(state: &AppState, repo: &str, pr_number: u64) -> Result<S
 }
 
 async fn update_status(state: &AppState, job_id: &str, status: JobStatus) {
-    let mut jobs = state.jobs.write().await;
-    if let Some(job) = jobs.get_mut(job_id) {
-        job.status = status;
+    if let Err(e) = state.jobs.set_status(job_id, status) {
+        error!("Failed to update job {}: {}", job_id, e);
     }
 }
 
 async fn fail_job(state: &AppState, job_id: &str, error: String) {
     error!("Job {} failed: {}", job_id, error);
-    let mut jobs = state.jobs.write().await;
-    if let Some(job) = jobs.get_mut(job_id) {
-        job.status = JobStatus::Failed;
-        job.error = Some(error);
+    if let Err(e) = state.jobs.fail(job_id, &error) {
+        error!("Failed to record failure of job {}: {}", job_id, e);
+        return;
+    }
+    notify(state, job_id).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockServer;
+
+    #[test]
+    fn body_token_wins_over_the_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_github_token(None, &headers), None);
+        headers.insert(GITHUB_TOKEN_HEADER, "from-header".parse().unwrap());
+        assert_eq!(request_github_token(None, &headers).as_deref(), Some("from-header"));
+        assert_eq!(request_github_token(Some("from-body".into()), &headers).as_deref(), Some("from-body"));
+        assert_eq!(request_github_token(Some(String::new()), &HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn diff_is_fetched_with_the_request_token() {
+        let server = MockServer::start(serde_json::json!({})).await;
+        let state = server.state();
+
+        fetch_pr_diff(&state, "owner/repo", 7).await.unwrap();
+        fetch_pr_diff(&state.with_overrides(Some("ghp-request".into()), None), "owner/repo", 7)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].uri.path(), "/repos/owner/repo/pulls/7");
+        assert_eq!(requests[0].headers["authorization"], "token ghp-default");
+        assert_eq!(requests[1].headers["authorization"], "token ghp-request");
+    }
+
+    #[tokio::test]
+    async fn review_jobs_record_the_model_but_not_the_token() {
+        let server = MockServer::start(serde_json::json!({})).await;
+        let state = Arc::new(server.state());
+        let mut headers = HeaderMap::new();
+        headers.insert(GITHUB_TOKEN_HEADER, "ghp-request".parse().unwrap());
+        let req = ReviewRequest {
+            model: Some("gpt-4o-mini".into()),
+            ..review_request(7)
+        };
+
+        let api_key = ApiKey { key_id: "key".into() };
+        let resp = create_review(State(state.clone()), api_key, headers, Json(req)).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let body = json_body(resp).await;
+        let id = body["id"].as_str().unwrap();
+
+        // The background review's first GitHub call carries the request's token
+        for _ in 0..100 {
+            if !server.requests().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(server.requests()[0].headers["authorization"], "Bearer ghp-request");
+
+        let job = state.jobs.get(id).unwrap().unwrap();
+        assert_eq!(job.model.as_deref(), Some("gpt-4o-mini"));
+        assert!(!serde_json::to_string(&job).unwrap().contains("ghp-request"));
+    }
+
+    fn review_request(pr_number: u64) -> ReviewRequest {
+        ReviewRequest {
+            repo: "owner/repo".into(),
+            pr_number,
+            strategy: None,
+            callback_url: None,
+            callback_secret: None,
+            github_token: None,
+            model: None,
+        }
+    }
+
+    async fn json_body(resp: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// GitHub serving PR 7 with nothing to triage, and an OpenAI that holds
+    /// every answer until `gate` has permits.
+    async fn held_backends(gate: Arc<tokio::sync::Semaphore>) -> String {
+        let pr = serde_json::json!({"data": {"repository": {"pullRequest": {
+            "number": 7, "title": "Tidy up", "body": null, "state": "OPEN",
+            "additions": 1, "deletions": 1, "changedFiles": 0,
+            "headRefName": "feature", "baseRefName": "main", "headRefOid": "head", "baseRefOid": "base",
+            "files": {"pageInfo": {"hasNextPage": false, "endCursor": null}, "nodes": []},
+        }}}});
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-old();\n+new();\n";
+        let answer = serde_json::json!({"choices": [{"message": {"content": r#"{"issues": [{"issue": "new() can fail"}]}"#}}]});
+        let app = axum::Router::new()
+            .route("/graphql", axum::routing::post(move || std::future::ready(Json(pr.clone()))))
+            .route("/repos/owner/repo/pulls/7", axum::routing::get(move || std::future::ready(diff)))
+            .route(
+                "/chat/completions",
+                axum::routing::post(move || {
+                    let (gate, answer) = (gate.clone(), answer.clone());
+                    async move {
+                        let _permit = gate.acquire().await.unwrap();
+                        Json(answer)
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    /// Poll `GET /v1/review/{id}` until the job reaches `status`.
+    async fn wait_for(state: &Arc<AppState>, id: &str, status: &str) -> serde_json::Value {
+        for _ in 0..500 {
+            let api_key = ApiKey { key_id: "key".into() };
+            let resp = get_review(State(state.clone()), api_key, Path(id.to_string())).await.into_response();
+            let job = json_body(resp).await;
+            if job["status"] == status {
+                return job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("job {id} never reached {status}");
+    }
+
+    #[tokio::test]
+    async fn triage_is_stored_before_the_llm_finishes() {
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let url = held_backends(gate.clone()).await;
+        let server = MockServer::start(serde_json::json!({})).await;
+        let state = Arc::new(AppState {
+            github_api_url: url.clone(),
+            openai_base_url: url,
+            ..server.state()
+        });
+        let req = ReviewRequest {
+            strategy: Some("raw_lenses".into()),
+            ..review_request(7)
+        };
+        let api_key = ApiKey { key_id: "key".into() };
+        let resp = create_review(State(state.clone()), api_key, HeaderMap::new(), Json(req)).await;
+        let id = json_body(resp).await["id"].as_str().unwrap().to_string();
+
+        // Every lens is held, so the job sits in reviewing with its triage
+        let job = wait_for(&state, &id, "reviewing").await;
+        let result = &job["result"];
+        assert_eq!(result["triage"]["total_entities"], 0);
+        assert!(result["timing"]["triage_ms"].is_u64());
+        assert!(result.get("findings").is_none(), "{result}");
+        assert!(result["timing"].get("total_ms").is_none());
+
+        gate.add_permits(100);
+        let job = wait_for(&state, &id, "complete").await;
+        let result = &job["result"];
+        assert_eq!(result["findings"][0]["issue"], "new() can fail");
+        assert_eq!(result["triage"]["total_entities"], 0);
+        assert!(result["timing"]["total_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn jobs_past_the_limit_wait_in_line_until_the_queue_fills() {
+        let server = MockServer::start(serde_json::json!({})).await;
+        // No slots at all, so every job stays queued
+        let state = Arc::new(AppState {
+            queue: Arc::new(crate::queue::ReviewQueue::new(0, 2)),
+            ..server.state()
+        });
+        let create = |pr_number| {
+            let api_key = ApiKey { key_id: "key".into() };
+            create_review(State(state.clone()), api_key, HeaderMap::new(), Json(review_request(pr_number)))
+        };
+
+        let first = json_body(create(1).await).await;
+        let second = json_body(create(2).await).await;
+        assert_eq!(first["queued_position"], 1);
+        assert_eq!(second["queued_position"], 2);
+
+        let full = create(3).await;
+        assert_eq!(full.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(full.headers()[RETRY_AFTER], "30");
+
+        let id = second["id"].as_str().unwrap().to_string();
+        let api_key = ApiKey { key_id: "key".into() };
+        let job = json_body(get_review(State(state.clone()), api_key, Path(id)).await.into_response()).await;
+        assert_eq!(job["status"], "pending");
+        assert_eq!(job["queued_position"], 2);
+        assert!(server.requests().is_empty(), "nothing started");
+    }
+
+    /// Serve the whole app on a local port, with `supabase` vouching for
+    /// every API key; returns its URL.
+    async fn serve_app(supabase: &MockServer, configure: impl FnOnce(&mut AppState)) -> String {
+        let mut state = supabase.state();
+        configure(&mut state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = crate::app(Arc::new(state));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    async fn post_analyze(url: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let resp = reqwest::Client::new()
+            .post(format!("{url}/v1/analyze"))
+            .bearer_auth("key")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let status = StatusCode::from_u16(resp.status().as_u16()).unwrap();
+        (status, resp.json().await.unwrap())
+    }
+
+    fn supabase() -> serde_json::Value {
+        serde_json::json!([{"id": "key", "request_count": 0}])
+    }
+
+    #[tokio::test]
+    async fn analyze_takes_files() {
+        let supabase = MockServer::start(supabase()).await;
+        let url = serve_app(&supabase, |_| {}).await;
+
+        let (status, body) = post_analyze(
+            &url,
+            serde_json::json!({"files": [{
+                "filename": "src/lib.rs",
+                "status": "modified",
+                "before_content": "pub fn total(a: u32) -> u32 {\n    a\n}\n",
+                "after_content": "pub fn total(a: u32) -> u32 {\n    a + 1\n}\n",
+            }]}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert!(body["verdict"].is_string());
+        assert!(body["stats"]["high"].is_number());
+        assert_eq!(body["entities"][0]["name"], "total");
+        assert_eq!(body["entities"][0]["file"], "src/lib.rs");
+    }
+
+    #[tokio::test]
+    async fn analyze_takes_a_unified_diff() {
+        let supabase = MockServer::start(supabase()).await;
+        let url = serve_app(&supabase, |_| {}).await;
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n pub fn total(a: u32) -> u32 {\n-    a\n+    a + 1\n }\n";
+
+        let (status, body) = post_analyze(&url, serde_json::json!({"diff": diff})).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(body["total_entities"], 1);
+        assert_eq!(body["entities"][0]["name"], "total");
+    }
+
+    #[tokio::test]
+    async fn analyze_turns_away_oversized_requests() {
+        let supabase = MockServer::start(supabase()).await;
+        let url = serve_app(&supabase, |state| {
+            state.max_analyze_bytes = 1024;
+            state.max_analyze_files = 2;
+        })
+        .await;
+
+        let (status, body) = post_analyze(&url, serde_json::json!({"diff": "+".repeat(2048)})).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{body}");
+
+        let file = serde_json::json!({"filename": "a.rs", "status": "added", "after_content": "fn a() {}"});
+        let (status, body) = post_analyze(&url, serde_json::json!({"files": [file, file, file]})).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(body["error"], "3 files; at most 2 allowed");
+
+        let (status, _) = post_analyze(&url, serde_json::json!({"min_risk": "high"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) = post_analyze(&url, serde_json::json!({"diff": "", "min_risk": "severe"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("severe"), "{body}");
     }
 }
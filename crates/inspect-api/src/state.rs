@@ -1,27 +1,59 @@
-use std::collections::HashMap;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 
 use crate::openai::Finding;
+use crate::queue::ReviewQueue;
+use crate::store::JobStore;
 
 #[derive(Clone)]
 pub struct AppState {
     pub port: u16,
     pub openai_api_key: String,
     pub openai_model: String,
+    /// OpenAI API root, e.g. `https://api.openai.com/v1` (`OPENAI_BASE_URL`)
+    pub openai_base_url: String,
+    /// Per-call limit on OpenAI requests (`OPENAI_TIMEOUT_SECS`)
+    pub openai_timeout: std::time::Duration,
     pub github_token: String,
+    /// GitHub REST API root (`GITHUB_API_URL`/`GH_HOST`)
+    pub github_api_url: String,
     pub http: reqwest::Client,
-    pub jobs: Arc<RwLock<HashMap<String, ReviewJob>>>,
+    pub jobs: Arc<dyn JobStore>,
+    /// Limits how many reviews run at once; the rest wait here
+    pub queue: Arc<ReviewQueue>,
     pub anthropic_api_key: Option<String>,
     pub anthropic_model: String,
     pub supabase_url: String,
     pub supabase_key: String,
+    /// Let callbacks use http and reach loopback, link-local and private
+    /// addresses (`ALLOW_INSECURE_CALLBACKS=1`)
+    pub allow_insecure_callbacks: bool,
+    /// Largest `POST /v1/analyze` body, in bytes (`MAX_ANALYZE_BYTES`)
+    pub max_analyze_bytes: usize,
+    /// Most files one `POST /v1/analyze` may carry (`MAX_ANALYZE_FILES`)
+    pub max_analyze_files: usize,
+    /// Key GitHub signs webhook deliveries with (`GITHUB_WEBHOOK_SECRET`);
+    /// webhooks are refused without one
+    pub github_webhook_secret: Option<String>,
+    /// Review draft PRs on webhook events too (`REVIEW_DRAFTS=1`)
+    pub review_drafts: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl AppState {
+    /// This state with a request's own GitHub token and OpenAI model in place
+    /// of the configured ones, where it gave them.
+    pub fn with_overrides(&self, github_token: Option<String>, model: Option<String>) -> Self {
+        Self {
+            github_token: github_token.unwrap_or_else(|| self.github_token.clone()),
+            openai_model: model.unwrap_or_else(|| self.openai_model.clone()),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum JobStatus {
     Pending,
@@ -31,6 +63,25 @@ pub enum JobStatus {
     Failed,
 }
 
+impl JobStatus {
+    /// The serialized name, as stored in the jobs table.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Analyzing => "analyzing",
+            Self::Reviewing => "reviewing",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Self::Pending, Self::Analyzing, Self::Reviewing, Self::Complete, Self::Failed]
+            .into_iter()
+            .find(|status| status.as_str() == s)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewJob {
     pub id: String,
@@ -44,11 +95,60 @@ pub struct ReviewJob {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Where to POST the job once it completes or fails
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Key for the callback's signature header; never sent back
+    #[serde(skip)]
+    pub callback_secret: Option<String>,
+    /// How the completion callback went, once it was attempted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback: Option<CallbackDelivery>,
+    /// OpenAI model the review runs on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Commit a webhook-triggered review is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_sha: Option<String>,
+    /// Place in line while pending, 1 being next to start; not stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queued_position: Option<usize>,
+}
+
+/// Outcome of POSTing a finished job to its `callback_url`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallbackDelivery {
+    pub delivered: bool,
+    pub attempts: u32,
+    /// Status of the last response, if any came back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    /// Why the last attempt failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub finished_at: DateTime<Utc>,
+}
+
+/// A job without its result, for listings.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobSummary {
+    pub id: String,
+    pub status: JobStatus,
+    pub repo: String,
+    pub pr_number: u64,
+    pub created_at: DateTime<Utc>,
+    /// Set once the job is complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finding_count: Option<usize>,
 }
 
+/// A job's result. The triage is stored as soon as analysis is done, while
+/// the job is still reviewing; the findings follow when the review is.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewResponse {
-    pub findings: Vec<Finding>,
+    /// Missing until the LLM review finishes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings: Option<Vec<Finding>>,
     pub triage: TriageResponse,
     pub timing: TimingInfo,
 }
@@ -64,8 +164,11 @@ pub struct TriageResponse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimingInfo {
     pub triage_ms: u64,
-    pub review_ms: u64,
-    pub total_ms: u64,
+    /// Set with the findings, like `total_ms`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_ms: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent_iterations: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,717 @@
+//! Review job persistence, so jobs survive deploys and crashes.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+
+use crate::state::{CallbackDelivery, JobStatus, JobSummary, ReviewJob, ReviewResponse};
+
+/// Error recorded on jobs that were mid-review when the server went down.
+pub const RESTARTED_ERROR: &str = "server restarted";
+
+/// Which jobs to list, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct JobFilter {
+    pub status: Option<JobStatus>,
+    pub repo: Option<String>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Jobs found by [`JobStore::recover`] at startup.
+#[derive(Debug, Default)]
+pub struct Recovered {
+    /// Accepted but never started
+    pub pending: Vec<ReviewJob>,
+    /// Cut off mid-review, now failed with [`RESTARTED_ERROR`]
+    pub interrupted: Vec<ReviewJob>,
+}
+
+/// One page of [`JobSummary`]s plus how many jobs match in all.
+#[derive(Debug)]
+pub struct JobPage {
+    pub jobs: Vec<JobSummary>,
+    pub total: usize,
+}
+
+/// Where review jobs live. Every status transition is written through, and
+/// reads always come from the store.
+pub trait JobStore: Send + Sync {
+    fn insert(&self, job: &ReviewJob) -> Result<(), String>;
+    /// Insert `job` unless one for the same repo, PR and head commit is
+    /// already stored; returns that one's id if so.
+    fn insert_unless_exists(&self, job: &ReviewJob) -> Result<Option<String>, String>;
+    fn get(&self, id: &str) -> Result<Option<ReviewJob>, String>;
+    fn set_status(&self, id: &str, status: JobStatus) -> Result<(), String>;
+    /// Move a job to reviewing with its result so far: the triage, without
+    /// findings yet.
+    fn set_triage(&self, id: &str, partial: &ReviewResponse) -> Result<(), String>;
+    fn complete(&self, id: &str, result: &ReviewResponse) -> Result<(), String>;
+    fn fail(&self, id: &str, error: &str) -> Result<(), String>;
+    /// Run at startup: fail the jobs a previous process left analyzing or
+    /// reviewing, and return those plus the pending ones, which can be
+    /// started again.
+    fn recover(&self) -> Result<Recovered, String>;
+    fn set_callback(&self, id: &str, delivery: &CallbackDelivery) -> Result<(), String>;
+    fn list(&self, filter: &JobFilter) -> Result<JobPage, String>;
+    /// Remove a job; false if there was none.
+    fn delete(&self, id: &str) -> Result<bool, String>;
+    /// Remove complete and failed jobs created before `cutoff`; returns how
+    /// many went. Jobs still in flight are left alone.
+    fn prune(&self, cutoff: DateTime<Utc>) -> Result<usize, String>;
+}
+
+/// Timestamps are stored in one fixed-width format, so they sort as text.
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// SQLite-backed [`JobStore`]. Writes are single-row statements, so the
+/// connection sits behind a plain mutex.
+pub struct SqliteJobStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteJobStore {
+    /// Open the database at `INSPECT_DB_PATH`, or an in-memory one (lost on
+    /// exit) when it isn't set.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("INSPECT_DB_PATH") {
+            Ok(path) if !path.is_empty() => Self::open(Path::new(&path)),
+            _ => Self::in_memory(),
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("opening {}: {e}", path.display()))?;
+        // Wait out another process's write instead of failing with SQLITE_BUSY
+        conn.busy_timeout(std::time::Duration::from_secs(5)).map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+
+    pub fn in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                repo TEXT NOT NULL,
+                pr_number INTEGER NOT NULL,
+                strategy TEXT,
+                created_at TEXT NOT NULL,
+                result TEXT,
+                error TEXT
+            );
+            CREATE INDEX IF NOT EXISTS jobs_status ON jobs (status);",
+        )
+        .map_err(|e| format!("creating jobs table: {e}"))?;
+        Self::migrate(&conn).map_err(|e| format!("migrating jobs table: {e}"))?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Add the columns newer than the table itself to older databases.
+    fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+        let existing: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('jobs')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for column in ["callback_url", "callback_secret", "callback", "model", "head_sha"] {
+            if !existing.iter().any(|c| c == column) {
+                conn.execute(&format!("ALTER TABLE jobs ADD COLUMN {column} TEXT"), [])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run an UPDATE on one job, failing if it doesn't exist.
+    fn update(&self, id: &str, sql: &str, params: impl rusqlite::Params) -> Result<(), String> {
+        match self.conn().execute(sql, params) {
+            Ok(0) => Err(format!("job {id} not found")),
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+fn conversion(e: String) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+}
+
+fn status_from_row(row: &Row) -> rusqlite::Result<JobStatus> {
+    let status: String = row.get("status")?;
+    JobStatus::parse(&status).ok_or_else(|| conversion(format!("unknown status {status}")))
+}
+
+fn created_at_from_row(row: &Row) -> rusqlite::Result<DateTime<Utc>> {
+    let created_at: String = row.get("created_at")?;
+    Ok(DateTime::parse_from_rfc3339(&created_at)
+        .map_err(|e| conversion(e.to_string()))?
+        .with_timezone(&Utc))
+}
+
+/// Parse an optional JSON column.
+fn json_column<T: serde::de::DeserializeOwned>(row: &Row, column: &str) -> rusqlite::Result<Option<T>> {
+    let json: Option<String> = row.get(column)?;
+    json.map(|json| serde_json::from_str(&json).map_err(|e| conversion(e.to_string())))
+        .transpose()
+}
+
+fn job_from_row(row: &Row) -> rusqlite::Result<ReviewJob> {
+    Ok(ReviewJob {
+        id: row.get("id")?,
+        status: status_from_row(row)?,
+        repo: row.get("repo")?,
+        pr_number: row.get::<_, i64>("pr_number")? as u64,
+        strategy: row.get("strategy")?,
+        result: json_column(row, "result")?,
+        error: row.get("error")?,
+        created_at: created_at_from_row(row)?,
+        callback_url: row.get("callback_url")?,
+        callback_secret: row.get("callback_secret")?,
+        callback: json_column(row, "callback")?,
+        model: row.get("model")?,
+        head_sha: row.get("head_sha")?,
+        queued_position: None,
+    })
+}
+
+fn summary_from_row(row: &Row) -> rusqlite::Result<JobSummary> {
+    Ok(JobSummary {
+        id: row.get("id")?,
+        status: status_from_row(row)?,
+        repo: row.get("repo")?,
+        pr_number: row.get::<_, i64>("pr_number")? as u64,
+        created_at: created_at_from_row(row)?,
+        finding_count: row.get::<_, Option<i64>>("finding_count")?.map(|n| n as usize),
+    })
+}
+
+fn insert_job(conn: &Connection, job: &ReviewJob) -> Result<(), String> {
+    let result = job
+        .result
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let callback = job
+        .callback
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO jobs (id, status, repo, pr_number, strategy, created_at, result, error,
+                           callback_url, callback_secret, callback, model, head_sha)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            job.id,
+            job.status.as_str(),
+            job.repo,
+            job.pr_number as i64,
+            job.strategy,
+            timestamp(job.created_at),
+            result,
+            job.error,
+            job.callback_url,
+            job.callback_secret,
+            callback,
+            job.model,
+            job.head_sha,
+        ],
+    )
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+impl JobStore for SqliteJobStore {
+    fn insert(&self, job: &ReviewJob) -> Result<(), String> {
+        insert_job(&self.conn(), job)
+    }
+
+    fn insert_unless_exists(&self, job: &ReviewJob) -> Result<Option<String>, String> {
+        let conn = self.conn();
+        let existing: Option<String> = conn
+            .query_row(
+                "SELECT id FROM jobs WHERE repo = ?1 AND pr_number = ?2 AND head_sha = ?3",
+                params![job.repo, job.pr_number as i64, job.head_sha],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if existing.is_none() {
+            insert_job(&conn, job)?;
+        }
+        Ok(existing)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<ReviewJob>, String> {
+        self.conn()
+            .query_row("SELECT * FROM jobs WHERE id = ?1", [id], job_from_row)
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    fn set_status(&self, id: &str, status: JobStatus) -> Result<(), String> {
+        self.update(id, "UPDATE jobs SET status = ?2 WHERE id = ?1", params![id, status.as_str()])
+    }
+
+    fn set_triage(&self, id: &str, partial: &ReviewResponse) -> Result<(), String> {
+        let json = serde_json::to_string(partial).map_err(|e| e.to_string())?;
+        self.update(
+            id,
+            "UPDATE jobs SET status = ?2, result = ?3 WHERE id = ?1",
+            params![id, JobStatus::Reviewing.as_str(), json],
+        )
+    }
+
+    fn complete(&self, id: &str, result: &ReviewResponse) -> Result<(), String> {
+        let json = serde_json::to_string(result).map_err(|e| e.to_string())?;
+        self.update(
+            id,
+            "UPDATE jobs SET status = ?2, result = ?3 WHERE id = ?1",
+            params![id, JobStatus::Complete.as_str(), json],
+        )
+    }
+
+    fn fail(&self, id: &str, error: &str) -> Result<(), String> {
+        self.update(
+            id,
+            "UPDATE jobs SET status = ?2, error = ?3 WHERE id = ?1",
+            params![id, JobStatus::Failed.as_str(), error],
+        )
+    }
+
+    fn recover(&self) -> Result<Recovered, String> {
+        let conn = self.conn();
+        let by_status = |statuses: &[JobStatus]| -> rusqlite::Result<Vec<ReviewJob>> {
+            let placeholders = vec!["?"; statuses.len()].join(", ");
+            conn.prepare(&format!(
+                "SELECT * FROM jobs WHERE status IN ({placeholders}) ORDER BY created_at"
+            ))?
+            .query_map(params_from_iter(statuses.iter().map(|s| s.as_str())), job_from_row)?
+            .collect()
+        };
+        let mut interrupted = by_status(&[JobStatus::Analyzing, JobStatus::Reviewing]).map_err(|e| e.to_string())?;
+        for job in &mut interrupted {
+            conn.execute(
+                "UPDATE jobs SET status = ?2, error = ?3 WHERE id = ?1",
+                params![job.id, JobStatus::Failed.as_str(), RESTARTED_ERROR],
+            )
+            .map_err(|e| e.to_string())?;
+            job.status = JobStatus::Failed;
+            job.error = Some(RESTARTED_ERROR.to_string());
+        }
+        let pending = by_status(&[JobStatus::Pending]).map_err(|e| e.to_string())?;
+        Ok(Recovered { pending, interrupted })
+    }
+
+    fn set_callback(&self, id: &str, delivery: &CallbackDelivery) -> Result<(), String> {
+        let json = serde_json::to_string(delivery).map_err(|e| e.to_string())?;
+        self.update(id, "UPDATE jobs SET callback = ?2 WHERE id = ?1", params![id, json])
+    }
+
+    fn list(&self, filter: &JobFilter) -> Result<JobPage, String> {
+        let mut clauses = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        if let Some(status) = filter.status {
+            clauses.push("status = ?");
+            values.push(status.as_str().to_string().into());
+        }
+        if let Some(ref repo) = filter.repo {
+            clauses.push("repo = ?");
+            values.push(repo.clone().into());
+        }
+        let filter_sql = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+
+        let conn = self.conn();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM jobs{filter_sql}"),
+                params_from_iter(&values),
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        // id breaks created_at ties, so pages never overlap or skip
+        let sql = format!(
+            "SELECT id, status, repo, pr_number, created_at,
+                    json_array_length(result, '$.findings') AS finding_count
+             FROM jobs{filter_sql}
+             ORDER BY created_at DESC, id DESC
+             LIMIT ? OFFSET ?"
+        );
+        values.push((filter.limit as i64).into());
+        values.push((filter.offset as i64).into());
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let jobs = stmt
+            .query_map(params_from_iter(&values), summary_from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(|e| e.to_string())?;
+        Ok(JobPage {
+            jobs,
+            total: total as usize,
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, String> {
+        self.conn()
+            .execute("DELETE FROM jobs WHERE id = ?1", [id])
+            .map(|n| n > 0)
+            .map_err(|e| e.to_string())
+    }
+
+    fn prune(&self, cutoff: DateTime<Utc>) -> Result<usize, String> {
+        self.conn()
+            .execute(
+                "DELETE FROM jobs WHERE created_at < ?1 AND status IN (?2, ?3)",
+                params![
+                    timestamp(cutoff),
+                    JobStatus::Complete.as_str(),
+                    JobStatus::Failed.as_str(),
+                ],
+            )
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{TimingInfo, TriageResponse};
+    use std::sync::Arc;
+
+    fn job(id: &str, status: JobStatus) -> ReviewJob {
+        ReviewJob {
+            id: id.into(),
+            status,
+            repo: "owner/repo".into(),
+            pr_number: 7,
+            strategy: None,
+            result: None,
+            error: None,
+            created_at: Utc::now(),
+            callback_url: None,
+            callback_secret: None,
+            callback: None,
+            model: None,
+            head_sha: None,
+            queued_position: None,
+        }
+    }
+
+    /// A job created `hours` before now.
+    fn aged(id: &str, status: JobStatus, hours: i64) -> ReviewJob {
+        ReviewJob {
+            created_at: Utc::now() - chrono::Duration::hours(hours),
+            ..job(id, status)
+        }
+    }
+
+    fn ids(page: &JobPage) -> Vec<&str> {
+        page.jobs.iter().map(|j| j.id.as_str()).collect()
+    }
+
+    fn response() -> ReviewResponse {
+        ReviewResponse {
+            findings: Some(vec![]),
+            triage: TriageResponse {
+                verdict: "standard_review".into(),
+                total_entities: 2,
+                entities: vec![],
+                stats: serde_json::json!({}),
+            },
+            timing: TimingInfo {
+                triage_ms: 1,
+                review_ms: Some(2),
+                total_ms: Some(3),
+                agent_iterations: None,
+                agent_tool_calls: None,
+            },
+        }
+    }
+
+    #[test]
+    fn transitions_are_written_through() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        store.insert(&job("a", JobStatus::Pending)).unwrap();
+        store.set_status("a", JobStatus::Analyzing).unwrap();
+        assert_eq!(store.get("a").unwrap().unwrap().status, JobStatus::Analyzing);
+
+        let partial = ReviewResponse {
+            findings: None,
+            ..response()
+        };
+        store.set_triage("a", &partial).unwrap();
+        let reviewing = store.get("a").unwrap().unwrap();
+        assert_eq!(reviewing.status, JobStatus::Reviewing);
+        assert!(reviewing.result.unwrap().findings.is_none());
+
+        store.complete("a", &response()).unwrap();
+        let done = store.get("a").unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Complete);
+        assert_eq!(done.result.unwrap().triage.total_entities, 2);
+
+        assert!(store.get("missing").unwrap().is_none());
+        assert!(store.fail("missing", "boom").is_err());
+    }
+
+    #[test]
+    fn restart_fails_interrupted_jobs_and_keeps_the_rest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("jobs.db");
+        {
+            let store = SqliteJobStore::open(&path).unwrap();
+            for (id, status) in [
+                ("pending", JobStatus::Pending),
+                ("analyzing", JobStatus::Analyzing),
+                ("reviewing", JobStatus::Reviewing),
+                ("done", JobStatus::Pending),
+                ("failed", JobStatus::Pending),
+            ] {
+                store.insert(&job(id, status)).unwrap();
+            }
+            store.complete("done", &response()).unwrap();
+            store.fail("failed", "Failed to fetch PR").unwrap();
+        }
+
+        let store = SqliteJobStore::open(&path).unwrap();
+        let recovered = store.recover().unwrap();
+        let ids = |jobs: &[ReviewJob]| jobs.iter().map(|j| j.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&recovered.pending), ["pending"]);
+        assert_eq!(ids(&recovered.interrupted), ["analyzing", "reviewing"]);
+        assert!(recovered.interrupted.iter().all(|j| j.status == JobStatus::Failed));
+        for id in ["analyzing", "reviewing"] {
+            let job = store.get(id).unwrap().unwrap();
+            assert_eq!(job.status, JobStatus::Failed);
+            assert_eq!(job.error.as_deref(), Some(RESTARTED_ERROR));
+        }
+        assert!(store.get("done").unwrap().unwrap().result.is_some());
+        assert_eq!(store.get("failed").unwrap().unwrap().error.as_deref(), Some("Failed to fetch PR"));
+    }
+
+    #[test]
+    fn concurrent_writes_all_land() {
+        let store = Arc::new(SqliteJobStore::in_memory().unwrap());
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let id = format!("{t}-{i}");
+                        store.insert(&job(&id, JobStatus::Pending)).unwrap();
+                        store.set_status(&id, JobStatus::Analyzing).unwrap();
+                        if i % 2 == 0 {
+                            store.complete(&id, &response()).unwrap();
+                        } else {
+                            store.fail(&id, "nope").unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        for t in 0..8 {
+            for i in 0..25 {
+                let job = store.get(&format!("{t}-{i}")).unwrap().unwrap();
+                let expected = if i % 2 == 0 { JobStatus::Complete } else { JobStatus::Failed };
+                assert_eq!(job.status, expected);
+            }
+        }
+        let recovered = store.recover().unwrap();
+        assert!(recovered.pending.is_empty() && recovered.interrupted.is_empty());
+    }
+
+    #[test]
+    fn pages_are_newest_first_and_stop_at_the_end() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        for i in 0..5 {
+            store.insert(&aged(&format!("job{i}"), JobStatus::Pending, i)).unwrap();
+        }
+        let page = |limit, offset| {
+            store
+                .list(&JobFilter {
+                    limit,
+                    offset,
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+
+        let first = page(2, 0);
+        assert_eq!(ids(&first), ["job0", "job1"]);
+        assert_eq!(first.total, 5);
+        assert_eq!(ids(&page(2, 2)), ["job2", "job3"]);
+        assert_eq!(ids(&page(2, 4)), ["job4"]);
+        assert!(page(2, 5).jobs.is_empty());
+        assert_eq!(page(5, 0).jobs.len(), 5);
+        assert!(page(0, 0).jobs.is_empty());
+    }
+
+    #[test]
+    fn list_filters_by_status_and_repo() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        store.insert(&aged("old", JobStatus::Pending, 2)).unwrap();
+        store.insert(&aged("new", JobStatus::Pending, 1)).unwrap();
+        store.insert(&ReviewJob {
+            repo: "other/repo".into(),
+            ..job("elsewhere", JobStatus::Pending)
+        })
+        .unwrap();
+        store.complete("old", &response()).unwrap();
+        store.complete("elsewhere", &response()).unwrap();
+
+        let complete = store
+            .list(&JobFilter {
+                status: Some(JobStatus::Complete),
+                limit: 50,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(ids(&complete), ["elsewhere", "old"]);
+        assert_eq!(complete.jobs[0].finding_count, Some(0));
+
+        let here = store
+            .list(&JobFilter {
+                repo: Some("owner/repo".into()),
+                limit: 50,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(ids(&here), ["new", "old"]);
+        assert_eq!(here.jobs[0].finding_count, None, "no result yet");
+
+        let both = store
+            .list(&JobFilter {
+                status: Some(JobStatus::Complete),
+                repo: Some("owner/repo".into()),
+                limit: 50,
+                offset: 0,
+            })
+            .unwrap();
+        assert_eq!((ids(&both), both.total), (vec!["old"], 1));
+    }
+
+    #[test]
+    fn prune_removes_only_expired_finished_jobs() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        for (id, hours) in [("expired-done", 100), ("expired-failed", 80), ("fresh-done", 10)] {
+            store.insert(&aged(id, JobStatus::Pending, hours)).unwrap();
+        }
+        store.complete("expired-done", &response()).unwrap();
+        store.fail("expired-failed", "nope").unwrap();
+        store.complete("fresh-done", &response()).unwrap();
+        store.insert(&aged("expired-running", JobStatus::Reviewing, 100)).unwrap();
+
+        let pruned = store.prune(Utc::now() - chrono::Duration::hours(72)).unwrap();
+        assert_eq!(pruned, 2);
+        assert!(store.get("expired-done").unwrap().is_none());
+        assert!(store.get("expired-failed").unwrap().is_none());
+        assert!(store.get("fresh-done").unwrap().is_some());
+        assert!(store.get("expired-running").unwrap().is_some());
+    }
+
+    #[test]
+    fn delete_reports_whether_the_job_existed() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        store.insert(&job("a", JobStatus::Pending)).unwrap();
+        assert!(store.delete("a").unwrap());
+        assert!(store.get("a").unwrap().is_none());
+        assert!(!store.delete("a").unwrap());
+    }
+
+    #[test]
+    fn callback_settings_and_delivery_persist() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        store
+            .insert(&ReviewJob {
+                callback_url: Some("https://bot.example.com/hook".into()),
+                callback_secret: Some("s3cret".into()),
+                ..job("a", JobStatus::Pending)
+            })
+            .unwrap();
+        let delivery = CallbackDelivery {
+            delivered: false,
+            attempts: 4,
+            status_code: Some(503),
+            error: Some("HTTP 503".into()),
+            finished_at: Utc::now(),
+        };
+        store.set_callback("a", &delivery).unwrap();
+
+        let job = store.get("a").unwrap().unwrap();
+        assert_eq!(job.callback_secret.as_deref(), Some("s3cret"));
+        assert_eq!(job.callback, Some(delivery));
+        let json = serde_json::to_value(&job).unwrap();
+        assert_eq!(json["callback_url"], "https://bot.example.com/hook");
+        assert!(json.get("callback_secret").is_none(), "the secret is never served");
+    }
+
+    #[test]
+    fn older_databases_gain_the_callback_columns() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("jobs.db");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE jobs (id TEXT PRIMARY KEY, status TEXT NOT NULL, repo TEXT NOT NULL,
+                 pr_number INTEGER NOT NULL, strategy TEXT, created_at TEXT NOT NULL, result TEXT, error TEXT);
+                 INSERT INTO jobs VALUES ('old', 'complete', 'owner/repo', 1, NULL, '2026-01-01T00:00:00.000000Z', NULL, NULL);",
+            )
+            .unwrap();
+
+        let store = SqliteJobStore::open(&path).unwrap();
+        let old = store.get("old").unwrap().unwrap();
+        assert_eq!(old.status, JobStatus::Complete);
+        assert!(old.callback_url.is_none());
+        assert!(old.model.is_none());
+        store.insert(&job("new", JobStatus::Pending)).unwrap();
+    }
+
+    #[test]
+    fn jobs_for_a_reviewed_head_are_not_inserted_twice() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        let at = |id: &str, pr_number, sha: &str| ReviewJob {
+            pr_number,
+            head_sha: Some(sha.into()),
+            ..job(id, JobStatus::Pending)
+        };
+        assert_eq!(store.insert_unless_exists(&at("a", 7, "abc")).unwrap(), None);
+        assert_eq!(store.insert_unless_exists(&at("b", 7, "abc")).unwrap().as_deref(), Some("a"));
+        assert_eq!(store.insert_unless_exists(&at("c", 7, "def")).unwrap(), None);
+        assert_eq!(store.insert_unless_exists(&at("d", 8, "abc")).unwrap(), None);
+        assert!(store.get("b").unwrap().is_none());
+        assert_eq!(store.get("c").unwrap().unwrap().head_sha.as_deref(), Some("def"));
+    }
+
+    #[test]
+    fn the_chosen_model_is_recorded() {
+        let store = SqliteJobStore::in_memory().unwrap();
+        store
+            .insert(&ReviewJob {
+                model: Some("gpt-4o-mini".into()),
+                ..job("a", JobStatus::Pending)
+            })
+            .unwrap();
+        let job = store.get("a").unwrap().unwrap();
+        assert_eq!(job.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(serde_json::to_value(&job).unwrap()["model"], "gpt-4o-mini");
+    }
+}
@@ -1,54 +1,174 @@
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::io::IsTerminal;
 use std::process::Command;
+use std::time::Instant;
 
-use clap::Args;
+use clap::{Args, ValueEnum};
 use sem_core::git::types::DiffScope;
 
-use inspect_core::analyze::analyze;
-use inspect_core::types::RiskLevel;
+use inspect_core::analyze::{analyze, analyze_fast, AnalyzeOptions};
+use inspect_core::filter::filter_paths;
+use inspect_core::search::FileFilter;
+use inspect_core::types::{RiskLevel, Timing};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use super::GATE_FAILED;
+use crate::output::{self, progress};
 
 #[derive(Args)]
 pub struct BenchArgs {
     /// Repository path
-    #[arg(long, default_value = ".")]
+    #[arg(short = 'C', long, default_value = ".")]
     pub repo: PathBuf,
 
     /// Maximum number of commits to analyze
     #[arg(long, default_value = "100")]
     pub limit: usize,
+
+    /// Skip the entity graph: diff, classify and group by file only. Risk and
+    /// blast-radius metrics come out null
+    #[arg(long)]
+    pub fast: bool,
+
+    /// Only commits after this date (anything `git log --since` accepts)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Only commits before this date
+    #[arg(long)]
+    pub until: Option<String>,
+
+    /// Only commits whose author matches this pattern (name or email)
+    #[arg(long)]
+    pub author: Option<String>,
+
+    /// Only commits touching files matching this glob, e.g. 'src/**'; entities
+    /// outside the globs are left out of the metrics (repeatable)
+    #[arg(long = "path", value_name = "GLOB")]
+    pub paths: Vec<String>,
+
+    /// Skip merge commits
+    #[arg(long)]
+    pub no_merges: bool,
+
+    /// Compare against an earlier run's JSON output, matching commits by SHA
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+
+    /// With --baseline, exit with code 2 if a metric moves by more than this
+    /// fraction of its baseline value
+    #[arg(long, default_value = "0.05", requires = "baseline")]
+    pub max_delta: f64,
+
+    /// Progress on stderr: a status line with an ETA, or one JSON line per
+    /// analyzed commit
+    #[arg(long, value_enum, default_value = "text")]
+    pub progress: ProgressMode,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    Text,
+    Json,
+}
+
+/// Which commits a run covers, recorded in the output so it can be repeated.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitFilter {
+    pub limit: usize,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub author: Option<String>,
+    pub paths: Vec<String>,
+    pub no_merges: bool,
+}
+
+impl CommitFilter {
+    fn git_log_args(&self) -> Vec<String> {
+        let mut args = vec!["log".to_string(), "--format=%H %s".to_string(), format!("-{}", self.limit)];
+        if let Some(ref since) = self.since {
+            args.push(format!("--since={}", since));
+        }
+        if let Some(ref until) = self.until {
+            args.push(format!("--until={}", until));
+        }
+        if let Some(ref author) = self.author {
+            args.push(format!("--author={}", author));
+        }
+        if self.no_merges {
+            args.push("--no-merges".to_string());
+        }
+        if !self.paths.is_empty() {
+            args.push("--".to_string());
+            args.extend(self.paths.iter().map(|p| format!(":(glob){}", p)));
+        }
+        args
+    }
+}
+
+/// `(sha, subject)` of the commits `filter` selects, newest first.
+fn list_commits(repo: &Path, filter: &CommitFilter) -> Result<Vec<(String, String)>, String> {
+    let output = Command::new("git")
+        .args(filter.git_log_args())
+        .current_dir(repo)
+        .output()
+        .map_err(|e| format!("failed to run git log: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("git log failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (sha, msg) = line.split_once(' ').unwrap_or((line, ""));
+            (!sha.is_empty()).then(|| (sha.to_string(), msg.to_string()))
+        })
+        .collect())
+}
+
+// Fields added after the first release default, so older runs still load
+// as baselines
+#[derive(Debug, Serialize, Deserialize)]
 pub struct BenchmarkResult {
     pub repo: String,
+    /// "full", or "fast" for --fast
+    #[serde(default)]
+    pub mode: String,
+    #[serde(default)]
+    pub filter: CommitFilter,
     pub total_commits: usize,
     pub analyzed_commits: usize,
     pub total_entities_reviewed: usize,
+    #[serde(default)]
+    pub wall_ms: u64,
     // Noise reduction
     pub cosmetic_ratio: f64,
     pub noise_reduction: f64,
     pub avg_entities_per_file: f64,
-    // Risk distribution
-    pub risk_distribution: RiskDistribution,
-    pub avg_blast_radius: f64,
-    pub max_blast_radius: usize,
+    // Risk distribution; null in fast mode, where scores lack the graph
+    pub risk_distribution: Option<RiskDistribution>,
+    pub avg_blast_radius: Option<f64>,
+    pub max_blast_radius: Option<usize>,
     // Grouping
     pub avg_groups_per_commit: f64,
     pub tangled_commit_ratio: f64,
     // Entity vs file comparison
     pub avg_files_per_commit: f64,
     pub avg_entities_per_commit: f64,
-    pub high_critical_ratio: f64,
+    pub high_critical_ratio: Option<f64>,
     // Cross-file impact
-    pub cross_file_impact_ratio: f64,
+    pub cross_file_impact_ratio: Option<f64>,
     // Per-commit detail
     pub commits: Vec<CommitBenchmark>,
+    #[serde(default)]
+    pub distributions: Distributions,
+    /// Set with --baseline
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<Comparison>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RiskDistribution {
     pub critical: usize,
     pub high: usize,
@@ -56,7 +176,7 @@ pub struct RiskDistribution {
     pub low: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CommitBenchmark {
     pub sha: String,
     pub message: String,
@@ -64,9 +184,256 @@ pub struct CommitBenchmark {
     pub file_count: usize,
     pub group_count: usize,
     pub cosmetic_count: usize,
-    pub high_critical_count: usize,
-    pub max_blast_radius: usize,
-    pub cross_file_impacts: usize,
+    pub high_critical_count: Option<usize>,
+    pub max_blast_radius: Option<usize>,
+    pub cross_file_impacts: Option<usize>,
+    #[serde(default)]
+    pub wall_ms: u64,
+    #[serde(default)]
+    pub timing: CommitTiming,
+}
+
+/// Where a commit's analysis time went, from [`Timing`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitTiming {
+    pub diff_ms: u64,
+    /// Listing source files plus building (or finding the cached) entity graph
+    pub graph_ms: u64,
+    pub scoring_ms: u64,
+    pub total_ms: u64,
+}
+
+impl From<&Timing> for CommitTiming {
+    fn from(t: &Timing) -> Self {
+        Self {
+            diff_ms: t.diff_ms,
+            graph_ms: t.list_files_ms + t.graph_build_ms + t.graph_lookup_ms,
+            scoring_ms: t.scoring_ms,
+            total_ms: t.total_ms,
+        }
+    }
+}
+
+/// Time left for `total - done` commits at the average pace so far.
+fn eta_ms(elapsed_ms: u64, done: usize, total: usize) -> Option<u64> {
+    (done > 0).then(|| elapsed_ms / done as u64 * total.saturating_sub(done) as u64)
+}
+
+fn format_eta(ms: u64) -> String {
+    let secs = ms.div_ceil(1000);
+    if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// One `--progress json` line for a commit that was just analyzed.
+fn progress_line(commit: &CommitBenchmark, done: usize, total: usize, eta_ms: Option<u64>) -> String {
+    serde_json::json!({
+        "sha": commit.sha,
+        "done": done,
+        "total": total,
+        "entity_count": commit.entity_count,
+        "total_ms": commit.timing.total_ms,
+        "eta_ms": eta_ms,
+    })
+    .to_string()
+}
+
+/// Spread of the per-entity and per-commit metrics that averages flatten.
+/// The graph-dependent ones are null in fast mode.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Distributions {
+    pub blast_radius: Option<Percentiles>,
+    pub entities_per_commit: Percentiles,
+    pub groups_per_commit: Percentiles,
+    pub risk_score: Option<Percentiles>,
+    pub risk_score_histogram: Option<Vec<HistogramBucket>>,
+}
+
+/// Nearest-rank percentiles of a set of values. All zero, with a count of
+/// 0, when there are no values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub count: usize,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl From<Vec<f64>> for Percentiles {
+    fn from(mut values: Vec<f64>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(f64::total_cmp);
+        // The smallest value with at least p% of the values at or below it
+        let rank = |p: f64| {
+            let rank = (p / 100.0 * values.len() as f64).ceil() as usize;
+            values[rank.clamp(1, values.len()) - 1]
+        };
+        Self {
+            count: values.len(),
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// Risk scores in `[lower, upper)`; the last bucket includes 1.0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+}
+
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// Counts of risk scores (0.0 to 1.0) in ten equal-width buckets.
+fn risk_histogram(scores: &[f64]) -> Vec<HistogramBucket> {
+    let mut counts = [0usize; HISTOGRAM_BUCKETS];
+    for &score in scores {
+        let bucket = (score.clamp(0.0, 1.0) * HISTOGRAM_BUCKETS as f64) as usize;
+        counts[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| HistogramBucket {
+            lower: i as f64 / HISTOGRAM_BUCKETS as f64,
+            upper: (i + 1) as f64 / HISTOGRAM_BUCKETS as f64,
+            count,
+        })
+        .collect()
+}
+
+/// How a run differs from a baseline run.
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub max_delta: f64,
+    pub matched_commits: usize,
+    pub metrics: Vec<MetricDelta>,
+    /// Matched commits whose entity, group or high/critical count changed
+    pub commits: Vec<CommitDelta>,
+    /// Metrics that moved by more than `max_delta`
+    pub exceeded: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricDelta {
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    pub delta: f64,
+    /// Null when the baseline is 0
+    pub pct_delta: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommitDelta {
+    pub sha: String,
+    pub message: String,
+    pub entity_count: (usize, usize),
+    pub group_count: (usize, usize),
+    /// Null when either run was --fast
+    pub high_critical_count: Option<(usize, usize)>,
+    /// The high/critical count changed
+    pub flagged: bool,
+}
+
+impl MetricDelta {
+    fn new(metric: &'static str, baseline: f64, current: f64) -> Self {
+        Self {
+            metric,
+            baseline,
+            current,
+            delta: current - baseline,
+            pct_delta: (baseline != 0.0).then(|| (current - baseline) / baseline.abs() * 100.0),
+        }
+    }
+
+    /// Whether the change is more than `max_delta` of the baseline value;
+    /// any change from a baseline of 0 counts.
+    fn exceeds(&self, max_delta: f64) -> bool {
+        match self.pct_delta {
+            Some(pct) => pct.abs() > max_delta * 100.0,
+            None => self.current != 0.0,
+        }
+    }
+}
+
+/// Compare `current` with `baseline`: aggregate metrics, and per-commit
+/// counts for commits both runs analyzed. Metrics only one run computed
+/// (see --fast) are left out.
+pub fn compare(baseline: &BenchmarkResult, current: &BenchmarkResult, max_delta: f64) -> Comparison {
+    let share = |r: &BenchmarkResult, count: fn(&RiskDistribution) -> usize| {
+        let dist = r.risk_distribution.as_ref()?;
+        Some(if r.total_entities_reviewed > 0 {
+            count(dist) as f64 / r.total_entities_reviewed as f64
+        } else {
+            0.0
+        })
+    };
+    let pairs: [(&'static str, Option<f64>, Option<f64>); 8] = [
+        ("critical_share", share(baseline, |d| d.critical), share(current, |d| d.critical)),
+        ("high_share", share(baseline, |d| d.high), share(current, |d| d.high)),
+        ("medium_share", share(baseline, |d| d.medium), share(current, |d| d.medium)),
+        ("low_share", share(baseline, |d| d.low), share(current, |d| d.low)),
+        ("cosmetic_ratio", Some(baseline.cosmetic_ratio), Some(current.cosmetic_ratio)),
+        ("avg_blast_radius", baseline.avg_blast_radius, current.avg_blast_radius),
+        ("avg_groups_per_commit", Some(baseline.avg_groups_per_commit), Some(current.avg_groups_per_commit)),
+        ("avg_entities_per_commit", Some(baseline.avg_entities_per_commit), Some(current.avg_entities_per_commit)),
+    ];
+    let metrics: Vec<MetricDelta> = pairs
+        .into_iter()
+        .filter_map(|(metric, base, cur)| Some(MetricDelta::new(metric, base?, cur?)))
+        .collect();
+    let exceeded = metrics
+        .iter()
+        .filter(|m| m.exceeds(max_delta))
+        .map(|m| m.metric.to_string())
+        .collect();
+
+    let base_commits: HashMap<&str, &CommitBenchmark> =
+        baseline.commits.iter().map(|c| (c.sha.as_str(), c)).collect();
+    let mut matched_commits = 0;
+    let mut commits = Vec::new();
+    for cur in &current.commits {
+        let Some(base) = base_commits.get(cur.sha.as_str()) else {
+            continue;
+        };
+        matched_commits += 1;
+        let high_critical_count = base.high_critical_count.zip(cur.high_critical_count);
+        let flagged = high_critical_count.is_some_and(|(b, c)| b != c);
+        if flagged || base.entity_count != cur.entity_count || base.group_count != cur.group_count {
+            commits.push(CommitDelta {
+                sha: cur.sha.clone(),
+                message: cur.message.clone(),
+                entity_count: (base.entity_count, cur.entity_count),
+                group_count: (base.group_count, cur.group_count),
+                high_critical_count,
+                flagged,
+            });
+        }
+    }
+
+    Comparison {
+        max_delta,
+        matched_commits,
+        metrics,
+        commits,
+        exceeded,
+    }
+}
+
+fn load_baseline(path: &Path) -> Result<BenchmarkResult, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{} is not inspect bench output: {}", path.display(), e))
 }
 
 pub fn run(args: BenchArgs) {
@@ -76,30 +443,47 @@ pub fn run(args: BenchArgs) {
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| repo.display().to_string());
 
-    eprintln!("inspect bench: analyzing {} (limit: {})", repo.display(), args.limit);
+    let baseline = args.baseline.as_deref().map(|path| match load_baseline(path) {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    });
 
-    // Get commit SHAs
-    let output = Command::new("git")
-        .args(["log", "--format=%H %s", &format!("-{}", args.limit)])
-        .current_dir(&repo)
-        .output()
-        .expect("failed to run git log");
+    let mode = if args.fast { "fast" } else { "full" };
+    progress!("inspect bench: analyzing {} (limit: {}, {} mode)", repo.display(), args.limit, mode);
+    let bench_start = Instant::now();
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let commits_info: Vec<(&str, &str)> = stdout
-        .lines()
-        .filter_map(|line| {
-            let (sha, msg) = line.split_once(' ')?;
-            Some((sha, msg))
-        })
-        .collect();
+    let filter = CommitFilter {
+        limit: args.limit,
+        since: args.since.clone(),
+        until: args.until.clone(),
+        author: args.author.clone(),
+        paths: args.paths.clone(),
+        no_merges: args.no_merges,
+    };
+    let files = match FileFilter::new(&filter.paths, &[]) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: invalid path glob: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let commits_info = match list_commits(&repo, &filter) {
+        Ok(commits) => commits,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     if commits_info.is_empty() {
         eprintln!("no commits found");
         return;
     }
 
-    eprintln!("found {} commits", commits_info.len());
+    progress!("found {} commits", commits_info.len());
 
     let mut commit_benchmarks: Vec<CommitBenchmark> = Vec::new();
     let mut total_entities = 0usize;
@@ -117,16 +501,50 @@ pub fn run(args: BenchArgs) {
         low: 0,
     };
     let mut tangled_commits = 0usize;
+    let mut blast_radii: Vec<f64> = Vec::new();
+    let mut risk_scores: Vec<f64> = Vec::new();
 
+    // Metrics that need the entity graph aren't computed in fast mode
+    let full = !args.fast;
+
+    let total = commits_info.len();
+    let loop_start = Instant::now();
+    // Redraw one status line on a terminal; elsewhere (CI logs) print plain lines
+    let redraw = std::io::stderr().is_terminal();
     for (i, (sha, msg)) in commits_info.iter().enumerate() {
-        eprint!("\r  [{}/{}] {:.50}", i + 1, commits_info.len(), msg);
+        if args.progress == ProgressMode::Text && !output::quiet() {
+            let eta = eta_ms(loop_start.elapsed().as_millis() as u64, i, total)
+                .map(|ms| format!(" eta {}", format_eta(ms)))
+                .unwrap_or_default();
+            if redraw {
+                eprint!("\r\x1b[K  [{}/{}]{} {:.50}", i + 1, total, eta, msg);
+            } else {
+                eprintln!("  [{}/{}]{} {:.50}", i + 1, total, eta, msg);
+            }
+        }
 
         let scope = DiffScope::Commit {
-            sha: sha.to_string(),
+            sha: sha.clone(),
+        };
+
+        let commit_start = Instant::now();
+        let analyzed = if args.fast {
+            // Group counts matter here, so keep same-file entities together
+            let options = AnalyzeOptions {
+                group_by_file: true,
+                ..Default::default()
+            };
+            analyze_fast(&repo, scope, &options)
+        } else {
+            analyze(&repo, scope)
         };
+        let wall_ms = commit_start.elapsed().as_millis() as u64;
 
-        match analyze(&repo, scope) {
-            Ok(result) => {
+        match analyzed {
+            Ok(mut result) => {
+                if !files.is_empty() {
+                    filter_paths(&mut result, &files);
+                }
                 if result.entity_reviews.is_empty() {
                     continue;
                 }
@@ -182,6 +600,9 @@ pub fn run(args: BenchArgs) {
                     tangled_commits += 1;
                 }
 
+                blast_radii.extend(result.entity_reviews.iter().map(|r| r.blast_radius as f64));
+                risk_scores.extend(result.entity_reviews.iter().map(|r| r.risk_score));
+
                 total_entities += entity_count;
                 total_cosmetic += cosmetic_count;
                 total_files += file_count;
@@ -194,16 +615,23 @@ pub fn run(args: BenchArgs) {
                 total_cross_file += cross_file_count;
 
                 commit_benchmarks.push(CommitBenchmark {
-                    sha: sha.to_string(),
-                    message: msg.to_string(),
+                    sha: sha.clone(),
+                    message: msg.clone(),
                     entity_count,
                     file_count,
                     group_count,
                     cosmetic_count,
-                    high_critical_count: hc_count,
-                    max_blast_radius: commit_max_blast,
-                    cross_file_impacts: cross_file_count,
+                    high_critical_count: full.then_some(hc_count),
+                    max_blast_radius: full.then_some(commit_max_blast),
+                    cross_file_impacts: full.then_some(cross_file_count),
+                    wall_ms,
+                    timing: CommitTiming::from(&result.timing),
                 });
+                if args.progress == ProgressMode::Json && !output::quiet() {
+                    let eta = eta_ms(loop_start.elapsed().as_millis() as u64, i + 1, total);
+                    let commit = commit_benchmarks.last().expect("just pushed");
+                    eprintln!("{}", progress_line(commit, i + 1, total, eta));
+                }
             }
             Err(_) => {
                 // Skip commits that fail (e.g. initial commit with no parent)
@@ -211,7 +639,12 @@ pub fn run(args: BenchArgs) {
         }
     }
 
-    eprintln!("\r  done.                                        ");
+    if args.progress == ProgressMode::Text && !output::quiet() {
+        if redraw {
+            eprint!("\r\x1b[K");
+        }
+        eprintln!("  done.");
+    }
 
     let analyzed = commit_benchmarks.len();
     let cosmetic_ratio = if total_entities > 0 {
@@ -278,26 +711,324 @@ pub fn run(args: BenchArgs) {
         0.0
     };
 
-    let result = BenchmarkResult {
+    let mut result = BenchmarkResult {
         repo: repo_name,
+        mode: mode.to_string(),
+        filter,
         total_commits: commits_info.len(),
         analyzed_commits: analyzed,
         total_entities_reviewed: total_entities,
+        wall_ms: bench_start.elapsed().as_millis() as u64,
         cosmetic_ratio,
         noise_reduction,
         avg_entities_per_file,
-        risk_distribution: risk_dist,
-        avg_blast_radius,
-        max_blast_radius,
+        risk_distribution: full.then_some(risk_dist),
+        avg_blast_radius: full.then_some(avg_blast_radius),
+        max_blast_radius: full.then_some(max_blast_radius),
         avg_groups_per_commit,
         tangled_commit_ratio,
         avg_files_per_commit,
         avg_entities_per_commit,
-        high_critical_ratio,
-        cross_file_impact_ratio,
+        high_critical_ratio: full.then_some(high_critical_ratio),
+        cross_file_impact_ratio: full.then_some(cross_file_impact_ratio),
+        distributions: Distributions {
+            blast_radius: full.then(|| Percentiles::from(blast_radii)),
+            entities_per_commit: Percentiles::from(
+                commit_benchmarks.iter().map(|c| c.entity_count as f64).collect::<Vec<_>>(),
+            ),
+            groups_per_commit: Percentiles::from(
+                commit_benchmarks.iter().map(|c| c.group_count as f64).collect::<Vec<_>>(),
+            ),
+            risk_score_histogram: full.then(|| risk_histogram(&risk_scores)),
+            risk_score: full.then(|| Percentiles::from(risk_scores)),
+        },
         commits: commit_benchmarks,
+        comparison: None,
     };
+    if let Some(ref baseline) = baseline {
+        result.comparison = Some(compare(baseline, &result, args.max_delta));
+    }
 
     let json = serde_json::to_string_pretty(&result).expect("failed to serialize");
     println!("{}", json);
+
+    if let Some(ref comparison) = result.comparison {
+        if !comparison.exceeded.is_empty() {
+            eprintln!(
+                "fail: {} moved by more than {}% from the baseline",
+                comparison.exceeded.join(", "),
+                comparison.max_delta * 100.0
+            );
+            output::exit(GATE_FAILED);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str], env: &[(&str, &str)]) {
+        let out = Command::new("git").args(args).envs(env.iter().copied()).current_dir(dir).output().unwrap();
+        assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    }
+
+    fn commit_as(dir: &Path, author: &str, date: &str, file: &str, msg: &str) {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, msg).unwrap();
+        git(dir, &["add", "-A"], &[]);
+        let email = format!("{}@example.com", author.to_lowercase());
+        let env = [
+            ("GIT_AUTHOR_NAME", author),
+            ("GIT_AUTHOR_EMAIL", email.as_str()),
+            ("GIT_AUTHOR_DATE", date),
+            ("GIT_COMMITTER_DATE", date),
+        ];
+        git(dir, &["commit", "-q", "-m", msg], &env);
+    }
+
+    /// Alice works in src/, Bob in docs/, and Bob's branch is merged last.
+    fn two_author_repo() -> TempDir {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        git(dir, &["init", "-q", "-b", "main"], &[]);
+        git(dir, &["config", "user.name", "Test"], &[]);
+        git(dir, &["config", "user.email", "test@example.com"], &[]);
+        commit_as(dir, "Alice", "2024-01-10T12:00:00Z", "src/a.rs", "alice one");
+        git(dir, &["checkout", "-q", "-b", "docs"], &[]);
+        commit_as(dir, "Bob", "2024-02-10T12:00:00Z", "docs/guide.md", "bob one");
+        git(dir, &["checkout", "-q", "main"], &[]);
+        commit_as(dir, "Alice", "2024-03-10T12:00:00Z", "src/nested/b.rs", "alice two");
+        let date = [("GIT_AUTHOR_DATE", "2024-04-10T12:00:00Z"), ("GIT_COMMITTER_DATE", "2024-04-10T12:00:00Z")];
+        git(dir, &["merge", "-q", "--no-ff", "-m", "merge docs", "docs"], &date);
+        tmp
+    }
+
+    fn messages(dir: &Path, filter: CommitFilter) -> Vec<String> {
+        let filter = CommitFilter { limit: 100, ..filter };
+        list_commits(dir, &filter).unwrap().into_iter().map(|(_, msg)| msg).collect()
+    }
+
+    #[test]
+    fn filters_by_author_merges_and_date() {
+        let tmp = two_author_repo();
+        let dir = tmp.path();
+        assert_eq!(messages(dir, CommitFilter::default()).len(), 4);
+
+        let alice = CommitFilter { author: Some("alice".into()), ..Default::default() };
+        assert_eq!(messages(dir, alice), ["alice two", "alice one"]);
+
+        let no_merges = CommitFilter { no_merges: true, ..Default::default() };
+        assert!(!messages(dir, no_merges).contains(&"merge docs".to_string()));
+
+        let window = CommitFilter {
+            since: Some("2024-02-01".into()),
+            until: Some("2024-03-31".into()),
+            ..Default::default()
+        };
+        assert_eq!(messages(dir, window), ["alice two", "bob one"]);
+    }
+
+    #[test]
+    fn path_globs_select_commits_touching_them() {
+        let tmp = two_author_repo();
+        let dir = tmp.path();
+        let src = CommitFilter {
+            paths: vec!["src/**".into()],
+            no_merges: true,
+            ..Default::default()
+        };
+        assert_eq!(messages(dir, src), ["alice two", "alice one"]);
+
+        // `*` stops at `/`, as in --include
+        let top = CommitFilter { paths: vec!["src/*.rs".into()], ..Default::default() };
+        assert_eq!(messages(dir, top), ["alice one"]);
+    }
+
+    fn commit(sha: &str, entity_count: usize, high_critical: usize) -> CommitBenchmark {
+        CommitBenchmark {
+            sha: sha.into(),
+            message: format!("commit {}", sha),
+            entity_count,
+            file_count: 1,
+            group_count: 1,
+            cosmetic_count: 0,
+            high_critical_count: Some(high_critical),
+            max_blast_radius: Some(3),
+            cross_file_impacts: Some(0),
+            wall_ms: 10,
+            timing: CommitTiming::default(),
+        }
+    }
+
+    fn bench(commits: Vec<CommitBenchmark>, dist: [usize; 4], avg_blast_radius: f64) -> BenchmarkResult {
+        let total: usize = commits.iter().map(|c| c.entity_count).sum();
+        BenchmarkResult {
+            repo: "r".into(),
+            mode: "full".into(),
+            filter: CommitFilter::default(),
+            total_commits: commits.len(),
+            analyzed_commits: commits.len(),
+            total_entities_reviewed: total,
+            wall_ms: 100,
+            cosmetic_ratio: 0.2,
+            noise_reduction: 0.2,
+            avg_entities_per_file: 1.0,
+            risk_distribution: Some(RiskDistribution {
+                critical: dist[0],
+                high: dist[1],
+                medium: dist[2],
+                low: dist[3],
+            }),
+            avg_blast_radius: Some(avg_blast_radius),
+            max_blast_radius: Some(3),
+            avg_groups_per_commit: 1.0,
+            tangled_commit_ratio: 0.0,
+            avg_files_per_commit: 1.0,
+            avg_entities_per_commit: total as f64 / commits.len() as f64,
+            high_critical_ratio: Some(0.0),
+            cross_file_impact_ratio: Some(0.0),
+            commits,
+            distributions: Distributions::default(),
+            comparison: None,
+        }
+    }
+
+    #[test]
+    fn one_drifted_commit_is_flagged_and_trips_the_tolerance() {
+        let baseline = bench(vec![commit("a", 4, 1), commit("b", 4, 0), commit("old", 2, 0)], [1, 0, 4, 5], 2.0);
+        // "b" now scores one entity high
+        let current = bench(vec![commit("new", 2, 0), commit("a", 4, 1), commit("b", 4, 1)], [1, 1, 3, 5], 2.0);
+
+        let cmp = compare(&baseline, &current, 0.05);
+        assert_eq!(cmp.matched_commits, 2);
+        assert_eq!(cmp.commits.len(), 1);
+        assert_eq!(cmp.commits[0].sha, "b");
+        assert!(cmp.commits[0].flagged);
+        assert_eq!(cmp.commits[0].high_critical_count, Some((0, 1)));
+
+        let high = cmp.metrics.iter().find(|m| m.metric == "high_share").unwrap();
+        assert_eq!(high.baseline, 0.0);
+        assert_eq!(high.pct_delta, None);
+        let medium = cmp.metrics.iter().find(|m| m.metric == "medium_share").unwrap();
+        assert!((medium.pct_delta.unwrap() + 25.0).abs() < 1e-9, "{:?}", medium);
+        assert_eq!(cmp.exceeded, ["high_share", "medium_share"]);
+
+        // Same commits, same scores: nothing to report
+        let same = compare(&baseline, &baseline, 0.05);
+        assert!(same.commits.is_empty() && same.exceeded.is_empty());
+        assert!(same.metrics.iter().all(|m| m.delta == 0.0));
+    }
+
+    #[test]
+    fn fast_runs_compare_without_graph_metrics() {
+        let baseline = bench(vec![commit("a", 4, 1)], [1, 0, 3, 0], 2.0);
+        let mut current = bench(vec![commit("a", 4, 1)], [1, 0, 3, 0], 2.0);
+        current.mode = "fast".into();
+        current.risk_distribution = None;
+        current.avg_blast_radius = None;
+        current.commits[0].high_critical_count = None;
+
+        let cmp = compare(&baseline, &current, 0.05);
+        let names: Vec<_> = cmp.metrics.iter().map(|m| m.metric).collect();
+        assert_eq!(names, ["cosmetic_ratio", "avg_groups_per_commit", "avg_entities_per_commit"]);
+        assert!(cmp.commits.is_empty());
+    }
+
+    #[test]
+    fn older_output_loads_as_a_baseline() {
+        let mut json = serde_json::to_value(bench(vec![commit("a", 4, 1)], [1, 0, 3, 0], 2.0)).unwrap();
+        for field in ["mode", "filter", "wall_ms"] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        json["commits"][0].as_object_mut().unwrap().remove("wall_ms");
+        let loaded: BenchmarkResult = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.commits[0].sha, "a");
+        assert_eq!(loaded.mode, "");
+    }
+
+    #[test]
+    fn progress_lines_are_json() {
+        let mut c = commit("abc123", 5, 1);
+        c.timing = CommitTiming::from(&Timing {
+            diff_ms: 3,
+            list_files_ms: 1,
+            graph_build_ms: 7,
+            graph_lookup_ms: 0,
+            scoring_ms: 2,
+            total_ms: 14,
+            ..Default::default()
+        });
+        assert_eq!(c.timing.graph_ms, 8);
+
+        let line = progress_line(&c, 2, 10, eta_ms(500, 2, 10));
+        assert!(!line.contains('\n'));
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["sha"], "abc123");
+        assert_eq!(parsed["entity_count"], 5);
+        assert_eq!(parsed["total_ms"], 14);
+        assert_eq!(parsed["eta_ms"], 2000);
+    }
+
+    #[test]
+    fn eta_follows_the_average_pace() {
+        assert_eq!(eta_ms(1000, 0, 10), None);
+        assert_eq!(eta_ms(1000, 4, 10), Some(1500));
+        assert_eq!(eta_ms(1000, 10, 10), Some(0));
+        assert_eq!(format_eta(1500), "2s");
+        assert_eq!(format_eta(65_000), "1m05s");
+    }
+
+    #[test]
+    fn percentiles_of_one_to_a_hundred() {
+        let p = Percentiles::from((1..=100).map(f64::from).collect::<Vec<_>>());
+        assert_eq!((p.count, p.p50, p.p90, p.p99, p.max), (100, 50.0, 90.0, 99.0, 100.0));
+
+        // Order doesn't matter, and small sets round up to the next value
+        let p = Percentiles::from(vec![10.0, 1.0, 7.0, 3.0, 5.0, 9.0, 2.0, 8.0, 4.0, 6.0]);
+        assert_eq!((p.p50, p.p90, p.p99, p.max), (5.0, 9.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn one_hub_shows_in_the_tail_not_the_median() {
+        let mut radii = vec![2.0; 199];
+        radii.push(8000.0);
+        let p = Percentiles::from(radii);
+        assert_eq!((p.p50, p.p90, p.p99, p.max), (2.0, 2.0, 2.0, 8000.0));
+    }
+
+    #[test]
+    fn percentiles_of_empty_and_single_inputs() {
+        assert_eq!(Percentiles::from(vec![]), Percentiles::default());
+        let p = Percentiles::from(vec![0.42]);
+        assert_eq!((p.count, p.p50, p.p90, p.p99, p.max), (1, 0.42, 0.42, 0.42, 0.42));
+    }
+
+    #[test]
+    fn histogram_buckets_cover_zero_to_one() {
+        let hist = risk_histogram(&[0.0, 0.05, 0.1, 0.55, 0.99, 1.0]);
+        assert_eq!(hist.len(), 10);
+        let counts: Vec<usize> = hist.iter().map(|b| b.count).collect();
+        assert_eq!(counts, [2, 1, 0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!((hist[9].lower, hist[9].upper), (0.9, 1.0));
+        assert!(risk_histogram(&[]).iter().all(|b| b.count == 0));
+    }
+
+    #[test]
+    fn filter_is_recorded_in_the_output() {
+        let filter = CommitFilter {
+            limit: 20,
+            author: Some("bob".into()),
+            paths: vec!["docs/**".into()],
+            no_merges: true,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&filter).unwrap();
+        assert_eq!(json["author"], "bob");
+        assert_eq!(json["paths"], serde_json::json!(["docs/**"]));
+        assert_eq!(json["since"], serde_json::Value::Null);
+        assert_eq!(json["no_merges"], true);
+    }
 }
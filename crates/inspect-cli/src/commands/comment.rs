@@ -3,9 +3,11 @@ use std::collections::HashMap;
 use clap::Args;
 use serde::Deserialize;
 
-use inspect_core::github::{CreateReview, GitHubClient, ReviewCommentInput};
+use inspect_core::github::{is_duplicate_comment, CreateReview, GitHubClient, ReviewCommentInput};
 use inspect_core::patch::{commentable_lines, parse_patch};
 
+use crate::output::progress;
+
 #[derive(Args)]
 pub struct CommentArgs {
     /// PR number
@@ -18,6 +20,10 @@ pub struct CommentArgs {
     /// Path to JSON file with review comments
     #[arg(long)]
     pub comments_file: String,
+
+    /// Post comments even if the same comment is already on the PR nearby
+    #[arg(long)]
+    pub allow_duplicates: bool,
 }
 
 #[derive(Deserialize)]
@@ -49,7 +55,7 @@ pub async fn run(args: CommentArgs) {
         }
     };
 
-    eprintln!(
+    progress!(
         "Fetching PR #{} from {} with patches...",
         args.number, args.remote
     );
@@ -114,6 +120,28 @@ pub async fn run(args: CommentArgs) {
         }
     }
 
+    let mut duplicates = 0;
+    if !args.allow_duplicates && !valid_comments.is_empty() {
+        let existing = match client.list_review_comments(&args.remote, args.number).await {
+            Ok(existing) => existing,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        valid_comments.retain(|c| {
+            let duplicate = is_duplicate_comment(c, &existing);
+            if duplicate {
+                duplicates += 1;
+                warnings.push(format!(
+                    "SKIP: {}:{} is already on the PR (--allow-duplicates to post anyway)",
+                    c.path, c.line
+                ));
+            }
+            !duplicate
+        });
+    }
+
     if !warnings.is_empty() {
         for w in &warnings {
             eprintln!("  {w}");
@@ -121,6 +149,10 @@ pub async fn run(args: CommentArgs) {
     }
 
     if valid_comments.is_empty() {
+        if duplicates > 0 && duplicates == input.comments.len() {
+            eprintln!("nothing to post: every comment is already on the PR");
+            return;
+        }
         eprintln!("error: no valid comments to post after validation");
         std::process::exit(1);
     }
@@ -132,11 +164,22 @@ pub async fn run(args: CommentArgs) {
         comments: valid_comments,
     };
 
-    match client.create_review(&args.remote, args.number, &review).await {
-        Ok(resp) => {
+    match client
+        .create_review(&args.remote, args.number, &review, &pr.files)
+        .await
+    {
+        Ok(posted) => {
+            for s in &posted.skipped {
+                eprintln!("  SKIP: {}:{} {}", s.comment.path, s.comment.line, s.reason);
+            }
             println!(
                 "{}",
-                serde_json::json!({ "id": resp.id, "url": resp.html_url })
+                serde_json::json!({
+                    "id": posted.id,
+                    "url": posted.html_url,
+                    "posted": posted.posted.len(),
+                    "skipped": posted.skipped,
+                })
             );
         }
         Err(e) => {
@@ -3,50 +3,89 @@ use std::path::PathBuf;
 use clap::Args;
 use sem_core::git::types::DiffScope;
 
+use super::{Gate, ListArgs, NoiseArgs, PathArgs, ShowDiffArgs};
 use crate::formatters;
 use crate::OutputFormat;
-use inspect_core::analyze::analyze;
+use inspect_core::analyze::analyze_with_options;
+use inspect_core::scope::parse_scope;
 use inspect_core::types::RiskLevel;
 
 #[derive(Args)]
 pub struct DiffArgs {
-    /// Commit ref or range (e.g. HEAD~1, main..feature, abc123)
-    pub target: String,
+    /// Commit ref or range (e.g. HEAD~1, main..feature, main...feature), or "staged"
+    #[arg(required_unless_present = "staged")]
+    pub target: Option<String>,
+
+    /// Analyze only staged changes (HEAD vs index)
+    #[arg(long, conflicts_with = "target")]
+    pub staged: bool,
 
     /// Output format
     #[arg(long, value_enum, default_value = "terminal")]
     pub format: OutputFormat,
 
     /// Minimum risk level to show
-    #[arg(long)]
-    pub min_risk: Option<String>,
+    #[arg(long, value_parser = inspect_core::filter::parse_risk_level)]
+    pub min_risk: Option<RiskLevel>,
+
+    /// Exit with code 2 if any entity is at or above this risk level
+    #[arg(long, value_parser = inspect_core::filter::parse_risk_level)]
+    pub fail_on: Option<RiskLevel>,
 
     /// Show dependency context
     #[arg(long)]
     pub context: bool,
 
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    #[command(flatten)]
+    pub list: ListArgs,
+
+    #[command(flatten)]
+    pub diffs: ShowDiffArgs,
+
+    #[command(flatten)]
+    pub paths: PathArgs,
+
     /// Repository path
     #[arg(short = 'C', long, default_value = ".")]
     pub repo: PathBuf,
 }
 
 pub fn run(args: DiffArgs) {
-    let scope = parse_scope(&args.target);
     let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
+    let scope = match args.target {
+        Some(ref target) if !args.staged => match parse_scope(&repo, target) {
+            Ok(scope) => scope,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => DiffScope::Staged,
+    };
 
-    match analyze(&repo, scope) {
+    match analyze_with_options(&repo, scope, &args.noise.options()) {
         Ok(mut result) => {
+            args.paths.apply(&mut result);
+            let gate = Gate::check(&result, args.fail_on);
+
             // Filter by min risk if specified
-            if let Some(ref min) = args.min_risk {
-                let min_level = parse_risk_level(min);
+            if let Some(min_level) = args.min_risk {
                 result.entity_reviews.retain(|r| r.risk_level >= min_level);
             }
 
-            match args.format {
-                OutputFormat::Terminal => formatters::terminal::print(&result, args.context),
-                OutputFormat::Json => formatters::json::print(&result),
-                OutputFormat::Markdown => formatters::markdown::print(&result, args.context),
-            }
+            let listing = args.list.apply(&mut result);
+            formatters::print(
+                &result,
+                args.format,
+                args.context,
+                listing,
+                args.diffs.inline(),
+                args.list.include_content,
+            );
+            Gate::exit_if_failed(gate);
         }
         Err(e) => {
             eprintln!("error: {}", e);
@@ -54,26 +93,3 @@ pub fn run(args: DiffArgs) {
         }
     }
 }
-
-fn parse_scope(target: &str) -> DiffScope {
-    if target.contains("..") {
-        let parts: Vec<&str> = target.split("..").collect();
-        DiffScope::Range {
-            from: parts[0].to_string(),
-            to: parts[1].to_string(),
-        }
-    } else {
-        DiffScope::Commit {
-            sha: target.to_string(),
-        }
-    }
-}
-
-fn parse_risk_level(s: &str) -> RiskLevel {
-    match s.to_lowercase().as_str() {
-        "critical" => RiskLevel::Critical,
-        "high" => RiskLevel::High,
-        "medium" => RiskLevel::Medium,
-        _ => RiskLevel::Low,
-    }
-}
@@ -0,0 +1,267 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use colored::Colorize;
+
+use super::NoiseArgs;
+use crate::OutputFormat;
+use inspect_core::analyze::analyze_with_options;
+use inspect_core::filter::{closest_names, find_reviews};
+use inspect_core::patch::{unified_diff, UnifiedDiff};
+use inspect_core::scope::parse_scope;
+use inspect_core::types::{EntityReview, RiskLevel};
+
+/// How many near-miss names to suggest when no entity matches.
+const SUGGESTION_COUNT: usize = 5;
+
+#[derive(Args)]
+pub struct EntityArgs {
+    /// Name of the changed entity
+    pub name: String,
+
+    /// Commit ref or range (e.g. HEAD~1, main..feature), "working" or "staged"
+    #[arg(long, default_value = "working")]
+    pub target: String,
+
+    /// File path (or path suffix) to pick between entities with the same name
+    #[arg(long)]
+    pub file: Option<String>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "terminal")]
+    pub format: OutputFormat,
+
+    /// Context lines around each diff hunk
+    #[arg(long, default_value = "3")]
+    pub context_lines: usize,
+
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    /// Repository path
+    #[arg(short = 'C', long, default_value = ".")]
+    pub repo: PathBuf,
+}
+
+pub fn run(args: EntityArgs) {
+    if matches!(args.format, OutputFormat::Sarif) {
+        eprintln!("error: entity doesn't support --format sarif; use terminal, json or markdown");
+        std::process::exit(1);
+    }
+    let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
+    let scope = match parse_scope(&repo, &args.target) {
+        Ok(scope) => scope,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let result = match analyze_with_options(&repo, scope, &args.noise.options()) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let review = match find_reviews(&result, &args.name, args.file.as_deref()).as_slice() {
+        [] => {
+            let names = result.entity_reviews.iter().map(|r| r.entity_name.as_str());
+            let suggestions = closest_names(&args.name, names, SUGGESTION_COUNT);
+            match &args.file {
+                Some(file) => eprintln!("error: entity '{}' not found in changes to {}", args.name, file),
+                None => eprintln!("error: entity '{}' not found in changes", args.name),
+            }
+            if !suggestions.is_empty() {
+                eprintln!("did you mean: {}", suggestions.join(", "));
+            }
+            std::process::exit(1);
+        }
+        [review] => *review,
+        candidates => {
+            eprintln!("error: '{}' is ambiguous; pass --file to pick one:", args.name);
+            for c in candidates {
+                eprintln!("  {} ({}:{}-{})", c.entity_name, c.file_path, c.start_line, c.end_line);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    // Added and deleted entities diff against an empty side
+    let diff = unified_diff(
+        review.before_content.as_deref().unwrap_or(""),
+        review.after_content.as_deref().unwrap_or(""),
+        args.context_lines,
+    );
+    match args.format {
+        OutputFormat::Terminal => print_terminal(review, &diff),
+        OutputFormat::Json => print_json(review, &diff),
+        OutputFormat::Markdown => print_markdown(review, &diff),
+        OutputFormat::Sarif => unreachable!("rejected before the analysis"),
+    }
+}
+
+fn print_terminal(review: &EntityReview, diff: &UnifiedDiff) {
+    let risk_badge = match review.risk_level {
+        RiskLevel::Critical => " CRITICAL ".on_red().white().bold().to_string(),
+        RiskLevel::High => " HIGH ".on_yellow().black().bold().to_string(),
+        RiskLevel::Medium => " MEDIUM ".on_blue().white().to_string(),
+        RiskLevel::Low => " LOW ".dimmed().to_string(),
+    };
+    println!(
+        "\n{} {} {}",
+        risk_badge,
+        format!("{} {}", review.entity_type, review.entity_name).bold(),
+        format!("({}:{}-{})", review.file_path, review.start_line, review.end_line).dimmed(),
+    );
+    println!(
+        "  change: {}  classification: {}  score: {:.2}  blast: {}  group: {}",
+        format!("{:?}", review.change_type).to_lowercase(),
+        review.classification,
+        review.risk_score,
+        review.blast_radius,
+        review.group_id,
+    );
+    for flag in flags(review) {
+        println!("  {}", flag.yellow());
+    }
+    if let Some(ref rule) = review.risk_rule {
+        println!("  {}", format!("{} by rule {}", rule.effect, rule.pattern).magenta());
+    }
+
+    if let Some(ref parts) = review.score_components {
+        println!("\n{}", "score breakdown:".bold());
+        for (name, value) in parts.factors().iter().filter(|(_, v)| *v != 0.0) {
+            println!("  {:<18} {:+.2}", name, value);
+        }
+    }
+
+    print_related("dependents", &review.dependent_names);
+    print_related("dependencies", &review.dependency_names);
+
+    println!("\n{} (+{} -{})", "diff".bold(), diff.added, diff.removed);
+    for line in diff.text.lines() {
+        let line = match line.chars().next() {
+            Some('+') => line.green().to_string(),
+            Some('-') => line.red().to_string(),
+            Some('@') => line.cyan().to_string(),
+            _ => line.to_string(),
+        };
+        println!("  {}", line);
+    }
+    println!();
+}
+
+fn print_related(label: &str, related: &[(String, String)]) {
+    if related.is_empty() {
+        return;
+    }
+    println!("\n{} ({}):", label.bold(), related.len());
+    for (name, file) in related {
+        println!("  {} {}", name, format!("({})", file).dimmed());
+    }
+}
+
+fn print_json(review: &EntityReview, diff: &UnifiedDiff) {
+    let related = |names: &[(String, String)]| {
+        names
+            .iter()
+            .map(|(name, file)| serde_json::json!({"name": name, "file": file}))
+            .collect::<Vec<_>>()
+    };
+    let output = serde_json::json!({
+        "entity_id": review.entity_id,
+        "name": review.entity_name,
+        "type": review.entity_type,
+        "file": review.file_path,
+        "lines": format!("{}-{}", review.start_line, review.end_line),
+        "change_type": format!("{:?}", review.change_type).to_lowercase(),
+        "classification": format!("{}", review.classification),
+        "risk": format!("{}", review.risk_level),
+        "score": review.risk_score,
+        "blast_radius": review.blast_radius,
+        "public_api": review.is_public_api,
+        "cosmetic": review.structural_change == Some(false),
+        "test": review.is_test,
+        "group_id": review.group_id,
+        "risk_rule": review.risk_rule,
+        "score_components": review.score_components,
+        "dependents": related(&review.dependent_names),
+        "dependencies": related(&review.dependency_names),
+        "before_content": review.before_content,
+        "after_content": review.after_content,
+        "added": diff.added,
+        "removed": diff.removed,
+        "diff": diff.text,
+    });
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+fn print_markdown(review: &EntityReview, diff: &UnifiedDiff) {
+    println!(
+        "# `{}` ({}) in `{}`\n",
+        review.entity_name, review.entity_type, review.file_path
+    );
+    println!(
+        "- **Risk:** {} ({:.2}) | **Change:** {} | **Classification:** {}",
+        review.risk_level,
+        review.risk_score,
+        format!("{:?}", review.change_type).to_lowercase(),
+        review.classification,
+    );
+    println!(
+        "- **Lines:** {}-{} | **Blast radius:** {} | **Group:** {}",
+        review.start_line, review.end_line, review.blast_radius, review.group_id
+    );
+    for flag in flags(review) {
+        println!("- {}", flag);
+    }
+    if let Some(ref rule) = review.risk_rule {
+        println!("- {} by rule `{}`", rule.effect, rule.pattern);
+    }
+
+    if let Some(ref parts) = review.score_components {
+        println!("\n## Score breakdown\n");
+        println!("| Factor | Contribution |");
+        println!("|--------|--------------|");
+        for (name, value) in parts.factors().iter().filter(|(_, v)| *v != 0.0) {
+            println!("| {} | {:+.2} |", name, value);
+        }
+    }
+
+    for (label, related) in [
+        ("Dependents", &review.dependent_names),
+        ("Dependencies", &review.dependency_names),
+    ] {
+        if related.is_empty() {
+            continue;
+        }
+        println!("\n## {} ({})\n", label, related.len());
+        for (name, file) in related {
+            println!("- `{}` ({})", name, file);
+        }
+    }
+
+    println!("\n## Diff (+{} -{})\n", diff.added, diff.removed);
+    println!("```diff");
+    print!("{}", diff.text);
+    if !diff.text.is_empty() && !diff.text.ends_with('\n') {
+        println!();
+    }
+    println!("```");
+}
+
+/// Notable properties worth a line of their own.
+fn flags(review: &EntityReview) -> Vec<&'static str> {
+    let mut flags = Vec::new();
+    if review.is_public_api {
+        flags.push("public API");
+    }
+    if review.structural_change == Some(false) {
+        flags.push("cosmetic only (no structural change)");
+    }
+    if review.is_test {
+        flags.push("test code");
+    }
+    flags
+}
@@ -3,9 +3,10 @@ use std::path::PathBuf;
 use clap::Args;
 use sem_core::git::types::DiffScope;
 
+use super::{Gate, ListArgs, NoiseArgs, ShowDiffArgs};
 use crate::formatters;
 use crate::OutputFormat;
-use inspect_core::analyze::analyze;
+use inspect_core::analyze::analyze_with_options;
 use inspect_core::types::RiskLevel;
 
 #[derive(Args)]
@@ -18,13 +19,30 @@ pub struct FileArgs {
     pub format: OutputFormat,
 
     /// Minimum risk level to show
-    #[arg(long)]
-    pub min_risk: Option<String>,
+    #[arg(long, value_parser = inspect_core::filter::parse_risk_level)]
+    pub min_risk: Option<RiskLevel>,
+
+    /// Exit with code 2 if any entity is at or above this risk level
+    #[arg(long, value_parser = inspect_core::filter::parse_risk_level)]
+    pub fail_on: Option<RiskLevel>,
 
     /// Show dependency context
     #[arg(long)]
     pub context: bool,
 
+    /// Inspect only staged changes instead of the full working tree
+    #[arg(long)]
+    pub staged: bool,
+
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    #[command(flatten)]
+    pub list: ListArgs,
+
+    #[command(flatten)]
+    pub diffs: ShowDiffArgs,
+
     /// Repository path
     #[arg(short = 'C', long, default_value = ".")]
     pub repo: PathBuf,
@@ -33,31 +51,35 @@ pub struct FileArgs {
 pub fn run(args: FileArgs) {
     let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
 
-    // Use working tree diff (uncommitted changes)
-    let scope = DiffScope::Working;
+    // Use working tree diff (uncommitted changes), or just the index
+    let scope = if args.staged {
+        DiffScope::Staged
+    } else {
+        DiffScope::Working
+    };
 
-    match analyze(&repo, scope) {
+    match analyze_with_options(&repo, scope, &args.noise.options()) {
         Ok(mut result) => {
             // Filter to only the specified file
             result
                 .entity_reviews
                 .retain(|r| r.file_path.ends_with(&args.path));
+            let gate = Gate::check(&result, args.fail_on);
 
-            if let Some(ref min) = args.min_risk {
-                let min_level = match min.to_lowercase().as_str() {
-                    "critical" => RiskLevel::Critical,
-                    "high" => RiskLevel::High,
-                    "medium" => RiskLevel::Medium,
-                    _ => RiskLevel::Low,
-                };
+            if let Some(min_level) = args.min_risk {
                 result.entity_reviews.retain(|r| r.risk_level >= min_level);
             }
 
-            match args.format {
-                OutputFormat::Terminal => formatters::terminal::print(&result, args.context),
-                OutputFormat::Json => formatters::json::print(&result),
-                OutputFormat::Markdown => formatters::markdown::print(&result, args.context),
-            }
+            let listing = args.list.apply(&mut result);
+            formatters::print(
+                &result,
+                args.format,
+                args.context,
+                listing,
+                args.diffs.inline(),
+                args.list.include_content,
+            );
+            Gate::exit_if_failed(gate);
         }
         Err(e) => {
             eprintln!("error: {}", e);
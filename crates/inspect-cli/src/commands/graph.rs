@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+
+use super::NoiseArgs;
+use crate::output::progress;
+use inspect_core::analyze::analyze_with_options;
+use inspect_core::graph::{build_graph, export_graph, find_entities};
+use inspect_core::scope::parse_scope;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// Adjacency list with node metadata
+    Json,
+}
+
+#[derive(Args)]
+pub struct GraphArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value = "dot")]
+    pub format: GraphFormat,
+
+    /// Diff whose changed entities are highlighted (commit ref, range, "working" or "staged")
+    #[arg(long)]
+    pub target: Option<String>,
+
+    /// Only export entities within --depth hops of a changed entity
+    #[arg(long, requires = "target", conflicts_with = "root")]
+    pub changed_only: bool,
+
+    /// Only export entities within --depth hops of this entity
+    #[arg(long, value_name = "ENTITY")]
+    pub root: Option<String>,
+
+    /// File path (or path suffix) to pick between roots with the same name
+    #[arg(long, requires = "root")]
+    pub file: Option<String>,
+
+    /// Hops to follow from the root or changed entities, in either direction
+    #[arg(long, default_value = "2")]
+    pub depth: usize,
+
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    /// Repository path
+    #[arg(short = 'C', long, default_value = ".")]
+    pub repo: PathBuf,
+}
+
+pub fn run(args: GraphArgs) {
+    let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
+
+    let changed: HashSet<String> = match args.target {
+        Some(ref target) => {
+            let scope = match parse_scope(&repo, target) {
+                Ok(scope) => scope,
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match analyze_with_options(&repo, scope, &args.noise.options()) {
+                Ok(result) => result.entity_reviews.into_iter().map(|r| r.entity_id).collect(),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => HashSet::new(),
+    };
+
+    let graph = match build_graph(&repo, args.noise.options().graph_cache.as_ref()) {
+        Ok(g) => g,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let roots: Option<Vec<String>> = if let Some(ref name) = args.root {
+        match find_entities(&graph, name, args.file.as_deref()).as_slice() {
+            [] => {
+                eprintln!("error: entity '{}' not found in the repository", name);
+                std::process::exit(1);
+            }
+            [entity] => Some(vec![entity.entity_id.clone()]),
+            candidates => {
+                eprintln!("error: '{}' is ambiguous; pass --file to pick one:", name);
+                for c in candidates {
+                    eprintln!("  {} ({}:{}-{})", c.name, c.file_path, c.start_line, c.end_line);
+                }
+                std::process::exit(1);
+            }
+        }
+    } else if args.changed_only {
+        Some(changed.iter().cloned().collect())
+    } else {
+        None
+    };
+
+    let export = export_graph(&graph, roots.as_deref(), args.depth, &changed);
+    progress!(
+        "{} of {} entities exported",
+        export.nodes.len(),
+        graph.entities.len()
+    );
+    match args.format {
+        GraphFormat::Dot => print!("{}", export.to_dot()),
+        GraphFormat::Json => println!("{}", serde_json::to_string_pretty(&export).unwrap()),
+    }
+}
@@ -1,10 +1,10 @@
-use std::collections::HashSet;
-
 use clap::Args;
 
-use inspect_core::github::GitHubClient;
+use inspect_core::github::{GitHubClient, GitHubError, DEFAULT_SEARCH_PAGES};
 use inspect_core::noise::is_noise_file;
-use inspect_core::search::{self, SearchMatch};
+use inspect_core::search;
+
+use crate::output::progress;
 
 #[derive(Args)]
 pub struct GrepArgs {
@@ -23,16 +23,47 @@ pub struct GrepArgs {
     #[arg(long)]
     pub repo_wide: bool,
 
+    /// Maximum Code Search pages (100 results each) to fetch with --repo-wide
+    #[arg(long, default_value_t = DEFAULT_SEARCH_PAGES)]
+    pub search_pages: usize,
+
     /// Case-sensitive search
     #[arg(long)]
     pub case_sensitive: bool,
 
+    /// Treat the pattern as a regular expression
+    #[arg(long)]
+    pub regex: bool,
+
     /// Context lines around matches
     #[arg(short = 'C', long, default_value = "0")]
     pub context: usize,
+
+    /// Only search files matching this glob (repeatable), e.g. "src/**/*.ts"
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip files matching this glob (repeatable), e.g. "**/*.test.ts"
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
 }
 
 pub async fn run(args: GrepArgs) {
+    let matcher = match search::build_matcher(&args.pattern, args.case_sensitive, args.regex) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("error: invalid pattern: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let filter = match search::FileFilter::new(&args.include, &args.exclude) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("error: invalid glob: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     let client = match GitHubClient::new() {
         Ok(c) => c,
         Err(e) => {
@@ -52,67 +83,62 @@ pub async fn run(args: GrepArgs) {
     let file_paths: Vec<String> = pr
         .files
         .iter()
-        .filter(|f| !is_noise_file(&f.filename))
+        .filter(|f| !is_noise_file(&f.filename) && filter.matches(&f.filename))
         .map(|f| f.filename.clone())
         .collect();
 
-    eprintln!("Fetching {} PR files at {}...", file_paths.len(), pr.head_ref);
-    let pr_files = client
+    progress!("Fetching {} PR files at {}...", file_paths.len(), pr.head_ref);
+    let pr_files = match client
         .fetch_file_contents(&args.remote, &file_paths, &pr.head_ref)
-        .await;
+        .await
+    {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let mut all_matches =
-        search::grep_files(&pr_files, &args.pattern, args.case_sensitive, args.context);
+    let mut all_matches = search::grep_files(&pr_files, &matcher, args.context);
 
-    if args.repo_wide {
-        eprintln!("Searching codebase via GitHub Code Search...");
+    // Code Search doesn't take regexes; its results are re-checked with the matcher
+    let query = args.repo_wide.then(|| search::code_search_query(&args.pattern, args.regex));
+    if let Some(None) = query {
+        eprintln!("The regex has no literal text for Code Search to look for; showing PR matches only");
+    }
+    if let Some(Some(query)) = query {
+        progress!("Searching codebase via GitHub Code Search...");
         match client
-            .search_code(&args.remote, &args.pattern, None)
+            .search_code(
+                &args.remote,
+                &query,
+                filter.code_search_path().as_deref(),
+                args.search_pages,
+            )
             .await
         {
             Ok(search_results) => {
+                let fetched = search_results.items.len() as u64;
+                if fetched < search_results.total_count || search_results.incomplete_results {
+                    progress!(
+                        "Code Search: {} of {} results from default branch",
+                        fetched, search_results.total_count
+                    );
+                } else {
+                    progress!("Code Search: {} results from default branch", fetched);
+                }
+
+                all_matches =
+                    search::merge_code_search(all_matches, &search_results, &matcher, &file_paths, &filter);
+            }
+            Err(GitHubError::RateLimited { reset_in_secs }) => {
                 eprintln!(
-                    "Code Search: {} results from default branch",
-                    search_results.total_count
+                    "Code Search rate limited (resets in {}s); showing PR matches only",
+                    reset_in_secs
                 );
-
-                let pr_file_set: HashSet<&str> =
-                    file_paths.iter().map(|s| s.as_str()).collect();
-
-                for item in &search_results.items {
-                    if pr_file_set.contains(item.path.as_str()) {
-                        continue;
-                    }
-                    if is_noise_file(&item.path) {
-                        continue;
-                    }
-                    if let Some(text_matches) = &item.text_matches {
-                        for tm in text_matches {
-                            for (line_idx, line) in tm.fragment.lines().enumerate() {
-                                let haystack = if args.case_sensitive {
-                                    line.to_string()
-                                } else {
-                                    line.to_lowercase()
-                                };
-                                let pat = if args.case_sensitive {
-                                    args.pattern.clone()
-                                } else {
-                                    args.pattern.to_lowercase()
-                                };
-                                if haystack.contains(&pat) {
-                                    all_matches.push(SearchMatch {
-                                        file: item.path.clone(),
-                                        line: line_idx + 1,
-                                        column: haystack.find(&pat).unwrap_or(0) + 1,
-                                        text: line.to_string(),
-                                        context_before: vec![],
-                                        context_after: vec![],
-                                    });
-                                }
-                            }
-                        }
-                    }
-                }
+            }
+            Err(GitHubError::InvalidQuery(msg)) => {
+                eprintln!("Code Search rejected the pattern ({}); showing PR matches only", msg);
             }
             Err(e) => {
                 eprintln!("Code Search failed: {}", e);
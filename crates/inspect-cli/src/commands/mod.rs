@@ -1,7 +1,202 @@
 pub mod bench;
 pub mod comment;
 pub mod diff;
+pub mod entity;
 pub mod file;
+pub mod graph;
 pub mod grep;
 pub mod pr;
 pub mod review;
+pub mod risk_map;
+pub mod untangle;
+
+use clap::{Args, ValueEnum};
+
+use crate::formatters::{GroupBy, InlineDiffs, Listing};
+use inspect_core::analyze::{AnalyzeOptions, GraphScope, DEFAULT_MAX_FILE_BYTES};
+use inspect_core::filter::{filter_paths, parse_sort_key, sort_reviews, SortKey};
+use inspect_core::graph_cache::GraphCache;
+use inspect_core::report::parse_group_by;
+use inspect_core::risk::entities_at_or_above;
+use inspect_core::search::FileFilter;
+use inspect_core::types::{ReviewResult, RiskLevel};
+
+/// Exit code when `--fail-on` trips; 1 stays reserved for operational errors.
+pub const GATE_FAILED: i32 = 2;
+
+/// Entities that trip `--fail-on`, counted before display filters like
+/// `--min-risk` run so they can't hide anything from the gate.
+pub struct Gate {
+    level: RiskLevel,
+    failing: usize,
+}
+
+impl Gate {
+    pub fn check(result: &ReviewResult, fail_on: Option<RiskLevel>) -> Option<Gate> {
+        let level = fail_on?;
+        Some(Gate {
+            level,
+            failing: entities_at_or_above(result, level),
+        })
+    }
+
+    /// Exit with [`GATE_FAILED`] if any entity met the threshold.
+    pub fn exit_if_failed(gate: Option<Gate>) {
+        if let Some(gate) = gate.filter(|g| g.failing > 0) {
+            eprintln!(
+                "fail: {} entities at or above {}",
+                gate.failing, gate.level
+            );
+            crate::output::exit(GATE_FAILED);
+        }
+    }
+}
+
+/// Which files the entity graph is built from.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GraphScopeArg {
+    /// Every source file in the repo
+    Full,
+    /// The changed files and files that mention their entities (see --graph-hops)
+    Neighborhood,
+}
+
+/// Noise and graph options shared by the commands that run an analysis.
+#[derive(Args)]
+pub struct NoiseArgs {
+    /// Skip files matching a gitignore-style glob; extends .inspectignore (repeatable)
+    #[arg(long = "ignore", value_name = "GLOB")]
+    pub ignore: Vec<String>,
+
+    /// Review entities in generated files instead of suppressing them
+    #[arg(long)]
+    pub include_generated: bool,
+
+    /// Build the entity graph from the whole repo or only the changes' neighborhood,
+    /// which is faster but makes blast radius a lower bound
+    #[arg(long, value_enum, default_value = "full")]
+    pub graph_scope: GraphScopeArg,
+
+    /// Rounds of name mentions to follow out from the changed files with --graph-scope neighborhood
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    pub graph_hops: usize,
+
+    /// Keep the whole-repo graph of each clean commit on disk ($XDG_CACHE_HOME/inspect/graph)
+    /// and reuse it in later runs
+    #[arg(long)]
+    pub graph_cache: bool,
+
+    /// Skip changed files larger than this many bytes, with a warning; 0 for no limit
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_FILE_BYTES)]
+    pub max_file_bytes: usize,
+}
+
+/// Ordering, length and detail of the printed entity list.
+#[derive(Args)]
+pub struct ListArgs {
+    /// Order entities by risk, file (path then line), blast (radius) or name
+    #[arg(long, default_value = "risk", value_parser = parse_sort_key)]
+    pub sort: SortKey,
+
+    /// Show only the first N entities after sorting; 0 shows all
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub top: usize,
+
+    /// Section terminal and markdown output by file, change group, or not at all (risk)
+    #[arg(long, default_value = "risk", value_parser = parse_group_by)]
+    pub group_by: GroupBy,
+
+    /// Include each entity's before/after source and dependent/dependency names in JSON output
+    #[arg(long)]
+    pub include_content: bool,
+}
+
+impl ListArgs {
+    /// Sort and cut `result`'s entities for display.
+    pub fn apply(&self, result: &mut ReviewResult) -> Listing {
+        sort_reviews(&mut result.entity_reviews, self.sort);
+        let mut omitted = 0;
+        if self.top > 0 && result.entity_reviews.len() > self.top {
+            omitted = result.entity_reviews.len() - self.top;
+            result.entity_reviews.truncate(self.top);
+        }
+        Listing {
+            sort: self.sort,
+            group_by: self.group_by,
+            omitted,
+            ..Listing::default()
+        }
+    }
+}
+
+/// Inline before/after diffs in terminal and markdown output.
+#[derive(Args)]
+pub struct ShowDiffArgs {
+    /// Show the diff of each high or critical entity under it
+    #[arg(long)]
+    pub show_diff: bool,
+
+    /// Show the diff of every entity under it
+    #[arg(long)]
+    pub show_diff_all: bool,
+
+    /// Cut each inline diff after N lines; 0 shows all
+    #[arg(long, value_name = "N", default_value_t = 40)]
+    pub diff_lines: usize,
+}
+
+impl ShowDiffArgs {
+    pub fn inline(&self) -> InlineDiffs {
+        let min_risk = if self.show_diff_all {
+            Some(RiskLevel::Low)
+        } else {
+            self.show_diff.then_some(RiskLevel::High)
+        };
+        InlineDiffs {
+            min_risk,
+            max_lines: self.diff_lines,
+        }
+    }
+}
+
+/// Path filters applied to the analysis result, shared by diff and pr.
+#[derive(Args)]
+pub struct PathArgs {
+    /// Only triage entities in files matching this glob, e.g. 'src/**' (repeatable)
+    #[arg(long = "include", value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Leave out entities in files matching this glob; applied after --include (repeatable)
+    #[arg(long = "exclude", value_name = "GLOB")]
+    pub exclude: Vec<String>,
+}
+
+impl PathArgs {
+    /// Drop entities outside the selected paths, so stats, groups and the
+    /// verdict describe only what's left. Exits on an invalid glob.
+    pub fn apply(&self, result: &mut ReviewResult) {
+        match FileFilter::new(&self.include, &self.exclude) {
+            Ok(files) => filter_paths(result, &files),
+            Err(e) => {
+                eprintln!("error: invalid path glob: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+impl NoiseArgs {
+    pub fn options(&self) -> AnalyzeOptions {
+        AnalyzeOptions {
+            ignore: self.ignore.clone(),
+            include_generated: self.include_generated,
+            graph_scope: match self.graph_scope {
+                GraphScopeArg::Full => GraphScope::Full,
+                GraphScopeArg::Neighborhood => GraphScope::Neighborhood { hops: self.graph_hops },
+            },
+            graph_cache: if self.graph_cache { GraphCache::at_default_location() } else { None },
+            max_file_bytes: Some(if self.max_file_bytes == 0 { usize::MAX } else { self.max_file_bytes }),
+            ..Default::default()
+        }
+    }
+}
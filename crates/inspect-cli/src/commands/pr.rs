@@ -4,12 +4,16 @@ use std::process::Command;
 use clap::Args;
 use sem_core::git::types::DiffScope;
 
+use super::{Gate, ListArgs, NoiseArgs, PathArgs, ShowDiffArgs};
 use crate::formatters;
+use crate::output::progress;
 use crate::OutputFormat;
-use inspect_core::analyze::{analyze, analyze_remote};
-use inspect_core::github::GitHubClient;
-use inspect_core::noise::is_noise_file;
-use inspect_core::types::RiskLevel;
+use inspect_core::analyze::{analyze_remote_with_options, analyze_with_options};
+use inspect_core::filter::parse_risk_level;
+use inspect_core::github::{CommitState, GitHubClient, PullRequest};
+use inspect_core::noise::NoiseFilter;
+use inspect_core::risk::{passes_risk_gate, risk_summary};
+use inspect_core::types::{ReviewResult, RiskLevel};
 
 #[derive(Args)]
 pub struct PrArgs {
@@ -21,8 +25,8 @@ pub struct PrArgs {
     pub format: OutputFormat,
 
     /// Minimum risk level to show
-    #[arg(long)]
-    pub min_risk: Option<String>,
+    #[arg(long, value_parser = parse_risk_level)]
+    pub min_risk: Option<RiskLevel>,
 
     /// Show dependency context
     #[arg(long)]
@@ -32,6 +36,31 @@ pub struct PrArgs {
     #[arg(long)]
     pub remote: Option<String>,
 
+    /// Post the result as an "inspect" commit status on the PR head (requires --remote)
+    #[arg(long, requires = "remote")]
+    pub status: bool,
+
+    /// Only review what changed after this PR commit (requires --remote)
+    #[arg(long, value_name = "SHA", requires = "remote")]
+    pub since_commit: Option<String>,
+
+    /// Exit with code 2 if any entity is at or above this risk level; also
+    /// the level that fails --status (default there: critical)
+    #[arg(long, value_parser = parse_risk_level)]
+    pub fail_on: Option<RiskLevel>,
+
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    #[command(flatten)]
+    pub list: ListArgs,
+
+    #[command(flatten)]
+    pub diffs: ShowDiffArgs,
+
+    #[command(flatten)]
+    pub paths: PathArgs,
+
     /// Repository path (for local mode)
     #[arg(short = 'C', long, default_value = ".")]
     pub repo: PathBuf,
@@ -84,8 +113,9 @@ fn run_local(args: &PrArgs) {
         to: head.to_string(),
     };
 
-    match analyze(&repo, scope) {
+    match analyze_with_options(&repo, scope, &args.noise.options()) {
         Ok(mut result) => {
+            args.paths.apply(&mut result);
             apply_filters_and_print(&mut result, args);
         }
         Err(e) => {
@@ -104,7 +134,7 @@ async fn run_remote(args: &PrArgs, remote_repo: &str) {
         }
     };
 
-    eprintln!("Fetching PR #{} from {}...", args.number, remote_repo);
+    progress!("Fetching PR #{} from {}...", args.number, remote_repo);
 
     let pr = match client.get_pr(remote_repo, args.number).await {
         Ok(pr) => pr,
@@ -114,29 +144,65 @@ async fn run_remote(args: &PrArgs, remote_repo: &str) {
         }
     };
 
-    let visible_files: Vec<_> = pr
-        .files
+    let (base_sha, files) = match args.since_commit {
+        Some(ref since) => match client.pr_since_commit(remote_repo, &pr, since).await {
+            Ok(range) => {
+                progress!(
+                    "Reviewing {} commits since {} ({} files)",
+                    range.commits.len(),
+                    since,
+                    range.files.len()
+                );
+                (range.base_sha, range.files)
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => (pr.base_sha.clone(), pr.files.clone()),
+    };
+
+    // No checkout to read .inspectignore from, so only built-ins and --ignore
+    let mut noise = NoiseFilter::default();
+    if let Err(e) = noise.extend(args.noise.ignore.iter().map(String::as_str)) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+    let visible_files: Vec<_> = files
         .iter()
-        .filter(|f| !is_noise_file(&f.filename))
+        .filter(|f| !noise.is_noise_file(&f.filename))
         .cloned()
         .collect();
 
-    let noise_count = pr.files.len() - visible_files.len();
+    let noise_count = files.len() - visible_files.len();
     if noise_count > 0 {
-        eprintln!("({} noise files hidden)", noise_count);
+        progress!("({} noise files hidden)", noise_count);
     }
 
-    eprintln!("Fetching {} file contents...", visible_files.len());
+    progress!("Fetching {} file contents...", visible_files.len());
 
     // Use head_sha (commit SHA) instead of head_ref (branch name) for fetching
     // after content. For fork PRs, the branch name doesn't exist on the base repo,
     // but the commit SHA is accessible via GitHub's merge refs.
-    let file_pairs = client
-        .get_file_pairs(remote_repo, &visible_files, &pr.base_sha, &pr.head_sha)
-        .await;
+    let file_pairs = match client
+        .get_file_pairs(remote_repo, &visible_files, &base_sha, &pr.head_sha)
+        .await
+    {
+        Ok(pairs) => pairs,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    match analyze_remote(&file_pairs) {
+    match analyze_remote_with_options(&file_pairs, &[], &args.noise.options()) {
         Ok(mut result) => {
+            args.paths.apply(&mut result);
+            if args.status {
+                let fail_on = args.fail_on.unwrap_or(RiskLevel::Critical);
+                post_status(&client, remote_repo, &pr, &result, fail_on).await;
+            }
             apply_filters_and_print(&mut result, args);
         }
         Err(e) => {
@@ -146,23 +212,49 @@ async fn run_remote(args: &PrArgs, remote_repo: &str) {
     }
 }
 
+async fn post_status(
+    client: &GitHubClient,
+    repo: &str,
+    pr: &PullRequest,
+    result: &ReviewResult,
+    fail_on: RiskLevel,
+) {
+    let state = if passes_risk_gate(result, fail_on) {
+        CommitState::Success
+    } else {
+        CommitState::Failure
+    };
+    let description = risk_summary(result);
+    match client
+        .create_commit_status(repo, &pr.head_sha, state, "inspect", &description, None)
+        .await
+    {
+        Ok(()) => progress!("Posted {} status on {}: {}", state, pr.head_sha, description),
+        Err(e) => {
+            eprintln!("error: failed to post commit status: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn apply_filters_and_print(
     result: &mut inspect_core::types::ReviewResult,
     args: &PrArgs,
 ) {
-    if let Some(ref min) = args.min_risk {
-        let min_level = match min.to_lowercase().as_str() {
-            "critical" => RiskLevel::Critical,
-            "high" => RiskLevel::High,
-            "medium" => RiskLevel::Medium,
-            _ => RiskLevel::Low,
-        };
+    let gate = Gate::check(result, args.fail_on);
+
+    if let Some(min_level) = args.min_risk {
         result.entity_reviews.retain(|r| r.risk_level >= min_level);
     }
 
-    match args.format {
-        OutputFormat::Terminal => formatters::terminal::print(result, args.context),
-        OutputFormat::Json => formatters::json::print(result),
-        OutputFormat::Markdown => formatters::markdown::print(result, args.context),
-    }
+    let listing = args.list.apply(result);
+    formatters::print(
+        result,
+        args.format,
+        args.context,
+        listing,
+        args.diffs.inline(),
+        args.list.include_content,
+    );
+    Gate::exit_if_failed(gate);
 }
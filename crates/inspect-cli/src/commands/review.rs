@@ -1,17 +1,33 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::Args;
-use colored::Colorize;
-use sem_core::git::types::DiffScope;
+use clap::{ArgGroup, Args};
+use colored::{ColoredString, Colorize};
+use serde::Serialize;
 
+use super::NoiseArgs;
+use crate::output::{self, progress};
 use crate::OutputFormat;
-use inspect_core::analyze::analyze;
-use inspect_core::llm::{AnthropicClient, OpenAIClient, LlmProvider, EntityLlmReview, LlmVerdict};
-use inspect_core::types::RiskLevel;
+use inspect_core::analyze::analyze_with_options;
+use inspect_core::context::ContextBudget;
+use inspect_core::scope::parse_scope;
+use inspect_core::github::{
+    filter_duplicate_comments, CommitState, CreateReview, GitHubClient, GitHubError,
+    PostedReview, PrFile, PullRequest,
+};
+use inspect_core::llm::{
+    estimate_usage, issue_comment, review_concurrently, AnthropicClient, CostSummary,
+    CostTracker, EntityLlmReview, LlmProvider, LlmVerdict, ModelPricing, OllamaClient,
+    OpenAIClient, TokenUsage, DEFAULT_BATCH_SIZE, DEFAULT_MAX_ATTEMPTS, DEFAULT_OLLAMA_TIMEOUT,
+};
+use inspect_core::patch::{entity_anchor_line, parse_patch};
+use inspect_core::risk::{passes_risk_gate, risk_summary};
+use inspect_core::types::{EntityReview, RiskLevel};
 
 #[derive(Args)]
+#[command(group(ArgGroup::new("publish").args(["post", "status"]).multiple(true)))]
 pub struct ReviewArgs {
-    /// Commit ref or range (e.g. HEAD~1, main..feature, abc123)
+    /// Commit ref or range (e.g. HEAD~1, main..feature, main...feature)
     pub target: String,
 
     /// Output format
@@ -19,8 +35,8 @@ pub struct ReviewArgs {
     pub format: OutputFormat,
 
     /// Minimum risk level to review (default: high)
-    #[arg(long, default_value = "high")]
-    pub min_risk: String,
+    #[arg(long, default_value = "high", value_parser = inspect_core::filter::parse_risk_level)]
+    pub min_risk: RiskLevel,
 
     /// Model to use (e.g. claude-sonnet-4-5-20250929, gpt-4o, llama3)
     #[arg(long, default_value = "claude-sonnet-4-5-20250929")]
@@ -30,21 +46,103 @@ pub struct ReviewArgs {
     #[arg(long, default_value = "10")]
     pub max_entities: usize,
 
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
     /// Repository path
     #[arg(short = 'C', long, default_value = ".")]
     pub repo: PathBuf,
 
+    /// Remote repository (owner/repo) of the PR under review
+    #[arg(long)]
+    pub remote: Option<String>,
+
+    /// PR number under review
+    #[arg(long, value_name = "N")]
+    pub pr: Option<u64>,
+
+    /// Post the findings as a review on the PR given by --remote and --pr
+    #[arg(long, requires_all = ["remote", "pr"])]
+    pub post: bool,
+
+    /// Post comments even if the same comment is already on the PR nearby
+    #[arg(long, requires = "post")]
+    pub allow_duplicates: bool,
+
+    /// Post an "inspect" commit status on the PR head (requires --remote and --pr)
+    #[arg(long, requires_all = ["remote", "pr"])]
+    pub status: bool,
+
+    /// Risk level that fails the status: any entity at or above it fails
+    #[arg(long, default_value = "critical", value_parser = inspect_core::filter::parse_risk_level)]
+    pub fail_on: RiskLevel,
+
     /// LLM provider: anthropic, openai, ollama. Inferred from --api-base if omitted.
     #[arg(long)]
     pub provider: Option<String>,
 
-    /// Custom API base URL (e.g. http://localhost:8000/v1). Implies openai provider.
+    /// Custom API base URL (e.g. http://localhost:8000/v1). Implies openai provider
+    /// unless --provider is given.
     #[arg(long)]
     pub api_base: Option<String>,
 
     /// API key (overrides env var)
     #[arg(long)]
     pub api_key: Option<String>,
+
+    /// Attempts per entity when the provider is overloaded or unreachable
+    #[arg(long, default_value_t = DEFAULT_MAX_ATTEMPTS)]
+    pub max_attempts: u32,
+
+    /// Pack several small entities into each LLM call (default: on above 5 entities)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub batch: Option<bool>,
+
+    /// Max entities per call in batch mode
+    #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+    pub batch_size: usize,
+
+    /// LLM calls to run at once
+    #[arg(long, default_value = "4")]
+    pub concurrency: usize,
+
+    /// Show the model the source of each entity's dependents and dependencies
+    #[arg(long)]
+    pub with_context: bool,
+
+    /// Stop making LLM calls once the estimated spend would pass this many USD
+    #[arg(long, value_name = "USD")]
+    pub max_cost: Option<f64>,
+
+    /// Print the estimated tokens and cost without calling the LLM
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Review, but print what --post and --status would post instead of posting it
+    #[arg(long, requires = "publish")]
+    pub no_publish: bool,
+
+    /// Seconds to wait for each local model response with --provider ollama (default: 300)
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+}
+
+/// Everything `inspect review` reports.
+#[derive(Serialize)]
+struct Report {
+    reviews: Vec<EntityLlmReview>,
+    cost: CostSummary,
+    /// Entities whose review failed
+    failed: Vec<FailedReview>,
+    /// Entities left unreviewed because of --max-cost
+    skipped: usize,
+}
+
+#[derive(Serialize)]
+struct FailedReview {
+    entity_name: String,
+    file_path: String,
+    error: String,
 }
 
 fn build_provider(args: &ReviewArgs) -> Result<Box<dyn LlmProvider>, String> {
@@ -56,24 +154,31 @@ fn build_provider(args: &ReviewArgs) -> Result<Box<dyn LlmProvider>, String> {
 
     match provider {
         "anthropic" => {
-            let client = AnthropicClient::new(&args.model, args.api_key.as_deref())?;
-            Ok(Box::new(client))
+            let client = AnthropicClient::new(
+                &args.model,
+                args.api_base.as_deref(),
+                args.api_key.as_deref(),
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(Box::new(client.with_max_attempts(args.max_attempts)))
         }
         "openai" => {
             let client = OpenAIClient::new(
                 &args.model,
                 args.api_base.as_deref(),
                 args.api_key.as_deref(),
-            )?;
-            Ok(Box::new(client))
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(Box::new(client.with_max_attempts(args.max_attempts)))
         }
         "ollama" => {
-            let base = args
-                .api_base
-                .as_deref()
-                .unwrap_or("http://localhost:11434/v1");
-            let client = OpenAIClient::new(&args.model, Some(base), None)?;
-            Ok(Box::new(client))
+            let timeout = args
+                .timeout
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_OLLAMA_TIMEOUT);
+            let client = OllamaClient::new(&args.model, args.api_base.as_deref(), timeout)
+                .map_err(|e| e.to_string())?;
+            Ok(Box::new(client.with_max_attempts(args.max_attempts)))
         }
         other => Err(format!(
             "Unknown provider '{}'. Use: anthropic, openai, ollama",
@@ -83,10 +188,24 @@ fn build_provider(args: &ReviewArgs) -> Result<Box<dyn LlmProvider>, String> {
 }
 
 pub async fn run(args: ReviewArgs) {
-    let scope = parse_scope(&args.target);
+    if matches!(args.format, OutputFormat::Sarif) {
+        eprintln!("error: review doesn't support --format sarif; use terminal, json or markdown");
+        std::process::exit(1);
+    }
     let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
+    let scope = match parse_scope(&repo, &args.target) {
+        Ok(scope) => scope,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let mut result = match analyze(&repo, scope) {
+    let mut options = args.noise.options();
+    if args.with_context {
+        options.context = Some(ContextBudget::default());
+    }
+    let mut result = match analyze_with_options(&repo, scope, &options) {
         Ok(r) => r,
         Err(e) => {
             eprintln!("error: {}", e);
@@ -95,16 +214,21 @@ pub async fn run(args: ReviewArgs) {
     };
 
     let total_entities = result.entity_reviews.len();
+    // The status gates on every entity, not just the ones sent for review
+    let risk_gate = passes_risk_gate(&result, args.fail_on);
+    let risk = risk_summary(&result);
 
-    let min_level = parse_risk_level(&args.min_risk);
-    result.entity_reviews.retain(|r| r.risk_level >= min_level);
+    result.entity_reviews.retain(|r| r.risk_level >= args.min_risk);
     result.entity_reviews.truncate(args.max_entities);
 
     let review_count = result.entity_reviews.len();
 
     if review_count == 0 {
-        eprintln!("No entities at {} risk or above.", args.min_risk);
-        std::process::exit(0);
+        progress!("No entities at {} risk or above.", args.min_risk);
+        if args.status {
+            post_status(&args, status_state(risk_gate, &[]), &risk).await;
+        }
+        output::exit(0);
     }
 
     let reduction = if total_entities > 0 {
@@ -113,11 +237,35 @@ pub async fn run(args: ReviewArgs) {
         0
     };
 
-    eprintln!(
+    progress!(
         "Triaged {} entities -> {} for LLM review ({}% reduction)",
         total_entities, review_count, reduction
     );
 
+    let entities: Vec<_> = result.entity_reviews.iter().collect();
+    let pricing = ModelPricing::for_model(&args.model);
+    let estimates: Vec<TokenUsage> = entities.iter().map(|e| estimate_usage(e)).collect();
+    let estimate = estimates.iter().fold(TokenUsage::default(), |acc, &u| acc + u);
+    eprintln!(
+        "Estimated ~{} input + ~{} output tokens, {}",
+        estimate.input_tokens,
+        estimate.output_tokens,
+        match pricing {
+            Some(p) => format!("~${:.4} with {}", p.cost(estimate), args.model),
+            None => format!("no pricing known for {}", args.model),
+        }
+    );
+    if args.dry_run {
+        return;
+    }
+    if args.max_cost.is_some() && pricing.is_none() {
+        eprintln!(
+            "error: --max-cost needs a model with known pricing; {} has none",
+            args.model
+        );
+        std::process::exit(1);
+    }
+
     let client = match build_provider(&args) {
         Ok(c) => c,
         Err(e) => {
@@ -126,48 +274,343 @@ pub async fn run(args: ReviewArgs) {
         }
     };
 
-    let mut reviews: Vec<EntityLlmReview> = Vec::new();
+    let mut tracker = CostTracker::new(pricing, args.max_cost);
+    let per_call = if args.batch.unwrap_or(review_count > 5) {
+        args.batch_size.max(1)
+    } else {
+        1
+    };
+    progress!(
+        "  Reviewing {} entities, up to {} per call, {} calls at a time ...",
+        review_count,
+        per_call,
+        args.concurrency.max(1)
+    );
 
-    for (i, entity) in result.entity_reviews.iter().enumerate() {
-        eprint!(
-            "  [{}/{}] Reviewing {} ... ",
-            i + 1,
-            review_count,
-            entity.entity_name
-        );
+    // Lines print as reviews finish; the report keeps the risk order
+    let mut done = 0;
+    let results = review_concurrently(
+        client.as_ref(),
+        &entities,
+        per_call,
+        args.concurrency,
+        &mut tracker,
+        &mut |i, review| {
+            done += 1;
+            let outcome = match review {
+                Ok(review) => output::for_stderr(verdict_inline(review.verdict)),
+                Err(e) => output::for_stderr(format!("error: {}", e).red()),
+            };
+            progress!("  [{}/{}] {} ... {}", done, review_count, entities[i].entity_name, outcome);
+            // Every other entity would fail the same way
+            if matches!(review, Err(e) if e.is_auth()) {
+                std::process::exit(1);
+            }
+        },
+    )
+    .await;
 
-        match client.review_entity(entity).await {
-            Ok(review) => {
-                eprintln!("{}", format_verdict_inline(review.verdict));
+    let mut reviews = Vec::new();
+    let mut reviewed_entities = Vec::new();
+    let mut failed = Vec::new();
+    let mut skipped = 0;
+    for (entity, result) in entities.iter().zip(results) {
+        match result {
+            Some(Ok(review)) => {
                 reviews.push(review);
+                reviewed_entities.push(*entity);
             }
-            Err(e) => {
-                eprintln!("{}", format!("error: {}", e).red());
-            }
+            Some(Err(e)) => failed.push(FailedReview {
+                entity_name: entity.entity_name.clone(),
+                file_path: entity.file_path.clone(),
+                error: e.to_string(),
+            }),
+            None => skipped += 1,
         }
     }
 
+    if skipped > 0 {
+        eprintln!(
+            "{}",
+            output::for_stderr(
+                format!(
+                    "Stopped at --max-cost ${:.2}: {} entities skipped",
+                    args.max_cost.unwrap_or_default(),
+                    skipped
+                )
+                .yellow()
+            )
+        );
+    }
+
+    let report = Report {
+        reviews,
+        cost: tracker.summary(),
+        failed,
+        skipped,
+    };
+    if args.post {
+        let reviewed: Vec<_> = reviewed_entities.into_iter().zip(&report.reviews).collect();
+        post_findings(&args, &reviewed).await;
+    }
+    if args.status {
+        let description = status_description(&report.reviews, &risk);
+        post_status(&args, status_state(risk_gate, &report.reviews), &description).await;
+    }
+    if args.post && args.no_publish {
+        // The review payload is the output
+        return;
+    }
+
     match args.format {
-        OutputFormat::Terminal => print_terminal(&reviews),
-        OutputFormat::Json => print_json(&reviews),
-        OutputFormat::Markdown => print_markdown(&reviews),
+        OutputFormat::Terminal => print_terminal(&report),
+        OutputFormat::Json => print_json(&report),
+        OutputFormat::Markdown => print_markdown(&report),
+        OutputFormat::Sarif => unreachable!("rejected before the review"),
     }
 }
 
-fn format_verdict_inline(verdict: LlmVerdict) -> String {
-    match verdict {
-        LlmVerdict::Approve => "approved".green().to_string(),
-        LlmVerdict::Comment => "comment".yellow().to_string(),
-        LlmVerdict::RequestChanges => "changes requested".red().bold().to_string(),
+/// Post the findings in `reviewed` as a review on the PR given by --remote
+/// and --pr, or print the payload on --no-publish.
+async fn post_findings(args: &ReviewArgs, reviewed: &[(&EntityReview, &EntityLlmReview)]) {
+    let (Some(remote), Some(number)) = (args.remote.as_deref(), args.pr) else {
+        unreachable!("clap requires --remote and --pr");
+    };
+    if reviewed.is_empty() {
+        progress!("No findings to post");
+        return;
+    }
+    let client = match GitHubClient::new() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    progress!("Fetching PR #{} from {} with patches...", number, remote);
+    let pr = match client.get_pr_with_patches(remote, number).await {
+        Ok(pr) => pr,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match post_review(&client, remote, &pr, reviewed, args.allow_duplicates, args.no_publish).await {
+        Ok(submission) => {
+            if submission.duplicates > 0 {
+                progress!(
+                    "  {} comments already on the PR left out (--allow-duplicates to post anyway)",
+                    submission.duplicates
+                );
+            }
+            match submission.posted {
+                Some(posted) => {
+                    for s in &posted.skipped {
+                        eprintln!("  SKIP: {}:{} {}", s.comment.path, s.comment.line, s.reason);
+                    }
+                    progress!(
+                        "Posted {} review with {} comments: {}",
+                        submission.review.event,
+                        posted.posted.len(),
+                        posted.html_url
+                    );
+                }
+                None => println!("{}", serde_json::to_string_pretty(&submission.review).unwrap()),
+            }
+        }
+        Err(e) => {
+            eprintln!("error: failed to post review: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// What `post_review` sent, or would send on dry run.
+struct Submission {
+    review: CreateReview,
+    /// Comments left out because they are already on the PR
+    duplicates: usize,
+    /// None on dry run
+    posted: Option<PostedReview>,
+}
+
+/// Post the findings in `reviewed` as a review on `pr`. Comments already on
+/// the PR are left out unless `allow_duplicates`; on `dry_run` nothing is
+/// posted.
+async fn post_review(
+    client: &GitHubClient,
+    repo: &str,
+    pr: &PullRequest,
+    reviewed: &[(&EntityReview, &EntityLlmReview)],
+    allow_duplicates: bool,
+    dry_run: bool,
+) -> Result<Submission, GitHubError> {
+    let mut review = pr_review(&pr.head_sha, &pr.files, reviewed);
+    let mut duplicates = 0;
+    if !allow_duplicates && !review.comments.is_empty() {
+        let existing = client.list_review_comments(repo, pr.number).await?;
+        let fresh = filter_duplicate_comments(&review.comments, &existing);
+        duplicates = review.comments.len() - fresh.len();
+        review.comments = fresh;
+    }
+    let posted = if dry_run {
+        None
+    } else {
+        Some(client.create_review(repo, pr.number, &review, &pr.files).await?)
+    };
+    Ok(Submission { review, duplicates, posted })
+}
+
+/// REQUEST_CHANGES if any entity's verdict asks for changes, else COMMENT.
+fn review_event(verdicts: impl IntoIterator<Item = LlmVerdict>) -> &'static str {
+    if verdicts.into_iter().any(|v| v == LlmVerdict::RequestChanges) {
+        "REQUEST_CHANGES"
+    } else {
+        "COMMENT"
+    }
+}
+
+/// The PR review for `reviewed` at commit `head_sha`. Each issue is anchored
+/// on its own lines in `files`' patches, else on its entity's first changed
+/// line. Issues with neither in the diff, and the summaries of entities that
+/// got a verdict without issues, go into the review body.
+fn pr_review(
+    head_sha: &str,
+    files: &[PrFile],
+    reviewed: &[(&EntityReview, &EntityLlmReview)],
+) -> CreateReview {
+    let verdicts = || reviewed.iter().map(|(_, r)| r.verdict);
+    let count = |verdict| verdicts().filter(|v| *v == verdict).count();
+    let mut body = format!(
+        "inspect reviewed {} entities: {} approved, {} comments, {} changes requested",
+        reviewed.len(),
+        count(LlmVerdict::Approve),
+        count(LlmVerdict::Comment),
+        count(LlmVerdict::RequestChanges),
+    );
+
+    let mut comments = Vec::new();
+    let mut outside = Vec::new();
+    for (entity, review) in reviewed {
+        let hunks = files
+            .iter()
+            .find(|f| f.filename == entity.file_path)
+            .and_then(|f| f.patch.as_deref())
+            .map(parse_patch)
+            .unwrap_or_default();
+        let anchor = entity_anchor_line(&hunks, entity.start_line, entity.end_line);
+        let location = format!("`{}` ({})", entity.entity_name, entity.file_path);
+        if review.issues.is_empty() && review.verdict != LlmVerdict::Approve && !review.summary.is_empty() {
+            outside.push(format!("- {}: {}", location, review.summary));
+        }
+        for issue in &review.issues {
+            match issue_comment(&entity.file_path, issue, &hunks, anchor) {
+                Some(comment) => comments.push(comment),
+                None => outside.push(format!(
+                    "- {} **{}**: {}",
+                    location, issue.severity, issue.description
+                )),
+            }
+        }
+    }
+    if !outside.is_empty() {
+        body.push_str("\n\n**Findings outside the diff**\n\n");
+        body.push_str(&outside.join("\n"));
+    }
+
+    CreateReview {
+        commit_id: head_sha.to_string(),
+        event: review_event(verdicts()).to_string(),
+        body,
+        comments,
     }
 }
 
-fn print_terminal(reviews: &[EntityLlmReview]) {
-    if reviews.is_empty() {
+/// Post `state` as the "inspect" status on the head of the PR given by
+/// --remote and --pr, or print it on --no-publish.
+async fn post_status(args: &ReviewArgs, state: CommitState, description: &str) {
+    let (Some(remote), Some(number)) = (args.remote.as_deref(), args.pr) else {
+        unreachable!("clap requires --remote and --pr");
+    };
+    if args.no_publish {
+        progress!("Would post {} status: {}", state, description);
         return;
     }
+    let client = match GitHubClient::new() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match set_status(&client, remote, number, state, description).await {
+        Ok(head_sha) => progress!("Posted {} status on {}: {}", state, head_sha, description),
+        Err(e) => {
+            eprintln!("error: failed to post commit status: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Set the "inspect" status on the head of PR `number` and return its sha.
+async fn set_status(
+    client: &GitHubClient,
+    repo: &str,
+    number: u64,
+    state: CommitState,
+    description: &str,
+) -> Result<String, GitHubError> {
+    let pr = client.get_pr(repo, number).await?;
+    client
+        .create_commit_status(repo, &pr.head_sha, state, "inspect", description, None)
+        .await?;
+    Ok(pr.head_sha)
+}
+
+/// Failure when an entity fails the risk gate or the LLM requested changes.
+fn status_state(passes_risk_gate: bool, reviews: &[EntityLlmReview]) -> CommitState {
+    if passes_risk_gate && !reviews.iter().any(|r| r.verdict == LlmVerdict::RequestChanges) {
+        CommitState::Success
+    } else {
+        CommitState::Failure
+    }
+}
 
-    let total_tokens: u64 = reviews.iter().map(|r| r.tokens_used).sum();
+/// The risk summary, plus the LLM's change requests when there are any.
+fn status_description(reviews: &[EntityLlmReview], risk: &str) -> String {
+    let changes = reviews
+        .iter()
+        .filter(|r| r.verdict == LlmVerdict::RequestChanges)
+        .count();
+    if changes == 0 {
+        risk.to_string()
+    } else {
+        format!("{}; {} changes requested", risk, changes)
+    }
+}
+
+fn verdict_inline(verdict: LlmVerdict) -> ColoredString {
+    match verdict {
+        LlmVerdict::Approve => "approved".green(),
+        LlmVerdict::Comment => "comment".yellow(),
+        LlmVerdict::RequestChanges => "changes requested".red().bold(),
+    }
+}
+
+/// "N input + M output tokens, ~$X" for a run's spend.
+fn format_cost(cost: &CostSummary) -> String {
+    let tokens = format!("{} input + {} output tokens", cost.input_tokens, cost.output_tokens);
+    match cost.estimated_usd {
+        Some(usd) => format!("{}, ~${:.4}", tokens, usd),
+        None => tokens,
+    }
+}
+
+fn print_terminal(report: &Report) {
+    let reviews = &report.reviews;
+    if reviews.is_empty() && report.failed.is_empty() {
+        return;
+    }
     let changes_requested = reviews
         .iter()
         .filter(|r| r.verdict == LlmVerdict::RequestChanges)
@@ -182,11 +625,28 @@ fn print_terminal(reviews: &[EntityLlmReview]) {
         .count();
 
     println!(
-        "\n{} {} entities reviewed ({} tokens)",
+        "\n{} {} entities reviewed ({})",
         "review".bold().cyan(),
         reviews.len(),
-        total_tokens,
+        format_cost(&report.cost),
     );
+    if report.skipped > 0 {
+        println!(
+            "  {}",
+            format!("{} entities skipped by --max-cost", report.skipped).yellow()
+        );
+    }
+    if !report.failed.is_empty() {
+        println!("  {}", format!("{} entities failed", report.failed.len()).red());
+        for f in &report.failed {
+            println!(
+                "    {} {} {}",
+                f.entity_name.bold(),
+                format!("({})", f.file_path).dimmed(),
+                f.error.red(),
+            );
+        }
+    }
     println!(
         "  {} approved, {} comments, {} changes requested",
         format!("{}", approved).green(),
@@ -227,11 +687,12 @@ fn print_terminal(reviews: &[EntityLlmReview]) {
     println!();
 }
 
-fn print_json(reviews: &[EntityLlmReview]) {
-    println!("{}", serde_json::to_string_pretty(reviews).unwrap());
+fn print_json(report: &Report) {
+    println!("{}", serde_json::to_string_pretty(report).unwrap());
 }
 
-fn print_markdown(reviews: &[EntityLlmReview]) {
+fn print_markdown(report: &Report) {
+    let reviews = &report.reviews;
     println!("# Code Review\n");
 
     let changes_requested = reviews
@@ -254,6 +715,17 @@ fn print_markdown(reviews: &[EntityLlmReview]) {
         comments,
         changes_requested,
     );
+    println!("Cost: {}\n", format_cost(&report.cost));
+    if report.skipped > 0 {
+        println!("{} entities were skipped by `--max-cost`.\n", report.skipped);
+    }
+    if !report.failed.is_empty() {
+        println!("{} entities could not be reviewed:\n", report.failed.len());
+        for f in &report.failed {
+            println!("- `{}` ({}): {}", f.entity_name, f.file_path, f.error);
+        }
+        println!();
+    }
 
     for review in reviews {
         let verdict_str = match review.verdict {
@@ -273,31 +745,334 @@ fn print_markdown(reviews: &[EntityLlmReview]) {
 
         for issue in &review.issues {
             println!("- **{}**: {}", issue.severity, issue.description);
+            if let Some(ref replacement) = issue.suggested_replacement {
+                println!("  ```suggestion");
+                for line in replacement.lines() {
+                    println!("  {}", line);
+                }
+                println!("  ```");
+            }
         }
 
         println!();
     }
 }
 
-fn parse_scope(target: &str) -> DiffScope {
-    if target.contains("..") {
-        let parts: Vec<&str> = target.split("..").collect();
-        DiffScope::Range {
-            from: parts[0].to_string(),
-            to: parts[1].to_string(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::llm::LlmIssue;
+
+    fn llm_review(verdict: LlmVerdict) -> EntityLlmReview {
+        EntityLlmReview {
+            entity_name: "parse".into(),
+            file_path: "src/lib.rs".into(),
+            verdict,
+            issues: vec![],
+            summary: String::new(),
+            tokens_used: 0,
+            usage: TokenUsage::default(),
         }
-    } else {
-        DiffScope::Commit {
-            sha: target.to_string(),
+    }
+
+    #[test]
+    fn change_requests_fail_the_status() {
+        let approved = [llm_review(LlmVerdict::Approve), llm_review(LlmVerdict::Comment)];
+        assert_eq!(status_state(true, &approved), CommitState::Success);
+        assert_eq!(status_state(true, &[llm_review(LlmVerdict::RequestChanges)]), CommitState::Failure);
+        assert_eq!(status_state(false, &approved), CommitState::Failure);
+        assert_eq!(status_description(&approved, "0 critical, 1 high of 4 entities"), "0 critical, 1 high of 4 entities");
+        assert_eq!(
+            status_description(&[llm_review(LlmVerdict::RequestChanges)], "0 critical, 1 high of 4 entities"),
+            "0 critical, 1 high of 4 entities; 1 changes requested"
+        );
+    }
+
+    #[tokio::test]
+    async fn status_goes_on_the_pr_head() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let pr = serde_json::json!({"data": {"repository": {"pullRequest": {
+            "number": 7, "title": "Parse input", "body": null, "state": "OPEN",
+            "additions": 2, "deletions": 1, "changedFiles": 1,
+            "headRefName": "feature", "baseRefName": "main",
+            "headRefOid": "head1", "baseRefOid": "base",
+            "files": {"pageInfo": {"hasNextPage": false, "endCursor": null}, "nodes": []},
+        }}}});
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pr))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/statuses/head1"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = GitHubClient::with_base_url("test-token", &server.uri()).unwrap();
+
+        for state in [CommitState::Success, CommitState::Failure] {
+            let head = set_status(&client, "owner/repo", 7, state, "1 critical, 0 high of 3 entities").await.unwrap();
+            assert_eq!(head, "head1");
         }
+        let statuses: Vec<serde_json::Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.url.path().ends_with("/statuses/head1"))
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                serde_json::json!({"state": "success", "context": "inspect", "description": "1 critical, 0 high of 3 entities"}),
+                serde_json::json!({"state": "failure", "context": "inspect", "description": "1 critical, 0 high of 3 entities"}),
+            ]
+        );
     }
-}
 
-fn parse_risk_level(s: &str) -> RiskLevel {
-    match s.to_lowercase().as_str() {
-        "critical" => RiskLevel::Critical,
-        "high" => RiskLevel::High,
-        "medium" => RiskLevel::Medium,
-        _ => RiskLevel::Low,
+    /// `parse` (lines 10-13) gains lines 11 and 12; the rest of src/lib.rs
+    /// is untouched.
+    const PR_DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -10,3 +10,4 @@
+ fn parse(input: &str) -> u32 {
+-    input.len() as u32
++    let n = input.trim();
++    n.parse().unwrap()
+ }
+";
+
+    fn entity(name: &str, file: &str, start_line: usize, end_line: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file, name),
+            entity_name: name.into(),
+            file_path: file.into(),
+            start_line,
+            end_line,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn entity_review(entity: &EntityReview, verdict: LlmVerdict, issues: Vec<LlmIssue>) -> EntityLlmReview {
+        EntityLlmReview {
+            entity_name: entity.entity_name.clone(),
+            file_path: entity.file_path.clone(),
+            issues,
+            summary: format!("{} summary", entity.entity_name),
+            ..llm_review(verdict)
+        }
+    }
+
+    fn issue(description: &str, lines: Option<(u64, u64)>, replacement: Option<&str>) -> LlmIssue {
+        LlmIssue {
+            severity: "error".into(),
+            description: description.into(),
+            suggested_replacement: replacement.map(String::from),
+            start_line: lines.map(|(start, _)| start),
+            end_line: lines.map(|(_, end)| end),
+        }
+    }
+
+    fn pr_files() -> Vec<PrFile> {
+        vec![PrFile {
+            filename: "src/lib.rs".into(),
+            status: "modified".into(),
+            additions: 2,
+            deletions: 1,
+            patch: Some(PR_DIFF.split_once("+++ b/src/lib.rs\n").unwrap().1.into()),
+            previous_filename: None,
+        }]
+    }
+
+    /// `parse` asks for changes with a suggestion on line 12 and an issue
+    /// without lines; `helper` comments on code outside the diff; `main` is
+    /// approved in a file the PR doesn't touch.
+    fn findings() -> (Vec<EntityReview>, Vec<EntityLlmReview>) {
+        let entities = vec![
+            entity("parse", "src/lib.rs", 10, 13),
+            entity("helper", "src/lib.rs", 30, 40),
+            entity("main", "src/main.rs", 1, 5),
+        ];
+        let reviews = vec![
+            entity_review(
+                &entities[0],
+                LlmVerdict::RequestChanges,
+                vec![
+                    issue("panics on bad input", Some((12, 12)), Some("    n.parse().unwrap_or(0)")),
+                    issue("trim is redundant", None, None),
+                ],
+            ),
+            entity_review(&entities[1], LlmVerdict::Comment, vec![issue("off by one", Some((35, 35)), None)]),
+            entity_review(&entities[2], LlmVerdict::Approve, vec![]),
+        ];
+        (entities, reviews)
+    }
+
+    #[test]
+    fn any_change_request_makes_the_review_request_changes() {
+        use LlmVerdict::*;
+        assert_eq!(review_event([]), "COMMENT");
+        assert_eq!(review_event([Approve, Comment]), "COMMENT");
+        assert_eq!(review_event([Approve, RequestChanges, Comment]), "REQUEST_CHANGES");
+    }
+
+    #[test]
+    fn issues_anchor_on_their_lines() {
+        let (entities, reviews) = findings();
+        let reviewed: Vec<_> = entities.iter().zip(&reviews).collect();
+        let review = pr_review("head1", &pr_files(), &reviewed);
+
+        assert_eq!(review.commit_id, "head1");
+        assert_eq!(review.event, "REQUEST_CHANGES");
+        // The suggestion on its own line, the issue without lines on parse's
+        // first added line
+        let comments: Vec<_> = review.comments.iter().map(|c| (c.line, c.body.as_str())).collect();
+        assert_eq!(
+            comments,
+            [
+                (12, "**error**: panics on bad input\n\n```suggestion\n    n.parse().unwrap_or(0)\n```"),
+                (11, "**error**: trim is redundant"),
+            ]
+        );
+        assert_eq!(
+            review.body,
+            "inspect reviewed 3 entities: 1 approved, 1 comments, 1 changes requested\n\n\
+             **Findings outside the diff**\n\n\
+             - `helper` (src/lib.rs) **error**: off by one"
+        );
+    }
+
+    #[test]
+    fn verdicts_without_issues_go_in_the_body() {
+        let parse = entity("parse", "src/lib.rs", 10, 13);
+        let review = entity_review(&parse, LlmVerdict::Comment, vec![]);
+        let review = pr_review("head1", &pr_files(), &[(&parse, &review)]);
+        assert_eq!(review.event, "COMMENT");
+        assert!(review.comments.is_empty());
+        assert!(review.body.ends_with("- `parse` (src/lib.rs): parse summary"));
+    }
+
+    /// PR #7 of owner/repo from [`PR_DIFF`] at head "head1", already carrying
+    /// a comment that trim is redundant. Posting a review returns id 1 at
+    /// most `reviews` times.
+    async fn mock_pr(reviews: u64) -> (wiremock::MockServer, GitHubClient) {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let pr = serde_json::json!({"data": {"repository": {"pullRequest": {
+            "number": 7, "title": "Parse input", "body": null, "state": "OPEN",
+            "additions": 2, "deletions": 1, "changedFiles": 1,
+            "headRefName": "feature", "baseRefName": "main",
+            "headRefOid": "head1", "baseRefOid": "base",
+            "files": {
+                "pageInfo": {"hasNextPage": false, "endCursor": null},
+                "nodes": [{"path": "src/lib.rs", "additions": 2, "deletions": 1, "changeType": "MODIFIED"}],
+            },
+        }}}});
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pr))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/pulls/7"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(PR_DIFF))
+            .mount(&server)
+            .await;
+        let existing = serde_json::json!([{
+            "path": "src/lib.rs", "line": 11, "original_line": 11,
+            "body": "**error**:  Trim is redundant", "user": {"login": "inspect-bot"},
+            "in_reply_to_id": null,
+        }]);
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/pulls/7/comments"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(existing))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/pulls/7/reviews"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"id": 1, "html_url": "https://github.com/owner/repo/pull/7#review-1"}),
+            ))
+            .expect(reviews)
+            .mount(&server)
+            .await;
+        let client = GitHubClient::with_base_url("test-token", &server.uri()).unwrap();
+        (server, client)
+    }
+
+    /// Bodies of the reviews posted to `server`.
+    async fn posted_reviews(server: &wiremock::MockServer) -> Vec<serde_json::Value> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.method.as_str() == "POST" && r.url.path().ends_with("/reviews"))
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn post_review_posts_new_findings_once() {
+        let (server, client) = mock_pr(1).await;
+        let (entities, reviews) = findings();
+        let reviewed: Vec<_> = entities.iter().zip(&reviews).collect();
+        let pr = client.get_pr_with_patches("owner/repo", 7).await.unwrap();
+
+        let submission = post_review(&client, "owner/repo", &pr, &reviewed, false, false).await.unwrap();
+        assert_eq!(submission.duplicates, 1);
+        let posted = submission.posted.unwrap();
+        assert_eq!(posted.html_url, "https://github.com/owner/repo/pull/7#review-1");
+
+        let reviews = posted_reviews(&server).await;
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(
+            reviews[0],
+            serde_json::json!({
+                "commit_id": "head1",
+                "event": "REQUEST_CHANGES",
+                "body": submission.review.body,
+                "comments": [{
+                    "path": "src/lib.rs",
+                    "line": 12,
+                    "body": "**error**: panics on bad input\n\n```suggestion\n    n.parse().unwrap_or(0)\n```",
+                }],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn post_review_can_repeat_comments() {
+        let (server, client) = mock_pr(1).await;
+        let (entities, reviews) = findings();
+        let reviewed: Vec<_> = entities.iter().zip(&reviews).collect();
+        let pr = client.get_pr_with_patches("owner/repo", 7).await.unwrap();
+
+        let submission = post_review(&client, "owner/repo", &pr, &reviewed, true, false).await.unwrap();
+        assert_eq!(submission.duplicates, 0);
+        assert_eq!(posted_reviews(&server).await[0]["comments"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn post_review_dry_run_posts_nothing() {
+        let (server, client) = mock_pr(0).await;
+        let (entities, reviews) = findings();
+        let reviewed: Vec<_> = entities.iter().zip(&reviews).collect();
+        let pr = client.get_pr_with_patches("owner/repo", 7).await.unwrap();
+
+        let submission = post_review(&client, "owner/repo", &pr, &reviewed, false, true).await.unwrap();
+        assert!(submission.posted.is_none());
+        assert_eq!(submission.review.event, "REQUEST_CHANGES");
+        assert_eq!(submission.review.comments.len(), 1);
+        assert!(posted_reviews(&server).await.is_empty());
     }
 }
@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use colored::Colorize;
+
+use super::NoiseArgs;
+use crate::OutputFormat;
+use inspect_core::analyze::analyze_with_options;
+use inspect_core::riskmap::{risk_map, BucketRisk, RiskMapBy};
+use inspect_core::scope::parse_scope;
+use inspect_core::types::RiskLevel;
+
+#[derive(Args)]
+pub struct RiskMapArgs {
+    /// Commit ref or range (e.g. HEAD~1, main..feature), "working" or "staged"
+    pub target: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "terminal")]
+    pub format: OutputFormat,
+
+    /// Bucket entities by "file", "directory" or "group"
+    #[arg(long, default_value = "file")]
+    pub by: String,
+
+    /// Directory components to roll up to with --by directory
+    #[arg(long)]
+    pub depth: Option<usize>,
+
+    /// Only show buckets whose riskiest entity is at or above this level
+    #[arg(long, value_parser = inspect_core::filter::parse_risk_level)]
+    pub min_risk: Option<RiskLevel>,
+
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    /// Repository path
+    #[arg(short = 'C', long, default_value = ".")]
+    pub repo: PathBuf,
+}
+
+pub fn run(args: RiskMapArgs) {
+    if matches!(args.format, OutputFormat::Sarif) {
+        eprintln!("error: risk-map doesn't support --format sarif; use terminal, json or markdown");
+        std::process::exit(1);
+    }
+    let by = match RiskMapBy::parse(&args.by, args.depth) {
+        Ok(by) => by,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
+    let scope = match parse_scope(&repo, &args.target) {
+        Ok(scope) => scope,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let result = match analyze_with_options(&repo, scope, &args.noise.options()) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut buckets = risk_map(&result, by);
+    if let Some(min) = args.min_risk {
+        buckets.retain(|b| b.max_risk >= min);
+    }
+
+    match args.format {
+        OutputFormat::Terminal => print_terminal(&buckets, by),
+        OutputFormat::Json => {
+            let rows: Vec<serde_json::Value> = buckets.iter().map(|b| b.to_json(by)).collect();
+            println!("{}", serde_json::to_string_pretty(&rows).unwrap());
+        }
+        OutputFormat::Markdown => print_markdown(&buckets, by),
+        OutputFormat::Sarif => unreachable!("rejected before the analysis"),
+    }
+}
+
+fn key_heading(by: RiskMapBy) -> &'static str {
+    match by {
+        RiskMapBy::File => "file",
+        RiskMapBy::Directory { .. } => "directory",
+        RiskMapBy::Group => "group",
+    }
+}
+
+fn key(bucket: &BucketRisk) -> String {
+    match bucket.group_id {
+        Some(id) => format!("[{}] {}", id, bucket.key),
+        None => bucket.key.clone(),
+    }
+}
+
+fn print_terminal(buckets: &[BucketRisk], by: RiskMapBy) {
+    if buckets.is_empty() {
+        println!("{}", "No entities to map.".dimmed());
+        return;
+    }
+    println!(
+        "{}",
+        format!(
+            "{:<10} {:>5} {:>5} {:>8} {:>4} {:>4} {:>3}  {}",
+            "risk", "max", "mean", "entities", "crit", "high", "api", key_heading(by)
+        )
+        .dimmed()
+    );
+    for b in buckets {
+        // Pad before coloring so escape codes don't skew the columns
+        let badge = format!("{:<10}", b.max_risk.to_string().to_uppercase());
+        let badge = match b.max_risk {
+            RiskLevel::Critical => badge.red().bold().to_string(),
+            RiskLevel::High => badge.yellow().bold().to_string(),
+            RiskLevel::Medium => badge.blue().to_string(),
+            RiskLevel::Low => badge.dimmed().to_string(),
+        };
+        println!(
+            "{} {:>5.2} {:>5.2} {:>8} {:>4} {:>4} {:>3}  {}",
+            badge,
+            b.max_score,
+            b.mean_score,
+            b.entity_count,
+            b.critical,
+            b.high,
+            b.public_api_changes,
+            key(b),
+        );
+    }
+}
+
+fn print_markdown(buckets: &[BucketRisk], by: RiskMapBy) {
+    println!("## Risk map\n");
+    if buckets.is_empty() {
+        println!("No entities to map.");
+        return;
+    }
+    let heading = match by {
+        RiskMapBy::File => "File",
+        RiskMapBy::Directory { .. } => "Directory",
+        RiskMapBy::Group => "Group",
+    };
+    println!("| {} | Risk | Max | Mean | Entities | Critical | High | Public API |", heading);
+    println!("|------|------|-----|------|----------|----------|------|------------|");
+    for b in buckets {
+        println!(
+            "| `{}` | {} | {:.2} | {:.2} | {} | {} | {} | {} |",
+            key(b),
+            b.max_risk,
+            b.max_score,
+            b.mean_score,
+            b.entity_count,
+            b.critical,
+            b.high,
+            b.public_api_changes,
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use colored::Colorize;
+
+use super::NoiseArgs;
+use inspect_core::analyze::analyze_with_options;
+use inspect_core::scope::parse_scope;
+use inspect_core::split::{scope_diff, split_patch, SplitPlan};
+use inspect_core::types::{ReviewResult, RiskLevel};
+
+#[derive(Args)]
+pub struct UntangleArgs {
+    /// Commit ref or range (e.g. HEAD~1, main..feature), "working" or "staged"
+    pub target: String,
+
+    /// Write one .patch file per group into this directory
+    #[arg(long, value_name = "DIR")]
+    pub emit_patches: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub noise: NoiseArgs,
+
+    /// Repository path
+    #[arg(short = 'C', long, default_value = ".")]
+    pub repo: PathBuf,
+}
+
+pub fn run(args: UntangleArgs) {
+    let repo = args.repo.canonicalize().unwrap_or(args.repo.clone());
+    let scope = match parse_scope(&repo, &args.target) {
+        Ok(scope) => scope,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let result = match analyze_with_options(&repo, scope.clone(), &args.noise.options()) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let diff = match scope_diff(&repo, &scope) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let plan = split_patch(&diff, &result);
+    print_plan(&plan, &result);
+
+    if let Some(ref dir) = args.emit_patches {
+        if let Err(e) = write_patches(&plan, dir) {
+            eprintln!("error: writing patches to {}: {}", dir.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_plan(plan: &SplitPlan, result: &ReviewResult) {
+    if plan.groups.is_empty() {
+        println!("{}", "No entity changes to untangle.".dimmed());
+    }
+    for group in &plan.groups {
+        let members: Vec<_> = result
+            .entity_reviews
+            .iter()
+            .filter(|r| r.group_id == group.group_id)
+            .collect();
+        let max_risk = members.iter().map(|r| r.risk_level).max().unwrap_or(RiskLevel::Low);
+        println!(
+            "\n{} {} {}",
+            format!("[{}]", group.group_id).bold(),
+            group.label.bold(),
+            format!("({} entities, {} risk)", members.len(), max_risk).dimmed(),
+        );
+        println!("  commit: {}", group.message.cyan());
+        for file in &group.files {
+            let ranges: Vec<String> = file.ranges.iter().map(|(s, e)| format!("{}-{}", s, e)).collect();
+            println!("  {} {}", file.file_path, format!(":{}", ranges.join(",")).dimmed());
+            for r in members.iter().filter(|r| r.file_path == file.file_path) {
+                println!("    {} {}", r.entity_type.dimmed(), r.entity_name);
+            }
+        }
+        if group.patch.is_empty() {
+            println!("  {}", "no hunks of its own".dimmed());
+        }
+    }
+    if !plan.ungrouped.is_empty() {
+        println!("\n{} {}", "[-]".bold(), "changes outside any entity".bold());
+    }
+    if !plan.warnings.is_empty() {
+        println!();
+        for warning in &plan.warnings {
+            println!("{}", format!("warning: {}", warning).yellow());
+        }
+    }
+    println!();
+}
+
+/// One `NN-label.patch` per group with hunks, plus `ungrouped.patch`.
+/// Each starts with the suggested commit message, which `git apply` skips.
+fn write_patches(plan: &SplitPlan, dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for group in plan.groups.iter().filter(|g| !g.patch.is_empty()) {
+        let path = dir.join(format!("{:02}-{}.patch", group.group_id, slug(&group.label)));
+        std::fs::write(&path, format!("{}\n\n{}", group.message, group.patch))?;
+        written.push(path);
+    }
+    if !plan.ungrouped.is_empty() {
+        let path = dir.join("ungrouped.patch");
+        std::fs::write(&path, &plan.ungrouped)?;
+        written.push(path);
+    }
+    for path in &written {
+        println!("wrote {}", path.display());
+    }
+    if !written.is_empty() {
+        println!(
+            "{}",
+            "apply each onto the base with `git apply <patch>` and commit it on its own".dimmed()
+        );
+    }
+    Ok(())
+}
+
+/// A file-name-safe form of a group label.
+fn slug(label: &str) -> String {
+    let slug: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("-");
+    if slug.is_empty() {
+        "group".to_string()
+    } else {
+        slug
+    }
+}
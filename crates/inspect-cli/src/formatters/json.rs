@@ -1,6 +1,132 @@
+use inspect_core::risk::suggest_verdict;
 use inspect_core::types::ReviewResult;
+use serde_json::Value;
 
-pub fn print(result: &ReviewResult) {
-    let json = serde_json::to_string_pretty(result).expect("failed to serialize");
+use super::Listing;
+
+/// Version of the `--format json` layout, bumped on breaking changes.
+///
+/// - 1: the serialized `ReviewResult`, enums in Rust spelling (`"Critical"`)
+/// - 2: adds `schema_version` and `verdict`; risk levels, classifications,
+///   change types and verdicts use the lowercase names shown everywhere
+///   else (`"critical"`, `"text+syntax"`, `"modified"`, `"requires_review"`)
+/// - 3: entity source and dependent/dependency names only with
+///   `--include-content`; empty name lists are left out
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Entity fields written only with `--include-content`.
+const CONTENT_FIELDS: [&str; 4] = ["before_content", "after_content", "dependent_names", "dependency_names"];
+
+pub fn print(result: &ReviewResult, listing: Listing, include_content: bool) {
+    let json = serde_json::to_string_pretty(&to_json(result, listing, include_content)).expect("failed to serialize");
     println!("{}", json);
 }
+
+/// The result as JSON with `schema_version` and the suggested `verdict` at
+/// the top level; a list cut by `--top` gains `truncated: true` and the
+/// uncut `total_count`. Without `include_content`, entities leave out their
+/// source and related names.
+fn to_json(result: &ReviewResult, listing: Listing, include_content: bool) -> Value {
+    let mut json = serde_json::to_value(result).expect("failed to serialize");
+    if !include_content {
+        if let Some(entities) = json["entity_reviews"].as_array_mut() {
+            for entity in entities.iter_mut().filter_map(Value::as_object_mut) {
+                for field in CONTENT_FIELDS {
+                    entity.remove(field);
+                }
+            }
+        }
+    }
+    json["schema_version"] = SCHEMA_VERSION.into();
+    json["verdict"] = serde_json::to_value(suggest_verdict(result)).expect("failed to serialize");
+    if listing.omitted > 0 {
+        json["truncated"] = Value::Bool(true);
+        json["total_count"] = (result.entity_reviews.len() + listing.omitted).into();
+    }
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::analyze::compute_stats;
+    use inspect_core::types::{EntityReview, RiskLevel};
+
+    fn result() -> ReviewResult {
+        ReviewResult {
+            stats: compute_stats(&[]),
+            entity_reviews: vec![],
+            groups: vec![],
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    #[test]
+    fn carries_version_verdict_and_timing() {
+        let json = to_json(&result(), Listing::default(), false);
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["verdict"], suggest_verdict(&result()).to_string());
+        assert!(json["timing"]["total_ms"].is_u64());
+    }
+
+    fn review() -> EntityReview {
+        EntityReview {
+            entity_id: "src/lib.rs::load".into(),
+            entity_name: "load".into(),
+            risk_score: 0.5,
+            risk_level: RiskLevel::Medium,
+            blast_radius: 1,
+            dependent_count: 1,
+            end_line: 3,
+            before_content: Some("fn load() {}".into()),
+            after_content: Some("fn load() { read() }".into()),
+            dependent_names: vec![("main".into(), "src/main.rs".into())],
+            ..EntityReview::test_default()
+        }
+    }
+
+    #[test]
+    fn content_is_opt_in() {
+        let mut result = result();
+        result.entity_reviews = vec![review()];
+
+        let small = to_json(&result, Listing::default(), false);
+        let entity = small["entity_reviews"][0].as_object().unwrap();
+        assert_eq!(entity["entity_name"], "load");
+        for field in CONTENT_FIELDS {
+            assert!(!entity.contains_key(field), "{}", field);
+        }
+
+        let full = to_json(&result, Listing::default(), true);
+        let entity = &full["entity_reviews"][0];
+        assert_eq!(entity["before_content"], "fn load() {}");
+        assert_eq!(entity["after_content"], "fn load() { read() }");
+        assert_eq!(entity["dependent_names"], serde_json::json!([["main", "src/main.rs"]]));
+        // Empty lists are skipped even when content is asked for
+        assert!(entity.get("dependency_names").is_none());
+    }
+
+    #[test]
+    fn truncated_lists_are_marked() {
+        let cut = Listing {
+            omitted: 12,
+            ..Default::default()
+        };
+        let json = to_json(&result(), cut, false);
+        assert_eq!(json["truncated"], true);
+        assert_eq!(json["total_count"], 12);
+        assert_eq!(
+            cut.more().as_deref(),
+            Some("… and 12 more entities (use --top 0 for all)")
+        );
+
+        let whole = to_json(&result(), Listing::default(), false);
+        assert!(whole.get("truncated").is_none());
+        assert!(whole.get("total_count").is_none());
+        assert_eq!(Listing::default().more(), None);
+    }
+}
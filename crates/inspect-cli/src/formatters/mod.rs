@@ -1,3 +1,26 @@
 pub mod json;
 pub mod markdown;
+pub mod sarif;
 pub mod terminal;
+
+use inspect_core::types::ReviewResult;
+
+use crate::OutputFormat;
+
+pub use inspect_core::report::{sections, GroupBy, InlineDiffs, Listing};
+
+pub fn print(
+    result: &ReviewResult,
+    format: OutputFormat,
+    show_context: bool,
+    listing: Listing,
+    diffs: InlineDiffs,
+    include_content: bool,
+) {
+    match format {
+        OutputFormat::Terminal => terminal::print(result, show_context, listing, diffs),
+        OutputFormat::Json => json::print(result, listing, include_content),
+        OutputFormat::Markdown => markdown::print(result, show_context, listing, diffs),
+        OutputFormat::Sarif => sarif::print(result),
+    }
+}
@@ -0,0 +1,254 @@
+use inspect_core::types::{ChangeClassification, ReviewResult, RiskLevel};
+use serde_json::{json, Value};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const LEVELS: [RiskLevel; 4] = [RiskLevel::Low, RiskLevel::Medium, RiskLevel::High, RiskLevel::Critical];
+const CLASSIFICATIONS: [ChangeClassification; 7] = [
+    ChangeClassification::Text,
+    ChangeClassification::Syntax,
+    ChangeClassification::Functional,
+    ChangeClassification::TextSyntax,
+    ChangeClassification::TextFunctional,
+    ChangeClassification::SyntaxFunctional,
+    ChangeClassification::TextSyntaxFunctional,
+];
+
+pub fn print(result: &ReviewResult) {
+    let sarif = serde_json::to_string_pretty(&to_sarif(result)).expect("failed to serialize");
+    println!("{}", sarif);
+}
+
+/// A SARIF 2.1.0 log with one run: a rule per risk level, then a rule per
+/// classification, and a result per changed entity. Each result is reported
+/// under its risk level's rule and names its classification's rule in
+/// `properties.classificationRuleId`.
+pub fn to_sarif(result: &ReviewResult) -> Value {
+    let level_rules = LEVELS.iter().map(|&level| {
+        json!({
+            "id": rule_id(level),
+            "name": format!("{}RiskChange", capitalize(&level.to_string())),
+            "shortDescription": { "text": format!("{} risk entity change", capitalize(&level.to_string())) },
+            "defaultConfiguration": { "level": sarif_level(level) },
+        })
+    });
+    let classification_rules = CLASSIFICATIONS.iter().map(|&classification| {
+        json!({
+            "id": classification_rule_id(classification),
+            "name": format!("{:?}Change", classification),
+            "shortDescription": { "text": describe(classification) },
+        })
+    });
+    let rules: Vec<Value> = level_rules.chain(classification_rules).collect();
+
+    let results: Vec<Value> = result
+        .entity_reviews
+        .iter()
+        .map(|r| {
+            let start_line = r.start_line.max(1);
+            json!({
+                "ruleId": rule_id(r.risk_level),
+                "ruleIndex": LEVELS.iter().position(|&l| l == r.risk_level).unwrap(),
+                "level": sarif_level(r.risk_level),
+                "message": {
+                    "text": format!(
+                        "{} {} {} ({} change), {} risk {:.2}, blast radius {}",
+                        format!("{:?}", r.change_type).to_lowercase(),
+                        r.entity_type,
+                        r.entity_name,
+                        r.classification,
+                        r.risk_level,
+                        r.risk_score,
+                        r.blast_radius,
+                    ),
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": r.file_path },
+                        "region": { "startLine": start_line, "endLine": r.end_line.max(start_line) },
+                    },
+                    "logicalLocations": [{ "name": r.entity_name, "kind": r.entity_type }],
+                }],
+                "partialFingerprints": { "entityId": r.entity_id },
+                "properties": {
+                    "riskScore": r.risk_score,
+                    "blastRadius": r.blast_radius,
+                    "dependentCount": r.dependent_count,
+                    "classification": r.classification.to_string(),
+                    "classificationRuleId": classification_rule_id(r.classification),
+                    "groupId": r.group_id,
+                    "isPublicApi": r.is_public_api,
+                },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "inspect",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/Ataraxy-Labs/inspect",
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn rule_id(level: RiskLevel) -> String {
+    format!("inspect/{}-risk", level)
+}
+
+fn classification_rule_id(classification: ChangeClassification) -> String {
+    format!("inspect/{}-change", classification)
+}
+
+fn describe(classification: ChangeClassification) -> &'static str {
+    match classification {
+        ChangeClassification::Text => "Only comments, whitespace or documentation changed",
+        ChangeClassification::Syntax => "Only signatures, types or declarations changed",
+        ChangeClassification::Functional => "Logic or behavior changed",
+        ChangeClassification::TextSyntax => "Comments and signatures changed",
+        ChangeClassification::TextFunctional => "Comments and logic changed",
+        ChangeClassification::SyntaxFunctional => "Signatures and logic changed",
+        ChangeClassification::TextSyntaxFunctional => "Comments, signatures and logic changed",
+    }
+}
+
+fn sarif_level(level: RiskLevel) -> &'static str {
+    match level {
+        RiskLevel::Critical | RiskLevel::High => "error",
+        RiskLevel::Medium => "warning",
+        RiskLevel::Low => "note",
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::analyze::compute_stats;
+    use inspect_core::types::{EntityReview, Timing};
+
+    fn review(name: &str, level: RiskLevel, start_line: usize, end_line: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("src/lib.rs::{}", name),
+            entity_name: name.into(),
+            risk_score: 0.6,
+            risk_level: level,
+            blast_radius: 7,
+            dependent_count: 3,
+            is_public_api: true,
+            group_id: 2,
+            start_line,
+            end_line,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn result(entity_reviews: Vec<EntityReview>) -> ReviewResult {
+        ReviewResult {
+            stats: compute_stats(&entity_reviews),
+            entity_reviews,
+            groups: vec![],
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    /// The published SARIF 2.1.0 schema (OASIS), vendored so the check runs
+    /// offline. One edit: the stray `]` in the two `language` patterns is
+    /// escaped, which ECMA regexes read the same way but Rust's regex crate
+    /// rejects.
+    const SCHEMA: &str = include_str!("../../tests/fixtures/sarif-schema-2.1.0.json");
+
+    fn validator() -> jsonschema::Validator {
+        let schema: Value = serde_json::from_str(SCHEMA).unwrap();
+        jsonschema::validator_for(&schema).expect("the schema compiles")
+    }
+
+    fn assert_valid(sarif: &Value) {
+        let validator = validator();
+        let errors: Vec<String> = validator
+            .iter_errors(sarif)
+            .map(|e| format!("{} at {}", e, e.instance_path()))
+            .collect();
+        assert!(errors.is_empty(), "not valid SARIF 2.1.0: {:#?}", errors);
+
+        // What the schema can't check: ruleIndex points at the rule named by
+        // ruleId, and regions don't end before they start
+        let rules = sarif["runs"][0]["tool"]["driver"]["rules"].as_array().unwrap();
+        for result in sarif["runs"][0]["results"].as_array().unwrap() {
+            let index = result["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(rules[index]["id"], result["ruleId"]);
+            let region = &result["locations"][0]["physicalLocation"]["region"];
+            assert!(region["endLine"].as_u64() >= region["startLine"].as_u64(), "region {}", region);
+        }
+    }
+
+    #[test]
+    fn the_schema_rejects_bad_logs() {
+        let validator = validator();
+        let mut sarif = to_sarif(&result(vec![review("parse", RiskLevel::High, 1, 2)]));
+        assert!(validator.is_valid(&sarif));
+        sarif["runs"][0]["results"][0]["level"] = json!("severe");
+        assert!(!validator.is_valid(&sarif));
+    }
+
+    #[test]
+    fn entities_become_results() {
+        let sarif = to_sarif(&result(vec![
+            review("parse", RiskLevel::Critical, 10, 40),
+            review("helper", RiskLevel::Medium, 0, 0),
+            review("log", RiskLevel::Low, 50, 52),
+        ]));
+        assert_valid(&sarif);
+
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        let levels: Vec<_> = results.iter().map(|r| r["level"].as_str().unwrap()).collect();
+        assert_eq!(levels, ["error", "warning", "note"]);
+        assert_eq!(results[0]["ruleId"], "inspect/critical-risk");
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["region"]["startLine"], 10);
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["region"]["endLine"], 40);
+        assert_eq!(results[1]["locations"][0]["physicalLocation"]["region"]["startLine"], 1);
+        let properties = &results[0]["properties"];
+        assert_eq!(properties["blastRadius"], 7);
+        assert_eq!(properties["classification"], "functional");
+        assert_eq!(properties["classificationRuleId"], "inspect/functional-change");
+        assert_eq!(properties["groupId"], 2);
+    }
+
+    #[test]
+    fn no_entities_is_still_a_valid_log() {
+        let sarif = to_sarif(&result(vec![]));
+        assert_valid(&sarif);
+        assert_eq!(sarif["runs"][0]["results"], json!([]));
+    }
+
+    #[test]
+    fn every_level_and_classification_has_a_rule() {
+        let sarif = to_sarif(&result(vec![]));
+        let ids: Vec<_> = sarif["runs"][0]["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(ids.len(), LEVELS.len() + CLASSIFICATIONS.len());
+        assert_eq!(ids[0], "inspect/low-risk");
+        assert!(ids.contains(&"inspect/text+syntax+functional-change".to_string()));
+    }
+}
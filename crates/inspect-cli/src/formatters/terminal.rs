@@ -1,7 +1,9 @@
 use colored::Colorize;
-use inspect_core::types::{ReviewResult, RiskLevel};
+use inspect_core::types::{EdgeKind, EntityReview, ReviewResult, RiskLevel};
 
-pub fn print(result: &ReviewResult, show_context: bool) {
+use super::{sections, GroupBy, InlineDiffs, Listing};
+
+pub fn print(result: &ReviewResult, show_context: bool, listing: Listing, diffs: InlineDiffs) {
     if result.entity_reviews.is_empty() {
         println!("{}", "No entity-level changes found.".dimmed());
         return;
@@ -20,9 +22,52 @@ pub fn print(result: &ReviewResult, show_context: bool) {
         format!("{}", stats.by_risk.medium).blue(),
         format!("{}", stats.by_risk.low).dimmed(),
     );
+    if stats.test_entities > 0 {
+        println!(
+            "  {}",
+            format!("{} in test code, {} non-test", stats.test_entities, stats.non_test_entities).dimmed()
+        );
+    }
+    if stats.generated_entities > 0 {
+        println!(
+            "  {}",
+            format!(
+                "{} entities in generated files hidden (--include-generated to show)",
+                stats.generated_entities
+            )
+            .dimmed()
+        );
+    }
+
+    if !result.blast_radius_exact && !result.entity_reviews.is_empty() {
+        println!(
+            "  {}",
+            format!("blast radius is a lower bound: the graph covered {} files", result.timing.file_count).dimmed()
+        );
+    }
 
-    // Groups summary
-    if result.groups.len() > 1 {
+    let unanalyzed: Vec<&str> = result
+        .warnings
+        .iter()
+        .filter(|w| w.file_path.is_some())
+        .map(|w| w.message.as_str())
+        .collect();
+    if !unanalyzed.is_empty() {
+        if crate::output::verbose() {
+            println!("  {}", format!("{} files not analyzed:", unanalyzed.len()).dimmed());
+            for message in &unanalyzed {
+                println!("    {}", message.dimmed());
+            }
+        } else {
+            println!(
+                "  {}",
+                format!("{} files not analyzed (use --verbose for details)", unanalyzed.len()).dimmed()
+            );
+        }
+    }
+
+    // Groups summary; --group-by group gives each its own section instead
+    if result.groups.len() > 1 && listing.group_by != GroupBy::Group {
         println!(
             "\n{} {} logical groups:",
             "groups".bold(),
@@ -30,83 +75,177 @@ pub fn print(result: &ReviewResult, show_context: bool) {
         );
         for group in &result.groups {
             println!(
-                "  [{}] {} ({} entities)",
+                "  [{}] {} ({} entities; {})",
                 group.id,
                 group.label.bold(),
-                group.entity_ids.len()
+                group.entity_ids.len(),
+                group.hint().dimmed()
             );
         }
     }
 
-    println!("\n{}", "entities (by risk):".bold().underline());
-
-    for review in &result.entity_reviews {
-        let risk_badge = match review.risk_level {
-            RiskLevel::Critical => format!(" CRITICAL ").on_red().white().bold().to_string(),
-            RiskLevel::High => format!(" HIGH ").on_yellow().black().bold().to_string(),
-            RiskLevel::Medium => format!(" MEDIUM ").on_blue().white().to_string(),
-            RiskLevel::Low => format!(" LOW ").dimmed().to_string(),
-        };
-
-        let change_icon = match review.change_type {
-            sem_core::model::change::ChangeType::Added => "+".green().bold(),
-            sem_core::model::change::ChangeType::Deleted => "-".red().bold(),
-            sem_core::model::change::ChangeType::Modified => "~".yellow(),
-            sem_core::model::change::ChangeType::Moved => ">".blue(),
-            sem_core::model::change::ChangeType::Renamed => "r".blue(),
-        };
-
-        println!(
-            "\n  {} {} {} {}",
-            change_icon,
-            risk_badge,
-            format!("{} {}", review.entity_type, review.entity_name).bold(),
-            format!("({})", review.file_path).dimmed(),
-        );
-
-        println!(
-            "    classification: {}  score: {:.2}  blast: {}  deps: {}/{}",
-            review.classification,
-            review.risk_score,
-            review.blast_radius,
-            review.dependency_count,
-            review.dependent_count,
-        );
-
-        if review.is_public_api {
-            println!("    {}", "public API".yellow());
+    for section in sections(result, listing.group_by) {
+        match section.bucket {
+            None => println!("\n{}", format!("entities (by {}):", listing.sort).bold().underline()),
+            Some(ref bucket) => {
+                let heading = match bucket.group_id {
+                    Some(id) => format!("[{}] {}", id, bucket.key),
+                    None => bucket.key.clone(),
+                };
+                println!(
+                    "\n{} {} {}",
+                    risk_badge(section.max_risk),
+                    heading.bold().underline(),
+                    format!("({} entities)", section.reviews.len()).dimmed(),
+                );
+            }
         }
-
-        if review.structural_change == Some(false) {
-            println!("    {}", "cosmetic only (no structural change)".dimmed());
+        for review in section.reviews {
+            let uses = if show_context { changed_dependencies(result, review) } else { vec![] };
+            print_review(review, show_context, &uses, diffs);
         }
+    }
 
-        if show_context {
-            // Find the corresponding change to show dependency info
-            if review.dependent_count > 0 {
-                println!("    {} {} dependents may be affected", ">>>".yellow(), review.dependent_count);
-            }
-            if review.dependency_count > 0 {
-                println!("    {} depends on {} other entities", "<<<".cyan(), review.dependency_count);
-            }
-        }
+    if let Some(more) = listing.more() {
+        println!("\n  {}", more.dimmed());
     }
 
     // Timing
     let t = &result.timing;
     if t.total_ms > 0 {
+        let excluded = if t.excluded_file_count > 0 {
+            format!(", {} excluded", t.excluded_file_count)
+        } else {
+            String::new()
+        };
         println!(
-            "\n{}  {}ms total ({} files, {} entities)",
+            "\n{}  {}ms total ({} files{}, {} entities)",
             "timing".dimmed(),
             t.total_ms,
             t.file_count,
+            excluded,
             t.graph_entity_count,
         );
+        let graph = if t.graph_cache_hit {
+            format!("cached ({}ms)", t.graph_lookup_ms)
+        } else {
+            format!("{}ms", t.graph_build_ms)
+        };
         println!(
-            "  diff: {}ms  graph: {}ms  scoring: {}ms",
-            t.diff_ms, t.graph_build_ms, t.scoring_ms,
+            "  diff: {}ms  graph: {}  scoring: {}ms",
+            t.diff_ms, graph, t.scoring_ms,
         );
     }
 
     println!();
 }
+
+fn risk_badge(level: RiskLevel) -> String {
+    match level {
+        RiskLevel::Critical => " CRITICAL ".on_red().white().bold().to_string(),
+        RiskLevel::High => " HIGH ".on_yellow().black().bold().to_string(),
+        RiskLevel::Medium => " MEDIUM ".on_blue().white().to_string(),
+        RiskLevel::Low => " LOW ".dimmed().to_string(),
+    }
+}
+
+/// Names of the other changed entities `review` depends on.
+fn changed_dependencies<'a>(result: &'a ReviewResult, review: &EntityReview) -> Vec<&'a str> {
+    result
+        .edges
+        .iter()
+        .filter(|e| e.kind == EdgeKind::Dependency && e.from == review.entity_id)
+        .map(|e| {
+            result
+                .entity_reviews
+                .iter()
+                .find(|r| r.entity_id == e.to)
+                .map_or(e.to.as_str(), |r| r.entity_name.as_str())
+        })
+        .collect()
+}
+
+fn print_review(review: &EntityReview, show_context: bool, uses: &[&str], diffs: InlineDiffs) {
+    let change_icon = match review.change_type {
+        sem_core::model::change::ChangeType::Added => "+".green().bold(),
+        sem_core::model::change::ChangeType::Deleted => "-".red().bold(),
+        sem_core::model::change::ChangeType::Modified => "~".yellow(),
+        sem_core::model::change::ChangeType::Moved => ">".blue(),
+        sem_core::model::change::ChangeType::Renamed => "r".blue(),
+    };
+
+    println!(
+        "\n  {} {} {} {}",
+        change_icon,
+        risk_badge(review.risk_level),
+        format!("{} {}", review.entity_type, review.entity_name).bold(),
+        format!("({})", review.file_path).dimmed(),
+    );
+
+    if let Some(ref old_path) = review.old_file_path {
+        println!("    {}", format!("moved from {}", old_path).dimmed());
+    }
+
+    println!(
+        "    classification: {}  score: {:.2}  blast: {}  deps: {}/{}",
+        review.classification,
+        review.risk_score,
+        review.blast_radius,
+        review.dependency_count,
+        review.dependent_count,
+    );
+
+    if let Some(ref parts) = review.score_components {
+        let top: Vec<String> = parts
+            .top(2)
+            .iter()
+            .map(|(name, value)| format!("{} ({:.2})", name, value))
+            .collect();
+        if !top.is_empty() {
+            println!("    {}", format!("driven by: {}", top.join(", ")).dimmed());
+        }
+    }
+
+    if review.is_public_api {
+        println!("    {}", "public API".yellow());
+    }
+
+    if let Some(ref rule) = review.risk_rule {
+        println!("    {}", format!("{} by rule {}", rule.effect, rule.pattern).magenta());
+    }
+
+    if review.structural_change == Some(false) {
+        println!("    {}", "cosmetic only (no structural change)".dimmed());
+    }
+
+    if review.is_test {
+        println!("    {}", "test code".dimmed());
+    }
+
+    if show_context {
+        // Find the corresponding change to show dependency info
+        if review.dependent_count > 0 {
+            println!("    {} {} dependents may be affected", ">>>".yellow(), review.dependent_count);
+        }
+        if review.dependency_count > 0 {
+            println!("    {} depends on {} other entities", "<<<".cyan(), review.dependency_count);
+        }
+        for name in uses {
+            println!("    {} uses changed entity {}", "<<<".cyan(), name.bold());
+        }
+    }
+
+    if let Some(diff) = diffs.diff(review) {
+        println!("    {}", format!("diff (+{} -{})", diff.added, diff.removed).dimmed());
+        for line in diffs.lines(&diff) {
+            let line = match line.chars().next() {
+                Some('+') => line.green().to_string(),
+                Some('-') => line.red().to_string(),
+                Some('@') => line.cyan().to_string(),
+                Some('…') => line.dimmed().to_string(),
+                _ => line,
+            };
+            println!("      {}", line);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 mod commands;
 mod formatters;
+mod output;
+
+use std::path::PathBuf;
 
 use clap::{Parser, Subcommand, ValueEnum};
 
@@ -8,6 +11,23 @@ use clap::{Parser, Subcommand, ValueEnum};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Write the report to this file instead of stdout, replacing it only
+    /// once the command finishes. Colors are left out
+    #[arg(long, global = true, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Only print errors and warnings to stderr, no progress
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Add detail to terminal reports, such as each file that was skipped
+    #[arg(long, global = true)]
+    verbose: bool,
+
+    /// When to color output; stdout and stderr are checked separately
+    #[arg(long, global = true, value_enum, default_value = "auto")]
+    color: output::ColorChoice,
 }
 
 #[derive(Subcommand)]
@@ -18,6 +38,14 @@ enum Commands {
     Pr(commands::pr::PrArgs),
     /// Review uncommitted changes in a file
     File(commands::file::FileArgs),
+    /// Show one changed entity in detail: score, dependents, and its diff
+    Entity(commands::entity::EntityArgs),
+    /// Export the entity dependency graph as DOT or JSON
+    Graph(commands::graph::GraphArgs),
+    /// Per-file (or directory, or group) risk heatmap
+    RiskMap(commands::risk_map::RiskMapArgs),
+    /// Propose commit splits from the change groups, optionally as patches
+    Untangle(commands::untangle::UntangleArgs),
     /// Benchmark entity-level review across a repo's history
     Bench(commands::bench::BenchArgs),
     /// Triage + LLM code review
@@ -33,18 +61,39 @@ pub enum OutputFormat {
     Terminal,
     Json,
     Markdown,
+    /// SARIF 2.1.0, for code-scanning tools (diff, pr and file only)
+    Sarif,
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    output::set_quiet(cli.quiet);
+    output::set_verbose(cli.verbose);
+    output::set_color(cli.color, cli.output.is_some());
+    if let Some(ref path) = cli.output {
+        if let Err(e) = output::redirect(path) {
+            eprintln!("error: redirecting output to {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+
     match cli.command {
         Commands::Diff(args) => commands::diff::run(args),
         Commands::Pr(args) => commands::pr::run(args).await,
         Commands::File(args) => commands::file::run(args),
+        Commands::Entity(args) => commands::entity::run(args),
+        Commands::Graph(args) => commands::graph::run(args),
+        Commands::RiskMap(args) => commands::risk_map::run(args),
+        Commands::Untangle(args) => commands::untangle::run(args),
         Commands::Bench(args) => commands::bench::run(args),
         Commands::Review(args) => commands::review::run(args).await,
         Commands::Comment(args) => commands::comment::run(args).await,
         Commands::Grep(args) => commands::grep::run(args).await,
     }
+
+    if let Err(e) = output::finish() {
+        eprintln!("error: writing report: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,210 @@
+//! Where reports and chatter go: `--output` sends stdout to a file that
+//! appears in one step when the command finishes, `--quiet` drops progress
+//! lines from stderr, `--verbose` adds detail to reports, and `--color`
+//! decides which stream gets colors.
+
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use colored::ColoredString;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static STDOUT_COLOR: AtomicBool = AtomicBool::new(false);
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color a stream only if it's a terminal and NO_COLOR is unset
+    Auto,
+    Always,
+    Never,
+}
+
+/// Stdout's redirect, while one is active.
+static REPORT: Mutex<Option<Report>> = Mutex::new(None);
+
+struct Report {
+    path: PathBuf,
+    /// Unlinked file holding stdout so far; nothing is left behind if the
+    /// process exits before [`finish`]
+    spool: File,
+    /// The real stdout, restored by [`finish`]
+    stdout: RawFd,
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Decide colors for stdout and stderr separately, each by its own TTY
+/// status under `auto`. A redirected report never gets colors.
+pub fn set_color(choice: ColorChoice, redirected: bool) {
+    let no_color = std::env::var("NO_COLOR").ok();
+    let force = std::env::var("CLICOLOR_FORCE").ok();
+    let stdout = !redirected && use_color(choice, io::stdout().is_terminal(), no_color.as_deref(), force.as_deref());
+    let stderr = use_color(choice, io::stderr().is_terminal(), no_color.as_deref(), force.as_deref());
+    STDOUT_COLOR.store(stdout, Ordering::Relaxed);
+    STDERR_COLOR.store(stderr, Ordering::Relaxed);
+    colored::control::set_override(stdout);
+}
+
+/// Like `colored`'s own detection: CLICOLOR_FORCE beats NO_COLOR, which
+/// beats the TTY check.
+fn use_color(choice: ColorChoice, tty: bool, no_color: Option<&str>, force: Option<&str>) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto if force.is_some_and(|v| v != "0") => true,
+        ColorChoice::Auto => no_color.is_none_or(str::is_empty) && tty,
+    }
+}
+
+/// Render `text` for stderr, colored only if stderr takes colors. `colored`
+/// has a single switch, set for stdout, so flip it for the duration.
+pub fn for_stderr(text: ColoredString) -> String {
+    colored::control::set_override(STDERR_COLOR.load(Ordering::Relaxed));
+    let rendered = text.to_string();
+    colored::control::set_override(STDOUT_COLOR.load(Ordering::Relaxed));
+    rendered
+}
+
+/// `eprintln!` for progress and status lines, silenced by `--quiet`. Errors
+/// and warnings use `eprintln!` directly.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+pub(crate) use progress;
+
+/// Send everything printed to stdout from here on to `path`, once [`finish`]
+/// runs.
+pub fn redirect(path: &Path) -> io::Result<()> {
+    let spool = tempfile::tempfile()?;
+    io::stdout().flush()?;
+    // SAFETY: dup and dup2 only act on descriptors; 1 is always open
+    let stdout = unsafe { libc::dup(1) };
+    if stdout < 0 || unsafe { libc::dup2(spool.as_raw_fd(), 1) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    *REPORT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Report {
+        path: path.to_path_buf(),
+        spool,
+        stdout,
+    });
+    Ok(())
+}
+
+/// Restore stdout and move the report into place. Does nothing without a
+/// redirect.
+pub fn finish() -> io::Result<()> {
+    let Some(mut report) = REPORT.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return Ok(());
+    };
+    io::stdout().flush()?;
+    // SAFETY: `report.stdout` came from dup in `redirect` and is closed once
+    unsafe {
+        libc::dup2(report.stdout, 1);
+        libc::close(report.stdout);
+    }
+
+    let mut contents = Vec::new();
+    report.spool.seek(SeekFrom::Start(0))?;
+    report.spool.read_to_end(&mut contents)?;
+    write_atomic(&report.path, &contents)?;
+    progress!("wrote {}", report.path.display());
+    Ok(())
+}
+
+/// Exit with `code`. A redirected report is kept unless the command failed
+/// (code 1), so a `--fail-on` trip still leaves the report behind.
+pub fn exit(code: i32) -> ! {
+    if code != 1 {
+        if let Err(e) = finish() {
+            eprintln!("error: writing report: {}", e);
+            std::process::exit(1);
+        }
+    }
+    std::process::exit(code)
+}
+
+/// Write `contents` to `path` through a temp file in the same directory, so
+/// readers see the old file or the new one, never a partial write.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    tmp.write_all(contents)?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn color_choice_follows_env_and_tty() {
+        use ColorChoice::*;
+        assert!(use_color(Auto, true, None, None));
+        assert!(!use_color(Auto, false, None, None));
+        assert!(!use_color(Auto, true, Some("1"), None));
+        assert!(use_color(Auto, true, Some(""), None), "an empty NO_COLOR is unset");
+        assert!(use_color(Auto, false, Some("1"), Some("1")));
+        assert!(!use_color(Auto, false, None, Some("0")));
+        assert!(use_color(Always, false, Some("1"), None));
+        assert!(!use_color(Never, true, None, Some("1")));
+    }
+
+    #[test]
+    fn atomic_write_replaces_without_leftovers() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("report.json");
+        std::fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, b"{\"new\": true}\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"new\": true}\n");
+        assert_eq!(entries(tmp.path()), ["report.json"]);
+    }
+
+    #[test]
+    fn failed_atomic_write_leaves_nothing() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("missing").join("report.json");
+        assert!(write_atomic(&path, b"x").is_err());
+        assert!(entries(tmp.path()).is_empty());
+    }
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::inspect;
+
+#[test]
+fn json_progress_and_output_file() {
+    let tmp = common::repo(&[
+        &[("main.rs", "fn one() -> i32 {\n    1\n}\n")],
+        &[("main.rs", "fn one() -> i32 {\n    2\n}\n")],
+        &[("main.rs", "fn one() -> i32 {\n    2\n}\n\nfn two() {}\n")],
+    ]);
+    let out_path = tmp.path().join("bench.json");
+    let out = inspect(
+        tmp.path(),
+        &["bench", "--progress", "json", "--output", out_path.to_str().unwrap()],
+    );
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(out.stdout.is_empty(), "--output keeps stdout quiet");
+
+    let result: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out_path).unwrap()).unwrap();
+    let commits = result["commits"].as_array().unwrap();
+    assert!(!commits.is_empty());
+    for commit in commits {
+        let timing = &commit["timing"];
+        let total = timing["total_ms"].as_u64().unwrap();
+        let parts = ["diff_ms", "graph_ms", "scoring_ms"].map(|k| timing[k].as_u64().unwrap());
+        assert!(parts.iter().sum::<u64>() <= total + 1, "{}", timing);
+        assert!(commit["wall_ms"].as_u64().unwrap() >= total);
+    }
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let lines: Vec<serde_json::Value> = stderr
+        .lines()
+        .filter(|l| l.starts_with('{'))
+        .map(|l| serde_json::from_str(l).expect("progress line is JSON"))
+        .collect();
+    assert_eq!(lines.len(), commits.len());
+    for (line, commit) in lines.iter().zip(commits) {
+        assert_eq!(line["sha"], commit["sha"]);
+        assert_eq!(line["entity_count"], commit["entity_count"]);
+        assert!(line["total_ms"].is_u64());
+    }
+    assert_eq!(lines.last().unwrap()["eta_ms"], 0);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{inspect, inspect_with_env};
+use tempfile::TempDir;
+
+fn repo() -> TempDir {
+    common::repo(&[
+        &[("main.rs", "")],
+        &[("main.rs", "fn hello() {\n    println!(\"hello\");\n}\n")],
+    ])
+}
+
+fn has_escapes(out: &[u8]) -> bool {
+    out.contains(&0x1b)
+}
+
+#[test]
+fn color_never_beats_clicolor_force() {
+    let tmp = repo();
+    let out = inspect_with_env(
+        tmp.path(),
+        &["diff", "HEAD", "--color", "never"],
+        &[("CLICOLOR_FORCE", "1")],
+    );
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!out.stdout.is_empty());
+    assert!(!has_escapes(&out.stdout), "{}", String::from_utf8_lossy(&out.stdout));
+    assert!(!has_escapes(&out.stderr), "{}", String::from_utf8_lossy(&out.stderr));
+}
+
+#[test]
+fn piped_output_is_plain_by_default() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["diff", "HEAD"]);
+    assert!(!has_escapes(&out.stdout));
+    assert!(!has_escapes(&out.stderr), "stderr is checked on its own");
+    let out = inspect_with_env(tmp.path(), &["diff", "HEAD", "--color", "auto"], &[("NO_COLOR", "1")]);
+    assert!(!has_escapes(&out.stdout));
+}
+
+#[test]
+fn color_always_colors_even_when_piped() {
+    let tmp = repo();
+    let out = inspect_with_env(tmp.path(), &["diff", "HEAD", "--color", "always"], &[("NO_COLOR", "1")]);
+    assert!(has_escapes(&out.stdout), "{}", String::from_utf8_lossy(&out.stdout));
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+pub fn git(dir: &Path, args: &[&str]) {
+    let out = Command::new("git").args(args).current_dir(dir).output().unwrap();
+    assert!(
+        out.status.success(),
+        "git {:?}: {}",
+        args,
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+/// A repo with one commit per `(path, content)` set, in order.
+pub fn repo(commits: &[&[(&str, &str)]]) -> TempDir {
+    let tmp = TempDir::new().unwrap();
+    let dir = tmp.path();
+    git(dir, &["init"]);
+    git(dir, &["config", "user.email", "test@test.com"]);
+    git(dir, &["config", "user.name", "Test"]);
+    for (i, files) in commits.iter().enumerate() {
+        for (path, content) in *files {
+            std::fs::write(dir.join(path), content).unwrap();
+        }
+        git(dir, &["add", "-A"]);
+        git(dir, &["commit", "-m", &format!("commit {}", i)]);
+    }
+    tmp
+}
+
+/// Run the inspect binary against `dir`.
+pub fn inspect(dir: &Path, args: &[&str]) -> Output {
+    inspect_with_env(dir, args, &[])
+}
+
+/// Run the inspect binary against `dir`, with color settings from the
+/// environment replaced by `env`.
+pub fn inspect_with_env(dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_inspect"))
+        .args(args)
+        .arg("-C")
+        .arg(dir)
+        .env_remove("NO_COLOR")
+        .env_remove("CLICOLOR_FORCE")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap()
+}
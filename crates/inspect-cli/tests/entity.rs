@@ -0,0 +1,68 @@
+mod common;
+
+use common::inspect;
+use tempfile::TempDir;
+
+/// `helper` changes in both a.rs and b.rs; `run` changes in a.rs.
+fn repo() -> TempDir {
+    common::repo(&[
+        &[
+            ("a.rs", "fn helper() -> u32 {\n    1\n}\n\nfn run() -> u32 {\n    helper()\n}\n"),
+            ("b.rs", "fn helper() -> u32 {\n    2\n}\n"),
+        ],
+        &[
+            ("a.rs", "fn helper() -> u32 {\n    10\n}\n\nfn run() -> u32 {\n    helper() + 1\n}\n"),
+            ("b.rs", "fn helper() -> u32 {\n    20\n}\n"),
+        ],
+    ])
+}
+
+fn json(out: &std::process::Output) -> serde_json::Value {
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    serde_json::from_slice(&out.stdout).unwrap()
+}
+
+#[test]
+fn shows_one_entity_with_its_diff() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["entity", "run", "--target", "HEAD", "--format", "json"]);
+    let entity = json(&out);
+    assert_eq!(entity["name"], "run");
+    assert_eq!(entity["file"], "a.rs");
+    assert_eq!(entity["change_type"], "modified");
+    assert!(entity["diff"].as_str().unwrap().contains("+    helper() + 1"));
+    assert_eq!(entity["added"], 1);
+    assert_eq!(entity["removed"], 1);
+}
+
+#[test]
+fn file_picks_between_same_named_entities() {
+    let tmp = repo();
+    let out = inspect(
+        tmp.path(),
+        &["entity", "helper", "--target", "HEAD", "--file", "b.rs", "--format", "json"],
+    );
+    let entity = json(&out);
+    assert_eq!(entity["file"], "b.rs");
+    assert!(entity["diff"].as_str().unwrap().contains("+    20"));
+}
+
+#[test]
+fn ambiguous_names_list_the_candidates() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["entity", "helper", "--target", "HEAD"]);
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("ambiguous"), "{}", stderr);
+    assert!(stderr.contains("a.rs") && stderr.contains("b.rs"), "{}", stderr);
+}
+
+#[test]
+fn unknown_names_suggest_near_misses() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["entity", "helpr", "--target", "HEAD"]);
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("entity 'helpr' not found"), "{}", stderr);
+    assert!(stderr.contains("did you mean: helper"), "{}", stderr);
+}
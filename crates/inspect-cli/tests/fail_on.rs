@@ -0,0 +1,45 @@
+mod common;
+
+use common::inspect;
+use tempfile::TempDir;
+
+/// A repo whose last commit adds one function.
+fn repo() -> TempDir {
+    common::repo(&[
+        &[("main.rs", "")],
+        &[("main.rs", "fn hello() {\n    println!(\"hello\");\n}\n")],
+    ])
+}
+
+#[test]
+fn passes_when_nothing_meets_the_threshold() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["diff", "HEAD", "--fail-on", "critical"]);
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+}
+
+#[test]
+fn fails_with_code_2_when_an_entity_meets_it() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["diff", "HEAD", "--fail-on", "low"]);
+    assert_eq!(out.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("1 entities at or above low"), "{}", stderr);
+}
+
+#[test]
+fn min_risk_does_not_hide_entities_from_the_gate() {
+    let tmp = repo();
+    let out = inspect(
+        tmp.path(),
+        &["diff", "HEAD", "--min-risk", "critical", "--fail-on", "low"],
+    );
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn operational_errors_keep_code_1() {
+    let tmp = repo();
+    let out = inspect(tmp.path(), &["diff", "nope...HEAD", "--fail-on", "low"]);
+    assert_eq!(out.status.code(), Some(1));
+}
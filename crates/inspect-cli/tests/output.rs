@@ -0,0 +1,70 @@
+mod common;
+
+use common::{inspect, inspect_with_env};
+use tempfile::TempDir;
+
+fn repo() -> TempDir {
+    common::repo(&[
+        &[("main.rs", "")],
+        &[("main.rs", "fn hello() {\n    println!(\"hello\");\n}\n")],
+    ])
+}
+
+#[test]
+fn report_goes_to_the_file_not_stdout() {
+    let tmp = repo();
+    let path = tmp.path().join("report.json");
+    let out = inspect(tmp.path(), &["diff", "HEAD", "--format", "json", "--output", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(out.stdout.is_empty());
+    let report: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(report["entity_reviews"].is_array(), "{}", report);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("wrote "));
+}
+
+#[test]
+fn terminal_reports_in_files_have_no_colors() {
+    let tmp = repo();
+    let path = tmp.path().join("report.txt");
+    let out = inspect_with_env(
+        tmp.path(),
+        &["diff", "HEAD", "--output", path.to_str().unwrap()],
+        &[("CLICOLOR_FORCE", "1")],
+    );
+    assert_eq!(out.status.code(), Some(0), "{}", String::from_utf8_lossy(&out.stderr));
+    let report = std::fs::read_to_string(&path).unwrap();
+    assert!(!report.is_empty());
+    assert!(!report.contains('\x1b'), "{:?}", report);
+}
+
+#[test]
+fn failed_runs_leave_the_old_report_alone() {
+    let tmp = repo();
+    let path = tmp.path().join("report.json");
+    std::fs::write(&path, "previous").unwrap();
+    let out = inspect(tmp.path(), &["diff", "nope...HEAD", "--output", path.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous");
+    let leftovers: Vec<_> = std::fs::read_dir(tmp.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .filter(|n| n.to_string_lossy().starts_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+}
+
+#[test]
+fn quiet_silences_progress_but_not_errors() {
+    let tmp = repo();
+    let loud = inspect(tmp.path(), &["bench"]);
+    assert!(String::from_utf8_lossy(&loud.stderr).contains("inspect bench: analyzing"));
+
+    let quiet = inspect(tmp.path(), &["bench", "--quiet", "--progress", "json"]);
+    assert_eq!(quiet.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&quiet.stderr), "");
+    assert!(!quiet.stdout.is_empty(), "the report still prints");
+
+    let failed = inspect(tmp.path(), &["--quiet", "diff", "nope...HEAD"]);
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("error:"));
+}
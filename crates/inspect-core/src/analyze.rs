@@ -1,74 +1,305 @@
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
+use std::sync::Arc;
 
 use sem_core::git::bridge::GitBridge;
 use sem_core::git::types::{DiffScope, FileChange, FileStatus};
-use sem_core::model::change::ChangeType;
+use sem_core::model::change::{ChangeType, SemanticChange};
 use sem_core::parser::differ::compute_semantic_diff;
 use sem_core::parser::graph::EntityGraph;
 use sem_core::parser::plugins::create_default_registry;
 
 use crate::classify::classify_change;
+use crate::context::{attach_context, ContextBudget};
 use crate::github::FilePair;
-use crate::risk::{compute_risk_score, is_public_api, score_to_level};
+use crate::graph::{build_entity_graph, cached_graph, neighborhood_files, GraphConfig};
+use crate::graph_cache::GraphCache;
+use crate::moves::match_moves;
+use crate::noise::{is_test_item, is_test_path, rust_test_modules, NoiseFilter};
+use crate::risk::{is_public_api, RiskConfig};
 use crate::types::*;
-use crate::untangle::untangle;
+use crate::untangle::{colocation_edges, entity_edges, rank_groups, summarize_groups, untangle};
+
+/// A stage of the analysis pipeline, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Diff,
+    ListFiles,
+    Graph,
+    Scoring,
+}
+
+impl Phase {
+    /// Number of phases, for callers mapping progress onto a single scale.
+    pub const COUNT: usize = 4;
+
+    /// Zero-based position of the phase in the pipeline.
+    pub fn index(self) -> usize {
+        match self {
+            Self::Diff => 0,
+            Self::ListFiles => 1,
+            Self::Graph => 2,
+            Self::Scoring => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Diff => write!(f, "diff"),
+            Self::ListFiles => write!(f, "list_files"),
+            Self::Graph => write!(f, "graph"),
+            Self::Scoring => write!(f, "scoring"),
+        }
+    }
+}
+
+/// Progress update from the analysis pipeline: `done` of `total` units of `phase`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    pub done: usize,
+    pub total: usize,
+}
 
-/// Analyze a diff scope and produce a ReviewResult.
+/// Scoring progress is reported roughly this many times per run.
+const SCORING_REPORTS: usize = 20;
+
+/// Changed files larger than this (either side) are skipped unless
+/// [`AnalyzeOptions::max_file_bytes`] says otherwise. Mostly minified bundles.
+pub const DEFAULT_MAX_FILE_BYTES: usize = 1 << 20;
+
+/// Leading bytes checked for a NUL, as git does, to spot binary content.
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+/// What to leave out of an analysis beyond the defaults.
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzeOptions {
+    /// Extra gitignore-style noise patterns, taking precedence over
+    /// `.inspectignore`
+    pub ignore: Vec<String>,
+    /// Keep entities in generated files instead of suppressing them
+    pub include_generated: bool,
+    /// Attach the source of related entities to each review, within this
+    /// budget
+    pub context: Option<ContextBudget>,
+    /// Which source files go into the entity graph
+    pub graph_scope: GraphScope,
+    /// Load and store full graphs of clean trees here, across processes
+    pub graph_cache: Option<GraphCache>,
+    /// Skip changed files larger than this, with a warning; None means
+    /// [`DEFAULT_MAX_FILE_BYTES`]
+    pub max_file_bytes: Option<usize>,
+    /// Without a graph, group entities changed in the same file together
+    /// instead of leaving each in its own group
+    pub group_by_file: bool,
+}
+
+impl AnalyzeOptions {
+    fn max_file_bytes(&self) -> usize {
+        self.max_file_bytes.unwrap_or(DEFAULT_MAX_FILE_BYTES)
+    }
+}
+
+/// How much of the repo the entity graph is built from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphScope {
+    /// Every source file, so blast radius and dependents are exact
+    #[default]
+    Full,
+    /// The changed files and the files that mention their entities by name,
+    /// following mentions out `hops` times. Much faster on large repos, but
+    /// blast radius and dependents are lower bounds and the graph is never
+    /// cached.
+    Neighborhood { hops: usize },
+}
+
+/// Analyze a diff scope and produce a ReviewResult. Noise files, per the
+/// built-in rules and the repo's `.inspectignore`, are left out, as are
+/// entities in generated files.
 pub fn analyze(repo_path: &Path, scope: DiffScope) -> Result<ReviewResult, AnalyzeError> {
+    run_analysis(repo_path, scope, &AnalyzeOptions::default(), &|_| {})
+}
+
+/// Like [`analyze`], with `options` adjusting what is left out.
+pub fn analyze_with_options(
+    repo_path: &Path,
+    scope: DiffScope,
+    options: &AnalyzeOptions,
+) -> Result<ReviewResult, AnalyzeError> {
+    run_analysis(repo_path, scope, options, &|_| {})
+}
+
+/// Like [`analyze_with_options`], calling `progress` at the start and end
+/// of each phase and periodically while scoring entities.
+pub fn analyze_with_progress(
+    repo_path: &Path,
+    scope: DiffScope,
+    options: &AnalyzeOptions,
+    progress: &dyn Fn(Progress),
+) -> Result<ReviewResult, AnalyzeError> {
+    run_analysis(repo_path, scope, options, progress)
+}
+
+fn run_analysis(
+    repo_path: &Path,
+    scope: DiffScope,
+    options: &AnalyzeOptions,
+    progress: &dyn Fn(Progress),
+) -> Result<ReviewResult, AnalyzeError> {
     use std::time::Instant;
 
     let total_start = Instant::now();
+    let report = |phase, done, total| progress(Progress { phase, done, total });
     let git = GitBridge::open(repo_path).map_err(|e| AnalyzeError::Git(e.to_string()))?;
+    let risk = RiskConfig::load(git.repo_root()).map_err(AnalyzeError::Config)?;
+    let graph_config = GraphConfig::load(git.repo_root()).map_err(AnalyzeError::Config)?;
+    let noise = noise_filter(git.repo_root(), &options.ignore)?;
     let registry = create_default_registry();
 
-    // Get file changes
-    let file_changes = git
+    // Get file changes, minus noise
+    let mut file_changes = git
         .get_changed_files(&scope)
         .map_err(|e| AnalyzeError::Git(e.to_string()))?;
+    file_changes.retain(|c| !noise.is_noise_file(&c.file_path));
 
     if file_changes.is_empty() {
         return Ok(empty_result());
     }
+    let mut warnings = skip_unparseable(&mut file_changes, options.max_file_bytes());
 
     // Phase 1: Compute entity-level diff
     let diff_start = Instant::now();
+    let changed_files = file_changes.len();
+    report(Phase::Diff, 0, changed_files);
     let diff = compute_semantic_diff(&file_changes, &registry, None, None);
+    report(Phase::Diff, changed_files, changed_files);
     let diff_ms = diff_start.elapsed().as_millis() as u64;
+    warnings.extend(coverage_warnings(&file_changes, &diff.changes, &graph_config));
+    let changes = match_moves(diff.changes);
+    let (changes, generated_entities) = drop_generated(changes, &file_changes, &noise, options.include_generated);
 
-    if diff.changes.is_empty() {
-        return Ok(empty_result());
+    if changes.is_empty() {
+        return Ok(generated_only(warnings, generated_entities));
     }
 
-    // Phase 2: List all source files in the repo
+    // Phase 2: List the source files in the repo, or in the neighborhood
+    // of the changes
     let list_start = Instant::now();
-    let all_files = list_source_files(repo_path)?;
-    let file_count = all_files.len();
+    report(Phase::ListFiles, 0, 1);
+    let sources = list_source_files(repo_path, &graph_config)?;
+    let graph_files = match options.graph_scope {
+        GraphScope::Full => sources.files,
+        GraphScope::Neighborhood { hops } => {
+            let changed_files: Vec<&str> = file_changes.iter().map(|c| c.file_path.as_str()).collect();
+            let names = changes.iter().map(|c| c.entity_name.clone()).collect();
+            neighborhood_files(repo_path, &sources.files, &changed_files, names, hops, &registry)?
+        }
+    };
+    let file_count = graph_files.len();
+    report(Phase::ListFiles, 1, 1);
     let list_files_ms = list_start.elapsed().as_millis() as u64;
 
-    let changed_entity_ids: HashSet<&str> = diff.changes.iter().map(|c| c.entity_id.as_str()).collect();
-
-    // Phase 3: Build entity graph from ALL source files (parallel via rayon)
+    // Phase 3: Build entity graph from the listed files (parallel via rayon),
+    // or reuse the full one from an earlier call on the same working tree
     let graph_start = Instant::now();
-    let graph = EntityGraph::build(git.repo_root(), &all_files, &registry);
-    let graph_build_ms = graph_start.elapsed().as_millis() as u64;
+    report(Phase::Graph, 0, file_count);
+    let (graph, graph_cache_hit) = match options.graph_scope {
+        GraphScope::Full => cached_graph(git.repo_root(), &graph_files, &registry, options.graph_cache.as_ref()),
+        GraphScope::Neighborhood { .. } => (
+            Arc::new(build_entity_graph(git.repo_root(), &graph_files, &registry)),
+            false,
+        ),
+    };
+    report(Phase::Graph, file_count, file_count);
+    let graph_ms = graph_start.elapsed().as_millis() as u64;
+    let (graph_build_ms, graph_lookup_ms) = if graph_cache_hit {
+        (0, graph_ms)
+    } else {
+        (graph_ms, 0)
+    };
     let total_graph_entities = graph.entities.len();
 
     // Phase 4: Score, classify, untangle
     let scoring_start = Instant::now();
+    let files = FileContext::new(&file_changes);
+    let (mut reviews, groups, edges) = score_with_graph(&changes, &graph, &files, &risk, &|done, total| {
+        report(Phase::Scoring, done, total)
+    });
+    if let Some(budget) = &options.context {
+        let root = git.repo_root();
+        attach_context(&mut reviews, &graph, budget, &|path| {
+            files
+                .content(path)
+                .map(String::from)
+                .or_else(|| std::fs::read_to_string(root.join(path)).ok())
+        });
+    }
+    let scoring_ms = scoring_start.elapsed().as_millis() as u64;
+    let total_ms = total_start.elapsed().as_millis() as u64;
+
+    let mut stats = compute_stats(&reviews);
+    stats.generated_entities = generated_entities;
+
+    let timing = Timing {
+        diff_ms,
+        list_files_ms,
+        file_count,
+        excluded_file_count: sources.excluded,
+        graph_build_ms,
+        graph_cache_hit,
+        graph_lookup_ms,
+        graph_entity_count: total_graph_entities,
+        scoring_ms,
+        total_ms,
+    };
+
+    Ok(ReviewResult {
+        entity_reviews: reviews,
+        groups,
+        edges,
+        blast_radius_exact: options.graph_scope == GraphScope::Full,
+        stats,
+        timing,
+        warnings,
+        changes,
+    })
+}
+
+/// Score each change against the entity graph, then group changes connected
+/// by dependency edges, which are returned too. Reviews are sorted by risk
+/// score, highest first. `report(done, total)` is called periodically while
+/// scoring.
+fn score_with_graph(
+    changes: &[SemanticChange],
+    graph: &EntityGraph,
+    files: &FileContext,
+    risk: &RiskConfig,
+    report: &dyn Fn(usize, usize),
+) -> (Vec<EntityReview>, Vec<ChangeGroup>, Vec<EntityEdge>) {
+    let changed_entity_ids: HashSet<&str> = changes.iter().map(|c| c.entity_id.as_str()).collect();
+    let total_graph_entities = graph.entities.len();
+
+    let entity_count = changes.len();
+    let report_every = (entity_count / SCORING_REPORTS).max(1);
+    report(0, entity_count);
 
     let mut reviews: Vec<EntityReview> = Vec::new();
     let mut dependency_edges: Vec<(String, String)> = Vec::new();
 
-    for change in &diff.changes {
+    for (i, change) in changes.iter().enumerate() {
+        if i > 0 && i % report_every == 0 {
+            report(i, entity_count);
+        }
+
         let dependents = graph.get_dependents(&change.entity_id);
         let dependencies = graph.get_dependencies(&change.entity_id);
         // Use capped impact count to avoid full BFS on hub entities
         let blast_radius = graph.impact_count(&change.entity_id, 10_000);
 
         let classification = classify_change(change);
-        let after_content_ref = change.after_content.as_deref();
-        let pub_api = is_public_api(&change.entity_type, &change.entity_name, after_content_ref);
+        let pub_api = files.is_public_api(change);
 
         let (start_line, end_line) = graph
             .entities
@@ -90,6 +321,7 @@ pub fn analyze(repo_path: &Path, scope: DiffScope) -> Result<ReviewResult, Analy
             entity_name: change.entity_name.clone(),
             entity_type: change.entity_type.clone(),
             file_path: change.file_path.clone(),
+            old_file_path: change.old_file_path.clone(),
             change_type: change.change_type,
             classification,
             risk_score: 0.0,
@@ -98,6 +330,7 @@ pub fn analyze(repo_path: &Path, scope: DiffScope) -> Result<ReviewResult, Analy
             dependent_count: dependents.len(),
             dependency_count: dependencies.len(),
             is_public_api: pub_api,
+            is_test: files.is_test(change, start_line),
             structural_change: change.structural_change,
             group_id: 0,
             start_line,
@@ -106,10 +339,12 @@ pub fn analyze(repo_path: &Path, scope: DiffScope) -> Result<ReviewResult, Analy
             after_content: change.after_content.clone(),
             dependent_names,
             dependency_names,
+            risk_rule: None,
+            score_components: None,
+            context_snippets: vec![],
         };
 
-        review.risk_score = compute_risk_score(&review, total_graph_entities);
-        review.risk_level = score_to_level(review.risk_score);
+        risk.assess(&mut review, total_graph_entities);
 
         for dep in &dependencies {
             if changed_entity_ids.contains(dep.id.as_str()) {
@@ -118,7 +353,7 @@ pub fn analyze(repo_path: &Path, scope: DiffScope) -> Result<ReviewResult, Analy
         }
         for dep in &dependents {
             if changed_entity_ids.contains(dep.id.as_str()) {
-                dependency_edges.push((change.entity_id.clone(), dep.id.clone()));
+                dependency_edges.push((dep.id.clone(), change.entity_id.clone()));
             }
         }
 
@@ -140,42 +375,60 @@ pub fn analyze(repo_path: &Path, scope: DiffScope) -> Result<ReviewResult, Analy
         }
     }
 
-    let scoring_ms = scoring_start.elapsed().as_millis() as u64;
-    let total_ms = total_start.elapsed().as_millis() as u64;
-
-    let stats = compute_stats(&reviews);
+    report(entity_count, entity_count);
 
-    let timing = Timing {
-        diff_ms,
-        list_files_ms,
-        file_count,
-        graph_build_ms,
-        graph_entity_count: total_graph_entities,
-        scoring_ms,
-        total_ms,
-    };
+    (reviews, groups, entity_edges(&dependency_edges, EdgeKind::Dependency))
+}
 
-    Ok(ReviewResult {
-        entity_reviews: reviews,
-        groups,
-        stats,
-        timing,
-        changes: diff.changes,
-    })
+/// Analyze a local diff scope without building the entity graph. Much faster
+/// on large repos; blast_radius and dependent_count will be 0. `options` is
+/// honoured except for what only the graph uses (context, graph scope and
+/// cache).
+pub fn analyze_fast(repo_path: &Path, scope: DiffScope, options: &AnalyzeOptions) -> Result<ReviewResult, AnalyzeError> {
+    let git = GitBridge::open(repo_path).map_err(|e| AnalyzeError::Git(e.to_string()))?;
+    let risk = RiskConfig::load(git.repo_root()).map_err(AnalyzeError::Config)?;
+    let noise = noise_filter(git.repo_root(), &options.ignore)?;
+    let mut file_changes = git
+        .get_changed_files(&scope)
+        .map_err(|e| AnalyzeError::Git(e.to_string()))?;
+    file_changes.retain(|c| !noise.is_noise_file(&c.file_path));
+    let file_count = file_changes.len();
+    Ok(analyze_without_graph(file_changes, file_count, &risk, &noise, options))
 }
 
 /// Analyze file pairs fetched from a remote source (e.g. GitHub API).
-/// No local git repo or graph needed. Gets entity-level granularity,
-/// ConGra classification, public API detection, and risk scoring
-/// (blast_radius and dependent_count will be 0 since no graph is available).
-pub fn analyze_remote(file_pairs: &[FilePair]) -> Result<ReviewResult, AnalyzeError> {
+/// No local git repo needed. The entity graph is built from the new contents
+/// of the changed files plus `extra_context` (other repo files as
+/// `(path, content)`, e.g. modules the patches import), so edges between
+/// changed entities are scored and grouped. Blast radius and dependents only
+/// count entities within that scope. Entities in files with a generated-code
+/// marker are left out.
+pub fn analyze_remote(
+    file_pairs: &[FilePair],
+    extra_context: &[(String, String)],
+) -> Result<ReviewResult, AnalyzeError> {
+    analyze_remote_with_options(file_pairs, extra_context, &AnalyzeOptions::default())
+}
+
+/// Like [`analyze_remote`], with `options` adjusting what is left out. There
+/// is no checkout, so `.inspectignore` and `.gitattributes` don't apply.
+pub fn analyze_remote_with_options(
+    file_pairs: &[FilePair],
+    extra_context: &[(String, String)],
+    options: &AnalyzeOptions,
+) -> Result<ReviewResult, AnalyzeError> {
     use std::time::Instant;
 
     let total_start = Instant::now();
     let registry = create_default_registry();
+    let mut noise = NoiseFilter::default();
+    noise
+        .extend(options.ignore.iter().map(String::as_str))
+        .map_err(AnalyzeError::Config)?;
 
-    let file_changes: Vec<FileChange> = file_pairs
+    let mut file_changes: Vec<FileChange> = file_pairs
         .iter()
+        .filter(|fp| !noise.is_noise_file(&fp.filename))
         .map(|fp| {
             let status = match fp.status.as_str() {
                 "added" => FileStatus::Added,
@@ -186,7 +439,7 @@ pub fn analyze_remote(file_pairs: &[FilePair]) -> Result<ReviewResult, AnalyzeEr
             FileChange {
                 file_path: fp.filename.clone(),
                 status,
-                old_file_path: None,
+                old_file_path: fp.previous_filename.clone(),
                 before_content: fp.before_content.clone(),
                 after_content: fp.after_content.clone(),
             }
@@ -196,29 +449,166 @@ pub fn analyze_remote(file_pairs: &[FilePair]) -> Result<ReviewResult, AnalyzeEr
     if file_changes.is_empty() {
         return Ok(empty_result());
     }
+    let mut warnings = skip_unparseable(&mut file_changes, options.max_file_bytes());
 
     let diff_start = Instant::now();
     let diff = compute_semantic_diff(&file_changes, &registry, None, None);
     let diff_ms = diff_start.elapsed().as_millis() as u64;
+    warnings.extend(coverage_warnings(&file_changes, &diff.changes, &GraphConfig::default()));
+    let changes = match_moves(diff.changes);
+    let (changes, generated_entities) = drop_generated(changes, &file_changes, &noise, options.include_generated);
 
-    if diff.changes.is_empty() {
-        return Ok(empty_result());
+    if changes.is_empty() {
+        return Ok(generated_only(warnings, generated_entities));
     }
 
+    // Scoped graph: changed files first, so they win over duplicate context paths
+    let graph_start = Instant::now();
+    let sources: Vec<(&str, &str)> = file_pairs
+        .iter()
+        .filter_map(|fp| fp.after_content.as_deref().map(|c| (fp.filename.as_str(), c)))
+        .chain(extra_context.iter().map(|(path, content)| (path.as_str(), content.as_str())))
+        .collect();
+    let scratch = ScratchTree::write(&sources)?;
+    let graph = build_entity_graph(scratch.root(), scratch.files(), &registry);
+    let file_count = scratch.files().len();
+    drop(scratch);
+    let graph_build_ms = graph_start.elapsed().as_millis() as u64;
+
     let scoring_start = Instant::now();
+    let files = FileContext::new(&file_changes);
+    let (mut reviews, groups, edges) =
+        score_with_graph(&changes, &graph, &files, &RiskConfig::default(), &|_, _| {});
+    if let Some(budget) = &options.context {
+        let contents: HashMap<&str, &str> = sources.iter().rev().copied().collect();
+        attach_context(&mut reviews, &graph, budget, &|path| contents.get(path).map(|c| c.to_string()));
+    }
+    let scoring_ms = scoring_start.elapsed().as_millis() as u64;
+
+    let mut stats = compute_stats(&reviews);
+    stats.generated_entities = generated_entities;
+    let timing = Timing {
+        diff_ms,
+        list_files_ms: 0,
+        file_count,
+        excluded_file_count: 0,
+        graph_build_ms,
+        graph_cache_hit: false,
+        graph_lookup_ms: 0,
+        graph_entity_count: graph.entities.len(),
+        scoring_ms,
+        total_ms: total_start.elapsed().as_millis() as u64,
+    };
 
+    Ok(ReviewResult {
+        entity_reviews: reviews,
+        groups,
+        edges,
+        blast_radius_exact: false,
+        stats,
+        timing,
+        warnings,
+        changes,
+    })
+}
+
+/// Source files written to a fresh private temporary directory so the graph
+/// builder can read them like a checkout. Removed on drop.
+struct ScratchTree {
+    root: tempfile::TempDir,
+    files: Vec<String>,
+}
+
+impl ScratchTree {
+    /// Write `(path, content)` pairs. Non-source files, paths that would
+    /// escape the directory, and repeated paths are skipped.
+    fn write(sources: &[(&str, &str)]) -> Result<Self, AnalyzeError> {
+        use std::path::Component;
+
+        let root = tempfile::Builder::new()
+            .prefix("inspect-remote-")
+            .tempdir()
+            .map_err(|e| AnalyzeError::Io(format!("creating a temporary directory: {}", e)))?;
+        let io_err = |e: std::io::Error| AnalyzeError::Io(format!("{}: {}", root.path().display(), e));
+        let mut files = Vec::new();
+
+        let mut seen = HashSet::new();
+        for (path, content) in sources {
+            let safe = Path::new(path).components().all(|c| matches!(c, Component::Normal(_)));
+            if !safe || !is_source_file(path) || !seen.insert(*path) {
+                continue;
+            }
+            let dest = root.path().join(path);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent).map_err(io_err)?;
+            }
+            std::fs::write(&dest, content).map_err(io_err)?;
+            files.push(path.to_string());
+        }
+        Ok(Self { root, files })
+    }
+
+    fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    fn files(&self) -> &[String] {
+        &self.files
+    }
+}
+
+/// Diff, classify, score, and untangle file changes with no entity graph.
+/// With [`AnalyzeOptions::group_by_file`], entities in the same file share a
+/// group; otherwise each entity is its own.
+fn analyze_without_graph(
+    mut file_changes: Vec<FileChange>,
+    file_count: usize,
+    risk: &RiskConfig,
+    noise: &NoiseFilter,
+    options: &AnalyzeOptions,
+) -> ReviewResult {
+    use std::time::Instant;
+
+    let total_start = Instant::now();
+    let registry = create_default_registry();
+
+    if file_changes.is_empty() {
+        return empty_result();
+    }
+    let mut warnings = skip_unparseable(&mut file_changes, options.max_file_bytes());
+
+    let diff_start = Instant::now();
+    let diff = compute_semantic_diff(&file_changes, &registry, None, None);
+    let diff_ms = diff_start.elapsed().as_millis() as u64;
+    warnings.extend(coverage_warnings(&file_changes, &diff.changes, &GraphConfig::default()));
+    let changes = match_moves(diff.changes);
+    let (changes, generated_entities) =
+        drop_generated(changes, &file_changes, noise, options.include_generated);
+
+    if changes.is_empty() {
+        return generated_only(warnings, generated_entities);
+    }
+    warnings.push(AnalysisWarning {
+        kind: WarningKind::GraphSkipped,
+        file_path: None,
+        message: "entity graph not built; blast radius and dependents are 0".into(),
+    });
+
+    let scoring_start = Instant::now();
+
+    let files = FileContext::new(&file_changes);
     let mut reviews: Vec<EntityReview> = Vec::new();
 
-    for change in &diff.changes {
+    for change in &changes {
         let classification = classify_change(change);
-        let after_content_ref = change.after_content.as_deref();
-        let pub_api = is_public_api(&change.entity_type, &change.entity_name, after_content_ref);
+        let pub_api = files.is_public_api(change);
 
         let mut review = EntityReview {
             entity_id: change.entity_id.clone(),
             entity_name: change.entity_name.clone(),
             entity_type: change.entity_type.clone(),
             file_path: change.file_path.clone(),
+            old_file_path: change.old_file_path.clone(),
             change_type: change.change_type,
             classification,
             risk_score: 0.0,
@@ -227,6 +617,7 @@ pub fn analyze_remote(file_pairs: &[FilePair]) -> Result<ReviewResult, AnalyzeEr
             dependent_count: 0,
             dependency_count: 0,
             is_public_api: pub_api,
+            is_test: files.is_test(change, 0),
             structural_change: change.structural_change,
             group_id: 0,
             start_line: 0,
@@ -235,17 +626,21 @@ pub fn analyze_remote(file_pairs: &[FilePair]) -> Result<ReviewResult, AnalyzeEr
             after_content: change.after_content.clone(),
             dependent_names: vec![],
             dependency_names: vec![],
+            risk_rule: None,
+            score_components: None,
+            context_snippets: vec![],
         };
 
-        review.risk_score = compute_risk_score(&review, 0);
-        review.risk_level = score_to_level(review.risk_score);
+        risk.assess(&mut review, 0);
 
         reviews.push(review);
     }
 
     reviews.sort_by(|a, b| b.risk_score.partial_cmp(&a.risk_score).unwrap());
 
-    let groups = untangle(&reviews, &[]);
+    let colocated = if options.group_by_file { colocation_edges(&reviews) } else { vec![] };
+    let groups = untangle(&reviews, &colocated);
+    let edges = entity_edges(&colocated, EdgeKind::SameFile);
 
     let entity_to_group: HashMap<&str, usize> = groups
         .iter()
@@ -261,28 +656,235 @@ pub fn analyze_remote(file_pairs: &[FilePair]) -> Result<ReviewResult, AnalyzeEr
     let scoring_ms = scoring_start.elapsed().as_millis() as u64;
     let total_ms = total_start.elapsed().as_millis() as u64;
 
-    let stats = compute_stats(&reviews);
+    let mut stats = compute_stats(&reviews);
+    stats.generated_entities = generated_entities;
 
     let timing = Timing {
         diff_ms,
         list_files_ms: 0,
-        file_count: file_pairs.len(),
+        file_count,
+        excluded_file_count: 0,
         graph_build_ms: 0,
+        graph_cache_hit: false,
+        graph_lookup_ms: 0,
         graph_entity_count: 0,
         scoring_ms,
         total_ms,
     };
 
-    Ok(ReviewResult {
+    ReviewResult {
         entity_reviews: reviews,
         groups,
+        edges,
+        blast_radius_exact: false,
         stats,
         timing,
-        changes: diff.changes,
-    })
+        warnings,
+        changes,
+    }
+}
+
+/// Split off changes in generated files unless `include` is set. Returns the
+/// changes to review and how many were dropped.
+fn drop_generated(
+    changes: Vec<SemanticChange>,
+    file_changes: &[FileChange],
+    noise: &NoiseFilter,
+    include: bool,
+) -> (Vec<SemanticChange>, usize) {
+    if include {
+        return (changes, 0);
+    }
+    let generated: HashSet<&str> = file_changes
+        .iter()
+        .filter(|f| {
+            let content = f.after_content.as_deref().or(f.before_content.as_deref()).unwrap_or("");
+            noise.is_generated(&f.file_path, content)
+        })
+        .map(|f| f.file_path.as_str())
+        .collect();
+    if generated.is_empty() {
+        return (changes, 0);
+    }
+    let before = changes.len();
+    let kept: Vec<SemanticChange> = changes
+        .into_iter()
+        .filter(|c| !generated.contains(c.file_path.as_str()))
+        .collect();
+    let dropped = before - kept.len();
+    (kept, dropped)
+}
+
+/// Result for a diff whose only entities were in generated files.
+fn generated_only(warnings: Vec<AnalysisWarning>, generated_entities: usize) -> ReviewResult {
+    let mut result = ReviewResult {
+        warnings,
+        ..empty_result()
+    };
+    result.stats.generated_entities = generated_entities;
+    result
+}
+
+/// File-level facts about the changed files that entity scoring needs: the
+/// new contents, and the line ranges of Rust `#[cfg(test)]` modules in them.
+struct FileContext<'a> {
+    contents: HashMap<&'a str, &'a str>,
+    rust_test_modules: HashMap<&'a str, Vec<(usize, usize)>>,
+}
+
+impl<'a> FileContext<'a> {
+    fn new(file_changes: &'a [FileChange]) -> Self {
+        let contents: HashMap<&str, &str> = file_changes
+            .iter()
+            .filter_map(|fc| Some((fc.file_path.as_str(), fc.after_content.as_deref()?)))
+            .collect();
+        let rust_test_modules = contents
+            .iter()
+            .filter(|(path, _)| path.ends_with(".rs"))
+            .filter_map(|(&path, &content)| {
+                let ranges = rust_test_modules(content);
+                (!ranges.is_empty()).then_some((path, ranges))
+            })
+            .collect();
+        Self {
+            contents,
+            rust_test_modules,
+        }
+    }
+
+    /// New content of a changed file, if it still exists.
+    fn content(&self, path: &str) -> Option<&'a str> {
+        self.contents.get(path).copied()
+    }
+
+    /// Whether a change is test code. `start_line` is 0 when unknown, in which
+    /// case only the path and the entity's own attributes are checked.
+    fn is_test(&self, change: &SemanticChange, start_line: usize) -> bool {
+        if is_test_path(&change.file_path) {
+            return true;
+        }
+        let content = change.after_content.as_deref().or(change.before_content.as_deref());
+        if content.is_some_and(is_test_item) {
+            return true;
+        }
+        start_line > 0
+            && self
+                .rust_test_modules
+                .get(change.file_path.as_str())
+                .is_some_and(|ranges| ranges.iter().any(|&(s, e)| s <= start_line && start_line <= e))
+    }
+
+    /// Whether a change is public API, judged from its own source and, where
+    /// the language needs it, the rest of the file.
+    fn is_public_api(&self, change: &SemanticChange) -> bool {
+        is_public_api(
+            &change.file_path,
+            &change.entity_type,
+            &change.entity_name,
+            change.after_content.as_deref(),
+            self.content(&change.file_path),
+        )
+    }
 }
 
-pub(crate) fn compute_stats(reviews: &[EntityReview]) -> ReviewStats {
+/// Take the changed files the parsers shouldn't see out of `file_changes`:
+/// binary content, and content over `max_bytes` on either side. Returns a
+/// warning for each.
+fn skip_unparseable(file_changes: &mut Vec<FileChange>, max_bytes: usize) -> Vec<AnalysisWarning> {
+    let mut warnings = Vec::new();
+    file_changes.retain(|f| {
+        let sides = || [f.before_content.as_deref(), f.after_content.as_deref()].into_iter().flatten();
+        let largest = sides().map(str::len).max().unwrap_or(0);
+        let (kind, message) = if sides().any(is_binary) {
+            (WarningKind::Unreadable, format!("{}: binary content, skipped", f.file_path))
+        } else if largest > max_bytes {
+            (
+                WarningKind::TooLarge,
+                format!("{}: {} bytes, over the {} byte limit, skipped", f.file_path, largest, max_bytes),
+            )
+        } else {
+            return true;
+        };
+        warnings.push(AnalysisWarning {
+            kind,
+            file_path: Some(f.file_path.clone()),
+            message,
+        });
+        false
+    });
+    warnings
+}
+
+/// Whether `content` has a NUL byte near the start.
+fn is_binary(content: &str) -> bool {
+    content.as_bytes().iter().take(BINARY_SNIFF_BYTES).any(|&b| b == 0)
+}
+
+/// Warn about changed files that contributed no entities: unreadable ones,
+/// ones of a type no parser handles (per `config`), and source files that
+/// yielded nothing. Deleted files are not expected to have any.
+fn coverage_warnings(
+    file_changes: &[FileChange],
+    changes: &[SemanticChange],
+    config: &GraphConfig,
+) -> Vec<AnalysisWarning> {
+    let with_entities: HashSet<&str> = changes.iter().map(|c| c.file_path.as_str()).collect();
+
+    file_changes
+        .iter()
+        .filter(|f| !matches!(f.status, FileStatus::Deleted) && !with_entities.contains(f.file_path.as_str()))
+        .map(|f| {
+            let (kind, message) = if f.after_content.is_none() {
+                (
+                    WarningKind::Unreadable,
+                    format!("{}: content unavailable (binary or not UTF-8)", f.file_path),
+                )
+            } else if !config.is_source_file(&f.file_path) {
+                (
+                    WarningKind::Unsupported,
+                    format!("{}: no parser for this file type, skipped", f.file_path),
+                )
+            } else {
+                (
+                    WarningKind::NoEntities,
+                    format!(
+                        "{}: changed but no entities extracted (parse failure or edits outside any entity)",
+                        f.file_path
+                    ),
+                )
+            };
+            AnalysisWarning {
+                kind,
+                file_path: Some(f.file_path.clone()),
+                message,
+            }
+        })
+        .collect()
+}
+
+/// Re-score a finished analysis with other risk settings: updates each
+/// entity's score and level, re-sorts by score, and recomputes stats. Group
+/// membership is unchanged since it depends only on the dependency edges;
+/// each group's max risk is refreshed and the groups re-ranked and
+/// renumbered to match.
+pub fn rescore(result: &mut ReviewResult, risk: &RiskConfig) {
+    let total = result.timing.graph_entity_count;
+    for review in &mut result.entity_reviews {
+        risk.assess(review, total);
+    }
+    result
+        .entity_reviews
+        .sort_by(|a, b| b.risk_score.total_cmp(&a.risk_score));
+    summarize_groups(&mut result.groups, &result.entity_reviews);
+    rank_groups(&mut result.groups, &mut result.entity_reviews);
+    result.stats = ReviewStats {
+        generated_entities: result.stats.generated_entities,
+        ..compute_stats(&result.entity_reviews)
+    };
+}
+
+/// Summary statistics over a set of entity reviews.
+pub fn compute_stats<'a>(reviews: impl IntoIterator<Item = &'a EntityReview>) -> ReviewStats {
     let mut by_risk = RiskBreakdown {
         critical: 0,
         high: 0,
@@ -303,7 +905,13 @@ pub(crate) fn compute_stats(reviews: &[EntityReview]) -> ReviewStats {
         renamed: 0,
     };
 
+    let mut total_entities = 0;
+    let mut test_entities = 0;
     for r in reviews {
+        total_entities += 1;
+        if r.is_test {
+            test_entities += 1;
+        }
         match r.risk_level {
             RiskLevel::Critical => by_risk.critical += 1,
             RiskLevel::High => by_risk.high += 1,
@@ -326,15 +934,27 @@ pub(crate) fn compute_stats(reviews: &[EntityReview]) -> ReviewStats {
     }
 
     ReviewStats {
-        total_entities: reviews.len(),
+        total_entities,
+        test_entities,
+        non_test_entities: total_entities - test_entities,
+        generated_entities: 0,
         by_risk,
         by_classification: by_classification,
         by_change_type: by_change,
     }
 }
 
-/// List all tracked source files in the repo via `git ls-files`.
-fn list_source_files(repo_path: &Path) -> Result<Vec<String>, AnalyzeError> {
+/// Tracked source files for the entity graph.
+pub(crate) struct SourceFiles {
+    pub files: Vec<String>,
+    /// Source files dropped by `[graph] exclude_paths`
+    pub excluded: usize,
+}
+
+/// List the tracked source files in the repo via `git ls-files`: those with
+/// a source extension per `config`, minus its excluded paths.
+pub(crate) fn list_source_files(repo_path: &Path, config: &GraphConfig) -> Result<SourceFiles, AnalyzeError> {
+    let excludes = config.excludes().map_err(AnalyzeError::Config)?;
     let output = std::process::Command::new("git")
         .args(["ls-files"])
         .current_dir(repo_path)
@@ -346,36 +966,34 @@ fn list_source_files(repo_path: &Path) -> Result<Vec<String>, AnalyzeError> {
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let files: Vec<String> = stdout
+    let (files, excluded): (Vec<String>, Vec<String>) = stdout
         .lines()
-        .filter(|f| {
-            let f = f.to_lowercase();
-            f.ends_with(".rs")
-                || f.ends_with(".ts")
-                || f.ends_with(".tsx")
-                || f.ends_with(".js")
-                || f.ends_with(".jsx")
-                || f.ends_with(".py")
-                || f.ends_with(".go")
-                || f.ends_with(".java")
-                || f.ends_with(".c")
-                || f.ends_with(".cpp")
-                || f.ends_with(".rb")
-                || f.ends_with(".cs")
-                || f.ends_with(".php")
-        })
+        .filter(|f| config.is_source_file(f))
         .map(|s| s.to_string())
-        .collect();
+        .partition(|f| excludes.matches(f));
 
-    Ok(files)
+    Ok(SourceFiles {
+        files,
+        excluded: excluded.len(),
+    })
+}
+
+/// Whether a path has an extension the entity parsers handle.
+fn is_source_file(path: &str) -> bool {
+    GraphConfig::default().is_source_file(path)
 }
 
 fn empty_result() -> ReviewResult {
     ReviewResult {
         entity_reviews: vec![],
         groups: vec![],
+        edges: vec![],
+        blast_radius_exact: true,
         stats: ReviewStats {
             total_entities: 0,
+            test_entities: 0,
+            non_test_entities: 0,
+            generated_entities: 0,
             by_risk: RiskBreakdown {
                 critical: 0,
                 high: 0,
@@ -397,14 +1015,28 @@ fn empty_result() -> ReviewResult {
             },
         },
         timing: Timing::default(),
+        warnings: vec![],
         changes: vec![],
     }
 }
 
-#[derive(Debug, thiserror::Error)]
+/// The repo's noise rules with `extra` patterns appended.
+fn noise_filter(repo_root: &Path, extra: &[String]) -> Result<NoiseFilter, AnalyzeError> {
+    let mut noise = NoiseFilter::load(repo_root).map_err(AnalyzeError::Config)?;
+    noise
+        .extend(extra.iter().map(String::as_str))
+        .map_err(AnalyzeError::Config)?;
+    Ok(noise)
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum AnalyzeError {
     #[error("git error: {0}")]
     Git(String),
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("config error: {0}")]
+    Config(String),
 }
 
 #[cfg(test)]
@@ -472,6 +1104,198 @@ mod tests {
         assert_eq!(review.classification, ChangeClassification::Functional);
     }
 
+    #[test]
+    fn moved_function_is_reviewed_once() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        let helper = "pub fn normalize(s: &str) -> String {\n    s.trim().to_lowercase()\n}\n";
+        std::fs::write(dir.join("utils.rs"), format!("{}\npub fn keep() {{}}\n", helper)).unwrap();
+        std::fs::write(dir.join("strings.rs"), "").unwrap();
+        commit(dir, "init");
+        std::fs::write(dir.join("utils.rs"), "pub fn keep() {}\n").unwrap();
+        std::fs::write(dir.join("strings.rs"), helper).unwrap();
+        commit(dir, "move normalize");
+
+        let result = analyze(dir, DiffScope::Commit { sha: "HEAD".to_string() }).unwrap();
+        let moved: Vec<&EntityReview> = result
+            .entity_reviews
+            .iter()
+            .filter(|r| r.entity_name == "normalize")
+            .collect();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].change_type, ChangeType::Moved);
+        assert_eq!(moved[0].file_path, "strings.rs");
+        assert_eq!(moved[0].old_file_path.as_deref(), Some("utils.rs"));
+        assert_eq!(moved[0].classification, ChangeClassification::Text);
+        assert_eq!(result.stats.by_change_type.moved, 1);
+        assert_eq!(result.stats.by_change_type.deleted, 0);
+    }
+
+    #[test]
+    fn fast_colocated_never_builds_a_graph() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(dir.join("lib.rs"), "fn a() {}\n\nfn b() {}\n").unwrap();
+        std::fs::write(dir.join("other.rs"), "fn c() {}\n").unwrap();
+        commit(dir, "init");
+        std::fs::write(dir.join("lib.rs"), "fn a() { 1; }\n\nfn b() { 2; }\n").unwrap();
+        std::fs::write(dir.join("other.rs"), "fn c() { 3; }\n").unwrap();
+        commit(dir, "change all three");
+
+        let built = crate::graph::graphs_built();
+        let scope = DiffScope::Commit { sha: "HEAD".to_string() };
+        let colocated = AnalyzeOptions {
+            group_by_file: true,
+            ..Default::default()
+        };
+        let result = analyze_fast(dir, scope.clone(), &colocated).unwrap();
+        assert_eq!(crate::graph::graphs_built(), built, "fast path built an entity graph");
+        assert_eq!(result.entity_reviews.len(), 3);
+        assert_eq!(result.groups.len(), 2, "a and b share lib.rs");
+        assert_eq!(result.edges.len(), 1);
+        assert_eq!(result.edges[0].kind, EdgeKind::SameFile);
+        assert!(result.entity_reviews.iter().all(|r| r.blast_radius == 0));
+
+        let result = analyze_fast(dir, scope.clone(), &AnalyzeOptions::default()).unwrap();
+        assert_eq!(result.groups.len(), 3, "ungrouped by default");
+        assert!(result.edges.is_empty());
+
+        let options = AnalyzeOptions {
+            ignore: vec!["other.rs".into()],
+            ..colocated
+        };
+        let result = analyze_fast(dir, scope.clone(), &options).unwrap();
+        assert_eq!(result.entity_reviews.len(), 2, "ignore patterns apply");
+
+        // The counter does see the full pipeline's graph
+        analyze(dir, scope).unwrap();
+        assert_eq!(crate::graph::graphs_built(), built + 1);
+    }
+
+    #[test]
+    fn staged_scope_excludes_unstaged_changes() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+
+        std::fs::write(dir.join("a.rs"), "fn staged() {}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "fn unstaged() {}\n").unwrap();
+        commit(dir, "init");
+
+        std::fs::write(dir.join("a.rs"), "fn staged() {\n    let x = 1;\n}\n").unwrap();
+        Command::new("git")
+            .args(["add", "a.rs"])
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        std::fs::write(dir.join("b.rs"), "fn unstaged() {\n    let y = 2;\n}\n").unwrap();
+
+        let staged = analyze(dir, DiffScope::Staged).unwrap();
+        let names: Vec<&str> = staged
+            .entity_reviews
+            .iter()
+            .map(|r| r.entity_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["staged"]);
+
+        let working = analyze(dir, DiffScope::Working).unwrap();
+        assert!(working
+            .entity_reviews
+            .iter()
+            .any(|r| r.entity_name == "unstaged"));
+    }
+
+    #[test]
+    fn invalid_repo_config_fails_the_run() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(dir.join("main.rs"), "fn hello() {}\n").unwrap();
+        std::fs::write(dir.join(".inspect.toml"), "[risk.weights]\nblast_radius = -1.0\n").unwrap();
+        commit(dir, "init");
+
+        let err = analyze(dir, DiffScope::Working).unwrap_err();
+        assert!(matches!(err, AnalyzeError::Config(_)), "{}", err);
+        assert!(err.to_string().contains("blast_radius"));
+    }
+
+    #[test]
+    fn neighborhood_graph_finds_nearby_dependents() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(dir.join("target.rs"), "pub fn target() -> i32 {\n    1\n}\n").unwrap();
+        std::fs::write(dir.join("caller.rs"), "fn caller() -> i32 {\n    target() + 1\n}\n").unwrap();
+        std::fs::write(dir.join("top.rs"), "fn top() -> i32 {\n    caller() + 1\n}\n").unwrap();
+        std::fs::write(dir.join("other.rs"), "fn other() -> i32 {\n    2\n}\n").unwrap();
+        commit(dir, "init");
+        std::fs::write(dir.join("target.rs"), "pub fn target() -> i32 {\n    3\n}\n").unwrap();
+
+        let run = |graph_scope| {
+            let options = AnalyzeOptions {
+                graph_scope,
+                ..Default::default()
+            };
+            let result = analyze_with_options(dir, DiffScope::Working, &options).unwrap();
+            let target = result.entity_reviews.iter().find(|r| r.entity_name == "target").unwrap().clone();
+            (result.timing.file_count, target.dependent_count, target.blast_radius, result.blast_radius_exact)
+        };
+        assert_eq!(run(GraphScope::Full), (4, 1, 2, true));
+        assert_eq!(run(GraphScope::Neighborhood { hops: 2 }), (3, 1, 2, false));
+        // One hop still finds the direct caller; the rest is a lower bound
+        assert_eq!(run(GraphScope::Neighborhood { hops: 1 }), (2, 1, 1, false));
+    }
+
+    #[test]
+    fn excluded_paths_stay_out_of_the_graph() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::create_dir_all(dir.join("vendor")).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn hello() {}\n").unwrap();
+        std::fs::write(dir.join("vendor/dep.rs"), "fn dep() {}\n").unwrap();
+        std::fs::write(dir.join(".inspect.toml"), "[graph]\nexclude_paths = [\"vendor/**\"]\n").unwrap();
+        commit(dir, "init");
+        std::fs::write(dir.join("main.rs"), "fn hello() { 1; }\n").unwrap();
+
+        let result = analyze(dir, DiffScope::Working).unwrap();
+        assert_eq!(result.timing.file_count, 1);
+        assert_eq!(result.timing.excluded_file_count, 1);
+    }
+
+    #[test]
+    fn ignored_files_are_not_analyzed() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::create_dir_all(dir.join("gen")).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn hello() {}\n").unwrap();
+        std::fs::write(dir.join("gen/api.rs"), "fn stub() {}\n").unwrap();
+        std::fs::write(dir.join("util.rs"), "fn util() {}\n").unwrap();
+        std::fs::write(dir.join(".inspectignore"), "gen/\n").unwrap();
+        commit(dir, "init");
+        std::fs::write(dir.join("main.rs"), "fn hello() { println!(\"hi\"); }\n").unwrap();
+        std::fs::write(dir.join("gen/api.rs"), "fn stub() { todo!() }\n").unwrap();
+        std::fs::write(dir.join("util.rs"), "fn util() { let _ = 1; }\n").unwrap();
+
+        let files = |r: &ReviewResult| {
+            let mut files: Vec<String> = r.entity_reviews.iter().map(|e| e.file_path.clone()).collect();
+            files.sort();
+            files.dedup();
+            files
+        };
+        let result = analyze(dir, DiffScope::Working).unwrap();
+        assert_eq!(files(&result), vec!["main.rs", "util.rs"]);
+        let options = AnalyzeOptions {
+            ignore: vec!["util.rs".into()],
+            ..Default::default()
+        };
+        let result = analyze_with_options(dir, DiffScope::Working, &options).unwrap();
+        assert_eq!(files(&result), vec!["main.rs"]);
+    }
+
     #[test]
     fn analyze_empty_diff() {
         let tmp = TempDir::new().unwrap();
@@ -492,4 +1316,271 @@ mod tests {
         // depending on whether the initial commit has a parent
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn unparseable_file_is_reported() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+
+        std::fs::write(dir.join("good.rs"), "fn a() {}\n").unwrap();
+        std::fs::write(dir.join("broken.rs"), "").unwrap();
+        commit(dir, "init");
+
+        std::fs::write(dir.join("good.rs"), "fn a() {\n    b();\n}\n").unwrap();
+        std::fs::write(dir.join("broken.rs"), "}}} ((( not rust\n").unwrap();
+        std::fs::write(dir.join("blob.rs"), "fn \0\0\x01\x02 not source\n").unwrap();
+        commit(dir, "edit");
+
+        let result = analyze(
+            dir,
+            DiffScope::Commit {
+                sha: "HEAD".to_string(),
+            },
+        )
+        .unwrap();
+
+        let broken: Vec<&AnalysisWarning> = result
+            .warnings
+            .iter()
+            .filter(|w| w.file_path.as_deref() == Some("broken.rs"))
+            .collect();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].kind, WarningKind::NoEntities);
+        let blob = result.warnings.iter().find(|w| w.file_path.as_deref() == Some("blob.rs"));
+        assert_eq!(blob.map(|w| &w.kind), Some(&WarningKind::Unreadable));
+        assert!(result.warnings.iter().all(|w| w.file_path.as_deref() != Some("good.rs")));
+        assert!(result.changes.iter().any(|r| r.entity_name == "a"));
+    }
+
+    #[test]
+    fn unreadable_file_is_reported() {
+        let changes = vec![FileChange {
+            file_path: "src/lib.rs".into(),
+            status: FileStatus::Modified,
+            old_file_path: None,
+            before_content: None,
+            after_content: None,
+        }];
+        let config = GraphConfig::default();
+        let warnings = coverage_warnings(&changes, &[], &config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::Unreadable);
+        assert!(coverage_warnings(&[], &[], &config).is_empty());
+    }
+
+    fn added(path: &str, content: &str) -> FileChange {
+        FileChange {
+            file_path: path.into(),
+            status: FileStatus::Added,
+            old_file_path: None,
+            before_content: None,
+            after_content: Some(content.into()),
+        }
+    }
+
+    #[test]
+    fn files_without_a_parser_are_unsupported() {
+        let changes = vec![added("notes.txt", "hello\n"), added("src/lib.rs", "}}}\n")];
+        let warnings = coverage_warnings(&changes, &[], &GraphConfig::default());
+        let kinds: Vec<&WarningKind> = warnings.iter().map(|w| &w.kind).collect();
+        assert_eq!(kinds, vec![&WarningKind::Unsupported, &WarningKind::NoEntities]);
+    }
+
+    #[test]
+    fn binary_and_oversized_files_are_skipped() {
+        let mut changes = vec![
+            added("src/lib.rs", "fn a() {}\n"),
+            added("assets/logo.rs", "\u{89}PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            added("dist/bundle.js", &"x".repeat(64)),
+        ];
+        let warnings = skip_unparseable(&mut changes, 32);
+
+        let kept: Vec<&str> = changes.iter().map(|c| c.file_path.as_str()).collect();
+        assert_eq!(kept, vec!["src/lib.rs"]);
+        let skipped: Vec<(&str, &WarningKind)> = warnings
+            .iter()
+            .map(|w| (w.file_path.as_deref().unwrap(), &w.kind))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![("assets/logo.rs", &WarningKind::Unreadable), ("dist/bundle.js", &WarningKind::TooLarge)]
+        );
+        assert!(warnings[1].message.contains("64 bytes"));
+    }
+
+    #[test]
+    fn second_analyze_reuses_graph() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+
+        std::fs::write(dir.join("main.rs"), "fn a() {}\n").unwrap();
+        commit(dir, "init");
+        std::fs::write(dir.join("main.rs"), "fn a() {}\n\nfn b() {\n    a();\n}\n").unwrap();
+        commit(dir, "add b");
+
+        let scope = || DiffScope::Commit {
+            sha: "HEAD".to_string(),
+        };
+        let first = analyze(dir, scope()).unwrap();
+        let second = analyze(dir, scope()).unwrap();
+        assert!(!first.timing.graph_cache_hit);
+        assert!(second.timing.graph_cache_hit);
+        assert_eq!(second.timing.graph_build_ms, 0);
+        assert_eq!(second.timing.graph_entity_count, first.timing.graph_entity_count);
+
+        // Editing the working tree changes the key
+        std::fs::write(dir.join("main.rs"), "fn a() {}\n").unwrap();
+        assert!(!analyze(dir, scope()).unwrap().timing.graph_cache_hit);
+    }
+
+    #[test]
+    fn rescore_reranks_groups() {
+        let review = |id: &str, is_public_api: bool, dependent_count: usize| EntityReview {
+            entity_id: id.into(),
+            entity_name: id.into(),
+            file_path: format!("src/{}.rs", id),
+            dependent_count,
+            is_public_api,
+            ..EntityReview::test_default()
+        };
+        let mut result = ReviewResult {
+            entity_reviews: vec![review("api", true, 0), review("core", false, 3)],
+            groups: vec![],
+            edges: vec![],
+            stats: compute_stats(&[]),
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        };
+        result.groups = untangle(&result.entity_reviews, &[]);
+        let labels = |result: &ReviewResult| -> Vec<(usize, String)> {
+            result.groups.iter().map(|g| (g.id, g.entity_ids[0].clone())).collect()
+        };
+        let group_of = |result: &ReviewResult, id: &str| {
+            result.entity_reviews.iter().find(|r| r.entity_id == id).unwrap().group_id
+        };
+
+        rescore(&mut result, &RiskConfig::default());
+        assert_eq!(labels(&result), [(0, "core".to_string()), (1, "api".to_string())]);
+        assert_eq!(group_of(&result, "core"), 0);
+
+        // Weigh public API over dependents and the order flips
+        let mut flipped = RiskConfig::default();
+        flipped.weights.public_api = 0.5;
+        flipped.weights.dependents = 0.0;
+        rescore(&mut result, &flipped);
+        assert_eq!(labels(&result), [(0, "api".to_string()), (1, "core".to_string())]);
+        assert_eq!(group_of(&result, "api"), 0);
+        assert_eq!(group_of(&result, "core"), 1);
+        assert!(result.groups[0].max_score > result.groups[1].max_score);
+    }
+
+    #[test]
+    fn remote_cross_file_call_is_scored_and_grouped() {
+        let pair = |filename: &str, before: &str, after: &str| FilePair {
+            filename: filename.into(),
+            status: "modified".into(),
+            previous_filename: None,
+            before_content: Some(before.into()),
+            after_content: Some(after.into()),
+        };
+        let pairs = vec![
+            pair(
+                "a.ts",
+                "import { bar } from './b';\nexport function foo() {\n  return 1;\n}\n",
+                "import { bar } from './b';\nexport function foo() {\n  return bar() + 1;\n}\n",
+            ),
+            pair(
+                "b.ts",
+                "export function bar() {\n  return 1;\n}\n",
+                "export function bar() {\n  return 2;\n}\n",
+            ),
+        ];
+        let context = vec![("c.ts".to_string(), "import { bar } from './b';\nexport const c = () => bar();\n".to_string())];
+
+        let result = analyze_remote(&pairs, &context).unwrap();
+        let foo = result.entity_reviews.iter().find(|r| r.entity_name == "foo").unwrap();
+        let bar = result.entity_reviews.iter().find(|r| r.entity_name == "bar").unwrap();
+        assert!(bar.dependent_count >= 1);
+        assert!(foo.dependency_count >= 1);
+        assert_eq!(foo.group_id, bar.group_id);
+        // One edge, dependent to dependency; the unchanged caller in c.ts isn't in it
+        let edge = EntityEdge {
+            from: foo.entity_id.clone(),
+            to: bar.entity_id.clone(),
+            kind: EdgeKind::Dependency,
+        };
+        assert_eq!(result.edges, [edge]);
+        assert!(result.timing.graph_entity_count >= 3);
+        assert!(result.warnings.iter().all(|w| w.kind != WarningKind::GraphSkipped));
+        assert!(bar.context_snippets.is_empty(), "context is opt-in");
+
+        let options = AnalyzeOptions {
+            context: Some(ContextBudget::default()),
+            ..Default::default()
+        };
+        let result = analyze_remote_with_options(&pairs, &context, &options).unwrap();
+        let bar = result.entity_reviews.iter().find(|r| r.entity_name == "bar").unwrap();
+        let callers: Vec<_> = bar.context_snippets.iter().map(|s| s.entity_name.as_str()).collect();
+        // foo is changed, so it outranks the unchanged caller in c.ts
+        assert_eq!(callers[0], "foo");
+        assert!(bar.context_snippets[0].content.contains("return bar() + 1;"));
+    }
+
+    #[test]
+    fn remote_generated_entities_are_suppressed() {
+        let pair = |filename: &str, before: &str, after: &str| FilePair {
+            filename: filename.into(),
+            status: "modified".into(),
+            previous_filename: None,
+            before_content: Some(before.into()),
+            after_content: Some(after.into()),
+        };
+        let header = "// Code generated by protoc-gen-go. DO NOT EDIT.\n\npackage api\n\n";
+        let pairs = vec![
+            pair(
+                "api/user.pb.go",
+                &format!("{}func GetName() string {{\n\treturn \"\"\n}}\n", header),
+                &format!("{}func GetName() string {{\n\treturn \"name\"\n}}\n", header),
+            ),
+            pair(
+                "api/user.go",
+                "package api\n\nfunc Load() int {\n\treturn 1\n}\n",
+                "package api\n\nfunc Load() int {\n\treturn 2\n}\n",
+            ),
+        ];
+
+        let result = analyze_remote(&pairs, &[]).unwrap();
+        assert!(result.entity_reviews.iter().all(|r| r.file_path == "api/user.go"));
+        assert_eq!(result.stats.generated_entities, 1);
+
+        let options = AnalyzeOptions {
+            include_generated: true,
+            ..Default::default()
+        };
+        let result = analyze_remote_with_options(&pairs, &[], &options).unwrap();
+        assert!(result.entity_reviews.iter().any(|r| r.file_path == "api/user.pb.go"));
+        assert_eq!(result.stats.generated_entities, 0);
+    }
+
+    #[test]
+    fn scratch_tree_skips_unsafe_and_non_source_paths() {
+        let tree = ScratchTree::write(&[
+            ("src/a.ts", "export const a = 1;"),
+            ("../escape.ts", "x"),
+            ("/abs.ts", "x"),
+            ("README.md", "x"),
+            ("src/a.ts", "duplicate"),
+        ])
+        .unwrap();
+        assert_eq!(tree.files(), ["src/a.ts".to_string()]);
+        assert_eq!(std::fs::read_to_string(tree.root().join("src/a.ts")).unwrap(), "export const a = 1;");
+
+        let root = tree.root().to_path_buf();
+        drop(tree);
+        assert!(!root.exists());
+    }
 }
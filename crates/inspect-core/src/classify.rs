@@ -1,12 +1,119 @@
-use std::collections::HashSet;
-
 use sem_core::model::change::SemanticChange;
 
+use crate::patch::{diff_ops, LineOp};
 use crate::types::ChangeClassification;
 
+/// A block comment's delimiters. `line_start` blocks only open at the start
+/// of a line (Python docstrings, Ruby `=begin`).
+struct Block {
+    open: &'static str,
+    close: &'static str,
+    line_start: bool,
+}
+
+const fn block(open: &'static str, close: &'static str) -> Block {
+    Block {
+        open,
+        close,
+        line_start: false,
+    }
+}
+
+/// How a language writes comments. Blocks are tried before line markers,
+/// in order, so longer openers go first.
+struct CommentSyntax {
+    line: &'static [&'static str],
+    block: &'static [Block],
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: &[block("/*", "*/")],
+};
+const JSX: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: &[block("{/*", "*/}"), block("/*", "*/")],
+};
+const MARKUP: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: &[block("<!--", "-->"), block("/*", "*/")],
+};
+const PYTHON: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: &[
+        Block {
+            open: "\"\"\"",
+            close: "\"\"\"",
+            line_start: true,
+        },
+        Block {
+            open: "\'\'\'",
+            close: "\'\'\'",
+            line_start: true,
+        },
+    ],
+};
+const RUBY: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: &[Block {
+        open: "=begin",
+        close: "=end",
+        line_start: true,
+    }],
+};
+const HASH: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: &[],
+};
+const SQL: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: &[block("/*", "*/")],
+};
+const LUA: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: &[block("--[[", "]]")],
+};
+/// Unknown languages: C-style comments, and `#` only when followed by a
+/// space so attributes, directives, and private fields stay code.
+const FALLBACK: CommentSyntax = CommentSyntax {
+    line: &["//", "# "],
+    block: &[block("/*", "*/")],
+};
+
+fn comment_syntax(file_path: &str) -> &'static CommentSyntax {
+    let ext = file_path.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+    match ext {
+        "rs" | "ts" | "js" | "mjs" | "cjs" | "go" | "java" | "kt" | "kts" | "c" | "h" | "cc" | "cpp" | "hpp"
+        | "cs" | "swift" | "scala" | "php" | "css" | "scss" => &C_LIKE,
+        "tsx" | "jsx" => &JSX,
+        "html" | "htm" | "xml" | "vue" | "svelte" => &MARKUP,
+        "py" => &PYTHON,
+        "rb" => &RUBY,
+        "sh" | "bash" | "zsh" | "yaml" | "yml" | "toml" | "r" | "pl" | "ex" | "exs" => &HASH,
+        "sql" => &SQL,
+        "lua" => &LUA,
+        _ => &FALLBACK,
+    }
+}
+
+/// A non-blank line split into code tokens (identifiers, literals,
+/// punctuation) and comment text. Whitespace between tokens is dropped.
+#[derive(Debug, PartialEq)]
+struct Line<'a> {
+    text: &'a str,
+    code: Vec<&'a str>,
+    comments: Vec<&'a str>,
+    /// An import/use statement, or a line inside a parenthesized import list
+    import: bool,
+}
+
 /// Classify a semantic change using ConGra taxonomy.
-/// Compares before/after content line-by-line to determine
-/// which dimensions (text, syntax, functional) changed.
+/// Diffs before/after content as a sequence of lines, then compares tokens
+/// within replaced lines to determine which dimensions (text, syntax,
+/// functional) changed. Comments are recognized per language from the file
+/// path, including the interior of multi-line block comments. Reordered and
+/// removed duplicate lines count as changes; whitespace and comment edits
+/// are text.
 pub fn classify_change(change: &SemanticChange) -> ChangeClassification {
     let before = change.before_content.as_deref().unwrap_or("");
     let after = change.after_content.as_deref().unwrap_or("");
@@ -21,40 +128,34 @@ pub fn classify_change(change: &SemanticChange) -> ChangeClassification {
         return ChangeClassification::Text;
     }
 
-    let before_lines: Vec<&str> = before.lines().collect();
-    let after_lines: Vec<&str> = after.lines().collect();
+    // Blank lines and indentation never matter here
+    let syntax = comment_syntax(&change.file_path);
+    let before_lines = tokenize(before, syntax);
+    let after_lines = tokenize(after, syntax);
 
     let mut has_text = false;
     let mut has_syntax = false;
     let mut has_functional = false;
 
-    // Build hash sets of non-empty trimmed lines for O(1) lookup
-    let before_set: HashSet<&str> = before_lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
-    let after_set: HashSet<&str> = after_lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
-
-    // Lines only in before (removed)
-    for line in &before_set {
-        if !after_set.contains(line) {
-            categorize_line(line, &mut has_text, &mut has_syntax, &mut has_functional);
+    for (removed, added) in changed_hunks(&before_lines, &after_lines) {
+        // Pair replaced lines up in order; the rest are pure removals/additions
+        let paired = removed.len().min(added.len());
+        for (old, new) in removed.iter().zip(added.iter()) {
+            if old.code == new.code {
+                // Only comments or spacing within the line changed
+                if old.comments != new.comments {
+                    has_text = true;
+                }
+                continue;
+            }
+            categorize_line(old, &mut has_text, &mut has_syntax, &mut has_functional);
+            categorize_line(new, &mut has_text, &mut has_syntax, &mut has_functional);
         }
-    }
-
-    // Lines only in after (added)
-    for line in &after_set {
-        if !before_set.contains(line) {
+        for line in removed[paired..].iter().chain(&added[paired..]) {
             categorize_line(line, &mut has_text, &mut has_syntax, &mut has_functional);
         }
     }
 
-    // If nothing detected but content differs, it's functional
-    if !has_text && !has_syntax && !has_functional {
-        if before.trim() != after.trim() {
-            has_functional = true;
-        } else {
-            has_text = true; // whitespace-only
-        }
-    }
-
     match (has_text, has_syntax, has_functional) {
         (true, true, true) => ChangeClassification::TextSyntaxFunctional,
         (true, true, false) => ChangeClassification::TextSyntax,
@@ -63,30 +164,157 @@ pub fn classify_change(change: &SemanticChange) -> ChangeClassification {
         (true, false, false) => ChangeClassification::Text,
         (false, true, false) => ChangeClassification::Syntax,
         (false, false, true) => ChangeClassification::Functional,
+        // Whitespace-only
         (false, false, false) => ChangeClassification::Text,
     }
 }
 
-fn categorize_line(line: &str, has_text: &mut bool, has_syntax: &mut bool, has_functional: &mut bool) {
-    if is_comment_line(line) {
+/// Runs of removed and added lines from an LCS diff, in order. A removal
+/// directly followed by an addition forms one hunk.
+fn changed_hunks<'l, T: PartialEq>(before: &'l [T], after: &'l [T]) -> Vec<(Vec<&'l T>, Vec<&'l T>)> {
+    let mut hunks = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    for (op, line) in diff_ops(before, after) {
+        match op {
+            LineOp::Delete => removed.push(line),
+            LineOp::Insert => added.push(line),
+            LineOp::Equal => {
+                if !removed.is_empty() || !added.is_empty() {
+                    hunks.push((std::mem::take(&mut removed), std::mem::take(&mut added)));
+                }
+            }
+        }
+    }
+    if !removed.is_empty() || !added.is_empty() {
+        hunks.push((removed, added));
+    }
+    hunks
+}
+
+/// Split content into non-blank, trimmed lines of tokens, carrying
+/// block-comment and import-list state from one line to the next.
+fn tokenize<'a>(content: &'a str, syntax: &CommentSyntax) -> Vec<Line<'a>> {
+    let mut open: Option<&'static str> = None;
+    let mut in_import_list = false;
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|text| {
+            let mut line = tokenize_line(text, syntax, &mut open);
+            if in_import_list {
+                line.import = true;
+                in_import_list = line.code.first() != Some(&")");
+            } else if is_import_line(&line.code) {
+                line.import = true;
+                // Go `import (` and Python `from x import (` span lines
+                in_import_list = line.code.last() == Some(&"(");
+            }
+            line
+        })
+        .collect()
+}
+
+/// The code tokens of `content`, without comments or whitespace.
+pub(crate) fn code_tokens<'a>(content: &'a str, file_path: &str) -> Vec<&'a str> {
+    tokenize(content, comment_syntax(file_path))
+        .into_iter()
+        .flat_map(|line| line.code)
+        .collect()
+}
+
+/// Whether a line's code is an import: Rust `use`, TS/JS `import` or
+/// `require(...)`, Python `import`/`from ... import`, Go and Java `import`.
+fn is_import_line(code: &[&str]) -> bool {
+    match code {
+        ["use", ..] | ["pub", "use", ..] | ["import", ..] => true,
+        ["from", rest @ ..] => rest.contains(&"import"),
+        // const x = require("x"); / require("x");
+        _ => code.windows(2).any(|w| w == ["require", "("]) && matches!(code[0], "const" | "let" | "var" | "require"),
+    }
+}
+
+fn tokenize_line<'a>(text: &'a str, syntax: &CommentSyntax, open: &mut Option<&'static str>) -> Line<'a> {
+    let mut code = Vec::new();
+    let mut comments = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    'line: while i < bytes.len() {
+        let rest = &text[i..];
+
+        // Inside a block comment: consume through its close
+        if let Some(close) = *open {
+            match rest.find(close) {
+                Some(pos) => {
+                    comments.push(&rest[..pos + close.len()]);
+                    i += pos + close.len();
+                    *open = None;
+                    continue;
+                }
+                None => {
+                    comments.push(rest);
+                    break;
+                }
+            }
+        }
+
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        for b in syntax.block {
+            if (!b.line_start || i == 0) && rest.starts_with(b.open) {
+                comments.push(b.open);
+                *open = Some(b.close);
+                i += b.open.len();
+                continue 'line;
+            }
+        }
+        if syntax.line.iter().any(|marker| rest.starts_with(marker)) {
+            comments.push(rest);
+            break;
+        }
+
+        if c == b'"' || c == b'\'' || c == b'`' {
+            // String literal, honoring backslash escapes
+            let mut end = i + 1;
+            while end < bytes.len() && bytes[end] != c {
+                end += if bytes[end] == b'\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(bytes.len());
+            code.push(&text[i..end]);
+            i = end;
+        } else if c.is_ascii_alphanumeric() || c == b'_' || !c.is_ascii() {
+            let len = rest
+                .find(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                .unwrap_or(rest.len())
+                .max(1);
+            code.push(&rest[..len]);
+            i += len;
+        } else {
+            code.push(&text[i..i + 1]);
+            i += 1;
+        }
+    }
+    Line {
+        text,
+        code,
+        comments,
+        import: false,
+    }
+}
+
+fn categorize_line(line: &Line, has_text: &mut bool, has_syntax: &mut bool, has_functional: &mut bool) {
+    if line.code.is_empty() {
         *has_text = true;
-    } else if is_syntax_line(line) {
+    } else if line.import || is_syntax_line(line.text) {
         *has_syntax = true;
     } else {
         *has_functional = true;
     }
 }
 
-fn is_comment_line(line: &str) -> bool {
-    line.starts_with("//")
-        || line.starts_with("/*")
-        || line.starts_with('*')
-        || line.starts_with("///")
-        || line.starts_with("/**")
-        || line.starts_with("\"\"\"")
-        || (line.starts_with('#') && !line.starts_with("#["))
-}
-
 fn is_syntax_line(line: &str) -> bool {
     line.starts_with("fn ")
         || line.starts_with("pub fn ")
@@ -121,13 +349,17 @@ mod tests {
     use sem_core::model::change::{ChangeType, SemanticChange};
 
     fn make_change(before: &str, after: &str, structural: Option<bool>) -> SemanticChange {
+        make_change_in("test.rs", before, after, structural)
+    }
+
+    fn make_change_in(path: &str, before: &str, after: &str, structural: Option<bool>) -> SemanticChange {
         SemanticChange {
             id: "test".into(),
             entity_id: "test::fn::foo".into(),
             change_type: ChangeType::Modified,
             entity_type: "function".into(),
             entity_name: "foo".into(),
-            file_path: "test.rs".into(),
+            file_path: path.into(),
             old_file_path: None,
             before_content: Some(before.into()),
             after_content: Some(after.into()),
@@ -167,4 +399,150 @@ mod tests {
         );
         assert_eq!(classify_change(&change), ChangeClassification::TextFunctional);
     }
+
+    #[test]
+    fn reordered_statements_are_functional() {
+        let change = make_change(
+            "fn foo() {\n    open();\n    close();\n}",
+            "fn foo() {\n    close();\n    open();\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Functional);
+    }
+
+    #[test]
+    fn removed_duplicate_line_is_functional() {
+        let change = make_change(
+            "fn foo() {\n    retry();\n    retry();\n}",
+            "fn foo() {\n    retry();\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Functional);
+    }
+
+    #[test]
+    fn string_literal_edit_is_functional() {
+        let change = make_change(
+            "fn foo() {\n    log(\"starting up\");\n}",
+            "fn foo() {\n    log(\"shutting down\");\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Functional);
+    }
+
+    #[test]
+    fn trailing_comment_and_spacing_edits_are_text() {
+        let change = make_change(
+            "fn foo() {\n    x+1 // old\n\n}",
+            "fn foo() {\n    x + 1 // new\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+
+        let change = make_change("fn foo() {\n    x+1\n}", "fn foo() {\n    x + 1\n}", Some(true));
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+    }
+
+    #[test]
+    fn block_comment_body_without_stars_is_text() {
+        let change = make_change(
+            "fn foo() {\n    /*\n    retry three times\n    */\n    run()\n}",
+            "fn foo() {\n    /*\n    retry up to five times\n    */\n    run()\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+
+        // Code after the block closes is still code
+        let change = make_change(
+            "fn foo() {\n    /* note */ run(1)\n}",
+            "fn foo() {\n    /* note */ run(2)\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Functional);
+    }
+
+    #[test]
+    fn jsx_comments_are_text() {
+        let change = make_change_in(
+            "App.tsx",
+            "function App() {\n  return (\n    <div>\n      {/* old */}\n      <Header />\n    </div>\n  );\n}",
+            "function App() {\n  return (\n    <div>\n      {/* new */}\n      <Header />\n    </div>\n  );\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+    }
+
+    #[test]
+    fn sql_dash_comments_are_text() {
+        let before = "CREATE VIEW active AS\n-- users seen this week\nSELECT * FROM users WHERE seen > now() - 7;";
+        let after = "CREATE VIEW active AS\n-- users seen in the last 7 days\nSELECT * FROM users WHERE seen > now() - 7;";
+        let change = make_change_in("views.sql", before, after, Some(true));
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+
+        // In C-like languages `--` is code
+        let change = make_change("fn foo() {\n    x -- 1\n}", "fn foo() {\n    x -- 2\n}", Some(true));
+        assert_eq!(classify_change(&change), ChangeClassification::Functional);
+    }
+
+    #[test]
+    fn ruby_begin_end_blocks_are_text() {
+        let before = "def run\n=begin\nold notes\n=end\n  go\nend";
+        let after = "def run\n=begin\nnew notes\n=end\n  go\nend";
+        let change = make_change_in("job.rb", before, after, Some(true));
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+
+        let change = make_change_in("job.rb", "def run\n  go # fast\nend", "def run\n  go # slow\nend", Some(true));
+        assert_eq!(classify_change(&change), ChangeClassification::Text);
+    }
+
+    #[test]
+    fn added_import_is_syntax() {
+        let change = make_change_in(
+            "app.ts",
+            "import { a } from \"./a\";\nexport function run() {}",
+            "import { a } from \"./a\";\nimport { b } from \"./b\";\nexport function run() {}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Syntax);
+
+        let change = make_change_in(
+            "app.js",
+            "const a = require(\"a\");",
+            "const a = require(\"a\");\nconst b = require(\"b\");",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Syntax);
+    }
+
+    #[test]
+    fn reordered_import_block_is_syntax() {
+        let change = make_change("use std::fmt;\nuse std::io;", "use std::io;\nuse std::fmt;", Some(true));
+        assert_eq!(classify_change(&change), ChangeClassification::Syntax);
+
+        let change = make_change_in(
+            "main.go",
+            "import (\n\t\"fmt\"\n\t\"os\"\n)",
+            "import (\n\t\"os\"\n\t\"fmt\"\n)",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Syntax);
+
+        let change = make_change_in(
+            "io.py",
+            "from os import (\n    path,\n)\nimport sys",
+            "from os import (\n    path,\n    sep,\n)\nimport sys",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::Syntax);
+    }
+
+    #[test]
+    fn import_with_body_change_is_syntax_functional() {
+        let change = make_change(
+            "use std::fmt;\nfn foo() {\n    x + 1\n}",
+            "use std::fmt;\nuse std::io;\nfn foo() {\n    x + 2\n}",
+            Some(true),
+        );
+        assert_eq!(classify_change(&change), ChangeClassification::SyntaxFunctional);
+    }
 }
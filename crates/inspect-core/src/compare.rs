@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::risk::{suggest_verdict, ReviewVerdict};
+use crate::types::{EntityReview, ReviewResult, RiskLevel};
+
+/// A changed entity present in only one of the two analyses.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeltaEntity {
+    pub entity_id: String,
+    pub name: String,
+    pub file_path: String,
+    pub risk_level: RiskLevel,
+}
+
+/// An entity whose risk level differs between the two analyses.
+#[derive(Debug, Clone, Serialize)]
+pub struct RiskTransition {
+    pub entity_id: String,
+    pub name: String,
+    pub file_path: String,
+    pub from: RiskLevel,
+    pub to: RiskLevel,
+}
+
+/// An entity that moved to a different logical group. Group ids are
+/// per-analysis, so groups are compared by label.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupMove {
+    pub entity_id: String,
+    pub from_group: String,
+    pub to_group: String,
+}
+
+/// What changed between a baseline analysis and a newer one.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewDelta {
+    /// Entities changed in head but not in base
+    pub added: Vec<DeltaEntity>,
+    /// Entities changed in base but not in head
+    pub removed: Vec<DeltaEntity>,
+    pub risk_transitions: Vec<RiskTransition>,
+    pub verdict_before: ReviewVerdict,
+    pub verdict_after: ReviewVerdict,
+    pub group_moves: Vec<GroupMove>,
+}
+
+impl ReviewDelta {
+    pub fn verdict_changed(&self) -> bool {
+        self.verdict_before != self.verdict_after
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.risk_transitions.is_empty()
+            && self.group_moves.is_empty()
+            && !self.verdict_changed()
+    }
+}
+
+/// Compare two analyses entity by entity. Lists are ordered by entity id.
+pub fn compare(base: &ReviewResult, head: &ReviewResult) -> ReviewDelta {
+    let base_by_id = by_id(base);
+    let head_by_id = by_id(head);
+    let base_groups = group_labels(base);
+    let head_groups = group_labels(head);
+
+    let mut added: Vec<DeltaEntity> = head_by_id
+        .iter()
+        .filter(|(id, _)| !base_by_id.contains_key(*id))
+        .map(|(_, r)| delta_entity(r))
+        .collect();
+    let mut removed: Vec<DeltaEntity> = base_by_id
+        .iter()
+        .filter(|(id, _)| !head_by_id.contains_key(*id))
+        .map(|(_, r)| delta_entity(r))
+        .collect();
+
+    let mut risk_transitions = Vec::new();
+    let mut group_moves = Vec::new();
+    for (id, after) in &head_by_id {
+        let Some(before) = base_by_id.get(id) else {
+            continue;
+        };
+        if before.risk_level != after.risk_level {
+            risk_transitions.push(RiskTransition {
+                entity_id: after.entity_id.clone(),
+                name: after.entity_name.clone(),
+                file_path: after.file_path.clone(),
+                from: before.risk_level,
+                to: after.risk_level,
+            });
+        }
+        let from_group = base_groups.get(&before.group_id).copied().unwrap_or_default();
+        let to_group = head_groups.get(&after.group_id).copied().unwrap_or_default();
+        if from_group != to_group {
+            group_moves.push(GroupMove {
+                entity_id: after.entity_id.clone(),
+                from_group: from_group.to_string(),
+                to_group: to_group.to_string(),
+            });
+        }
+    }
+
+    added.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+    removed.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+    risk_transitions.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+    group_moves.sort_by(|a, b| a.entity_id.cmp(&b.entity_id));
+
+    ReviewDelta {
+        added,
+        removed,
+        risk_transitions,
+        verdict_before: suggest_verdict(base),
+        verdict_after: suggest_verdict(head),
+        group_moves,
+    }
+}
+
+fn by_id(result: &ReviewResult) -> HashMap<&str, &EntityReview> {
+    result
+        .entity_reviews
+        .iter()
+        .map(|r| (r.entity_id.as_str(), r))
+        .collect()
+}
+
+fn group_labels(result: &ReviewResult) -> HashMap<usize, &str> {
+    result.groups.iter().map(|g| (g.id, g.label.as_str())).collect()
+}
+
+fn delta_entity(r: &EntityReview) -> DeltaEntity {
+    DeltaEntity {
+        entity_id: r.entity_id.clone(),
+        name: r.entity_name.clone(),
+        file_path: r.file_path.clone(),
+        risk_level: r.risk_level,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::compute_stats;
+    use crate::types::*;
+
+    fn review(name: &str, level: RiskLevel, group_id: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("src/lib.rs::{}", name),
+            entity_name: name.into(),
+            risk_score: 0.5,
+            risk_level: level,
+            group_id,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn result(entity_reviews: Vec<EntityReview>, labels: &[&str]) -> ReviewResult {
+        let groups = labels
+            .iter()
+            .enumerate()
+            .map(|(id, label)| ChangeGroup {
+                id,
+                label: label.to_string(),
+                entity_ids: entity_reviews
+                    .iter()
+                    .filter(|r| r.group_id == id)
+                    .map(|r| r.entity_id.clone())
+                    .collect(),
+                dominant_classification: ChangeClassification::Functional,
+                max_risk: RiskLevel::Medium,
+                max_score: 0.5,
+                has_public_api: false,
+            })
+            .collect();
+        ReviewResult {
+            stats: compute_stats(&entity_reviews),
+            entity_reviews,
+            groups,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    #[test]
+    fn fix_one_critical_and_introduce_one_medium() {
+        let base = result(
+            vec![
+                review("parse", RiskLevel::Critical, 0),
+                review("render", RiskLevel::Low, 0),
+            ],
+            &["parser"],
+        );
+        let head = result(
+            vec![
+                review("parse", RiskLevel::Low, 0),
+                review("render", RiskLevel::Low, 0),
+                review("validate", RiskLevel::Medium, 0),
+            ],
+            &["parser"],
+        );
+
+        let delta = compare(&base, &head);
+        assert_eq!(delta.added.len(), 1);
+        assert_eq!(delta.added[0].name, "validate");
+        assert_eq!(delta.added[0].risk_level, RiskLevel::Medium);
+        assert!(delta.removed.is_empty());
+
+        assert_eq!(delta.risk_transitions.len(), 1);
+        let t = &delta.risk_transitions[0];
+        assert_eq!(t.name, "parse");
+        assert_eq!((t.from, t.to), (RiskLevel::Critical, RiskLevel::Low));
+
+        assert_eq!(delta.verdict_before, ReviewVerdict::RequiresCarefulReview);
+        assert_ne!(delta.verdict_after, ReviewVerdict::RequiresCarefulReview);
+        assert!(delta.verdict_changed());
+        assert!(delta.group_moves.is_empty());
+    }
+
+    #[test]
+    fn removed_entities_and_group_moves() {
+        let base = result(
+            vec![review("a", RiskLevel::Low, 0), review("b", RiskLevel::Low, 1)],
+            &["alpha", "beta"],
+        );
+        let head = result(vec![review("a", RiskLevel::Low, 0)], &["beta"]);
+
+        let delta = compare(&base, &head);
+        assert_eq!(delta.removed.len(), 1);
+        assert_eq!(delta.removed[0].name, "b");
+        assert_eq!(delta.group_moves.len(), 1);
+        assert_eq!(delta.group_moves[0].from_group, "alpha");
+        assert_eq!(delta.group_moves[0].to_group, "beta");
+    }
+
+    #[test]
+    fn identical_analyses_have_empty_delta() {
+        let base = result(vec![review("a", RiskLevel::High, 0)], &["g"]);
+        let head = result(vec![review("a", RiskLevel::High, 0)], &["g"]);
+        assert!(compare(&base, &head).is_empty());
+    }
+}
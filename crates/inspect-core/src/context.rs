@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use sem_core::parser::graph::EntityGraph;
+
+use crate::types::{ContextSnippet, EntityReview, Relation};
+
+/// Limits on the related source attached to each reviewed entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextBudget {
+    /// Related entities quoted per reviewed entity
+    pub max_snippets: usize,
+    /// Characters per snippet before it is truncated
+    pub max_snippet_chars: usize,
+    /// Characters across all of one entity's snippets
+    pub max_total_chars: usize,
+}
+
+impl Default for ContextBudget {
+    fn default() -> Self {
+        Self {
+            max_snippets: 5,
+            max_snippet_chars: 1500,
+            max_total_chars: 6000,
+        }
+    }
+}
+
+/// A related entity that could be quoted.
+#[derive(Debug, Clone)]
+struct Candidate {
+    name: String,
+    file_path: String,
+    relation: Relation,
+    start_line: usize,
+    end_line: usize,
+    /// Risk score when the entity is itself changed, else 0
+    risk: f64,
+    /// Direct dependents of the entity
+    impact: usize,
+}
+
+/// Fill each review's `context_snippets` with the source of its dependents
+/// and dependencies, riskiest first. `read` returns a file's current
+/// content; each file is read at most once.
+pub(crate) fn attach_context(
+    reviews: &mut [EntityReview],
+    graph: &EntityGraph,
+    budget: &ContextBudget,
+    read: &dyn Fn(&str) -> Option<String>,
+) {
+    let risk: HashMap<String, f64> = reviews
+        .iter()
+        .map(|r| (r.entity_id.clone(), r.risk_score))
+        .collect();
+    let mut files: HashMap<String, Option<String>> = HashMap::new();
+
+    for review in reviews.iter_mut() {
+        let dependents = graph.get_dependents(&review.entity_id);
+        let dependencies = graph.get_dependencies(&review.entity_id);
+        let candidates: Vec<Candidate> = dependents
+            .into_iter()
+            .map(|e| (e, Relation::Dependent))
+            .chain(dependencies.into_iter().map(|e| (e, Relation::Dependency)))
+            .filter(|(e, _)| e.id != review.entity_id)
+            .map(|(e, relation)| Candidate {
+                name: e.name.clone(),
+                file_path: e.file_path.clone(),
+                relation,
+                start_line: e.start_line,
+                end_line: e.end_line,
+                risk: risk.get(&e.id).copied().unwrap_or(0.0),
+                impact: graph.get_dependents(&e.id).len(),
+            })
+            .collect();
+
+        for c in &candidates {
+            if !files.contains_key(&c.file_path) {
+                files.insert(c.file_path.clone(), read(&c.file_path));
+            }
+        }
+        review.context_snippets =
+            select_snippets(candidates, budget, &|path| files.get(path)?.as_deref());
+    }
+}
+
+/// Quote candidates in order of risk, then impact, until the snippet count
+/// or character budget runs out. Dependents win ties over dependencies.
+fn select_snippets<'a>(
+    mut candidates: Vec<Candidate>,
+    budget: &ContextBudget,
+    source: &dyn Fn(&str) -> Option<&'a str>,
+) -> Vec<ContextSnippet> {
+    candidates.sort_by(|a, b| b.risk.total_cmp(&a.risk).then(b.impact.cmp(&a.impact)));
+
+    let mut remaining = budget.max_total_chars;
+    let mut snippets = Vec::new();
+    for c in candidates {
+        if snippets.len() >= budget.max_snippets {
+            break;
+        }
+        let Some(text) = source(&c.file_path).and_then(|content| line_range(content, c.start_line, c.end_line))
+        else {
+            continue;
+        };
+        let (content, truncated) = truncate(&text, budget.max_snippet_chars.min(remaining));
+        if content.is_empty() {
+            continue;
+        }
+        remaining -= content.len();
+        snippets.push(ContextSnippet {
+            entity_name: c.name,
+            file_path: c.file_path,
+            relation: c.relation,
+            start_line: c.start_line,
+            end_line: c.end_line,
+            content,
+            truncated,
+        });
+    }
+    snippets
+}
+
+/// Lines `start..=end` (1-based) of `content`.
+fn line_range(content: &str, start: usize, end: usize) -> Option<String> {
+    if start == 0 || end < start {
+        return None;
+    }
+    let lines: Vec<&str> = content.lines().skip(start - 1).take(end - start + 1).collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// The whole lines of `text` that fit in `limit` bytes, and whether any
+/// were dropped. Empty when not even the first line fits.
+fn truncate(text: &str, limit: usize) -> (String, bool) {
+    if text.len() <= limit {
+        return (text.to_string(), false);
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = &text[..end];
+    let cut = cut.rfind('\n').map_or("", |i| &cut[..i]);
+    (cut.to_string(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, relation: Relation, risk: f64, impact: usize) -> Candidate {
+        Candidate {
+            name: name.into(),
+            file_path: format!("{name}.rs"),
+            relation,
+            start_line: 1,
+            end_line: 40,
+            risk,
+            impact,
+        }
+    }
+
+    /// `missing.rs` is unreadable, `big.rs` holds `big`, the rest a small body.
+    fn source<'a>(big: &'a str) -> impl Fn(&str) -> Option<&'a str> {
+        move |path: &str| match path {
+            "missing.rs" => None,
+            "big.rs" => Some(big),
+            _ => Some("fn body() {\n    work();\n}\n"),
+        }
+    }
+
+    #[test]
+    fn riskiest_related_entities_come_first() {
+        let candidates = vec![
+            candidate("quiet", Relation::Dependent, 0.0, 1),
+            candidate("missing", Relation::Dependent, 0.9, 9),
+            candidate("hub", Relation::Dependency, 0.0, 12),
+            candidate("changed", Relation::Dependent, 0.7, 0),
+        ];
+        let budget = ContextBudget {
+            max_snippets: 2,
+            ..ContextBudget::default()
+        };
+        let snippets = select_snippets(candidates, &budget, &source(""));
+        let names: Vec<_> = snippets.iter().map(|s| s.entity_name.as_str()).collect();
+        assert_eq!(names, ["changed", "hub"]);
+        assert_eq!(snippets[0].content, "fn body() {\n    work();\n}");
+        assert_eq!(snippets[1].relation, Relation::Dependency);
+    }
+
+    #[test]
+    fn snippets_stay_within_budget() {
+        let candidates = vec![
+            candidate("big", Relation::Dependent, 0.5, 0),
+            candidate("a", Relation::Dependent, 0.4, 0),
+            candidate("b", Relation::Dependent, 0.3, 0),
+            candidate("c", Relation::Dependent, 0.2, 0),
+        ];
+        let budget = ContextBudget {
+            max_snippets: 10,
+            max_snippet_chars: 100,
+            max_total_chars: 150,
+        };
+        let big: String = (1..=40).map(|i| format!("line {i:02} of big\n")).collect();
+        let snippets = select_snippets(candidates, &budget, &source(&big));
+
+        let big = &snippets[0];
+        assert!(big.truncated);
+        assert!(big.content.len() <= 100);
+        assert!(big.content.ends_with("of big"), "cut at a line end");
+
+        let total: usize = snippets.iter().map(|s| s.content.len()).sum();
+        assert!(total <= 150, "total {total}");
+        // "c" only had room for a partial line, so it is left out
+        let names: Vec<_> = snippets.iter().map(|s| s.entity_name.as_str()).collect();
+        assert_eq!(names, ["big", "a", "b"]);
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        let (cut, truncated) = truncate("a\nhéllo", 4);
+        assert_eq!((cut.as_str(), truncated), ("a", true));
+        assert_eq!(truncate("one long line", 4), (String::new(), true));
+        assert_eq!(truncate("abc", 3), ("abc".to_string(), false));
+        assert_eq!(line_range("a\nb\nc", 2, 3).as_deref(), Some("b\nc"));
+        assert_eq!(line_range("a", 0, 0), None);
+    }
+}
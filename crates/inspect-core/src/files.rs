@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use sem_core::git::bridge::GitBridge;
+use sem_core::git::types::DiffScope;
+
+use crate::analyze::AnalyzeError;
+use crate::noise::{is_noise_file, noise_kind};
+use crate::types::{EntityReview, RiskLevel};
+
+/// A file touched by a diff, as reported by git.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    pub path: String,
+    /// "added", "modified", "deleted", "renamed"
+    pub status: String,
+}
+
+/// File-level overview row. Entity fields are None when the summary was
+/// built from the raw diff alone.
+#[derive(Debug, Clone)]
+pub struct FileSummary {
+    pub path: String,
+    pub status: String,
+    pub entity_count: Option<usize>,
+    pub max_risk: Option<RiskLevel>,
+    pub max_score: Option<f64>,
+    pub public_api_changes: Option<usize>,
+    pub is_noise: bool,
+    /// See [`crate::noise::noise_kind`]
+    pub noise_kind: Option<&'static str>,
+}
+
+/// List the files changed in a diff scope, without parsing entities.
+pub fn list_changed_files(repo_path: &Path, scope: &DiffScope) -> Result<Vec<ChangedFile>, AnalyzeError> {
+    let git = GitBridge::open(repo_path).map_err(|e| AnalyzeError::Git(e.to_string()))?;
+    let changes = git
+        .get_changed_files(scope)
+        .map_err(|e| AnalyzeError::Git(e.to_string()))?;
+
+    Ok(changes
+        .into_iter()
+        .map(|c| ChangedFile {
+            path: c.file_path,
+            status: format!("{:?}", c.status).to_lowercase(),
+        })
+        .collect())
+}
+
+/// Post-change contents of the files in a diff scope, as `(path, content)`.
+/// Deleted files and noise files are skipped.
+pub fn changed_file_contents(repo_path: &Path, scope: &DiffScope) -> Result<Vec<(String, String)>, AnalyzeError> {
+    let git = GitBridge::open(repo_path).map_err(|e| AnalyzeError::Git(e.to_string()))?;
+    let changes = git
+        .get_changed_files(scope)
+        .map_err(|e| AnalyzeError::Git(e.to_string()))?;
+
+    Ok(changes
+        .into_iter()
+        .filter(|c| !is_noise_file(&c.file_path))
+        .filter_map(|c| c.after_content.map(|content| (c.file_path, content)))
+        .collect())
+}
+
+/// Summarize changed files, folding in per-entity results when available.
+/// Sorted by max risk score (highest first), then path; files without
+/// entity data sort last.
+pub fn summarize_files(files: &[ChangedFile], reviews: Option<&[EntityReview]>) -> Vec<FileSummary> {
+    let mut by_file: HashMap<&str, Vec<&EntityReview>> = HashMap::new();
+    for r in reviews.unwrap_or_default() {
+        by_file.entry(r.file_path.as_str()).or_default().push(r);
+    }
+
+    let mut rows: Vec<FileSummary> = files
+        .iter()
+        .map(|f| {
+            let kind = noise_kind(&f.path);
+            let mut row = FileSummary {
+                path: f.path.clone(),
+                status: f.status.clone(),
+                entity_count: None,
+                max_risk: None,
+                max_score: None,
+                public_api_changes: None,
+                is_noise: kind.is_some(),
+                noise_kind: kind,
+            };
+
+            if reviews.is_some() {
+                let entities = by_file.get(f.path.as_str()).map(Vec::as_slice).unwrap_or_default();
+                let top = entities.iter().max_by(|a, b| a.risk_score.total_cmp(&b.risk_score));
+                row.entity_count = Some(entities.len());
+                row.max_risk = top.map(|r| r.risk_level);
+                row.max_score = top.map(|r| r.risk_score);
+                row.public_api_changes = Some(entities.iter().filter(|r| r.is_public_api).count());
+            }
+
+            row
+        })
+        .collect();
+
+    rows.sort_by(|a, b| {
+        let a_score = a.max_score.unwrap_or(f64::NEG_INFINITY);
+        let b_score = b.max_score.unwrap_or(f64::NEG_INFINITY);
+        b_score.total_cmp(&a_score).then_with(|| a.path.cmp(&b.path))
+    });
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, status: &str) -> ChangedFile {
+        ChangedFile {
+            path: path.into(),
+            status: status.into(),
+        }
+    }
+
+    fn review(file_path: &str, score: f64, level: RiskLevel, public: bool) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file_path, score),
+            file_path: file_path.into(),
+            risk_score: score,
+            risk_level: level,
+            is_public_api: public,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn files() -> Vec<ChangedFile> {
+        vec![
+            file("Cargo.lock", "modified"),
+            file("src/a.rs", "modified"),
+            file("src/b.rs", "added"),
+        ]
+    }
+
+    #[test]
+    fn fast_mode_lists_files_without_entity_data() {
+        let rows = summarize_files(&files(), None);
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|r| r.entity_count.is_none() && r.max_risk.is_none()));
+        // No scores, so path order
+        assert_eq!(rows[0].path, "Cargo.lock");
+        assert!(rows[0].is_noise);
+        assert_eq!(rows[0].noise_kind, Some("lockfile"));
+        assert_eq!(rows[2].status, "added");
+    }
+
+    #[test]
+    fn full_mode_aggregates_entities_and_keeps_noise() {
+        let reviews = vec![
+            review("src/a.rs", 0.2, RiskLevel::Low, false),
+            review("src/b.rs", 0.5, RiskLevel::Medium, true),
+            review("src/b.rs", 0.8, RiskLevel::High, true),
+        ];
+        let rows = summarize_files(&files(), Some(&reviews));
+
+        assert_eq!(rows[0].path, "src/b.rs");
+        assert_eq!(rows[0].entity_count, Some(2));
+        assert_eq!(rows[0].max_risk, Some(RiskLevel::High));
+        assert_eq!(rows[0].public_api_changes, Some(2));
+        assert_eq!(rows[1].path, "src/a.rs");
+
+        let lock = &rows[2];
+        assert_eq!(lock.path, "Cargo.lock");
+        assert_eq!(lock.entity_count, Some(0));
+        assert!(lock.is_noise);
+    }
+}
@@ -0,0 +1,567 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analyze::compute_stats;
+use crate::noise::is_test_path;
+use crate::search::FileFilter;
+use crate::untangle::summarize_groups;
+use crate::types::{ChangeClassification, EntityReview, ReviewResult, ReviewStats, RiskLevel};
+
+/// Classification names accepted by [`parse_classification`], matching the Display output.
+pub const CLASSIFICATIONS: &[&str] = &[
+    "text",
+    "syntax",
+    "functional",
+    "text+syntax",
+    "text+functional",
+    "syntax+functional",
+    "text+syntax+functional",
+];
+
+/// Criteria for narrowing a set of entity reviews. Every set criterion must
+/// match; an empty filter keeps everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityFilter {
+    pub min_risk: Option<RiskLevel>,
+    /// Inclusive lower bound on risk_score, in 0.0..=1.0
+    pub min_score: Option<f64>,
+    /// Keep entities whose classification is one of these (exact match)
+    pub classifications: Option<Vec<ChangeClassification>>,
+    /// Drop test code: entities flagged `is_test` or in test files (see
+    /// [`crate::noise::is_test_path`])
+    pub hide_tests: bool,
+}
+
+impl EntityFilter {
+    /// Build a filter from raw tool/API arguments, validating each one.
+    pub fn parse(
+        min_risk: Option<&str>,
+        min_score: Option<f64>,
+        classifications: Option<&[String]>,
+    ) -> Result<Self, String> {
+        let min_risk = min_risk.map(parse_risk_level).transpose()?;
+
+        if let Some(score) = min_score {
+            if !(0.0..=1.0).contains(&score) {
+                return Err(format!("min_score must be between 0.0 and 1.0, got {}", score));
+            }
+        }
+
+        let classifications = classifications
+            .map(|names| names.iter().map(|n| parse_classification(n)).collect::<Result<Vec<_>, _>>())
+            .transpose()?;
+
+        Ok(Self {
+            min_risk,
+            min_score,
+            classifications,
+            hide_tests: false,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min_risk.is_none() && self.min_score.is_none() && self.classifications.is_none() && !self.hide_tests
+    }
+
+    pub fn matches(&self, review: &EntityReview) -> bool {
+        self.min_risk.map(|min| review.risk_level >= min).unwrap_or(true)
+            && self.min_score.map(|min| review.risk_score >= min).unwrap_or(true)
+            && self
+                .classifications
+                .as_ref()
+                .map(|cs| cs.contains(&review.classification))
+                .unwrap_or(true)
+            && !(self.hide_tests && (review.is_test || is_test_path(&review.file_path)))
+    }
+
+    pub fn apply<'a>(&self, reviews: impl IntoIterator<Item = &'a EntityReview>) -> Vec<&'a EntityReview> {
+        reviews.into_iter().filter(|r| self.matches(r)).collect()
+    }
+
+    /// Copy of `result` holding only matching entities, with stats and group
+    /// membership and summaries recomputed from them. Groups left empty are
+    /// dropped.
+    pub fn filter_result(&self, result: &ReviewResult) -> ReviewResult {
+        let mut filtered = result.clone();
+        if self.is_empty() {
+            return filtered;
+        }
+        filtered.entity_reviews.retain(|r| self.matches(r));
+        let kept: HashSet<&str> = filtered.entity_reviews.iter().map(|r| r.entity_id.as_str()).collect();
+        for group in &mut filtered.groups {
+            group.entity_ids.retain(|id| kept.contains(id.as_str()));
+        }
+        filtered.groups.retain(|g| !g.entity_ids.is_empty());
+        filtered
+            .edges
+            .retain(|e| kept.contains(e.from.as_str()) && kept.contains(e.to.as_str()));
+        summarize_groups(&mut filtered.groups, &filtered.entity_reviews);
+        filtered.stats = ReviewStats {
+            generated_entities: result.stats.generated_entities,
+            ..compute_stats(&filtered.entity_reviews)
+        };
+        filtered
+    }
+}
+
+/// Narrow `result` to entities in files `files` matches, as if nothing else
+/// had changed: stats and group summaries are recomputed, groups left empty
+/// are dropped and the rest renumbered from 0.
+pub fn filter_paths(result: &mut ReviewResult, files: &FileFilter) {
+    if files.is_empty() {
+        return;
+    }
+    result.entity_reviews.retain(|r| files.matches(&r.file_path));
+    result.changes.retain(|c| files.matches(&c.file_path));
+    result
+        .warnings
+        .retain(|w| w.file_path.as_deref().is_none_or(|path| files.matches(path)));
+
+    let kept: HashSet<&str> = result.entity_reviews.iter().map(|r| r.entity_id.as_str()).collect();
+    for group in &mut result.groups {
+        group.entity_ids.retain(|id| kept.contains(id.as_str()));
+    }
+    result.groups.retain(|g| !g.entity_ids.is_empty());
+    result
+        .edges
+        .retain(|e| kept.contains(e.from.as_str()) && kept.contains(e.to.as_str()));
+    let ids: HashMap<usize, usize> = result
+        .groups
+        .iter_mut()
+        .enumerate()
+        .map(|(new, group)| (std::mem::replace(&mut group.id, new), new))
+        .collect();
+    for review in &mut result.entity_reviews {
+        // A review pointing at a group that doesn't list it (e.g. a result
+        // built by hand) follows its entity to whichever group holds it
+        let new = ids.get(&review.group_id).copied().or_else(|| {
+            result
+                .groups
+                .iter()
+                .position(|g| g.entity_ids.contains(&review.entity_id))
+        });
+        if let Some(new) = new {
+            review.group_id = new;
+        }
+    }
+    summarize_groups(&mut result.groups, &result.entity_reviews);
+
+    result.stats = ReviewStats {
+        generated_entities: result.stats.generated_entities,
+        ..compute_stats(&result.entity_reviews)
+    };
+}
+
+/// Changed entities named `name`, narrowed to files ending in `file_path`
+/// when given. More than one means the name is ambiguous.
+pub fn find_reviews<'a>(result: &'a ReviewResult, name: &str, file_path: Option<&str>) -> Vec<&'a EntityReview> {
+    result
+        .entity_reviews
+        .iter()
+        .filter(|r| r.entity_name == name && file_path.is_none_or(|fp| r.file_path.ends_with(fp)))
+        .collect()
+}
+
+/// Up to `n` distinct names closest to `target` by edit distance (case-insensitive).
+pub fn closest_names<'a>(target: &str, names: impl IntoIterator<Item = &'a str>, n: usize) -> Vec<&'a str> {
+    let target = target.to_lowercase();
+    let mut scored: Vec<(usize, &str)> = names
+        .into_iter()
+        .map(|name| (edit_distance(&target, &name.to_lowercase()), name))
+        .collect();
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored.into_iter().take(n).map(|(_, name)| name).collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Order for listing entity reviews.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// Highest risk score first (the order analysis produces)
+    #[default]
+    Risk,
+    /// By file path, then start line
+    File,
+    /// Largest blast radius first
+    Blast,
+    /// By entity name
+    Name,
+}
+
+impl std::fmt::Display for SortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Risk => write!(f, "risk"),
+            Self::File => write!(f, "file"),
+            Self::Blast => write!(f, "blast"),
+            Self::Name => write!(f, "name"),
+        }
+    }
+}
+
+/// Parse a sort key name (case-insensitive).
+pub fn parse_sort_key(s: &str) -> Result<SortKey, String> {
+    match s.to_lowercase().as_str() {
+        "risk" => Ok(SortKey::Risk),
+        "file" => Ok(SortKey::File),
+        "blast" => Ok(SortKey::Blast),
+        "name" => Ok(SortKey::Name),
+        other => Err(format!(
+            "sort must be 'risk', 'file', 'blast', or 'name', got '{}'",
+            other
+        )),
+    }
+}
+
+/// Sort `reviews` by `key`. The sort is stable, so ties keep their current
+/// (risk) order.
+pub fn sort_reviews(reviews: &mut [EntityReview], key: SortKey) {
+    match key {
+        SortKey::Risk => reviews.sort_by(|a, b| b.risk_score.total_cmp(&a.risk_score)),
+        SortKey::File => reviews.sort_by(|a, b| {
+            a.file_path.cmp(&b.file_path).then(a.start_line.cmp(&b.start_line))
+        }),
+        SortKey::Blast => reviews.sort_by_key(|r| std::cmp::Reverse(r.blast_radius)),
+        SortKey::Name => reviews.sort_by(|a, b| a.entity_name.cmp(&b.entity_name)),
+    }
+}
+
+/// Parse a risk level name (case-insensitive).
+pub fn parse_risk_level(s: &str) -> Result<RiskLevel, String> {
+    match s.to_lowercase().as_str() {
+        "critical" => Ok(RiskLevel::Critical),
+        "high" => Ok(RiskLevel::High),
+        "medium" => Ok(RiskLevel::Medium),
+        "low" => Ok(RiskLevel::Low),
+        other => Err(format!(
+            "risk level must be 'low', 'medium', 'high', or 'critical', got '{}'",
+            other
+        )),
+    }
+}
+
+/// Parse a classification name as printed by ChangeClassification's Display (case-insensitive).
+pub fn parse_classification(s: &str) -> Result<ChangeClassification, String> {
+    match s.to_lowercase().as_str() {
+        "text" => Ok(ChangeClassification::Text),
+        "syntax" => Ok(ChangeClassification::Syntax),
+        "functional" => Ok(ChangeClassification::Functional),
+        "text+syntax" => Ok(ChangeClassification::TextSyntax),
+        "text+functional" => Ok(ChangeClassification::TextFunctional),
+        "syntax+functional" => Ok(ChangeClassification::SyntaxFunctional),
+        "text+syntax+functional" => Ok(ChangeClassification::TextSyntaxFunctional),
+        other => Err(format!(
+            "unknown classification '{}'; expected one of: {}",
+            other,
+            CLASSIFICATIONS.join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EdgeKind, EntityEdge};
+
+    fn review(score: f64, level: RiskLevel, classification: ChangeClassification) -> EntityReview {
+        EntityReview {
+            entity_id: format!("e{}", score),
+            classification,
+            risk_score: score,
+            risk_level: level,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn reviews() -> Vec<EntityReview> {
+        vec![
+            review(0.9, RiskLevel::Critical, ChangeClassification::Functional),
+            review(0.62, RiskLevel::High, ChangeClassification::Syntax),
+            review(0.55, RiskLevel::High, ChangeClassification::Functional),
+            review(0.3, RiskLevel::Medium, ChangeClassification::Text),
+        ]
+    }
+
+    #[test]
+    fn min_risk_and_min_score_combine() {
+        let reviews = reviews();
+        let filter = EntityFilter::parse(Some("high"), Some(0.6), None).unwrap();
+        let kept = filter.apply(&reviews);
+        let scores: Vec<f64> = kept.iter().map(|r| r.risk_score).collect();
+        assert_eq!(scores, vec![0.9, 0.62]);
+    }
+
+    #[test]
+    fn classification_filter_is_exact() {
+        let reviews = reviews();
+        let names = vec!["functional".to_string()];
+        let filter = EntityFilter::parse(Some("medium"), None, Some(&names)).unwrap();
+        assert_eq!(filter.apply(&reviews).len(), 2);
+    }
+
+    #[test]
+    fn empty_filter_keeps_everything() {
+        let reviews = reviews();
+        let filter = EntityFilter::default();
+        assert!(filter.is_empty());
+        assert_eq!(filter.apply(&reviews).len(), reviews.len());
+    }
+
+    #[test]
+    fn hide_tests_drops_test_files() {
+        let mut reviews = reviews();
+        reviews[0].file_path = "tests/parse.rs".into();
+        let filter = EntityFilter {
+            hide_tests: true,
+            ..Default::default()
+        };
+        assert!(!filter.is_empty());
+        assert_eq!(filter.apply(&reviews).len(), reviews.len() - 1);
+
+        // A #[cfg(test)] module in a source file
+        reviews[1].is_test = true;
+        assert_eq!(filter.apply(&reviews).len(), reviews.len() - 2);
+    }
+
+    #[test]
+    fn rejects_out_of_range_score_and_unknown_classification() {
+        assert!(EntityFilter::parse(None, Some(1.5), None).is_err());
+        assert!(EntityFilter::parse(None, Some(-0.1), None).is_err());
+        assert!(EntityFilter::parse(None, Some(f64::NAN), None).is_err());
+        assert!(EntityFilter::parse(Some("severe"), None, None).is_err());
+        let bad = vec!["logic".to_string()];
+        assert!(EntityFilter::parse(None, None, Some(&bad)).is_err());
+    }
+
+    #[test]
+    fn filter_result_recomputes_stats_and_groups() {
+        let mut reviews = reviews();
+        for (i, r) in reviews.iter_mut().enumerate() {
+            r.entity_id = format!("e{}", i);
+            r.group_id = i / 2;
+        }
+        let result = ReviewResult {
+            stats: compute_stats(&reviews),
+            groups: vec![
+                crate::types::ChangeGroup {
+                    id: 0,
+                    label: "a".into(),
+                    entity_ids: vec!["e0".into(), "e1".into()],
+                    dominant_classification: ChangeClassification::Functional,
+                    max_risk: RiskLevel::Medium,
+                    max_score: 0.5,
+                    has_public_api: false,
+                },
+                crate::types::ChangeGroup {
+                    id: 1,
+                    label: "b".into(),
+                    entity_ids: vec!["e2".into(), "e3".into()],
+                    dominant_classification: ChangeClassification::Functional,
+                    max_risk: RiskLevel::Medium,
+                    max_score: 0.5,
+                    has_public_api: false,
+                },
+            ],
+            entity_reviews: reviews,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        };
+
+        let filter = EntityFilter::parse(Some("critical"), None, None).unwrap();
+        let filtered = filter.filter_result(&result);
+        assert_eq!(filtered.entity_reviews.len(), 1);
+        assert_eq!(filtered.stats.total_entities, 1);
+        assert_eq!(filtered.stats.by_risk.critical, 1);
+        assert_eq!(filtered.groups.len(), 1);
+        assert_eq!(filtered.groups[0].entity_ids, vec!["e0".to_string()]);
+
+        // The source is untouched
+        assert_eq!(result.entity_reviews.len(), 4);
+        assert_eq!(result.groups[1].entity_ids.len(), 2);
+    }
+
+    #[test]
+    fn filter_paths_recomputes_stats_and_renumbers_groups() {
+        let paths = ["vendor/lib/a.rs", "src/api/deep/nested/b.rs", "src/c.rs", "src/api/d.ts"];
+        let group_of = [0, 1, 1, 2];
+        let mut reviews = reviews();
+        for (i, r) in reviews.iter_mut().enumerate() {
+            r.entity_id = format!("e{}", i);
+            r.file_path = paths[i].into();
+            r.group_id = group_of[i];
+        }
+        let group = |id: usize, members: &[&str]| crate::types::ChangeGroup {
+            id,
+            label: format!("g{}", id),
+            entity_ids: members.iter().map(|m| m.to_string()).collect(),
+            dominant_classification: ChangeClassification::Functional,
+            max_risk: RiskLevel::Critical,
+            max_score: 0.9,
+            has_public_api: false,
+        };
+        let mut result = ReviewResult {
+            stats: compute_stats(&reviews),
+            groups: vec![group(0, &["e0"]), group(1, &["e1", "e2"]), group(2, &["e3"])],
+            entity_reviews: reviews,
+            edges: [("e0", "e1"), ("e1", "e2"), ("e3", "e2")]
+                .map(|(from, to)| EntityEdge {
+                    from: from.into(),
+                    to: to.into(),
+                    kind: EdgeKind::Dependency,
+                })
+                .to_vec(),
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        };
+
+        let files = FileFilter::new(&["src/**".into()], &["**/*.ts".into()]).unwrap();
+        filter_paths(&mut result, &files);
+
+        let kept: Vec<_> = result.entity_reviews.iter().map(|r| r.file_path.as_str()).collect();
+        assert_eq!(kept, ["src/api/deep/nested/b.rs", "src/c.rs"]);
+        assert_eq!(result.stats.total_entities, 2);
+        assert_eq!(result.stats.by_risk.critical, 0);
+        assert_eq!(result.stats.by_risk.high, 2);
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.groups[0].id, 0);
+        assert_eq!(result.groups[0].max_risk, RiskLevel::High, "summary recomputed");
+        assert!(result.entity_reviews.iter().all(|r| r.group_id == 0));
+        // Only edges between kept entities survive
+        let edges: Vec<_> = result.edges.iter().map(|e| (e.from.as_str(), e.to.as_str())).collect();
+        assert_eq!(edges, [("e1", "e2")]);
+    }
+
+    #[test]
+    fn filter_paths_tolerates_stale_group_ids() {
+        let mut reviews = reviews();
+        for (i, r) in reviews.iter_mut().enumerate() {
+            r.entity_id = format!("e{}", i);
+            r.file_path = if i < 2 { "src/a.rs" } else { "vendor/b.rs" }.into();
+        }
+        // e0 points at a group that doesn't exist, e1 at one that gets dropped
+        reviews[0].group_id = 7;
+        reviews[1].group_id = 0;
+        let group = |id: usize, members: &[&str]| crate::types::ChangeGroup {
+            id,
+            label: format!("g{}", id),
+            entity_ids: members.iter().map(|m| m.to_string()).collect(),
+            dominant_classification: ChangeClassification::Functional,
+            max_risk: RiskLevel::Critical,
+            max_score: 0.9,
+            has_public_api: false,
+        };
+        let mut result = ReviewResult {
+            stats: compute_stats(&reviews),
+            groups: vec![group(0, &["e2", "e3"]), group(1, &["e0", "e1"])],
+            entity_reviews: reviews,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        };
+
+        let files = FileFilter::new(&["src/**".into()], &[]).unwrap();
+        filter_paths(&mut result, &files);
+
+        assert_eq!(result.groups.len(), 1);
+        let groups: Vec<_> = result.entity_reviews.iter().map(|r| r.group_id).collect();
+        assert_eq!(groups, [0, 0]);
+    }
+
+    #[test]
+    fn path_globs_span_nested_directories() {
+        let files = FileFilter::new(&["src/**/*.rs".into()], &[]).unwrap();
+        assert!(files.matches("src/c.rs"));
+        assert!(files.matches("src/a/b/c.rs"));
+        assert!(!files.matches("vendor/src/c.rs"));
+
+        let shallow = FileFilter::new(&["src/*.rs".into()], &["src/gen/**".into()]).unwrap();
+        assert!(shallow.matches("src/c.rs"));
+        assert!(!shallow.matches("src/a/c.rs"), "* stays within one directory");
+        let excluded = FileFilter::new(&[], &["**/generated/**".into()]).unwrap();
+        assert!(!excluded.matches("a/b/generated/x.rs"));
+        assert!(excluded.matches("a/b/x.rs"));
+    }
+
+    #[test]
+    fn sorts_are_stable() {
+        let mut reviews = reviews();
+        let spots = [("b.rs", 30, 2, "zeta"), ("a.rs", 9, 5, "beta"), ("b.rs", 4, 5, "alpha"), ("a.rs", 9, 2, "beta")];
+        for (r, (path, line, blast, name)) in reviews.iter_mut().zip(spots) {
+            r.file_path = path.into();
+            r.start_line = line;
+            r.blast_radius = blast;
+            r.entity_name = name.into();
+        }
+        let scores = |reviews: &[EntityReview]| reviews.iter().map(|r| r.risk_score).collect::<Vec<_>>();
+
+        let mut by_file = reviews.clone();
+        sort_reviews(&mut by_file, SortKey::File);
+        assert_eq!(scores(&by_file), [0.62, 0.3, 0.55, 0.9], "a.rs:9 tie keeps risk order");
+
+        let mut by_blast = reviews.clone();
+        sort_reviews(&mut by_blast, SortKey::Blast);
+        assert_eq!(scores(&by_blast), [0.62, 0.55, 0.9, 0.3]);
+
+        let mut by_name = reviews.clone();
+        sort_reviews(&mut by_name, SortKey::Name);
+        assert_eq!(scores(&by_name), [0.55, 0.62, 0.3, 0.9]);
+
+        sort_reviews(&mut by_name, SortKey::Risk);
+        assert_eq!(scores(&by_name), scores(&reviews), "back to risk order");
+
+        assert_eq!(parse_sort_key("Blast"), Ok(SortKey::Blast));
+        assert!(parse_sort_key("size").is_err());
+    }
+
+    #[test]
+    fn find_reviews_matches_name_and_file_suffix() {
+        let mut reviews = reviews();
+        let spots = [("helper", "src/a.rs"), ("helper", "src/b.rs"), ("run", "src/a.rs"), ("Helper", "src/c.rs")];
+        for (r, (name, path)) in reviews.iter_mut().zip(spots) {
+            r.entity_name = name.into();
+            r.file_path = path.into();
+        }
+        let result = ReviewResult {
+            stats: compute_stats(&reviews),
+            groups: vec![],
+            entity_reviews: reviews,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        };
+        assert_eq!(find_reviews(&result, "helper", None).len(), 2);
+        let narrowed = find_reviews(&result, "helper", Some("b.rs"));
+        assert_eq!(narrowed.len(), 1);
+        assert_eq!(narrowed[0].file_path, "src/b.rs");
+        assert!(find_reviews(&result, "run", Some("b.rs")).is_empty());
+    }
+
+    #[test]
+    fn closest_names_ranks_by_edit_distance() {
+        let names = ["parse_config", "parse_args", "render", "parse_config", "ParseConfig"];
+        let found = closest_names("parse_confg", names, 3);
+        assert_eq!(found, vec!["parse_config", "ParseConfig", "parse_args"]);
+    }
+}
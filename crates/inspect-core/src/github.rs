@@ -1,7 +1,34 @@
+use futures::stream::{self, StreamExt};
 use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT};
+use reqwest::{RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::patch::{commentable_lines, parse_patch};
+
+/// Rate-limited requests are retried this many times before giving up.
+const MAX_RETRIES: u32 = 3;
+/// First backoff when GitHub gives no hint; doubles on each retry.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+/// Longest we sleep before a retry. A reset further out fails fast instead.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Concurrent blob queries while fetching file contents.
+const MAX_CONCURRENT_FETCHES: usize = 10;
+/// Blobs fetched per GraphQL query, one alias each.
+const BLOB_BATCH_SIZE: usize = 50;
+/// Larger blobs come back as `None`, matching the REST contents API limit.
+const MAX_BLOB_BYTES: u64 = 1024 * 1024;
+/// A new comment within this many lines of an existing one with the same
+/// body is a duplicate.
+const DUPLICATE_LINE_WINDOW: u64 = 3;
+/// Comments per review submission; larger reviews are posted in parts.
+const MAX_REVIEW_COMMENTS: usize = 30;
+const DEFAULT_API_URL: &str = "https://api.github.com";
+/// Code Search pages (of 100 results) fetched unless the caller says otherwise.
+pub const DEFAULT_SEARCH_PAGES: usize = 3;
 
 // --- Error type ---
 
@@ -10,6 +37,10 @@ pub enum GitHubError {
     Auth(String),
     Api(String),
     Parse(String),
+    /// Still rate limited after retrying; the limit resets in `reset_in_secs`.
+    RateLimited { reset_in_secs: u64 },
+    /// Code Search rejected the query (422), e.g. because it is too long.
+    InvalidQuery(String),
 }
 
 impl fmt::Display for GitHubError {
@@ -18,6 +49,12 @@ impl fmt::Display for GitHubError {
             Self::Auth(msg) => write!(f, "auth error: {}", msg),
             Self::Api(msg) => write!(f, "GitHub API error: {}", msg),
             Self::Parse(msg) => write!(f, "parse error: {}", msg),
+            Self::RateLimited { reset_in_secs } => write!(
+                f,
+                "GitHub rate limit exceeded; try again in {}s",
+                reset_in_secs
+            ),
+            Self::InvalidQuery(msg) => write!(f, "search query rejected: {}", msg),
         }
     }
 }
@@ -41,6 +78,9 @@ struct GraphQLResponse<T> {
 #[derive(Debug, Deserialize)]
 struct GraphQLError {
     message: String,
+    /// Where in the response the error happened, e.g. `["repository", "f3"]`.
+    #[serde(default)]
+    path: Vec<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -110,11 +150,73 @@ struct FilesPagePR {
     files: FileConnection,
 }
 
+#[derive(Debug, Deserialize)]
+struct CommitsPageData {
+    repository: CommitsPageRepository,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitsPageRepository {
+    pull_request: CommitsPagePR,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitsPagePR {
+    commits: CommitConnection,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitConnection {
+    page_info: PageInfo,
+    nodes: Vec<GraphQLPrCommitNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQLPrCommitNode {
+    commit: GraphQLCommit,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQLCommit {
+    oid: String,
+    message: String,
+    authored_date: String,
+}
+
 #[derive(Debug, Deserialize)]
 struct FileContent {
     content: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct BlobBatchData {
+    /// Keyed by alias; `null` when the path doesn't exist at the ref.
+    repository: HashMap<String, Option<BlobNode>>,
+}
+
+/// A `... on Blob` selection; every field is absent when the object is a tree.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlobNode {
+    text: Option<String>,
+    #[serde(default)]
+    is_binary: bool,
+    #[serde(default)]
+    byte_size: u64,
+}
+
+impl BlobNode {
+    fn into_text(self) -> Option<String> {
+        if self.is_binary || self.byte_size > MAX_BLOB_BYTES {
+            return None;
+        }
+        self.text
+    }
+}
+
 // --- Public types ---
 
 #[derive(Debug, Clone)]
@@ -140,12 +242,34 @@ pub struct PrFile {
     pub additions: u64,
     pub deletions: u64,
     pub patch: Option<String>,
+    /// Path before a rename
+    pub previous_filename: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PrCommit {
+    pub sha: String,
+    pub message: String,
+    pub authored_at: String,
+}
+
+/// The part of a PR pushed after a given commit.
+#[derive(Debug, Clone)]
+pub struct PrSinceCommit {
+    /// Full sha of the commit the range starts from
+    pub base_sha: String,
+    /// Commits after `base_sha`, oldest first
+    pub commits: Vec<PrCommit>,
+    /// PR files changed in the range, patched against `base_sha`
+    pub files: Vec<PrFile>,
 }
 
 #[derive(Debug, Clone)]
 pub struct FilePair {
     pub filename: String,
     pub status: String,
+    /// Path before a rename; `before_content` was read from here
+    pub previous_filename: Option<String>,
     pub before_content: Option<String>,
     pub after_content: Option<String>,
 }
@@ -173,9 +297,223 @@ pub struct CreateReviewResponse {
     pub html_url: String,
 }
 
+/// State of a commit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+impl fmt::Display for CommitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Success => write!(f, "success"),
+            Self::Failure => write!(f, "failure"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CommitStatusRequest<'a> {
+    state: CommitState,
+    context: &'a str,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_url: Option<&'a str>,
+}
+
+/// GitHub rejects commit status descriptions longer than this.
+const MAX_STATUS_DESCRIPTION: usize = 140;
+
+/// A comment left out of a posted review, and why.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedComment {
+    #[serde(flatten)]
+    pub comment: ReviewCommentInput,
+    pub reason: String,
+}
+
+/// The outcome of `create_review`. `id` and `html_url` belong to the first
+/// submission, which carries the review body and event.
+#[derive(Debug, Serialize)]
+pub struct PostedReview {
+    pub id: u64,
+    pub html_url: String,
+    pub review_ids: Vec<u64>,
+    pub posted: Vec<ReviewCommentInput>,
+    pub skipped: Vec<SkippedComment>,
+}
+
+/// A review comment already on the PR.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExistingReviewComment {
+    pub path: String,
+    /// Line in the current diff, or where it was when the comment is outdated.
+    pub line: Option<u64>,
+    pub body: String,
+    pub author: String,
+    pub in_reply_to: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestReviewComment {
+    path: String,
+    line: Option<u64>,
+    original_line: Option<u64>,
+    body: String,
+    user: Option<RestUser>,
+    in_reply_to_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestUser {
+    login: String,
+}
+
+impl From<RestReviewComment> for ExistingReviewComment {
+    fn from(c: RestReviewComment) -> Self {
+        Self {
+            path: c.path,
+            line: c.line.or(c.original_line),
+            body: c.body,
+            author: c.user.map(|u| u.login).unwrap_or_default(),
+            in_reply_to: c.in_reply_to_id,
+        }
+    }
+}
+
+fn normalize_comment_body(body: &str) -> String {
+    body.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Whether `comment` repeats an existing comment: same path, same body up to
+/// whitespace and case, within `DUPLICATE_LINE_WINDOW` lines.
+pub fn is_duplicate_comment(
+    comment: &ReviewCommentInput,
+    existing: &[ExistingReviewComment],
+) -> bool {
+    let body = normalize_comment_body(&comment.body);
+    existing.iter().any(|e| {
+        e.path == comment.path
+            && e.line
+                .is_some_and(|line| line.abs_diff(comment.line) <= DUPLICATE_LINE_WINDOW)
+            && normalize_comment_body(&e.body) == body
+    })
+}
+
+/// Split comments into those on commentable diff lines of `files` and
+/// those that would make GitHub reject the review.
+fn validate_review_comments(
+    comments: &[ReviewCommentInput],
+    files: &[PrFile],
+) -> (Vec<ReviewCommentInput>, Vec<SkippedComment>) {
+    let commentable: HashMap<&str, Vec<u64>> = files
+        .iter()
+        .map(|f| {
+            let hunks = f.patch.as_deref().map(parse_patch).unwrap_or_default();
+            (f.filename.as_str(), commentable_lines(&hunks))
+        })
+        .collect();
+
+    let mut valid = Vec::new();
+    let mut skipped = Vec::new();
+    for c in comments {
+        let reason = match commentable.get(c.path.as_str()) {
+            None => "not a changed file",
+            Some(lines) if !lines.contains(&c.line) => "line not in diff",
+            Some(_) => {
+                valid.push(c.clone());
+                continue;
+            }
+        };
+        skipped.push(SkippedComment {
+            comment: c.clone(),
+            reason: reason.to_string(),
+        });
+    }
+    (valid, skipped)
+}
+
+/// The comment a 422 review error is about: the one whose path and line both
+/// appear in the error, else the only one whose path does.
+fn offending_comment(error: &str, comments: &[ReviewCommentInput]) -> Option<usize> {
+    let numbers: HashSet<u64> = error
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    if let Some(i) = comments
+        .iter()
+        .position(|c| error.contains(&c.path) && numbers.contains(&c.line))
+    {
+        return Some(i);
+    }
+    let mut on_path = comments
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| error.contains(&c.path));
+    match (on_path.next(), on_path.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
+}
+
+/// The PR commits after `since` (a full or abbreviated sha), oldest first.
+/// None when `since` isn't one of the PR's commits.
+pub fn commits_since<'a>(commits: &'a [PrCommit], since: &str) -> Option<&'a [PrCommit]> {
+    if since.is_empty() {
+        return None;
+    }
+    let i = commits.iter().position(|c| c.sha.starts_with(since))?;
+    Some(&commits[i + 1..])
+}
+
+/// The PR files a range diff touches, carrying the range's patch. Their
+/// status becomes "modified" since the range start may already have them,
+/// or "renamed" when the range itself renames them.
+pub fn files_in_range(files: &[PrFile], range_diff: &str) -> Vec<PrFile> {
+    let patches = parse_raw_diff(range_diff);
+    let renames = parse_renames(range_diff);
+    files
+        .iter()
+        .filter(|f| patches.contains_key(&f.filename) || renames.contains_key(&f.filename))
+        .map(|f| {
+            let previous_filename = renames.get(&f.filename).cloned();
+            PrFile {
+                status: if previous_filename.is_some() { "renamed" } else { "modified" }.to_string(),
+                patch: patches.get(&f.filename).cloned(),
+                previous_filename,
+                ..f.clone()
+            }
+        })
+        .collect()
+}
+
+/// The comments in `new` that don't repeat one in `existing`.
+pub fn filter_duplicate_comments(
+    new: &[ReviewCommentInput],
+    existing: &[ExistingReviewComment],
+) -> Vec<ReviewCommentInput> {
+    new.iter()
+        .filter(|c| !is_duplicate_comment(c, existing))
+        .cloned()
+        .collect()
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CodeSearchResponse {
     pub total_count: u64,
+    /// Set by GitHub on timeouts, and by `search_code` when a later page
+    /// fails, so `items` holds fewer results than `total_count` promises.
+    #[serde(default)]
+    pub incomplete_results: bool,
     pub items: Vec<CodeSearchItem>,
 }
 
@@ -188,6 +526,20 @@ pub struct CodeSearchItem {
     pub text_matches: Option<Vec<TextMatch>>,
 }
 
+impl CodeSearchItem {
+    /// Code search can return the same file on several pages; the path and
+    /// matched fragments identify a result.
+    fn dedup_key(&self) -> (String, Vec<String>) {
+        let fragments = self
+            .text_matches
+            .iter()
+            .flatten()
+            .map(|m| m.fragment.clone())
+            .collect();
+        (self.path.clone(), fragments)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CodeSearchRepo {
     pub full_name: String,
@@ -211,7 +563,9 @@ pub struct GitHubClient {
     base_url: String,
 }
 
-fn parse_raw_diff(raw: &str) -> std::collections::HashMap<String, String> {
+/// Split a raw PR diff into per-file patches (hunks only), keyed by the
+/// post-change path.
+pub fn parse_raw_diff(raw: &str) -> std::collections::HashMap<String, String> {
     let mut map = std::collections::HashMap::new();
     let mut current_file: Option<String> = None;
     let mut current_patch = String::new();
@@ -256,6 +610,83 @@ fn parse_raw_diff(raw: &str) -> std::collections::HashMap<String, String> {
     map
 }
 
+/// A file that can't be fetched (deleted, binary, too large) has no content,
+/// but running out of rate limit must fail the whole fetch: silently dropping
+/// the file would skew the analysis.
+fn missing_as_none(content: Result<String, GitHubError>) -> Result<Option<String>, GitHubError> {
+    match content {
+        Ok(content) => Ok(Some(content)),
+        Err(e @ GitHubError::RateLimited { .. }) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+/// The `rel="next"` target of a `Link` header, if any.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get("link")?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|p| p.trim() == r#"rel="next""#)
+            .then(|| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+/// The messages out of a 422 validation body, or the raw body if it isn't one.
+fn validation_message(body: &str) -> String {
+    #[derive(Deserialize)]
+    struct Validation {
+        message: String,
+        #[serde(default)]
+        errors: Vec<ValidationError>,
+    }
+    /// Search returns `{ "message": ... }` objects, reviews plain strings.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ValidationError {
+        Text(String),
+        Detail { message: Option<String> },
+    }
+    match serde_json::from_str::<Validation>(body) {
+        Ok(v) => {
+            let msgs: Vec<String> = v
+                .errors
+                .into_iter()
+                .filter_map(|e| match e {
+                    ValidationError::Text(msg) => Some(msg),
+                    ValidationError::Detail { message } => message,
+                })
+                .collect();
+            if msgs.is_empty() {
+                v.message
+            } else {
+                msgs.join("; ")
+            }
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+/// Renamed files in a raw diff, new path to old path, including pure renames
+/// that have no hunks.
+pub fn parse_renames(raw: &str) -> HashMap<String, String> {
+    let mut renames = HashMap::new();
+    let mut from: Option<&str> = None;
+    for line in raw.lines() {
+        if line.starts_with("diff --git ") {
+            from = None;
+        } else if let Some(old) = line.strip_prefix("rename from ") {
+            from = Some(old);
+        } else if let Some(new) = line.strip_prefix("rename to ") {
+            if let Some(old) = from.take() {
+                renames.insert(new.to_string(), old.to_string());
+            }
+        }
+    }
+    renames
+}
+
 fn map_change_type(ct: &str) -> String {
     match ct {
         "ADDED" => "added".to_string(),
@@ -272,12 +703,86 @@ fn split_repo(repo: &str) -> Result<(&str, &str), GitHubError> {
         .ok_or_else(|| GitHubError::Parse(format!("Repository must be owner/repo, got: {repo}")))
 }
 
+/// How long to wait before retrying `resp`, or `None` if it isn't rate limited.
+/// 429 is always a rate limit; 403 only when GitHub says so via headers.
+fn rate_limit_wait(resp: &Response, attempt: u32) -> Option<Duration> {
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+    };
+    let retry_after = header("retry-after");
+    let exhausted = header("x-ratelimit-remaining") == Some(0);
+    let limited = match resp.status() {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => retry_after.is_some() || exhausted,
+        _ => false,
+    };
+    if !limited {
+        return None;
+    }
+
+    if let Some(secs) = retry_after {
+        return Some(Duration::from_secs(secs));
+    }
+    if let (true, Some(reset)) = (exhausted, header("x-ratelimit-reset")) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        return Some(Duration::from_secs(reset.saturating_sub(now)));
+    }
+    Some((BASE_BACKOFF * 2u32.pow(attempt)).min(MAX_BACKOFF))
+}
+
+/// The REST API root from `GITHUB_API_URL`, else `GH_HOST`, else github.com.
+pub fn api_url_from_env() -> String {
+    resolve_api_url(
+        std::env::var("GITHUB_API_URL").ok().as_deref(),
+        std::env::var("GH_HOST").ok().as_deref(),
+    )
+}
+
+fn resolve_api_url(api_url: Option<&str>, host: Option<&str>) -> String {
+    if let Some(url) = api_url.map(str::trim).filter(|u| !u.is_empty()) {
+        return url.trim_end_matches('/').to_string();
+    }
+    match host.map(str::trim).filter(|h| !h.is_empty()) {
+        None | Some("github.com") => DEFAULT_API_URL.to_string(),
+        // Enterprise Server serves REST under /api/v3 on its own host
+        Some(host) if host.contains("://") => format!("{}/api/v3", host.trim_end_matches('/')),
+        Some(host) => format!("https://{}/api/v3", host.trim_end_matches('/')),
+    }
+}
+
+/// GraphQL sits next to REST on github.com (`/graphql`) but at `/api/graphql`
+/// on Enterprise Server, whose REST root is `/api/v3`.
+fn graphql_url(base_url: &str) -> String {
+    match base_url.strip_suffix("/api/v3") {
+        Some(host) => format!("{host}/api/graphql"),
+        None => format!("{base_url}/graphql"),
+    }
+}
+
 impl GitHubClient {
+    /// Client for the API at `GITHUB_API_URL`/`GH_HOST` (default github.com),
+    /// authenticated by `GITHUB_TOKEN` or the gh CLI.
     pub fn new() -> Result<Self, GitHubError> {
         let token = std::env::var("GITHUB_TOKEN")
             .or_else(|_| Self::token_from_gh_cli())
             .map_err(|e| GitHubError::Auth(format!("Set GITHUB_TOKEN or install/auth gh CLI: {e}")))?;
+        Self::with_token(&token)
+    }
+
+    /// Client with an explicit token for the API named by the environment.
+    pub fn with_token(token: &str) -> Result<Self, GitHubError> {
+        Self::with_base_url(token, &api_url_from_env())
+    }
 
+    /// Client for the REST API rooted at `base_url`, e.g.
+    /// `https://github.example.com/api/v3` for Enterprise Server.
+    pub fn with_base_url(token: &str, base_url: &str) -> Result<Self, GitHubError> {
         let mut headers = HeaderMap::new();
         headers.insert(
             AUTHORIZATION,
@@ -301,7 +806,7 @@ impl GitHubClient {
 
         Ok(Self {
             http,
-            base_url: "https://api.github.com".to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
         })
     }
 
@@ -316,26 +821,36 @@ impl GitHubClient {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 
+    /// Send a request, retrying while GitHub reports a rate limit. Waits for
+    /// `Retry-After` or `X-RateLimit-Reset` when given, otherwise backs off
+    /// exponentially; gives up with `RateLimited` once retries run out or the
+    /// reset is further away than `MAX_BACKOFF`.
+    async fn send(&self, req: RequestBuilder) -> Result<Response, GitHubError> {
+        let mut attempt = 0;
+        loop {
+            let this_try = req
+                .try_clone()
+                .ok_or_else(|| GitHubError::Api("request body cannot be retried".into()))?;
+            let resp = this_try.send().await?;
+            let Some(wait) = rate_limit_wait(&resp, attempt) else {
+                return Ok(resp);
+            };
+            if attempt >= MAX_RETRIES || wait > MAX_BACKOFF {
+                return Err(GitHubError::RateLimited {
+                    reset_in_secs: wait.as_secs_f64().ceil() as u64,
+                });
+            }
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
     async fn graphql<T: DeserializeOwned>(
         &self,
         query: &str,
         variables: &serde_json::Value,
     ) -> Result<T, GitHubError> {
-        let body = serde_json::json!({
-            "query": query,
-            "variables": variables,
-        });
-        let url = format!("{}/graphql", self.base_url);
-        let resp = self.http.post(&url).json(&body).send().await?;
-        let status = resp.status();
-        if !status.is_success() {
-            let text = resp.text().await.unwrap_or_default();
-            return Err(GitHubError::Api(format!("{status}: {text}")));
-        }
-        let gql_resp: GraphQLResponse<T> = resp
-            .json()
-            .await
-            .map_err(|e| GitHubError::Parse(e.to_string()))?;
+        let gql_resp: GraphQLResponse<T> = self.graphql_response(query, variables).await?;
         if let Some(errors) = gql_resp.errors {
             let msgs: Vec<String> = errors.into_iter().map(|e| e.message).collect();
             return Err(GitHubError::Api(format!("GraphQL: {}", msgs.join("; "))));
@@ -345,26 +860,32 @@ impl GitHubClient {
             .ok_or_else(|| GitHubError::Api("No data in GraphQL response".into()))
     }
 
-    async fn rest_get<T: DeserializeOwned>(&self, path: &str) -> Result<T, GitHubError> {
-        let url = format!("{}{}", self.base_url, path);
-        let resp = self.http.get(&url).send().await?;
+    /// A GraphQL response with its errors left in, for queries where partial
+    /// data is still useful.
+    async fn graphql_response<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: &serde_json::Value,
+    ) -> Result<GraphQLResponse<T>, GitHubError> {
+        let body = serde_json::json!({
+            "query": query,
+            "variables": variables,
+        });
+        let url = graphql_url(&self.base_url);
+        let resp = self.send(self.http.post(&url).json(&body)).await?;
         let status = resp.status();
         if !status.is_success() {
-            let body = resp.text().await.unwrap_or_default();
-            return Err(GitHubError::Api(format!("{status}: {body}")));
+            let text = resp.text().await.unwrap_or_default();
+            return Err(GitHubError::Api(format!("{status}: {text}")));
         }
         resp.json()
             .await
             .map_err(|e| GitHubError::Parse(e.to_string()))
     }
 
-    async fn rest_post<B: Serialize, R: DeserializeOwned>(
-        &self,
-        path: &str,
-        body: &B,
-    ) -> Result<R, GitHubError> {
+    async fn rest_get<T: DeserializeOwned>(&self, path: &str) -> Result<T, GitHubError> {
         let url = format!("{}{}", self.base_url, path);
-        let resp = self.http.post(&url).json(body).send().await?;
+        let resp = self.send(self.http.get(&url)).await?;
         let status = resp.status();
         if !status.is_success() {
             let body = resp.text().await.unwrap_or_default();
@@ -426,6 +947,7 @@ query($owner: String!, $repo: String!, $number: Int!) {
                 additions: f.additions,
                 deletions: f.deletions,
                 patch: None,
+                previous_filename: None,
             })
             .collect();
 
@@ -442,11 +964,21 @@ query($owner: String!, $repo: String!, $number: Int!) {
                     additions: f.additions,
                     deletions: f.deletions,
                     patch: None,
+                    previous_filename: None,
                 });
             }
             page_info = more.page_info;
         }
 
+        // The GraphQL file list has no previous path, so recover renames
+        // from the raw diff
+        if files.iter().any(|f| f.status == "renamed") {
+            let renames = parse_renames(&self.get_pr_raw_diff(repo, number).await?);
+            for f in &mut files {
+                f.previous_filename = renames.get(&f.filename).cloned();
+            }
+        }
+
         Ok(PullRequest {
             number: pr.number,
             title: pr.title,
@@ -499,12 +1031,24 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
     }
 
     async fn get_pr_raw_diff(&self, repo: &str, number: u64) -> Result<String, GitHubError> {
-        let url = format!("{}/repos/{}/pulls/{}", self.base_url, repo, number);
+        self.get_diff(&format!("/repos/{repo}/pulls/{number}")).await
+    }
+
+    /// Raw diff of `base...head`, as the compare endpoint serves it.
+    pub async fn get_compare_diff(
+        &self,
+        repo: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<String, GitHubError> {
+        self.get_diff(&format!("/repos/{repo}/compare/{base}...{head}"))
+            .await
+    }
+
+    async fn get_diff(&self, path: &str) -> Result<String, GitHubError> {
+        let url = format!("{}{}", self.base_url, path);
         let resp = self
-            .http
-            .get(&url)
-            .header(ACCEPT, "application/vnd.github.diff")
-            .send()
+            .send(self.http.get(&url).header(ACCEPT, "application/vnd.github.diff"))
             .await?;
         let status = resp.status();
         if !status.is_success() {
@@ -516,6 +1060,74 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
             .map_err(|e| GitHubError::Parse(e.to_string()))
     }
 
+    /// All commits on a PR, oldest first.
+    pub async fn list_pr_commits(
+        &self,
+        repo: &str,
+        number: u64,
+    ) -> Result<Vec<PrCommit>, GitHubError> {
+        let (owner, name) = split_repo(repo)?;
+
+        const QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!, $cursor: String) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      commits(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          commit { oid message authoredDate }
+        }
+      }
+    }
+  }
+}
+"#;
+
+        let mut commits = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let vars = serde_json::json!({
+                "owner": owner,
+                "repo": name,
+                "number": number as i64,
+                "cursor": cursor,
+            });
+            let data: CommitsPageData = self.graphql(QUERY, &vars).await?;
+            let page = data.repository.pull_request.commits;
+            commits.extend(page.nodes.into_iter().map(|n| PrCommit {
+                sha: n.commit.oid,
+                message: n.commit.message,
+                authored_at: n.commit.authored_date,
+            }));
+            if !page.page_info.has_next_page {
+                return Ok(commits);
+            }
+            cursor = page.page_info.end_cursor;
+        }
+    }
+
+    /// What `pr` changed after its commit `since`: the newer commits and the
+    /// PR files touched by `since...head`.
+    pub async fn pr_since_commit(
+        &self,
+        repo: &str,
+        pr: &PullRequest,
+        since: &str,
+    ) -> Result<PrSinceCommit, GitHubError> {
+        let commits = self.list_pr_commits(repo, pr.number).await?;
+        let newer = commits_since(&commits, since).ok_or_else(|| {
+            GitHubError::Api(format!("commit {since} is not part of PR #{}", pr.number))
+        })?;
+        let base_sha = commits[commits.len() - newer.len() - 1].sha.clone();
+        let range_diff = self.get_compare_diff(repo, &base_sha, &pr.head_sha).await?;
+
+        Ok(PrSinceCommit {
+            files: files_in_range(&pr.files, &range_diff),
+            base_sha,
+            commits: newer.to_vec(),
+        })
+    }
+
     pub async fn get_pr_with_patches(
         &self,
         repo: &str,
@@ -527,6 +1139,7 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
         )?;
 
         let patch_map = parse_raw_diff(&raw_diff);
+        let renames = parse_renames(&raw_diff);
 
         let files = pr
             .files
@@ -535,6 +1148,9 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
                 if let Some(patch) = patch_map.get(&f.filename) {
                     f.patch = Some(patch.clone());
                 }
+                if let Some(old) = renames.get(&f.filename) {
+                    f.previous_filename = Some(old.clone());
+                }
                 f
             })
             .collect();
@@ -561,53 +1177,128 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
         String::from_utf8(bytes).map_err(|e| GitHubError::Parse(format!("utf8: {e}")))
     }
 
+    /// Fetch contents of `paths` at `git_ref`, `BLOB_BATCH_SIZE` blobs per
+    /// GraphQL query. Missing, binary and oversized files map to `None`. A
+    /// blob GraphQL fails on is retried over REST.
+    pub async fn get_file_contents_batch(
+        &self,
+        repo: &str,
+        git_ref: &str,
+        paths: &[String],
+    ) -> Result<HashMap<String, Option<String>>, GitHubError> {
+        let (owner, name) = split_repo(repo)?;
+        let futs: Vec<_> = paths
+            .chunks(BLOB_BATCH_SIZE)
+            .map(|chunk| self.get_blob_chunk(owner, name, git_ref, chunk))
+            .collect();
+
+        let chunks: Vec<_> = stream::iter(futs)
+            .buffered(MAX_CONCURRENT_FETCHES)
+            .collect()
+            .await;
+        let mut contents = HashMap::with_capacity(paths.len());
+        for chunk in chunks {
+            contents.extend(chunk?);
+        }
+        Ok(contents)
+    }
+
+    async fn get_blob_chunk(
+        &self,
+        owner: &str,
+        name: &str,
+        git_ref: &str,
+        paths: &[String],
+    ) -> Result<Vec<(String, Option<String>)>, GitHubError> {
+        let mut params = Vec::with_capacity(paths.len());
+        let mut fields = String::new();
+        let mut vars = serde_json::json!({ "owner": owner, "repo": name });
+        for (i, path) in paths.iter().enumerate() {
+            params.push(format!("$p{i}: String!"));
+            fields.push_str(&format!(
+                "    f{i}: object(expression: $p{i}) {{ ... on Blob {{ text isBinary byteSize }} }}\n"
+            ));
+            vars[format!("p{i}")] = serde_json::json!(format!("{git_ref}:{path}"));
+        }
+        let query = format!(
+            "query($owner: String!, $repo: String!, {}) {{\n  repository(owner: $owner, name: $repo) {{\n{}  }}\n}}",
+            params.join(", "),
+            fields
+        );
+
+        // Without data every blob falls back to REST below
+        let resp: GraphQLResponse<BlobBatchData> = match self.graphql_response(&query, &vars).await {
+            Ok(resp) => resp,
+            Err(e @ GitHubError::RateLimited { .. }) => return Err(e),
+            Err(_) => GraphQLResponse { data: None, errors: None },
+        };
+        let failed: HashSet<String> = resp
+            .errors
+            .iter()
+            .flatten()
+            .filter_map(|e| e.path.get(1)?.as_str().map(str::to_string))
+            .collect();
+        let mut blobs = resp.data.map(|d| d.repository).unwrap_or_default();
+
+        let repo = format!("{owner}/{name}");
+        let mut contents = Vec::with_capacity(paths.len());
+        for (i, path) in paths.iter().enumerate() {
+            let alias = format!("f{i}");
+            let content = match blobs.remove(&alias) {
+                Some(blob) if !failed.contains(&alias) => blob.and_then(BlobNode::into_text),
+                _ => missing_as_none(self.get_file_content(&repo, path, git_ref).await)?,
+            };
+            contents.push((path.clone(), content));
+        }
+        Ok(contents)
+    }
+
     pub async fn get_file_pairs(
         &self,
         repo: &str,
         files: &[PrFile],
         base_ref: &str,
         head_ref: &str,
-    ) -> Vec<FilePair> {
-        let futs: Vec<_> = files
+    ) -> Result<Vec<FilePair>, GitHubError> {
+        // A renamed file's before content lives at its old path
+        let before_path = |f: &PrFile| f.previous_filename.clone().unwrap_or_else(|| f.filename.clone());
+        let before_paths: Vec<String> = files
             .iter()
-            .map(|f| {
-                let filename = f.filename.clone();
-                let status = f.status.clone();
-                let repo = repo.to_string();
-                let base = base_ref.to_string();
-                let head = head_ref.to_string();
-
-                async move {
-                    let before = if status == "added" {
-                        None
-                    } else {
-                        self.get_file_content(&repo, &filename, &base).await.ok()
-                    };
-
-                    let after = if status == "removed" {
-                        None
-                    } else {
-                        self.get_file_content(&repo, &filename, &head).await.ok()
-                    };
-
-                    FilePair {
-                        filename,
-                        status,
-                        before_content: before,
-                        after_content: after,
-                    }
-                }
-            })
+            .filter(|f| f.status != "added")
+            .map(before_path)
             .collect();
+        let after_paths: Vec<String> = files
+            .iter()
+            .filter(|f| f.status != "removed")
+            .map(|f| f.filename.clone())
+            .collect();
+
+        let (mut before, mut after) = tokio::try_join!(
+            self.get_file_contents_batch(repo, base_ref, &before_paths),
+            self.get_file_contents_batch(repo, head_ref, &after_paths),
+        )?;
 
-        futures::future::join_all(futs).await
+        Ok(files
+            .iter()
+            .map(|f| FilePair {
+                filename: f.filename.clone(),
+                status: f.status.clone(),
+                previous_filename: f.previous_filename.clone(),
+                before_content: before.remove(&before_path(f)).flatten(),
+                after_content: after.remove(&f.filename).flatten(),
+            })
+            .collect())
     }
 
+    /// Search code in `repo`, following `Link: rel="next"` for up to
+    /// `max_pages` pages. Results repeated across pages are dropped. If a
+    /// later page fails, the pages so far come back marked incomplete.
     pub async fn search_code(
         &self,
         repo: &str,
         query: &str,
         path_prefix: Option<&str>,
+        max_pages: usize,
     ) -> Result<CodeSearchResponse, GitHubError> {
         let mut q = format!("{} repo:{}", query, repo);
         if let Some(prefix) = path_prefix {
@@ -615,37 +1306,234 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
         }
 
         let encoded_q = urlencoding::encode(&q);
-        let url = format!("{}/search/code?q={}&per_page=100", self.base_url, encoded_q);
+        let mut url = format!("{}/search/code?q={}&per_page=100", self.base_url, encoded_q);
+        let mut merged: Option<CodeSearchResponse> = None;
+        let mut seen = HashSet::new();
+
+        for _ in 0..max_pages.max(1) {
+            let (page, next) = match self.search_code_page(&url).await {
+                Ok(page) => page,
+                Err(e) => match merged.as_mut() {
+                    Some(results) => {
+                        results.incomplete_results = true;
+                        break;
+                    }
+                    None => return Err(e),
+                },
+            };
+
+            let results = merged.get_or_insert_with(|| CodeSearchResponse {
+                total_count: page.total_count,
+                incomplete_results: false,
+                items: Vec::new(),
+            });
+            results.incomplete_results |= page.incomplete_results;
+            for item in page.items {
+                if seen.insert(item.dedup_key()) {
+                    results.items.push(item);
+                }
+            }
 
+            match next {
+                Some(next) => url = next,
+                None => break,
+            }
+        }
+
+        Ok(merged.unwrap_or(CodeSearchResponse {
+            total_count: 0,
+            incomplete_results: false,
+            items: Vec::new(),
+        }))
+    }
+
+    async fn search_code_page(
+        &self,
+        url: &str,
+    ) -> Result<(CodeSearchResponse, Option<String>), GitHubError> {
         let resp = self
-            .http
-            .get(&url)
-            .header(ACCEPT, "application/vnd.github.text-match+json")
-            .send()
+            .send(
+                self.http
+                    .get(url)
+                    .header(ACCEPT, "application/vnd.github.text-match+json"),
+            )
             .await?;
 
         let status = resp.status();
+        if status == StatusCode::UNPROCESSABLE_ENTITY {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(GitHubError::InvalidQuery(validation_message(&body)));
+        }
         if !status.is_success() {
             let body = resp.text().await.unwrap_or_default();
             return Err(GitHubError::Api(format!("Code Search {status}: {body}")));
         }
 
-        resp.json()
+        let next = next_link(resp.headers());
+        let page = resp
+            .json()
             .await
-            .map_err(|e| GitHubError::Parse(e.to_string()))
+            .map_err(|e| GitHubError::Parse(e.to_string()))?;
+        Ok((page, next))
+    }
+
+    /// Set the status for `context` on commit `sha`, e.g. to gate CI on
+    /// inspect's verdict. Long descriptions are truncated.
+    pub async fn create_commit_status(
+        &self,
+        repo: &str,
+        sha: &str,
+        state: CommitState,
+        context: &str,
+        description: &str,
+        target_url: Option<&str>,
+    ) -> Result<(), GitHubError> {
+        let url = format!("{}/repos/{repo}/statuses/{sha}", self.base_url);
+        let status = CommitStatusRequest {
+            state,
+            context,
+            description: description.chars().take(MAX_STATUS_DESCRIPTION).collect(),
+            target_url,
+        };
+        let resp = self.send(self.http.post(&url).json(&status)).await?;
+        let code = resp.status();
+        if !code.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(GitHubError::Api(format!("{code}: {body}")));
+        }
+        Ok(())
+    }
+
+    /// All review comments on a PR, following pagination.
+    pub async fn list_review_comments(
+        &self,
+        repo: &str,
+        number: u64,
+    ) -> Result<Vec<ExistingReviewComment>, GitHubError> {
+        let mut url = format!(
+            "{}/repos/{repo}/pulls/{number}/comments?per_page=100",
+            self.base_url
+        );
+        let mut comments = Vec::new();
+        loop {
+            let resp = self.send(self.http.get(&url)).await?;
+            let status = resp.status();
+            if !status.is_success() {
+                let body = resp.text().await.unwrap_or_default();
+                return Err(GitHubError::Api(format!("{status}: {body}")));
+            }
+            let next = next_link(resp.headers());
+            let page: Vec<RestReviewComment> = resp
+                .json()
+                .await
+                .map_err(|e| GitHubError::Parse(e.to_string()))?;
+            comments.extend(page.into_iter().map(ExistingReviewComment::from));
+            match next {
+                Some(next) => url = next,
+                None => return Ok(comments),
+            }
+        }
     }
 
+    /// Post a review on a PR whose patches are in `files`. Comments on lines
+    /// outside the diff are skipped up front, the rest go out in parts of
+    /// `MAX_REVIEW_COMMENTS`: the first with the review body and event, later
+    /// ones as plain comments. A comment GitHub rejects is dropped and its
+    /// part resubmitted, so one bad line doesn't sink the whole review.
     pub async fn create_review(
         &self,
         repo: &str,
         number: u64,
         review: &CreateReview,
-    ) -> Result<CreateReviewResponse, GitHubError> {
-        self.rest_post(
-            &format!("/repos/{repo}/pulls/{number}/reviews"),
-            review,
-        )
-        .await
+        files: &[PrFile],
+    ) -> Result<PostedReview, GitHubError> {
+        let (valid, mut skipped) = validate_review_comments(&review.comments, files);
+        let parts: Vec<&[ReviewCommentInput]> = if valid.is_empty() {
+            vec![&[]]
+        } else {
+            valid.chunks(MAX_REVIEW_COMMENTS).collect()
+        };
+
+        let total = parts.len();
+        let mut responses = Vec::new();
+        let mut posted = Vec::new();
+        for (i, comments) in parts.into_iter().enumerate() {
+            let part = if i == 0 {
+                CreateReview {
+                    commit_id: review.commit_id.clone(),
+                    event: review.event.clone(),
+                    body: review.body.clone(),
+                    comments: comments.to_vec(),
+                }
+            } else {
+                CreateReview {
+                    commit_id: review.commit_id.clone(),
+                    event: "COMMENT".to_string(),
+                    body: format!("(continued, part {} of {})", i + 1, total),
+                    comments: comments.to_vec(),
+                }
+            };
+            let (resp, part_posted, rejected) =
+                self.submit_review_part(repo, number, part, i > 0).await?;
+            responses.extend(resp);
+            posted.extend(part_posted);
+            skipped.extend(rejected);
+        }
+
+        let first = responses
+            .first()
+            .ok_or_else(|| GitHubError::Api("no review was created".into()))?;
+        Ok(PostedReview {
+            id: first.id,
+            html_url: first.html_url.clone(),
+            review_ids: responses.iter().map(|r| r.id).collect(),
+            posted,
+            skipped,
+        })
+    }
+
+    /// Submit one part of a review, dropping comments a 422 names until
+    /// GitHub accepts it. A continuation left without comments isn't posted.
+    async fn submit_review_part(
+        &self,
+        repo: &str,
+        number: u64,
+        mut review: CreateReview,
+        continuation: bool,
+    ) -> Result<
+        (Option<CreateReviewResponse>, Vec<ReviewCommentInput>, Vec<SkippedComment>),
+        GitHubError,
+    > {
+        let url = format!("{}/repos/{repo}/pulls/{number}/reviews", self.base_url);
+        let mut rejected = Vec::new();
+        loop {
+            if continuation && review.comments.is_empty() {
+                return Ok((None, Vec::new(), rejected));
+            }
+            let resp = self.send(self.http.post(&url).json(&review)).await?;
+            let status = resp.status();
+            if status.is_success() {
+                let created = resp
+                    .json()
+                    .await
+                    .map_err(|e| GitHubError::Parse(e.to_string()))?;
+                return Ok((Some(created), review.comments, rejected));
+            }
+
+            let body = resp.text().await.unwrap_or_default();
+            let offending = if status == StatusCode::UNPROCESSABLE_ENTITY {
+                offending_comment(&body, &review.comments)
+            } else {
+                None
+            };
+            let Some(i) = offending else {
+                return Err(GitHubError::Api(format!("{status}: {body}")));
+            };
+            rejected.push(SkippedComment {
+                comment: review.comments.remove(i),
+                reason: format!("rejected by GitHub: {}", validation_message(&body)),
+            });
+        }
     }
 
     pub async fn fetch_file_contents(
@@ -653,26 +1541,855 @@ query($owner: String!, $repo: String!, $number: Int!, $cursor: String!) {
         repo: &str,
         paths: &[String],
         git_ref: &str,
-    ) -> Vec<(String, String)> {
-        let futs: Vec<_> = paths
+    ) -> Result<Vec<(String, String)>, GitHubError> {
+        let mut contents = self.get_file_contents_batch(repo, git_ref, paths).await?;
+        Ok(paths
             .iter()
-            .map(|path| {
-                let path = path.clone();
-                let repo = repo.to_string();
-                let git_ref = git_ref.to_string();
-                async move {
-                    match self.get_file_content(&repo, &path, &git_ref).await {
-                        Ok(content) => Some((path, content)),
-                        Err(_) => None,
-                    }
-                }
-            })
+            .filter_map(|path| Some((path.clone(), contents.remove(path).flatten()?)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn client(server: &MockServer) -> GitHubClient {
+        GitHubClient::with_base_url("test-token", &server.uri()).unwrap()
+    }
+
+    fn file_body(content: &str) -> serde_json::Value {
+        let encoded =
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, content);
+        serde_json::json!({ "content": encoded })
+    }
+
+    #[tokio::test]
+    async fn retries_after_429() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/a.rs"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/a.rs"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_body("fn a() {}")))
+            .mount(&server)
+            .await;
+
+        let content = client(&server)
+            .get_file_content("o/r", "a.rs", "main")
+            .await
+            .unwrap();
+        assert_eq!(content, "fn a() {}");
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn secondary_limit_403_is_retried_but_plain_403_is_not() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/a.rs"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("x-ratelimit-reset", "0"),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/a.rs"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_body("a")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/b.rs"))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        assert_eq!(client.get_file_content("o/r", "a.rs", "main").await.unwrap(), "a");
+        assert!(matches!(
+            client.get_file_content("o/r", "b.rs", "main").await,
+            Err(GitHubError::Api(_))
+        ));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_with_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/far.rs"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "3600"))
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        let err = client.get_pr("o/r", 1).await.unwrap_err();
+        assert!(matches!(err, GitHubError::RateLimited { reset_in_secs: 0 }));
+        assert_eq!(
+            server.received_requests().await.unwrap().len(),
+            MAX_RETRIES as usize + 1
+        );
+
+        // A reset an hour out isn't worth waiting for.
+        let err = client.get_file_content("o/r", "far.rs", "main").await.unwrap_err();
+        assert!(matches!(err, GitHubError::RateLimited { reset_in_secs: 3600 }));
+        assert_eq!(
+            server.received_requests().await.unwrap().len(),
+            MAX_RETRIES as usize + 2
+        );
+    }
+
+    /// Answers blob batch queries from the `pN` variables: `*.png` is
+    /// binary, `missing*` doesn't exist, `broken*` errors, anything else
+    /// holds "path@ref".
+    fn blob_responder(req: &wiremock::Request) -> ResponseTemplate {
+        let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+        let mut repository = serde_json::Map::new();
+        let mut errors = Vec::new();
+        for (key, value) in body["variables"].as_object().unwrap() {
+            let Some(i) = key.strip_prefix('p') else { continue };
+            let alias = format!("f{i}");
+            let (git_ref, path) = value.as_str().unwrap().split_once(':').unwrap();
+            let blob = if path.ends_with(".png") {
+                serde_json::json!({ "text": null, "isBinary": true, "byteSize": 10 })
+            } else if path.starts_with("missing") {
+                serde_json::Value::Null
+            } else if path.starts_with("broken") {
+                errors.push(serde_json::json!({ "message": "timeout", "path": ["repository", alias] }));
+                serde_json::Value::Null
+            } else {
+                serde_json::json!({ "text": format!("{path}@{git_ref}"), "isBinary": false, "byteSize": 8 })
+            };
+            repository.insert(alias, blob);
+        }
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "data": { "repository": repository },
+            "errors": if errors.is_empty() { serde_json::Value::Null } else { errors.into() },
+        }))
+    }
+
+    #[tokio::test]
+    async fn batch_fetch_chunks_queries_and_decodes_aliases() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(blob_responder)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/contents/broken.rs"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_body("via rest")))
+            .mount(&server)
+            .await;
+
+        let mut paths: Vec<String> = (0..118).map(|i| format!("src/f{i}.rs")).collect();
+        paths.extend(["logo.png", "missing.rs"].map(String::from));
+        paths.push("broken.rs".into());
+        let contents = client(&server)
+            .get_file_contents_batch("o/r", "h1", &paths)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let graphql = requests.iter().filter(|r| r.url.path() == "/graphql").count();
+        assert_eq!(graphql, 3, "121 paths in batches of {BLOB_BATCH_SIZE}");
+        assert_eq!(requests.len(), 4, "one REST fallback for the failed blob");
+
+        assert_eq!(contents.len(), 121);
+        assert_eq!(contents["src/f0.rs"].as_deref(), Some("src/f0.rs@h1"));
+        assert_eq!(contents["src/f117.rs"].as_deref(), Some("src/f117.rs@h1"));
+        assert_eq!(contents["logo.png"], None);
+        assert_eq!(contents["missing.rs"], None);
+        assert_eq!(contents["broken.rs"].as_deref(), Some("via rest"));
+    }
+
+    #[tokio::test]
+    async fn file_pairs_read_base_and_head() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(blob_responder)
+            .mount(&server)
+            .await;
+        let file = |name: &str, status: &str| PrFile {
+            filename: name.into(),
+            status: status.into(),
+            additions: 1,
+            deletions: 0,
+            patch: None,
+            previous_filename: None,
+        };
+
+        let pairs = client(&server)
+            .get_file_pairs(
+                "o/r",
+                &[
+                    file("a.rs", "modified"),
+                    file("new.rs", "added"),
+                    file("old.rs", "removed"),
+                    PrFile {
+                        previous_filename: Some("before.rs".into()),
+                        ..file("after.rs", "renamed")
+                    },
+                ],
+                "b1",
+                "h1",
+            )
+            .await
+            .unwrap();
+        let contents: Vec<_> = pairs
+            .iter()
+            .map(|p| (p.before_content.as_deref(), p.after_content.as_deref()))
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                (Some("a.rs@b1"), Some("a.rs@h1")),
+                (None, Some("new.rs@h1")),
+                (Some("old.rs@b1"), None),
+                (Some("before.rs@b1"), Some("after.rs@h1")),
+            ]
+        );
+        assert_eq!(pairs[3].previous_filename.as_deref(), Some("before.rs"));
+    }
+
+    #[tokio::test]
+    async fn file_pairs_fail_on_rate_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "120"))
+            .mount(&server)
+            .await;
+        let added = PrFile {
+            filename: "limited.rs".into(),
+            status: "added".into(),
+            additions: 1,
+            deletions: 0,
+            patch: None,
+            previous_filename: None,
+        };
+
+        let err = client(&server)
+            .get_file_pairs("o/r", &[added], "b", "h")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GitHubError::RateLimited { .. }));
+    }
+
+    fn pr_node(files: serde_json::Value, next: Option<&str>) -> serde_json::Value {
+        serde_json::json!({
+            "data": { "repository": { "pullRequest": {
+                "number": 7,
+                "title": "Add b",
+                "body": null,
+                "state": "OPEN",
+                "additions": 3,
+                "deletions": 1,
+                "changedFiles": 2,
+                "headRefName": "feature",
+                "baseRefName": "main",
+                "headRefOid": "h1",
+                "baseRefOid": "b1",
+                "files": {
+                    "pageInfo": { "hasNextPage": next.is_some(), "endCursor": next },
+                    "nodes": files,
+                },
+            }}}
+        })
+    }
+
+    async fn mount_pr(server: &MockServer, graphql_path: &str) {
+        Mock::given(method("POST"))
+            .and(path(graphql_path))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": "c1" } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": { "repository": { "pullRequest": { "files": {
+                    "pageInfo": { "hasNextPage": false, "endCursor": null },
+                    "nodes": [{ "path": "b.rs", "additions": 2, "deletions": 0, "changeType": "ADDED" }],
+                }}}}
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(graphql_path))
+            .and(header("authorization", "Bearer test-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pr_node(
+                serde_json::json!([{ "path": "a.rs", "additions": 1, "deletions": 1, "changeType": "MODIFIED" }]),
+                Some("c1"),
+            )))
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn api_url_resolution() {
+        assert_eq!(resolve_api_url(None, None), DEFAULT_API_URL);
+        assert_eq!(resolve_api_url(None, Some("github.com")), DEFAULT_API_URL);
+        assert_eq!(
+            resolve_api_url(None, Some("ghe.example.com")),
+            "https://ghe.example.com/api/v3"
+        );
+        assert_eq!(
+            resolve_api_url(Some("https://ghe.example.com/api/v3/"), Some("other.com")),
+            "https://ghe.example.com/api/v3"
+        );
+        assert_eq!(graphql_url(DEFAULT_API_URL), "https://api.github.com/graphql");
+        assert_eq!(
+            graphql_url("https://ghe.example.com/api/v3"),
+            "https://ghe.example.com/api/graphql"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_pr_follows_file_pages() {
+        let server = MockServer::start().await;
+        mount_pr(&server, "/graphql").await;
+
+        let pr = client(&server).get_pr("o/r", 7).await.unwrap();
+        assert_eq!(pr.number, 7);
+        assert_eq!(pr.head_sha, "h1");
+        let files: Vec<_> = pr.files.iter().map(|f| (f.filename.as_str(), f.status.as_str())).collect();
+        assert_eq!(files, [("a.rs", "modified"), ("b.rs", "added")]);
+    }
+
+    const RENAME_DIFF: &str = "diff --git a/old.rs b/new.rs\n\
+        similarity index 90%\n\
+        rename from old.rs\n\
+        rename to new.rs\n\
+        --- a/old.rs\n\
+        +++ b/new.rs\n\
+        @@ -1 +1 @@\n\
+        -x\n\
+        +y\n\
+        diff --git a/docs/a.md b/guide/a.md\n\
+        similarity index 100%\n\
+        rename from docs/a.md\n\
+        rename to guide/a.md\n";
+
+    #[test]
+    fn renames_include_pure_renames() {
+        let renames = parse_renames(RENAME_DIFF);
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames["new.rs"], "old.rs");
+        assert_eq!(renames["guide/a.md"], "docs/a.md");
+        assert!(parse_renames("diff --git a/x b/x\n--- a/x\n+++ b/x\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_pr_recovers_previous_paths_of_renames() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pr_node(
+                serde_json::json!([
+                    { "path": "new.rs", "additions": 1, "deletions": 1, "changeType": "RENAMED" },
+                    { "path": "guide/a.md", "additions": 0, "deletions": 0, "changeType": "RENAMED" },
+                ]),
+                None,
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/pulls/7"))
+            .and(header("accept", "application/vnd.github.diff"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(RENAME_DIFF))
+            .mount(&server)
+            .await;
+
+        let pr = client(&server).get_pr("o/r", 7).await.unwrap();
+        let previous: Vec<_> = pr.files.iter().map(|f| f.previous_filename.as_deref()).collect();
+        assert_eq!(previous, [Some("old.rs"), Some("docs/a.md")]);
+    }
+
+    #[tokio::test]
+    async fn enterprise_layout_routes_graphql_and_rest() {
+        let server = MockServer::start().await;
+        mount_pr(&server, "/api/graphql").await;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/repos/o/r/pulls/7"))
+            .and(header("accept", "application/vnd.github.diff"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-old\n+new\n",
+            ))
+            .mount(&server)
+            .await;
+
+        let base = format!("{}/api/v3", server.uri());
+        let client = GitHubClient::with_base_url("test-token", &base).unwrap();
+        let pr = client.get_pr_with_patches("o/r", 7).await.unwrap();
+        assert_eq!(pr.files.len(), 2);
+        assert_eq!(pr.files[0].patch.as_deref(), Some("@@ -1 +1 @@\n-old\n+new"));
+        assert_eq!(pr.files[1].patch, None);
+    }
+
+    fn patched_file(name: &str, added: u64) -> PrFile {
+        let lines: String = (1..=added).map(|i| format!("+line {i}\n")).collect();
+        PrFile {
+            filename: name.into(),
+            status: "added".into(),
+            additions: added,
+            deletions: 0,
+            patch: Some(format!("@@ -0,0 +1,{added} @@\n{lines}")),
+            previous_filename: None,
+        }
+    }
+
+    fn review_with(comments: Vec<ReviewCommentInput>) -> CreateReview {
+        CreateReview {
+            commit_id: "h1".into(),
+            event: "COMMENT".into(),
+            body: "inspect".into(),
+            comments,
+        }
+    }
+
+    fn created(id: u64) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "id": id,
+            "html_url": format!("https://github.com/o/r/pull/7#pullrequestreview-{id}"),
+        }))
+    }
+
+    #[tokio::test]
+    async fn create_review_posts_comments() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/o/r/pulls/7/reviews"))
+            .and(body_partial_json(serde_json::json!({
+                "commit_id": "h1",
+                "event": "COMMENT",
+                "comments": [{ "path": "a.rs", "line": 3, "body": "check this" }],
+            })))
+            .respond_with(created(99))
+            .mount(&server)
+            .await;
+
+        let review = review_with(vec![
+            new_comment("a.rs", 3, "check this"),
+            new_comment("a.rs", 40, "outside the diff"),
+            new_comment("z.rs", 1, "not in the PR"),
+        ]);
+        let posted = client(&server)
+            .create_review("o/r", 7, &review, &[patched_file("a.rs", 5)])
+            .await
+            .unwrap();
+        assert_eq!(posted.id, 99);
+        assert_eq!(posted.posted.len(), 1);
+        let reasons: Vec<_> = posted.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(reasons, ["line not in diff", "not a changed file"]);
+    }
+
+    #[tokio::test]
+    async fn create_review_posts_large_reviews_in_parts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/o/r/pulls/7/reviews"))
+            .and(body_partial_json(serde_json::json!({ "body": "inspect" })))
+            .respond_with(created(1))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/o/r/pulls/7/reviews"))
+            .respond_with(created(2))
+            .mount(&server)
+            .await;
+
+        let comments = (1..=65).map(|line| new_comment("a.rs", line, "note")).collect();
+        let mut review = review_with(comments);
+        review.event = "REQUEST_CHANGES".into();
+        let posted = client(&server)
+            .create_review("o/r", 7, &review, &[patched_file("a.rs", 65)])
+            .await
+            .unwrap();
+        assert_eq!(posted.review_ids, [1, 2, 2]);
+        assert_eq!(posted.posted.len(), 65);
+
+        let bodies: Vec<serde_json::Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect();
+        let parts: Vec<_> = bodies
+            .iter()
+            .map(|b| (b["event"].as_str().unwrap(), b["comments"].as_array().unwrap().len()))
             .collect();
+        assert_eq!(parts, [("REQUEST_CHANGES", 30), ("COMMENT", 30), ("COMMENT", 5)]);
+        assert_eq!(bodies[2]["body"], "(continued, part 3 of 3)");
+    }
 
-        futures::future::join_all(futs)
+    #[tokio::test]
+    async fn create_review_drops_comment_named_in_422() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/o/r/pulls/7/reviews"))
+            .and(body_partial_json(serde_json::json!({
+                "comments": [{ "path": "a.rs", "line": 2 }, { "path": "b.rs", "line": 4 }],
+            })))
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "message": "Unprocessable Entity",
+                "errors": ["Line 4 of b.rs could not be resolved"],
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/o/r/pulls/7/reviews"))
+            .respond_with(created(5))
+            .mount(&server)
+            .await;
+
+        let review = review_with(vec![new_comment("a.rs", 2, "keep"), new_comment("b.rs", 4, "bad")]);
+        let posted = client(&server)
+            .create_review("o/r", 7, &review, &[patched_file("a.rs", 5), patched_file("b.rs", 5)])
             .await
+            .unwrap();
+        assert_eq!(posted.id, 5);
+        assert_eq!(posted.posted.len(), 1);
+        assert_eq!(posted.posted[0].path, "a.rs");
+        assert_eq!(posted.skipped.len(), 1);
+        assert_eq!(posted.skipped[0].comment.path, "b.rs");
+        assert_eq!(
+            posted.skipped[0].reason,
+            "rejected by GitHub: Line 4 of b.rs could not be resolved"
+        );
+    }
+
+    fn search_item(path: &str, fragment: &str) -> serde_json::Value {
+        serde_json::json!({
+            "name": path,
+            "path": path,
+            "repository": { "full_name": "o/r" },
+            "html_url": format!("https://github.com/o/r/blob/main/{path}"),
+            "text_matches": [{ "fragment": fragment, "matches": [] }],
+        })
+    }
+
+    async fn mount_first_search_page(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/search/code"))
+            .and(query_param("per_page", "100"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "link",
+                        format!(
+                            r#"<{0}/search/code?page=2>; rel="next", <{0}/search/code?page=2>; rel="last""#,
+                            server.uri()
+                        )
+                        .as_str(),
+                    )
+                    .set_body_json(serde_json::json!({
+                        "total_count": 3,
+                        "items": [search_item("a.rs", "foo()"), search_item("b.rs", "foo()")],
+                    })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn search_code_merges_pages_and_dedupes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search/code"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "total_count": 3,
+                "items": [search_item("b.rs", "foo()"), search_item("c.rs", "foo()")],
+            })))
+            .mount(&server)
+            .await;
+        mount_first_search_page(&server).await;
+
+        let client = client(&server);
+        let results = client.search_code("o/r", "foo", None, 5).await.unwrap();
+        let paths: Vec<_> = results.items.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, ["a.rs", "b.rs", "c.rs"]);
+        assert!(!results.incomplete_results);
+
+        let first_only = client.search_code("o/r", "foo", None, 1).await.unwrap();
+        assert_eq!(first_only.items.len(), 2);
+        assert_eq!(first_only.total_count, 3);
+    }
+
+    #[tokio::test]
+    async fn search_code_keeps_first_page_when_next_is_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search/code"))
+            .and(query_param("page", "2"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("retry-after", "3600"),
+            )
+            .mount(&server)
+            .await;
+        mount_first_search_page(&server).await;
+
+        let results = client(&server).search_code("o/r", "foo", None, 5).await.unwrap();
+        assert_eq!(results.items.len(), 2);
+        assert!(results.incomplete_results);
+    }
+
+    #[tokio::test]
+    async fn search_code_reports_rejected_queries() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/search/code"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "message": "Validation Failed",
+                "errors": [{ "message": "The search is longer than 256 characters." }],
+            })))
+            .mount(&server)
+            .await;
+
+        let err = client(&server).search_code("o/r", "foo", None, 5).await.unwrap_err();
+        match err {
+            GitHubError::InvalidQuery(msg) => {
+                assert_eq!(msg, "The search is longer than 256 characters.")
+            }
+            other => panic!("expected InvalidQuery, got {other:?}"),
+        }
+    }
+
+    fn new_comment(path: &str, line: u64, body: &str) -> ReviewCommentInput {
+        ReviewCommentInput {
+            path: path.into(),
+            line,
+            body: body.into(),
+            start_line: None,
+        }
+    }
+
+    fn existing_comment(path: &str, line: u64, body: &str) -> ExistingReviewComment {
+        ExistingReviewComment {
+            path: path.into(),
+            line: Some(line),
+            body: body.into(),
+            author: "inspect-bot".into(),
+            in_reply_to: None,
+        }
+    }
+
+    #[test]
+    fn duplicate_comments_are_dropped() {
+        let existing = [existing_comment("a.rs", 10, "Missing  null check\n")];
+        let new = [
+            new_comment("a.rs", 10, "missing null check"),
+            new_comment("a.rs", 13, "Missing null check"),
+            new_comment("a.rs", 14, "Missing null check"),
+            new_comment("b.rs", 10, "Missing null check"),
+            new_comment("a.rs", 10, "Unbounded retry loop"),
+        ];
+
+        let kept: Vec<_> = filter_duplicate_comments(&new, &existing)
             .into_iter()
-            .flatten()
-            .collect()
+            .map(|c| (c.path, c.line))
+            .collect();
+        assert_eq!(
+            kept,
+            [("a.rs".to_string(), 14), ("b.rs".to_string(), 10), ("a.rs".to_string(), 10)]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_review_comments_follows_pages() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/pulls/7/comments"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                "path": "b.rs",
+                "line": null,
+                "original_line": 4,
+                "body": "outdated",
+                "user": { "login": "bob" },
+                "in_reply_to_id": 1,
+            }])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/pulls/7/comments"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "link",
+                        format!(r#"<{}/repos/o/r/pulls/7/comments?page=2>; rel="next""#, server.uri())
+                            .as_str(),
+                    )
+                    .set_body_json(serde_json::json!([{
+                        "path": "a.rs",
+                        "line": 3,
+                        "original_line": 3,
+                        "body": "first",
+                        "user": { "login": "alice" },
+                    }])),
+            )
+            .mount(&server)
+            .await;
+
+        let comments = client(&server).list_review_comments("o/r", 7).await.unwrap();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].author, "alice");
+        assert_eq!(comments[0].in_reply_to, None);
+        assert_eq!(comments[1].line, Some(4));
+        assert_eq!(comments[1].in_reply_to, Some(1));
+    }
+
+    #[tokio::test]
+    async fn commit_status_payloads() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/o/r/statuses/h1"))
+            .respond_with(ResponseTemplate::new(201))
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        client
+            .create_commit_status("o/r", "h1", CommitState::Success, "inspect", "0 critical, 1 high of 4 entities", None)
+            .await
+            .unwrap();
+        client
+            .create_commit_status(
+                "o/r",
+                "h1",
+                CommitState::Failure,
+                "inspect",
+                &"x".repeat(200),
+                Some("https://example.com/run/1"),
+            )
+            .await
+            .unwrap();
+
+        let bodies: Vec<serde_json::Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect();
+        assert_eq!(
+            bodies[0],
+            serde_json::json!({
+                "state": "success",
+                "context": "inspect",
+                "description": "0 critical, 1 high of 4 entities",
+            })
+        );
+        assert_eq!(bodies[1]["state"], "failure");
+        assert_eq!(bodies[1]["description"].as_str().unwrap().len(), MAX_STATUS_DESCRIPTION);
+        assert_eq!(bodies[1]["target_url"], "https://example.com/run/1");
+    }
+
+    fn commit(sha: &str) -> PrCommit {
+        PrCommit {
+            sha: sha.into(),
+            message: format!("commit {sha}"),
+            authored_at: "2026-01-01T00:00:00Z".into(),
+        }
+    }
+
+    #[test]
+    fn commits_since_takes_the_tail_after_a_sha() {
+        let commits = [commit("aaa111"), commit("bbb222"), commit("ccc333")];
+        let shas = |c: &[PrCommit]| c.iter().map(|c| c.sha.clone()).collect::<Vec<_>>();
+        assert_eq!(shas(commits_since(&commits, "aaa").unwrap()), ["bbb222", "ccc333"]);
+        assert!(commits_since(&commits, "ccc333").unwrap().is_empty());
+        assert!(commits_since(&commits, "ddd").is_none());
+        assert!(commits_since(&commits, "").is_none());
+    }
+
+    #[tokio::test]
+    async fn pr_since_commit_keeps_files_touched_after_the_commit() {
+        let server = MockServer::start().await;
+        let commits_page = |nodes: serde_json::Value, next: Option<&str>| {
+            serde_json::json!({
+                "data": { "repository": { "pullRequest": { "commits": {
+                    "pageInfo": { "hasNextPage": next.is_some(), "endCursor": next },
+                    "nodes": nodes,
+                }}}}
+            })
+        };
+        let node = |oid: &str| serde_json::json!({
+            "commit": { "oid": oid, "message": "m", "authoredDate": "2026-01-01T00:00:00Z" }
+        });
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "cursor": "c1" } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(commits_page(
+                serde_json::json!([node("ccc333")]),
+                None,
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(commits_page(
+                serde_json::json!([node("aaa111"), node("bbb222")]),
+                Some("c1"),
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/o/r/compare/aaa111...ccc333"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "diff --git a/b.rs b/b.rs\n--- a/b.rs\n+++ b/b.rs\n@@ -1 +1 @@\n-x\n+y\n",
+            ))
+            .mount(&server)
+            .await;
+
+        let pr_file = |name: &str| PrFile {
+            filename: name.into(),
+            status: "added".into(),
+            additions: 1,
+            deletions: 0,
+            patch: None,
+            previous_filename: None,
+        };
+        let pr = PullRequest {
+            number: 7,
+            title: "t".into(),
+            body: None,
+            state: "OPEN".into(),
+            additions: 2,
+            deletions: 0,
+            changed_files: 2,
+            head_ref: "feature".into(),
+            base_ref: "main".into(),
+            head_sha: "ccc333".into(),
+            base_sha: "base".into(),
+            files: vec![pr_file("a.rs"), pr_file("b.rs")],
+        };
+
+        let client = client(&server);
+        let since = client.pr_since_commit("o/r", &pr, "aaa1").await.unwrap();
+        assert_eq!(since.base_sha, "aaa111");
+        let shas: Vec<_> = since.commits.iter().map(|c| c.sha.as_str()).collect();
+        assert_eq!(shas, ["bbb222", "ccc333"]);
+        assert_eq!(since.files.len(), 1);
+        assert_eq!(since.files[0].filename, "b.rs");
+        assert_eq!(since.files[0].status, "modified");
+        assert_eq!(since.files[0].patch.as_deref(), Some("@@ -1 +1 @@\n-x\n+y"));
+
+        let err = client.pr_since_commit("o/r", &pr, "fff").await.unwrap_err();
+        assert!(matches!(err, GitHubError::Api(msg) if msg.contains("not part of PR #7")));
     }
 }
@@ -0,0 +1,854 @@
+#[cfg(test)]
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use sem_core::parser::graph::EntityGraph;
+use sem_core::parser::plugins::{create_default_registry, ParserRegistry};
+use serde::{Deserialize, Serialize};
+
+use crate::analyze::{list_source_files, AnalyzeError};
+use crate::graph_cache::GraphCache;
+use crate::risk::CONFIG_FILE;
+use crate::search::FileFilter;
+
+/// Extensions the entity parsers handle, lowercase and without the dot.
+pub const DEFAULT_SOURCE_EXTENSIONS: &[&str] =
+    &["rs", "ts", "tsx", "js", "jsx", "py", "go", "java", "c", "cpp", "rb", "cs", "php"];
+
+/// Which tracked files go into the entity graph, read from the `[graph]`
+/// section of `.inspect.toml` at the repo root:
+///
+/// ```toml
+/// [graph]
+/// include_extensions = ["kt", "swift"]
+/// exclude_paths = ["vendor/**", "third_party/**"]
+/// ```
+///
+/// `include_extensions` adds to [`DEFAULT_SOURCE_EXTENSIONS`]. Files matching
+/// an `exclude_paths` glob stay out of the graph, so nothing in them counts
+/// as a dependent.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphConfig {
+    pub include_extensions: Vec<String>,
+    pub exclude_paths: Vec<String>,
+}
+
+/// Top level of `.inspect.toml`. Other sections are ignored here.
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    graph: GraphConfig,
+}
+
+impl GraphConfig {
+    /// Load `.inspect.toml` from the repo root. A missing file means defaults.
+    pub fn load(repo_root: &Path) -> Result<Self, String> {
+        let path = repo_root.join(CONFIG_FILE);
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        Self::from_toml(&raw).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse and validate the `[graph]` section of a config file.
+    pub fn from_toml(raw: &str) -> Result<Self, String> {
+        let file: ConfigFile = toml::from_str(raw).map_err(|e| format!("invalid config: {}", e))?;
+        file.graph.excludes()?;
+        Ok(file.graph)
+    }
+
+    /// Whether `path` has a default or included source extension.
+    pub fn is_source_file(&self, path: &str) -> bool {
+        let Some((_, ext)) = path.rsplit_once('.') else {
+            return false;
+        };
+        let ext = ext.to_lowercase();
+        DEFAULT_SOURCE_EXTENSIONS.contains(&ext.as_str())
+            || self
+                .include_extensions
+                .iter()
+                .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+    }
+
+    /// The `exclude_paths` globs as a filter that keeps everything else.
+    pub fn excludes(&self) -> Result<FileFilter, String> {
+        FileFilter::new(&[], &self.exclude_paths).map_err(|e| format!("invalid graph.exclude_paths glob: {}", e))
+    }
+}
+
+/// Which edges to follow when walking the graph from an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Entities that depend on the start entity (callers, users)
+    Dependents,
+    /// Entities the start entity depends on (callees)
+    Dependencies,
+    Both,
+}
+
+/// An entity in the graph, located by file and line range.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEntity {
+    pub entity_id: String,
+    pub name: String,
+    pub entity_type: String,
+    pub file_path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// An entity reached from a start entity, with the hop count and edge kind.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNeighbor {
+    #[serde(flatten)]
+    pub entity: GraphEntity,
+    pub depth: usize,
+    /// "dependent" or "dependency"
+    pub relation: &'static str,
+}
+
+/// Build the entity graph for the repo's current working tree, independent
+/// of any diff, through the same in-memory cache as [`cached_graph`]. With
+/// `disk`, a clean tree's graph is loaded from there when it was stored
+/// before, and stored after it is built.
+pub fn build_graph(repo_path: &Path, disk: Option<&GraphCache>) -> Result<Arc<EntityGraph>, AnalyzeError> {
+    let registry = create_default_registry();
+    let config = GraphConfig::load(repo_path).map_err(AnalyzeError::Config)?;
+    let sources = list_source_files(repo_path, &config)?;
+    Ok(cached_graph(repo_path, &sources.files, &registry, disk).0)
+}
+
+/// Load the graph for `files` from `disk` when the tree is clean and it was
+/// stored before, else build it (and store it, if the tree is clean).
+/// Returns the graph and whether it was loaded.
+fn build_or_load(
+    repo_root: &Path,
+    clean_tree: Option<&str>,
+    files: &[String],
+    registry: &ParserRegistry,
+    disk: Option<&GraphCache>,
+) -> (EntityGraph, bool) {
+    let (Some(disk), Some(tree)) = (disk, clean_tree) else {
+        return (build_entity_graph(repo_root, files, registry), false);
+    };
+    if let Some(graph) = disk.load(repo_root, tree, files) {
+        return (graph, true);
+    }
+    let graph = build_entity_graph(repo_root, files, registry);
+    // A cache that can't be written only costs the next run its speedup
+    let _ = disk.store(repo_root, tree, files, &graph);
+    (graph, false)
+}
+
+/// The part of `files` a change can reach in `hops` steps: the changed
+/// files, then the files mentioning `names` (the changed entities) as a
+/// whole word, then the files mentioning any entity in those, and so on.
+/// Mentions are found with `git grep`, so the graph built from the result
+/// sees every dependent within reach, plus some files that only share a name.
+pub(crate) fn neighborhood_files(
+    repo_path: &Path,
+    files: &[String],
+    changed_files: &[&str],
+    names: Vec<String>,
+    hops: usize,
+    registry: &ParserRegistry,
+) -> Result<Vec<String>, AnalyzeError> {
+    let candidates: HashSet<&str> = files.iter().map(String::as_str).collect();
+    let mut selected: HashSet<String> = changed_files
+        .iter()
+        .filter(|f| candidates.contains(**f))
+        .map(|f| f.to_string())
+        .collect();
+    let mut searched: BTreeSet<String> = BTreeSet::new();
+    let mut names: BTreeSet<String> = names.into_iter().filter(|n| !n.is_empty()).collect();
+
+    for hop in 1..=hops {
+        if names.is_empty() {
+            break;
+        }
+        let mut reached: Vec<String> = files_mentioning(repo_path, &names)?
+            .into_iter()
+            .filter(|f| candidates.contains(f.as_str()) && !selected.contains(f))
+            .collect();
+        if reached.is_empty() {
+            break;
+        }
+        reached.sort();
+        selected.extend(reached.iter().cloned());
+        searched.append(&mut names);
+        if hop < hops {
+            // Parsed only for the names of what's in them
+            let graph = EntityGraph::build(repo_path, &reached, registry);
+            names = graph
+                .entities
+                .values()
+                .map(|e| e.name.clone())
+                .filter(|n| !n.is_empty() && !searched.contains(n))
+                .collect();
+        }
+    }
+
+    Ok(files.iter().filter(|f| selected.contains(*f)).cloned().collect())
+}
+
+/// Tracked files under `repo_path` that contain any of `names` as a whole
+/// word, via `git grep`.
+fn files_mentioning(repo_path: &Path, names: &BTreeSet<String>) -> Result<Vec<String>, AnalyzeError> {
+    let mut child = Command::new("git")
+        .args(["grep", "-l", "-z", "-w", "-F", "-f", "-"])
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AnalyzeError::Git(format!("failed to run git grep: {}", e)))?;
+    {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        for name in names {
+            writeln!(stdin, "{}", name).map_err(|e| AnalyzeError::Io(e.to_string()))?;
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| AnalyzeError::Git(format!("failed to run git grep: {}", e)))?;
+
+    // Exit code 1 means nothing matched
+    match output.status.code() {
+        Some(0) => {}
+        Some(1) => return Ok(vec![]),
+        _ => {
+            return Err(AnalyzeError::Git(format!(
+                "git grep failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+    Ok(output
+        .stdout
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).into_owned())
+        .collect())
+}
+
+#[cfg(test)]
+thread_local! {
+    static GRAPHS_BUILT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// `EntityGraph::build`, counted so tests can check a pipeline never builds one.
+pub(crate) fn build_entity_graph(root: &Path, files: &[String], registry: &ParserRegistry) -> EntityGraph {
+    #[cfg(test)]
+    GRAPHS_BUILT.with(|n| n.set(n.get() + 1));
+    EntityGraph::build(root, files, registry)
+}
+
+/// Entity graphs built on the calling thread so far.
+#[cfg(test)]
+pub(crate) fn graphs_built() -> usize {
+    GRAPHS_BUILT.with(Cell::get)
+}
+
+/// Whole-repo graphs kept in memory across calls in this process.
+const GRAPH_CACHE_CAPACITY: usize = 2;
+
+/// (repo root, graph key, graph), most recently used at the back. The key is
+/// the [`tree_key`] plus a hash of the file list the graph was built from.
+static GRAPH_CACHE: Mutex<VecDeque<(PathBuf, String, Arc<EntityGraph>)>> = Mutex::new(VecDeque::new());
+
+/// HEAD's tree and the uncommitted changes on top of it.
+struct TreeState {
+    tree: String,
+    /// `git status --porcelain -z` output, empty for a clean tree
+    status: Vec<u8>,
+}
+
+impl TreeState {
+    /// HEAD's tree SHA if nothing is uncommitted, untracked files included.
+    fn clean_tree(&self) -> Option<&str> {
+        self.status.is_empty().then_some(self.tree.as_str())
+    }
+
+    /// The tree SHA plus a fingerprint of uncommitted files (status, sizes,
+    /// mtimes).
+    fn key(&self, repo_path: &Path) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.status.hash(&mut hasher);
+        for entry in self.status.split(|b| *b == 0) {
+            // "XY path"; metadata catches edits to files that were already dirty
+            let Some(path) = entry.get(3..) else {
+                continue;
+            };
+            if let Ok(meta) = std::fs::metadata(repo_path.join(String::from_utf8_lossy(path).as_ref())) {
+                meta.len().hash(&mut hasher);
+                meta.modified().ok().hash(&mut hasher);
+            }
+        }
+        format!("{}:{}:{:016x}", repo_path.display(), self.tree, hasher.finish())
+    }
+}
+
+/// None when git can't say (e.g. no commits yet).
+fn tree_state(repo_path: &Path) -> Option<TreeState> {
+    let git = |args: &[&str]| {
+        let output = Command::new("git").args(args).current_dir(repo_path).output().ok()?;
+        output.status.success().then_some(output.stdout)
+    };
+
+    let tree = git(&["rev-parse", "HEAD^{tree}"])?;
+    let status = git(&["status", "--porcelain", "-z", "--untracked-files=all"])?;
+    Some(TreeState {
+        tree: String::from_utf8_lossy(&tree).trim().to_string(),
+        status,
+    })
+}
+
+/// Identify the working tree a graph is built from: the repo root, HEAD's tree
+/// SHA, and a fingerprint of uncommitted files (status, sizes, mtimes). None
+/// when git can't say (e.g. no commits yet), in which case nothing is cached.
+pub fn tree_key(repo_path: &Path) -> Option<String> {
+    tree_state(repo_path).map(|state| state.key(repo_path))
+}
+
+/// Build the graph for `files` under `repo_root`, reusing the graph from an
+/// earlier call when the tree key and file list match, or from `disk` when
+/// the tree is clean. Returns the graph and whether it came from either cache.
+pub fn cached_graph(
+    repo_root: &Path,
+    files: &[String],
+    registry: &ParserRegistry,
+    disk: Option<&GraphCache>,
+) -> (Arc<EntityGraph>, bool) {
+    let Some(state) = tree_state(repo_root) else {
+        return (Arc::new(build_entity_graph(repo_root, files, registry)), false);
+    };
+    let mut hasher = DefaultHasher::new();
+    files.hash(&mut hasher);
+    let key = format!("{}:{:016x}", state.key(repo_root), hasher.finish());
+
+    {
+        let mut cache = GRAPH_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = cache.iter().position(|(_, k, _)| *k == key) {
+            let entry = cache.remove(pos).expect("position is in bounds");
+            let graph = entry.2.clone();
+            cache.push_back(entry);
+            return (graph, true);
+        }
+    }
+
+    let (graph, loaded) = build_or_load(repo_root, state.clean_tree(), files, registry, disk);
+    let graph = Arc::new(graph);
+    let mut cache = GRAPH_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|(_, k, _)| *k != key);
+    cache.push_back((repo_root.to_path_buf(), key, graph.clone()));
+    while cache.len() > GRAPH_CACHE_CAPACITY {
+        cache.pop_front();
+    }
+    (graph, loaded)
+}
+
+/// Drop the in-memory graphs for `repo_root`, so the next call rebuilds (or
+/// reloads from disk) instead of reusing them.
+pub fn forget_graphs(repo_root: &Path) {
+    let mut cache = GRAPH_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|(root, _, _)| root != repo_root);
+}
+
+/// Find graph entities named `name`, optionally narrowed by a file path suffix.
+/// More than one result means the caller should ask for disambiguation.
+pub fn find_entities(graph: &EntityGraph, name: &str, file_path: Option<&str>) -> Vec<GraphEntity> {
+    let mut found: Vec<GraphEntity> = graph
+        .entities
+        .values()
+        .filter(|e| e.name == name && file_path.map(|fp| e.file_path.ends_with(fp)).unwrap_or(true))
+        .map(|e| GraphEntity {
+            entity_id: e.id.clone(),
+            name: e.name.clone(),
+            entity_type: e.entity_type.clone(),
+            file_path: e.file_path.clone(),
+            start_line: e.start_line,
+            end_line: e.end_line,
+        })
+        .collect();
+    found.sort_by(|a, b| (&a.file_path, a.start_line).cmp(&(&b.file_path, b.start_line)));
+    found
+}
+
+/// Breadth-first walk from `entity_id` up to `depth` hops in the given direction.
+/// Each entity is reported once per direction, at its shortest distance.
+pub fn neighborhood(
+    graph: &EntityGraph,
+    entity_id: &str,
+    direction: Direction,
+    depth: usize,
+) -> Vec<GraphNeighbor> {
+    let mut out = Vec::new();
+
+    for (dir, relation) in [
+        (Direction::Dependents, "dependent"),
+        (Direction::Dependencies, "dependency"),
+    ] {
+        if direction != dir && direction != Direction::Both {
+            continue;
+        }
+
+        let mut seen: HashSet<String> = HashSet::from([entity_id.to_string()]);
+        let mut frontier = vec![entity_id.to_string()];
+
+        for d in 1..=depth {
+            let mut next = Vec::new();
+            for id in &frontier {
+                let neighbors = match dir {
+                    Direction::Dependents => graph.get_dependents(id),
+                    _ => graph.get_dependencies(id),
+                };
+                for e in neighbors {
+                    if seen.insert(e.id.clone()) {
+                        out.push(GraphNeighbor {
+                            entity: GraphEntity {
+                                entity_id: e.id.clone(),
+                                name: e.name.clone(),
+                                entity_type: e.entity_type.clone(),
+                                file_path: e.file_path.clone(),
+                                start_line: e.start_line,
+                                end_line: e.end_line,
+                            },
+                            depth: d,
+                            relation,
+                        });
+                        next.push(e.id.clone());
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+    }
+
+    out
+}
+
+/// What changing an entity could break: its direct neighbors both ways, and
+/// everything depending on it up to some depth.
+#[derive(Debug, Clone, Serialize)]
+pub struct Impact {
+    pub dependents: Vec<GraphEntity>,
+    pub dependencies: Vec<GraphEntity>,
+    /// Dependents up to the requested depth, each at its shortest distance
+    pub transitive_dependents: Vec<GraphNeighbor>,
+    /// How many transitive dependents sit at each depth, starting from 1
+    pub per_level: Vec<usize>,
+    /// Whether dependents continue past the requested depth
+    pub more_beyond_depth: bool,
+}
+
+/// The impact of changing `entity_id`, following dependents `depth` hops.
+pub fn impact(graph: &EntityGraph, entity_id: &str, depth: usize) -> Impact {
+    let direct = |direction| {
+        neighborhood(graph, entity_id, direction, 1)
+            .into_iter()
+            .map(|n| n.entity)
+            .collect()
+    };
+    // One hop further than asked, to tell whether the walk was cut short
+    let mut transitive = neighborhood(graph, entity_id, Direction::Dependents, depth + 1);
+    let more_beyond_depth = transitive.iter().any(|n| n.depth > depth);
+    transitive.retain(|n| n.depth <= depth);
+
+    let mut per_level = vec![0; depth];
+    for n in &transitive {
+        per_level[n.depth - 1] += 1;
+    }
+    Impact {
+        dependents: direct(Direction::Dependents),
+        dependencies: direct(Direction::Dependencies),
+        transitive_dependents: transitive,
+        per_level,
+        more_beyond_depth,
+    }
+}
+
+/// A node in an exported graph, with the entities it depends on.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportNode {
+    pub id: String,
+    pub name: String,
+    pub entity_type: String,
+    pub file_path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Changed in the analyzed diff
+    pub changed: bool,
+    /// Ids of exported nodes this one depends on
+    pub dependencies: Vec<String>,
+}
+
+/// A graph or subgraph as an adjacency list, ordered by file and line.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphExport {
+    pub nodes: Vec<ExportNode>,
+}
+
+/// Export `graph`, or with `roots` only the entities within `depth` hops of
+/// them in either direction. Edges leaving the exported set are dropped;
+/// entities in `changed` are marked.
+pub fn export_graph(
+    graph: &EntityGraph,
+    roots: Option<&[String]>,
+    depth: usize,
+    changed: &HashSet<String>,
+) -> GraphExport {
+    let ids: HashSet<String> = match roots {
+        None => graph.entities.keys().cloned().collect(),
+        Some(roots) => roots
+            .iter()
+            .filter(|id| graph.entities.contains_key(id.as_str()))
+            .flat_map(|id| {
+                neighborhood(graph, id, Direction::Both, depth)
+                    .into_iter()
+                    .map(|n| n.entity.entity_id)
+                    .chain([id.clone()])
+            })
+            .collect(),
+    };
+
+    let mut nodes: Vec<ExportNode> = ids
+        .iter()
+        .filter_map(|id| graph.entities.get(id))
+        .map(|e| {
+            let mut dependencies: Vec<String> = graph
+                .get_dependencies(&e.id)
+                .into_iter()
+                .filter(|d| ids.contains(&d.id))
+                .map(|d| d.id.clone())
+                .collect();
+            dependencies.sort();
+            dependencies.dedup();
+            ExportNode {
+                id: e.id.clone(),
+                name: e.name.clone(),
+                entity_type: e.entity_type.clone(),
+                file_path: e.file_path.clone(),
+                start_line: e.start_line,
+                end_line: e.end_line,
+                changed: changed.contains(&e.id),
+                dependencies,
+            }
+        })
+        .collect();
+    nodes.sort_by(|a, b| (&a.file_path, a.start_line, &a.id).cmp(&(&b.file_path, b.start_line, &b.id)));
+    GraphExport { nodes }
+}
+
+impl GraphExport {
+    /// Graphviz DOT, with nodes labeled `file::name` and changed entities
+    /// filled red. Edges point from an entity to what it depends on.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph inspect {\n    rankdir=LR;\n    node [shape=box, fontname=\"monospace\"];\n");
+        for node in &self.nodes {
+            let label = dot_quote(&format!("{}::{}", node.file_path, node.name));
+            let _ = write!(out, "    {} [label={}", dot_quote(&node.id), label);
+            if node.changed {
+                out.push_str(", style=filled, fillcolor=\"#f8d0d0\", color=\"#d03030\"");
+            }
+            out.push_str("];\n");
+        }
+        for node in &self.nodes {
+            for dep in &node.dependencies {
+                let _ = writeln!(out, "    {} -> {};", dot_quote(&node.id), dot_quote(dep));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// A DOT double-quoted string. Inside quotes `<`, `>` and `{` are literal,
+/// so only quotes, backslashes and newlines need escaping.
+fn dot_quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn init_repo(dir: &Path) {
+        for args in [
+            vec!["init"],
+            vec!["config", "user.email", "test@test.com"],
+            vec!["config", "user.name", "Test"],
+        ] {
+            Command::new("git").args(&args).current_dir(dir).output().unwrap();
+        }
+    }
+
+    fn add_all(dir: &Path) {
+        Command::new("git").args(["add", "-A"]).current_dir(dir).output().unwrap();
+    }
+
+    #[test]
+    fn graph_config_adds_extensions_and_excludes_paths() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::create_dir_all(dir.join("vendor")).unwrap();
+        for file in ["App.kt", "lib.rs", "vendor/dep.rs", "README.md"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        add_all(dir);
+
+        let default = list_source_files(dir, &GraphConfig::default()).unwrap();
+        assert_eq!(default.files, ["lib.rs", "vendor/dep.rs"]);
+        assert_eq!(default.excluded, 0);
+
+        let config =
+            GraphConfig::from_toml("[graph]\ninclude_extensions = [\".kt\"]\nexclude_paths = [\"vendor/**\"]\n").unwrap();
+        let configured = list_source_files(dir, &config).unwrap();
+        assert_eq!(configured.files, ["App.kt", "lib.rs"]);
+        assert_eq!(configured.excluded, 1);
+
+        // Other sections are left to their own loaders
+        assert_eq!(GraphConfig::from_toml("[risk.weights]\nblast_radius = 0.2\n").unwrap(), GraphConfig::default());
+        assert!(GraphConfig::from_toml("[graph]\nexclude_paths = [\"[\"]\n").is_err());
+        assert!(GraphConfig::from_toml("[graph]\nextensions = [\"kt\"]\n").is_err());
+    }
+
+    #[test]
+    fn neighborhood_is_the_changed_files_and_their_mentions() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(dir.join("a.rs"), "fn target() {}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "fn caller() {\n    target();\n}\n").unwrap();
+        std::fs::write(dir.join("c.rs"), "fn retarget() {}\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "target\n").unwrap();
+        add_all(dir);
+
+        let files = list_source_files(dir, &GraphConfig::default()).unwrap().files;
+        let registry = create_default_registry();
+        let reach = |hops| neighborhood_files(dir, &files, &["a.rs"], vec!["target".into()], hops, &registry).unwrap();
+        // Whole words only, and only files that go into the graph
+        assert_eq!(reach(1), ["a.rs", "b.rs"]);
+        assert_eq!(reach(0), ["a.rs"]);
+        assert!(files_mentioning(dir, &BTreeSet::from(["nowhere".to_string()])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ambiguous_names_return_all_candidates() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(dir.join("a.rs"), "fn helper() -> i32 {\n    1\n}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "fn helper() -> i32 {\n    2\n}\n").unwrap();
+        add_all(dir);
+
+        let graph = build_graph(dir, None).unwrap();
+        let all = find_entities(&graph, "helper", None);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].file_path, "a.rs");
+
+        let narrowed = find_entities(&graph, "helper", Some("b.rs"));
+        assert_eq!(narrowed.len(), 1);
+        assert_eq!(narrowed[0].file_path, "b.rs");
+    }
+
+    #[test]
+    fn depth_two_dependents() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(
+            dir.join("main.rs"),
+            "fn leaf() -> i32 {\n    1\n}\n\nfn middle() -> i32 {\n    leaf() + 1\n}\n\nfn top() -> i32 {\n    middle() + 1\n}\n",
+        )
+        .unwrap();
+        add_all(dir);
+
+        let graph = build_graph(dir, None).unwrap();
+        let leaf = &find_entities(&graph, "leaf", None)[0];
+
+        let one = neighborhood(&graph, &leaf.entity_id, Direction::Dependents, 1);
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].entity.name, "middle");
+
+        let two = neighborhood(&graph, &leaf.entity_id, Direction::Dependents, 2);
+        let top = two.iter().find(|n| n.entity.name == "top").unwrap();
+        assert_eq!(top.depth, 2);
+        assert_eq!(top.relation, "dependent");
+
+        let deps = neighborhood(&graph, &leaf.entity_id, Direction::Dependencies, 2);
+        assert!(deps.is_empty());
+    }
+
+    #[test]
+    fn impact_counts_dependents_per_level() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        std::fs::write(
+            dir.join("main.rs"),
+            "fn base() -> i32 {\n    0\n}\n\nfn leaf() -> i32 {\n    base()\n}\n\nfn middle() -> i32 {\n    leaf() + 1\n}\n\nfn top() -> i32 {\n    middle() + 1\n}\n\nfn other() -> i32 {\n    leaf() + 2\n}\n",
+        )
+        .unwrap();
+        add_all(dir);
+
+        let graph = build_graph(dir, None).unwrap();
+        let leaf = &find_entities(&graph, "leaf", None)[0];
+
+        let shallow = impact(&graph, &leaf.entity_id, 1);
+        let mut direct: Vec<&str> = shallow.dependents.iter().map(|e| e.name.as_str()).collect();
+        direct.sort();
+        assert_eq!(direct, ["middle", "other"]);
+        assert_eq!(shallow.dependencies[0].name, "base");
+        assert_eq!(shallow.dependencies[0].entity_type, "function");
+        assert_eq!(shallow.per_level, [2]);
+        assert!(shallow.more_beyond_depth);
+
+        let deep = impact(&graph, &leaf.entity_id, 3);
+        assert_eq!(deep.per_level, [2, 1, 0]);
+        assert_eq!(deep.transitive_dependents.len(), 3);
+        assert!(!deep.more_beyond_depth);
+    }
+
+    #[test]
+    fn tree_key_tracks_working_tree_changes() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        init_repo(dir);
+        assert_eq!(tree_key(dir), None);
+
+        std::fs::write(dir.join("a.rs"), "fn a() {}\n").unwrap();
+        add_all(dir);
+        Command::new("git").args(["commit", "-m", "init"]).current_dir(dir).output().unwrap();
+
+        let clean = tree_key(dir).unwrap();
+        assert_eq!(tree_key(dir).unwrap(), clean);
+
+        std::fs::write(dir.join("a.rs"), "fn a() { 1; }\n").unwrap();
+        let dirty = tree_key(dir).unwrap();
+        assert_ne!(dirty, clean);
+
+        std::fs::write(dir.join("b.rs"), "fn b() {}\n").unwrap();
+        assert_ne!(tree_key(dir).unwrap(), dirty);
+    }
+
+    #[test]
+    fn disk_cache_serves_clean_trees_and_survives_poisoning() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        let cache_dir = TempDir::new().unwrap();
+        let disk = GraphCache::new(cache_dir.path(), crate::graph_cache::DEFAULT_MAX_BYTES);
+        init_repo(dir);
+        std::fs::write(dir.join("a.rs"), "fn leaf() -> i32 {\n    1\n}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "fn top() -> i32 {\n    leaf() + 1\n}\n").unwrap();
+        add_all(dir);
+        Command::new("git").args(["commit", "-m", "init"]).current_dir(dir).output().unwrap();
+
+        let built = graphs_built();
+        let first = build_graph(dir, Some(&disk)).unwrap();
+        let again = build_graph(dir, Some(&disk)).unwrap();
+        assert!(Arc::ptr_eq(&first, &again), "second call came from memory");
+        forget_graphs(dir);
+        let second = build_graph(dir, Some(&disk)).unwrap();
+        assert_eq!(graphs_built(), built + 1, "a forgotten graph came from disk");
+        assert_eq!(second.entities.len(), first.entities.len());
+
+        let stored: Vec<_> = std::fs::read_dir(cache_dir.path())
+            .unwrap()
+            .flat_map(|repo| std::fs::read_dir(repo.unwrap().path()).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(stored.len(), 1);
+        std::fs::write(&stored[0], b"poisoned").unwrap();
+        forget_graphs(dir);
+        let rebuilt = build_graph(dir, Some(&disk)).unwrap();
+        assert_eq!(graphs_built(), built + 2, "a poisoned file means a rebuild");
+        assert_eq!(rebuilt.entities.len(), first.entities.len());
+        forget_graphs(dir);
+        build_graph(dir, Some(&disk)).unwrap();
+        assert_eq!(graphs_built(), built + 2, "the rebuild replaced the poisoned file");
+
+        // Dirty trees are never read from or written to disk
+        std::fs::write(dir.join("a.rs"), "fn leaf() -> i32 {\n    2\n}\n").unwrap();
+        build_graph(dir, Some(&disk)).unwrap();
+        forget_graphs(dir);
+        build_graph(dir, Some(&disk)).unwrap();
+        assert_eq!(graphs_built(), built + 4);
+    }
+
+    fn node(id: &str, name: &str, changed: bool, dependencies: &[&str]) -> ExportNode {
+        ExportNode {
+            id: id.into(),
+            name: name.into(),
+            entity_type: "function".into(),
+            file_path: "src/ops.rs".into(),
+            start_line: 1,
+            end_line: 3,
+            changed,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn dot_escapes_quotes_and_angle_brackets() {
+        let export = GraphExport {
+            nodes: vec![
+                node("src/ops.rs::operator<<", "operator<<", true, &["src/ops.rs::say \"hi\""]),
+                node("src/ops.rs::say \"hi\"", "say \"hi\"", false, &[]),
+                node("src/ops.rs::Vec<T>\\n", "Vec<T>\\n\nnext", false, &[]),
+            ],
+        };
+        let dot = export.to_dot();
+        assert!(dot.starts_with("digraph inspect {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains(r#""src/ops.rs::operator<<" [label="src/ops.rs::operator<<", style=filled"#));
+        assert!(dot.contains(r#""src/ops.rs::say \"hi\"" [label="src/ops.rs::say \"hi\""];"#));
+        assert!(dot.contains(r#"[label="src/ops.rs::Vec<T>\\n\nnext"];"#));
+        assert!(dot.contains(r#""src/ops.rs::operator<<" -> "src/ops.rs::say \"hi\"";"#));
+
+        // Every quote that isn't escaped opens or closes a string
+        for line in dot.lines() {
+            let unescaped = line.replace("\\\\", "").replace("\\\"", "");
+            assert_eq!(unescaped.matches('"').count() % 2, 0, "unbalanced: {}", line);
+        }
+    }
+
+    #[test]
+    fn json_export_is_an_adjacency_list() {
+        let export = GraphExport {
+            nodes: vec![node("a", "a", true, &["b"]), node("b", "b", false, &[])],
+        };
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(json["nodes"][0]["dependencies"], serde_json::json!(["b"]));
+        assert_eq!(json["nodes"][0]["changed"], true);
+        assert_eq!(json["nodes"][1]["entity_type"], "function");
+        assert_eq!(json["nodes"][1]["start_line"], 1);
+    }
+}
@@ -0,0 +1,259 @@
+//! Entity graphs kept on disk between runs, one file per clean commit tree
+//! at `<cache root>/<repo hash>/<tree sha>.bin`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bincode::Options;
+use sem_core::parser::graph::EntityGraph;
+use serde::{Deserialize, Serialize};
+
+/// Layout of the cache files. Files written with another version are
+/// ignored and rebuilt.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Size the cache is pruned back to after each write.
+pub const DEFAULT_MAX_BYTES: u64 = 1 << 30;
+
+/// Written ahead of the graph. Any mismatch makes the file a miss.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Header {
+    format: u32,
+    /// The inspect build that wrote the file; the graph's layout comes from
+    /// the sem-core it was built with
+    inspect_version: String,
+    /// Hash of the source file list the graph was built from
+    files: u64,
+}
+
+impl Header {
+    fn new(files: &[String]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        files.hash(&mut hasher);
+        Self {
+            format: FORMAT_VERSION,
+            inspect_version: env!("CARGO_PKG_VERSION").to_string(),
+            files: hasher.finish(),
+        }
+    }
+}
+
+/// Encoding for cache files. Reads are capped at the file's length, so a
+/// corrupt length prefix can't ask for a huge allocation.
+fn codec(limit: u64) -> impl Options + Copy {
+    bincode::DefaultOptions::new().with_limit(limit)
+}
+
+/// A directory of entity graphs, pruned least recently used first once it
+/// grows past its size cap.
+#[derive(Debug, Clone)]
+pub struct GraphCache {
+    root: PathBuf,
+    max_bytes: u64,
+}
+
+impl GraphCache {
+    pub fn new(root: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            root: root.into(),
+            max_bytes,
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/inspect/graph`, falling back to `~/.cache`.
+    pub fn default_location() -> Option<PathBuf> {
+        let set = |var| std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from);
+        let base = set("XDG_CACHE_HOME").or_else(|| set("HOME").map(|home| home.join(".cache")))?;
+        Some(base.join("inspect").join("graph"))
+    }
+
+    /// The cache at [`GraphCache::default_location`], capped at
+    /// [`DEFAULT_MAX_BYTES`].
+    pub fn at_default_location() -> Option<Self> {
+        Self::default_location().map(|root| Self::new(root, DEFAULT_MAX_BYTES))
+    }
+
+    /// The cache a setting such as an environment variable asks for: `1`
+    /// for the default location, a directory for that one, and empty or `0`
+    /// for none.
+    pub fn from_setting(value: &str) -> Option<Self> {
+        match value.trim() {
+            "" | "0" | "false" => None,
+            "1" | "true" => Self::at_default_location(),
+            dir => Some(Self::new(dir, DEFAULT_MAX_BYTES)),
+        }
+    }
+
+    fn path(&self, repo_root: &Path, tree: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        repo_root.hash(&mut hasher);
+        self.root
+            .join(format!("{:016x}", hasher.finish()))
+            .join(format!("{}.bin", tree))
+    }
+
+    /// The graph stored for `tree` of the repo at `repo_root`, if it was
+    /// built from the same `files`. Missing, corrupt and other-version files
+    /// are all misses.
+    pub fn load(&self, repo_root: &Path, tree: &str, files: &[String]) -> Option<EntityGraph> {
+        let path = self.path(repo_root, tree);
+        let file = fs::File::open(&path).ok()?;
+        let codec = codec(file.metadata().ok()?.len());
+        let mut reader = BufReader::new(file);
+        let header: Header = codec.deserialize_from(&mut reader).ok()?;
+        if header != Header::new(files) {
+            return None;
+        }
+        let graph = codec.deserialize_from(&mut reader).ok()?;
+        // A hit counts as a use when pruning
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(SystemTime::now()));
+        Some(graph)
+    }
+
+    /// Store `graph` as the one for `tree`, then prune. The file is written
+    /// under a temporary name and renamed into place, so concurrent runs
+    /// never read half of it.
+    pub fn store(&self, repo_root: &Path, tree: &str, files: &[String], graph: &EntityGraph) -> std::io::Result<()> {
+        let path = self.path(repo_root, tree);
+        let dir = path.parent().expect("cache files live in a repo directory");
+        fs::create_dir_all(dir)?;
+        let tmp = dir.join(format!(".{}.{}.tmp", tree, std::process::id()));
+        let write = || -> std::io::Result<()> {
+            let mut writer = BufWriter::new(fs::File::create(&tmp)?);
+            let codec = codec(u64::MAX);
+            codec.serialize_into(&mut writer, &Header::new(files)).map_err(std::io::Error::other)?;
+            codec.serialize_into(&mut writer, graph).map_err(std::io::Error::other)?;
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            fs::rename(&tmp, &path)
+        };
+        if let Err(e) = write() {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete the least recently used graphs, across all repos, until the
+    /// cache fits its size cap.
+    fn prune(&self) {
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(&self.root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .flat_map(|repo| fs::read_dir(repo.path()).into_iter().flatten().flatten())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "bin"))
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                Some((meta.modified().ok()?, meta.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sem_core::parser::plugins::create_default_registry;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// A graph of two small Rust files, and the file list it came from.
+    fn sample_graph(dir: &Path) -> (EntityGraph, Vec<String>) {
+        std::fs::write(dir.join("a.rs"), "fn leaf() -> i32 {\n    1\n}\n").unwrap();
+        std::fs::write(dir.join("b.rs"), "fn top() -> i32 {\n    leaf() + 1\n}\n").unwrap();
+        let files = vec!["a.rs".to_string(), "b.rs".to_string()];
+        (EntityGraph::build(dir, &files, &create_default_registry()), files)
+    }
+
+    #[test]
+    fn graphs_round_trip() {
+        let repo = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let cache = GraphCache::new(cache_dir.path(), DEFAULT_MAX_BYTES);
+        let (graph, files) = sample_graph(repo.path());
+
+        assert!(cache.load(repo.path(), "abc123", &files).is_none());
+        cache.store(repo.path(), "abc123", &files, &graph).unwrap();
+        let loaded = cache.load(repo.path(), "abc123", &files).expect("stored graph loads");
+        let mut ids: Vec<&String> = loaded.entities.keys().collect();
+        ids.sort();
+        let mut expected: Vec<&String> = graph.entities.keys().collect();
+        expected.sort();
+        assert_eq!(ids, expected);
+        let leaf = loaded.entities.values().find(|e| e.name == "leaf").unwrap();
+        assert_eq!(loaded.get_dependents(&leaf.id).len(), 1);
+
+        // Another tree, or the same tree with other files, is a miss
+        assert!(cache.load(repo.path(), "def456", &files).is_none());
+        assert!(cache.load(repo.path(), "abc123", &files[..1]).is_none());
+    }
+
+    #[test]
+    fn corrupt_and_foreign_files_are_misses() {
+        let repo = TempDir::new().unwrap();
+        let cache_dir = TempDir::new().unwrap();
+        let cache = GraphCache::new(cache_dir.path(), DEFAULT_MAX_BYTES);
+        let files = vec!["a.rs".to_string()];
+        let path = cache.path(repo.path(), "abc123");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        fs::write(&path, b"\xff\xff\xff\xff\xff\xff\xff\xff not a graph").unwrap();
+        assert!(cache.load(repo.path(), "abc123", &files).is_none());
+
+        let old = Header {
+            format: FORMAT_VERSION + 1,
+            ..Header::new(&files)
+        };
+        fs::write(&path, codec(u64::MAX).serialize(&old).unwrap()).unwrap();
+        assert!(cache.load(repo.path(), "abc123", &files).is_none());
+
+        // Right header, truncated graph
+        fs::write(&path, codec(u64::MAX).serialize(&Header::new(&files)).unwrap()).unwrap();
+        assert!(cache.load(repo.path(), "abc123", &files).is_none());
+    }
+
+    #[test]
+    fn pruning_drops_the_least_recently_used() {
+        let cache_dir = TempDir::new().unwrap();
+        let cache = GraphCache::new(cache_dir.path(), 250);
+        let repo = cache_dir.path().join("repo");
+        let now = SystemTime::now();
+        for (i, tree) in ["old", "mid", "new"].iter().enumerate() {
+            let path = cache.path(&repo, tree);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, [0u8; 100]).unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - Duration::from_secs(60 * (3 - i as u64))).unwrap();
+        }
+
+        cache.prune();
+        assert!(!cache.path(&repo, "old").exists());
+        assert!(cache.path(&repo, "mid").exists());
+        assert!(cache.path(&repo, "new").exists());
+    }
+
+    #[test]
+    fn settings_pick_the_location() {
+        assert!(GraphCache::from_setting("").is_none());
+        assert!(GraphCache::from_setting("0").is_none());
+        assert_eq!(GraphCache::from_setting("/tmp/graphs").unwrap().root, PathBuf::from("/tmp/graphs"));
+    }
+}
@@ -1,10 +1,21 @@
 pub mod analyze;
 pub mod classify;
+pub mod compare;
+pub mod context;
+pub mod files;
+pub mod filter;
 pub mod github;
+pub mod graph;
+pub mod graph_cache;
 pub mod llm;
+pub mod moves;
 pub mod noise;
 pub mod patch;
+pub mod report;
 pub mod risk;
+pub mod riskmap;
+pub mod scope;
 pub mod search;
+pub mod split;
 pub mod types;
 pub mod untangle;
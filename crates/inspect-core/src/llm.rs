@@ -1,7 +1,74 @@
 use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::types::EntityReview;
+use crate::github::ReviewCommentInput;
+use crate::patch::{commentable_lines, nearest_commentable_line, DiffHunk};
+use crate::types::{EntityReview, Relation};
+
+/// How far an issue's line may be from the diff and still anchor a comment.
+const ISSUE_ANCHOR_WINDOW: u64 = 3;
+
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+
+/// Attempts per entity before a transient failure is given up on.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Entities packed into one call by default in batch mode.
+pub const DEFAULT_BATCH_SIZE: usize = 5;
+/// Entities with more before + after + context content than this get their
+/// own call.
+pub const BATCH_MAX_ENTITY_CHARS: usize = 4000;
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    #[error("{0} not set. Set it to use LLM review.")]
+    MissingApiKey(&'static str),
+    #[error("API request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("API error {status}: {body}")]
+    Api { status: u16, body: String },
+    #[error("Failed to parse API response: {0}")]
+    Parse(String),
+    #[error("gave up after {attempts} attempts: {last}")]
+    RetriesExhausted { attempts: u32, last: Box<LlmError> },
+}
+
+impl LlmError {
+    /// Overload, rate limits, server errors and transport failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LlmError::Api { status, .. } => *status == 429 || *status >= 500,
+            LlmError::Request(e) => !e.is_builder(),
+            _ => false,
+        }
+    }
+
+    /// A missing or rejected key; every further request would fail too.
+    pub fn is_auth(&self) -> bool {
+        match self {
+            LlmError::MissingApiKey(_) => true,
+            LlmError::Api { status, .. } => *status == 401 || *status == 403,
+            _ => false,
+        }
+    }
+
+    /// HTTP status of the (last) response, if there was one.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            LlmError::Api { status, .. } => Some(*status),
+            LlmError::RetriesExhausted { last, .. } => last.status(),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EntityLlmReview {
@@ -11,6 +78,32 @@ pub struct EntityLlmReview {
     pub issues: Vec<LlmIssue>,
     pub summary: String,
     pub tokens_used: u64,
+    #[serde(default)]
+    pub usage: TokenUsage,
+}
+
+/// Prompt and reply tokens of a call, or an entity's share of one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+impl std::ops::Add for TokenUsage {
+    type Output = TokenUsage;
+
+    fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens + other.input_tokens,
+            output_tokens: self.output_tokens + other.output_tokens,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -35,11 +128,356 @@ impl std::fmt::Display for LlmVerdict {
 pub struct LlmIssue {
     pub severity: String,
     pub description: String,
+    /// Replacement for new-file lines `start_line..=end_line`, when the fix
+    /// is a small local edit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_replacement: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<u64>,
+}
+
+impl LlmIssue {
+    /// The issue's new-file line span, if it names one.
+    fn span(&self) -> Option<(u64, u64)> {
+        let start = self.start_line.or(self.end_line)?;
+        let end = self.end_line.unwrap_or(start).max(start);
+        Some((start, end))
+    }
+}
+
+/// A review comment on `path` for `issue`. A suggested replacement becomes a
+/// ```` ```suggestion ```` block when every line it replaces is in the diff;
+/// otherwise it is downgraded to a plain comment near the issue's line, or at
+/// `fallback_line` (e.g. the entity's anchor). None when nothing is in the diff.
+pub fn issue_comment(
+    path: &str,
+    issue: &LlmIssue,
+    hunks: &[DiffHunk],
+    fallback_line: Option<u64>,
+) -> Option<ReviewCommentInput> {
+    let commentable = commentable_lines(hunks);
+    let mut body = format!("**{}**: {}", issue.severity, issue.description);
+    let span = issue.span();
+
+    if let (Some(replacement), Some((start, end))) = (&issue.suggested_replacement, span) {
+        let replacement = replacement.trim_end_matches('\n');
+        if (start..=end).all(|l| commentable.contains(&l)) {
+            body.push_str(&format!("\n\n```suggestion\n{}\n```", replacement));
+            return Some(ReviewCommentInput {
+                path: path.to_string(),
+                line: end,
+                body,
+                start_line: (start < end).then_some(start),
+            });
+        }
+        body.push_str(&format!("\n\nSuggested change:\n```\n{}\n```", replacement));
+    }
+
+    let line = span
+        .and_then(|(_, end)| nearest_commentable_line(&commentable, end, ISSUE_ANCHOR_WINDOW))
+        .or(fallback_line.filter(|l| commentable.contains(l)))?;
+    Some(ReviewCommentInput {
+        path: path.to_string(),
+        line,
+        body,
+        start_line: None,
+    })
+}
+
+/// One model reply and the tokens it cost, prompt included.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub usage: TokenUsage,
 }
 
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
-    async fn review_entity(&self, entity: &EntityReview) -> Result<EntityLlmReview, String>;
+    async fn complete(&self, system: &str, prompt: &str, max_tokens: u32) -> Result<Completion, LlmError>;
+
+    async fn review_entity(&self, entity: &EntityReview) -> Result<EntityLlmReview, LlmError> {
+        let reply = self.complete(SYSTEM_PROMPT, &build_prompt(entity), 1024).await?;
+        Ok(parse_llm_output(&reply.text, entity, reply.usage))
+    }
+
+    /// Review `entities` packing up to `max_per_call` small ones into each
+    /// call. Entities over `BATCH_MAX_ENTITY_CHARS`, and the entities of a
+    /// batch call that fails or comes back unparseable, are reviewed one by
+    /// one. Results are in input order.
+    async fn review_entities_batch(&self, entities: &[&EntityReview], max_per_call: usize) -> BatchReviews {
+        let mut results: Vec<Option<Result<EntityLlmReview, LlmError>>> =
+            entities.iter().map(|_| None).collect();
+        let mut discarded = TokenUsage::default();
+        let (small, large): (Vec<usize>, Vec<usize>) =
+            (0..entities.len()).partition(|&i| batchable(entities[i]));
+
+        for chunk in small.chunks(max_per_call.max(1)) {
+            if let [i] = chunk {
+                results[*i] = Some(self.review_entity(entities[*i]).await);
+                continue;
+            }
+            let batch: Vec<&EntityReview> = chunk.iter().map(|&i| entities[i]).collect();
+            let max_tokens = 1024 * chunk.len() as u32;
+            let reply = self.complete(BATCH_SYSTEM_PROMPT, &build_batch_prompt(&batch), max_tokens).await;
+            let split = reply.map(|r| (split_batch_output(&r.text, &batch, r.usage), r.usage));
+            match split {
+                Ok((Ok(reviews), _)) => {
+                    for (&i, review) in chunk.iter().zip(reviews) {
+                        results[i] = Some(Ok(review));
+                    }
+                }
+                failed => {
+                    // An unparseable reply was still paid for
+                    if let Ok((Err(_), usage)) = failed {
+                        discarded = discarded + usage;
+                    }
+                    for &i in chunk {
+                        results[i] = Some(self.review_entity(entities[i]).await);
+                    }
+                }
+            }
+        }
+        for i in large {
+            results[i] = Some(self.review_entity(entities[i]).await);
+        }
+
+        BatchReviews {
+            reviews: results.into_iter().map(|r| r.expect("every entity is reviewed")).collect(),
+            discarded,
+        }
+    }
+}
+
+/// What [`LlmProvider::review_entities_batch`] got back.
+#[derive(Debug)]
+pub struct BatchReviews {
+    /// One result per entity, in input order
+    pub reviews: Vec<Result<EntityLlmReview, LlmError>>,
+    /// Tokens of batch replies that couldn't be split into reviews, and so
+    /// aren't in any review's usage
+    pub discarded: TokenUsage,
+}
+
+// --- Cost ---
+
+/// Price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+const fn price(input_per_mtok: f64, output_per_mtok: f64) -> ModelPricing {
+    ModelPricing {
+        input_per_mtok,
+        output_per_mtok,
+    }
+}
+
+/// Model name prefixes and their list prices. More specific prefixes come
+/// first since the first match wins.
+const PRICING: &[(&str, ModelPricing)] = &[
+    ("claude-opus-4-5", price(5.0, 25.0)),
+    ("claude-opus-4", price(15.0, 75.0)),
+    ("claude-sonnet-4", price(3.0, 15.0)),
+    ("claude-3-7-sonnet", price(3.0, 15.0)),
+    ("claude-3-5-sonnet", price(3.0, 15.0)),
+    ("claude-haiku-4-5", price(1.0, 5.0)),
+    ("claude-3-5-haiku", price(0.8, 4.0)),
+    ("gpt-5-nano", price(0.05, 0.4)),
+    ("gpt-5-mini", price(0.25, 2.0)),
+    ("gpt-5", price(1.25, 10.0)),
+    ("gpt-4.1-nano", price(0.1, 0.4)),
+    ("gpt-4.1-mini", price(0.4, 1.6)),
+    ("gpt-4.1", price(2.0, 8.0)),
+    ("gpt-4o-mini", price(0.15, 0.6)),
+    ("gpt-4o", price(2.5, 10.0)),
+    ("o4-mini", price(1.1, 4.4)),
+    ("o3", price(2.0, 8.0)),
+];
+
+impl ModelPricing {
+    /// List price of `model`, if it is a known hosted model.
+    pub fn for_model(model: &str) -> Option<Self> {
+        PRICING
+            .iter()
+            .find(|(prefix, _)| model.starts_with(prefix))
+            .map(|(_, pricing)| *pricing)
+    }
+
+    pub fn cost(&self, usage: TokenUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_mtok
+            + usage.output_tokens as f64 * self.output_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// Reply tokens assumed per entity when estimating ahead of a run.
+const ESTIMATED_OUTPUT_TOKENS: u64 = 300;
+
+/// Rough token count of `text`, at about four characters a token.
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// Estimated usage of reviewing `entity` in a call of its own.
+pub fn estimate_usage(entity: &EntityReview) -> TokenUsage {
+    TokenUsage {
+        input_tokens: estimate_tokens(SYSTEM_PROMPT) + estimate_tokens(&build_prompt(entity)),
+        output_tokens: ESTIMATED_OUTPUT_TOKENS,
+    }
+}
+
+/// Estimated usage of reviewing `entities` in one batch call.
+fn estimate_batch_usage(entities: &[&EntityReview]) -> TokenUsage {
+    TokenUsage {
+        input_tokens: estimate_tokens(BATCH_SYSTEM_PROMPT) + estimate_tokens(&build_batch_prompt(entities)),
+        output_tokens: ESTIMATED_OUTPUT_TOKENS * entities.len() as u64,
+    }
+}
+
+/// Estimated usage of [`LlmProvider::review_entities_batch`] on a chunk of at
+/// most `per_call` entities in the worst case: its small entities' batch
+/// call comes back unparseable, and every entity is then reviewed alone.
+fn estimate_worst_case(chunk: &[&EntityReview], per_call: usize) -> TokenUsage {
+    let singles = chunk.iter().fold(TokenUsage::default(), |acc, e| acc + estimate_usage(e));
+    let small: Vec<&EntityReview> = chunk.iter().copied().filter(|e| batchable(e)).collect();
+    if per_call > 1 && small.len() > 1 {
+        singles + estimate_batch_usage(&small)
+    } else {
+        singles
+    }
+}
+
+/// Tokens spent over a run, and their cost when the model's price is known.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CostSummary {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub estimated_usd: Option<f64>,
+}
+
+/// Running spend of a review against an optional cap.
+#[derive(Debug, Clone)]
+pub struct CostTracker {
+    pricing: Option<ModelPricing>,
+    max_usd: Option<f64>,
+    summary: CostSummary,
+    /// Estimated cost of calls still in flight
+    reserved_usd: f64,
+}
+
+impl CostTracker {
+    pub fn new(pricing: Option<ModelPricing>, max_usd: Option<f64>) -> Self {
+        Self {
+            pricing,
+            max_usd,
+            summary: CostSummary {
+                estimated_usd: pricing.map(|_| 0.0),
+                ..CostSummary::default()
+            },
+            reserved_usd: 0.0,
+        }
+    }
+
+    /// Whether a call estimated at `estimate` keeps the spend, counting calls
+    /// in flight, within the cap. Always true without a cap or a price.
+    pub fn allows(&self, estimate: TokenUsage) -> bool {
+        match (self.max_usd, self.pricing, self.summary.estimated_usd) {
+            (Some(max), Some(pricing), Some(spent)) => {
+                spent + self.reserved_usd + pricing.cost(estimate) <= max
+            }
+            _ => true,
+        }
+    }
+
+    /// Hold `estimate` against the cap for a call about to start, if it fits.
+    pub fn reserve(&mut self, estimate: TokenUsage) -> bool {
+        if !self.allows(estimate) {
+            return false;
+        }
+        self.reserved_usd += self.pricing.map_or(0.0, |p| p.cost(estimate));
+        true
+    }
+
+    /// Swap a finished call's reservation for what it actually used.
+    pub fn settle(&mut self, estimate: TokenUsage, usage: TokenUsage) {
+        self.reserved_usd -= self.pricing.map_or(0.0, |p| p.cost(estimate));
+        self.record(usage);
+    }
+
+    pub fn record(&mut self, usage: TokenUsage) {
+        self.summary.input_tokens += usage.input_tokens;
+        self.summary.output_tokens += usage.output_tokens;
+        if let (Some(spent), Some(pricing)) = (&mut self.summary.estimated_usd, self.pricing) {
+            *spent += pricing.cost(usage);
+        }
+    }
+
+    pub fn summary(&self) -> CostSummary {
+        self.summary
+    }
+}
+
+/// Review `entities` in calls of up to `per_call` entities (see
+/// [`LlmProvider::review_entities_batch`]), with up to `concurrency` calls in
+/// flight. A call starts only if its worst-case estimate, a failed batch plus
+/// a single call per entity, fits `tracker`'s cap; once one doesn't, no
+/// further calls start and their entities come back `None`.
+/// `on_done` sees each result as it completes; the returned results are in
+/// `entities` order, and a failed entity doesn't affect the others.
+pub async fn review_concurrently(
+    provider: &dyn LlmProvider,
+    entities: &[&EntityReview],
+    per_call: usize,
+    concurrency: usize,
+    tracker: &mut CostTracker,
+    on_done: &mut dyn FnMut(usize, &Result<EntityLlmReview, LlmError>),
+) -> Vec<Option<Result<EntityLlmReview, LlmError>>> {
+    let per_call = per_call.max(1);
+    let tracker = RefCell::new(tracker);
+    let stopped = Cell::new(false);
+
+    // The stream builds each call's future only when a slot frees up, so
+    // admission sees the spend of everything finished or in flight by then
+    let mut calls = stream::iter(entities.chunks(per_call).enumerate())
+        .map(|(n, chunk)| {
+            let estimate = estimate_worst_case(chunk, per_call);
+            let admitted = !stopped.get() && tracker.borrow_mut().reserve(estimate);
+            stopped.set(!admitted);
+            async move {
+                let batch = if admitted {
+                    provider.review_entities_batch(chunk, per_call).await
+                } else {
+                    BatchReviews {
+                        reviews: Vec::new(),
+                        discarded: TokenUsage::default(),
+                    }
+                };
+                (n * per_call, estimate, admitted, batch)
+            }
+        })
+        .buffer_unordered(concurrency.max(1));
+
+    let mut results: Vec<Option<Result<EntityLlmReview, LlmError>>> = entities.iter().map(|_| None).collect();
+    while let Some((first, estimate, admitted, batch)) = calls.next().await {
+        if !admitted {
+            continue;
+        }
+        let usage = batch
+            .reviews
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .fold(batch.discarded, |acc, r| acc + r.usage);
+        tracker.borrow_mut().settle(estimate, usage);
+        for (i, review) in batch.reviews.into_iter().enumerate() {
+            on_done(first + i, &review);
+            results[first + i] = Some(review);
+        }
+    }
+    results
 }
 
 // --- Anthropic structs ---
@@ -50,6 +488,17 @@ struct AnthropicRequest {
     max_tokens: u32,
     system: String,
     messages: Vec<Message>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,7 +515,32 @@ struct AnthropicResponse {
 
 #[derive(Debug, Clone, Deserialize)]
 struct AnthropicContentBlock {
+    #[serde(rename = "type", default)]
+    kind: String,
     text: Option<String>,
+    name: Option<String>,
+    input: Option<serde_json::Value>,
+}
+
+impl AnthropicResponse {
+    fn text(&self) -> String {
+        self.content.iter().find_map(|b| b.text.clone()).unwrap_or_default()
+    }
+
+    fn usage(&self) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.usage.input_tokens,
+            output_tokens: self.usage.output_tokens,
+        }
+    }
+
+    /// The input of the response's call to `tool`, if it made one.
+    fn tool_input(&self, tool: &str) -> Option<&serde_json::Value> {
+        self.content
+            .iter()
+            .find(|b| b.kind == "tool_use" && b.name.as_deref() == Some(tool))
+            .and_then(|b| b.input.as_ref())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -117,32 +591,181 @@ struct LlmOutput {
     summary: String,
 }
 
-fn parse_llm_output(text: &str, entity: &EntityReview, tokens: u64) -> EntityLlmReview {
-    let json_str = text
-        .trim()
-        .strip_prefix("```json")
-        .or_else(|| text.trim().strip_prefix("```"))
-        .and_then(|s| s.strip_suffix("```"))
-        .unwrap_or(text)
-        .trim();
+/// Send a provider request and decode its JSON response, retrying retryable
+/// failures up to `max_attempts` times. Waits honor `retry-after`, else back
+/// off exponentially with jitter.
+async fn send_json<T: DeserializeOwned>(
+    req: reqwest::RequestBuilder,
+    max_attempts: u32,
+) -> Result<T, LlmError> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let this = req.try_clone().expect("JSON request bodies can be cloned");
+        let (err, retry_after) = match this.send().await {
+            Ok(resp) if resp.status().is_success() => {
+                return resp.json().await.map_err(|e| LlmError::Parse(e.to_string()));
+            }
+            Ok(resp) => {
+                let status = resp.status().as_u16();
+                let retry_after = resp
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                let body = resp.text().await.unwrap_or_default();
+                (LlmError::Api { status, body }, retry_after)
+            }
+            Err(e) => (LlmError::Request(e), None),
+        };
+
+        if !err.is_retryable() {
+            return Err(err);
+        }
+        if attempt >= max_attempts {
+            return Err(LlmError::RetriesExhausted {
+                attempts: attempt,
+                last: Box::new(err),
+            });
+        }
+        let wait = retry_after.unwrap_or_else(|| backoff(attempt - 1));
+        tokio::time::sleep(wait.min(MAX_BACKOFF)).await;
+    }
+}
+
+/// Exponential backoff plus up to half again, so concurrent reviews that
+/// failed together don't retry together.
+fn backoff(attempt: u32) -> Duration {
+    let delay = (BASE_BACKOFF * 2u32.pow(attempt)).min(MAX_BACKOFF);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 2000.0)
+}
 
-    let output: LlmOutput = serde_json::from_str(json_str).unwrap_or(LlmOutput {
+/// The model's verdict, if its reply holds the expected JSON.
+fn try_parse_output(text: &str) -> Option<LlmOutput> {
+    serde_json::from_str(extract_json(text)).ok()
+}
+
+/// Decode the model's reply, wherever its JSON sits (see [`extract_json`]).
+/// A reply without the expected JSON comes back as a single info issue
+/// holding the text.
+fn parse_llm_output(text: &str, entity: &EntityReview, usage: TokenUsage) -> EntityLlmReview {
+    let output: LlmOutput = try_parse_output(text).unwrap_or(LlmOutput {
         verdict: LlmVerdict::Comment,
         issues: vec![LlmIssue {
             severity: "info".to_string(),
             description: text.to_string(),
+            suggested_replacement: None,
+            start_line: None,
+            end_line: None,
         }],
         summary: "Could not parse structured response".to_string(),
     });
 
+    review_from(output, entity, usage)
+}
+
+fn review_from(output: LlmOutput, entity: &EntityReview, usage: TokenUsage) -> EntityLlmReview {
     EntityLlmReview {
         entity_name: entity.entity_name.clone(),
         file_path: entity.file_path.clone(),
         verdict: output.verdict,
         issues: output.issues,
         summary: output.summary,
-        tokens_used: tokens,
+        tokens_used: usage.total(),
+        usage,
+    }
+}
+
+/// The JSON in a reply: the whole reply, the inside of a ```json (or bare
+/// ```) fence, or, when the model wrapped it in prose, the span from the
+/// first `{`/`[` to the last `}`/`]`.
+fn extract_json(text: &str) -> &str {
+    let trimmed = text.trim();
+    // A reply that opens with JSON is taken as JSON, even if it quotes a fence
+    if trimmed.starts_with(['{', '[']) {
+        return trimmed.rfind(['}', ']']).map_or(trimmed, |close| &trimmed[..=close]);
     }
+    if let Some(start) = trimmed.find("```") {
+        let fenced = &trimmed[start + 3..];
+        let fenced = fenced.strip_prefix("json").unwrap_or(fenced);
+        if let Some(end) = fenced.find("```") {
+            return fenced[..end].trim();
+        }
+    }
+    match (trimmed.find(['{', '[']), trimmed.rfind(['}', ']'])) {
+        (Some(open), Some(close)) if open < close => &trimmed[open..=close],
+        _ => trimmed,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchItem {
+    entity: usize,
+    #[serde(flatten)]
+    output: LlmOutput,
+}
+
+/// Split a batch reply into one review per entity, in `entities` order.
+/// Entities the model skipped get a `comment` verdict saying so. Tokens are
+/// shared out by each entity's share of the prompt.
+fn split_batch_output(
+    text: &str,
+    entities: &[&EntityReview],
+    usage: TokenUsage,
+) -> Result<Vec<EntityLlmReview>, LlmError> {
+    let items: Vec<BatchItem> =
+        serde_json::from_str(extract_json(text)).map_err(|e| LlmError::Parse(e.to_string()))?;
+    let mut outputs: HashMap<usize, LlmOutput> =
+        items.into_iter().map(|item| (item.entity, item.output)).collect();
+    let shares = split_usage(usage, entities);
+
+    Ok(entities
+        .iter()
+        .zip(shares)
+        .enumerate()
+        .map(|(i, (entity, usage))| {
+            let output = outputs.remove(&(i + 1)).unwrap_or(LlmOutput {
+                verdict: LlmVerdict::Comment,
+                issues: vec![],
+                summary: "No response for this entity".to_string(),
+            });
+            review_from(output, entity, usage)
+        })
+        .collect())
+}
+
+/// `usage` in proportion to each entity's prompt size; the rounding
+/// remainder goes to the last entity so the shares add up.
+fn split_usage(usage: TokenUsage, entities: &[&EntityReview]) -> Vec<TokenUsage> {
+    let sizes: Vec<u64> = entities.iter().map(|e| build_prompt(e).len() as u64).collect();
+    let total: u64 = sizes.iter().sum::<u64>().max(1);
+    let split = |tokens: u64| {
+        let mut shares: Vec<u64> = sizes.iter().map(|size| tokens * size / total).collect();
+        if let Some(last) = shares.last_mut() {
+            *last += tokens - sizes.iter().map(|size| tokens * size / total).sum::<u64>();
+        }
+        shares
+    };
+    split(usage.input_tokens)
+        .into_iter()
+        .zip(split(usage.output_tokens))
+        .map(|(input_tokens, output_tokens)| TokenUsage {
+            input_tokens,
+            output_tokens,
+        })
+        .collect()
+}
+
+/// Whether an entity is small enough to share a call with others.
+fn batchable(entity: &EntityReview) -> bool {
+    let len = |c: &Option<String>| c.as_ref().map_or(0, String::len);
+    let context: usize = entity.context_snippets.iter().map(|s| s.content.len()).sum();
+    len(&entity.before_content) + len(&entity.after_content) + context <= BATCH_MAX_ENTITY_CHARS
 }
 
 // --- AnthropicClient ---
@@ -150,70 +773,149 @@ fn parse_llm_output(text: &str, entity: &EntityReview, tokens: u64) -> EntityLlm
 pub struct AnthropicClient {
     client: reqwest::Client,
     api_key: String,
+    api_base: String,
     model: String,
+    max_attempts: u32,
+    /// Set once the endpoint rejects tool definitions; reviews then parse text
+    tools_unsupported: AtomicBool,
+}
+
+const REVIEW_TOOL: &str = "submit_review";
+
+const TOOL_SYSTEM_PROMPT: &str = "\
+You are a code reviewer. Review the entity for bugs, security issues, and correctness problems, \
+then submit your review with the submit_review tool. start_line and end_line are the AFTER line \
+numbers an issue is about. Only when the fix is a small, localized edit (a typo'd identifier, an \
+inverted condition), add suggested_replacement: the exact new text for lines start_line..end_line, \
+without line numbers. Otherwise omit it.";
+
+/// The `submit_review` tool, whose input mirrors `LlmOutput`.
+fn review_tool() -> AnthropicTool {
+    AnthropicTool {
+        name: REVIEW_TOOL.to_string(),
+        description: "Submit the review of the entity.".to_string(),
+        input_schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "verdict": { "type": "string", "enum": ["approve", "comment", "request_changes"] },
+                "issues": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "severity": { "type": "string", "enum": ["error", "warning", "info"] },
+                            "description": { "type": "string" },
+                            "start_line": { "type": "integer" },
+                            "end_line": { "type": "integer" },
+                            "suggested_replacement": { "type": "string" },
+                        },
+                        "required": ["severity", "description"],
+                    },
+                },
+                "summary": { "type": "string", "description": "One sentence" },
+            },
+            "required": ["verdict", "issues", "summary"],
+        }),
+    }
 }
 
 impl AnthropicClient {
-    pub fn new(model: &str, api_key: Option<&str>) -> Result<Self, String> {
+    pub fn new(model: &str, api_base: Option<&str>, api_key: Option<&str>) -> Result<Self, LlmError> {
         let api_key = api_key
             .map(|k| k.to_string())
             .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok().filter(|k| !k.is_empty()))
-            .ok_or_else(|| "ANTHROPIC_API_KEY not set. Set it to use LLM review.".to_string())?;
+            .ok_or(LlmError::MissingApiKey("ANTHROPIC_API_KEY"))?;
 
         Ok(Self {
             client: reqwest::Client::new(),
             api_key,
+            api_base: api_base
+                .map(|s| s.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| ANTHROPIC_API_BASE.to_string()),
             model: model.to_string(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            tools_unsupported: AtomicBool::new(false),
         })
     }
-}
 
-#[async_trait]
-impl LlmProvider for AnthropicClient {
-    async fn review_entity(&self, entity: &EntityReview) -> Result<EntityLlmReview, String> {
-        let prompt = build_prompt(entity);
+    /// Attempts per entity for overload, rate limit and transport failures.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
 
+    async fn messages(
+        &self,
+        system: &str,
+        prompt: &str,
+        max_tokens: u32,
+        tools: Vec<AnthropicTool>,
+    ) -> Result<AnthropicResponse, LlmError> {
+        // Forcing the one tool makes the model answer through it
+        let tool_choice = match tools.as_slice() {
+            [tool] => Some(serde_json::json!({ "type": "tool", "name": tool.name })),
+            _ => None,
+        };
         let request = AnthropicRequest {
             model: self.model.clone(),
-            max_tokens: 1024,
-            system: SYSTEM_PROMPT.to_string(),
+            max_tokens,
+            system: system.to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: prompt,
+                content: prompt.to_string(),
             }],
+            tools,
+            tool_choice,
         };
 
-        let resp = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| format!("API request failed: {}", e))?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("API error {}: {}", status, body));
-        }
-
-        let api_resp: AnthropicResponse = resp
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse API response: {}", e))?;
+        send_json(
+            self.client
+                .post(format!("{}/messages", self.api_base))
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", "2023-06-01")
+                .header("content-type", "application/json")
+                .json(&request),
+            self.max_attempts,
+        )
+        .await
+    }
+}
 
-        let text = api_resp
-            .content
-            .first()
-            .and_then(|b| b.text.as_deref())
-            .unwrap_or("");
+#[async_trait]
+impl LlmProvider for AnthropicClient {
+    async fn complete(&self, system: &str, prompt: &str, max_tokens: u32) -> Result<Completion, LlmError> {
+        let api_resp = self.messages(system, prompt, max_tokens, vec![]).await?;
+        Ok(Completion {
+            text: api_resp.text(),
+            usage: api_resp.usage(),
+        })
+    }
 
-        let tokens = api_resp.usage.input_tokens + api_resp.usage.output_tokens;
+    /// Asks for the review as a forced `submit_review` tool call, so there
+    /// is no JSON to scrape. Endpoints that reject tools (e.g. a proxy in
+    /// front of an older API) get the text prompt from then on.
+    async fn review_entity(&self, entity: &EntityReview) -> Result<EntityLlmReview, LlmError> {
+        let prompt = build_prompt(entity);
+        if !self.tools_unsupported.load(Ordering::Relaxed) {
+            match self.messages(TOOL_SYSTEM_PROMPT, &prompt, 1024, vec![review_tool()]).await {
+                Ok(resp) => {
+                    let output = resp
+                        .tool_input(REVIEW_TOOL)
+                        .and_then(|input| serde_json::from_value::<LlmOutput>(input.clone()).ok());
+                    return Ok(match output {
+                        Some(output) => review_from(output, entity, resp.usage()),
+                        None => parse_llm_output(&resp.text(), entity, resp.usage()),
+                    });
+                }
+                Err(LlmError::Api { status: 400, body }) if body.contains("tool") => {
+                    self.tools_unsupported.store(true, Ordering::Relaxed);
+                }
+                Err(e) => return Err(e),
+            }
+        }
 
-        Ok(parse_llm_output(text, entity, tokens))
+        let reply = self.complete(SYSTEM_PROMPT, &prompt, 1024).await?;
+        Ok(parse_llm_output(&reply.text, entity, reply.usage))
     }
 }
 
@@ -224,45 +926,56 @@ pub struct OpenAIClient {
     api_key: Option<String>,
     api_base: String,
     model: String,
+    max_attempts: u32,
 }
 
 impl OpenAIClient {
-    pub fn new(model: &str, api_base: Option<&str>, api_key: Option<&str>) -> Result<Self, String> {
+    /// A custom `api_base` (a local or self-hosted server) may go without a
+    /// key; api.openai.com needs one.
+    pub fn new(model: &str, api_base: Option<&str>, api_key: Option<&str>) -> Result<Self, LlmError> {
         let api_key = api_key
             .map(|k| k.to_string())
             .or_else(|| std::env::var("OPENAI_API_KEY").ok().filter(|k| !k.is_empty()));
+        if api_key.is_none() && api_base.is_none() {
+            return Err(LlmError::MissingApiKey("OPENAI_API_KEY"));
+        }
 
         let api_base = api_base
             .map(|s| s.trim_end_matches('/').to_string())
-            .unwrap_or_else(|| "https://api.openai.com/v1".to_string());
+            .unwrap_or_else(|| OPENAI_API_BASE.to_string());
 
         Ok(Self {
             client: reqwest::Client::new(),
             api_key,
             api_base,
             model: model.to_string(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
         })
     }
+
+    /// Attempts per entity for overload, rate limit and transport failures.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
 }
 
 #[async_trait]
 impl LlmProvider for OpenAIClient {
-    async fn review_entity(&self, entity: &EntityReview) -> Result<EntityLlmReview, String> {
-        let prompt = build_prompt(entity);
-
+    async fn complete(&self, system: &str, prompt: &str, max_tokens: u32) -> Result<Completion, LlmError> {
         let request = OpenAIRequest {
             model: self.model.clone(),
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: SYSTEM_PROMPT.to_string(),
+                    content: system.to_string(),
                 },
                 Message {
                     role: "user".to_string(),
-                    content: prompt,
+                    content: prompt.to_string(),
                 },
             ],
-            max_tokens: 1024,
+            max_tokens,
         };
 
         let url = format!("{}/chat/completions", self.api_base);
@@ -276,35 +989,145 @@ impl LlmProvider for OpenAIClient {
             req = req.header("authorization", format!("Bearer {}", key));
         }
 
-        let resp = req
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| format!("API request failed: {}", e))?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("API error {}: {}", status, body));
-        }
-
-        let api_resp: OpenAIResponse = resp
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse API response: {}", e))?;
+        let api_resp: OpenAIResponse = send_json(req.json(&request), self.max_attempts).await?;
 
         let text = api_resp
             .choices
             .first()
-            .and_then(|c| c.message.content.as_deref())
-            .unwrap_or("");
+            .and_then(|c| c.message.content.clone())
+            .unwrap_or_default();
 
-        let tokens = api_resp
+        let usage = api_resp
             .usage
-            .map(|u| u.prompt_tokens + u.completion_tokens)
-            .unwrap_or(0);
+            .map(|u| TokenUsage {
+                input_tokens: u.prompt_tokens,
+                output_tokens: u.completion_tokens,
+            })
+            .unwrap_or_default();
 
-        Ok(parse_llm_output(text, entity, tokens))
+        Ok(Completion { text, usage })
+    }
+}
+
+// --- OllamaClient ---
+
+#[derive(Debug, Clone, Serialize)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OllamaOptions {
+    num_predict: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OllamaResponse {
+    message: OllamaMessage,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+}
+
+const OLLAMA_API_BASE: &str = "http://localhost:11434";
+/// Local inference is slow, so requests get much longer than usual to finish.
+pub const DEFAULT_OLLAMA_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Appended to the prompt when a local model's first reply had no usable JSON.
+const JSON_ONLY_NUDGE: &str = "Your previous reply could not be parsed. Respond with the JSON object \
+only: no prose, no markdown, nothing before or after it.";
+
+/// A local Ollama server's native chat API. Entity content never leaves the
+/// machine.
+pub struct OllamaClient {
+    client: reqwest::Client,
+    api_base: String,
+    model: String,
+    max_attempts: u32,
+}
+
+impl OllamaClient {
+    pub fn new(model: &str, api_base: Option<&str>, timeout: Duration) -> Result<Self, LlmError> {
+        // Accept the OpenAI-compatible base (`.../v1`) too; the native API sits at the root
+        let api_base = api_base
+            .map(|s| s.trim_end_matches('/').trim_end_matches("/v1").to_string())
+            .unwrap_or_else(|| OLLAMA_API_BASE.to_string());
+
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            api_base,
+            model: model.to_string(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        })
+    }
+
+    /// Attempts per entity for overload and transport failures.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaClient {
+    async fn complete(&self, system: &str, prompt: &str, max_tokens: u32) -> Result<Completion, LlmError> {
+        let request = OllamaRequest {
+            model: self.model.clone(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: system.to_string(),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: prompt.to_string(),
+                },
+            ],
+            stream: false,
+            options: OllamaOptions {
+                num_predict: max_tokens,
+            },
+        };
+
+        let api_resp: OllamaResponse = send_json(
+            self.client
+                .post(format!("{}/api/chat", self.api_base))
+                .json(&request),
+            self.max_attempts,
+        )
+        .await?;
+
+        Ok(Completion {
+            text: api_resp.message.content,
+            usage: TokenUsage {
+                input_tokens: api_resp.prompt_eval_count,
+                output_tokens: api_resp.eval_count,
+            },
+        })
+    }
+
+    /// Local models stray from JSON more often, so a reply without it gets
+    /// one more try with a firmer instruction before falling back.
+    async fn review_entity(&self, entity: &EntityReview) -> Result<EntityLlmReview, LlmError> {
+        let prompt = build_prompt(entity);
+        let reply = self.complete(SYSTEM_PROMPT, &prompt, 1024).await?;
+        if let Some(output) = try_parse_output(&reply.text) {
+            return Ok(review_from(output, entity, reply.usage));
+        }
+
+        let nudged = format!("{}\n\n{}", prompt, JSON_ONLY_NUDGE);
+        let retry = self.complete(SYSTEM_PROMPT, &nudged, 1024).await?;
+        Ok(parse_llm_output(&retry.text, entity, reply.usage + retry.usage))
     }
 }
 
@@ -313,7 +1136,28 @@ impl LlmProvider for OpenAIClient {
 const SYSTEM_PROMPT: &str = "\
 You are a code reviewer. Review the entity for bugs, security issues, and correctness problems. \
 Respond with JSON only, no explanation outside the JSON. Format:
-{\"verdict\": \"approve\" | \"comment\" | \"request_changes\", \"issues\": [{\"severity\": \"error\" | \"warning\" | \"info\", \"description\": \"...\"}], \"summary\": \"one sentence\"}";
+{\"verdict\": \"approve\" | \"comment\" | \"request_changes\", \"issues\": [{\"severity\": \"error\" | \"warning\" | \"info\", \"description\": \"...\", \"start_line\": 12, \"end_line\": 12, \"suggested_replacement\": \"...\"}], \"summary\": \"one sentence\"}
+start_line and end_line are the AFTER line numbers the issue is about. Only when the fix is a small, \
+localized edit (a typo'd identifier, an inverted condition), add suggested_replacement: the exact new \
+text for lines start_line..end_line, without line numbers. Otherwise omit it.";
+
+const BATCH_SYSTEM_PROMPT: &str = "\
+You are a code reviewer. Review each numbered entity for bugs, security issues, and correctness problems. \
+Respond with JSON only, no explanation outside the JSON: an array with one object per entity, in any order. Format:
+[{\"entity\": 1, \"verdict\": \"approve\" | \"comment\" | \"request_changes\", \"issues\": [{\"severity\": \"error\" | \"warning\" | \"info\", \"description\": \"...\", \"start_line\": 12, \"end_line\": 12, \"suggested_replacement\": \"...\"}], \"summary\": \"one sentence\"}]
+entity is the entity's section number. start_line and end_line are the AFTER line numbers the issue is about. \
+Only when the fix is a small, localized edit (a typo'd identifier, an inverted condition), add \
+suggested_replacement: the exact new text for lines start_line..end_line, without line numbers. Otherwise omit it.";
+
+/// Number the entities' prompts into sections of one batch prompt.
+fn build_batch_prompt(entities: &[&EntityReview]) -> String {
+    entities
+        .iter()
+        .enumerate()
+        .map(|(i, entity)| format!("## Entity {}\n\n{}", i + 1, build_prompt(entity)))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
 
 fn build_prompt(entity: &EntityReview) -> String {
     let mut parts = vec![
@@ -344,8 +1188,689 @@ fn build_prompt(entity: &EntityReview) -> String {
     }
 
     if let Some(ref after) = entity.after_content {
-        parts.push(format!("AFTER:\n```\n{}\n```", after));
+        parts.push(format!("AFTER:\n```\n{}\n```", number_lines(after, entity.start_line)));
+    }
+
+    for snippet in &entity.context_snippets {
+        let relation = match snippet.relation {
+            Relation::Dependent => "Dependent",
+            Relation::Dependency => "Dependency",
+        };
+        parts.push(format!(
+            "{} {} ({}:{}-{}{}):\n```\n{}\n```",
+            relation,
+            snippet.entity_name,
+            snippet.file_path,
+            snippet.start_line,
+            snippet.end_line,
+            if snippet.truncated { ", truncated" } else { "" },
+            snippet.content
+        ));
     }
 
     parts.join("\n\n")
 }
+
+/// Prefix each line with its file line number so issues can point at lines.
+fn number_lines(content: &str, first_line: usize) -> String {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>5} | {}", first_line + i, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::parse_patch;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // New-file lines 10..=14, of which 11 and 12 are added
+    const PATCH: &str = "@@ -10,3 +10,5 @@\n fn check(x: u32) -> bool {\n+    let limit = 10;\n+    x > limt\n }\n \n";
+
+    fn issue(start: Option<u64>, end: Option<u64>, replacement: Option<&str>) -> LlmIssue {
+        LlmIssue {
+            severity: "error".into(),
+            description: "`limt` is not defined".into(),
+            suggested_replacement: replacement.map(String::from),
+            start_line: start,
+            end_line: end,
+        }
+    }
+
+    #[test]
+    fn suggestion_on_diff_lines_becomes_a_suggestion_block() {
+        let hunks = parse_patch(PATCH);
+        let c = issue_comment("a.rs", &issue(Some(12), None, Some("    x > limit\n")), &hunks, None).unwrap();
+        assert_eq!(c.line, 12);
+        assert_eq!(c.start_line, None);
+        assert_eq!(
+            c.body,
+            "**error**: `limt` is not defined\n\n```suggestion\n    x > limit\n```"
+        );
+
+        let range = issue(Some(11), Some(12), Some("    let limit = 10;\n    x > limit"));
+        let c = issue_comment("a.rs", &range, &hunks, None).unwrap();
+        assert_eq!((c.start_line, c.line), (Some(11), 12));
+        assert!(c.body.contains("```suggestion\n    let limit = 10;\n    x > limit\n```"));
+    }
+
+    #[test]
+    fn suggestion_outside_the_diff_is_downgraded() {
+        let hunks = parse_patch(PATCH);
+        let c = issue_comment("a.rs", &issue(Some(14), Some(16), Some("}")), &hunks, Some(11)).unwrap();
+        assert!(!c.body.contains("```suggestion"));
+        assert!(c.body.contains("Suggested change:\n```\n}\n```"));
+        assert_eq!(c.line, 14);
+        assert_eq!(c.start_line, None);
+
+        let far = issue(Some(40), None, Some("x"));
+        assert_eq!(issue_comment("a.rs", &far, &hunks, Some(11)).unwrap().line, 11);
+        assert!(issue_comment("a.rs", &far, &hunks, None).is_none());
+    }
+
+    #[test]
+    fn issue_lines_parse_from_llm_output() {
+        let text = r#"{"verdict": "request_changes", "issues": [{"severity": "error", "description": "typo", "start_line": 12, "suggested_replacement": "x > limit"}], "summary": "s"}"#;
+        let output: LlmOutput = serde_json::from_str(text).unwrap();
+        let issue = &output.issues[0];
+        assert_eq!(issue.span(), Some((12, 12)));
+        assert_eq!(issue.suggested_replacement.as_deref(), Some("x > limit"));
+    }
+
+    fn entity() -> EntityReview {
+        named("check", "fn check(x: u32) -> bool {\n    x > limt\n}")
+    }
+
+    fn named(name: &str, after: &str) -> EntityReview {
+        EntityReview {
+            entity_id: format!("src/a.rs::{name}"),
+            entity_name: name.into(),
+            file_path: "src/a.rs".into(),
+            risk_score: 0.8,
+            risk_level: crate::types::RiskLevel::High,
+            start_line: 10,
+            end_line: 12,
+            after_content: Some(after.into()),
+            ..EntityReview::test_default()
+        }
+    }
+
+    const REPLY: &str = "```json\n{\"verdict\": \"request_changes\", \"issues\": [{\"severity\": \"error\", \"description\": \"typo\"}], \"summary\": \"broken\"}\n```";
+
+    #[tokio::test]
+    async fn anthropic_client_reviews_entity() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("x-api-key", "k"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": [{ "type": "text", "text": REPLY }],
+                "usage": { "input_tokens": 100, "output_tokens": 20 },
+            })))
+            .mount(&server)
+            .await;
+
+        let base = format!("{}/v1", server.uri());
+        let client = AnthropicClient::new("m", Some(&base), Some("k")).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::RequestChanges);
+        assert_eq!(review.issues[0].description, "typo");
+        assert_eq!(review.summary, "broken");
+        assert_eq!(review.tokens_used, 120);
+        assert_eq!(review.entity_name, "check");
+    }
+
+    #[tokio::test]
+    async fn openai_client_reviews_entity() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer k"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": REPLY } }],
+                "usage": { "prompt_tokens": 90, "completion_tokens": 10 },
+            })))
+            .mount(&server)
+            .await;
+
+        let base = format!("{}/v1/", server.uri());
+        let client = OpenAIClient::new("m", Some(&base), Some("k")).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::RequestChanges);
+        assert_eq!(review.issues.len(), 1);
+        assert_eq!(review.tokens_used, 100);
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][0]["content"], SYSTEM_PROMPT);
+    }
+
+    #[tokio::test]
+    async fn api_errors_surface_status_and_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("bad key"))
+            .mount(&server)
+            .await;
+
+        let client = OpenAIClient::new("m", Some(&server.uri()), None).unwrap();
+        match client.review_entity(&entity()).await {
+            Err(LlmError::Api { status, body }) => {
+                assert_eq!(status, 401);
+                assert_eq!(body, "bad key");
+            }
+            other => panic!("expected an API error, got {:?}", other.map(|r| r.summary)),
+        }
+    }
+
+    #[tokio::test]
+    async fn overloaded_response_is_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(529).insert_header("retry-after", "0"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": [{ "type": "text", "text": REPLY }],
+                "usage": { "input_tokens": 1, "output_tokens": 1 },
+            })))
+            .mount(&server)
+            .await;
+
+        let client = AnthropicClient::new("m", Some(&server.uri()), Some("k")).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::RequestChanges);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn auth_failure_is_not_retried_and_overload_gives_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "bad"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("invalid x-api-key"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(header("x-api-key", "k"))
+            .respond_with(ResponseTemplate::new(529).insert_header("retry-after", "0"))
+            .mount(&server)
+            .await;
+
+        let client = AnthropicClient::new("m", Some(&server.uri()), Some("bad")).unwrap();
+        let err = client.review_entity(&entity()).await.unwrap_err();
+        assert!(err.is_auth());
+        assert!(!err.is_retryable());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let client = AnthropicClient::new("m", Some(&server.uri()), Some("k"))
+            .unwrap()
+            .with_max_attempts(2);
+        let err = client.review_entity(&entity()).await.unwrap_err();
+        assert!(matches!(err, LlmError::RetriesExhausted { attempts: 2, .. }));
+        assert_eq!(err.status(), Some(529));
+        assert!(err.to_string().starts_with("gave up after 2 attempts: API error 529"));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn malformed_output_falls_back_to_an_info_issue() {
+        let review = parse_llm_output("Looks fine to me!", &entity(), TokenUsage::default());
+        assert_eq!(review.verdict, LlmVerdict::Comment);
+        assert_eq!(review.summary, "Could not parse structured response");
+        assert_eq!(review.issues[0].severity, "info");
+        assert_eq!(review.issues[0].description, "Looks fine to me!");
+
+        let fenced = parse_llm_output("```\n{\"verdict\": \"approve\"}\n```", &entity(), TokenUsage::default());
+        assert_eq!(fenced.verdict, LlmVerdict::Approve);
+        assert!(fenced.issues.is_empty());
+    }
+
+    #[test]
+    fn batch_prompt_numbers_each_entity() {
+        let (a, b) = (named("a", "fn a() {}"), named("b", "fn b() {}"));
+        let prompt = build_batch_prompt(&[&a, &b]);
+        let first = prompt.find("## Entity 1\n\nEntity: a (function)").unwrap();
+        let second = prompt.find("## Entity 2\n\nEntity: b (function)").unwrap();
+        assert!(first < second);
+        assert!(prompt.contains("   10 | fn b() {}"));
+    }
+
+    #[test]
+    fn prompt_quotes_context_snippets() {
+        let mut e = entity();
+        e.context_snippets = vec![crate::types::ContextSnippet {
+            entity_name: "caller".into(),
+            file_path: "src/b.rs".into(),
+            relation: Relation::Dependent,
+            start_line: 3,
+            end_line: 5,
+            content: "fn caller() {\n    check(1);".into(),
+            truncated: true,
+        }];
+        let prompt = build_prompt(&e);
+        assert!(prompt.ends_with("Dependent caller (src/b.rs:3-5, truncated):\n```\nfn caller() {\n    check(1);\n```"));
+    }
+
+    #[test]
+    fn batch_reply_splits_per_entity() {
+        let (a, b, c) = (named("a", "fn a() {}"), named("b", "fn b() {}"), named("c", "fn c() {}"));
+        let text = r#"```json
+[{"entity": 3, "verdict": "request_changes", "issues": [{"severity": "error", "description": "bad"}], "summary": "c"},
+ {"entity": 1, "verdict": "approve", "summary": "a"}]
+```"#;
+        let usage = TokenUsage {
+            input_tokens: 301,
+            output_tokens: 30,
+        };
+        let reviews = split_batch_output(text, &[&a, &b, &c], usage).unwrap();
+        let got: Vec<_> = reviews
+            .iter()
+            .map(|r| (r.entity_name.as_str(), r.verdict, r.summary.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("a", LlmVerdict::Approve, "a"),
+                ("b", LlmVerdict::Comment, "No response for this entity"),
+                ("c", LlmVerdict::RequestChanges, "c"),
+            ]
+        );
+        // Same-sized prompts share evenly; the remainder lands on the last
+        let input: Vec<_> = reviews.iter().map(|r| r.usage.input_tokens).collect();
+        assert_eq!(input, [100, 100, 101]);
+        let tokens: Vec<_> = reviews.iter().map(|r| r.tokens_used).collect();
+        assert_eq!(tokens, [110, 110, 111]);
+
+        assert!(matches!(
+            split_batch_output("not json", &[&a], usage),
+            Err(LlmError::Parse(_))
+        ));
+    }
+
+    /// Answers batch prompts with an approval per section, or with garbage
+    /// when the prompt mentions `garbled`, and single prompts with a comment.
+    struct Scripted {
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl LlmProvider for Scripted {
+        async fn complete(&self, system: &str, prompt: &str, _max_tokens: u32) -> Result<Completion, LlmError> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            let text = if system != BATCH_SYSTEM_PROMPT {
+                r#"{"verdict": "comment", "summary": "single"}"#.to_string()
+            } else if prompt.contains("garbled") {
+                "I reviewed them all!".to_string()
+            } else {
+                let sections = prompt.matches("## Entity ").count();
+                let items: Vec<_> = (1..=sections)
+                    .map(|i| serde_json::json!({ "entity": i, "verdict": "approve", "summary": "batched" }))
+                    .collect();
+                serde_json::to_string(&items).unwrap()
+            };
+            Ok(Completion {
+                text,
+                usage: TokenUsage::default(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn batch_review_falls_back_for_large_and_unparseable() {
+        let big = "x".repeat(BATCH_MAX_ENTITY_CHARS + 1);
+        let entities = [
+            named("a", "fn a() {}"),
+            named("big", &big),
+            named("b", "fn b() {}"),
+            named("c", "fn c() {}"),
+            named("garbled", "fn garbled() {}"),
+        ];
+        let refs: Vec<_> = entities.iter().collect();
+        let provider = Scripted { prompts: Default::default() };
+
+        let reviews = provider.review_entities_batch(&refs, 2).await.reviews;
+        let got: Vec<_> = reviews
+            .iter()
+            .map(|r| {
+                let r = r.as_ref().unwrap();
+                (r.entity_name.as_str(), r.summary.as_str())
+            })
+            .collect();
+        assert_eq!(
+            got,
+            [
+                ("a", "batched"),
+                ("big", "single"),
+                ("b", "batched"),
+                ("c", "single"),
+                ("garbled", "single"),
+            ]
+        );
+        // [a, b] batched, [c, garbled] batched then retried alone, big alone
+        assert_eq!(provider.prompts.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn pricing_matches_most_specific_prefix() {
+        let mini = ModelPricing::for_model("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(mini.input_per_mtok, 0.15);
+        assert_eq!(ModelPricing::for_model("gpt-4o").unwrap().input_per_mtok, 2.5);
+        assert_eq!(ModelPricing::for_model("claude-sonnet-4-5-20250929").unwrap().output_per_mtok, 15.0);
+        assert_eq!(ModelPricing::for_model("llama3"), None);
+
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+        };
+        assert!((mini.cost(usage) - 0.21).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_counts_four_chars_a_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+
+        let e = entity();
+        let usage = estimate_usage(&e);
+        assert_eq!(
+            usage.input_tokens,
+            estimate_tokens(SYSTEM_PROMPT) + estimate_tokens(&build_prompt(&e))
+        );
+        assert_eq!(usage.output_tokens, ESTIMATED_OUTPUT_TOKENS);
+    }
+
+    #[test]
+    fn tracker_accumulates_and_stops_at_the_cap() {
+        let pricing = price(1.0, 10.0);
+        let call = TokenUsage {
+            input_tokens: 100_000,
+            output_tokens: 10_000,
+        };
+        // 0.10 + 0.10 = 0.20 per call
+        let mut tracker = CostTracker::new(Some(pricing), Some(0.5));
+        let mut calls = 0;
+        while tracker.allows(call) {
+            tracker.record(call);
+            calls += 1;
+        }
+        assert_eq!(calls, 2);
+        let summary = tracker.summary();
+        assert_eq!((summary.input_tokens, summary.output_tokens), (200_000, 20_000));
+        assert!((summary.estimated_usd.unwrap() - 0.4).abs() < 1e-9);
+
+        let mut unpriced = CostTracker::new(None, Some(0.01));
+        unpriced.record(call);
+        assert!(unpriced.allows(call));
+        assert_eq!(unpriced.summary().estimated_usd, None);
+        assert_eq!(unpriced.summary().input_tokens, 100_000);
+    }
+
+    #[test]
+    fn json_is_rescued_from_fences_and_prose() {
+        let json = r#"{"verdict": "approve"}"#;
+        assert_eq!(extract_json(json), json);
+        assert_eq!(extract_json(&format!("```json\n{json}\n```")), json);
+        assert_eq!(extract_json(&format!("Here you go:\n```\n{json}\n```\nHope it helps")), json);
+        assert_eq!(extract_json(&format!("Sure! {json} Let me know.")), json);
+        assert_eq!(extract_json("[{\"entity\": 1}] done"), "[{\"entity\": 1}]");
+        assert_eq!(extract_json("no json here"), "no json here");
+    }
+
+    fn ollama_reply(content: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "model": "qwen2.5-coder:14b",
+            "message": { "role": "assistant", "content": content },
+            "done": true,
+            "prompt_eval_count": 50,
+            "eval_count": 5,
+        }))
+    }
+
+    #[tokio::test]
+    async fn ollama_reply_wrapped_in_prose_is_rescued() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(wiremock::matchers::body_partial_json(serde_json::json!({
+                "model": "qwen2.5-coder:14b",
+                "stream": false,
+            })))
+            .respond_with(ollama_reply(
+                "Sure, here is my review:\n{\"verdict\": \"request_changes\", \"summary\": \"typo\"}\nThanks!",
+            ))
+            .mount(&server)
+            .await;
+
+        // The OpenAI-style base still reaches the native API
+        let base = format!("{}/v1", server.uri());
+        let client = OllamaClient::new("qwen2.5-coder:14b", Some(&base), DEFAULT_OLLAMA_TIMEOUT).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::RequestChanges);
+        assert_eq!(review.summary, "typo");
+        assert_eq!(review.tokens_used, 55);
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ollama_retries_once_with_a_json_nudge() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(wiremock::matchers::body_string_contains("could not be parsed"))
+            .respond_with(ollama_reply(r#"{"verdict": "approve", "summary": "fine"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ollama_reply("The function looks fine to me."))
+            .mount(&server)
+            .await;
+
+        let client = OllamaClient::new("m", Some(&server.uri()), DEFAULT_OLLAMA_TIMEOUT).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::Approve);
+        assert_eq!(review.usage.input_tokens, 100, "both calls are counted");
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn anthropic_review_decodes_tool_use() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(wiremock::matchers::body_partial_json(serde_json::json!({
+                "tool_choice": { "type": "tool", "name": "submit_review" },
+                "tools": [{ "name": "submit_review" }],
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "content": [{
+                    "type": "tool_use",
+                    "id": "toolu_01",
+                    "name": "submit_review",
+                    "input": {
+                        "verdict": "request_changes",
+                        "issues": [{
+                            "severity": "error",
+                            "description": "`limt` is not defined",
+                            "start_line": 11,
+                            "suggested_replacement": "    x > limit",
+                        }],
+                        "summary": "Typo breaks the build",
+                    },
+                }],
+                "stop_reason": "tool_use",
+                "usage": { "input_tokens": 400, "output_tokens": 60 },
+            })))
+            .mount(&server)
+            .await;
+
+        let client = AnthropicClient::new("m", Some(&server.uri()), Some("k")).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::RequestChanges);
+        assert_eq!(review.summary, "Typo breaks the build");
+        assert_eq!(review.issues[0].span(), Some((11, 11)));
+        assert_eq!(review.tokens_used, 460);
+    }
+
+    #[tokio::test]
+    async fn anthropic_falls_back_to_text_without_tool_support() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(wiremock::matchers::body_string_contains("submit_review"))
+            .respond_with(ResponseTemplate::new(400).set_body_string(
+                r#"{"type":"error","error":{"type":"invalid_request_error","message":"tools: Extra inputs are not permitted"}}"#,
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": [{ "type": "text", "text": REPLY }],
+                "usage": { "input_tokens": 1, "output_tokens": 1 },
+            })))
+            .mount(&server)
+            .await;
+
+        let client = AnthropicClient::new("m", Some(&server.uri()), Some("k")).unwrap();
+        let review = client.review_entity(&entity()).await.unwrap();
+        assert_eq!(review.verdict, LlmVerdict::RequestChanges);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+        // Later reviews go straight to the text prompt
+        client.review_entity(&entity()).await.unwrap();
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+    }
+
+    /// Sleeps for the milliseconds in the entity's name (`slow-30`) and fails
+    /// entities named `fail-*`, tracking how many calls overlap.
+    #[derive(Default)]
+    struct Sleepy {
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmProvider for Sleepy {
+        async fn complete(&self, _system: &str, prompt: &str, _max_tokens: u32) -> Result<Completion, LlmError> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            let name = prompt.lines().next().unwrap().trim_start_matches("Entity: ");
+            let ms: u64 = name.split(['-', ' ']).nth(1).unwrap().parse().unwrap();
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            if name.starts_with("fail") {
+                return Err(LlmError::Api {
+                    status: 500,
+                    body: "boom".into(),
+                });
+            }
+            Ok(Completion {
+                text: r#"{"verdict": "approve", "summary": "ok"}"#.into(),
+                usage: TokenUsage {
+                    input_tokens: 10,
+                    output_tokens: 1,
+                },
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_reviews_keep_order_and_cap() {
+        let entities: Vec<_> = [50, 10, 40, 5, 30, 20, 15]
+            .iter()
+            .enumerate()
+            .map(|(i, ms)| {
+                let kind = if i == 2 { "fail" } else { "slow" };
+                named(&format!("{kind}-{ms}"), "fn f() {}")
+            })
+            .collect();
+        let refs: Vec<_> = entities.iter().collect();
+        let provider = Sleepy::default();
+        let mut tracker = CostTracker::new(None, None);
+        let mut completed = Vec::new();
+
+        let results = review_concurrently(&provider, &refs, 1, 3, &mut tracker, &mut |i, _| completed.push(i)).await;
+
+        assert_eq!(provider.max_in_flight.load(Ordering::SeqCst), 3);
+        assert_ne!(completed, (0..7).collect::<Vec<_>>(), "completion order differs");
+        let names: Vec<_> = results
+            .iter()
+            .map(|r| match r {
+                Some(Ok(review)) => review.entity_name.clone(),
+                Some(Err(_)) => "error".to_string(),
+                None => "skipped".to_string(),
+            })
+            .collect();
+        assert_eq!(
+            names,
+            ["slow-50", "slow-10", "error", "slow-5", "slow-30", "slow-20", "slow-15"]
+        );
+        assert_eq!(tracker.summary().input_tokens, 60, "six successes counted");
+    }
+
+    #[tokio::test]
+    async fn concurrent_reviews_stop_at_the_cap() {
+        let entities: Vec<_> = (0..6).map(|_| named("slow-1", "fn f() {}")).collect();
+        let refs: Vec<_> = entities.iter().collect();
+        let estimate = estimate_usage(refs[0]);
+        let pricing = price(1.0, 1.0);
+        // Room for three estimated calls, counting the ones in flight
+        let cap = pricing.cost(estimate) * 3.5;
+        let mut tracker = CostTracker::new(Some(pricing), Some(cap));
+
+        let results = review_concurrently(&Sleepy::default(), &refs, 1, 4, &mut tracker, &mut |_, _| {}).await;
+        let reviewed = results.iter().filter(|r| r.is_some()).count();
+        assert!((3..6).contains(&reviewed), "reviewed {reviewed}");
+        assert!(results[reviewed..].iter().all(Option::is_none));
+    }
+
+    /// Answers every batch prompt with garbage and every single prompt with
+    /// an approval, each billed at exactly its estimate.
+    struct Unparseable;
+
+    #[async_trait]
+    impl LlmProvider for Unparseable {
+        async fn complete(&self, system: &str, prompt: &str, _max_tokens: u32) -> Result<Completion, LlmError> {
+            let (text, entities) = if system == BATCH_SYSTEM_PROMPT {
+                ("I reviewed them all!", prompt.matches("## Entity ").count() as u64)
+            } else {
+                (r#"{"verdict": "approve", "summary": "ok"}"#, 1)
+            };
+            Ok(Completion {
+                text: text.to_string(),
+                usage: TokenUsage {
+                    input_tokens: estimate_tokens(system) + estimate_tokens(prompt),
+                    output_tokens: ESTIMATED_OUTPUT_TOKENS * entities,
+                },
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn unparseable_batches_count_against_the_cap() {
+        let entities: Vec<_> = ["a", "b", "c", "d"].iter().map(|n| named(n, "fn f() {}")).collect();
+        let refs: Vec<_> = entities.iter().collect();
+        let pricing = price(1.0, 1.0);
+        let batch = estimate_batch_usage(&refs[..2]);
+        let singles = estimate_usage(refs[0]) + estimate_usage(refs[1]);
+        // Room for one chunk's batch and fallbacks, but not two
+        let cap = pricing.cost(batch + singles) * 1.5;
+        let mut tracker = CostTracker::new(Some(pricing), Some(cap));
+
+        let results = review_concurrently(&Unparseable, &refs, 2, 1, &mut tracker, &mut |_, _| {}).await;
+        assert!(results[..2].iter().all(|r| matches!(r, Some(Ok(_)))));
+        assert!(results[2..].iter().all(Option::is_none), "second chunk would pass the cap");
+
+        let spent = tracker.summary();
+        assert_eq!(spent.input_tokens, batch.input_tokens + singles.input_tokens);
+        assert!(spent.estimated_usd.unwrap() <= cap);
+    }
+}
@@ -0,0 +1,269 @@
+//! Pair entities the diff reports as deleted in one place and added in
+//! another, so a moved or renamed entity is reviewed once.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use sem_core::model::change::{ChangeType, SemanticChange};
+
+use crate::classify::code_tokens;
+
+/// Token similarity at or above which a deleted and an added entity are
+/// taken to be one entity that moved.
+pub const MOVE_SIMILARITY: f64 = 0.8;
+
+/// Stands in for an entity's own name, so a rename alone leaves its tokens
+/// the same.
+const NAME_TOKEN: &str = "\u{0}name";
+
+/// One side of a candidate pair: the entity's code tokens with its name
+/// masked, counted, and hashed for exact matches.
+struct Shape<'a> {
+    counts: HashMap<&'a str, usize>,
+    len: usize,
+    hash: u64,
+}
+
+impl<'a> Shape<'a> {
+    fn new(change: &'a SemanticChange, content: &'a str) -> Self {
+        let tokens: Vec<&str> = code_tokens(content, &change.file_path)
+            .into_iter()
+            .map(|t| if t == change.entity_name { NAME_TOKEN } else { t })
+            .collect();
+        let mut hasher = DefaultHasher::new();
+        tokens.hash(&mut hasher);
+        let mut counts = HashMap::new();
+        for t in &tokens {
+            *counts.entry(*t).or_insert(0) += 1;
+        }
+        Self {
+            counts,
+            len: tokens.len(),
+            hash: hasher.finish(),
+        }
+    }
+
+    /// Dice coefficient of the two token multisets; 1.0 for the same tokens
+    /// in the same order.
+    fn similarity(&self, other: &Shape) -> f64 {
+        if self.hash == other.hash && self.len == other.len {
+            return 1.0;
+        }
+        let total = self.len + other.len;
+        // Even full overlap of the shorter one can't reach the threshold
+        if total == 0 || 2.0 * self.len.min(other.len) as f64 / (total as f64) < MOVE_SIMILARITY {
+            return 0.0;
+        }
+        let shared: usize = self
+            .counts
+            .iter()
+            .map(|(t, n)| (*n).min(other.counts.get(t).copied().unwrap_or(0)))
+            .sum();
+        2.0 * shared as f64 / total as f64
+    }
+}
+
+/// Replace each deleted entity that reappears as an added one, in another
+/// file or under another name, with a single change: `Moved` if only the
+/// file changed, `Renamed` if the name did. The merged change keeps the added
+/// entity's identity, takes the deleted one's content as its before side,
+/// and records the old path in `old_file_path`, so classification sees only
+/// the edits made along the way.
+///
+/// Entities of the same type pair up when their tokens, ignoring comments,
+/// whitespace and the entity's own name, are at least [`MOVE_SIMILARITY`]
+/// alike. Exact matches go first; then the most similar pair is taken
+/// greedily, preferring a pair that kept its name, then earlier changes. The
+/// other changes are returned as they came.
+pub fn match_moves(changes: Vec<SemanticChange>) -> Vec<SemanticChange> {
+    let sides = |kind: ChangeType| -> Vec<(usize, Shape)> {
+        changes
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.change_type == kind)
+            .filter_map(|(i, c)| {
+                let content = match kind {
+                    ChangeType::Deleted => c.before_content.as_deref(),
+                    _ => c.after_content.as_deref(),
+                }?;
+                Some((i, Shape::new(c, content)))
+            })
+            .collect()
+    };
+    let deleted = sides(ChangeType::Deleted);
+    let added = sides(ChangeType::Added);
+
+    let mut candidates: Vec<(f64, bool, usize, usize)> = Vec::new();
+    for (d, old) in &deleted {
+        for (a, new) in &added {
+            if changes[*d].entity_type != changes[*a].entity_type {
+                continue;
+            }
+            let score = old.similarity(new);
+            if score >= MOVE_SIMILARITY {
+                let renamed = changes[*d].entity_name != changes[*a].entity_name;
+                candidates.push((score, renamed, *d, *a));
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return changes;
+    }
+    candidates.sort_by(|x, y| {
+        y.0.total_cmp(&x.0)
+            .then(x.1.cmp(&y.1))
+            .then(x.2.cmp(&y.2))
+            .then(x.3.cmp(&y.3))
+    });
+
+    let mut taken: HashSet<usize> = HashSet::new();
+    let mut moved_from: HashMap<usize, usize> = HashMap::new();
+    for (_, _, d, a) in candidates {
+        if !taken.contains(&d) && !taken.contains(&a) {
+            taken.extend([d, a]);
+            moved_from.insert(a, d);
+        }
+    }
+
+    let mut slots: Vec<Option<SemanticChange>> = changes.into_iter().map(Some).collect();
+    let mut merged = Vec::with_capacity(slots.len() - moved_from.len());
+    for i in 0..slots.len() {
+        if let Some(&d) = moved_from.get(&i) {
+            let old = slots[d].take().expect("each deleted entity pairs once");
+            let new = slots[i].take().expect("each added entity pairs once");
+            merged.push(merge(old, new));
+        } else if !taken.contains(&i) {
+            merged.push(slots[i].take().expect("unpaired changes are kept once"));
+        }
+    }
+    merged
+}
+
+/// One change for a deleted entity and the added entity it became.
+fn merge(old: SemanticChange, new: SemanticChange) -> SemanticChange {
+    let moved_file = old.file_path != new.file_path;
+    let change_type = if old.entity_name != new.entity_name {
+        ChangeType::Renamed
+    } else if moved_file {
+        ChangeType::Moved
+    } else {
+        ChangeType::Modified
+    };
+    let before = old.before_content.as_deref().unwrap_or("");
+    let after = new.after_content.as_deref().unwrap_or("");
+    // Same code means only comments or whitespace changed on the way
+    let unchanged = code_tokens(before, &old.file_path) == code_tokens(after, &new.file_path);
+    SemanticChange {
+        change_type,
+        old_file_path: moved_file.then_some(old.file_path),
+        before_content: old.before_content,
+        structural_change: if unchanged { Some(false) } else { None },
+        ..new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(kind: ChangeType, path: &str, name: &str, content: &str) -> SemanticChange {
+        let (before_content, after_content) = match kind {
+            ChangeType::Deleted => (Some(content.into()), None),
+            _ => (None, Some(content.into())),
+        };
+        SemanticChange {
+            id: format!("{}::{}", path, name),
+            entity_id: format!("{}::function::{}", path, name),
+            change_type: kind,
+            entity_type: "function".into(),
+            entity_name: name.into(),
+            file_path: path.into(),
+            old_file_path: None,
+            before_content,
+            after_content,
+            commit_sha: None,
+            author: None,
+            timestamp: None,
+            structural_change: None,
+        }
+    }
+
+    const TRIM: &str = "export function trim(s: string): string {\n  // drop the padding\n  return s.replace(/^\\s+|\\s+$/g, \"\");\n}\n";
+
+    fn summary(changes: &[SemanticChange]) -> Vec<(ChangeType, &str, Option<&str>)> {
+        changes
+            .iter()
+            .map(|c| (c.change_type, c.entity_id.as_str(), c.old_file_path.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn pure_move_becomes_one_change() {
+        let changes = vec![
+            change(ChangeType::Deleted, "utils.ts", "trim", TRIM),
+            change(ChangeType::Added, "helpers/strings.ts", "trim", &TRIM.replace("drop the padding", "strip")),
+        ];
+        let merged = match_moves(changes);
+        assert_eq!(
+            summary(&merged),
+            vec![(ChangeType::Moved, "helpers/strings.ts::function::trim", Some("utils.ts"))]
+        );
+        assert_eq!(merged[0].before_content.as_deref(), Some(TRIM));
+        // Only a comment changed on the way
+        assert_eq!(merged[0].structural_change, Some(false));
+    }
+
+    #[test]
+    fn move_with_edits_keeps_the_delta() {
+        let edited = TRIM.replace("\"\")", "\" \").trim()");
+        let changes = vec![
+            change(ChangeType::Added, "helpers/strings.ts", "trimAll", &edited.replace("trim(s", "trimAll(s")),
+            change(ChangeType::Deleted, "utils.ts", "trim", TRIM),
+            change(ChangeType::Added, "helpers/strings.ts", "pad", "export function pad(n: number) {\n  return n;\n}\n"),
+        ];
+        let merged = match_moves(changes);
+        assert_eq!(
+            summary(&merged),
+            vec![
+                (ChangeType::Renamed, "helpers/strings.ts::function::trimAll", Some("utils.ts")),
+                (ChangeType::Added, "helpers/strings.ts::function::pad", None),
+            ]
+        );
+        assert_eq!(merged[0].structural_change, None);
+        assert!(merged[0].after_content.as_deref().unwrap().contains(".trim()"));
+    }
+
+    #[test]
+    fn similar_functions_pair_with_their_own_copies() {
+        let add = "fn add(a: i64, b: i64) -> i64 {\n    let total = a + b;\n    log(total);\n    total\n}\n";
+        let sub = "fn sub(a: i64, b: i64) -> i64 {\n    let total = a - b;\n    log(total);\n    total\n}\n";
+        let changes = vec![
+            change(ChangeType::Deleted, "src/math.rs", "add", add),
+            change(ChangeType::Deleted, "src/math.rs", "sub", sub),
+            // Listed in the other order, and sub picked up an edit
+            change(ChangeType::Added, "src/ops.rs", "sub", &sub.replace("log(total)", "trace(total)")),
+            change(ChangeType::Added, "src/ops.rs", "add", add),
+        ];
+        let merged = match_moves(changes);
+        assert_eq!(
+            summary(&merged),
+            vec![
+                (ChangeType::Moved, "src/ops.rs::function::sub", Some("src/math.rs")),
+                (ChangeType::Moved, "src/ops.rs::function::add", Some("src/math.rs")),
+            ]
+        );
+        assert_eq!(merged[0].before_content.as_deref(), Some(sub));
+        assert_eq!(merged[1].before_content.as_deref(), Some(add));
+    }
+
+    #[test]
+    fn unrelated_changes_are_left_alone() {
+        let changes = vec![
+            change(ChangeType::Deleted, "a.rs", "old", "fn old() {\n    cleanup_everything();\n}\n"),
+            change(ChangeType::Added, "b.rs", "new", "fn new(x: u8) -> bool {\n    x > 3 && ready()\n}\n"),
+        ];
+        let merged = match_moves(changes.clone());
+        assert_eq!(summary(&merged), summary(&changes));
+    }
+}
@@ -1,55 +1,327 @@
-const NOISE_EXACT: &[&str] = &[
-    "pnpm-lock.yaml",
-    "package-lock.json",
-    "yarn.lock",
-    "npm-shrinkwrap.json",
-    "bun.lockb",
-    "Cargo.lock",
-    "Gemfile.lock",
-    "poetry.lock",
-    "Pipfile.lock",
-    "uv.lock",
-    "go.sum",
-    "composer.lock",
-    "packages.lock.json",
-    "pubspec.lock",
-    "Package.resolved",
-    "mix.lock",
-    ".DS_Store",
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+/// Noise by file name, with the kind [`noise_kind`] reports for it.
+const NOISE_EXACT: &[(&str, &str)] = &[
+    ("pnpm-lock.yaml", "lockfile"),
+    ("package-lock.json", "lockfile"),
+    ("yarn.lock", "lockfile"),
+    ("npm-shrinkwrap.json", "lockfile"),
+    ("bun.lockb", "lockfile"),
+    ("Cargo.lock", "lockfile"),
+    ("Gemfile.lock", "lockfile"),
+    ("poetry.lock", "lockfile"),
+    ("Pipfile.lock", "lockfile"),
+    ("uv.lock", "lockfile"),
+    ("go.sum", "lockfile"),
+    ("composer.lock", "lockfile"),
+    ("packages.lock.json", "lockfile"),
+    ("pubspec.lock", "lockfile"),
+    ("Package.resolved", "lockfile"),
+    ("mix.lock", "lockfile"),
+    (".DS_Store", "os_metadata"),
+];
+
+const NOISE_EXTENSIONS: &[(&str, &str)] = &[
+    (".min.js", "generated"),
+    (".min.css", "generated"),
+    (".map", "generated"),
+    (".chunk.js", "generated"),
+    (".bundle.js", "generated"),
 ];
 
-const NOISE_EXTENSIONS: &[&str] = &[
-    ".min.js",
-    ".min.css",
-    ".map",
-    ".chunk.js",
-    ".bundle.js",
+const NOISE_PREFIXES: &[(&str, &str)] = &[
+    ("dist/", "build_output"),
+    (".next/", "build_output"),
+    ("build/", "build_output"),
+    ("__generated__/", "generated"),
+    (".turbo/", "build_output"),
 ];
 
-const NOISE_PREFIXES: &[&str] = &[
-    "dist/",
-    ".next/",
-    "build/",
-    "__generated__/",
-    ".turbo/",
+const TEST_DIRS: &[&str] = &["tests/", "test/", "__tests__/", "spec/"];
+
+const TEST_SUFFIXES: &[&str] = &[
+    "_test.go",
+    "_test.rs",
+    "_test.py",
+    ".test.ts",
+    ".test.tsx",
+    ".test.js",
+    ".test.jsx",
+    ".spec.ts",
+    ".spec.tsx",
+    ".spec.js",
+    ".spec.jsx",
 ];
 
+/// Repo-level ignore file, read from the repository root.
+pub const IGNORE_FILE: &str = ".inspectignore";
+
+/// Git attributes file; `linguist-generated` entries mark generated files.
+pub const ATTRIBUTES_FILE: &str = ".gitattributes";
+
+/// Lines this far into a file are scanned for generated-file markers.
+const GENERATED_HEAD_LINES: usize = 10;
+
+/// Header markers left by code generators. Matched case-sensitively
+/// anywhere in the first [`GENERATED_HEAD_LINES`] lines.
+const GENERATED_MARKERS: &[&str] = &[
+    "Code generated by",
+    "DO NOT EDIT",
+    "@generated",
+    "Generated by the protocol buffer compiler",
+    "generated by protoc",
+    "Generated by: https://openapi-generator.tech",
+    "swagger-codegen",
+    "graphql-codegen",
+    "THIS FILE IS AUTO-GENERATED",
+    "This file is automatically generated",
+];
+
+/// Whether the start of a file carries a code generator's marker, e.g.
+/// `// Code generated by protoc-gen-go. DO NOT EDIT.`
+pub fn has_generated_marker(content: &str) -> bool {
+    content
+        .lines()
+        .take(GENERATED_HEAD_LINES)
+        .any(|line| GENERATED_MARKERS.iter().any(|m| line.contains(m)))
+}
+
 pub fn is_noise_file(path: &str) -> bool {
-    let filename = path.rsplit('/').next().unwrap_or(path);
+    noise_kind(path).is_some()
+}
 
-    if NOISE_EXACT.iter().any(|n| filename == *n) {
-        return true;
+/// Noise rules: the built-in lists plus gitignore-style patterns from
+/// [`IGNORE_FILE`] or the command line. User patterns are checked after the
+/// built-ins and the last matching one wins, so `!Cargo.lock` brings a
+/// lockfile back into review. `NoiseFilter::default()` applies only the
+/// built-ins.
+#[derive(Debug, Clone, Default)]
+pub struct NoiseFilter {
+    rules: Vec<IgnoreRule>,
+    /// `linguist-generated` attribute patterns: set (true) or unset (false)
+    generated: Vec<(GlobSet, bool)>,
+}
+
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    globs: GlobSet,
+    negated: bool,
+}
+
+impl NoiseFilter {
+    /// Built-ins plus the repo's [`IGNORE_FILE`] and the generated-file
+    /// entries of its root [`ATTRIBUTES_FILE`]. Missing files add nothing.
+    pub fn load(repo_root: &Path) -> Result<Self, String> {
+        let read = |name: &str| {
+            let path = repo_root.join(name);
+            match std::fs::read_to_string(&path) {
+                Ok(raw) => Ok(Some(raw)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("failed to read {}: {}", path.display(), e)),
+            }
+        };
+        let mut filter = match read(IGNORE_FILE)? {
+            Some(raw) => Self::parse(&raw).map_err(|e| format!("{}: {}", IGNORE_FILE, e))?,
+            None => Self::default(),
+        };
+        if let Some(raw) = read(ATTRIBUTES_FILE)? {
+            filter.add_attributes(&raw);
+        }
+        Ok(filter)
     }
 
-    if NOISE_EXTENSIONS.iter().any(|ext| path.ends_with(ext)) {
-        return true;
+    /// Pick up `linguist-generated` entries from `.gitattributes` content.
+    /// Other attributes, macros, and patterns globset can't compile are
+    /// skipped, since the file belongs to git rather than inspect.
+    pub fn add_attributes(&mut self, raw: &str) {
+        for line in raw.lines() {
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next().filter(|p| !p.starts_with('#') && !p.starts_with('[')) else {
+                continue;
+            };
+            let state = fields.fold(None, |state, attr| match attr {
+                "linguist-generated" | "linguist-generated=true" => Some(true),
+                "-linguist-generated" | "!linguist-generated" | "linguist-generated=false" => Some(false),
+                _ => state,
+            });
+            if let (Some(state), Ok(globs)) = (state, path_globs(pattern)) {
+                self.generated.push((globs, state));
+            }
+        }
+    }
+
+    /// Built-ins plus the patterns in `raw`, one per line. Blank lines and
+    /// `#` comments are skipped.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        filter.extend(raw.lines())?;
+        Ok(filter)
+    }
+
+    /// Append patterns, which take precedence over earlier ones.
+    pub fn extend<'a>(&mut self, patterns: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        for line in patterns {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.rules.push(IgnoreRule::new(line)?);
+        }
+        Ok(())
     }
 
-    if NOISE_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+    pub fn is_noise_file(&self, path: &str) -> bool {
+        self.noise_kind(path).is_some()
+    }
+
+    /// Whether a file is generated code: marked `linguist-generated` in the
+    /// repo's attributes, or carrying a generator marker in `content_head`.
+    /// An attribute set either way decides on its own, so
+    /// `-linguist-generated` keeps a file with a stray marker in review.
+    pub fn is_generated(&self, path: &str, content_head: &str) -> bool {
+        let path = path.replace('\\', "/");
+        match self.generated.iter().rev().find(|(globs, _)| globs.is_match(&path)) {
+            Some((_, state)) => *state,
+            None => has_generated_marker(content_head),
+        }
+    }
+
+    /// Like [`noise_kind`], with "ignored" for files matched by a user
+    /// pattern.
+    pub fn noise_kind(&self, path: &str) -> Option<&'static str> {
+        let path = path.replace('\\', "/");
+        match self.rules.iter().rev().find(|r| r.globs.is_match(&path)) {
+            Some(rule) if rule.negated => None,
+            Some(_) => Some("ignored"),
+            None => noise_kind(&path),
+        }
+    }
+}
+
+impl IgnoreRule {
+    /// Translate one gitignore-style line, `!` negating it.
+    fn new(line: &str) -> Result<Self, String> {
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let globs = path_globs(pattern).map_err(|e| format!("{} in '{}'", e, line))?;
+        Ok(Self { globs, negated })
+    }
+}
+
+/// Compile a gitignore-style path pattern. A pattern without a `/` (other
+/// than a trailing one) matches at any depth; a trailing `/` matches only
+/// directories, i.e. everything under them.
+fn path_globs(pattern: &str) -> Result<GlobSet, String> {
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let base = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{}", pattern),
+    };
+    if base.is_empty() || base == "**/" {
+        return Err("empty pattern".to_string());
+    }
+
+    let mut globs = vec![format!("{}/**", base)];
+    if !dir_only {
+        globs.push(base);
+    }
+    let mut set = GlobSetBuilder::new();
+    for glob in &globs {
+        let glob = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("invalid pattern: {}", e))?;
+        set.add(glob);
+    }
+    set.build().map_err(|e| format!("invalid pattern: {}", e))
+}
+
+/// Why a file counts as noise: "lockfile", "generated", "build_output", or
+/// "os_metadata". None for files that should be reviewed.
+pub fn noise_kind(path: &str) -> Option<&'static str> {
+    let filename = path.rsplit('/').next().unwrap_or(path);
+
+    NOISE_EXACT
+        .iter()
+        .find(|(name, _)| filename == *name)
+        .or_else(|| NOISE_EXTENSIONS.iter().find(|(ext, _)| path.ends_with(ext)))
+        .or_else(|| NOISE_PREFIXES.iter().find(|(prefix, _)| path.starts_with(prefix)))
+        .map(|(_, kind)| *kind)
+}
+
+/// Whether a path looks like a test file, by directory or naming convention.
+pub fn is_test_path(path: &str) -> bool {
+    let filename = path.rsplit('/').next().unwrap_or(path);
+
+    if TEST_DIRS
+        .iter()
+        .any(|dir| path.starts_with(dir) || path.contains(&format!("/{}", dir)))
+    {
         return true;
     }
 
-    false
+    TEST_SUFFIXES.iter().any(|suffix| filename.ends_with(suffix))
+        || (filename.starts_with("test_") && filename.ends_with(".py"))
+}
+
+/// Attributes that mark a Rust item as test-only.
+const RUST_TEST_ATTRS: &[&str] = &["#[test]", "#[cfg(test)]", "#[tokio::test"];
+
+/// Whether an entity's source is a Rust test item: its leading attributes
+/// include `#[test]`, `#[cfg(test)]`, or `#[tokio::test]`.
+pub fn is_test_item(content: &str) -> bool {
+    content
+        .lines()
+        .map(str::trim)
+        .take_while(|l| l.is_empty() || l.starts_with("#[") || l.starts_with("//"))
+        .any(|l| RUST_TEST_ATTRS.iter().any(|attr| l.starts_with(attr)))
+}
+
+/// Line ranges (1-based, inclusive) of `#[cfg(test)]` modules in Rust
+/// source. Braces are counted naively, so a brace inside a string or comment
+/// in the module can shift the end.
+pub fn rust_test_modules(content: &str) -> Vec<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim() != "#[cfg(test)]" {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        // Skip any further attributes between the cfg and the module
+        let mut j = i + 1;
+        while j < lines.len() && lines[j].trim().starts_with("#[") {
+            j += 1;
+        }
+        let header = lines.get(j).map(|l| l.trim()).unwrap_or("");
+        if !(header.starts_with("mod ") || header.starts_with("pub mod ")) || !header.contains('{') {
+            i = j;
+            continue;
+        }
+
+        let mut depth = 0i64;
+        let mut end = lines.len() - 1;
+        for (k, line) in lines.iter().enumerate().skip(j) {
+            depth += line.matches('{').count() as i64 - line.matches('}').count() as i64;
+            if depth <= 0 {
+                end = k;
+                break;
+            }
+        }
+        ranges.push((start + 1, end + 1));
+        i = end + 1;
+    }
+    ranges
 }
 
 #[cfg(test)]
@@ -81,4 +353,123 @@ mod tests {
         assert!(!is_noise_file("src/main.rs"));
         assert!(!is_noise_file("lib/utils.ts"));
     }
+
+    #[test]
+    fn noise_kinds() {
+        assert_eq!(noise_kind("yarn.lock"), Some("lockfile"));
+        assert_eq!(noise_kind("app.min.js"), Some("generated"));
+        assert_eq!(noise_kind("dist/bundle.js"), Some("build_output"));
+        assert_eq!(noise_kind("src/main.rs"), None);
+
+        let expected = |name: &str| match name {
+            ".DS_Store" => "os_metadata",
+            "__generated__/" => "generated",
+            n if n.ends_with('/') => "build_output",
+            n if n.starts_with('.') => "generated",
+            _ => "lockfile",
+        };
+        for (name, _) in NOISE_EXACT {
+            assert_eq!(noise_kind(&format!("web/{}", name)), Some(expected(name)), "{}", name);
+        }
+        for (ext, _) in NOISE_EXTENSIONS {
+            assert_eq!(noise_kind(&format!("static/app{}", ext)), Some(expected(ext)), "{}", ext);
+        }
+        for (prefix, _) in NOISE_PREFIXES {
+            assert_eq!(noise_kind(&format!("{}out.ts", prefix)), Some(expected(prefix)), "{}", prefix);
+        }
+    }
+
+    #[test]
+    fn ignore_file_patterns() {
+        let filter = NoiseFilter::parse("# generated code\n\n*.pb.go\nsdk/generated/\nthird_party/\n").unwrap();
+        assert!(filter.is_noise_file("api/v1/service.pb.go"));
+        assert!(filter.is_noise_file("sdk/generated/client.ts"));
+        assert!(filter.is_noise_file("sdk/generated/models/user.ts"));
+        assert!(!filter.is_noise_file("sdk/generated.ts"));
+        assert!(!filter.is_noise_file("pkg/sdk/generated/client.ts"));
+        assert!(filter.is_noise_file("vendor/third_party/lib.c"));
+        assert!(!filter.is_noise_file("src/third_party.rs"));
+        assert_eq!(filter.noise_kind("third_party/lib.c"), Some("ignored"));
+        assert!(!filter.is_noise_file("src/main.rs"));
+    }
+
+    #[test]
+    fn negated_patterns_reinclude() {
+        let filter = NoiseFilter::parse("*.gen.ts\n!important.gen.ts\n").unwrap();
+        assert!(filter.is_noise_file("src/types.gen.ts"));
+        assert!(!filter.is_noise_file("src/important.gen.ts"));
+
+        // Later lines win
+        let filter = NoiseFilter::parse("!important.gen.ts\n*.gen.ts\n").unwrap();
+        assert!(filter.is_noise_file("src/important.gen.ts"));
+    }
+
+    #[test]
+    fn user_rules_override_builtins() {
+        assert!(NoiseFilter::default().is_noise_file("Cargo.lock"));
+        assert!(NoiseFilter::default().is_noise_file("dist/bundle.js"));
+
+        let mut filter = NoiseFilter::parse("!Cargo.lock\n").unwrap();
+        filter.extend(["!/dist/keep.js", "src/legacy/"]).unwrap();
+        assert!(!filter.is_noise_file("Cargo.lock"));
+        assert!(!filter.is_noise_file("dist/keep.js"));
+        assert!(filter.is_noise_file("dist/bundle.js"));
+        assert_eq!(filter.noise_kind("yarn.lock"), Some("lockfile"));
+        assert!(filter.is_noise_file("src/legacy/old.rs"));
+
+        assert!(NoiseFilter::parse("foo/[\n").is_err());
+    }
+
+    #[test]
+    fn generated_markers() {
+        let protoc = "// Code generated by protoc-gen-go. DO NOT EDIT.\n// versions:\n// \tprotoc v4.25.1\n\npackage api\n\nfunc (x *User) GetName() string {\n\treturn x.Name\n}\n";
+        assert!(has_generated_marker(protoc));
+        assert!(NoiseFilter::default().is_generated("api/user.pb.go", protoc));
+        assert!(has_generated_marker("/**\n * @generated\n */\nexport const x = 1;\n"));
+        assert!(!has_generated_marker("package api\n\nfunc Get() {}\n"));
+
+        // Markers past the head don't count
+        let late = format!("{}// DO NOT EDIT\n", "fn a() {}\n".repeat(GENERATED_HEAD_LINES));
+        assert!(!has_generated_marker(&late));
+    }
+
+    #[test]
+    fn generated_attributes() {
+        let mut filter = NoiseFilter::default();
+        filter.add_attributes(
+            "# generated clients\n*.rs text eol=lf\nsdk/** linguist-generated=true\nsdk/hand_written.ts -linguist-generated\nschema.ts linguist-generated\n",
+        );
+        assert!(filter.is_generated("sdk/client.ts", "export function get() {}"));
+        assert!(filter.is_generated("web/schema.ts", ""));
+        assert!(!filter.is_generated("sdk/hand_written.ts", "export function get() {}"));
+        assert!(!filter.is_generated("src/lib.rs", "fn a() {}"));
+        // An explicit false overrides a marker; without an attribute it counts
+        assert!(!filter.is_generated("sdk/hand_written.ts", "// @generated\n"));
+        assert!(filter.is_generated("src/lib.rs", "// @generated\n"));
+        // Attributes don't make a file noise
+        assert!(!filter.is_noise_file("sdk/client.ts"));
+    }
+
+    #[test]
+    fn test_files() {
+        assert!(is_test_path("tests/integration.rs"));
+        assert!(is_test_path("crates/core/tests/graph.rs"));
+        assert!(is_test_path("src/__tests__/app.tsx"));
+        assert!(is_test_path("pkg/server_test.go"));
+        assert!(is_test_path("src/button.spec.ts"));
+        assert!(is_test_path("app/test_models.py"));
+        assert!(!is_test_path("src/testing.rs"));
+        assert!(!is_test_path("src/attest/mod.rs"));
+    }
+
+    #[test]
+    fn rust_test_code() {
+        assert!(is_test_item("#[test]\nfn parses() {}"));
+        assert!(is_test_item("/// doc\n#[tokio::test(flavor = \"multi_thread\")]\nasync fn runs() {}"));
+        assert!(!is_test_item("fn parse() {\n    #[cfg(test)]\n    let x = 1;\n}"));
+
+        let src = "fn a() {}\n\n#[cfg(test)]\nmod tests {\n    fn helper() {\n    }\n}\n\nfn b() {}\n";
+        assert_eq!(rust_test_modules(src), vec![(3, 7)]);
+        assert!(rust_test_modules("#[cfg(test)]\nuse std::fmt;\n").is_empty());
+    }
 }
@@ -19,13 +19,41 @@ pub struct DiffHunk {
     pub lines: Vec<DiffLine>,
 }
 
+/// A parsed patch. Binary patches have no hunks.
+#[derive(Debug, Serialize)]
+pub struct PatchInfo {
+    pub hunks: Vec<DiffHunk>,
+    pub is_binary: bool,
+}
+
+/// Hunks of a unified diff patch; empty for a binary patch.
 pub fn parse_patch(patch: &str) -> Vec<DiffHunk> {
+    parse_patch_info(patch).hunks
+}
+
+/// Parse a unified diff patch, recognizing binary patches (`Binary files
+/// ... differ` or `GIT binary patch`).
+pub fn parse_patch_info(patch: &str) -> PatchInfo {
+    let is_binary = patch.lines().any(|l| {
+        l == "GIT binary patch" || (l.starts_with("Binary files ") && l.ends_with(" differ"))
+    });
+    if is_binary {
+        return PatchInfo {
+            hunks: Vec::new(),
+            is_binary,
+        };
+    }
+
     let mut hunks = Vec::new();
     let mut current_hunk: Option<DiffHunk> = None;
     let mut old_line: u64 = 0;
     let mut new_line: u64 = 0;
 
     for raw_line in patch.lines() {
+        // "\ No newline at end of file" annotates the previous line
+        if raw_line.starts_with('\\') {
+            continue;
+        }
         if raw_line.starts_with("@@") {
             if let Some(h) = current_hunk.take() {
                 hunks.push(h);
@@ -86,7 +114,10 @@ pub fn parse_patch(patch: &str) -> Vec<DiffHunk> {
         hunks.push(h);
     }
 
-    hunks
+    PatchInfo {
+        hunks,
+        is_binary: false,
+    }
 }
 
 pub fn commentable_lines(hunks: &[DiffHunk]) -> Vec<u64> {
@@ -98,6 +129,208 @@ pub fn commentable_lines(hunks: &[DiffHunk]) -> Vec<u64> {
         .collect()
 }
 
+/// Commentable new-file lines (added or context) inside an entity's
+/// post-change span `start_line..=end_line`, in order. Empty when the entity
+/// lies outside every hunk.
+pub fn entity_commentable_lines(hunks: &[DiffHunk], start_line: usize, end_line: usize) -> Vec<u64> {
+    let span = start_line as u64..=end_line as u64;
+    hunks
+        .iter()
+        .flat_map(|h| &h.lines)
+        .filter(|l| l.commentable)
+        .filter_map(|l| l.new_line)
+        .filter(|n| span.contains(n))
+        .collect()
+}
+
+/// The line to anchor a comment about an entity on: its first added line,
+/// else its first context line. None when the entity lies outside the diff.
+pub fn entity_anchor_line(hunks: &[DiffHunk], start_line: usize, end_line: usize) -> Option<u64> {
+    let span = start_line as u64..=end_line as u64;
+    let in_span = |kind: &str| {
+        hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .filter(|l| l.commentable && l.kind == kind)
+            .filter_map(|l| l.new_line)
+            .find(|n| span.contains(n))
+    };
+    in_span("add").or_else(|| in_span("context"))
+}
+
+/// Find the commentable line closest to `line`, at most `max_distance` lines away.
+/// Returns `line` itself when it is commentable; on ties prefers the later line.
+pub fn nearest_commentable_line(commentable: &[u64], line: u64, max_distance: u64) -> Option<u64> {
+    commentable
+        .iter()
+        .copied()
+        .filter(|l| l.abs_diff(line) <= max_distance)
+        .min_by_key(|l| (l.abs_diff(line), *l < line))
+}
+
+/// A line-level edit operation produced by `diff_lines`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Above this many cells the LCS table is skipped and every line between the
+/// common prefix and suffix counts as changed.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Line diff between two texts (LCS over the lines that differ after
+/// trimming the common prefix and suffix).
+pub fn diff_lines<'a>(before: &'a str, after: &'a str) -> Vec<(LineOp, &'a str)> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    diff_ops(&a, &b).into_iter().map(|(op, l)| (op, *l)).collect()
+}
+
+/// LCS diff of two sequences of lines, in order. The common prefix and
+/// suffix are trimmed first; past [`MAX_LCS_CELLS`] the rest is reported as
+/// all deleted, then all inserted.
+pub(crate) fn diff_ops<'a, T: PartialEq>(a: &'a [T], b: &'a [T]) -> Vec<(LineOp, &'a T)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+
+    let mut ops: Vec<(LineOp, &T)> = a[..prefix].iter().map(|l| (LineOp::Equal, l)).collect();
+    let (mut i, mut j) = (0, 0);
+    if n * m <= MAX_LCS_CELLS {
+        // lcs[i][j] = length of the LCS of a_mid[i..] and b_mid[j..]
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if a_mid[i] == b_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        while i < n && j < m {
+            if a_mid[i] == b_mid[j] {
+                ops.push((LineOp::Equal, &a_mid[i]));
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                ops.push((LineOp::Delete, &a_mid[i]));
+                i += 1;
+            } else {
+                ops.push((LineOp::Insert, &b_mid[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(a_mid[i..].iter().map(|l| (LineOp::Delete, l)));
+    ops.extend(b_mid[j..].iter().map(|l| (LineOp::Insert, l)));
+    ops.extend(a[a.len() - suffix..].iter().map(|l| (LineOp::Equal, l)));
+    ops
+}
+
+/// A unified diff plus its line counts.
+#[derive(Debug, Clone, Serialize)]
+pub struct UnifiedDiff {
+    pub text: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl UnifiedDiff {
+    /// The first `max_lines` lines of the diff and how many were left off;
+    /// 0 keeps them all.
+    pub fn head(&self, max_lines: usize) -> (Vec<&str>, usize) {
+        let mut lines: Vec<&str> = self.text.lines().collect();
+        if max_lines == 0 || lines.len() <= max_lines {
+            return (lines, 0);
+        }
+        let omitted = lines.len() - max_lines;
+        lines.truncate(max_lines);
+        (lines, omitted)
+    }
+}
+
+/// Render a unified diff (hunks only, no file headers) between two texts.
+/// Hunk line numbers are relative to the start of the given texts.
+pub fn unified_diff(before: &str, after: &str, context_lines: usize) -> UnifiedDiff {
+    let ops = diff_lines(before, after);
+    let added = ops.iter().filter(|(op, _)| *op == LineOp::Insert).count();
+    let removed = ops.iter().filter(|(op, _)| *op == LineOp::Delete).count();
+
+    // Lines consumed on each side before op k
+    let mut old_before = Vec::with_capacity(ops.len() + 1);
+    let mut new_before = Vec::with_capacity(ops.len() + 1);
+    let (mut old_n, mut new_n) = (0usize, 0usize);
+    for (op, _) in &ops {
+        old_before.push(old_n);
+        new_before.push(new_n);
+        if *op != LineOp::Insert {
+            old_n += 1;
+        }
+        if *op != LineOp::Delete {
+            new_n += 1;
+        }
+    }
+    old_before.push(old_n);
+    new_before.push(new_n);
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (op, _))| *op != LineOp::Equal)
+        .map(|(k, _)| k)
+        .collect();
+
+    let mut text = String::new();
+    let mut c = 0;
+    while c < changes.len() {
+        let mut last = c;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] - 1 <= 2 * context_lines {
+            last += 1;
+        }
+
+        let start = changes[c].saturating_sub(context_lines);
+        let end = (changes[last] + context_lines + 1).min(ops.len());
+
+        let old_count = old_before[end] - old_before[start];
+        let new_count = new_before[end] - new_before[start];
+        let old_start = if old_count == 0 { old_before[start] } else { old_before[start] + 1 };
+        let new_start = if new_count == 0 { new_before[start] } else { new_before[start] + 1 };
+
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_count, new_start, new_count
+        ));
+        for (op, line) in &ops[start..end] {
+            let marker = match op {
+                LineOp::Equal => ' ',
+                LineOp::Delete => '-',
+                LineOp::Insert => '+',
+            };
+            text.push(marker);
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        c = last + 1;
+    }
+
+    UnifiedDiff {
+        text,
+        added,
+        removed,
+    }
+}
+
 fn parse_hunk_header(header: &str) -> (u64, u64, u64, u64) {
     let parts: Vec<&str> = header.split_whitespace().collect();
 
@@ -159,4 +392,150 @@ mod tests {
         let cl = commentable_lines(&hunks);
         assert_eq!(cl, vec![1, 2, 3]);
     }
+
+    #[test]
+    fn test_no_newline_marker_is_skipped() {
+        let patch = "@@ -1,2 +1,3 @@\n a\n-b\n\\ No newline at end of file\n+b\n+c\n\\ No newline at end of file";
+        let hunks = parse_patch(patch);
+        assert_eq!(hunks[0].lines.len(), 4);
+        assert_eq!(commentable_lines(&hunks), vec![1, 2, 3]);
+        assert!(hunks[0].lines.iter().all(|l| !l.content.contains("No newline")));
+
+        // A later hunk keeps its own numbering
+        let patch = "@@ -1,1 +1,1 @@\n-x\n\\ No newline at end of file\n+y\n@@ -9,1 +9,2 @@\n z\n+w";
+        assert_eq!(commentable_lines(&parse_patch(patch)), vec![1, 9, 10]);
+    }
+
+    #[test]
+    fn test_binary_patch() {
+        let info = parse_patch_info("Binary files a/logo.png and b/logo.png differ");
+        assert!(info.is_binary);
+        assert!(info.hunks.is_empty());
+
+        let info = parse_patch_info("GIT binary patch\nliteral 1024\nzcmV<3@@\n\nliteral 0\nHcmV?d00001\n");
+        assert!(info.is_binary);
+        assert!(parse_patch("GIT binary patch\nliteral 12\n@@ -1 +1 @@\n").is_empty());
+
+        assert!(!parse_patch_info("@@ -1 +1 @@\n-a\n+b").is_binary);
+    }
+
+    #[test]
+    fn test_hunk_header_without_counts() {
+        let hunks = parse_patch("@@ -5 +5 @@ fn main()\n-old\n+new");
+        assert_eq!((hunks[0].old_start, hunks[0].old_count), (5, 1));
+        assert_eq!((hunks[0].new_start, hunks[0].new_count), (5, 1));
+        assert_eq!(commentable_lines(&hunks), vec![5]);
+
+        let hunks = parse_patch("@@ -0,0 +1 @@\n+only");
+        assert_eq!((hunks[0].new_start, hunks[0].new_count), (1, 1));
+        assert_eq!(commentable_lines(&hunks), vec![1]);
+    }
+
+    #[test]
+    fn test_nearest_commentable_line() {
+        let cl = vec![10, 11, 12, 20];
+        assert_eq!(nearest_commentable_line(&cl, 11, 3), Some(11));
+        assert_eq!(nearest_commentable_line(&cl, 14, 3), Some(12));
+        assert_eq!(nearest_commentable_line(&cl, 16, 3), None);
+        assert_eq!(nearest_commentable_line(&cl, 16, 4), Some(20));
+        assert_eq!(nearest_commentable_line(&[], 5, 3), None);
+    }
+
+    const TWO_HUNKS: &str = "@@ -1,4 +1,5 @@\n fn a() {\n-    old();\n+    new();\n+    more();\n }\n fn b() {\n@@ -20,4 +21,4 @@\n fn c() {\n     keep();\n-    x();\n+    y();\n }";
+
+    #[test]
+    fn test_entity_commentable_lines() {
+        let hunks = parse_patch(TWO_HUNKS);
+        // a() spans 1..=4: context, two adds, context
+        assert_eq!(entity_commentable_lines(&hunks, 1, 4), vec![1, 2, 3, 4]);
+        // Spanning both hunks skips the lines between them
+        assert_eq!(entity_commentable_lines(&hunks, 4, 22), vec![4, 5, 21, 22]);
+        // Outside every hunk
+        assert!(entity_commentable_lines(&hunks, 8, 18).is_empty());
+        assert!(entity_commentable_lines(&hunks, 30, 40).is_empty());
+    }
+
+    #[test]
+    fn test_entity_anchor_line() {
+        let hunks = parse_patch(TWO_HUNKS);
+        assert_eq!(entity_anchor_line(&hunks, 1, 4), Some(2));
+        assert_eq!(entity_anchor_line(&hunks, 21, 24), Some(23));
+        // Only context lines of the entity are in the diff
+        assert_eq!(entity_anchor_line(&hunks, 5, 7), Some(5));
+        assert_eq!(entity_anchor_line(&hunks, 8, 18), None);
+    }
+
+    #[test]
+    fn test_entity_lines_with_deletion_only_hunk() {
+        let patch = "@@ -10,5 +10,3 @@\n fn gone() {\n-    a();\n-    b();\n }\n fn next() {";
+        let hunks = parse_patch(patch);
+        // Deleted lines have no new-file line; the context around them does
+        assert_eq!(entity_commentable_lines(&hunks, 10, 11), vec![10, 11]);
+        assert_eq!(entity_anchor_line(&hunks, 10, 11), Some(10));
+        assert_eq!(entity_anchor_line(&hunks, 12, 20), Some(12));
+    }
+
+    #[test]
+    fn test_unified_diff_modified() {
+        let before = "a\nb\nc\nd\ne\nf\ng";
+        let after = "a\nb\nc\nD\ne\nf\ng";
+        let diff = unified_diff(before, after, 1);
+        assert_eq!(diff.added, 1);
+        assert_eq!(diff.removed, 1);
+        assert_eq!(diff.text, "@@ -3,3 +3,3 @@\n c\n-d\n+D\n e\n");
+
+        // Round-trips through the patch parser
+        let hunks = parse_patch(&diff.text);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].lines.len(), 4);
+    }
+
+    #[test]
+    fn test_unified_diff_added_entity() {
+        let diff = unified_diff("", "fn a() {\n    1\n}", 3);
+        assert_eq!(diff.added, 3);
+        assert_eq!(diff.removed, 0);
+        assert!(diff.text.starts_with("@@ -0,0 +1,3 @@\n+fn a() {\n"));
+    }
+
+    #[test]
+    fn test_unified_diff_zero_context_splits_hunks() {
+        let before = "a\nb\nc\nd";
+        let after = "A\nb\nc\nD";
+        let diff = unified_diff(before, after, 0);
+        assert_eq!(diff.text, "@@ -1,1 +1,1 @@\n-a\n+A\n@@ -4,1 +4,1 @@\n-d\n+D\n");
+
+        // With enough context both changes share a hunk
+        assert_eq!(parse_patch(&unified_diff(before, after, 1).text).len(), 1);
+    }
+
+    #[test]
+    fn test_unified_diff_head() {
+        let diff = unified_diff("a\nb\nc", "A\nB\nC", 0);
+        assert_eq!(diff.text.lines().count(), 7);
+        assert_eq!(diff.head(3), (vec!["@@ -1,3 +1,3 @@", "-a", "-b"], 4));
+        assert_eq!(diff.head(0).1, 0);
+        assert_eq!(diff.head(7).1, 0);
+    }
+
+    #[test]
+    fn test_unified_diff_identical() {
+        let diff = unified_diff("a\nb", "a\nb", 3);
+        assert!(diff.text.is_empty());
+        assert_eq!((diff.added, diff.removed), (0, 0));
+    }
+
+    #[test]
+    fn test_diff_lines_caps_the_lcs_table() {
+        // 2500 x 2500 lines in the middle is past the cap
+        let middle = |side: &str| (0..2500).map(|i| format!("{} {}\n", side, i)).collect::<String>();
+        let before = format!("head\n{}tail", middle("old"));
+        let after = format!("head\n{}tail", middle("new"));
+        let ops = diff_lines(&before, &after);
+        assert_eq!(ops.len(), 5002);
+        assert_eq!(ops[0], (LineOp::Equal, "head"));
+        assert!(ops[1..2501].iter().all(|(op, _)| *op == LineOp::Delete));
+        assert!(ops[2501..5001].iter().all(|(op, _)| *op == LineOp::Insert));
+        assert_eq!(ops[5001], (LineOp::Equal, "tail"));
+    }
 }
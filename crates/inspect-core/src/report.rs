@@ -0,0 +1,571 @@
+use std::fmt::Write;
+
+use crate::filter::SortKey;
+use crate::patch::{unified_diff, UnifiedDiff};
+use crate::risk::suggest_verdict;
+use crate::riskmap::{risk_map, BucketRisk, RiskMapBy};
+use crate::types::{EntityReview, ReviewResult, RiskLevel};
+
+/// How a report sections the entity list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    /// One list, in `--sort` order
+    #[default]
+    Risk,
+    /// A section per file, entities by line number
+    File,
+    /// A section per logical change group
+    Group,
+}
+
+/// Parse a `GroupBy` name (case-insensitive).
+pub fn parse_group_by(s: &str) -> Result<GroupBy, String> {
+    match s.to_lowercase().as_str() {
+        "risk" => Ok(GroupBy::Risk),
+        "file" => Ok(GroupBy::File),
+        "group" => Ok(GroupBy::Group),
+        other => Err(format!("group_by must be 'risk', 'file', or 'group', got '{}'", other)),
+    }
+}
+
+/// How the entity list was ordered and cut for display.
+#[derive(Debug, Clone, Copy)]
+pub struct Listing {
+    pub sort: SortKey,
+    pub group_by: GroupBy,
+    /// Entities left off the end of the list
+    pub omitted: usize,
+    /// How to see the omitted entities, e.g. the CLI's `--top 0`
+    pub more_hint: &'static str,
+}
+
+impl Default for Listing {
+    fn default() -> Self {
+        Self {
+            sort: SortKey::default(),
+            group_by: GroupBy::default(),
+            omitted: 0,
+            more_hint: "use --top 0 for all",
+        }
+    }
+}
+
+impl Listing {
+    /// Closing line for a list cut short.
+    pub fn more(&self) -> Option<String> {
+        (self.omitted > 0).then(|| format!("… and {} more entities ({})", self.omitted, self.more_hint))
+    }
+}
+
+/// Context lines around each change in an inline diff.
+const INLINE_DIFF_CONTEXT: usize = 2;
+
+/// Which entities get their before/after diff printed under them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InlineDiffs {
+    /// Lowest risk level to show diffs for; `None` shows none
+    pub min_risk: Option<RiskLevel>,
+    /// Lines per diff before it's cut short; 0 shows all
+    pub max_lines: usize,
+}
+
+impl InlineDiffs {
+    /// The compact diff of `review`'s content, if it's shown at all.
+    /// Added and deleted entities diff against an empty side.
+    pub fn diff(&self, review: &EntityReview) -> Option<UnifiedDiff> {
+        let min = self.min_risk?;
+        if review.risk_level < min || (review.before_content.is_none() && review.after_content.is_none()) {
+            return None;
+        }
+        let diff = unified_diff(
+            review.before_content.as_deref().unwrap_or(""),
+            review.after_content.as_deref().unwrap_or(""),
+            INLINE_DIFF_CONTEXT,
+        );
+        (!diff.text.is_empty()).then_some(diff)
+    }
+
+    /// The lines of `diff` to print, ending with a "(truncated)" marker if
+    /// it was cut short.
+    pub fn lines(&self, diff: &UnifiedDiff) -> Vec<String> {
+        let (head, omitted) = diff.head(self.max_lines);
+        let mut lines: Vec<String> = head.into_iter().map(String::from).collect();
+        if omitted > 0 {
+            lines.push(format!("… {} more lines (truncated)", omitted));
+        }
+        lines
+    }
+}
+
+/// Entities under one heading. `bucket` is `None` for the single section of
+/// `--group-by risk`.
+pub struct Section<'a> {
+    pub bucket: Option<BucketRisk>,
+    /// Highest risk level among `reviews`
+    pub max_risk: RiskLevel,
+    pub reviews: Vec<&'a EntityReview>,
+}
+
+fn max_risk(reviews: &[&EntityReview]) -> RiskLevel {
+    reviews.iter().map(|r| r.risk_level).max().unwrap_or(RiskLevel::Low)
+}
+
+/// Split the entity list into sections, riskiest first. Every entity lands
+/// in exactly one section.
+pub fn sections(result: &ReviewResult, group_by: GroupBy) -> Vec<Section<'_>> {
+    let by = match group_by {
+        GroupBy::Risk => {
+            let reviews: Vec<&EntityReview> = result.entity_reviews.iter().collect();
+            return vec![Section {
+                bucket: None,
+                max_risk: max_risk(&reviews),
+                reviews,
+            }];
+        }
+        GroupBy::File => RiskMapBy::File,
+        GroupBy::Group => RiskMapBy::Group,
+    };
+    let mut sections: Vec<Section> = risk_map(result, by)
+        .into_iter()
+        .map(|bucket| {
+            let mut reviews: Vec<&EntityReview> = result
+                .entity_reviews
+                .iter()
+                .filter(|r| match bucket.group_id {
+                    Some(id) => r.group_id == id,
+                    None => r.file_path.replace('\\', "/") == bucket.key,
+                })
+                .collect();
+            if group_by == GroupBy::File {
+                reviews.sort_by_key(|r| r.start_line);
+            }
+            Section {
+                bucket: Some(bucket),
+                max_risk: max_risk(&reviews),
+                reviews,
+            }
+        })
+        .collect();
+    // risk_map orders by max score; a risk rule can set a level the score
+    // doesn't reflect, so put the level first and keep score order within it
+    sections.sort_by_key(|s| std::cmp::Reverse(s.max_risk));
+    sections
+}
+
+/// The review as a markdown document: verdict and counts, groups, the entity
+/// table (sectioned per `listing`), details for high-risk entities, inline
+/// diffs per `diffs`, and timing. `show_context` adds dependent and
+/// dependency counts to the details.
+pub fn markdown(result: &ReviewResult, show_context: bool, listing: Listing, diffs: InlineDiffs) -> String {
+    if result.entity_reviews.is_empty() {
+        return "No entity-level changes found.\n".to_string();
+    }
+    let mut out = String::new();
+
+    let stats = &result.stats;
+    let verdict = suggest_verdict(result);
+
+    let _ = writeln!(out, "# inspect: {} entities changed", stats.total_entities);
+    out.push('\n');
+    let _ = writeln!(
+        out,
+        "**Verdict:** {} | **Critical:** {} | **High:** {} | **Medium:** {} | **Low:** {}",
+        verdict, stats.by_risk.critical, stats.by_risk.high, stats.by_risk.medium, stats.by_risk.low,
+    );
+    if stats.test_entities > 0 {
+        out.push('\n');
+        let _ = writeln!(
+            out,
+            "{} of {} entities are in test code.",
+            stats.test_entities, stats.total_entities
+        );
+    }
+    if stats.generated_entities > 0 {
+        out.push('\n');
+        let _ = writeln!(
+            out,
+            "{} entities in generated files were left out.",
+            stats.generated_entities
+        );
+    }
+
+    // Groups; --group-by group gives each its own section instead
+    if result.groups.len() > 1 && listing.group_by != GroupBy::Group {
+        out.push('\n');
+        let _ = writeln!(out, "## Groups ({} logical change groups)", result.groups.len());
+        out.push('\n');
+        for group in &result.groups {
+            let _ = writeln!(
+                out,
+                "- **[{}]** {} ({} entities; {})",
+                group.id,
+                group.label,
+                group.entity_ids.len(),
+                group.hint()
+            );
+        }
+    }
+
+    for section in sections(result, listing.group_by) {
+        out.push('\n');
+        match section.bucket {
+            None => {
+                let _ = writeln!(out, "## Entities (by {})", listing.sort);
+            }
+            Some(ref bucket) => {
+                let heading = match bucket.group_id {
+                    Some(id) => format!("[{}] {}", id, bucket.key),
+                    None => format!("`{}`", bucket.key),
+                };
+                let _ = writeln!(
+                    out,
+                    "## {} ({}, {} entities)",
+                    heading,
+                    risk_label(section.max_risk),
+                    section.reviews.len()
+                );
+            }
+        }
+        out.push('\n');
+        out.push_str("| Risk | Type | Entity | File | Score | Classification | Blast | Change |\n");
+        out.push_str("|------|------|--------|------|-------|----------------|-------|--------|\n");
+        for review in section.reviews {
+            out.push_str(&table_row(review));
+        }
+    }
+    if let Some(more) = listing.more() {
+        out.push('\n');
+        let _ = writeln!(out, "{}", more);
+    }
+
+    // Detail section for high-risk entities
+    let high_risk: Vec<_> = result
+        .entity_reviews
+        .iter()
+        .filter(|r| r.risk_level >= RiskLevel::High)
+        .collect();
+
+    if !high_risk.is_empty() {
+        out.push('\n');
+        out.push_str("## High-risk details\n");
+
+        for review in high_risk {
+            out.push('\n');
+            let _ = writeln!(
+                out,
+                "### `{}` ({}) in `{}`",
+                review.entity_name, review.entity_type, review.file_path
+            );
+            out.push('\n');
+            let _ = writeln!(
+                out,
+                "- **Risk:** {:?} ({:.2}) | **Blast radius:** {} | **Public API:** {}",
+                review.risk_level, review.risk_score, review.blast_radius, review.is_public_api,
+            );
+
+            if let Some(ref rule) = review.risk_rule {
+                let _ = writeln!(out, "- {} by rule `{}`", rule.effect, rule.pattern);
+            }
+
+            if review.structural_change == Some(false) {
+                out.push_str("- Cosmetic only (no structural change)\n");
+            }
+
+            if review.is_test {
+                out.push_str("- Test code\n");
+            }
+
+            if show_context {
+                if review.dependent_count > 0 {
+                    let _ = writeln!(out, "- {} dependents may be affected", review.dependent_count);
+                }
+                if review.dependency_count > 0 {
+                    let _ = writeln!(out, "- Depends on {} other entities", review.dependency_count);
+                }
+            }
+
+            if let Some(diff) = diffs.diff(review) {
+                out.push('\n');
+                out.push_str(&diff_block(&diff, diffs));
+            }
+        }
+    }
+
+    // Diffs of the entities below high, with --show-diff-all
+    let other_diffs: Vec<_> = result
+        .entity_reviews
+        .iter()
+        .filter(|r| r.risk_level < RiskLevel::High)
+        .filter_map(|r| diffs.diff(r).map(|diff| (r, diff)))
+        .collect();
+    if !other_diffs.is_empty() {
+        out.push('\n');
+        out.push_str("## Other diffs\n");
+        for (review, diff) in other_diffs {
+            out.push('\n');
+            let _ = writeln!(
+                out,
+                "### `{}` ({}) in `{}`",
+                review.entity_name, review.entity_type, review.file_path
+            );
+            out.push('\n');
+            out.push_str(&diff_block(&diff, diffs));
+        }
+    }
+
+    // Timing
+    let t = &result.timing;
+    if t.total_ms > 0 {
+        out.push('\n');
+        out.push_str("---\n");
+        let graph = if t.graph_cache_hit {
+            format!("cached ({}ms)", t.graph_lookup_ms)
+        } else {
+            format!("{}ms", t.graph_build_ms)
+        };
+        let _ = writeln!(
+            out,
+            "*{}ms total ({} files, {} entities) | diff: {}ms, graph: {}, scoring: {}ms*",
+            t.total_ms, t.file_count, t.graph_entity_count, t.diff_ms, graph, t.scoring_ms,
+        );
+    }
+    out
+}
+
+fn risk_label(level: RiskLevel) -> &'static str {
+    match level {
+        RiskLevel::Critical => "CRITICAL",
+        RiskLevel::High => "HIGH",
+        RiskLevel::Medium => "MEDIUM",
+        RiskLevel::Low => "LOW",
+    }
+}
+
+fn table_row(review: &EntityReview) -> String {
+    let change = format!("{:?}", review.change_type);
+
+    format!(
+        "| {} | {} | `{}` | `{}` | {:.2} | {} | {} | {} |\n",
+        risk_label(review.risk_level),
+        review.entity_type,
+        review.entity_name,
+        review.file_path,
+        review.risk_score,
+        review.classification,
+        review.blast_radius,
+        change.to_lowercase(),
+    )
+}
+
+/// A fenced diff block, cut short per `diffs`.
+fn diff_block(diff: &UnifiedDiff, diffs: InlineDiffs) -> String {
+    let mut block = String::from("```diff\n");
+    for line in diffs.lines(diff) {
+        block.push_str(&line);
+        block.push('\n');
+    }
+    block.push_str("```\n");
+    block
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::compute_stats;
+    use crate::patch::unified_diff;
+    use crate::types::{ChangeClassification, ChangeGroup, Timing};
+
+    fn review(name: &str, file: &str, line: usize, score: f64, level: RiskLevel, group_id: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file, name),
+            entity_name: name.into(),
+            file_path: file.into(),
+            risk_score: score,
+            risk_level: level,
+            group_id,
+            start_line: line,
+            end_line: line + 5,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn group(id: usize, label: &str) -> ChangeGroup {
+        ChangeGroup {
+            id,
+            label: label.into(),
+            entity_ids: vec![],
+            dominant_classification: ChangeClassification::Functional,
+            max_risk: RiskLevel::Low,
+            max_score: 0.0,
+            has_public_api: false,
+        }
+    }
+
+    /// In risk order, as `--sort risk` leaves them. `b.rs` has the top
+    /// score, but a rule raised `a.rs::late` to critical.
+    fn result() -> ReviewResult {
+        let entity_reviews = vec![
+            review("hot", "b.rs", 5, 0.9, RiskLevel::High, 0),
+            review("late", "a.rs", 30, 0.5, RiskLevel::Critical, 1),
+            review("mild", "c.rs", 1, 0.4, RiskLevel::Medium, 2),
+            review("early", "a.rs", 10, 0.1, RiskLevel::Low, 0),
+        ];
+        ReviewResult {
+            stats: compute_stats(&entity_reviews),
+            entity_reviews,
+            groups: vec![group(0, "hot path"), group(1, "late fix"), group(2, "mild")],
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    fn names<'a>(section: &Section<'a>) -> Vec<&'a str> {
+        section.reviews.iter().map(|r| r.entity_name.as_str()).collect()
+    }
+
+    #[test]
+    fn inline_diffs_follow_the_risk_threshold() {
+        let mut high = review("hot", "b.rs", 5, 0.9, RiskLevel::High, 0);
+        high.before_content = Some("fn hot() {\n    1\n}\n".into());
+        high.after_content = Some("fn hot() {\n    2\n}\n".into());
+        let mut low = high.clone();
+        low.risk_level = RiskLevel::Low;
+        let mut added = high.clone();
+        added.before_content = None;
+
+        let shown = InlineDiffs {
+            min_risk: Some(RiskLevel::High),
+            max_lines: 0,
+        };
+        let diff = shown.diff(&high).unwrap();
+        assert_eq!((diff.added, diff.removed), (1, 1));
+        assert!(shown.diff(&low).is_none());
+        assert_eq!(shown.diff(&added).unwrap().added, 3, "added entities diff against nothing");
+        assert!(InlineDiffs::default().diff(&high).is_none());
+        assert!(shown.diff(&review("bare", "b.rs", 1, 0.9, RiskLevel::High, 0)).is_none());
+    }
+
+    #[test]
+    fn every_entity_lands_in_exactly_one_section() {
+        let result = result();
+        for group_by in [GroupBy::Risk, GroupBy::File, GroupBy::Group] {
+            let mut seen: Vec<&str> = sections(&result, group_by).iter().flat_map(names).collect();
+            seen.sort();
+            assert_eq!(seen, ["early", "hot", "late", "mild"], "{:?}", group_by);
+        }
+    }
+
+    #[test]
+    fn risk_mode_keeps_the_sorted_list() {
+        let result = result();
+        let sections = sections(&result, GroupBy::Risk);
+        assert_eq!(sections.len(), 1);
+        assert!(sections[0].bucket.is_none());
+        assert_eq!(names(&sections[0]), ["hot", "late", "mild", "early"]);
+    }
+
+    #[test]
+    fn file_sections_order_by_max_risk_then_line() {
+        let result = result();
+        let sections = sections(&result, GroupBy::File);
+        let keys: Vec<&str> = sections.iter().map(|s| s.bucket.as_ref().unwrap().key.as_str()).collect();
+        assert_eq!(keys, ["a.rs", "b.rs", "c.rs"]);
+        assert_eq!(sections[0].max_risk, RiskLevel::Critical);
+        assert_eq!(names(&sections[0]), ["early", "late"]);
+    }
+
+    #[test]
+    fn group_sections_order_by_max_risk() {
+        let result = result();
+        let sections = sections(&result, GroupBy::Group);
+        let ids: Vec<Option<usize>> = sections.iter().map(|s| s.bucket.as_ref().unwrap().group_id).collect();
+        assert_eq!(ids, [Some(1), Some(0), Some(2)]);
+        assert_eq!(sections[1].bucket.as_ref().unwrap().key, "hot path");
+        assert_eq!(sections[1].max_risk, RiskLevel::High);
+        assert_eq!(names(&sections[1]), ["hot", "early"]);
+    }
+
+    #[test]
+    fn diff_blocks_are_fenced_and_truncated() {
+        let diff = unified_diff("a\nb\nc\n", "a\nB\nC\n", 2);
+        let whole = InlineDiffs {
+            min_risk: Some(RiskLevel::High),
+            max_lines: 0,
+        };
+        assert_eq!(
+            diff_block(&diff, whole),
+            "```diff\n@@ -1,3 +1,3 @@\n a\n-b\n-c\n+B\n+C\n```\n"
+        );
+
+        let cut = InlineDiffs { max_lines: 3, ..whole };
+        assert_eq!(
+            diff_block(&diff, cut),
+            "```diff\n@@ -1,3 +1,3 @@\n a\n-b\n… 3 more lines (truncated)\n```\n"
+        );
+    }
+
+    #[test]
+    fn markdown_report_snapshot() {
+        let mut result = result();
+        result.entity_reviews[0].before_content = Some("fn hot() {\n    1\n}\n".into());
+        result.entity_reviews[0].after_content = Some("fn hot() {\n    2\n}\n".into());
+        result.entity_reviews[0].dependent_count = 3;
+        result.entity_reviews.truncate(3);
+        result.stats = compute_stats(&result.entity_reviews);
+        let listing = Listing {
+            omitted: 1,
+            more_hint: "raise the limit to see them",
+            ..Default::default()
+        };
+        let diffs = InlineDiffs {
+            min_risk: Some(RiskLevel::High),
+            max_lines: 0,
+        };
+        let expected = "\
+# inspect: 3 entities changed
+
+**Verdict:** requires_careful_review | **Critical:** 1 | **High:** 1 | **Medium:** 1 | **Low:** 0
+
+## Groups (3 logical change groups)
+
+- **[0]** hot path (0 entities; functional, low 0.00)
+- **[1]** late fix (0 entities; functional, low 0.00)
+- **[2]** mild (0 entities; functional, low 0.00)
+
+## Entities (by risk)
+
+| Risk | Type | Entity | File | Score | Classification | Blast | Change |
+|------|------|--------|------|-------|----------------|-------|--------|
+| HIGH | function | `hot` | `b.rs` | 0.90 | functional | 0 | modified |
+| CRITICAL | function | `late` | `a.rs` | 0.50 | functional | 0 | modified |
+| MEDIUM | function | `mild` | `c.rs` | 0.40 | functional | 0 | modified |
+
+… and 1 more entities (raise the limit to see them)
+
+## High-risk details
+
+### `hot` (function) in `b.rs`
+
+- **Risk:** High (0.90) | **Blast radius:** 0 | **Public API:** false
+- 3 dependents may be affected
+
+```diff
+@@ -1,3 +1,3 @@
+ fn hot() {
+-    1
++    2
+ }
+```
+
+### `late` (function) in `a.rs`
+
+- **Risk:** Critical (0.50) | **Blast radius:** 0 | **Public API:** false
+";
+        assert_eq!(markdown(&result, true, listing, diffs), expected);
+        assert_eq!(markdown(&result, false, listing, InlineDiffs::default()).matches("```").count(), 0);
+    }
+}
@@ -1,14 +1,24 @@
+use globset::{GlobBuilder, GlobMatcher};
 use sem_core::model::change::ChangeType;
 use serde::{Deserialize, Serialize};
 
-use crate::types::{ChangeClassification, EntityReview, ReviewResult, RiskLevel};
+use crate::filter::parse_risk_level;
+use crate::types::{
+    ChangeClassification, EntityReview, ReviewResult, RiskLevel, RuleEffect, RuleHit, ScoreComponents,
+};
 
 /// Quick signal for agents about how much review attention a change needs.
+/// Serialized in snake_case like its display name.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ReviewVerdict {
+    #[serde(alias = "LikelyApprovable")]
     LikelyApprovable,
+    #[serde(alias = "StandardReview")]
     StandardReview,
+    #[serde(alias = "RequiresReview")]
     RequiresReview,
+    #[serde(alias = "RequiresCarefulReview")]
     RequiresCarefulReview,
 }
 
@@ -23,21 +33,534 @@ impl std::fmt::Display for ReviewVerdict {
     }
 }
 
+/// Minimum score for each risk level.
+pub const CRITICAL_THRESHOLD: f64 = 0.7;
+pub const HIGH_THRESHOLD: f64 = 0.5;
+pub const MEDIUM_THRESHOLD: f64 = 0.3;
+
+/// Entities named in a verdict trigger before the rest are summarized as "+N more".
+const TRIGGER_NAMES: usize = 3;
+
+/// A verdict together with the facts that decided it.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerdictReport {
+    pub verdict: ReviewVerdict,
+    /// Human-readable triggers, e.g. "2 critical entities: parse in a.rs, run in b.rs"
+    pub reasons: Vec<String>,
+    /// Highest-scoring entity behind the verdict, if any
+    pub top_entity_id: Option<String>,
+}
+
 /// Suggest a review verdict based on the analysis result.
 pub fn suggest_verdict(result: &ReviewResult) -> ReviewVerdict {
-    if result.stats.by_risk.critical > 0 {
-        return ReviewVerdict::RequiresCarefulReview;
-    }
-    if result.stats.by_risk.high > 0 {
-        return ReviewVerdict::RequiresReview;
+    explain_verdict(result).verdict
+}
+
+/// Suggest a review verdict and say why.
+pub fn explain_verdict(result: &ReviewResult) -> VerdictReport {
+    let reviews = &result.entity_reviews;
+    for (level, verdict) in [
+        (RiskLevel::Critical, ReviewVerdict::RequiresCarefulReview),
+        (RiskLevel::High, ReviewVerdict::RequiresReview),
+    ] {
+        let mut hits: Vec<&EntityReview> = reviews.iter().filter(|r| r.risk_level == level).collect();
+        if hits.is_empty() {
+            continue;
+        }
+        hits.sort_by(|a, b| b.risk_score.total_cmp(&a.risk_score));
+        let mut named: Vec<String> = hits
+            .iter()
+            .take(TRIGGER_NAMES)
+            .map(|r| format!("{} in {}", r.entity_name, r.file_path))
+            .collect();
+        if hits.len() > TRIGGER_NAMES {
+            named.push(format!("+{} more", hits.len() - TRIGGER_NAMES));
+        }
+        let noun = if hits.len() == 1 { "entity" } else { "entities" };
+        return VerdictReport {
+            verdict,
+            reasons: vec![format!("{} {} {}: {}", hits.len(), level, noun, named.join(", "))],
+            top_entity_id: Some(hits[0].entity_id.clone()),
+        };
     }
+
     // All cosmetic = likely approvable
-    let all_cosmetic = !result.entity_reviews.is_empty()
-        && result.entity_reviews.iter().all(|r| r.structural_change == Some(false));
+    let all_cosmetic = !reviews.is_empty() && reviews.iter().all(|r| r.structural_change == Some(false));
     if all_cosmetic {
-        return ReviewVerdict::LikelyApprovable;
+        return VerdictReport {
+            verdict: ReviewVerdict::LikelyApprovable,
+            reasons: vec![format!("all {} changes cosmetic", reviews.len())],
+            top_entity_id: None,
+        };
+    }
+
+    let reasons = if reviews.is_empty() {
+        vec!["no entity-level changes".to_string()]
+    } else {
+        let structural = reviews.iter().filter(|r| r.structural_change != Some(false)).count();
+        vec![
+            "no critical or high-risk entities".to_string(),
+            format!("{} of {} changes structural", structural, reviews.len()),
+        ]
+    };
+    VerdictReport {
+        verdict: ReviewVerdict::StandardReview,
+        reasons,
+        top_entity_id: reviews
+            .iter()
+            .max_by(|a, b| a.risk_score.total_cmp(&b.risk_score))
+            .map(|r| r.entity_id.clone()),
+    }
+}
+
+/// Whether a CI gate at `fail_on` passes: no entity at or above that level.
+pub fn passes_risk_gate(result: &ReviewResult, fail_on: RiskLevel) -> bool {
+    entities_at_or_above(result, fail_on) == 0
+}
+
+/// Number of entities at or above `level`.
+pub fn entities_at_or_above(result: &ReviewResult, level: RiskLevel) -> usize {
+    result.entity_reviews.iter().filter(|r| r.risk_level >= level).count()
+}
+
+/// One-line risk summary, e.g. "2 critical, 5 high of 40 entities".
+pub fn risk_summary(result: &ReviewResult) -> String {
+    let count = |level| result.entity_reviews.iter().filter(|r| r.risk_level == level).count();
+    format!(
+        "{} critical, {} high of {} entities",
+        count(RiskLevel::Critical),
+        count(RiskLevel::High),
+        result.entity_reviews.len()
+    )
+}
+
+/// Tunable weights for the graph-centric part of risk scoring. Fields left
+/// out of a weights file keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskWeights {
+    /// Added for public API entities
+    pub public_api: f64,
+    /// Multiplier on sqrt(blast radius / total entities)
+    pub blast_radius: f64,
+    /// Multiplier on ln(1 + dependent count)
+    pub dependents: f64,
+    /// Factor applied to cosmetic-only changes
+    pub cosmetic_discount: f64,
+    /// Factor applied to changes in test code
+    pub test_discount: f64,
+}
+
+impl Default for RiskWeights {
+    fn default() -> Self {
+        Self {
+            public_api: 0.12,
+            blast_radius: 0.30,
+            dependents: 0.15,
+            cosmetic_discount: 0.2,
+            test_discount: 0.5,
+        }
+    }
+}
+
+impl RiskWeights {
+    /// Load weights from a JSON file.
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read weights file {}: {}", path.display(), e))?;
+        let weights: Self =
+            serde_json::from_str(&raw).map_err(|e| format!("invalid weights file {}: {}", path.display(), e))?;
+        weights.validate()?;
+        Ok(weights)
+    }
+
+    /// Every weight must be finite and non-negative; the discounts are at most 1.
+    pub fn validate(&self) -> Result<(), String> {
+        let fields = [
+            ("public_api", self.public_api),
+            ("blast_radius", self.blast_radius),
+            ("dependents", self.dependents),
+            ("cosmetic_discount", self.cosmetic_discount),
+            ("test_discount", self.test_discount),
+        ];
+        for (name, value) in fields {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("weight {} must be a non-negative number, got {}", name, value));
+            }
+        }
+        for (name, value) in [("cosmetic_discount", self.cosmetic_discount), ("test_discount", self.test_discount)] {
+            if value > 1.0 {
+                return Err(format!("weight {} must be at most 1.0, got {}", name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Minimum score for each risk level. Must be strictly increasing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskThresholds {
+    pub medium: f64,
+    pub high: f64,
+    pub critical: f64,
+}
+
+impl Default for RiskThresholds {
+    fn default() -> Self {
+        Self {
+            medium: MEDIUM_THRESHOLD,
+            high: HIGH_THRESHOLD,
+            critical: CRITICAL_THRESHOLD,
+        }
+    }
+}
+
+impl RiskThresholds {
+    /// Map a risk score to a risk level.
+    pub fn level(&self, score: f64) -> RiskLevel {
+        if score >= self.critical {
+            RiskLevel::Critical
+        } else if score >= self.high {
+            RiskLevel::High
+        } else if score >= self.medium {
+            RiskLevel::Medium
+        } else {
+            RiskLevel::Low
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("medium", self.medium), ("high", self.high), ("critical", self.critical)] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("threshold {} must be a non-negative number, got {}", name, value));
+            }
+        }
+        if !(self.medium < self.high && self.high < self.critical) {
+            return Err(format!(
+                "thresholds must be strictly increasing (medium < high < critical), got {} / {} / {}",
+                self.medium, self.high, self.critical
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Baseline score for each change classification.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassificationWeights {
+    pub text: f64,
+    pub syntax: f64,
+    pub functional: f64,
+    pub text_syntax: f64,
+    pub text_functional: f64,
+    pub syntax_functional: f64,
+    pub text_syntax_functional: f64,
+}
+
+impl Default for ClassificationWeights {
+    fn default() -> Self {
+        Self {
+            text: 0.0,
+            syntax: 0.08,
+            functional: 0.22,
+            text_syntax: 0.1,
+            text_functional: 0.22,
+            syntax_functional: 0.25,
+            text_syntax_functional: 0.28,
+        }
+    }
+}
+
+impl ClassificationWeights {
+    fn weight(&self, c: ChangeClassification) -> f64 {
+        match c {
+            ChangeClassification::Text => self.text,
+            ChangeClassification::Syntax => self.syntax,
+            ChangeClassification::Functional => self.functional,
+            ChangeClassification::TextSyntax => self.text_syntax,
+            ChangeClassification::TextFunctional => self.text_functional,
+            ChangeClassification::SyntaxFunctional => self.syntax_functional,
+            ChangeClassification::TextSyntaxFunctional => self.text_syntax_functional,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let fields = [
+            ("text", self.text),
+            ("syntax", self.syntax),
+            ("functional", self.functional),
+            ("text_syntax", self.text_syntax),
+            ("text_functional", self.text_functional),
+            ("syntax_functional", self.syntax_functional),
+            ("text_syntax_functional", self.text_syntax_functional),
+        ];
+        for (name, value) in fields {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!(
+                    "classification weight {} must be a non-negative number, got {}",
+                    name, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a path rule does to matching entities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleAction {
+    /// Add to the score (capped at 1.0)
+    Boost(f64),
+    /// Raise the level to at least this
+    MinLevel(RiskLevel),
+    /// Lower the level to at most this
+    MaxLevel(RiskLevel),
+}
+
+/// A glob over repo-relative file paths and what it does to entities there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RuleSpec", into = "RuleSpec")]
+pub struct RiskRule {
+    pub pattern: String,
+    pub action: RuleAction,
+    matcher: GlobMatcher,
+}
+
+impl PartialEq for RiskRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.action == other.action
+    }
+}
+
+impl RiskRule {
+    /// `*` stays within one path segment; `**` crosses directories.
+    pub fn new(pattern: &str, action: RuleAction) -> Result<Self, String> {
+        if let RuleAction::Boost(amount) = action {
+            if !amount.is_finite() || amount < 0.0 {
+                return Err(format!("rule '{}': boost must be a non-negative number, got {}", pattern, amount));
+            }
+        }
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("rule '{}': invalid pattern: {}", pattern, e))?
+            .compile_matcher();
+        Ok(Self {
+            pattern: pattern.to_string(),
+            action,
+            matcher,
+        })
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.matcher.is_match(path)
+    }
+}
+
+/// A rule as written in `.inspect.toml`: a pattern and exactly one action.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    boost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min_level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_level: Option<String>,
+}
+
+impl TryFrom<RuleSpec> for RiskRule {
+    type Error = String;
+
+    fn try_from(spec: RuleSpec) -> Result<Self, String> {
+        let action = match (spec.boost, spec.min_level, spec.max_level) {
+            (Some(amount), None, None) => RuleAction::Boost(amount),
+            (None, Some(level), None) => RuleAction::MinLevel(parse_risk_level(&level)?),
+            (None, None, Some(level)) => RuleAction::MaxLevel(parse_risk_level(&level)?),
+            _ => {
+                return Err(format!(
+                    "rule '{}' must set exactly one of boost, min_level, max_level",
+                    spec.pattern
+                ))
+            }
+        };
+        RiskRule::new(&spec.pattern, action)
+    }
+}
+
+impl From<RiskRule> for RuleSpec {
+    fn from(rule: RiskRule) -> Self {
+        let mut spec = RuleSpec {
+            pattern: rule.pattern,
+            boost: None,
+            min_level: None,
+            max_level: None,
+        };
+        match rule.action {
+            RuleAction::Boost(amount) => spec.boost = Some(amount),
+            RuleAction::MinLevel(level) => spec.min_level = Some(level.to_string()),
+            RuleAction::MaxLevel(level) => spec.max_level = Some(level.to_string()),
+        }
+        spec
+    }
+}
+
+/// Path rules applied after scoring, e.g.
+/// `RiskRules::new().min_level("src/auth/**", RiskLevel::High)`.
+///
+/// Boosts apply first, in order, and the level is taken from the boosted
+/// score. Level clamps apply after that, in order, so a `max_level` rule
+/// listed after a `min_level` rule wins for paths matching both.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RiskRules(Vec<RiskRule>);
+
+impl RiskRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `amount` to the score of matching entities.
+    ///
+    /// Panics if the pattern is not a valid glob or the amount is negative.
+    pub fn boost(self, pattern: &str, amount: f64) -> Self {
+        self.with(pattern, RuleAction::Boost(amount))
+    }
+
+    /// Raise matching entities to at least `level`. Panics on an invalid glob.
+    pub fn min_level(self, pattern: &str, level: RiskLevel) -> Self {
+        self.with(pattern, RuleAction::MinLevel(level))
+    }
+
+    /// Cap matching entities at `level`. Panics on an invalid glob.
+    pub fn max_level(self, pattern: &str, level: RiskLevel) -> Self {
+        self.with(pattern, RuleAction::MaxLevel(level))
+    }
+
+    fn with(mut self, pattern: &str, action: RuleAction) -> Self {
+        self.0.push(RiskRule::new(pattern, action).unwrap_or_else(|e| panic!("{}", e)));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Apply matching rules to a computed score. Returns the final score,
+    /// the level, and the last rule that changed either.
+    pub fn apply(&self, path: &str, score: f64, thresholds: &RiskThresholds) -> (f64, RiskLevel, Option<RuleHit>) {
+        let mut score = score;
+        let mut hit = None;
+        let matching: Vec<&RiskRule> = self.0.iter().filter(|r| r.matches(path)).collect();
+
+        for rule in &matching {
+            if let RuleAction::Boost(amount) = rule.action {
+                let boosted = (score + amount).min(1.0);
+                if boosted > score {
+                    score = boosted;
+                    hit = Some((rule, RuleEffect::Boosted));
+                }
+            }
+        }
+
+        let mut level = thresholds.level(score);
+        for rule in &matching {
+            match rule.action {
+                RuleAction::MinLevel(min) if level < min => {
+                    level = min;
+                    hit = Some((rule, RuleEffect::Raised));
+                }
+                RuleAction::MaxLevel(max) if level > max => {
+                    level = max;
+                    hit = Some((rule, RuleEffect::Capped));
+                }
+                _ => {}
+            }
+        }
+
+        let hit = hit.map(|(rule, effect)| RuleHit {
+            pattern: rule.pattern.clone(),
+            effect,
+        });
+        (score, level, hit)
+    }
+}
+
+/// Repo-level risk scoring settings, read from the `[risk]` section of
+/// `.inspect.toml` at the repo root:
+///
+/// ```toml
+/// [risk.weights]
+/// blast_radius = 0.2
+///
+/// [risk.thresholds]
+/// high = 0.6
+/// critical = 0.8
+///
+/// [[risk.rules]]
+/// pattern = "src/auth/**"
+/// min_level = "high"
+/// ```
+///
+/// Anything left out keeps its default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    pub weights: RiskWeights,
+    pub classification: ClassificationWeights,
+    pub thresholds: RiskThresholds,
+    pub rules: RiskRules,
+}
+
+/// Name of the repo-level config file.
+pub const CONFIG_FILE: &str = ".inspect.toml";
+
+/// Top level of `.inspect.toml`. Other sections are ignored here.
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    risk: RiskConfig,
+}
+
+impl RiskConfig {
+    /// Load `.inspect.toml` from the repo root. A missing file means defaults.
+    pub fn load(repo_root: &std::path::Path) -> Result<Self, String> {
+        let path = repo_root.join(CONFIG_FILE);
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        Self::from_toml(&raw).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse and validate the `[risk]` section of a config file.
+    pub fn from_toml(raw: &str) -> Result<Self, String> {
+        let file: ConfigFile = toml::from_str(raw).map_err(|e| format!("invalid config: {}", e))?;
+        file.risk.validate()?;
+        Ok(file.risk)
+    }
+
+    /// Score an entity, set its level, then apply path rules.
+    pub fn assess(&self, review: &mut EntityReview, total_entities: usize) {
+        let mut parts = score_components(review, total_entities, self);
+        let base = parts.total().min(1.0);
+        let (score, level, hit) = self.rules.apply(&review.file_path, base, &self.thresholds);
+        parts.rule_boost = score - base;
+        review.risk_score = score;
+        review.risk_level = level;
+        review.risk_rule = hit;
+        review.score_components = Some(parts);
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.weights.validate()?;
+        self.classification.validate()?;
+        self.thresholds.validate()
     }
-    ReviewVerdict::StandardReview
 }
 
 /// Compute a risk score (0.0 to 1.0) for an entity review.
@@ -46,61 +569,59 @@ pub fn suggest_verdict(result: &ReviewResult) -> ReviewVerdict {
 /// discriminators. Classification and change type set a low baseline.
 /// Only entities with real graph impact reach High/Critical.
 pub fn compute_risk_score(review: &EntityReview, total_entities: usize) -> f64 {
-    let mut score = 0.0;
+    compute_risk_score_with(review, total_entities, &RiskConfig::default())
+}
 
-    // Classification weight (low baseline: 0.0 to 0.15)
-    score += classification_weight(review.classification);
+/// [`compute_risk_score`] with repo or session settings.
+pub fn compute_risk_score_with(review: &EntityReview, total_entities: usize, config: &RiskConfig) -> f64 {
+    score_components(review, total_entities, config).total().min(1.0)
+}
 
-    // Change type weight (0.0 to 0.1)
-    score += change_type_weight(review.change_type);
+/// Break a risk score into its contributions. They sum to the score before
+/// it is capped at 1.0.
+pub fn score_components(review: &EntityReview, total_entities: usize, config: &RiskConfig) -> ScoreComponents {
+    let weights = &config.weights;
+    let mut parts = ScoreComponents {
+        // Classification weight (low baseline: 0.0 to 0.28 by default)
+        classification: config.classification.weight(review.classification),
+        // Change type weight (0.0 to 0.1)
+        change_type: change_type_weight(review.change_type),
+        ..ScoreComponents::default()
+    };
 
     // Public API boost
     if review.is_public_api {
-        score += 0.12;
+        parts.public_api = weights.public_api;
     }
 
     // Blast radius: normalized by total entity count, sqrt-scaled
     if total_entities > 0 && review.blast_radius > 0 {
         let blast_ratio = review.blast_radius as f64 / total_entities as f64;
-        score += blast_ratio.sqrt() * 0.30;
+        parts.blast_radius = blast_ratio.sqrt() * weights.blast_radius;
     }
 
     // Dependent count: logarithmic scaling
     if review.dependent_count > 0 {
-        score += (1.0 + review.dependent_count as f64).ln() * 0.15;
+        parts.dependents = (1.0 + review.dependent_count as f64).ln() * weights.dependents;
     }
 
+    // Discounts scale the score so far and are recorded as what they take off.
     // Cosmetic-only discount (structural_hash unchanged)
     if review.structural_change == Some(false) {
-        score *= 0.2;
+        parts.cosmetic_discount = -parts.total() * (1.0 - weights.cosmetic_discount);
     }
 
-    score.min(1.0)
-}
-
-/// Map risk score to risk level.
-pub fn score_to_level(score: f64) -> RiskLevel {
-    if score >= 0.7 {
-        RiskLevel::Critical
-    } else if score >= 0.5 {
-        RiskLevel::High
-    } else if score >= 0.3 {
-        RiskLevel::Medium
-    } else {
-        RiskLevel::Low
+    // Test code discount
+    if review.is_test {
+        parts.test_discount = -parts.total() * (1.0 - weights.test_discount);
     }
+
+    parts
 }
 
-fn classification_weight(c: ChangeClassification) -> f64 {
-    match c {
-        ChangeClassification::Text => 0.0,
-        ChangeClassification::Syntax => 0.08,
-        ChangeClassification::Functional => 0.22,
-        ChangeClassification::TextSyntax => 0.1,
-        ChangeClassification::TextFunctional => 0.22,
-        ChangeClassification::SyntaxFunctional => 0.25,
-        ChangeClassification::TextSyntaxFunctional => 0.28,
-    }
+/// Map risk score to risk level using the default thresholds.
+pub fn score_to_level(score: f64) -> RiskLevel {
+    RiskThresholds::default().level(score)
 }
 
 fn change_type_weight(ct: ChangeType) -> f64 {
@@ -113,30 +634,110 @@ fn change_type_weight(ct: ChangeType) -> f64 {
     }
 }
 
-/// Detect if an entity is a public API based on name and type patterns.
-pub fn is_public_api(entity_type: &str, entity_name: &str, content: Option<&str>) -> bool {
-    // Check content for explicit pub/export markers
-    if let Some(content) = content {
-        let first_line = content.lines().next().unwrap_or("");
-        if first_line.starts_with("pub ")
-            || first_line.starts_with("pub(crate)")
-            || first_line.starts_with("export ")
-            || first_line.starts_with("module.exports")
-        {
-            return true;
-        }
-    }
+/// Detect if an entity is public API, by the conventions of its language:
+///
+/// - Rust: a `pub` or `pub(crate)` item
+/// - JS/TS: an `export` on the item, or `export default NAME`,
+///   `export { NAME }`, or `module.exports` naming it elsewhere in the module
+/// - Python: no leading underscore (dunders count as public), and listed in
+///   `__all__` when the module defines one
+/// - Go: a capitalized name
+/// - Java and C#: a `public` modifier; Kotlin: no restricting modifier
+///
+/// Attribute, decorator, annotation, and comment lines before the
+/// declaration are skipped. `module` is the whole file's content, when
+/// available. Other languages only count `pub`/`export` on the declaration.
+pub fn is_public_api(
+    file_path: &str,
+    entity_type: &str,
+    entity_name: &str,
+    content: Option<&str>,
+    module: Option<&str>,
+) -> bool {
+    let ext = file_path.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+    let decl = content.and_then(declaration_line).unwrap_or("");
 
-    // Convention: capitalized names in Go/Java are public
-    if matches!(entity_type, "function" | "method" | "struct" | "interface") {
-        if let Some(first_char) = entity_name.chars().next() {
-            if first_char.is_uppercase() {
-                return true;
+    match ext {
+        "rs" => decl.starts_with("pub ") || decl.starts_with("pub(crate)"),
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => {
+            decl.starts_with("export ")
+                || decl.starts_with("module.exports")
+                || module.is_some_and(|m| exported_by_module(m, entity_name))
+        }
+        "py" => {
+            let dunder = entity_name.starts_with("__") && entity_name.ends_with("__");
+            if entity_name.starts_with('_') && !dunder {
+                return false;
+            }
+            // __all__ lists module-level names only
+            match module.and_then(python_all) {
+                Some(names) if entity_type != "method" => names.iter().any(|n| n == entity_name),
+                _ => true,
             }
         }
+        "go" => entity_name.chars().next().is_some_and(char::is_uppercase),
+        "java" | "cs" => modifiers(decl).any(|m| m == "public"),
+        "kt" | "kts" => !modifiers(decl).any(|m| matches!(m, "private" | "internal" | "protected")),
+        _ => decl.starts_with("pub ") || decl.starts_with("export "),
     }
+}
+
+/// First line of an entity's source that isn't blank, a comment, an
+/// attribute, or a decorator/annotation.
+fn declaration_line(content: &str) -> Option<&str> {
+    content.lines().map(str::trim).find(|l| {
+        !(l.is_empty()
+            || l.starts_with("//")
+            || l.starts_with("/*")
+            || l.starts_with('*')
+            || l.starts_with('#')
+            || l.starts_with('@'))
+    })
+}
+
+/// Words before the declaration's name or parameter list.
+fn modifiers(decl: &str) -> impl Iterator<Item = &str> {
+    decl.split(['(', '{', '=', '<'])
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+}
 
-    false
+/// Whether a JS/TS module exports `name` separately from its declaration.
+fn exported_by_module(module: &str, name: &str) -> bool {
+    module.lines().map(str::trim).any(|line| {
+        if let Some(rest) = line.strip_prefix("export default ") {
+            return rest.trim_end_matches(';').trim() == name;
+        }
+        if let Some(rest) = line.strip_prefix("export {") {
+            let list = rest.split('}').next().unwrap_or("");
+            return list
+                .split(',')
+                .any(|item| item.split_whitespace().next() == Some(name));
+        }
+        if let Some(rest) = line.strip_prefix("module.exports") {
+            let rest = rest.trim_start();
+            return rest.strip_prefix('.').is_some_and(|r| r.starts_with(name))
+                || rest
+                    .strip_prefix('=')
+                    .is_some_and(|r| r.trim().trim_end_matches(';') == name);
+        }
+        false
+    })
+}
+
+/// Names in a Python module's `__all__`, if it defines one.
+fn python_all(module: &str) -> Option<Vec<String>> {
+    let start = module.find("__all__")?;
+    let rest = &module[start..];
+    let open = rest.find(['[', '('])?;
+    let close = rest[open..].find([']', ')'])? + open;
+    let names = rest[open + 1..close]
+        .split(',')
+        .map(|item| item.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    Some(names)
 }
 
 #[cfg(test)]
@@ -156,24 +757,15 @@ mod tests {
         EntityReview {
             entity_id: "test".into(),
             entity_name: "foo".into(),
-            entity_type: "function".into(),
             file_path: "test.rs".into(),
             change_type,
             classification,
-            risk_score: 0.0,
-            risk_level: RiskLevel::Low,
             blast_radius,
             dependent_count,
-            dependency_count: 0,
             is_public_api: is_public,
             structural_change,
-            group_id: 0,
-            start_line: 1,
             end_line: 10,
-            before_content: None,
-            after_content: None,
-            dependent_names: vec![],
-            dependency_names: vec![],
+            ..EntityReview::test_default()
         }
     }
 
@@ -239,4 +831,314 @@ mod tests {
         let score = compute_risk_score(&review, 100);
         assert!(score >= 0.5, "Expected High+, got score={score}");
     }
+
+    fn verdict_result(reviews: Vec<EntityReview>) -> ReviewResult {
+        ReviewResult {
+            stats: crate::analyze::compute_stats(&reviews),
+            entity_reviews: reviews,
+            groups: vec![],
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    fn scored(name: &str, file: &str, score: f64, structural: Option<bool>) -> EntityReview {
+        let mut r = make_review(ChangeType::Modified, ChangeClassification::Functional, 0, 0, false, structural);
+        r.entity_id = format!("{}::{}", file, name);
+        r.entity_name = name.into();
+        r.file_path = file.into();
+        r.risk_score = score;
+        r.risk_level = score_to_level(score);
+        r
+    }
+
+    #[test]
+    fn verdict_names_critical_entities() {
+        let report = explain_verdict(&verdict_result(vec![
+            scored("x", "a.rs", 0.75, Some(true)),
+            scored("y", "b.rs", 0.9, Some(true)),
+            scored("z", "c.rs", 0.55, Some(true)),
+        ]));
+        assert_eq!(report.verdict, ReviewVerdict::RequiresCarefulReview);
+        assert_eq!(report.reasons, vec!["2 critical entities: y in b.rs, x in a.rs"]);
+        assert_eq!(report.top_entity_id.as_deref(), Some("b.rs::y"));
+    }
+
+    #[test]
+    fn verdict_summarizes_many_high_entities() {
+        let reviews = (0..5)
+            .map(|i| scored(&format!("f{}", i), "a.rs", 0.5 + i as f64 * 0.01, Some(true)))
+            .collect();
+        let report = explain_verdict(&verdict_result(reviews));
+        assert_eq!(report.verdict, ReviewVerdict::RequiresReview);
+        assert_eq!(
+            report.reasons,
+            vec!["5 high entities: f4 in a.rs, f3 in a.rs, f2 in a.rs, +2 more"]
+        );
+    }
+
+    #[test]
+    fn verdict_all_cosmetic_is_likely_approvable() {
+        let report = explain_verdict(&verdict_result(vec![
+            scored("x", "a.rs", 0.05, Some(false)),
+            scored("y", "a.rs", 0.02, Some(false)),
+        ]));
+        assert_eq!(report.verdict, ReviewVerdict::LikelyApprovable);
+        assert_eq!(report.reasons, vec!["all 2 changes cosmetic"]);
+        assert_eq!(report.top_entity_id, None);
+    }
+
+    #[test]
+    fn verdict_standard_review() {
+        let report = explain_verdict(&verdict_result(vec![
+            scored("x", "a.rs", 0.35, Some(true)),
+            scored("y", "a.rs", 0.05, Some(false)),
+        ]));
+        assert_eq!(report.verdict, ReviewVerdict::StandardReview);
+        assert_eq!(report.reasons[1], "1 of 2 changes structural");
+        assert_eq!(report.top_entity_id.as_deref(), Some("a.rs::x"));
+
+        let empty = explain_verdict(&verdict_result(vec![]));
+        assert_eq!(empty.verdict, ReviewVerdict::StandardReview);
+        assert_eq!(empty.reasons, vec!["no entity-level changes"]);
+    }
+
+    #[test]
+    fn risk_gate_and_summary() {
+        let result = verdict_result(vec![
+            scored("x", "a.rs", 0.75, Some(true)),
+            scored("y", "a.rs", 0.55, Some(true)),
+            scored("z", "a.rs", 0.1, Some(true)),
+        ]);
+        assert!(!passes_risk_gate(&result, RiskLevel::Critical));
+        assert!(!passes_risk_gate(&result, RiskLevel::High));
+        assert_eq!(entities_at_or_above(&result, RiskLevel::High), 2);
+        assert_eq!(entities_at_or_above(&result, RiskLevel::Low), 3);
+        assert_eq!(risk_summary(&result), "1 critical, 1 high of 3 entities");
+
+        let calm = verdict_result(vec![scored("y", "a.rs", 0.55, Some(true))]);
+        assert!(passes_risk_gate(&calm, RiskLevel::Critical));
+        assert!(!passes_risk_gate(&calm, RiskLevel::Medium));
+    }
+
+    #[test]
+    fn custom_weights_change_scores() {
+        let review = make_review(ChangeType::Modified, ChangeClassification::Functional, 0, 0, true, Some(true));
+        let base = compute_risk_score(&review, 10);
+        assert_eq!(compute_risk_score_with(&review, 10, &RiskConfig::default()), base);
+
+        let weights: RiskWeights = serde_json::from_str(r#"{"public_api": 0.4}"#).unwrap();
+        assert_eq!(weights.dependents, RiskWeights::default().dependents);
+        let config = RiskConfig {
+            weights,
+            ..RiskConfig::default()
+        };
+        assert!(compute_risk_score_with(&review, 10, &config) > base);
+        assert!(serde_json::from_str::<RiskWeights>(r#"{"public": 0.4}"#).is_err());
+    }
+
+    #[test]
+    fn missing_config_uses_defaults() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        assert_eq!(RiskConfig::load(tmp.path()).unwrap(), RiskConfig::default());
+        assert_eq!(RiskConfig::from_toml("[other]\nkey = 1\n").unwrap(), RiskConfig::default());
+        assert_eq!(RiskThresholds::default().level(0.5), score_to_level(0.5));
+    }
+
+    #[test]
+    fn partial_config_overrides_only_named_fields() {
+        let raw = "[risk.weights]\nblast_radius = 0.1\n\n[risk.thresholds]\nhigh = 0.6\ncritical = 0.8\n";
+        let config = RiskConfig::from_toml(raw).unwrap();
+        assert_eq!(config.weights.blast_radius, 0.1);
+        assert_eq!(config.weights.public_api, RiskWeights::default().public_api);
+        assert_eq!(config.classification, ClassificationWeights::default());
+        assert_eq!(config.thresholds.medium, MEDIUM_THRESHOLD);
+        assert_eq!(config.thresholds.level(0.55), RiskLevel::Medium);
+        assert_eq!(config.thresholds.level(0.75), RiskLevel::High);
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let negative = "[risk.classification]\nfunctional = -0.1\n";
+        assert!(RiskConfig::from_toml(negative).unwrap_err().contains("functional"));
+
+        let unordered = "[risk.thresholds]\nhigh = 0.8\ncritical = 0.7\n";
+        assert!(RiskConfig::from_toml(unordered).unwrap_err().contains("strictly increasing"));
+
+        let unknown = "[risk.weights]\nblast = 0.1\n";
+        assert!(RiskConfig::from_toml(unknown).is_err());
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join(CONFIG_FILE), unordered).unwrap();
+        assert!(RiskConfig::load(tmp.path()).unwrap_err().contains(CONFIG_FILE));
+    }
+
+    fn at(path: &str, score: f64) -> EntityReview {
+        let mut review = make_review(ChangeType::Modified, ChangeClassification::Functional, 0, 0, false, Some(true));
+        review.file_path = path.into();
+        review.risk_score = score;
+        review
+    }
+
+    fn apply(rules: &RiskRules, review: &EntityReview) -> (f64, RiskLevel, Option<RuleHit>) {
+        rules.apply(&review.file_path, review.risk_score, &RiskThresholds::default())
+    }
+
+    #[test]
+    fn rule_globs_match_repo_paths() {
+        let rule = RiskRule::new("src/auth/**", RuleAction::Boost(0.1)).unwrap();
+        assert!(rule.matches("src/auth/login.rs"));
+        assert!(rule.matches("src/auth/oauth/token.rs"));
+        assert!(!rule.matches("src/authz.rs"));
+        assert!(!rule.matches("lib/src/auth/login.rs"));
+
+        let any_depth = RiskRule::new("**/migrations/*.sql", RuleAction::Boost(0.1)).unwrap();
+        assert!(any_depth.matches("migrations/001.sql"));
+        assert!(any_depth.matches("db/migrations/002.sql"));
+        assert!(!any_depth.matches("db/migrations/old/003.sql"));
+
+        assert!(RiskRule::new("src/[auth", RuleAction::Boost(0.1)).is_err());
+        assert!(RiskRule::new("src/**", RuleAction::Boost(-0.1)).is_err());
+    }
+
+    #[test]
+    fn no_matching_rule_is_a_no_op() {
+        let rules = RiskRules::new().min_level("src/auth/**", RiskLevel::High);
+        let review = at("src/lib.rs", 0.2);
+        assert_eq!(apply(&rules, &review), (0.2, RiskLevel::Low, None));
+        assert_eq!(apply(&RiskRules::new(), &review), (0.2, RiskLevel::Low, None));
+    }
+
+    #[test]
+    fn clamps_apply_after_boosts() {
+        let rules = RiskRules::new()
+            .max_level("examples/**", RiskLevel::Medium)
+            .boost("examples/**", 0.5)
+            .min_level("src/auth/**", RiskLevel::High);
+
+        // The boost lifts the score past High, then the cap pulls the level back
+        let (score, level, hit) = apply(&rules, &at("examples/demo.rs", 0.3));
+        assert_eq!(score, 0.8);
+        assert_eq!(level, RiskLevel::Medium);
+        assert_eq!(hit.unwrap().effect, RuleEffect::Capped);
+
+        let (score, level, hit) = apply(&rules, &at("src/auth/login.rs", 0.1));
+        assert_eq!(score, 0.1);
+        assert_eq!(level, RiskLevel::High);
+        let hit = hit.unwrap();
+        assert_eq!(hit.pattern, "src/auth/**");
+        assert_eq!(hit.effect, RuleEffect::Raised);
+
+        // Already above the minimum: the rule doesn't fire
+        assert_eq!(apply(&rules, &at("src/auth/login.rs", 0.75)).2, None);
+    }
+
+    #[test]
+    fn rules_load_from_config() {
+        let raw = "[[risk.rules]]\npattern = \"billing/**\"\nmin_level = \"high\"\n\n[[risk.rules]]\npattern = \"examples/**\"\nmax_level = \"medium\"\n";
+        let config = RiskConfig::from_toml(raw).unwrap();
+        assert_eq!(
+            config.rules,
+            RiskRules::new()
+                .min_level("billing/**", RiskLevel::High)
+                .max_level("examples/**", RiskLevel::Medium)
+        );
+
+        let mut review = make_review(ChangeType::Added, ChangeClassification::Text, 0, 0, false, None);
+        review.file_path = "billing/invoice.rs".into();
+        config.assess(&mut review, 10);
+        assert_eq!(review.risk_level, RiskLevel::High);
+        assert_eq!(review.risk_rule.as_ref().unwrap().pattern, "billing/**");
+
+        let both = "[[risk.rules]]\npattern = \"a/**\"\nboost = 0.1\nmax_level = \"low\"\n";
+        assert!(RiskConfig::from_toml(both).unwrap_err().contains("exactly one"));
+    }
+
+    #[test]
+    fn test_code_scores_lower() {
+        let score_at = |path: &str| {
+            let mut review = make_review(ChangeType::Modified, ChangeClassification::Functional, 4, 3, true, Some(true));
+            review.file_path = path.into();
+            review.is_test = crate::noise::is_test_path(path);
+            compute_risk_score(&review, 20)
+        };
+        let prod = score_at("pkg/foo.go");
+        let test = score_at("pkg/foo_test.go");
+        assert!(test < prod, "test {} vs prod {}", test, prod);
+        assert!((test - prod * RiskWeights::default().test_discount).abs() < 1e-9);
+    }
+
+    #[test]
+    fn components_sum_to_score() {
+        let cases = [
+            make_review(ChangeType::Modified, ChangeClassification::Functional, 5, 3, true, Some(true)),
+            make_review(ChangeType::Deleted, ChangeClassification::SyntaxFunctional, 40, 12, true, Some(true)),
+            make_review(ChangeType::Added, ChangeClassification::Text, 0, 0, false, Some(false)),
+        ];
+        let config = RiskConfig::default();
+        for mut review in cases {
+            review.is_test = review.change_type == ChangeType::Added;
+            let parts = score_components(&review, 20, &config);
+            let score = compute_risk_score(&review, 20);
+            assert!((parts.total().min(1.0) - score).abs() < 1e-9);
+            if score < 1.0 {
+                assert!((parts.total() - score).abs() < 1e-9);
+            }
+        }
+
+        // Rule boosts are part of the breakdown too
+        let config = RiskConfig {
+            rules: RiskRules::new().boost("**", 0.1),
+            ..RiskConfig::default()
+        };
+        let mut review = make_review(ChangeType::Modified, ChangeClassification::Functional, 0, 0, false, Some(true));
+        config.assess(&mut review, 10);
+        let parts = review.score_components.unwrap();
+        assert!((parts.rule_boost - 0.1).abs() < 1e-9);
+        assert!((parts.total() - review.risk_score).abs() < 1e-9);
+        assert_eq!(parts.top(2)[0].0, "classification");
+    }
+
+    #[test]
+    fn public_api_by_language() {
+        let ts_module = "function helper() {}\nfunction App() {}\nexport default App;\nexport { helper as util };\n";
+        let py_module = "__all__ = [\"load\", 'Store']\n\ndef load(): pass\ndef dump(): pass\n";
+        // (path, type, name, entity source, module source, expected)
+        type Case<'a> = (&'a str, &'a str, &'a str, &'a str, Option<&'a str>, bool);
+        let cases: &[Case] = &[
+            ("src/lib.rs", "function", "parse", "pub fn parse() {}", None, true),
+            ("src/lib.rs", "function", "parse", "#[inline]\n/// Parses.\npub fn parse() {}", None, true),
+            ("src/lib.rs", "function", "Parse", "fn Parse() {}", None, false),
+            ("src/lib.rs", "struct", "Inner", "#[derive(Debug)]\nstruct Inner;", None, false),
+            ("src/app.ts", "function", "load", "export async function load() {}", None, true),
+            ("src/app.tsx", "function", "App", "function App() {}", Some(ts_module), true),
+            ("src/app.tsx", "function", "helper", "function helper() {}", Some(ts_module), true),
+            ("src/app.ts", "class", "Store", "@Injectable()\nexport class Store {}", None, true),
+            ("src/app.ts", "function", "Render", "function Render() {}", Some(ts_module), false),
+            ("pkg/io.py", "function", "load", "def load(): pass", Some(py_module), true),
+            ("pkg/io.py", "function", "dump", "def dump(): pass", Some(py_module), false),
+            ("pkg/io.py", "function", "_cache", "def _cache(): pass", None, false),
+            ("pkg/io.py", "method", "__init__", "def __init__(self): pass", Some(py_module), true),
+            ("pkg/io.py", "function", "dump", "@cache\ndef dump(): pass", None, true),
+            ("srv/server.go", "function", "Serve", "func Serve() {}", None, true),
+            ("srv/server.go", "method", "handle", "func (s *Server) handle() {}", None, false),
+            ("App.java", "method", "run", "@Override\npublic void run() {}", None, true),
+            ("App.java", "method", "Helper", "private static void Helper() {}", None, false),
+            ("App.kt", "function", "run", "fun run() {}", None, true),
+            ("App.kt", "function", "run", "internal fun run() {}", None, false),
+        ];
+        for &(path, ty, name, content, module, expected) in cases {
+            assert_eq!(
+                is_public_api(path, ty, name, Some(content), module),
+                expected,
+                "{} {} in {}",
+                ty,
+                name,
+                path
+            );
+        }
+    }
 }
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use crate::types::{ReviewResult, RiskLevel};
+
+/// How to bucket entities in a risk map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskMapBy {
+    File,
+    /// Directory prefix of at most `depth` components
+    Directory { depth: usize },
+    /// Logical change group from untangling
+    Group,
+}
+
+impl RiskMapBy {
+    /// Parse a `group_by` value ("file", "directory", "group").
+    /// `depth` only applies to directory mode and defaults to 1.
+    pub fn parse(s: &str, depth: Option<usize>) -> Result<Self, String> {
+        match s {
+            "file" => Ok(Self::File),
+            "directory" => Ok(Self::Directory {
+                depth: depth.unwrap_or(1).max(1),
+            }),
+            "group" => Ok(Self::Group),
+            other => Err(format!(
+                "group_by must be 'file', 'directory', or 'group', got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+/// Aggregate risk for one bucket of entities.
+#[derive(Debug, Clone)]
+pub struct BucketRisk {
+    /// File path, directory, or group label
+    pub key: String,
+    /// Set in group mode
+    pub group_id: Option<usize>,
+    pub max_score: f64,
+    pub mean_score: f64,
+    pub max_risk: RiskLevel,
+    pub entity_count: usize,
+    pub critical: usize,
+    pub high: usize,
+    pub public_api_changes: usize,
+}
+
+impl BucketRisk {
+    /// JSON row as served by the MCP tool and `inspect risk-map --format json`.
+    /// The key is named after the bucket kind: `file`, `directory`, or
+    /// `label` next to `group_id`.
+    pub fn to_json(&self, by: RiskMapBy) -> serde_json::Value {
+        let mut row = serde_json::json!({
+            "max_risk": format!("{}", self.max_risk),
+            "max_score": format!("{:.2}", self.max_score),
+            "mean_score": format!("{:.2}", self.mean_score),
+            "entity_count": self.entity_count,
+            "critical": self.critical,
+            "high": self.high,
+            "public_api_changes": self.public_api_changes,
+        });
+        match by {
+            RiskMapBy::File => row["file"] = serde_json::json!(self.key),
+            RiskMapBy::Directory { .. } => row["directory"] = serde_json::json!(self.key),
+            RiskMapBy::Group => {
+                row["group_id"] = serde_json::json!(self.group_id);
+                row["label"] = serde_json::json!(self.key);
+            }
+        }
+        row
+    }
+}
+
+/// Directory of `path` truncated to `depth` components, with `\` separators
+/// normalized to `/`. Files at the repo root map to ".".
+pub fn directory_key(path: &str, depth: usize) -> String {
+    let normalized = path.replace('\\', "/");
+    let normalized = normalized.trim_start_matches("./");
+    let mut dirs: Vec<&str> = normalized.split('/').filter(|c| !c.is_empty()).collect();
+    dirs.pop(); // file name
+
+    if dirs.is_empty() {
+        return ".".to_string();
+    }
+    dirs.truncate(depth);
+    dirs.join("/")
+}
+
+/// Aggregate entity risk per bucket, sorted by max score (highest first), then key.
+pub fn risk_map(result: &ReviewResult, by: RiskMapBy) -> Vec<BucketRisk> {
+    let group_labels: HashMap<usize, &str> =
+        result.groups.iter().map(|g| (g.id, g.label.as_str())).collect();
+
+    let mut buckets: HashMap<(String, Option<usize>), (BucketRisk, f64)> = HashMap::new();
+
+    for r in &result.entity_reviews {
+        let (key, group_id) = match by {
+            RiskMapBy::File => (r.file_path.replace('\\', "/"), None),
+            RiskMapBy::Directory { depth } => (directory_key(&r.file_path, depth), None),
+            RiskMapBy::Group => (
+                group_labels.get(&r.group_id).copied().unwrap_or_default().to_string(),
+                Some(r.group_id),
+            ),
+        };
+
+        let (bucket, total_score) = buckets
+            .entry((key.clone(), group_id))
+            .or_insert_with(|| {
+                (
+                    BucketRisk {
+                        key,
+                        group_id,
+                        max_score: 0.0,
+                        mean_score: 0.0,
+                        max_risk: RiskLevel::Low,
+                        entity_count: 0,
+                        critical: 0,
+                        high: 0,
+                        public_api_changes: 0,
+                    },
+                    0.0,
+                )
+            });
+
+        bucket.entity_count += 1;
+        *total_score += r.risk_score;
+        if r.risk_score > bucket.max_score {
+            bucket.max_score = r.risk_score;
+            bucket.max_risk = r.risk_level;
+        }
+        match r.risk_level {
+            RiskLevel::Critical => bucket.critical += 1,
+            RiskLevel::High => bucket.high += 1,
+            _ => {}
+        }
+        if r.is_public_api {
+            bucket.public_api_changes += 1;
+        }
+    }
+
+    let mut out: Vec<BucketRisk> = buckets
+        .into_values()
+        .map(|(mut bucket, total_score)| {
+            bucket.mean_score = total_score / bucket.entity_count as f64;
+            bucket
+        })
+        .collect();
+
+    out.sort_by(|a, b| {
+        b.max_score
+            .total_cmp(&a.max_score)
+            .then_with(|| a.key.cmp(&b.key))
+            .then_with(|| a.group_id.cmp(&b.group_id))
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::*;
+
+    fn review(file_path: &str, score: f64, level: RiskLevel, group_id: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file_path, score),
+            file_path: file_path.into(),
+            risk_score: score,
+            risk_level: level,
+            group_id,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn result() -> ReviewResult {
+        let entity_reviews = vec![
+            review("src/core/a.rs", 0.9, RiskLevel::Critical, 0),
+            review("src/core/b.rs", 0.5, RiskLevel::Medium, 0),
+            review("src\\cli\\main.rs", 0.7, RiskLevel::High, 1),
+            review("README.md", 0.1, RiskLevel::Low, 1),
+        ];
+        let groups = vec![
+            ChangeGroup {
+                id: 0,
+                label: "core".into(),
+                entity_ids: vec![entity_reviews[0].entity_id.clone(), entity_reviews[1].entity_id.clone()],
+                dominant_classification: ChangeClassification::Functional,
+                max_risk: RiskLevel::Medium,
+                max_score: 0.5,
+                has_public_api: false,
+            },
+            ChangeGroup {
+                id: 1,
+                label: "cli".into(),
+                entity_ids: vec![entity_reviews[2].entity_id.clone(), entity_reviews[3].entity_id.clone()],
+                dominant_classification: ChangeClassification::Functional,
+                max_risk: RiskLevel::Medium,
+                max_score: 0.5,
+                has_public_api: false,
+            },
+        ];
+        let stats = crate::analyze::compute_stats(&entity_reviews);
+        ReviewResult {
+            entity_reviews,
+            groups,
+            stats,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    #[test]
+    fn file_mode_aggregates_per_file() {
+        let mut result = result();
+        let mut extra = review("src/core/a.rs", 0.6, RiskLevel::High, 0);
+        extra.entity_id.push_str("-2");
+        extra.is_public_api = true;
+        result.entity_reviews.push(extra);
+
+        let map = risk_map(&result, RiskMapBy::File);
+        let keys: Vec<&str> = map.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(keys, vec!["src/core/a.rs", "src/cli/main.rs", "src/core/b.rs", "README.md"]);
+
+        let a = &map[0];
+        assert_eq!(a.entity_count, 2);
+        assert_eq!(a.max_risk, RiskLevel::Critical);
+        assert!((a.max_score - 0.9).abs() < 1e-9);
+        assert!((a.mean_score - 0.75).abs() < 1e-9);
+        assert_eq!((a.critical, a.high, a.public_api_changes), (1, 1, 1));
+        assert_eq!(a.group_id, None);
+
+        let row = a.to_json(RiskMapBy::File);
+        assert_eq!(row["file"], "src/core/a.rs");
+        assert_eq!(row["max_score"], "0.90");
+        assert_eq!(row["public_api_changes"], 1);
+    }
+
+    #[test]
+    fn directory_depth_one_rolls_up_to_top_level() {
+        let map = risk_map(&result(), RiskMapBy::Directory { depth: 1 });
+        let keys: Vec<&str> = map.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(keys, vec!["src", "."]);
+
+        let src = &map[0];
+        assert_eq!(src.entity_count, 3);
+        assert_eq!(src.max_risk, RiskLevel::Critical);
+        assert_eq!(src.critical, 1);
+        assert_eq!(src.high, 1);
+        assert!((src.mean_score - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn directory_depth_two_splits_subdirectories() {
+        let map = risk_map(&result(), RiskMapBy::Directory { depth: 2 });
+        let keys: Vec<&str> = map.iter().map(|b| b.key.as_str()).collect();
+        assert_eq!(keys, vec!["src/core", "src/cli", "."]);
+        assert_eq!(map[0].entity_count, 2);
+        assert!((map[0].mean_score - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn group_mode_uses_group_ids() {
+        let map = risk_map(&result(), RiskMapBy::Group);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[0].group_id, Some(0));
+        assert_eq!(map[0].key, "core");
+        assert_eq!(map[1].group_id, Some(1));
+        assert_eq!(map[1].entity_count, 2);
+        assert_eq!(map[1].high, 1);
+    }
+
+    #[test]
+    fn directory_key_normalizes_separators() {
+        assert_eq!(directory_key("a\\b\\c.rs", 5), "a/b");
+        assert_eq!(directory_key("./a/b.rs", 1), "a");
+        assert_eq!(directory_key("top.rs", 2), ".");
+    }
+
+    #[test]
+    fn parse_group_by() {
+        assert_eq!(RiskMapBy::parse("directory", None), Ok(RiskMapBy::Directory { depth: 1 }));
+        assert_eq!(RiskMapBy::parse("directory", Some(3)), Ok(RiskMapBy::Directory { depth: 3 }));
+        assert!(RiskMapBy::parse("module", None).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::Path;
+use std::process::Command;
+
+use sem_core::git::types::DiffScope;
+
+/// A parsed diff target, before anything is resolved against a repo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// Uncommitted changes (`working`).
+    Working,
+    /// Index changes against HEAD (`staged`).
+    Staged,
+    /// A single commit against its parent.
+    Commit(String),
+    /// Two-dot range: the tree at `from` against the tree at `to`.
+    Range { from: String, to: String },
+    /// Three-dot range: the merge base of `from` and `to` against `to`.
+    MergeBase { from: String, to: String },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScopeError {
+    #[error("target must not be empty")]
+    Empty,
+    #[error("malformed range '{0}'; expected 'from..to' or 'from...to'")]
+    Malformed(String),
+    #[error("no merge base between '{from}' and '{to}': {reason}")]
+    MergeBase {
+        from: String,
+        to: String,
+        reason: String,
+    },
+}
+
+/// Parse a target string: `working`, `staged`, a commit ref, `from..to`, or
+/// `from...to`.
+pub fn parse_target(target: &str) -> Result<Target, ScopeError> {
+    match target {
+        "" => return Err(ScopeError::Empty),
+        "working" => return Ok(Target::Working),
+        "staged" => return Ok(Target::Staged),
+        _ => {}
+    }
+
+    let (from, to, three_dot) = match target.split_once("...") {
+        Some((from, to)) => (from, to, true),
+        None => match target.split_once("..") {
+            Some((from, to)) => (from, to, false),
+            None => return Ok(Target::Commit(target.to_string())),
+        },
+    };
+    if from.is_empty() || to.is_empty() || from.contains("..") || to.starts_with('.') || to.contains("..") {
+        return Err(ScopeError::Malformed(target.to_string()));
+    }
+
+    let (from, to) = (from.to_string(), to.to_string());
+    Ok(if three_dot {
+        Target::MergeBase { from, to }
+    } else {
+        Target::Range { from, to }
+    })
+}
+
+impl Target {
+    /// Turn the target into a diff scope, resolving the merge base for
+    /// three-dot ranges.
+    pub fn resolve(self, repo_path: &Path) -> Result<DiffScope, ScopeError> {
+        Ok(match self {
+            Target::Working => DiffScope::Working,
+            Target::Staged => DiffScope::Staged,
+            Target::Commit(sha) => DiffScope::Commit { sha },
+            Target::Range { from, to } => DiffScope::Range { from, to },
+            Target::MergeBase { from, to } => {
+                let base = merge_base(repo_path, &from, &to)?;
+                DiffScope::Range { from: base, to }
+            }
+        })
+    }
+}
+
+/// Parse a target and resolve it against the repo.
+pub fn parse_scope(repo_path: &Path, target: &str) -> Result<DiffScope, ScopeError> {
+    parse_target(target)?.resolve(repo_path)
+}
+
+fn merge_base(repo_path: &Path, from: &str, to: &str) -> Result<String, ScopeError> {
+    let err = |reason: String| ScopeError::MergeBase {
+        from: from.to_string(),
+        to: to.to_string(),
+        reason,
+    };
+    let output = Command::new("git")
+        .args(["merge-base", from, to])
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| err(format!("failed to run git merge-base: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(err(if stderr.is_empty() {
+            "histories are unrelated".to_string()
+        } else {
+            stderr
+        }));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn range(from: &str, to: &str) -> Target {
+        Target::Range {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    #[test]
+    fn parses_refs_and_ranges() {
+        assert_eq!(parse_target("working").unwrap(), Target::Working);
+        assert_eq!(parse_target("staged").unwrap(), Target::Staged);
+        assert_eq!(parse_target("HEAD~1").unwrap(), Target::Commit("HEAD~1".into()));
+        assert_eq!(parse_target("main..feature").unwrap(), range("main", "feature"));
+        assert_eq!(
+            parse_target("main...feature").unwrap(),
+            Target::MergeBase {
+                from: "main".into(),
+                to: "feature".into()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_targets() {
+        assert!(matches!(parse_target(""), Err(ScopeError::Empty)));
+        for bad in ["main..", "..main", "a..b..c", "main...", "...main", "a...b..c", "a..b...c", "a....b"] {
+            assert!(
+                matches!(parse_target(bad), Err(ScopeError::Malformed(_))),
+                "target {:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn three_dot_resolves_to_merge_base() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        let git = |args: &[&str]| {
+            let out = Command::new("git").args(args).current_dir(dir).output().unwrap();
+            String::from_utf8_lossy(&out.stdout).trim().to_string()
+        };
+        git(&["init", "-b", "main"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["commit", "--allow-empty", "-m", "base"]);
+        let base = git(&["rev-parse", "HEAD"]);
+        git(&["checkout", "-b", "feature"]);
+        git(&["commit", "--allow-empty", "-m", "feature work"]);
+        git(&["checkout", "main"]);
+        git(&["commit", "--allow-empty", "-m", "main moves on"]);
+
+        match parse_scope(dir, "main...feature").unwrap() {
+            DiffScope::Range { from, to } => {
+                assert_eq!(from, base);
+                assert_eq!(to, "feature");
+            }
+            other => panic!("expected a range, got {:?}", other),
+        }
+        assert!(matches!(
+            parse_scope(dir, "main...nope"),
+            Err(ScopeError::MergeBase { .. })
+        ));
+    }
+}
@@ -1,5 +1,26 @@
+use std::collections::HashSet;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::{Hir, HirKind};
 use serde::Serialize;
 
+use crate::github::CodeSearchResponse;
+use crate::noise::is_noise_file;
+use crate::types::EntityReview;
+
+/// Where a match was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchSource {
+    /// A file changed in the PR, searched at head
+    Pr,
+    /// GitHub Code Search over the default branch
+    Codebase,
+    /// A file changed in a local diff target, searched at its post-change state
+    Local,
+}
+
 #[derive(Debug, Serialize)]
 pub struct SearchMatch {
     pub file: String,
@@ -8,39 +29,137 @@ pub struct SearchMatch {
     pub text: String,
     pub context_before: Vec<String>,
     pub context_after: Vec<String>,
+    pub source: MatchSource,
+}
+
+/// Include/exclude globs over file paths. With no include globs every path
+/// is included; exclude globs always win. `*` doesn't cross `/`, so use
+/// `**/*.ts` for any depth.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    include: Vec<String>,
+    include_set: Option<GlobSet>,
+    exclude_set: Option<GlobSet>,
+}
+
+impl FileFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: include.to_vec(),
+            include_set: glob_set(include)?,
+            exclude_set: glob_set(exclude)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include_set.is_none() && self.exclude_set.is_none()
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.include_set.as_ref().is_none_or(|set| set.is_match(path))
+            && !self.exclude_set.as_ref().is_some_and(|set| set.is_match(path))
+    }
+
+    /// Directory for a Code Search `path:` qualifier that covers every
+    /// included path: the literal leading directories of a single include
+    /// glob (`src/**/*.ts` → `src`). None when there isn't one; results are
+    /// filtered with [`FileFilter::matches`] either way.
+    pub fn code_search_path(&self) -> Option<String> {
+        let [glob] = self.include.as_slice() else {
+            return None;
+        };
+        let components: Vec<&str> = glob.split('/').collect();
+        let dirs: Vec<&str> = components[..components.len() - 1]
+            .iter()
+            .take_while(|c| !c.is_empty() && !c.contains(['*', '?', '[', '{', '\\']))
+            .copied()
+            .collect();
+        if dirs.is_empty() {
+            None
+        } else {
+            Some(dirs.join("/"))
+        }
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        set.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+    }
+    set.build().map(Some)
+}
+
+/// Compile a search pattern. Unless `regex` is set the pattern is matched literally.
+pub fn build_matcher(pattern: &str, case_sensitive: bool, regex: bool) -> Result<Regex, regex::Error> {
+    let source = if regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(!case_sensitive)
+        .build()
+}
+
+/// Shortest literal worth sending to Code Search for a regex.
+const MIN_QUERY_LITERAL: usize = 3;
+
+/// What to ask GitHub Code Search for, which matches words rather than
+/// regexes: a literal pattern as is, and for a regex the longest literal run
+/// every match must contain (`fn\s+parse_\w+` → `parse_`). None when a regex
+/// has no such run of at least 3 characters. Results are checked against the
+/// full pattern either way.
+pub fn code_search_query(pattern: &str, regex: bool) -> Option<String> {
+    if !regex {
+        return Some(pattern.to_string());
+    }
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    let mut runs = Vec::new();
+    required_literals(&hir, &mut runs);
+    runs.into_iter()
+        .map(str::trim)
+        .filter(|run| run.chars().count() >= MIN_QUERY_LITERAL)
+        .reduce(|best, run| if run.chars().count() > best.chars().count() { run } else { best })
+        .map(str::to_string)
+}
+
+/// Literal runs in `hir` that any match contains: the literals of the
+/// top-level sequence, looking through groups but not repetitions or
+/// alternations.
+fn required_literals<'a>(hir: &'a Hir, runs: &mut Vec<&'a str>) {
+    match hir.kind() {
+        HirKind::Literal(literal) => runs.extend(std::str::from_utf8(&literal.0).ok()),
+        HirKind::Concat(parts) => parts.iter().for_each(|part| required_literals(part, runs)),
+        HirKind::Capture(capture) => required_literals(&capture.sub, runs),
+        _ => {}
+    }
 }
 
 pub fn grep_files(
     files: &[(String, String)],
-    pattern: &str,
-    case_sensitive: bool,
+    matcher: &Regex,
     context_lines: usize,
 ) -> Vec<SearchMatch> {
-    let pattern_lower = if case_sensitive {
-        pattern.to_string()
-    } else {
-        pattern.to_lowercase()
-    };
     let mut matches = Vec::new();
 
     for (filepath, content) in files {
         let lines: Vec<&str> = content.lines().collect();
         for (i, line) in lines.iter().enumerate() {
-            let haystack = if case_sensitive {
-                line.to_string()
-            } else {
-                line.to_lowercase()
-            };
-            if haystack.contains(&pattern_lower) {
+            if let Some(m) = matcher.find(line) {
                 let start = i.saturating_sub(context_lines);
                 let end = (i + context_lines + 1).min(lines.len());
                 matches.push(SearchMatch {
                     file: filepath.clone(),
                     line: i + 1,
-                    column: haystack.find(&pattern_lower).unwrap_or(0) + 1,
+                    column: m.start() + 1,
                     text: line.to_string(),
                     context_before: lines[start..i].iter().map(|s| s.to_string()).collect(),
                     context_after: lines[i + 1..end].iter().map(|s| s.to_string()).collect(),
+                    source: MatchSource::Pr,
                 });
             }
         }
@@ -48,6 +167,99 @@ pub fn grep_files(
     matches
 }
 
+/// Search only the post-change content of changed entities. Line numbers
+/// are file lines; context stays within the entity. Noise files are skipped.
+pub fn grep_entities(reviews: &[EntityReview], matcher: &Regex, context_lines: usize) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+
+    for review in reviews {
+        let Some(content) = &review.after_content else {
+            continue;
+        };
+        if is_noise_file(&review.file_path) {
+            continue;
+        }
+        let entity = [(review.file_path.clone(), content.clone())];
+        for mut m in grep_files(&entity, matcher, context_lines) {
+            m.line += review.start_line.saturating_sub(1);
+            matches.push(m);
+        }
+    }
+    matches.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+    // Nested entities (a method and its impl) can both hold a line
+    matches.dedup_by(|a, b| a.file == b.file && a.line == b.line && a.column == b.column);
+    matches
+}
+
+/// The innermost changed entity whose post-change line range covers `line`.
+/// Deleted entities are skipped: their ranges refer to the old file.
+pub fn enclosing_entity<'a>(reviews: &'a [EntityReview], file: &str, line: usize) -> Option<&'a EntityReview> {
+    reviews
+        .iter()
+        .filter(|r| r.file_path == file && r.after_content.is_some())
+        .filter(|r| r.start_line <= line && line <= r.end_line)
+        .min_by_key(|r| r.end_line - r.start_line)
+}
+
+/// Turn GitHub Code Search text matches into `SearchMatch`es, skipping noise
+/// files, paths `filter` rejects, and any path in `exclude` (typically the
+/// PR's own files, which were already searched at head). Line numbers are
+/// relative to the fragment.
+pub fn code_search_matches(
+    response: &CodeSearchResponse,
+    matcher: &Regex,
+    exclude: &HashSet<&str>,
+    filter: &FileFilter,
+) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+
+    for item in &response.items {
+        if exclude.contains(item.path.as_str()) || is_noise_file(&item.path) || !filter.matches(&item.path) {
+            continue;
+        }
+        let Some(text_matches) = &item.text_matches else {
+            continue;
+        };
+        for tm in text_matches {
+            for (line_idx, line) in tm.fragment.lines().enumerate() {
+                if let Some(m) = matcher.find(line) {
+                    matches.push(SearchMatch {
+                        file: item.path.clone(),
+                        line: line_idx + 1,
+                        column: m.start() + 1,
+                        text: line.to_string(),
+                        context_before: vec![],
+                        context_after: vec![],
+                        source: MatchSource::Codebase,
+                    });
+                }
+            }
+        }
+    }
+    matches
+}
+
+/// PR matches followed by Code Search matches from outside the PR: files in
+/// `pr_paths` were already searched at head, so their default-branch copies
+/// are skipped, as are noise files and paths `filter` rejects. Overlapping
+/// fragments can quote a line more than once; each file's line is kept once.
+pub fn merge_code_search(
+    mut pr_matches: Vec<SearchMatch>,
+    response: &CodeSearchResponse,
+    matcher: &Regex,
+    pr_paths: &[String],
+    filter: &FileFilter,
+) -> Vec<SearchMatch> {
+    let exclude: HashSet<&str> = pr_paths.iter().map(|s| s.as_str()).collect();
+    let mut seen = HashSet::new();
+    for m in code_search_matches(response, matcher, &exclude, filter) {
+        if seen.insert((m.file.clone(), m.text.clone())) {
+            pr_matches.push(m);
+        }
+    }
+    pr_matches
+}
+
 pub fn format_matches(matches: &[SearchMatch]) -> String {
     if matches.is_empty() {
         return "No matches found.".to_string();
@@ -89,3 +301,245 @@ pub fn format_matches(matches: &[SearchMatch]) -> String {
 
     lines.join("\n")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<(String, String)> {
+        vec![(
+            "src/lib.rs".to_string(),
+            "fn main() {\n    let Token = parse();\n    token.check();\n}".to_string(),
+        )]
+    }
+
+    #[test]
+    fn literal_search_ignores_case_by_default() {
+        let matcher = build_matcher("token", false, false).unwrap();
+        let matches = grep_files(&files(), &matcher, 1);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].column, 9);
+        assert_eq!(matches[0].context_before, vec!["fn main() {"]);
+        assert_eq!(matches[1].source, MatchSource::Pr);
+    }
+
+    #[test]
+    fn literal_search_escapes_regex_syntax() {
+        let matcher = build_matcher("check()", true, false).unwrap();
+        let matches = grep_files(&files(), &matcher, 0);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 3);
+    }
+
+    #[test]
+    fn regex_search() {
+        let matcher = build_matcher(r"let \w+ =", true, true).unwrap();
+        let matches = grep_files(&files(), &matcher, 0);
+        assert_eq!(matches.len(), 1);
+        assert!(build_matcher("(", true, true).is_err());
+    }
+
+    #[test]
+    fn code_search_gets_a_literal_from_regexes() {
+        assert_eq!(code_search_query("a.b(", false).as_deref(), Some("a.b("));
+        assert_eq!(code_search_query(r"fn\s+parse_\w+", true).as_deref(), Some("parse_"));
+        assert_eq!(code_search_query(r"(fetch|load)_user\(\)", true).as_deref(), Some("_user()"));
+        assert_eq!(code_search_query(r"\bTODO: (\w+)", true).as_deref(), Some("TODO:"));
+        assert_eq!(code_search_query("colou?r_map", true).as_deref(), Some("r_map"));
+        // Nothing every match must contain, or too short to search for
+        assert_eq!(code_search_query("foo|bar", true), None);
+        assert_eq!(code_search_query(r"\d+px", true), None);
+        assert_eq!(code_search_query("(", true), None);
+    }
+
+    #[test]
+    fn code_search_skips_pr_and_noise_files() {
+        let response: CodeSearchResponse = serde_json::from_value(serde_json::json!({
+            "total_count": 3,
+            "items": [
+                {
+                    "name": "lib.rs", "path": "src/lib.rs", "html_url": "",
+                    "repository": {"full_name": "o/r"},
+                    "text_matches": [{"fragment": "token", "matches": []}]
+                },
+                {
+                    "name": "app.min.js", "path": "dist/app.min.js", "html_url": "",
+                    "repository": {"full_name": "o/r"},
+                    "text_matches": [{"fragment": "token", "matches": []}]
+                },
+                {
+                    "name": "auth.rs", "path": "src/auth.rs", "html_url": "",
+                    "repository": {"full_name": "o/r"},
+                    "text_matches": [{"fragment": "use x;\nlet token = 1;", "matches": []}]
+                }
+            ]
+        }))
+        .unwrap();
+
+        let matcher = build_matcher("TOKEN", false, false).unwrap();
+        let exclude: HashSet<&str> = ["src/lib.rs"].into_iter().collect();
+        let matches = code_search_matches(&response, &matcher, &exclude, &FileFilter::default());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].file, "src/auth.rs");
+        assert_eq!(matches[0].line, 2);
+        assert_eq!(matches[0].source, MatchSource::Codebase);
+
+        let filter = FileFilter::new(&[], &["**/auth.rs".into()]).unwrap();
+        assert!(code_search_matches(&response, &matcher, &exclude, &filter).is_empty());
+    }
+
+    #[test]
+    fn code_search_merges_after_pr_matches_once_per_line() {
+        let response: CodeSearchResponse = serde_json::from_value(serde_json::json!({
+            "total_count": 2,
+            "items": [
+                {
+                    "name": "lib.rs", "path": "src/lib.rs", "html_url": "",
+                    "repository": {"full_name": "o/r"},
+                    "text_matches": [{"fragment": "let token = old();", "matches": []}]
+                },
+                {
+                    "name": "auth.rs", "path": "src/auth.rs", "html_url": "",
+                    "repository": {"full_name": "o/r"},
+                    "text_matches": [
+                        {"fragment": "fn check() {\n    token.verify();", "matches": []},
+                        {"fragment": "    token.verify();\n}\nfn refresh(token: T) {}", "matches": []}
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+
+        let matcher = build_matcher("token", false, false).unwrap();
+        let pr_matches = grep_files(&files(), &matcher, 0);
+        let merged = merge_code_search(pr_matches, &response, &matcher, &["src/lib.rs".into()], &FileFilter::default());
+        let found: Vec<_> = merged.iter().map(|m| (m.file.as_str(), m.text.trim(), m.source)).collect();
+        assert_eq!(
+            found,
+            [
+                ("src/lib.rs", "let Token = parse();", MatchSource::Pr),
+                ("src/lib.rs", "token.check();", MatchSource::Pr),
+                ("src/auth.rs", "token.verify();", MatchSource::Codebase),
+                ("src/auth.rs", "fn refresh(token: T) {}", MatchSource::Codebase),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_filter_includes_and_excludes() {
+        let filter = FileFilter::new(&["src/**/*.ts".into()], &["**/*.test.ts".into()]).unwrap();
+        assert!(filter.matches("src/app.ts"));
+        assert!(filter.matches("src/api/client.ts"));
+        assert!(!filter.matches("src/api/client.test.ts"));
+        assert!(!filter.matches("lib/app.ts"));
+        assert!(!filter.matches("src/app.tsx"));
+
+        let empty = FileFilter::default();
+        assert!(empty.is_empty());
+        assert!(empty.matches("anything/at/all.rs"));
+
+        // Excludes alone keep everything else
+        let filter = FileFilter::new(&[], &["vendor/**".into()]).unwrap();
+        assert!(filter.matches("src/main.rs"));
+        assert!(!filter.matches("vendor/lib/x.rs"));
+
+        // `*` stays within one directory
+        let filter = FileFilter::new(&["*.rs".into()], &[]).unwrap();
+        assert!(filter.matches("main.rs"));
+        assert!(!filter.matches("src/main.rs"));
+
+        assert!(FileFilter::new(&["src/[".into()], &[]).is_err());
+    }
+
+    #[test]
+    fn file_filter_code_search_path() {
+        let path = |include: &[&str]| {
+            let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+            FileFilter::new(&include, &[]).unwrap().code_search_path()
+        };
+        assert_eq!(path(&["src/**/*.ts"]).as_deref(), Some("src"));
+        assert_eq!(path(&["packages/web/src/*.tsx"]).as_deref(), Some("packages/web/src"));
+        assert_eq!(path(&["src/lib.rs"]).as_deref(), Some("src"));
+        assert_eq!(path(&["**/*.ts"]), None);
+        assert_eq!(path(&["*.ts"]), None);
+        assert_eq!(path(&["src/*/api/*.ts"]).as_deref(), Some("src"));
+        // Qualifiers would AND, so several includes get no qualifier
+        assert_eq!(path(&["src/**", "lib/**"]), None);
+        assert_eq!(path(&[]), None);
+    }
+
+    fn entity(name: &str, file: &str, start: usize, content: &str) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file, name),
+            entity_name: name.into(),
+            file_path: file.into(),
+            risk_score: 0.3,
+            risk_level: crate::types::RiskLevel::Medium,
+            start_line: start,
+            end_line: start + content.lines().count() - 1,
+            after_content: Some(content.into()),
+            ..EntityReview::test_default()
+        }
+    }
+
+    #[test]
+    fn entity_search_uses_file_line_numbers() {
+        let reviews = vec![
+            entity("outer", "src/lib.rs", 10, "impl A {\n    fn inner() {\n        token();\n    }\n}"),
+            entity("inner", "src/lib.rs", 11, "    fn inner() {\n        token();\n    }"),
+            entity("lock", "Cargo.lock", 1, "token"),
+        ];
+        let matcher = build_matcher("token", false, false).unwrap();
+        let matches = grep_entities(&reviews, &matcher, 0);
+        assert_eq!(matches.len(), 1);
+        assert_eq!((matches[0].file.as_str(), matches[0].line), ("src/lib.rs", 12));
+        assert_eq!(enclosing_entity(&reviews, "src/lib.rs", 12).unwrap().entity_name, "inner");
+        assert_eq!(enclosing_entity(&reviews, "src/lib.rs", 10).unwrap().entity_name, "outer");
+        assert!(enclosing_entity(&reviews, "src/lib.rs", 20).is_none());
+    }
+
+    #[test]
+    fn local_search_over_temp_repo() {
+        use crate::analyze::analyze;
+        use crate::files::changed_file_contents;
+        use sem_core::git::types::DiffScope;
+        use std::process::Command;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let git = |args: &[&str]| {
+            Command::new("git").args(args).current_dir(dir).output().unwrap();
+        };
+        git(&["init"]);
+        git(&["config", "user.email", "test@test.com"]);
+        git(&["config", "user.name", "Test"]);
+        std::fs::write(dir.join("lib.rs"), "// token outside\nfn a() {}\n").unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-m", "init"]);
+
+        std::fs::write(
+            dir.join("lib.rs"),
+            "// token outside\nfn a() {}\n\nfn b() {\n    let token = 1;\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("Cargo.lock"), "token\n").unwrap();
+
+        let result = analyze(dir, DiffScope::Working).unwrap();
+        let files = changed_file_contents(dir, &DiffScope::Working).unwrap();
+        assert!(files.iter().all(|(path, _)| path != "Cargo.lock"));
+
+        let matcher = build_matcher("token", false, false).unwrap();
+        let matches = grep_files(&files, &matcher, 0);
+        assert_eq!(matches.len(), 2);
+        let owners: Vec<Option<&str>> = matches
+            .iter()
+            .map(|m| enclosing_entity(&result.entity_reviews, &m.file, m.line).map(|r| r.entity_name.as_str()))
+            .collect();
+        assert_eq!(owners, vec![None, Some("b")]);
+
+        let changed = grep_entities(&result.entity_reviews, &matcher, 0);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].line, 5);
+    }
+}
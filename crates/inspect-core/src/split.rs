@@ -0,0 +1,462 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::process::Command;
+
+use sem_core::git::types::DiffScope;
+use sem_core::model::change::ChangeType;
+use serde::Serialize;
+
+use crate::analyze::AnalyzeError;
+use crate::patch::parse_patch;
+use crate::types::{ChangeClassification, ChangeGroup, EntityReview, ReviewResult};
+
+/// One change group's share of a diff, ready to commit on its own.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupPatch {
+    pub group_id: usize,
+    pub label: String,
+    /// Suggested commit subject, seeded from the label
+    pub message: String,
+    /// Files the group's entities live in, with their line spans
+    pub files: Vec<FileSpans>,
+    /// Headers and hunks for this group only; empty when no hunk was
+    /// assigned to it
+    pub patch: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSpans {
+    pub file_path: String,
+    /// Merged `(start, end)` entity spans, in order
+    pub ranges: Vec<(usize, usize)>,
+}
+
+/// A diff split by change group.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SplitPlan {
+    /// In group order
+    pub groups: Vec<GroupPatch>,
+    /// Hunks in files without any changed entity (configs, docs, ...)
+    pub ungrouped: String,
+    /// Hunks shared between groups, and where they went
+    pub warnings: Vec<String>,
+}
+
+/// The diff git reports for `scope`, with binary changes and renames in a
+/// form `git apply` accepts.
+pub fn scope_diff(repo_path: &Path, scope: &DiffScope) -> Result<String, AnalyzeError> {
+    let mut args = match scope {
+        DiffScope::Working => vec!["diff".to_string(), "HEAD".to_string()],
+        DiffScope::Staged => vec!["diff".to_string(), "--cached".to_string()],
+        // diff-tree handles root commits, and diffs merges against their first parent
+        DiffScope::Commit { sha } => ["diff-tree", "-p", "--root", "-m", "--first-parent", "--no-commit-id", sha]
+            .map(String::from)
+            .to_vec(),
+        DiffScope::Range { from, to } => vec!["diff".to_string(), from.clone(), to.clone()],
+    };
+    args.extend(["-M", "--binary", "--no-color", "--no-ext-diff"].map(String::from));
+
+    let output = Command::new("git")
+        .args(&args)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| AnalyzeError::Git(format!("failed to run git {}: {}", args[0], e)))?;
+    if !output.status.success() {
+        return Err(AnalyzeError::Git(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// One file's part of a diff, kept verbatim.
+struct FileDiff {
+    header: String,
+    old_path: String,
+    new_path: String,
+    hunks: Vec<String>,
+}
+
+/// Split `diff` into one patch per group of `result`. Each hunk goes to the
+/// group whose entities it touches; a hunk between entities goes to the
+/// nearest one in its file, and a hunk touching several groups goes to the
+/// largest of them, with a warning.
+pub fn split_patch(diff: &str, result: &ReviewResult) -> SplitPlan {
+    let size: HashMap<usize, usize> = result.groups.iter().map(|g| (g.id, g.entity_ids.len())).collect();
+    let largest = |ids: &[usize]| {
+        ids.iter()
+            .copied()
+            .max_by_key(|id| (size.get(id).copied().unwrap_or(0), std::cmp::Reverse(*id)))
+    };
+
+    let mut plan = SplitPlan::default();
+    // group id -> that group's patch text
+    let mut patches: BTreeMap<usize, String> = BTreeMap::new();
+    let mut ungrouped = String::new();
+
+    for file in split_files(diff) {
+        let entities: Vec<&EntityReview> = result
+            .entity_reviews
+            .iter()
+            .filter(|r| r.file_path == file.new_path || r.file_path == file.old_path)
+            .collect();
+
+        // Renames, mode changes and binary files have no hunks
+        let mut owned: BTreeMap<Option<usize>, Vec<&str>> = BTreeMap::new();
+        if file.hunks.is_empty() {
+            let mut ids: Vec<usize> = entities.iter().map(|r| r.group_id).collect();
+            ids.sort();
+            ids.dedup();
+            owned.insert(largest(&ids), Vec::new());
+        }
+        for hunk in &file.hunks {
+            let mut ids = touched_groups(hunk, &entities);
+            ids.sort();
+            ids.dedup();
+            let owner = largest(&ids);
+            if ids.len() > 1 {
+                let header = hunk.lines().next().unwrap_or_default();
+                plan.warnings.push(format!(
+                    "{} {} touches groups {}; kept with group {}",
+                    file.new_path,
+                    header,
+                    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", "),
+                    owner.unwrap_or_default()
+                ));
+            }
+            owned.entry(owner).or_default().push(hunk);
+        }
+
+        for (owner, hunks) in owned {
+            let out = match owner {
+                Some(id) => patches.entry(id).or_default(),
+                None => &mut ungrouped,
+            };
+            out.push_str(&file.header);
+            for hunk in hunks {
+                out.push_str(hunk);
+            }
+        }
+    }
+
+    plan.groups = result
+        .groups
+        .iter()
+        .map(|group| {
+            let members: Vec<&EntityReview> = result
+                .entity_reviews
+                .iter()
+                .filter(|r| group.entity_ids.contains(&r.entity_id))
+                .collect();
+            GroupPatch {
+                group_id: group.id,
+                label: group.label.clone(),
+                message: commit_message(group, &members),
+                files: file_spans(&members),
+                patch: patches.remove(&group.id).unwrap_or_default(),
+            }
+        })
+        .collect();
+    plan.ungrouped = ungrouped;
+    plan
+}
+
+/// Groups of the entities a hunk's changed lines fall in, or of the nearest
+/// entity when they fall in none. Empty when the file has no entities.
+fn touched_groups(hunk: &str, entities: &[&EntityReview]) -> Vec<usize> {
+    let Some(parsed) = parse_patch(hunk).into_iter().next() else {
+        return Vec::new();
+    };
+    // Where each change sits on either side; a deletion sits before the next
+    // new-file line, an insertion before the next old-file line
+    let (mut old_pos, mut new_pos) = (parsed.old_start.max(1), parsed.new_start.max(1));
+    let (mut old_touched, mut new_touched) = (Vec::new(), Vec::new());
+    for line in &parsed.lines {
+        match line.kind.as_str() {
+            "add" => {
+                new_touched.push(new_pos);
+                old_touched.push(old_pos);
+                new_pos += 1;
+            }
+            "delete" => {
+                old_touched.push(old_pos);
+                new_touched.push(new_pos);
+                old_pos += 1;
+            }
+            _ => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+        }
+    }
+
+    // Deleted entities' spans are in the old file, the rest in the new one
+    let distance = |r: &EntityReview| {
+        let touched = if r.change_type == ChangeType::Deleted {
+            &old_touched
+        } else {
+            &new_touched
+        };
+        let (start, end) = (r.start_line as u64, r.end_line as u64);
+        touched
+            .iter()
+            .map(|&l| if l < start { start - l } else { l.saturating_sub(end) })
+            .min()
+            .unwrap_or(u64::MAX)
+    };
+
+    let inside: Vec<usize> = entities.iter().filter(|r| distance(r) == 0).map(|r| r.group_id).collect();
+    if !inside.is_empty() {
+        return inside;
+    }
+    entities.iter().min_by_key(|r| distance(r)).map(|r| vec![r.group_id]).unwrap_or_default()
+}
+
+/// Break a multi-file diff into per-file headers and hunks.
+fn split_files(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            let (old_path, new_path) = git_header_paths(line.trim_end());
+            files.push(FileDiff {
+                header: line.to_string(),
+                old_path,
+                new_path,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            file.hunks.push(line.to_string());
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.push_str(line);
+        } else {
+            let line_path = |prefix: &str| {
+                let path = line.strip_prefix(prefix)?.trim_end();
+                (path != "/dev/null").then(|| path.trim_start_matches("a/").trim_start_matches("b/").to_string())
+            };
+            if let Some(path) = line_path("--- ").or_else(|| line_path("rename from ")) {
+                file.old_path = path;
+            } else if let Some(path) = line_path("+++ ").or_else(|| line_path("rename to ")) {
+                file.new_path = path;
+            }
+            file.header.push_str(line);
+        }
+    }
+    files
+}
+
+/// Paths from `diff --git a/old b/new`; exact unless a path contains " b/".
+fn git_header_paths(line: &str) -> (String, String) {
+    let paths = line.trim_start_matches("diff --git ");
+    match paths.split_once(" b/") {
+        Some((old, new)) => (old.trim_start_matches("a/").to_string(), new.to_string()),
+        None => (paths.to_string(), paths.to_string()),
+    }
+}
+
+/// Each file the members live in, with their spans merged.
+fn file_spans(members: &[&EntityReview]) -> Vec<FileSpans> {
+    let mut by_file: BTreeMap<&str, Vec<(usize, usize)>> = BTreeMap::new();
+    for r in members {
+        by_file.entry(&r.file_path).or_default().push((r.start_line, r.end_line));
+    }
+    by_file
+        .into_iter()
+        .map(|(file_path, mut spans)| {
+            spans.sort();
+            let mut ranges: Vec<(usize, usize)> = Vec::new();
+            for (start, end) in spans {
+                match ranges.last_mut() {
+                    Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                    _ => ranges.push((start, end)),
+                }
+            }
+            FileSpans {
+                file_path: file_path.to_string(),
+                ranges,
+            }
+        })
+        .collect()
+}
+
+/// A commit subject for a group: its label, with a verb from what changed.
+fn commit_message(group: &ChangeGroup, members: &[&EntityReview]) -> String {
+    let all = |change_type| !members.is_empty() && members.iter().all(|r| r.change_type == change_type);
+    let verb = if all(ChangeType::Added) {
+        "Add"
+    } else if all(ChangeType::Deleted) {
+        "Remove"
+    } else if group.dominant_classification == ChangeClassification::Text {
+        "Document"
+    } else {
+        "Update"
+    };
+    format!("{} {}", verb, group.label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::compute_stats;
+    use crate::types::RiskLevel;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let out = Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    }
+
+    fn entity(name: &str, file: &str, lines: (usize, usize), group_id: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("{}::{}", file, name),
+            entity_name: name.into(),
+            file_path: file.into(),
+            risk_score: 0.5,
+            risk_level: RiskLevel::Medium,
+            group_id,
+            start_line: lines.0,
+            end_line: lines.1,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn result(entity_reviews: Vec<EntityReview>) -> ReviewResult {
+        let mut groups: Vec<ChangeGroup> = Vec::new();
+        for r in &entity_reviews {
+            if groups.len() <= r.group_id {
+                groups.resize_with(r.group_id + 1, || ChangeGroup {
+                    id: 0,
+                    label: String::new(),
+                    entity_ids: vec![],
+                    dominant_classification: ChangeClassification::Functional,
+                    max_risk: RiskLevel::Medium,
+                    max_score: 0.5,
+                    has_public_api: false,
+                });
+            }
+            let group = &mut groups[r.group_id];
+            group.id = r.group_id;
+            group.label = format!("group{}", r.group_id);
+            group.entity_ids.push(r.entity_id.clone());
+        }
+        ReviewResult {
+            stats: compute_stats(&entity_reviews),
+            entity_reviews,
+            groups,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Default::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    fn two_functions(one: &str, two: &str) -> String {
+        let filler: String = (0..10).map(|i| format!("// filler {}\n", i)).collect();
+        format!(
+            "fn one() -> i32 {{\n    {}\n}}\n\n{}fn two() -> i32 {{\n    {}\n}}\n",
+            one, filler, two
+        )
+    }
+
+    #[test]
+    fn group_patches_apply_on_their_own_and_together() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        git(dir, &["init", "-b", "main"]);
+        git(dir, &["config", "user.email", "test@test.com"]);
+        git(dir, &["config", "user.name", "Test"]);
+        std::fs::write(dir.join("a.rs"), two_functions("1", "2")).unwrap();
+        std::fs::write(dir.join("b.rs"), "fn three() -> i32 {\n    3\n}\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "draft\n").unwrap();
+        git(dir, &["add", "-A"]);
+        git(dir, &["commit", "-m", "base"]);
+        std::fs::write(dir.join("a.rs"), two_functions("10", "20")).unwrap();
+        std::fs::write(dir.join("b.rs"), "fn three() -> i32 {\n    30\n}\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "final\n").unwrap();
+        git(dir, &["add", "-A"]);
+        git(dir, &["commit", "-m", "tangled"]);
+
+        // one and three belong together; two stands alone
+        let result = result(vec![
+            entity("one", "a.rs", (1, 3), 0),
+            entity("three", "b.rs", (1, 3), 0),
+            entity("two", "a.rs", (15, 17), 1),
+        ]);
+        let diff = scope_diff(dir, &DiffScope::Commit { sha: "HEAD".into() }).unwrap();
+        let plan = split_patch(&diff, &result);
+
+        assert!(plan.warnings.is_empty(), "{:?}", plan.warnings);
+        let first = &plan.groups[0].patch;
+        assert!(first.contains("+    10") && first.contains("+    30") && !first.contains("+    20"));
+        let second = &plan.groups[1].patch;
+        assert!(second.contains("+    20") && !second.contains("+    10"));
+        assert!(plan.ungrouped.contains("+final"));
+        assert_eq!(plan.groups[0].message, "Update group0");
+        let files: Vec<_> = plan.groups[0].files.iter().map(|f| f.file_path.as_str()).collect();
+        assert_eq!(files, ["a.rs", "b.rs"]);
+
+        git(dir, &["checkout", "-q", "-b", "split", "HEAD~1"]);
+        let patches = [first, second, &plan.ungrouped];
+        for (i, patch) in patches.iter().enumerate() {
+            let path = dir.join(format!("{}.patch", i));
+            std::fs::write(&path, patch).unwrap();
+            // Each applies to the base by itself...
+            git(dir, &["apply", "--check", path.to_str().unwrap()]);
+        }
+        // ...and all of them, in order, rebuild the tangled commit
+        for i in 0..patches.len() {
+            git(dir, &["apply", &format!("{}.patch", i)]);
+            std::fs::remove_file(dir.join(format!("{}.patch", i))).unwrap();
+        }
+        assert_eq!(git(dir, &["diff", "main"]), "");
+    }
+
+    #[test]
+    fn shared_hunks_go_to_the_larger_group() {
+        let diff = "diff --git a/a.rs b/a.rs\n\
+                    --- a/a.rs\n\
+                    +++ b/a.rs\n\
+                    @@ -1,6 +1,6 @@\n \
+                    fn one() {\n\
+                    -    1\n\
+                    +    10\n \
+                    }\n \
+                    fn two() {\n\
+                    -    2\n\
+                    +    20\n \
+                    }\n";
+        let result = result(vec![
+            entity("one", "a.rs", (1, 3), 0),
+            entity("two", "a.rs", (4, 6), 1),
+            entity("three", "b.rs", (1, 3), 1),
+        ]);
+        let plan = split_patch(diff, &result);
+        assert!(plan.groups[0].patch.is_empty());
+        assert!(plan.groups[1].patch.contains("+    10"));
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(plan.warnings[0].contains("touches groups 0, 1; kept with group 1"), "{}", plan.warnings[0]);
+    }
+
+    #[test]
+    fn hunks_between_entities_go_to_the_nearest() {
+        let diff = "diff --git a/a.rs b/a.rs\n\
+                    --- a/a.rs\n\
+                    +++ b/a.rs\n\
+                    @@ -8,2 +8,3 @@\n \
+                    // gap\n\
+                    +use std::fmt;\n \
+                    // gap\n";
+        let result = result(vec![entity("one", "a.rs", (1, 3), 0), entity("two", "a.rs", (11, 14), 1)]);
+        let plan = split_patch(diff, &result);
+        assert!(plan.groups[1].patch.contains("+use std::fmt;"));
+        assert!(plan.ungrouped.is_empty());
+    }
+}
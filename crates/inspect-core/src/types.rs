@@ -3,21 +3,30 @@ use serde::{Deserialize, Serialize};
 
 /// ConGra change classification taxonomy.
 /// Categorizes what dimension(s) of the code changed.
+/// Serialized as its display name ("text+syntax"); the Rust variant names
+/// of schema version 1 still deserialize.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChangeClassification {
     /// Only comments, whitespace, or documentation changed
+    #[serde(rename = "text", alias = "Text")]
     Text,
     /// Only signatures, types, or declarations changed (no logic)
+    #[serde(rename = "syntax", alias = "Syntax")]
     Syntax,
     /// Logic or behavior changed
+    #[serde(rename = "functional", alias = "Functional")]
     Functional,
     /// Comments + signature changes
+    #[serde(rename = "text+syntax", alias = "TextSyntax")]
     TextSyntax,
     /// Comments + logic changes
+    #[serde(rename = "text+functional", alias = "TextFunctional")]
     TextFunctional,
     /// Signature + logic changes
+    #[serde(rename = "syntax+functional", alias = "SyntaxFunctional")]
     SyntaxFunctional,
     /// All three dimensions changed
+    #[serde(rename = "text+syntax+functional", alias = "TextSyntaxFunctional")]
     TextSyntaxFunctional,
 }
 
@@ -35,12 +44,18 @@ impl std::fmt::Display for ChangeClassification {
     }
 }
 
-/// Risk level for a changed entity.
+/// Risk level for a changed entity. Serialized lowercase; the capitalized
+/// names of schema version 1 still deserialize.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RiskLevel {
+    #[serde(alias = "Low")]
     Low,
+    #[serde(alias = "Medium")]
     Medium,
+    #[serde(alias = "High")]
     High,
+    #[serde(alias = "Critical")]
     Critical,
 }
 
@@ -62,6 +77,10 @@ pub struct EntityReview {
     pub entity_name: String,
     pub entity_type: String,
     pub file_path: String,
+    /// Where a Moved or Renamed entity was before, if in another file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_file_path: Option<String>,
+    #[serde(with = "change_type_name")]
     pub change_type: ChangeType,
     pub classification: ChangeClassification,
     pub risk_score: f64,
@@ -70,6 +89,9 @@ pub struct EntityReview {
     pub dependent_count: usize,
     pub dependency_count: usize,
     pub is_public_api: bool,
+    /// In a test file or a Rust `#[cfg(test)]` module
+    #[serde(default)]
+    pub is_test: bool,
     pub structural_change: Option<bool>,
     pub group_id: usize,
     pub start_line: usize,
@@ -81,9 +103,186 @@ pub struct EntityReview {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub after_content: Option<String>,
     /// Entities that depend on this entity: (name, file_path)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub dependent_names: Vec<(String, String)>,
     /// Entities this entity depends on: (name, file_path)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub dependency_names: Vec<(String, String)>,
+    /// Path rule that changed the score or level, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_rule: Option<RuleHit>,
+    /// What the risk score is made of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_components: Option<ScoreComponents>,
+    /// Source of related entities for LLM review; only filled when
+    /// `AnalyzeOptions::context` is set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_snippets: Vec<ContextSnippet>,
+}
+
+impl EntityReview {
+    /// A low-risk functional edit to `f` in src/lib.rs, lines 1-2, with no
+    /// dependents or content: a base for test fixtures to override with
+    /// struct update syntax. Other crates get it with the `test-support`
+    /// feature.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn test_default() -> Self {
+        Self {
+            entity_id: "src/lib.rs::f".into(),
+            entity_name: "f".into(),
+            entity_type: "function".into(),
+            file_path: "src/lib.rs".into(),
+            old_file_path: None,
+            change_type: ChangeType::Modified,
+            classification: ChangeClassification::Functional,
+            risk_score: 0.0,
+            risk_level: RiskLevel::Low,
+            blast_radius: 0,
+            dependent_count: 0,
+            dependency_count: 0,
+            is_public_api: false,
+            is_test: false,
+            structural_change: Some(true),
+            group_id: 0,
+            start_line: 1,
+            end_line: 2,
+            before_content: None,
+            after_content: None,
+            dependent_names: vec![],
+            dependency_names: vec![],
+            risk_rule: None,
+            score_components: None,
+            context_snippets: vec![],
+        }
+    }
+}
+
+/// `ChangeType` as the lowercase name used across inspect's output
+/// ("added", "modified", ...), independent of sem-core's own serde spelling.
+mod change_type_name {
+    use sem_core::model::change::ChangeType;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(change_type: &ChangeType, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match change_type {
+            ChangeType::Added => "added",
+            ChangeType::Modified => "modified",
+            ChangeType::Deleted => "deleted",
+            ChangeType::Moved => "moved",
+            ChangeType::Renamed => "renamed",
+        })
+    }
+
+    /// Case-insensitive, so schema version 1's "Added" still reads.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ChangeType, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.to_lowercase().as_str() {
+            "added" => Ok(ChangeType::Added),
+            "modified" => Ok(ChangeType::Modified),
+            "deleted" => Ok(ChangeType::Deleted),
+            "moved" => Ok(ChangeType::Moved),
+            "renamed" => Ok(ChangeType::Renamed),
+            _ => Err(serde::de::Error::unknown_variant(
+                &name,
+                &["added", "modified", "deleted", "moved", "renamed"],
+            )),
+        }
+    }
+}
+
+/// How a context snippet's entity relates to the reviewed one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relation {
+    /// Depends on the reviewed entity (e.g. a caller)
+    Dependent,
+    /// The reviewed entity depends on it (e.g. a callee)
+    Dependency,
+}
+
+/// Current source of an entity related to a reviewed one, possibly truncated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextSnippet {
+    pub entity_name: String,
+    pub file_path: String,
+    pub relation: Relation,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub content: String,
+    pub truncated: bool,
+}
+
+/// Per-entity risk score contributions. The fields sum to the score before
+/// it is capped at 1.0; discounts are negative.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScoreComponents {
+    pub classification: f64,
+    pub change_type: f64,
+    pub public_api: f64,
+    pub blast_radius: f64,
+    pub dependents: f64,
+    pub cosmetic_discount: f64,
+    pub test_discount: f64,
+    /// Added by a path rule boost
+    pub rule_boost: f64,
+}
+
+impl ScoreComponents {
+    /// Sum of all components, before capping.
+    pub fn total(&self) -> f64 {
+        self.factors().iter().map(|(_, v)| v).sum()
+    }
+
+    /// Every component with a human-readable name.
+    pub fn factors(&self) -> [(&'static str, f64); 8] {
+        [
+            ("classification", self.classification),
+            ("change type", self.change_type),
+            ("public API", self.public_api),
+            ("blast radius", self.blast_radius),
+            ("dependents", self.dependents),
+            ("cosmetic discount", self.cosmetic_discount),
+            ("test discount", self.test_discount),
+            ("rule boost", self.rule_boost),
+        ]
+    }
+
+    /// The `n` largest positive contributions, largest first.
+    pub fn top(&self, n: usize) -> Vec<(&'static str, f64)> {
+        let mut factors: Vec<(&'static str, f64)> = self.factors().into_iter().filter(|(_, v)| *v > 0.0).collect();
+        factors.sort_by(|a, b| b.1.total_cmp(&a.1));
+        factors.truncate(n);
+        factors
+    }
+}
+
+/// How a path rule changed an entity's risk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleEffect {
+    /// Score boosted
+    Boosted,
+    /// Level raised to a rule's minimum
+    Raised,
+    /// Level capped at a rule's maximum
+    Capped,
+}
+
+impl std::fmt::Display for RuleEffect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Boosted => write!(f, "boosted"),
+            Self::Raised => write!(f, "raised"),
+            Self::Capped => write!(f, "capped"),
+        }
+    }
+}
+
+/// A path rule that fired for an entity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleHit {
+    pub pattern: String,
+    pub effect: RuleEffect,
 }
 
 /// A logical group of related changes (from untangling).
@@ -92,12 +291,43 @@ pub struct ChangeGroup {
     pub id: usize,
     pub label: String,
     pub entity_ids: Vec<String>,
+    /// Most common classification among the members
+    pub dominant_classification: ChangeClassification,
+    /// Highest risk level among the members
+    pub max_risk: RiskLevel,
+    /// Highest risk score among the members
+    pub max_score: f64,
+    /// Whether any member is public API
+    pub has_public_api: bool,
+}
+
+impl ChangeGroup {
+    /// Short hint for display next to the label, e.g.
+    /// `functional, high 0.72, public API`.
+    pub fn hint(&self) -> String {
+        let mut hint = format!(
+            "{}, {} {:.2}",
+            self.dominant_classification, self.max_risk, self.max_score
+        );
+        if self.has_public_api {
+            hint.push_str(", public API");
+        }
+        hint
+    }
 }
 
 /// Summary statistics for a review.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewStats {
     pub total_entities: usize,
+    /// Entities in test code; the rest are non-test
+    #[serde(default)]
+    pub test_entities: usize,
+    #[serde(default)]
+    pub non_test_entities: usize,
+    /// Entities in generated files, left out of the review
+    #[serde(default)]
+    pub generated_entities: usize,
     pub by_risk: RiskBreakdown,
     pub by_classification: ClassificationBreakdown,
     pub by_change_type: ChangeTypeBreakdown,
@@ -135,10 +365,19 @@ pub struct Timing {
     pub diff_ms: u64,
     /// Time to list source files (ms)
     pub list_files_ms: u64,
-    /// Number of source files in the repo
+    /// Number of source files in the repo that went into the graph
     pub file_count: usize,
-    /// Time to build the entity graph (ms)
+    /// Source files left out of the graph by `[graph] exclude_paths`
+    #[serde(default)]
+    pub excluded_file_count: usize,
+    /// Time to build the entity graph (ms); 0 on a cache hit
     pub graph_build_ms: u64,
+    /// Whether the entity graph was reused from an earlier analysis of the same tree
+    #[serde(default)]
+    pub graph_cache_hit: bool,
+    /// Time to find the cached graph (ms); 0 when it was built
+    #[serde(default)]
+    pub graph_lookup_ms: u64,
     /// Number of entities in the graph
     pub graph_entity_count: usize,
     /// Time for scoring, classification, untangling (ms)
@@ -147,14 +386,162 @@ pub struct Timing {
     pub total_ms: u64,
 }
 
+/// What kind of coverage problem an analysis ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// A changed source file yielded no entities: it failed to parse, or
+    /// every edit was outside any entity
+    NoEntities,
+    /// A changed file's content could not be read (binary or not UTF-8)
+    Unreadable,
+    /// A changed file was over the size limit and was skipped
+    TooLarge,
+    /// A changed file has a type no parser handles
+    Unsupported,
+    /// The entity graph was not built, so blast radius and dependents are 0
+    GraphSkipped,
+}
+
+/// Something that reduced how much of a change the analysis could score.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalysisWarning {
+    pub kind: WarningKind,
+    pub file_path: Option<String>,
+    pub message: String,
+}
+
+/// How two changed entities are linked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// `from` depends on `to` in the entity graph
+    Dependency,
+    /// Changed in the same file as `to`, the only link known without the
+    /// graph (`inspect bench --fast`)
+    SameFile,
+}
+
+/// A link between two changed entities, by entity id. Dependency edges
+/// point from the dependent to its dependency.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EntityEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+fn exact() -> bool {
+    true
+}
+
 /// Complete review result for a set of changes.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReviewResult {
     pub entity_reviews: Vec<EntityReview>,
     pub groups: Vec<ChangeGroup>,
+    /// Links between changed entities that formed the groups, each once
+    #[serde(default)]
+    pub edges: Vec<EntityEdge>,
+    /// Whether the entity graph covered every source file. When false,
+    /// blast radius and dependent counts are lower bounds
+    #[serde(default = "exact")]
+    pub blast_radius_exact: bool,
     pub stats: ReviewStats,
     pub timing: Timing,
+    /// Coverage problems; empty when every changed file was analyzed
+    #[serde(default)]
+    pub warnings: Vec<AnalysisWarning>,
     /// The underlying semantic changes (for formatters that want raw data)
     #[serde(skip)]
     pub changes: Vec<SemanticChange>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::risk::ReviewVerdict;
+    use serde_json::json;
+
+    /// Serializes to `expected` and reads back, from it and from `legacy`.
+    fn pin<T>(value: T, expected: &str, legacy: &str)
+    where
+        T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug,
+    {
+        assert_eq!(serde_json::to_value(&value).unwrap(), json!(expected));
+        assert_eq!(serde_json::from_value::<T>(json!(expected)).unwrap(), value);
+        assert_eq!(serde_json::from_value::<T>(json!(legacy)).unwrap(), value);
+    }
+
+    #[test]
+    fn enum_spellings_are_pinned() {
+        pin(RiskLevel::Low, "low", "Low");
+        pin(RiskLevel::Medium, "medium", "Medium");
+        pin(RiskLevel::High, "high", "High");
+        pin(RiskLevel::Critical, "critical", "Critical");
+
+        pin(ChangeClassification::Text, "text", "Text");
+        pin(ChangeClassification::Syntax, "syntax", "Syntax");
+        pin(ChangeClassification::Functional, "functional", "Functional");
+        pin(ChangeClassification::TextSyntax, "text+syntax", "TextSyntax");
+        pin(ChangeClassification::TextFunctional, "text+functional", "TextFunctional");
+        pin(ChangeClassification::SyntaxFunctional, "syntax+functional", "SyntaxFunctional");
+        pin(ChangeClassification::TextSyntaxFunctional, "text+syntax+functional", "TextSyntaxFunctional");
+
+        pin(ReviewVerdict::LikelyApprovable, "likely_approvable", "LikelyApprovable");
+        pin(ReviewVerdict::RequiresCarefulReview, "requires_careful_review", "RequiresCarefulReview");
+    }
+
+    #[test]
+    fn serialized_names_match_display() {
+        for level in [RiskLevel::Low, RiskLevel::Medium, RiskLevel::High, RiskLevel::Critical] {
+            assert_eq!(serde_json::to_value(level).unwrap(), json!(level.to_string()));
+        }
+        for classification in [
+            ChangeClassification::Text,
+            ChangeClassification::TextSyntax,
+            ChangeClassification::TextSyntaxFunctional,
+        ] {
+            assert_eq!(serde_json::to_value(classification).unwrap(), json!(classification.to_string()));
+        }
+    }
+
+    #[test]
+    fn change_type_round_trips_lowercase() {
+        let mut review = json!({
+            "entity_id": "a.rs::f",
+            "entity_name": "f",
+            "entity_type": "function",
+            "file_path": "a.rs",
+            "change_type": "Renamed",
+            "classification": "Functional",
+            "risk_score": 0.5,
+            "risk_level": "High",
+            "blast_radius": 0,
+            "dependent_count": 0,
+            "dependency_count": 0,
+            "is_public_api": false,
+            "structural_change": null,
+            "group_id": 0,
+            "start_line": 1,
+            "end_line": 2,
+            "dependent_names": [],
+            "dependency_names": [],
+        });
+        let parsed: EntityReview = serde_json::from_value(review.clone()).expect("version 1 spellings still read");
+        assert_eq!(parsed.change_type, ChangeType::Renamed);
+
+        let written = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(written["change_type"], "renamed");
+        assert_eq!(written["classification"], "functional");
+        assert_eq!(written["risk_level"], "high");
+        // Empty name lists are left out, and read back as empty
+        assert!(written.get("dependent_names").is_none());
+        assert!(written.get("dependency_names").is_none());
+        let reread: EntityReview = serde_json::from_value(written).unwrap();
+        assert!(reread.dependent_names.is_empty());
+
+        review["change_type"] = json!("teleported");
+        assert!(serde_json::from_value::<EntityReview>(review).is_err());
+    }
+}
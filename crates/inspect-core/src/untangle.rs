@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::types::{ChangeGroup, EntityReview};
+use crate::types::{ChangeClassification, ChangeGroup, EdgeKind, EntityEdge, EntityReview};
 
 /// Union-Find data structure for grouping related entities.
 struct UnionFind {
@@ -85,91 +85,203 @@ pub fn untangle(
                 .map(|&i| reviews[i].entity_id.clone())
                 .collect();
 
-            // Label from the first entity's file path
-            let label = if entity_ids.len() == 1 {
-                reviews[indices[0]].entity_name.clone()
-            } else {
-                // Use common file path prefix or first entity name
-                let files: Vec<&str> = indices.iter().map(|&i| reviews[i].file_path.as_str()).collect();
-                let common = common_prefix(&files);
-                if common.is_empty() {
-                    format!("{} entities", entity_ids.len())
-                } else {
-                    common
-                }
-            };
-
-            ChangeGroup {
+            let members: Vec<&EntityReview> = indices.iter().map(|&i| &reviews[i]).collect();
+            let mut group = ChangeGroup {
                 id: group_id,
-                label,
+                label: group_label(&members),
                 entity_ids,
-            }
+                dominant_classification: reviews[indices[0]].classification,
+                max_risk: reviews[indices[0]].risk_level,
+                max_score: 0.0,
+                has_public_api: false,
+            };
+            summarize(&mut group, &members);
+            group
         })
         .collect();
 
-    // Sort by group size (largest first)
-    groups.sort_by(|a, b| b.entity_ids.len().cmp(&a.entity_ids.len()));
+    sort_groups(&mut groups);
+    groups
+}
 
-    // Re-number group IDs
+/// Riskiest group first, larger groups first among equals; ids renumbered
+/// from 0 in that order.
+fn sort_groups(groups: &mut [ChangeGroup]) {
+    groups.sort_by(|a, b| {
+        b.max_score
+            .total_cmp(&a.max_score)
+            .then(b.entity_ids.len().cmp(&a.entity_ids.len()))
+    });
     for (i, group) in groups.iter_mut().enumerate() {
         group.id = i;
     }
+}
 
-    groups
+/// Re-order groups by their current summaries, as [`untangle`] does, and
+/// point each review at its group's new id. Call after
+/// [`summarize_groups`] when scores have changed.
+pub fn rank_groups(groups: &mut [ChangeGroup], reviews: &mut [EntityReview]) {
+    sort_groups(groups);
+    let ids: HashMap<&str, usize> = groups
+        .iter()
+        .flat_map(|g| g.entity_ids.iter().map(move |id| (id.as_str(), g.id)))
+        .collect();
+    for review in reviews {
+        if let Some(&id) = ids.get(review.entity_id.as_str()) {
+            review.group_id = id;
+        }
+    }
 }
 
-fn common_prefix(strings: &[&str]) -> String {
-    if strings.is_empty() {
-        return String::new();
+/// Edges tying together entities changed in the same file, for grouping
+/// when no dependency graph is available.
+pub fn colocation_edges(reviews: &[EntityReview]) -> Vec<(String, String)> {
+    let mut first_in_file: HashMap<&str, &str> = HashMap::new();
+    let mut edges = Vec::new();
+    for r in reviews {
+        match first_in_file.get(r.file_path.as_str()) {
+            Some(&first) => edges.push((r.entity_id.clone(), first.to_string())),
+            None => {
+                first_in_file.insert(&r.file_path, &r.entity_id);
+            }
+        }
     }
-    let first = strings[0];
-    let mut len = first.len();
-    for s in &strings[1..] {
-        len = len.min(s.len());
-        for (i, (a, b)) in first.bytes().zip(s.bytes()).enumerate() {
-            if a != b {
-                len = len.min(i);
-                break;
+    edges
+}
+
+/// `(from, to)` pairs as edges of one kind, sorted, each once, without
+/// self-links.
+pub fn entity_edges(pairs: &[(String, String)], kind: EdgeKind) -> Vec<EntityEdge> {
+    let mut edges: Vec<EntityEdge> = pairs
+        .iter()
+        .filter(|(from, to)| from != to)
+        .map(|(from, to)| EntityEdge {
+            from: from.clone(),
+            to: to.clone(),
+            kind,
+        })
+        .collect();
+    edges.sort();
+    edges.dedup();
+    edges
+}
+
+/// Refresh each group's classification and risk summary from the current
+/// reviews, e.g. after re-scoring or filtering. Order and ids are left alone.
+pub fn summarize_groups(groups: &mut [ChangeGroup], reviews: &[EntityReview]) {
+    let by_id: HashMap<&str, &EntityReview> =
+        reviews.iter().map(|r| (r.entity_id.as_str(), r)).collect();
+    for group in groups {
+        let members: Vec<&EntityReview> = group
+            .entity_ids
+            .iter()
+            .filter_map(|id| by_id.get(id.as_str()).copied())
+            .collect();
+        summarize(group, &members);
+    }
+}
+
+fn summarize(group: &mut ChangeGroup, members: &[&EntityReview]) {
+    // Count classifications in order of first appearance; ties go to the
+    // classification of the riskier entity.
+    let mut counts: Vec<(ChangeClassification, usize, f64)> = Vec::new();
+    for r in members {
+        match counts.iter_mut().find(|(c, _, _)| *c == r.classification) {
+            Some((_, n, score)) => {
+                *n += 1;
+                *score = score.max(r.risk_score);
             }
+            None => counts.push((r.classification, 1, r.risk_score)),
+        }
+    }
+    if let Some((c, _, _)) = counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(a.2.total_cmp(&b.2)))
+    {
+        group.dominant_classification = c;
+    }
+    if let Some(level) = members.iter().map(|r| r.risk_level).max() {
+        group.max_risk = level;
+    }
+    group.max_score = members.iter().map(|r| r.risk_score).fold(0.0, f64::max);
+    group.has_public_api = members.iter().any(|r| r.is_public_api);
+}
+
+/// Label a group by where its entities live:
+///
+/// - one entity: `file.rs::name`
+/// - all under one directory: that directory's name (`src/payments/` → `payments`)
+/// - spread over sibling directories: `api ↔ frontend`, with `+N` for more
+fn group_label(members: &[&EntityReview]) -> String {
+    if let [only] = members {
+        let path = only.file_path.replace('\\', "/");
+        let file = path.rsplit('/').next().unwrap_or(&path);
+        return format!("{}::{}", file, only.entity_name);
+    }
+
+    let paths: Vec<Vec<&str>> = members
+        .iter()
+        .map(|r| r.file_path.split(['/', '\\']).filter(|c| !c.is_empty()).collect())
+        .collect();
+    let dirs: Vec<&[&str]> = paths.iter().map(|p| &p[..p.len().saturating_sub(1)]).collect();
+    let common = common_dir(&dirs);
+
+    // The next path component below the common directory, by entity count.
+    // Files sitting directly in it only count when there is no common
+    // directory at all (top-level files).
+    let mut branches: Vec<(&str, usize)> = Vec::new();
+    for path in &paths {
+        let Some(&next) = path.get(common.len()) else { continue };
+        if !common.is_empty() && path.len() == common.len() + 1 {
+            continue;
+        }
+        match branches.iter_mut().find(|(b, _)| *b == next) {
+            Some((_, n)) => *n += 1,
+            None => branches.push((next, 1)),
         }
     }
-    // Trim to last '/' for a clean path prefix
-    let prefix = &first[..len];
-    if let Some(pos) = prefix.rfind('/') {
-        first[..=pos].to_string()
+    branches.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    if branches.len() >= 2 || (common.is_empty() && !branches.is_empty()) {
+        let mut label = branches.iter().take(2).map(|(b, _)| *b).collect::<Vec<_>>().join(" ↔ ");
+        if branches.len() > 2 {
+            label.push_str(&format!(" +{}", branches.len() - 2));
+        }
+        label
     } else {
-        prefix.to_string()
+        common
+            .last()
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| format!("{} entities", members.len()))
     }
 }
 
+/// Longest run of leading directory components shared by all `dirs`.
+fn common_dir<'a>(dirs: &[&[&'a str]]) -> Vec<&'a str> {
+    let Some((first, rest)) = dirs.split_first() else {
+        return vec![];
+    };
+    let mut len = first.len();
+    for dir in rest {
+        len = len.min(first.iter().zip(dir.iter()).take_while(|(a, b)| a == b).count());
+    }
+    first[..len].to_vec()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::types::{ChangeClassification, EntityReview, RiskLevel};
-    use sem_core::model::change::ChangeType;
 
     fn make_review(id: &str, name: &str, file: &str) -> EntityReview {
         EntityReview {
             entity_id: id.into(),
             entity_name: name.into(),
-            entity_type: "function".into(),
             file_path: file.into(),
-            change_type: ChangeType::Modified,
-            classification: ChangeClassification::Functional,
             risk_score: 0.5,
             risk_level: RiskLevel::Medium,
-            blast_radius: 0,
-            dependent_count: 0,
-            dependency_count: 0,
-            is_public_api: false,
-            structural_change: Some(true),
-            group_id: 0,
-            start_line: 1,
             end_line: 10,
-            before_content: None,
-            after_content: None,
-            dependent_names: vec![],
-            dependency_names: vec![],
+            ..EntityReview::test_default()
         }
     }
 
@@ -199,4 +311,117 @@ mod tests {
         assert_eq!(groups.len(), 1);
         assert_eq!(groups[0].entity_ids.len(), 3);
     }
+
+    #[test]
+    fn colocated_entities_share_a_group() {
+        let reviews = vec![
+            make_review("a", "foo", "src/a.rs"),
+            make_review("b", "bar", "src/b.rs"),
+            make_review("c", "baz", "src/a.rs"),
+            make_review("d", "qux", "src/a.rs"),
+        ];
+        let edges = colocation_edges(&reviews);
+        assert_eq!(edges.len(), 2);
+        let groups = untangle(&reviews, &edges);
+        assert_eq!(groups.len(), 2);
+        let sizes: Vec<usize> = groups.iter().map(|g| g.entity_ids.len()).collect();
+        assert!(sizes.contains(&3) && sizes.contains(&1));
+    }
+
+    #[test]
+    fn edges_are_deduplicated_without_self_links() {
+        let pairs = vec![
+            ("b".to_string(), "a".to_string()),
+            ("a".to_string(), "b".to_string()),
+            ("b".to_string(), "a".to_string()),
+            ("c".to_string(), "c".to_string()),
+        ];
+        let edges = entity_edges(&pairs, EdgeKind::Dependency);
+        let ends: Vec<(&str, &str)> = edges.iter().map(|e| (e.from.as_str(), e.to.as_str())).collect();
+        // Direction is kept: a -> b and b -> a are different edges
+        assert_eq!(ends, [("a", "b"), ("b", "a")]);
+        assert!(edges.iter().all(|e| e.kind == EdgeKind::Dependency));
+    }
+
+    fn chain(n: usize) -> Vec<(String, String)> {
+        (1..n).map(|i| (format!("e{}", i - 1), format!("e{}", i))).collect()
+    }
+
+    #[test]
+    fn single_dir_group_is_labeled_by_directory() {
+        let mut reviews = vec![
+            make_review("e0", "charge", "src/payments/charge.rs"),
+            make_review("e1", "refund", "src/payments/refund.rs"),
+            make_review("e2", "client", "src/payments/stripe/client.rs"),
+        ];
+        reviews[1].risk_level = RiskLevel::High;
+        reviews[2].classification = ChangeClassification::Syntax;
+        let groups = untangle(&reviews, &chain(3));
+        assert_eq!(groups[0].label, "payments");
+        assert_eq!(groups[0].dominant_classification, ChangeClassification::Functional);
+        assert_eq!(groups[0].max_risk, RiskLevel::High);
+        assert_eq!(groups[0].hint(), "functional, high 0.50");
+    }
+
+    #[test]
+    fn cross_dir_group_names_both_sides() {
+        let reviews = vec![
+            make_review("e0", "handler", "api/routes.rs"),
+            make_review("e1", "fetch", "frontend/client.ts"),
+            make_review("e2", "render", "frontend/view.ts"),
+        ];
+        let groups = untangle(&reviews, &chain(3));
+        assert_eq!(groups[0].label, "frontend ↔ api");
+
+        let reviews = vec![
+            make_review("e0", "a", "src/api/a.rs"),
+            make_review("e1", "b", "src/db/b.rs"),
+            make_review("e2", "c", "src/web/c.rs"),
+        ];
+        let groups = untangle(&reviews, &chain(3));
+        assert_eq!(groups[0].label, "api ↔ db +1");
+    }
+
+    #[test]
+    fn riskiest_group_sorts_first_and_ids_are_renumbered() {
+        let mut reviews = vec![
+            make_review("e0", "a", "src/ui/a.rs"),
+            make_review("e1", "b", "src/ui/b.rs"),
+            make_review("e2", "c", "src/ui/c.rs"),
+            make_review("e3", "delete_account", "src/api/account.rs"),
+            make_review("e4", "route", "src/api/routes.rs"),
+            make_review("e5", "log", "src/log.rs"),
+        ];
+        for r in &mut reviews[..3] {
+            r.risk_score = 0.1;
+            r.risk_level = RiskLevel::Low;
+        }
+        reviews[3].risk_score = 0.9;
+        reviews[3].risk_level = RiskLevel::Critical;
+        reviews[3].is_public_api = true;
+        let edges = vec![
+            ("e0".to_string(), "e1".to_string()),
+            ("e1".to_string(), "e2".to_string()),
+            ("e3".to_string(), "e4".to_string()),
+        ];
+        let groups = untangle(&reviews, &edges);
+
+        let sizes: Vec<usize> = groups.iter().map(|g| g.entity_ids.len()).collect();
+        assert_eq!(sizes, vec![2, 1, 3]);
+        assert_eq!(groups[0].max_risk, RiskLevel::Critical);
+        assert_eq!(groups[0].max_score, 0.9);
+        assert!(groups[0].has_public_api);
+        assert!(!groups[2].has_public_api);
+        for (i, g) in groups.iter().enumerate() {
+            assert_eq!(g.id, i);
+        }
+    }
+
+    #[test]
+    fn singleton_group_names_file_and_entity() {
+        let reviews = vec![make_review("e0", "validate", "src/payments/charge.rs")];
+        let groups = untangle(&reviews, &[]);
+        assert_eq!(groups[0].label, "charge.rs::validate");
+        assert_eq!(groups[0].max_risk, RiskLevel::Medium);
+    }
 }
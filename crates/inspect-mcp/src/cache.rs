@@ -0,0 +1,329 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::process::Command;
+
+use sem_core::git::types::DiffScope;
+
+use inspect_core::types::ReviewResult;
+
+/// Number of analyses kept when INSPECT_MCP_CACHE_SIZE is unset.
+pub const DEFAULT_CAPACITY: usize = 8;
+
+/// Cache key: the repo plus the target resolved to commit SHAs. Working-tree
+/// targets resolve to HEAD plus a fingerprint of the dirty state, so any edit
+/// produces a new key instead of serving a stale result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub repo_path: String,
+    pub resolved: String,
+}
+
+/// One local analysis: the target, the session weights generation it was
+/// scored with, and whether it skipped the entity graph (fast mode).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RunKey {
+    pub target: CacheKey,
+    pub generation: u64,
+    pub fast: bool,
+}
+
+/// Remote PR analyses: the PR at a head commit, diffed from `base_sha` (its
+/// base, or the commit given as since_commit). A new push is a new key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteKey {
+    pub repo: String,
+    pub pr_number: u64,
+    pub head_sha: String,
+    pub base_sha: String,
+}
+
+/// PR file contents fetched for inspect_search: the files at `paths`, read at
+/// the PR's head commit.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrFileKey {
+    pub repo: String,
+    pub head_sha: String,
+    pub paths: Vec<String>,
+}
+
+/// Small LRU keyed by resolved target. The most recently used entry is at the back.
+pub struct LruCache<V, K = CacheKey> {
+    capacity: usize,
+    entries: VecDeque<(K, V)>,
+}
+
+pub type AnalysisCache = LruCache<ReviewResult, RunKey>;
+
+/// inspect_pr results before session weights, which are applied on each use.
+pub type RemoteCache = LruCache<ReviewResult, RemoteKey>;
+
+/// (path, content) pairs of PR files, by [`PrFileKey`].
+pub type PrFileCache = LruCache<Vec<(String, String)>, PrFileKey>;
+
+impl<V: Clone, K: PartialEq> LruCache<V, K> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let pos = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(pos)?;
+        let value = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.entries.retain(|(k, _)| k != &key);
+        self.entries.push_back((key, value));
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+/// Resolve a diff scope to a cache key.
+pub fn resolve_key(repo_path: &str, scope: &DiffScope) -> Result<CacheKey, String> {
+    let repo = Path::new(repo_path);
+    let resolved = match scope {
+        DiffScope::Commit { sha } => rev_parse(repo, sha)?,
+        DiffScope::Range { from, to } => {
+            format!("{}..{}", rev_parse(repo, from)?, rev_parse(repo, to)?)
+        }
+        DiffScope::Staged => {
+            format!("staged:{}:{:016x}", rev_parse(repo, "HEAD")?, staged_fingerprint(repo)?)
+        }
+        _ => format!("working:{}:{:016x}", rev_parse(repo, "HEAD")?, dirty_fingerprint(repo)?),
+    };
+
+    Ok(CacheKey {
+        repo_path: repo_path.to_string(),
+        resolved,
+    })
+}
+
+fn rev_parse(repo: &Path, rev: &str) -> Result<String, String> {
+    let output = git(repo, &["rev-parse", "--verify", &format!("{}^{{commit}}", rev)])?;
+    Ok(output.trim().to_string())
+}
+
+/// Hash of the uncommitted state: tracked changes against HEAD, the status
+/// of every changed or untracked path, and each such file's size and mtime.
+/// Untracked files aren't in the diff, so the metadata is what notices them
+/// being edited.
+fn dirty_fingerprint(repo: &Path) -> Result<u64, String> {
+    let mut hasher = DefaultHasher::new();
+    git(repo, &["diff", "HEAD"])?.hash(&mut hasher);
+    let status = git(repo, &["status", "--porcelain", "-z", "--untracked-files=all"])?;
+    status.hash(&mut hasher);
+    for path in status_paths(&status) {
+        if let Ok(meta) = std::fs::metadata(repo.join(path)) {
+            meta.len().hash(&mut hasher);
+            meta.modified().ok().hash(&mut hasher);
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// The paths in `git status --porcelain -z` output. Entries are "XY path",
+/// except that a rename or copy is followed by its source path as an entry
+/// of its own, which is skipped.
+fn status_paths(status: &str) -> Vec<&str> {
+    let mut paths = Vec::new();
+    let mut entries = status.split('\0');
+    while let Some(entry) = entries.next() {
+        let Some(path) = entry.get(3..) else { continue };
+        paths.push(path);
+        if entry[..2].contains(['R', 'C']) {
+            entries.next();
+        }
+    }
+    paths
+}
+
+/// Hash of the index state relative to HEAD. Unstaged edits don't affect it.
+fn staged_fingerprint(repo: &Path) -> Result<u64, String> {
+    let mut hasher = DefaultHasher::new();
+    git(repo, &["diff", "--cached", "HEAD"])?.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+fn git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .map_err(|e| format!("failed to run git {}: {}", args[0], e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::types::*;
+    use tempfile::TempDir;
+
+    fn result(total: usize) -> ReviewResult {
+        ReviewResult {
+            entity_reviews: vec![],
+            groups: vec![],
+            stats: ReviewStats {
+                total_entities: total,
+                test_entities: 0,
+                non_test_entities: total,
+                generated_entities: 0,
+                by_risk: RiskBreakdown { critical: 0, high: 0, medium: 0, low: 0 },
+                by_classification: ClassificationBreakdown { text: 0, syntax: 0, functional: 0, mixed: 0 },
+                by_change_type: ChangeTypeBreakdown { added: 0, modified: 0, deleted: 0, moved: 0, renamed: 0 },
+            },
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    fn key(resolved: &str) -> CacheKey {
+        CacheKey {
+            repo_path: "/repo".into(),
+            resolved: resolved.into(),
+        }
+    }
+
+    fn run(dir: &Path, args: &[&str]) {
+        Command::new("git").args(args).current_dir(dir).output().unwrap();
+    }
+
+    fn commit(dir: &Path, content: &str) {
+        std::fs::write(dir.join("main.rs"), content).unwrap();
+        run(dir, &["add", "-A"]);
+        run(dir, &["commit", "-m", "change"]);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::<ReviewResult>::new(2);
+        cache.insert(key("a"), result(1));
+        cache.insert(key("b"), result(2));
+        // Touch "a" so "b" becomes the eviction candidate
+        assert!(cache.get(&key("a")).is_some());
+        cache.insert(key("c"), result(3));
+
+        assert!(cache.get(&key("b")).is_none());
+        assert_eq!(cache.get(&key("a")).unwrap().stats.total_entities, 1);
+        assert_eq!(cache.get(&key("c")).unwrap().stats.total_entities, 3);
+    }
+
+    #[test]
+    fn same_ref_resolves_to_new_key_after_commit() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        run(dir, &["init"]);
+        run(dir, &["config", "user.email", "test@test.com"]);
+        run(dir, &["config", "user.name", "Test"]);
+        commit(dir, "fn a() {}\n");
+
+        let repo = dir.to_str().unwrap();
+        let head = DiffScope::Commit { sha: "HEAD".into() };
+        let first = resolve_key(repo, &head).unwrap();
+        assert_eq!(resolve_key(repo, &head).unwrap(), first);
+
+        commit(dir, "fn a() {}\nfn b() {}\n");
+        assert_ne!(resolve_key(repo, &head).unwrap(), first);
+    }
+
+    #[test]
+    fn working_key_tracks_dirty_state() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        run(dir, &["init"]);
+        run(dir, &["config", "user.email", "test@test.com"]);
+        run(dir, &["config", "user.name", "Test"]);
+        commit(dir, "fn a() {}\n");
+
+        let repo = dir.to_str().unwrap();
+        let clean = resolve_key(repo, &DiffScope::Working).unwrap();
+
+        std::fs::write(dir.join("main.rs"), "fn a() { 1 }\n").unwrap();
+        let dirty = resolve_key(repo, &DiffScope::Working).unwrap();
+        assert_ne!(clean, dirty);
+        assert_eq!(resolve_key(repo, &DiffScope::Working).unwrap(), dirty);
+    }
+
+    #[test]
+    fn working_key_tracks_untracked_edits() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        run(dir, &["init"]);
+        run(dir, &["config", "user.email", "test@test.com"]);
+        run(dir, &["config", "user.name", "Test"]);
+        commit(dir, "fn a() {}\n");
+
+        let repo = dir.to_str().unwrap();
+        std::fs::create_dir(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/new.rs"), "fn b() {}\n").unwrap();
+        let added = resolve_key(repo, &DiffScope::Working).unwrap();
+        assert_eq!(resolve_key(repo, &DiffScope::Working).unwrap(), added);
+
+        // Same file list, new content
+        std::fs::write(dir.join("src/new.rs"), "fn b() { todo!() }\n").unwrap();
+        assert_ne!(resolve_key(repo, &DiffScope::Working).unwrap(), added);
+    }
+
+    #[test]
+    fn status_paths_skip_rename_sources() {
+        let status = "R  src/new name.rs\0src/old.rs\0 M main.rs\0C  copy.rs\0orig.rs\0?? notes.txt\0";
+        assert_eq!(status_paths(status), ["src/new name.rs", "main.rs", "copy.rs", "notes.txt"]);
+        assert!(status_paths("").is_empty());
+    }
+
+    #[test]
+    fn staged_key_ignores_unstaged_edits() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        run(dir, &["init"]);
+        run(dir, &["config", "user.email", "test@test.com"]);
+        run(dir, &["config", "user.name", "Test"]);
+        commit(dir, "fn a() {}\n");
+
+        let repo = dir.to_str().unwrap();
+        std::fs::write(dir.join("main.rs"), "fn a() { 1 }\n").unwrap();
+        run(dir, &["add", "main.rs"]);
+        let staged = resolve_key(repo, &DiffScope::Staged).unwrap();
+        assert_ne!(staged, resolve_key(repo, &DiffScope::Working).unwrap());
+
+        std::fs::write(dir.join("main.rs"), "fn a() { 2 }\n").unwrap();
+        assert_eq!(resolve_key(repo, &DiffScope::Staged).unwrap(), staged);
+
+        run(dir, &["add", "main.rs"]);
+        assert_ne!(resolve_key(repo, &DiffScope::Staged).unwrap(), staged);
+    }
+
+    #[test]
+    fn unknown_ref_is_an_error() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        run(dir, &["init"]);
+        let scope = DiffScope::Commit { sha: "nope".into() };
+        assert!(resolve_key(dir.to_str().unwrap(), &scope).is_err());
+    }
+}
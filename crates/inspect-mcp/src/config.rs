@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use inspect_core::filter::{parse_risk_level, EntityFilter};
+use inspect_core::risk::{RiskConfig, RiskWeights};
+use inspect_core::types::RiskLevel;
+
+use crate::tools::ConfigureParams;
+
+/// Defaults set with inspect_configure for the rest of the session.
+/// Arguments passed on a call take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct SessionConfig {
+    pub weights: RiskWeights,
+    /// Weights file path, "inline", or None for the built-in weights
+    pub weights_source: Option<String>,
+    /// Bumped whenever the weights change, so results scored with older
+    /// weights are not cached
+    pub generation: u64,
+    pub min_risk: Option<RiskLevel>,
+    pub hide_tests: bool,
+    pub fast: bool,
+}
+
+impl SessionConfig {
+    /// Fill in the filter settings a call left unset.
+    pub fn apply_defaults(&self, filter: &mut EntityFilter, hide_tests: Option<bool>) {
+        if filter.min_risk.is_none() {
+            filter.min_risk = self.min_risk;
+        }
+        filter.hide_tests = hide_tests.unwrap_or(self.hide_tests);
+    }
+
+    pub fn custom_weights(&self) -> bool {
+        self.weights != RiskWeights::default()
+    }
+
+    /// The repo's risk settings with the session weights on top.
+    pub fn risk_config(&self, repo: RiskConfig) -> RiskConfig {
+        RiskConfig {
+            weights: self.weights.clone(),
+            ..repo
+        }
+    }
+
+    /// Apply an inspect_configure call. Nothing changes unless every argument
+    /// is valid. Returns whether the weights changed.
+    pub fn update(&mut self, params: &ConfigureParams) -> Result<bool, String> {
+        let mut next = if params.reset.unwrap_or(false) {
+            Self::default()
+        } else {
+            self.clone()
+        };
+
+        if let Some(ref path) = params.weights_path {
+            next.weights = RiskWeights::load(Path::new(path))?;
+            next.weights_source = Some(path.clone());
+        }
+        if let Some(ref overrides) = params.weights {
+            let Some(overrides) = overrides.as_object() else {
+                return Err("weights must be an object of weight names to numbers".into());
+            };
+            let mut merged = serde_json::to_value(&next.weights).map_err(|e| e.to_string())?;
+            if let Some(merged) = merged.as_object_mut() {
+                merged.extend(overrides.clone());
+            }
+            next.weights = serde_json::from_value(merged).map_err(|e| format!("invalid weights: {}", e))?;
+            next.weights.validate()?;
+            next.weights_source = Some(match next.weights_source {
+                Some(path) if path != "inline" => format!("{} + inline", path),
+                _ => "inline".into(),
+            });
+        }
+
+        if let Some(ref min_risk) = params.min_risk {
+            next.min_risk = Some(parse_risk_level(min_risk)?);
+        }
+        if let Some(hide_tests) = params.hide_tests {
+            next.hide_tests = hide_tests;
+        }
+        if let Some(fast) = params.fast {
+            next.fast = fast;
+        }
+
+        let weights_changed = next.weights != self.weights;
+        next.generation = self.generation + u64::from(weights_changed);
+        *self = next;
+        Ok(weights_changed)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "weights": self.weights,
+            "weights_source": self.weights_source.as_deref().unwrap_or("built-in"),
+            "min_risk": self.min_risk.map(|r| r.to_string()),
+            "hide_tests": self.hide_tests,
+            "fast": self.fast,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(value: serde_json::Value) -> ConfigureParams {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn call_arguments_override_session_defaults() {
+        let mut config = SessionConfig::default();
+        config
+            .update(&params(serde_json::json!({ "min_risk": "high", "hide_tests": true })))
+            .unwrap();
+
+        let mut filter = EntityFilter::default();
+        config.apply_defaults(&mut filter, None);
+        assert_eq!(filter.min_risk, Some(RiskLevel::High));
+        assert!(filter.hide_tests);
+
+        let mut filter = EntityFilter::parse(Some("low"), None, None).unwrap();
+        config.apply_defaults(&mut filter, Some(false));
+        assert_eq!(filter.min_risk, Some(RiskLevel::Low));
+        assert!(!filter.hide_tests);
+    }
+
+    #[test]
+    fn weights_merge_and_bump_generation() {
+        let mut config = SessionConfig::default();
+        assert!(!config.update(&params(serde_json::json!({ "fast": true }))).unwrap());
+        assert_eq!(config.generation, 0);
+
+        assert!(config
+            .update(&params(serde_json::json!({ "weights": { "public_api": 0.3 } })))
+            .unwrap());
+        assert_eq!(config.generation, 1);
+        assert_eq!(config.weights.public_api, 0.3);
+        assert_eq!(config.weights.dependents, RiskWeights::default().dependents);
+        assert_eq!(config.to_json()["weights_source"], "inline");
+        assert!(config.fast);
+
+        // Same weights again: no change
+        assert!(!config
+            .update(&params(serde_json::json!({ "weights": { "public_api": 0.3 } })))
+            .unwrap());
+
+        assert!(config.update(&params(serde_json::json!({ "reset": true }))).unwrap());
+        assert_eq!(config.generation, 2);
+        assert!(!config.fast && !config.custom_weights());
+    }
+
+    #[test]
+    fn invalid_arguments_leave_config_untouched() {
+        let mut config = SessionConfig::default();
+        for bad in [
+            serde_json::json!({ "min_risk": "severe", "fast": true }),
+            serde_json::json!({ "weights": { "publicapi": 0.3 } }),
+            serde_json::json!({ "weights": { "dependents": -1.0 } }),
+            serde_json::json!({ "weights": [0.3] }),
+            serde_json::json!({ "weights_path": "/nonexistent/weights.json" }),
+        ] {
+            assert!(config.update(&params(bad)).is_err());
+        }
+        assert!(!config.fast);
+        assert_eq!(config.generation, 0);
+    }
+}
@@ -0,0 +1,140 @@
+use rmcp::model::{CallToolResult, Content};
+use rmcp::ErrorData;
+
+use inspect_core::github::GitHubError;
+
+pub use inspect_core::filter::closest_names;
+
+/// How many near-miss names to suggest when a lookup fails.
+pub const SUGGESTION_COUNT: usize = 5;
+
+// Three kinds of failure:
+// - invalid_params: the arguments are malformed (bad risk level, malformed range, missing repo)
+// - tool_error: an expected outcome the agent can act on (unknown entity, missing group,
+//   GitHub refusing the request); returned as a normal result flagged is_error
+// - internal_error: everything else, i.e. bugs
+
+// Failures an agent is likely to branch on carry one of these as `code`: in
+// the error data for invalid_params, in the body for tool_error.
+pub const NOT_A_REPO: &str = "NOT_A_REPO";
+pub const INVALID_TARGET: &str = "INVALID_TARGET";
+pub const ENTITY_NOT_FOUND: &str = "ENTITY_NOT_FOUND";
+pub const GROUP_NOT_FOUND: &str = "GROUP_NOT_FOUND";
+
+pub fn invalid_params(msg: impl ToString) -> ErrorData {
+    ErrorData::invalid_params(msg.to_string(), None)
+}
+
+/// invalid_params whose data is `{"code": code}` plus `details` (an object).
+pub fn coded_invalid_params(code: &str, msg: impl ToString, details: serde_json::Value) -> ErrorData {
+    let mut data = serde_json::json!({ "code": code });
+    if let (Some(data), Some(details)) = (data.as_object_mut(), details.as_object()) {
+        data.extend(details.clone());
+    }
+    ErrorData::invalid_params(msg.to_string(), Some(data))
+}
+
+pub fn internal_err(msg: impl ToString) -> ErrorData {
+    ErrorData::internal_error(msg.to_string(), None)
+}
+
+/// Expected failure reported as tool output, so clients show the message
+/// rather than a protocol error. `details` (an object) is merged into the body.
+pub fn tool_error(message: impl Into<String>, details: serde_json::Value) -> CallToolResult {
+    let mut body = serde_json::json!({ "error": message.into() });
+    if let (Some(body), Some(details)) = (body.as_object_mut(), details.as_object()) {
+        body.extend(details.clone());
+    }
+    CallToolResult::error(vec![Content::text(
+        serde_json::to_string_pretty(&body).unwrap_or_default(),
+    )])
+}
+
+/// Point auth failures at the setting that fixes them.
+fn with_token_hint(message: String) -> String {
+    if message.contains("GITHUB_TOKEN") {
+        message
+    } else {
+        format!("{message} (check GITHUB_TOKEN, or run `gh auth login`)")
+    }
+}
+
+/// Map a GitHub failure to a tool result: auth and API errors (missing token,
+/// unknown PR, rate limits) are the caller's to fix; parse errors are bugs.
+pub fn github_error(e: GitHubError) -> Result<CallToolResult, ErrorData> {
+    match e {
+        GitHubError::Auth(_) => Ok(tool_error(with_token_hint(e.to_string()), serde_json::json!({}))),
+        GitHubError::Api(ref msg) if msg.starts_with("401") => {
+            Ok(tool_error(with_token_hint(e.to_string()), serde_json::json!({})))
+        }
+        GitHubError::Api(_) | GitHubError::InvalidQuery(_) => Ok(tool_error(e.to_string(), serde_json::json!({}))),
+        GitHubError::RateLimited { reset_in_secs } => Ok(tool_error(
+            e.to_string(),
+            serde_json::json!({ "reset_in_secs": reset_in_secs }),
+        )),
+        GitHubError::Parse(_) => Err(internal_err(e)),
+    }
+}
+
+/// Check a `owner/repo` argument before any API call.
+pub fn check_repo_slug(repo: &str) -> Result<(), ErrorData> {
+    match repo.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => Ok(()),
+        _ => Err(invalid_params(format!("repo must be in owner/repo format, got '{}'", repo))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ErrorCode;
+
+    fn text(result: &CallToolResult) -> serde_json::Value {
+        let raw = result.content[0].as_text().unwrap().text.clone();
+        serde_json::from_str(&raw).unwrap()
+    }
+
+    #[test]
+    fn tool_error_is_flagged_and_carries_details() {
+        let result = tool_error("Entity 'x' not found", serde_json::json!({ "did_you_mean": ["y"] }));
+        assert_eq!(result.is_error, Some(true));
+        let body = text(&result);
+        assert_eq!(body["error"], "Entity 'x' not found");
+        assert_eq!(body["did_you_mean"][0], "y");
+    }
+
+    #[test]
+    fn coded_invalid_params_carry_their_code() {
+        let err = coded_invalid_params(INVALID_TARGET, "bad ref", serde_json::json!({ "target": "nope" }));
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(err.message, "bad ref");
+        assert_eq!(err.data, Some(serde_json::json!({ "code": "INVALID_TARGET", "target": "nope" })));
+    }
+
+    #[test]
+    fn github_errors_split_between_tool_and_internal() {
+        let auth = github_error(GitHubError::Auth("no token".into())).unwrap();
+        assert_eq!(auth.is_error, Some(true));
+        assert!(text(&auth)["error"].as_str().unwrap().contains("GITHUB_TOKEN"));
+
+        let rejected = github_error(GitHubError::Api("401 Unauthorized: Bad credentials".into())).unwrap();
+        assert!(text(&rejected)["error"].as_str().unwrap().contains("GITHUB_TOKEN"));
+
+        let missing = github_error(GitHubError::Api("404 Not Found".into())).unwrap();
+        assert_eq!(missing.is_error, Some(true));
+
+        let limited = github_error(GitHubError::RateLimited { reset_in_secs: 42 }).unwrap();
+        assert_eq!(text(&limited)["reset_in_secs"], 42);
+
+        let parse = github_error(GitHubError::Parse("bad json".into())).unwrap_err();
+        assert_eq!(parse.code, ErrorCode::INTERNAL_ERROR);
+    }
+
+    #[test]
+    fn repo_slug_validation() {
+        assert!(check_repo_slug("owner/repo").is_ok());
+        for bad in ["owner", "/repo", "owner/", "a/b/c"] {
+            assert_eq!(check_repo_slug(bad).unwrap_err().code, ErrorCode::INVALID_PARAMS);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use inspect_core::analyze::Progress;
+use tokio::sync::{broadcast, watch, Mutex, OwnedSemaphorePermit, Semaphore};
+
+/// Buffered updates per run; slow subscribers skip older ones.
+const UPDATE_BUFFER: usize = 64;
+
+/// Status update from a queued or running analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    /// Waiting for a free slot; 1 means next in line
+    Queued { position: usize },
+    Progress(Progress),
+}
+
+type Done<V, E> = Option<Result<V, E>>;
+
+/// Shared state of one in-flight run.
+struct Flight<V, E> {
+    done: watch::Receiver<Done<V, E>>,
+    updates: broadcast::Sender<Update>,
+}
+
+/// FIFO ticket counter used to report queue positions.
+struct Queue {
+    next_ticket: AtomicUsize,
+    served: watch::Sender<usize>,
+}
+
+/// Runs blocking work at most once per key at a time, with a global limit
+/// on concurrent runs. Callers asking for a key that is already running
+/// wait on that run instead of starting another. Failures are `E`; a task
+/// that dies without returning becomes `E::from` a message.
+pub struct Coalescer<K, V, E = String> {
+    inflight: Arc<Mutex<HashMap<K, Flight<V, E>>>>,
+    slots: Arc<Semaphore>,
+    queue: Arc<Queue>,
+}
+
+impl<K, V, E> Clone for Coalescer<K, V, E> {
+    fn clone(&self) -> Self {
+        Self {
+            inflight: self.inflight.clone(),
+            slots: self.slots.clone(),
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<K, V, E> Coalescer<K, V, E>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    E: Clone + From<String> + Send + Sync + 'static,
+{
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+            slots: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            queue: Arc::new(Queue {
+                next_ticket: AtomicUsize::new(0),
+                served: watch::Sender::new(0),
+            }),
+        }
+    }
+
+    /// Wait on the in-flight run for `key`, starting `work` on the blocking
+    /// pool if there is none. `work` receives a progress callback.
+    pub async fn join<W>(&self, key: K, work: W) -> Waiter<V, E>
+    where
+        W: FnOnce(&dyn Fn(Progress)) -> Result<V, E> + Send + 'static,
+    {
+        let mut inflight = self.inflight.lock().await;
+        if let Some(flight) = inflight.get(&key) {
+            return Waiter {
+                done: flight.done.clone(),
+                updates: flight.updates.subscribe(),
+            };
+        }
+
+        let (done_tx, done_rx) = watch::channel(None);
+        let (updates, updates_rx) = broadcast::channel(UPDATE_BUFFER);
+        inflight.insert(
+            key.clone(),
+            Flight {
+                done: done_rx.clone(),
+                updates: updates.clone(),
+            },
+        );
+        drop(inflight);
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            let _permit = this.acquire(&updates).await;
+            let tx = updates.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                work(&move |p| {
+                    let _ = tx.send(Update::Progress(p));
+                })
+            })
+            .await
+            .unwrap_or_else(|e| Err(E::from(format!("analysis task failed: {}", e))));
+
+            this.inflight.lock().await.remove(&key);
+            let _ = done_tx.send(Some(result));
+        });
+
+        Waiter {
+            done: done_rx,
+            updates: updates_rx,
+        }
+    }
+
+    /// Take a slot, reporting queue position while waiting for one.
+    async fn acquire(&self, updates: &broadcast::Sender<Update>) -> OwnedSemaphorePermit {
+        let ticket = self.queue.next_ticket.fetch_add(1, Ordering::SeqCst);
+        let mut served = self.queue.served.subscribe();
+        let acquire = self.slots.clone().acquire_owned();
+        tokio::pin!(acquire);
+
+        let mut first = true;
+        loop {
+            tokio::select! {
+                biased;
+                permit = &mut acquire => {
+                    self.queue.served.send_modify(|n| *n += 1);
+                    return permit.expect("analysis semaphore is never closed");
+                }
+                _ = served.changed(), if !first => {}
+                // No free slot on the first poll
+                _ = std::future::ready(()), if first => {}
+            }
+            first = false;
+            let position = (ticket + 1).saturating_sub(*served.borrow_and_update()).max(1);
+            let _ = updates.send(Update::Queued { position });
+        }
+    }
+}
+
+/// One caller's handle on a run.
+pub struct Waiter<V, E = String> {
+    done: watch::Receiver<Done<V, E>>,
+    updates: broadcast::Receiver<Update>,
+}
+
+impl<V: Clone, E: Clone> Waiter<V, E> {
+    /// Wait for the run to finish, passing each update to `notify` in order.
+    /// Returns None if `deadline` passes first; the run keeps going and can
+    /// still be waited on.
+    pub async fn wait<N, Fut>(&mut self, deadline: Duration, mut notify: N) -> Option<Result<V, E>>
+    where
+        N: FnMut(Update) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let sleep = tokio::time::sleep(deadline);
+        tokio::pin!(sleep);
+
+        loop {
+            let finished = self.done.borrow_and_update().clone();
+            if let Some(result) = finished {
+                // Updates sent just before the run finished
+                while let Ok(update) = self.updates.try_recv() {
+                    notify(update).await;
+                }
+                return Some(result);
+            }
+
+            tokio::select! {
+                biased;
+                update = self.updates.recv() => {
+                    if let Ok(update) = update {
+                        notify(update).await;
+                    }
+                }
+                _ = self.done.changed() => {}
+                _ = &mut sleep => return None,
+            }
+        }
+    }
+
+    /// Wait for the run to finish however long it takes, ignoring updates.
+    pub async fn result(mut self) -> Result<V, E>
+    where
+        E: From<String>,
+    {
+        loop {
+            if let Some(result) = self.done.borrow_and_update().clone() {
+                return result;
+            }
+            if self.done.changed().await.is_err() {
+                // Dropped without a result, e.g. at runtime shutdown
+                return Err(E::from("analysis task was dropped".to_string()));
+            }
+        }
+    }
+}
+
+/// Concurrent analyses when INSPECT_MCP_MAX_ANALYSES is unset: half the
+/// available cores, at least one.
+pub fn max_concurrent() -> usize {
+    std::env::var("INSPECT_MCP_MAX_ANALYSES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get() / 2)
+                .unwrap_or(1)
+        })
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::analyze::Phase;
+    use std::sync::Mutex as StdMutex;
+
+    /// Stand-in for analyze: reports every phase with a pause in between.
+    fn slow_analysis(report: &dyn Fn(Progress), pause: Duration) -> usize {
+        for phase in [Phase::Diff, Phase::ListFiles, Phase::Graph, Phase::Scoring] {
+            report(Progress { phase, done: 0, total: 2 });
+            std::thread::sleep(pause);
+            report(Progress { phase, done: 2, total: 2 });
+        }
+        42
+    }
+
+    #[tokio::test]
+    async fn forwards_progress_in_order() {
+        let coalescer: Coalescer<&str, usize> = Coalescer::new(1);
+        let seen = Arc::new(StdMutex::new(Vec::new()));
+        let sink = seen.clone();
+
+        let mut waiter = coalescer
+            .join("repo", |report| Ok(slow_analysis(report, Duration::from_millis(5))))
+            .await;
+        let result = waiter
+            .wait(Duration::from_secs(10), |u| {
+                sink.lock().unwrap().push(u);
+                async {}
+            })
+            .await;
+        assert_eq!(result, Some(Ok(42)));
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 8);
+        assert_eq!(seen[0], Update::Progress(Progress { phase: Phase::Diff, done: 0, total: 2 }));
+        assert_eq!(seen[7], Update::Progress(Progress { phase: Phase::Scoring, done: 2, total: 2 }));
+    }
+
+    #[tokio::test]
+    async fn deadline_leaves_run_going() {
+        let coalescer: Coalescer<&str, usize> = Coalescer::new(1);
+        let work = |report: &dyn Fn(Progress)| Ok(slow_analysis(report, Duration::from_millis(50)));
+
+        let mut waiter = coalescer.join("repo", work).await;
+        assert_eq!(waiter.wait(Duration::from_millis(10), |_| async {}).await, None);
+
+        // A later caller picks up the same run and gets its result
+        let mut later = coalescer.join("repo", |_| Err("started twice".into())).await;
+        assert_eq!(later.wait(Duration::from_secs(10), |_| async {}).await, Some(Ok(42)));
+        // And the first can keep waiting past its deadline
+        assert_eq!(waiter.result().await, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn parallel_calls_for_one_key_share_a_run() {
+        let coalescer: Coalescer<&str, usize> = Coalescer::new(4);
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        // As if triage, entity, and stats were called at once for one target
+        let calls = (0..3).map(|_| {
+            let coalescer = coalescer.clone();
+            let runs = runs.clone();
+            tokio::spawn(async move {
+                let mut waiter = coalescer
+                    .join("repo@HEAD", move |report| {
+                        runs.fetch_add(1, Ordering::SeqCst);
+                        Ok(slow_analysis(report, Duration::from_millis(20)))
+                    })
+                    .await;
+                waiter.wait(Duration::from_secs(10), |_| async {}).await
+            })
+        });
+
+        for call in calls.collect::<Vec<_>>() {
+            assert_eq!(call.await.unwrap(), Some(Ok(42)));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn queued_runs_report_position() {
+        let coalescer: Coalescer<&str, usize> = Coalescer::new(1);
+
+        let mut first = coalescer
+            .join("a", |report| Ok(slow_analysis(report, Duration::from_millis(20))))
+            .await;
+        // Let the first run take the only slot
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let mut second = coalescer
+            .join("b", |report| Ok(slow_analysis(report, Duration::from_millis(1))))
+            .await;
+
+        let seen = Arc::new(StdMutex::new(Vec::new()));
+        let sink = seen.clone();
+        let (a, b) = tokio::join!(
+            first.wait(Duration::from_secs(10), |_| async {}),
+            second.wait(Duration::from_secs(10), |u| {
+                sink.lock().unwrap().push(u);
+                async {}
+            }),
+        );
+        assert_eq!(a, Some(Ok(42)));
+        assert_eq!(b, Some(Ok(42)));
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0], Update::Queued { position: 1 });
+        assert!(matches!(seen.last(), Some(Update::Progress(_))));
+    }
+}
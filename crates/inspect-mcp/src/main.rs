@@ -1,3 +1,9 @@
+mod cache;
+mod config;
+mod errors;
+mod inflight;
+mod paging;
+mod progress;
 mod server;
 mod tools;
 
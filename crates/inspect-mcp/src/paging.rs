@@ -0,0 +1,174 @@
+use inspect_core::types::EntityReview;
+
+/// Default page size for inspect_triage.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// The page size a call asked for, [`DEFAULT_LIMIT`] if none. A limit of 0
+/// would return an empty page whose `next_offset` never moves.
+pub fn page_limit(limit: Option<usize>) -> Result<usize, String> {
+    match limit {
+        None => Ok(DEFAULT_LIMIT),
+        Some(0) => Err("limit must be at least 1".into()),
+        Some(limit) => Ok(limit),
+    }
+}
+
+/// Ordering for paged entity lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntitySort {
+    /// Highest risk score first
+    Risk,
+    /// By file path, then position in the file
+    File,
+}
+
+impl EntitySort {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "risk" => Ok(Self::Risk),
+            "file" => Ok(Self::File),
+            other => Err(format!("sort must be 'risk' or 'file', got '{}'", other)),
+        }
+    }
+}
+
+/// How much of each entity a paged list includes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityFields {
+    /// Name, file, risk, score and group id
+    Minimal,
+    /// Everything triage knows, short of content
+    Full,
+}
+
+impl EntityFields {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "minimal" => Ok(Self::Minimal),
+            "full" => Ok(Self::Full),
+            other => Err(format!("fields must be 'minimal' or 'full', got '{}'", other)),
+        }
+    }
+}
+
+/// One page of entities plus what's needed to fetch the next.
+pub struct Page<'a> {
+    pub items: Vec<&'a EntityReview>,
+    pub total: usize,
+    pub next_offset: Option<usize>,
+}
+
+/// Sort and slice entities. Ties are broken by file, line, and entity id so
+/// page boundaries are stable across calls on the same result. Pages hold
+/// at least one entity, so `next_offset` always moves forward.
+pub fn paginate<'a>(
+    mut items: Vec<&'a EntityReview>,
+    sort: EntitySort,
+    offset: usize,
+    limit: usize,
+) -> Page<'a> {
+    items.sort_by(|a, b| {
+        let position = (&a.file_path, a.start_line, &a.entity_id)
+            .cmp(&(&b.file_path, b.start_line, &b.entity_id));
+        match sort {
+            EntitySort::Risk => b.risk_score.total_cmp(&a.risk_score).then(position),
+            EntitySort::File => position,
+        }
+    });
+
+    let total = items.len();
+    let end = offset.saturating_add(limit.max(1)).min(total);
+    let page: Vec<&EntityReview> = items.get(offset..end).map(|s| s.to_vec()).unwrap_or_default();
+    let next_offset = if end < total { Some(end) } else { None };
+
+    Page {
+        items: page,
+        total,
+        next_offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_review(i: usize) -> EntityReview {
+        EntityReview {
+            entity_id: format!("e{:03}", i),
+            entity_name: format!("f{}", i),
+            file_path: format!("src/m{}.rs", i % 7),
+            // Only 10 distinct scores, so ordering relies on tie-breaking
+            risk_score: (i % 10) as f64 / 10.0,
+            start_line: i,
+            end_line: i + 5,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn collect_pages(reviews: &[EntityReview], sort: EntitySort) -> Vec<String> {
+        let mut ids = Vec::new();
+        let mut offset = Some(0);
+        while let Some(o) = offset {
+            let page = paginate(reviews.iter().collect(), sort, o, 50);
+            assert_eq!(page.total, 200);
+            assert!(page.items.len() <= 50);
+            ids.extend(page.items.iter().map(|r| r.entity_id.clone()));
+            offset = page.next_offset;
+        }
+        ids
+    }
+
+    #[test]
+    fn page_boundaries() {
+        let reviews: Vec<EntityReview> = (0..200).map(make_review).collect();
+
+        let first = paginate(reviews.iter().collect(), EntitySort::Risk, 0, 50);
+        assert_eq!(first.items.len(), 50);
+        assert_eq!(first.next_offset, Some(50));
+
+        let last = paginate(reviews.iter().collect(), EntitySort::Risk, 150, 50);
+        assert_eq!(last.items.len(), 50);
+        assert_eq!(last.next_offset, None);
+
+        let past_end = paginate(reviews.iter().collect(), EntitySort::Risk, 500, 50);
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.next_offset, None);
+    }
+
+    #[test]
+    fn zero_limit_is_rejected_and_never_stalls_the_cursor() {
+        assert_eq!(page_limit(None), Ok(DEFAULT_LIMIT));
+        assert_eq!(page_limit(Some(5)), Ok(5));
+        assert!(page_limit(Some(0)).is_err());
+
+        let reviews: Vec<EntityReview> = (0..3).map(make_review).collect();
+        let page = paginate(reviews.iter().collect(), EntitySort::Risk, 1, 0);
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.next_offset, Some(2));
+    }
+
+    #[test]
+    fn pages_cover_every_entity_once_in_stable_order() {
+        let reviews: Vec<EntityReview> = (0..200).map(make_review).collect();
+        let mut reversed: Vec<EntityReview> = (0..200).map(make_review).collect();
+        reversed.reverse();
+
+        for sort in [EntitySort::Risk, EntitySort::File] {
+            let ids = collect_pages(&reviews, sort);
+            assert_eq!(ids.len(), 200);
+            let mut unique = ids.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), 200);
+
+            // Input order must not affect page contents
+            assert_eq!(ids, collect_pages(&reversed, sort));
+        }
+
+        let page = paginate(reviews.iter().collect(), EntitySort::Risk, 0, 200);
+        assert!(page
+            .items
+            .windows(2)
+            .all(|w| w[0].risk_score >= w[1].risk_score));
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use inspect_core::analyze::{Phase, Progress};
+use rmcp::model::{ProgressNotificationParam, ProgressToken};
+
+use crate::inflight::Update;
+
+/// Soft deadline for a full analysis when INSPECT_MCP_DEADLINE_SECS is unset.
+pub const DEFAULT_DEADLINE: Duration = Duration::from_secs(60);
+
+/// Map a progress event onto one scale of 0..=Phase::COUNT that only increases
+/// over a run: whole units per finished phase, plus the fraction of the current one.
+pub fn progress_value(p: &Progress) -> f64 {
+    let fraction = if p.total == 0 {
+        0.0
+    } else {
+        p.done as f64 / p.total as f64
+    };
+    p.phase.index() as f64 + fraction
+}
+
+/// Total on the progress_value scale.
+pub fn progress_total() -> f64 {
+    Phase::COUNT as f64
+}
+
+/// Progress notification for an analysis update. Queued analyses report
+/// zero progress with their position in the message.
+pub fn notification(token: ProgressToken, update: &Update) -> ProgressNotificationParam {
+    let (progress, message) = match update {
+        Update::Queued { position } => (0.0, format!("queued ({} ahead)", position - 1)),
+        Update::Progress(p) => (progress_value(p), format!("{} {}/{}", p.phase, p.done, p.total)),
+    };
+    ProgressNotificationParam {
+        progress_token: token,
+        progress,
+        total: Some(progress_total()),
+        message: Some(message),
+    }
+}
+
+/// Soft deadline from INSPECT_MCP_DEADLINE_SECS, falling back to the default.
+pub fn deadline() -> Duration {
+    std::env::var("INSPECT_MCP_DEADLINE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DEADLINE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> ProgressToken {
+        serde_json::from_value(serde_json::json!("t")).unwrap()
+    }
+
+    #[test]
+    fn values_increase_across_phases() {
+        let events = [
+            Progress { phase: Phase::Diff, done: 0, total: 3 },
+            Progress { phase: Phase::Diff, done: 3, total: 3 },
+            Progress { phase: Phase::ListFiles, done: 1, total: 1 },
+            Progress { phase: Phase::Graph, done: 0, total: 0 },
+            Progress { phase: Phase::Scoring, done: 5, total: 10 },
+            Progress { phase: Phase::Scoring, done: 10, total: 10 },
+        ];
+        let values: Vec<f64> = events.iter().map(progress_value).collect();
+        assert!(values.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(values[5], progress_total());
+    }
+
+    #[test]
+    fn queued_notification_reports_position() {
+        let n = notification(token(), &Update::Queued { position: 3 });
+        assert_eq!(n.progress, 0.0);
+        assert_eq!(n.message.as_deref(), Some("queued (2 ahead)"));
+
+        let p = Progress { phase: Phase::Scoring, done: 1, total: 4 };
+        let n = notification(token(), &Update::Progress(p));
+        assert_eq!(n.progress, 3.25);
+        assert_eq!(n.message.as_deref(), Some("scoring 1/4"));
+    }
+}
@@ -1,100 +1,1121 @@
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
 use rmcp::handler::server::router::tool::ToolRouter;
 use rmcp::handler::server::wrapper::Parameters;
 use rmcp::model::{CallToolResult, Content, ServerCapabilities, ServerInfo};
-use rmcp::{tool, tool_handler, tool_router, ServerHandler};
+use rmcp::service::RequestContext;
+use rmcp::{tool, tool_handler, tool_router, ErrorData, RoleServer, ServerHandler};
 use sem_core::git::types::DiffScope;
+use sem_core::parser::graph::EntityGraph;
 use tokio::sync::Mutex;
 
-use inspect_core::analyze::{analyze, analyze_remote, AnalyzeError};
-use inspect_core::github::{CreateReview, GitHubClient, ReviewCommentInput};
+use inspect_core::analyze::{
+    analyze_fast, analyze_remote, analyze_with_progress, rescore, AnalyzeError, AnalyzeOptions, Progress,
+};
+use inspect_core::filter::{find_reviews, sort_reviews, EntityFilter, SortKey};
+use inspect_core::compare::{compare, DeltaEntity};
+use inspect_core::files::{changed_file_contents, list_changed_files, summarize_files};
+use inspect_core::github::{
+    is_duplicate_comment, CreateReview, GitHubClient, GitHubError, ReviewCommentInput,
+    DEFAULT_SEARCH_PAGES,
+};
+use inspect_core::graph::{build_graph, find_entities, forget_graphs, impact, neighborhood, Direction, GraphEntity};
+use inspect_core::graph_cache::GraphCache;
 use inspect_core::noise::is_noise_file;
-use inspect_core::patch::{commentable_lines, parse_patch};
-use inspect_core::risk::suggest_verdict;
+use inspect_core::patch::{commentable_lines, nearest_commentable_line, parse_patch, unified_diff};
+use inspect_core::report::{markdown, InlineDiffs, Listing};
+use inspect_core::risk::{
+    explain_verdict, suggest_verdict, ReviewVerdict, RiskConfig, VerdictReport, CONFIG_FILE,
+};
+use inspect_core::riskmap::{risk_map, RiskMapBy};
+use inspect_core::scope::{parse_target, Target};
 use inspect_core::search;
-use inspect_core::types::{ReviewResult, RiskLevel};
-
+use inspect_core::types::{EntityEdge, EntityReview, ReviewResult, RiskLevel, WarningKind};
+
+use crate::cache::{
+    resolve_key, AnalysisCache, CacheKey, PrFileCache, PrFileKey, RemoteCache, RemoteKey, RunKey, DEFAULT_CAPACITY,
+};
+use crate::config::SessionConfig;
+use crate::errors::{
+    check_repo_slug, closest_names, coded_invalid_params, github_error, internal_err, invalid_params, tool_error,
+    ENTITY_NOT_FOUND, GROUP_NOT_FOUND, INVALID_TARGET, NOT_A_REPO, SUGGESTION_COUNT,
+};
+use crate::paging::{page_limit, paginate, EntityFields, EntitySort};
+use crate::inflight::{max_concurrent, Coalescer};
+use crate::progress;
 use crate::tools::*;
 
-/// Cached analysis result keyed by (repo_path, target).
-struct CachedResult {
-    key: (String, String),
-    result: ReviewResult,
-}
-
 #[derive(Clone)]
 pub struct InspectServer {
-    cache: Arc<Mutex<Option<CachedResult>>>,
+    cache: Arc<Mutex<AnalysisCache>>,
+    analyses: Coalescer<RunKey, ReviewResult, RunError>,
+    config: Arc<Mutex<SessionConfig>>,
+    /// Whole-repo graphs of clean trees kept across server restarts, under
+    /// the in-memory cache in `inspect_core::graph`
+    disk_graphs: Option<GraphCache>,
+    pr_files: Arc<Mutex<PrFileCache>>,
+    /// inspect_pr analyses, by PR head commit
+    remote: Arc<Mutex<RemoteCache>>,
     tool_router: ToolRouter<Self>,
 }
 
-fn parse_scope(target: &str) -> DiffScope {
-    if target == "working" {
-        DiffScope::Working
-    } else if target.contains("..") {
-        let parts: Vec<&str> = target.split("..").collect();
-        DiffScope::Range {
-            from: parts[0].to_string(),
-            to: parts[1].to_string(),
+/// Max distance (in lines) a review comment may be moved to land on the diff.
+const REANCHOR_WINDOW: u64 = 3;
+
+/// Upper bound on inspect_dependents and inspect_impact traversal depth.
+const MAX_GRAPH_DEPTH: usize = 5;
+
+/// Hops of dependents inspect_impact follows by default.
+const DEFAULT_IMPACT_DEPTH: usize = 3;
+
+/// Entities inspect_report lists by default.
+const DEFAULT_REPORT_ENTITIES: usize = 50;
+
+/// Lines each inline diff in inspect_report is cut to.
+const REPORT_DIFF_LINES: usize = 40;
+
+/// Default cap on matches returned by inspect_search.
+const DEFAULT_MAX_MATCHES: usize = 100;
+
+/// Most matches inspect_search returns, whatever max_results asks for, since
+/// the response goes into the model's context.
+const MAX_MATCHES: usize = 200;
+
+/// Default and largest number of context lines around inspect_search matches.
+const DEFAULT_CONTEXT_LINES: usize = 2;
+const MAX_CONTEXT_LINES: usize = 5;
+
+/// INVALID_TARGET error naming the target.
+fn invalid_target(target: &str, msg: impl ToString) -> ErrorData {
+    coded_invalid_params(INVALID_TARGET, msg, serde_json::json!({ "target": target }))
+}
+
+/// An analysis failure as a bad param: git failures are reported against
+/// the target, bad repo config or unreadable files as plain invalid_params.
+fn analyze_error(target: &str, e: AnalyzeError) -> ErrorData {
+    match e {
+        AnalyzeError::Git(_) => invalid_target(target, e),
+        AnalyzeError::Config(_) | AnalyzeError::Io(_) => invalid_params(e),
+    }
+}
+
+/// Why a shared analysis run failed.
+#[derive(Debug, Clone)]
+enum RunError {
+    Analyze(AnalyzeError),
+    /// The run's task died, e.g. panicked
+    Task(String),
+}
+
+impl From<AnalyzeError> for RunError {
+    fn from(e: AnalyzeError) -> Self {
+        RunError::Analyze(e)
+    }
+}
+
+impl From<String> for RunError {
+    fn from(msg: String) -> Self {
+        RunError::Task(msg)
+    }
+}
+
+impl RunError {
+    fn into_error(self, target: &str) -> ErrorData {
+        match self {
+            RunError::Analyze(e) => analyze_error(target, e),
+            RunError::Task(msg) => internal_err(msg),
+        }
+    }
+}
+
+fn parse_target_param(target: &str) -> Result<Target, ErrorData> {
+    parse_target(target).map_err(|e| invalid_target(target, e))
+}
+
+/// Parse a target and resolve it against the repo. Three-dot ranges run
+/// `git merge-base`, so an unknown ref there is reported as a bad param.
+async fn resolve_scope(repo_path: &str, target: &str) -> Result<DiffScope, ErrorData> {
+    let parsed = parse_target_param(target)?;
+    let repo = PathBuf::from(repo_path);
+    tokio::task::spawn_blocking(move || parsed.resolve(&repo))
+        .await
+        .map_err(internal_err)?
+        .map_err(|e| invalid_target(target, e))
+}
+
+/// Validate filter arguments shared by the entity-listing tools.
+fn entity_filter(
+    min_risk: Option<&str>,
+    min_score: Option<f64>,
+    classification: Option<&[String]>,
+) -> Result<EntityFilter, ErrorData> {
+    EntityFilter::parse(min_risk, min_score, classification).map_err(invalid_params)
+}
+
+/// Echo of the filters a response was computed with.
+fn filters_json(filter: &EntityFilter) -> serde_json::Value {
+    serde_json::json!({
+        "min_risk": filter.min_risk.map(|r| r.to_string()),
+        "min_score": filter.min_score,
+        "classification": filter
+            .classifications
+            .as_ref()
+            .map(|cs| cs.iter().map(|c| c.to_string()).collect::<Vec<_>>()),
+        "hide_tests": filter.hide_tests,
+    })
+}
+
+/// Reject repo paths that aren't absolute paths into a git work tree before
+/// shelling out to git. A `.git` entry in the path or a parent (a file, for
+/// worktrees and submodules) is enough; git reports anything subtler.
+fn check_repo_path(repo_path: &str) -> Result<(), ErrorData> {
+    let path = Path::new(repo_path);
+    let problem = if !path.is_absolute() {
+        "is not an absolute path"
+    } else if !path.is_dir() {
+        "is not a directory"
+    } else if !path.ancestors().any(|dir| dir.join(".git").exists()) {
+        "is not inside a git repository"
+    } else {
+        return Ok(());
+    };
+    Err(coded_invalid_params(
+        NOT_A_REPO,
+        format!("repo_path '{}' {}", repo_path, problem),
+        serde_json::json!({ "repo_path": repo_path }),
+    ))
+}
+
+/// Cache capacity from INSPECT_MCP_CACHE_SIZE, falling back to the default.
+fn cache_capacity() -> usize {
+    std::env::var("INSPECT_MCP_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CAPACITY)
+}
+
+/// On-disk graph cache from INSPECT_MCP_GRAPH_CACHE: `1` for the default
+/// location or a directory; off when unset.
+fn disk_graph_cache() -> Option<GraphCache> {
+    std::env::var("INSPECT_MCP_GRAPH_CACHE")
+        .ok()
+        .and_then(|v| GraphCache::from_setting(&v))
+}
+
+/// Find a changed entity by name, optionally narrowed by file path suffix.
+fn find_entity<'a>(
+    result: &'a ReviewResult,
+    entity_name: &str,
+    file_path: Option<&str>,
+) -> Option<&'a EntityReview> {
+    find_reviews(result, entity_name, file_path).into_iter().next()
+}
+
+/// Tool error for an entity missing from the diff, suggesting the closest changed names.
+fn entity_not_found(result: &ReviewResult, entity_name: &str) -> CallToolResult {
+    let names = result.entity_reviews.iter().map(|r| r.entity_name.as_str());
+    tool_error(
+        format!("Entity '{}' not found in changes", entity_name),
+        serde_json::json!({
+            "code": ENTITY_NOT_FOUND,
+            "changed_entities": result.entity_reviews.len(),
+            "did_you_mean": closest_names(entity_name, names, SUGGESTION_COUNT),
+        }),
+    )
+}
+
+/// The one graph entity `name` (narrowed by `file_path`) refers to, else a
+/// tool error suggesting close names or listing the candidates to pick from.
+fn graph_entity(graph: &EntityGraph, name: &str, file_path: Option<&str>) -> Result<GraphEntity, CallToolResult> {
+    let mut candidates = find_entities(graph, name, file_path);
+    match candidates.len() {
+        0 => {
+            let names = graph.entities.values().map(|e| e.name.as_str());
+            Err(tool_error(
+                format!("Entity '{}' not found in the repository", name),
+                serde_json::json!({
+                    "code": ENTITY_NOT_FOUND,
+                    "did_you_mean": closest_names(name, names, SUGGESTION_COUNT),
+                }),
+            ))
         }
+        1 => Ok(candidates.remove(0)),
+        _ => Err(tool_error(
+            format!("'{}' is ambiguous; pass file_path to pick one", name),
+            serde_json::json!({ "candidates": candidates }),
+        )),
+    }
+}
+
+/// Tool error for a group id that doesn't exist, listing the groups that do
+/// and the range of their ids (null when there are none).
+fn group_not_found(result: &ReviewResult, group_id: usize) -> CallToolResult {
+    let groups: Vec<serde_json::Value> = result
+        .groups
+        .iter()
+        .map(|g| serde_json::json!({ "id": g.id, "label": g.label }))
+        .collect();
+    let ids = result.groups.iter().map(|g| g.id);
+    let valid_range = ids.clone().min().zip(ids.max()).map(|(min, max)| [min, max]);
+    tool_error(
+        format!("Group {} not found", group_id),
+        serde_json::json!({
+            "code": GROUP_NOT_FOUND,
+            "valid_range": valid_range,
+            "groups": groups,
+        }),
+    )
+}
+
+/// inspect_report body: the CLI's markdown report over the filtered entities,
+/// riskiest first and cut to `max_entities` (0 keeps them all). Stats and
+/// groups still cover every filtered entity.
+fn report_markdown(result: &ReviewResult, filter: &EntityFilter, max_entities: usize, include_diffs: bool) -> String {
+    let mut result = filter.filter_result(result);
+    sort_reviews(&mut result.entity_reviews, SortKey::Risk);
+    let mut omitted = 0;
+    if max_entities > 0 && result.entity_reviews.len() > max_entities {
+        omitted = result.entity_reviews.len() - max_entities;
+        result.entity_reviews.truncate(max_entities);
+    }
+    let listing = Listing {
+        omitted,
+        more_hint: "raise max_entities to see them",
+        ..Listing::default()
+    };
+    let diffs = InlineDiffs {
+        min_risk: include_diffs.then_some(RiskLevel::High),
+        max_lines: REPORT_DIFF_LINES,
+    };
+    markdown(&result, true, listing, diffs)
+}
+
+/// inspect_triage body. Stats and groups describe every filtered entity, not
+/// just the page; the verdict covers the whole change.
+fn triage_output(
+    result: &ReviewResult,
+    filter: &EntityFilter,
+    sort: EntitySort,
+    fields: EntityFields,
+    offset: usize,
+    limit: usize,
+) -> serde_json::Value {
+    let verdict = suggest_verdict(result);
+    let filtered = filter.filter_result(result);
+    let stats = &filtered.stats;
+
+    let page = paginate(filtered.entity_reviews.iter().collect(), sort, offset, limit);
+
+    let entities: Vec<serde_json::Value> = page
+        .items
+        .iter()
+        .map(|r| match fields {
+            EntityFields::Minimal => serde_json::json!({
+                "name": r.entity_name,
+                "file": r.file_path,
+                "risk": format!("{}", r.risk_level),
+                "score": format!("{:.2}", r.risk_score),
+                "group_id": r.group_id,
+            }),
+            EntityFields::Full => serde_json::json!({
+                "name": r.entity_name,
+                "type": r.entity_type,
+                "file": r.file_path,
+                "risk": format!("{}", r.risk_level),
+                "score": format!("{:.2}", r.risk_score),
+                "classification": format!("{}", r.classification),
+                "blast_radius": r.blast_radius,
+                "change_type": format!("{:?}", r.change_type).to_lowercase(),
+                "public_api": r.is_public_api,
+                "cosmetic": r.structural_change == Some(false),
+                "test": r.is_test,
+                "group_id": r.group_id,
+                "risk_rule": r.risk_rule,
+            }),
+        })
+        .collect();
+
+    let groups: Vec<serde_json::Value> = filtered
+        .groups
+        .iter()
+        .map(|g| {
+            serde_json::json!({
+                "id": g.id,
+                "label": g.label,
+                "entity_count": g.entity_ids.len(),
+                "classification": format!("{}", g.dominant_classification),
+                "max_risk": format!("{}", g.max_risk),
+                "max_score": format!("{:.2}", g.max_score),
+                "has_public_api": g.has_public_api,
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "verdict": format!("{}", verdict),
+        "stats": {
+            "total_entities": stats.total_entities,
+            "critical": stats.by_risk.critical,
+            "high": stats.by_risk.high,
+            "medium": stats.by_risk.medium,
+            "low": stats.by_risk.low,
+        },
+        "total_before_filter": result.stats.total_entities,
+        "generated_suppressed": result.stats.generated_entities,
+        "filters": filters_json(filter),
+        "total_entities_matching": page.total,
+        "returned": entities.len(),
+        "next_offset": page.next_offset,
+        "entities": entities,
+        "groups": groups,
+        "timing_ms": result.timing.total_ms,
+    })
+}
+
+/// Share of changed source files left unanalyzed at which inspect_stats
+/// reports degraded confidence.
+const DEGRADED_FILE_SHARE: f64 = 0.25;
+
+/// inspect_stats body. `confidence` is "degraded" when the graph was skipped
+/// or enough files went unanalyzed that a quiet result may just mean little was scored.
+fn stats_output(result: &ReviewResult) -> serde_json::Value {
+    let verdict = suggest_verdict(result);
+    let graph_skipped = result.warnings.iter().any(|w| w.kind == WarningKind::GraphSkipped);
+    // Files no parser handles were never going to yield entities
+    let unanalyzed = result
+        .warnings
+        .iter()
+        .filter(|w| w.file_path.is_some() && w.kind != WarningKind::Unsupported)
+        .count();
+    let analyzed: HashSet<&str> = result.entity_reviews.iter().map(|r| r.file_path.as_str()).collect();
+    let unanalyzed_share = if unanalyzed == 0 {
+        0.0
     } else {
-        DiffScope::Commit {
-            sha: target.to_string(),
+        unanalyzed as f64 / (unanalyzed + analyzed.len()) as f64
+    };
+    let confidence = if graph_skipped || unanalyzed_share >= DEGRADED_FILE_SHARE {
+        "degraded"
+    } else {
+        "full"
+    };
+
+    serde_json::json!({
+        "verdict": format!("{}", verdict),
+        "total_entities": result.stats.total_entities,
+        "generated_entities": result.stats.generated_entities,
+        "risk": {
+            "critical": result.stats.by_risk.critical,
+            "high": result.stats.by_risk.high,
+            "medium": result.stats.by_risk.medium,
+            "low": result.stats.by_risk.low,
+        },
+        "classification": {
+            "text": result.stats.by_classification.text,
+            "syntax": result.stats.by_classification.syntax,
+            "functional": result.stats.by_classification.functional,
+            "mixed": result.stats.by_classification.mixed,
+        },
+        "change_types": {
+            "added": result.stats.by_change_type.added,
+            "modified": result.stats.by_change_type.modified,
+            "deleted": result.stats.by_change_type.deleted,
+            "moved": result.stats.by_change_type.moved,
+            "renamed": result.stats.by_change_type.renamed,
+        },
+        "groups": result.groups.len(),
+        "timing_ms": result.timing.total_ms,
+        "timing": result.timing,
+        "warnings": result.warnings,
+        "unanalyzed_files": unanalyzed,
+        "confidence": confidence,
+    })
+}
+
+/// Suggested next step for a verdict, pointing at the entity that decided it.
+fn next_action(result: &ReviewResult, report: &VerdictReport) -> String {
+    let top = report
+        .top_entity_id
+        .as_deref()
+        .and_then(|id| result.entity_reviews.iter().find(|r| r.entity_id == id));
+    match (report.verdict, top) {
+        (ReviewVerdict::RequiresCarefulReview | ReviewVerdict::RequiresReview, Some(top)) => format!(
+            "drill into group {} with inspect_group, starting at {} ({})",
+            top.group_id, top.entity_name, top.file_path
+        ),
+        (ReviewVerdict::LikelyApprovable, _) => "spot-check with inspect_diff, then approve".into(),
+        (_, Some(top)) => format!(
+            "review with inspect_triage, starting at {} ({})",
+            top.entity_name, top.file_path
+        ),
+        (_, None) => "nothing to review at entity level; check inspect_changed_files".into(),
+    }
+}
+
+/// How a tool call's analysis was obtained.
+struct RunInfo {
+    elapsed_ms: u64,
+    /// Set when the result is degraded (e.g. fast mode after the deadline)
+    warning: Option<String>,
+    /// Whether the analysis came from the cache; None when the cache wasn't consulted
+    cache_hit: Option<bool>,
+}
+
+impl RunInfo {
+    fn new(start: Instant, warning: Option<String>) -> Self {
+        Self {
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            warning,
+            cache_hit: None,
+        }
+    }
+
+    fn cached(self, hit: bool) -> Self {
+        Self {
+            cache_hit: Some(hit),
+            ..self
         }
     }
 }
 
-fn parse_risk_level(s: &str) -> RiskLevel {
-    match s.to_lowercase().as_str() {
-        "critical" => RiskLevel::Critical,
-        "high" => RiskLevel::High,
-        "medium" => RiskLevel::Medium,
-        _ => RiskLevel::Low,
+/// The call's elapsed time, cache hit or miss, and any warning.
+fn run_fields(run: &RunInfo) -> serde_json::Map<String, serde_json::Value> {
+    let mut extra = serde_json::Map::new();
+    extra.insert("elapsed_ms".into(), run.elapsed_ms.into());
+    if let Some(hit) = run.cache_hit {
+        extra.insert("cache".into(), if hit { "hit" } else { "miss" }.into());
+    }
+    if let Some(ref warning) = run.warning {
+        extra.insert("warning".into(), warning.clone().into());
+    }
+    extra
+}
+
+/// Serialize tool output, adding [`run_fields`]. Object outputs get them as
+/// fields; other outputs get a second content item.
+fn respond(mut output: serde_json::Value, run: &RunInfo) -> CallToolResult {
+    let extra = run_fields(run);
+    let content = match output.as_object_mut() {
+        Some(obj) => {
+            obj.extend(extra);
+            vec![Content::text(serde_json::to_string_pretty(&output).unwrap_or_default())]
+        }
+        None => vec![
+            Content::text(serde_json::to_string_pretty(&output).unwrap_or_default()),
+            Content::text(serde_json::to_string_pretty(&extra).unwrap_or_default()),
+        ],
+    };
+    CallToolResult::success(content)
+}
+
+/// The review event to submit: COMMENT unless the caller asks for APPROVE or
+/// REQUEST_CHANGES.
+fn review_event(event: Option<&str>) -> Result<String, ErrorData> {
+    let Some(event) = event else {
+        return Ok("COMMENT".into());
+    };
+    let upper = event.to_ascii_uppercase();
+    match upper.as_str() {
+        "COMMENT" | "APPROVE" | "REQUEST_CHANGES" => Ok(upper),
+        _ => Err(invalid_params(format!(
+            "event must be COMMENT, APPROVE or REQUEST_CHANGES, got '{}'",
+            event
+        ))),
     }
 }
 
-fn internal_err(msg: impl ToString) -> rmcp::ErrorData {
-    rmcp::ErrorData::internal_error(msg.to_string(), None)
+/// A comment that won't be posted inline, with the commentable line closest
+/// to the one it asked for.
+fn rejected_comment(c: &ReviewComment, reason: &str, commentable: Option<&Vec<u64>>) -> serde_json::Value {
+    let suggested_line = commentable.and_then(|cl| nearest_commentable_line(cl, c.line, u64::MAX));
+    serde_json::json!({
+        "path": c.path,
+        "line": c.line,
+        "reason": reason,
+        "suggested_line": suggested_line,
+    })
+}
+
+/// Validate `params.comments` against the PR's patches and post them as one
+/// review (or preview it on dry_run). Comments off the diff go into the review
+/// body; comments with a bad line range aren't posted at all.
+async fn post_review(client: &GitHubClient, params: PostReviewParams) -> Result<CallToolResult, ErrorData> {
+    let event = review_event(params.event.as_deref())?;
+    let dry_run = params.dry_run.unwrap_or(false);
+
+    let pr = match client.get_pr_with_patches(&params.repo, params.pr_number).await {
+        Ok(pr) => pr,
+        Err(e) => return github_error(e),
+    };
+    let existing = if params.allow_duplicates.unwrap_or(false) {
+        Vec::new()
+    } else {
+        match client.list_review_comments(&params.repo, params.pr_number).await {
+            Ok(existing) => existing,
+            Err(e) => return github_error(e),
+        }
+    };
+
+    let file_commentable: HashMap<String, Vec<u64>> = pr
+        .files
+        .iter()
+        .map(|f| {
+            let hunks = f.patch.as_deref().map(parse_patch).unwrap_or_default();
+            let cl = commentable_lines(&hunks);
+            (f.filename.clone(), cl)
+        })
+        .collect();
+
+    let mut dispositions = Vec::new();
+    let mut rejected = Vec::new();
+    let mut demoted = Vec::new();
+    let mut valid_comments = Vec::new();
+
+    for c in &params.comments {
+        let commentable = file_commentable.get(&c.path);
+        if c.start_line.is_some_and(|s| s >= c.line) {
+            let reason = "start_line must be before line";
+            rejected.push(rejected_comment(c, reason, commentable));
+            dispositions.push(serde_json::json!({
+                "path": c.path,
+                "line": c.line,
+                "status": "rejected",
+                "reason": reason,
+            }));
+            continue;
+        }
+        let anchored = commentable.map(|cl| (cl, nearest_commentable_line(cl, c.line, REANCHOR_WINDOW)));
+
+        match anchored {
+            Some((cl, Some(line))) => {
+                // Drop a start_line that no longer forms a valid range
+                let start_line = c.start_line.filter(|s| *s < line && cl.contains(s));
+                let comment = ReviewCommentInput {
+                    path: c.path.clone(),
+                    line,
+                    body: c.body.clone(),
+                    start_line,
+                };
+                if is_duplicate_comment(&comment, &existing) {
+                    dispositions.push(serde_json::json!({
+                        "path": c.path,
+                        "line": c.line,
+                        "status": "duplicate",
+                    }));
+                    continue;
+                }
+                valid_comments.push(comment);
+                let status = if line == c.line { "anchored" } else { "reanchored" };
+                dispositions.push(serde_json::json!({
+                    "path": c.path,
+                    "line": c.line,
+                    "status": status,
+                    "posted_line": line,
+                }));
+            }
+            Some((_, None)) | None => {
+                let reason = if anchored.is_some() {
+                    "line not in diff"
+                } else {
+                    "not a changed file"
+                };
+                demoted.push(format!("- `{}:{}` ({}): {}", c.path, c.line, reason, c.body));
+                rejected.push(rejected_comment(c, reason, commentable));
+                dispositions.push(serde_json::json!({
+                    "path": c.path,
+                    "line": c.line,
+                    "status": "demoted",
+                    "reason": reason,
+                }));
+            }
+        }
+    }
+
+    if valid_comments.is_empty() && demoted.is_empty() {
+        return Ok(CallToolResult::success(vec![Content::text(
+            serde_json::json!({
+                "error": "no comments to post",
+                "comments": dispositions,
+                "rejected": rejected,
+            })
+            .to_string(),
+        )]));
+    }
+
+    let mut body = params.body.unwrap_or_else(|| "Review from inspect".into());
+    if !demoted.is_empty() {
+        body.push_str("\n\n**Comments outside the diff**\n\n");
+        body.push_str(&demoted.join("\n"));
+    }
+
+    let review = CreateReview {
+        commit_id: pr.head_sha,
+        event,
+        body,
+        comments: valid_comments,
+    };
+
+    if dry_run {
+        let output = serde_json::json!({
+            "dry_run": true,
+            "review": review,
+            "comments": dispositions,
+            "rejected": rejected,
+        });
+        return Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&output).unwrap_or_default(),
+        )]));
+    }
+
+    let posted = match client
+        .create_review(&params.repo, params.pr_number, &review, &pr.files)
+        .await
+    {
+        Ok(posted) => posted,
+        Err(e) => return github_error(e),
+    };
+    for skipped in &posted.skipped {
+        let c = &skipped.comment;
+        rejected.push(serde_json::json!({
+            "path": c.path,
+            "line": c.line,
+            "reason": skipped.reason,
+            "suggested_line": null,
+        }));
+    }
+
+    let output = serde_json::json!({
+        "id": posted.id,
+        "html_url": posted.html_url,
+        "event": review.event,
+        "comments": dispositions,
+        "rejected": rejected,
+    });
+
+    Ok(CallToolResult::success(vec![Content::text(
+        serde_json::to_string_pretty(&output).unwrap_or_default(),
+    )]))
 }
 
 impl InspectServer {
-    /// Run analysis, using cache if the key matches.
+    /// Run analysis, reusing a cached result when the target resolves to the
+    /// same commits (or the same dirty working tree) as an earlier run.
+    ///
+    /// Concurrent calls for the same target share one analysis, and at most
+    /// `max_concurrent()` analyses run at once. Queue position and phase
+    /// progress are sent to the client when the request carries a progress
+    /// token. If the full analysis passes the soft deadline, a fast-mode result
+    /// (no entity graph) is returned with a warning; the full analysis keeps
+    /// running and is cached when it finishes.
+    ///
+    /// Results are scored with the session's weights. In session fast mode the
+    /// graph is skipped; fast results are cached and shared apart from full
+    /// ones.
     async fn get_result(
         &self,
         repo_path: &str,
         target: &str,
-    ) -> Result<ReviewResult, AnalyzeError> {
-        // Check cache
-        {
-            let cache = self.cache.lock().await;
-            if let Some(cached) = cache.as_ref() {
-                if cached.key.0 == repo_path && cached.key.1 == target {
-                    return Ok(cached.result.clone());
+        force_refresh: bool,
+        context: &RequestContext<RoleServer>,
+    ) -> Result<(ReviewResult, RunInfo), ErrorData> {
+        let start = Instant::now();
+        check_repo_path(repo_path)?;
+        let scope = resolve_scope(repo_path, target).await?;
+        let key = {
+            let repo_path = repo_path.to_string();
+            let scope = scope.clone();
+            // The repo checked out, so a failure here is a ref git doesn't know
+            tokio::task::spawn_blocking(move || resolve_key(&repo_path, &scope))
+                .await
+                .map_err(internal_err)?
+                .map_err(|e| invalid_target(target, e))?
+        };
+
+        let config = self.config.lock().await.clone();
+        let run = RunKey {
+            target: key,
+            generation: config.generation,
+            fast: config.fast,
+        };
+        if !force_refresh {
+            if let Some(result) = self.cache.lock().await.get(&run) {
+                return Ok((result, RunInfo::new(start, None).cached(true)));
+            }
+        }
+        if config.fast {
+            let result = self.fast_result(&run.target, repo_path, target, scope, &config).await?;
+            return Ok((result, RunInfo::new(start, None).cached(false)));
+        }
+
+        // Run analysis in a blocking task (CPU-bound). The run caches its own
+        // result so it lands even if every caller has hit the deadline.
+        let work = {
+            let repo = PathBuf::from(repo_path);
+            let scope = scope.clone();
+            let cache = self.cache.clone();
+            let session = self.config.clone();
+            let config = config.clone();
+            let key = run.clone();
+            let options = AnalyzeOptions {
+                graph_cache: self.disk_graphs.clone(),
+                ..Default::default()
+            };
+            move |report: &dyn Fn(Progress)| {
+                let mut result = analyze_with_progress(&repo, scope, &options, report)?;
+                if config.custom_weights() {
+                    let risk = RiskConfig::load(&repo).map_err(AnalyzeError::Config)?;
+                    rescore(&mut result, &config.risk_config(risk));
+                }
+                // Weights changed mid-run: the result is stale for later calls
+                let session = session.blocking_lock();
+                if session.generation == config.generation {
+                    cache.blocking_lock().insert(key, result.clone());
+                }
+                Ok(result)
+            }
+        };
+        let mut waiter = self.analyses.join(run.clone(), work).await;
+
+        let token = context.meta.get_progress_token();
+        let peer = context.peer.clone();
+        let deadline = progress::deadline();
+
+        let finished = waiter
+            .wait(deadline, |update| {
+                let token = token.clone();
+                let peer = peer.clone();
+                async move {
+                    if let Some(token) = token {
+                        let _ = peer.notify_progress(progress::notification(token, &update)).await;
+                    }
+                }
+            })
+            .await;
+
+        match finished {
+            Some(result) => Ok((result.map_err(|e| e.into_error(target))?, RunInfo::new(start, None).cached(false))),
+            None => {
+                // The fast run takes a slot like any other, so it may queue
+                // behind the full one; take whichever finishes first
+                let fast = self.fast_result(&run.target, repo_path, target, scope, &config);
+                let result = tokio::select! {
+                    full = waiter.result() => {
+                        let result = full.map_err(|e| e.into_error(target))?;
+                        return Ok((result, RunInfo::new(start, None).cached(false)));
+                    }
+                    fast = fast => fast?,
+                };
+
+                let warning = format!(
+                    "Full analysis exceeded the {}s deadline; returning fast-mode results without the entity graph (blast radius and dependents are 0). The full analysis continues in the background and later calls will use it.",
+                    deadline.as_secs()
+                );
+                Ok((result, RunInfo::new(start, Some(warning)).cached(false)))
+            }
+        }
+    }
+
+    /// Fast-mode analysis (no entity graph), scored with the session's
+    /// weights. Runs through the same coalescer as full analyses, so it waits
+    /// for a free slot and concurrent calls for `key` share one run, and is
+    /// cached under its own key.
+    async fn fast_result(
+        &self,
+        key: &CacheKey,
+        repo_path: &str,
+        target: &str,
+        scope: DiffScope,
+        config: &SessionConfig,
+    ) -> Result<ReviewResult, ErrorData> {
+        let run = RunKey {
+            target: key.clone(),
+            generation: config.generation,
+            fast: true,
+        };
+        let work = {
+            let repo = PathBuf::from(repo_path);
+            let cache = self.cache.clone();
+            let session = self.config.clone();
+            let config = config.clone();
+            let run = run.clone();
+            move |_: &dyn Fn(Progress)| {
+                let mut result = analyze_fast(&repo, scope, &AnalyzeOptions::default())?;
+                if config.custom_weights() {
+                    let risk = RiskConfig::load(&repo).map_err(AnalyzeError::Config)?;
+                    rescore(&mut result, &config.risk_config(risk));
                 }
+                if session.blocking_lock().generation == config.generation {
+                    cache.blocking_lock().insert(run, result.clone());
+                }
+                Ok(result)
             }
+        };
+        self.analyses
+            .join(run, work)
+            .await
+            .result()
+            .await
+            .map_err(|e| e.into_error(target))
+    }
+
+    /// Apply an inspect_configure call, dropping cached results when the
+    /// weights change. Returns the new config and whether the cache was cleared.
+    async fn configure(&self, params: &ConfigureParams) -> Result<(SessionConfig, bool), ErrorData> {
+        let mut config = self.config.lock().await;
+        let weights_changed = config.update(params).map_err(invalid_params)?;
+        if weights_changed {
+            self.cache.lock().await.clear();
         }
+        Ok((config.clone(), weights_changed))
+    }
 
-        // Run analysis in a blocking task (CPU-bound)
+    /// Entity graph for the repo's current working tree, rebuilt only when
+    /// HEAD, the dirty state or the source file list changes. Shares the
+    /// in-memory cache analyses use, so a triage's graph serves
+    /// inspect_dependents too.
+    async fn get_graph(
+        &self,
+        repo_path: &str,
+        force_refresh: bool,
+    ) -> Result<Arc<EntityGraph>, ErrorData> {
+        check_repo_path(repo_path)?;
         let repo = PathBuf::from(repo_path);
-        let scope = parse_scope(target);
-        let result =
-            tokio::task::spawn_blocking(move || analyze(&repo, scope))
-                .await
-                .map_err(|e| AnalyzeError::Git(format!("spawn_blocking failed: {}", e)))??;
+        let disk = self.disk_graphs.clone();
+        tokio::task::spawn_blocking(move || {
+            if force_refresh {
+                forget_graphs(&repo);
+            }
+            build_graph(&repo, disk.as_ref())
+        })
+        .await
+        .map_err(internal_err)?
+        .map_err(internal_err)
+    }
+
+    /// inspect_pr against `client`: the PR's triage, from the cache when the
+    /// PR's head (and base, or since_commit) haven't moved since an earlier call.
+    async fn remote_triage(
+        &self,
+        client: &GitHubClient,
+        params: RemoteTriageParams,
+    ) -> Result<CallToolResult, ErrorData> {
+        let start = Instant::now();
+        let sort = params
+            .sort
+            .as_deref()
+            .map(EntitySort::parse)
+            .transpose()
+            .map_err(invalid_params)?
+            .unwrap_or(EntitySort::Risk);
+        let fields = params
+            .fields
+            .as_deref()
+            .map(EntityFields::parse)
+            .transpose()
+            .map_err(invalid_params)?
+            .unwrap_or(EntityFields::Full);
+        let limit = page_limit(params.limit).map_err(invalid_params)?;
+        let mut filter = entity_filter(params.min_risk.as_deref(), None, None)?;
+        let config = self.config.lock().await.clone();
+        config.apply_defaults(&mut filter, params.hide_tests);
+
+        let pr = match client.get_pr(&params.repo, params.pr_number).await {
+            Ok(pr) => pr,
+            Err(e) => return github_error(e),
+        };
+
+        let (base_sha, files, since) = match params.since_commit {
+            Some(ref since) => match client.pr_since_commit(&params.repo, &pr, since).await {
+                Ok(range) => {
+                    let summary = serde_json::json!({
+                        "commit": range.base_sha,
+                        "new_commits": range.commits,
+                    });
+                    (range.base_sha, range.files, Some(summary))
+                }
+                Err(e) => return github_error(e),
+            },
+            None => (pr.base_sha.clone(), pr.files.clone(), None),
+        };
+
+        let key = RemoteKey {
+            repo: params.repo.clone(),
+            pr_number: params.pr_number,
+            head_sha: pr.head_sha.clone(),
+            base_sha: base_sha.clone(),
+        };
+        let cached = self.remote.lock().await.get(&key);
+        let hit = cached.is_some();
+        let mut result = match cached {
+            Some(result) => result,
+            None => {
+                let visible_files: Vec<_> = files
+                    .iter()
+                    .filter(|f| !is_noise_file(&f.filename))
+                    .cloned()
+                    .collect();
+
+                let file_pairs = match client
+                    .get_file_pairs(&params.repo, &visible_files, &base_sha, &pr.head_sha)
+                    .await
+                {
+                    Ok(pairs) => pairs,
+                    Err(e) => return github_error(e),
+                };
+
+                let result = tokio::task::spawn_blocking(move || analyze_remote(&file_pairs, &[]))
+                    .await
+                    .map_err(internal_err)?
+                    .map_err(internal_err)?;
+                self.remote.lock().await.insert(key, result.clone());
+                result
+            }
+        };
+        if config.custom_weights() {
+            rescore(&mut result, &config.risk_config(RiskConfig::default()));
+        }
+
+        let mut output = triage_output(
+            &result,
+            &filter,
+            sort,
+            fields,
+            params.offset.unwrap_or(0),
+            limit,
+        );
+        output["pr"] = serde_json::json!({
+            "number": pr.number,
+            "title": pr.title,
+            "state": pr.state,
+            "head_sha": pr.head_sha,
+            "additions": pr.additions,
+            "deletions": pr.deletions,
+        });
+        if let Some(since) = since {
+            output["since"] = since;
+        }
 
-        // Cache it
+        Ok(respond(output, &RunInfo::new(start, None).cached(hit)))
+    }
+
+    /// inspect_search against `client`: grep the PR's files at head and, with
+    /// repo_wide, merge in Code Search matches from the rest of the repo.
+    async fn search_pr(&self, client: &GitHubClient, params: SearchParams) -> Result<CallToolResult, ErrorData> {
+        let case_sensitive = params.case_sensitive.unwrap_or(false);
+        let repo_wide = params.repo_wide.unwrap_or(false);
+        let max_results = params.max_results.unwrap_or(DEFAULT_MAX_MATCHES).min(MAX_MATCHES);
+        let context_lines = params.context_lines.unwrap_or(DEFAULT_CONTEXT_LINES).min(MAX_CONTEXT_LINES);
+
+        let regex = params.regex.unwrap_or(false);
+        let matcher = search::build_matcher(&params.pattern, case_sensitive, regex)
+            .map_err(|e| invalid_params(format!("invalid pattern: {}", e)))?;
+        let filter = search::FileFilter::new(
+            params.include.as_deref().unwrap_or_default(),
+            params.exclude.as_deref().unwrap_or_default(),
+        )
+        .map_err(|e| invalid_params(format!("invalid glob: {}", e)))?;
+
+        let pr = match client.get_pr(&params.repo, params.pr_number).await {
+            Ok(pr) => pr,
+            Err(e) => return github_error(e),
+        };
+
+        let file_paths: Vec<String> = pr
+            .files
+            .iter()
+            .filter(|f| !is_noise_file(&f.filename) && filter.matches(&f.filename))
+            .map(|f| f.filename.clone())
+            .collect();
+
+        let pr_files = match self
+            .get_pr_files(client, &params.repo, &pr.head_sha, &file_paths)
+            .await
         {
-            let mut cache = self.cache.lock().await;
-            *cache = Some(CachedResult {
-                key: (repo_path.to_string(), target.to_string()),
-                result: result.clone(),
+            Ok(files) => files,
+            Err(e) => return github_error(e),
+        };
+
+        let mut matches = search::grep_files(&pr_files, &matcher, context_lines);
+
+        let mut code_search = None;
+        if repo_wide {
+            // Code Search doesn't take regexes; its results are re-checked with the matcher
+            code_search = Some(match search::code_search_query(&params.pattern, regex) {
+                None => serde_json::json!({
+                    "skipped": "the regex has no literal text for Code Search to look for",
+                }),
+                Some(query) => match client
+                    .search_code(&params.repo, &query, filter.code_search_path().as_deref(), DEFAULT_SEARCH_PAGES)
+                    .await
+                {
+                    Ok(search_results) => {
+                        let summary = serde_json::json!({
+                            "results": search_results.items.len(),
+                            "total_count": search_results.total_count,
+                            "incomplete": search_results.incomplete_results
+                                || (search_results.items.len() as u64) < search_results.total_count,
+                        });
+                        matches = search::merge_code_search(matches, &search_results, &matcher, &file_paths, &filter);
+                        summary
+                    }
+                    // PR matches are still useful without the rest of the codebase
+                    Err(e) => serde_json::json!({ "error": e.to_string() }),
+                },
             });
         }
 
-        Ok(result)
+        let from_pr = matches.iter().filter(|m| m.source == search::MatchSource::Pr).count();
+        let files: HashSet<&str> = matches.iter().map(|m| m.file.as_str()).collect();
+        let mut output = serde_json::json!({
+            "total_matches": matches.len(),
+            "pr_matches": from_pr,
+            "codebase_matches": matches.len() - from_pr,
+            "files": files.len(),
+            "truncated": matches.len() > max_results,
+            "matches": matches.iter().take(max_results).map(|m| {
+                serde_json::json!({
+                    "file": m.file,
+                    "line": m.line,
+                    "column": m.column,
+                    "text": m.text,
+                    "context_before": m.context_before,
+                    "context_after": m.context_after,
+                    "source": m.source,
+                })
+            }).collect::<Vec<_>>(),
+        });
+        if let Some(code_search) = code_search {
+            output["code_search"] = code_search;
+        }
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&output).unwrap_or_default(),
+        )]))
+    }
+
+    /// Fetch PR file contents at `head_sha`, reusing an earlier fetch of the
+    /// same paths at the same commit.
+    async fn get_pr_files(
+        &self,
+        client: &GitHubClient,
+        repo: &str,
+        head_sha: &str,
+        paths: &[String],
+    ) -> Result<Vec<(String, String)>, GitHubError> {
+        let key = PrFileKey {
+            repo: repo.to_string(),
+            head_sha: head_sha.to_string(),
+            paths: paths.to_vec(),
+        };
+        if let Some(files) = self.pr_files.lock().await.get(&key) {
+            return Ok(files.clone());
+        }
+
+        let files = client.fetch_file_contents(repo, paths, head_sha).await?;
+        self.pr_files.lock().await.insert(key, files.clone());
+        Ok(files)
     }
 }
 
@@ -102,105 +1123,84 @@ impl InspectServer {
 impl InspectServer {
     pub fn new() -> Self {
         Self {
-            cache: Arc::new(Mutex::new(None)),
+            cache: Arc::new(Mutex::new(AnalysisCache::new(cache_capacity()))),
+            analyses: Coalescer::new(max_concurrent()),
+            config: Arc::new(Mutex::new(SessionConfig::default())),
+            disk_graphs: disk_graph_cache(),
+            pr_files: Arc::new(Mutex::new(PrFileCache::new(cache_capacity()))),
+            remote: Arc::new(Mutex::new(RemoteCache::new(cache_capacity()))),
             tool_router: Self::tool_router(),
         }
     }
 
-    #[tool(description = "Run entity-level code review triage. Returns a compact summary of all changed entities sorted by risk score, with classification, blast radius, and logical grouping. This is the primary entry point for understanding what changed and where to focus review effort.")]
+    #[tool(description = "Run entity-level code review triage. Returns a compact summary of changed entities sorted by risk score, with classification, blast radius, and logical grouping. Results are paged (default 50 per page): total_entities_matching counts every match, and next_offset is the offset of the next page. Pass fields='minimal' for just name, file, risk, score and group per entity. This is the primary entry point for understanding what changed and where to focus review effort.")]
     async fn inspect_triage(
         &self,
         Parameters(params): Parameters<TriageParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = self
-            .get_result(&params.repo_path, &params.target)
-            .await
-            .map_err(internal_err)?;
-
-        let verdict = suggest_verdict(&result);
-
-        let entities: Vec<serde_json::Value> = result
-            .entity_reviews
-            .iter()
-            .filter(|r| {
-                if let Some(ref min) = params.min_risk {
-                    r.risk_level >= parse_risk_level(min)
-                } else {
-                    true
-                }
-            })
-            .map(|r| {
-                serde_json::json!({
-                    "name": r.entity_name,
-                    "type": r.entity_type,
-                    "file": r.file_path,
-                    "risk": format!("{}", r.risk_level),
-                    "score": format!("{:.2}", r.risk_score),
-                    "classification": format!("{}", r.classification),
-                    "blast_radius": r.blast_radius,
-                    "change_type": format!("{:?}", r.change_type).to_lowercase(),
-                    "public_api": r.is_public_api,
-                    "cosmetic": r.structural_change == Some(false),
-                    "group_id": r.group_id,
-                })
-            })
-            .collect();
-
-        let groups: Vec<serde_json::Value> = result
-            .groups
-            .iter()
-            .map(|g| {
-                serde_json::json!({
-                    "id": g.id,
-                    "label": g.label,
-                    "entity_count": g.entity_ids.len(),
-                })
-            })
-            .collect();
-
-        let output = serde_json::json!({
-            "verdict": format!("{}", verdict),
-            "stats": {
-                "total_entities": result.stats.total_entities,
-                "critical": result.stats.by_risk.critical,
-                "high": result.stats.by_risk.high,
-                "medium": result.stats.by_risk.medium,
-                "low": result.stats.by_risk.low,
-            },
-            "entities": entities,
-            "groups": groups,
-            "timing_ms": result.timing.total_ms,
-        });
-
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        let sort = params
+            .sort
+            .as_deref()
+            .map(EntitySort::parse)
+            .transpose()
+            .map_err(invalid_params)?
+            .unwrap_or(EntitySort::Risk);
+        let fields = params
+            .fields
+            .as_deref()
+            .map(EntityFields::parse)
+            .transpose()
+            .map_err(invalid_params)?
+            .unwrap_or(EntityFields::Full);
+        let limit = page_limit(params.limit).map_err(invalid_params)?;
+        let mut filter = entity_filter(
+            params.min_risk.as_deref(),
+            params.min_score,
+            params.classification.as_deref(),
+        )?;
+        self.config.lock().await.apply_defaults(&mut filter, params.hide_tests);
+
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let output = triage_output(
+            &result,
+            &filter,
+            sort,
+            fields,
+            params.offset.unwrap_or(0),
+            limit,
+        );
+
+        Ok(respond(output, &run))
     }
 
     #[tool(description = "Drill into a single entity to see full details including before/after content, dependents, and dependencies. Use after inspect_triage to understand a specific high-risk entity.")]
     async fn inspect_entity(
         &self,
         Parameters(params): Parameters<EntityParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = self
-            .get_result(&params.repo_path, &params.target)
-            .await
-            .map_err(internal_err)?;
-
-        let review = result
-            .entity_reviews
-            .iter()
-            .find(|r| {
-                r.entity_name == params.entity_name
-                    && params
-                        .file_path
-                        .as_ref()
-                        .map(|fp| r.file_path.ends_with(fp))
-                        .unwrap_or(true)
-            })
-            .ok_or_else(|| {
-                internal_err(format!("Entity '{}' not found in changes", params.entity_name))
-            })?;
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let Some(review) = find_entity(&result, &params.entity_name, params.file_path.as_deref())
+        else {
+            return Ok(entity_not_found(&result, &params.entity_name));
+        };
 
         let output = serde_json::json!({
             "entity_id": review.entity_id,
@@ -215,7 +1215,10 @@ impl InspectServer {
             "blast_radius": review.blast_radius,
             "public_api": review.is_public_api,
             "cosmetic": review.structural_change == Some(false),
+            "test": review.is_test,
             "group_id": review.group_id,
+            "risk_rule": review.risk_rule,
+            "score_components": review.score_components,
             "before_content": review.before_content,
             "after_content": review.after_content,
             "dependents": review.dependent_names.iter().map(|(name, file)| {
@@ -226,34 +1229,156 @@ impl InspectServer {
             }).collect::<Vec<_>>(),
         });
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        Ok(respond(output, &run))
     }
 
-    #[tool(description = "Get all entities in a logical change group. Groups are formed by dependency edges between changed entities. Use after inspect_triage to understand related changes.")]
-    async fn inspect_group(
+    #[tool(description = "Show an entity's unified diff instead of full before/after content. Returns the diff hunks (line numbers relative to the entity), the entity's line range in the file, and lines added/removed. Cheaper than inspect_entity when you only need the delta.")]
+    async fn inspect_diff(
         &self,
-        Parameters(params): Parameters<GroupParams>,
+        Parameters(params): Parameters<DiffParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = self
-            .get_result(&params.repo_path, &params.target)
-            .await
-            .map_err(internal_err)?;
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let Some(review) = find_entity(&result, &params.entity_name, params.file_path.as_deref())
+        else {
+            return Ok(entity_not_found(&result, &params.entity_name));
+        };
 
-        let group = result
-            .groups
-            .iter()
-            .find(|g| g.id == params.group_id)
-            .ok_or_else(|| internal_err(format!("Group {} not found", params.group_id)))?;
+        // Added and deleted entities diff against an empty side
+        let diff = unified_diff(
+            review.before_content.as_deref().unwrap_or(""),
+            review.after_content.as_deref().unwrap_or(""),
+            params.context_lines.unwrap_or(3),
+        );
 
-        let entities: Vec<serde_json::Value> = result
-            .entity_reviews
-            .iter()
-            .filter(|r| group.entity_ids.contains(&r.entity_id))
-            .map(|r| {
-                serde_json::json!({
-                    "name": r.entity_name,
+        let output = serde_json::json!({
+            "name": review.entity_name,
+            "type": review.entity_type,
+            "file": review.file_path,
+            "lines": format!("{}-{}", review.start_line, review.end_line),
+            "change_type": format!("{:?}", review.change_type).to_lowercase(),
+            "classification": format!("{}", review.classification),
+            "added": diff.added,
+            "removed": diff.removed,
+            "diff": diff.text,
+        });
+
+        Ok(respond(output, &run))
+    }
+
+    #[tool(description = "Look up who depends on an entity (or what it depends on) anywhere in the repo, not just among changed entities. Walks the entity graph of the current working tree up to the given depth and returns each neighbor with file path, line range, and hop distance. Returns candidates when the name is ambiguous.")]
+    async fn inspect_dependents(
+        &self,
+        Parameters(params): Parameters<DependentsParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let direction = match params.direction.as_deref().unwrap_or("dependents") {
+            "dependents" => Direction::Dependents,
+            "dependencies" => Direction::Dependencies,
+            "both" => Direction::Both,
+            other => {
+                return Err(invalid_params(format!(
+                    "direction must be 'dependents', 'dependencies', or 'both', got '{}'",
+                    other
+                )))
+            }
+        };
+        let depth = params.depth.unwrap_or(1).clamp(1, MAX_GRAPH_DEPTH);
+
+        let graph = self
+            .get_graph(&params.repo_path, params.force_refresh.unwrap_or(false))
+            .await?;
+
+        let entity = match graph_entity(&graph, &params.entity_name, params.file_path.as_deref()) {
+            Ok(entity) => entity,
+            Err(not_found) => return Ok(not_found),
+        };
+        let neighbors = neighborhood(&graph, &entity.entity_id, direction, depth);
+        let output = serde_json::json!({
+            "entity": entity,
+            "depth": depth,
+            "count": neighbors.len(),
+            "neighbors": neighbors,
+        });
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&output).unwrap_or_default(),
+        )]))
+    }
+
+    #[tool(description = "What could break if you change an entity, before changing it: its direct dependents and dependencies, plus every transitive dependent up to depth hops (default 3) with a count per level and whether more lie beyond. Works on any entity in the repo's working tree, not just changed ones. Returns candidates when the name is ambiguous.")]
+    async fn inspect_impact(
+        &self,
+        Parameters(params): Parameters<ImpactParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let depth = params.depth.unwrap_or(DEFAULT_IMPACT_DEPTH).clamp(1, MAX_GRAPH_DEPTH);
+
+        let graph = self
+            .get_graph(&params.repo_path, params.force_refresh.unwrap_or(false))
+            .await?;
+
+        let entity = match graph_entity(&graph, &params.entity_name, params.file_path.as_deref()) {
+            Ok(entity) => entity,
+            Err(not_found) => return Ok(not_found),
+        };
+        let impact = impact(&graph, &entity.entity_id, depth);
+        let output = serde_json::json!({
+            "entity": entity,
+            "depth": depth,
+            "dependent_count": impact.dependents.len(),
+            "dependency_count": impact.dependencies.len(),
+            "transitive_count": impact.transitive_dependents.len(),
+            "impact": impact,
+        });
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&output).unwrap_or_default(),
+        )]))
+    }
+
+    #[tool(description = "Get all entities in a logical change group. Groups are formed by dependency edges between changed entities; the response lists those edges (from dependent to dependency, by entity id). Use after inspect_triage to understand related changes.")]
+    async fn inspect_group(
+        &self,
+        Parameters(params): Parameters<GroupParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let mut filter = entity_filter(
+            params.min_risk.as_deref(),
+            params.min_score,
+            params.classification.as_deref(),
+        )?;
+        self.config.lock().await.apply_defaults(&mut filter, params.hide_tests);
+
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let Some(group) = result.groups.iter().find(|g| g.id == params.group_id) else {
+            return Ok(group_not_found(&result, params.group_id));
+        };
+
+        let members = result
+            .entity_reviews
+            .iter()
+            .filter(|r| group.entity_ids.contains(&r.entity_id));
+        let entities: Vec<serde_json::Value> = filter
+            .apply(members)
+            .into_iter()
+            .map(|r| {
+                serde_json::json!({
+                    "name": r.entity_name,
                     "type": r.entity_type,
                     "file": r.file_path,
                     "risk": format!("{}", r.risk_level),
@@ -264,32 +1389,70 @@ impl InspectServer {
             })
             .collect();
 
+        let edges: Vec<&EntityEdge> = result
+            .edges
+            .iter()
+            .filter(|e| group.entity_ids.contains(&e.from) && group.entity_ids.contains(&e.to))
+            .collect();
+
         let output = serde_json::json!({
             "group_id": group.id,
             "label": group.label,
             "entity_count": group.entity_ids.len(),
+            "classification": format!("{}", group.dominant_classification),
+            "max_risk": format!("{}", group.max_risk),
+            "max_score": format!("{:.2}", group.max_score),
+            "has_public_api": group.has_public_api,
+            "filters": filters_json(&filter),
+            "returned": entities.len(),
             "entities": entities,
+            "edges": edges,
         });
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        Ok(respond(output, &run))
     }
 
     #[tool(description = "Scope review to a single file. Returns entity reviews for only the specified file path.")]
     async fn inspect_file(
         &self,
         Parameters(params): Parameters<FileParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = self
-            .get_result(&params.repo_path, &params.target)
-            .await
-            .map_err(internal_err)?;
-
-        let entities: Vec<serde_json::Value> = result
+        let mut filter = entity_filter(
+            params.min_risk.as_deref(),
+            params.min_score,
+            params.classification.as_deref(),
+        )?;
+        self.config.lock().await.apply_defaults(&mut filter, params.hide_tests);
+
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let in_file: Vec<&EntityReview> = result
             .entity_reviews
             .iter()
             .filter(|r| r.file_path.ends_with(&params.file_path))
+            .collect();
+
+        if in_file.is_empty() {
+            let files = result.entity_reviews.iter().map(|r| r.file_path.as_str());
+            return Ok(tool_error(
+                format!("No changed entities in files matching '{}'", params.file_path),
+                serde_json::json!({
+                    "did_you_mean": closest_names(&params.file_path, files, SUGGESTION_COUNT),
+                }),
+            ));
+        }
+
+        let entities: Vec<serde_json::Value> = filter
+            .apply(in_file)
+            .into_iter()
             .map(|r| {
                 serde_json::json!({
                     "name": r.entity_name,
@@ -309,380 +1472,348 @@ impl InspectServer {
 
         let output = serde_json::json!({
             "file": params.file_path,
+            "filters": filters_json(&filter),
             "entity_count": entities.len(),
             "entities": entities,
         });
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        Ok(respond(output, &run))
     }
 
     #[tool(description = "Lightweight summary with no entity details. Returns stats, group count, verdict, and timing.")]
     async fn inspect_stats(
         &self,
         Parameters(params): Parameters<StatsParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = self
-            .get_result(&params.repo_path, &params.target)
-            .await
-            .map_err(internal_err)?;
-
-        let verdict = suggest_verdict(&result);
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        Ok(respond(stats_output(&result), &run))
+    }
 
+    #[tool(description = "Explain the review verdict: returns the verdict, the specific triggers behind it (which critical/high entities, or that every change is cosmetic), the risk thresholds in effect, and a suggested next step. Compact; use before deciding whether to drill in.")]
+    async fn inspect_verdict(
+        &self,
+        Parameters(params): Parameters<VerdictParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let report = explain_verdict(&result);
+        // The analysis already validated the repo config
+        let thresholds = RiskConfig::load(Path::new(&params.repo_path))
+            .unwrap_or_default()
+            .thresholds;
+        let source = if thresholds == Default::default() { "built-in" } else { CONFIG_FILE };
         let output = serde_json::json!({
-            "verdict": format!("{}", verdict),
-            "total_entities": result.stats.total_entities,
-            "risk": {
-                "critical": result.stats.by_risk.critical,
-                "high": result.stats.by_risk.high,
-                "medium": result.stats.by_risk.medium,
-                "low": result.stats.by_risk.low,
-            },
-            "classification": {
-                "text": result.stats.by_classification.text,
-                "syntax": result.stats.by_classification.syntax,
-                "functional": result.stats.by_classification.functional,
-                "mixed": result.stats.by_classification.mixed,
+            "verdict": format!("{}", report.verdict),
+            "reasons": report.reasons,
+            "policy": {
+                "source": source,
+                "critical_min_score": thresholds.critical,
+                "high_min_score": thresholds.high,
+                "medium_min_score": thresholds.medium,
+                "rule": "any critical: requires_careful_review; any high: requires_review; all cosmetic: likely_approvable; else standard_review",
             },
-            "change_types": {
-                "added": result.stats.by_change_type.added,
-                "modified": result.stats.by_change_type.modified,
-                "deleted": result.stats.by_change_type.deleted,
-                "moved": result.stats.by_change_type.moved,
-                "renamed": result.stats.by_change_type.renamed,
-            },
-            "groups": result.groups.len(),
-            "timing_ms": result.timing.total_ms,
+            "next_action": next_action(&result, &report),
         });
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        Ok(respond(output, &run))
     }
 
-    #[tool(description = "File-level risk heatmap. Returns per-file aggregate risk showing max risk, entity count, critical/high counts, and public API changes. Sorted by max risk descending.")]
+    #[tool(description = "Risk heatmap. Aggregates max/mean risk, entity count, critical/high counts, and public API changes per file (default), per directory rolled up to a depth, or per logical change group. Sorted by max risk descending.")]
     async fn inspect_risk_map(
         &self,
         Parameters(params): Parameters<RiskMapParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let result = self
-            .get_result(&params.repo_path, &params.target)
-            .await
-            .map_err(internal_err)?;
-
-        // Aggregate per file
-        let mut file_map: HashMap<String, FileRisk> = HashMap::new();
-        for r in &result.entity_reviews {
-            let entry = file_map.entry(r.file_path.clone()).or_insert(FileRisk {
-                max_score: 0.0,
-                max_risk: RiskLevel::Low,
-                entity_count: 0,
-                critical: 0,
-                high: 0,
-                public_api_changes: 0,
-            });
-            entry.entity_count += 1;
-            if r.risk_score > entry.max_score {
-                entry.max_score = r.risk_score;
-                entry.max_risk = r.risk_level;
-            }
-            if r.risk_level == RiskLevel::Critical {
-                entry.critical += 1;
-            }
-            if r.risk_level == RiskLevel::High {
-                entry.high += 1;
-            }
-            if r.is_public_api {
-                entry.public_api_changes += 1;
-            }
-        }
-
-        let mut files: Vec<(String, &FileRisk)> = file_map.iter().map(|(k, v)| (k.clone(), v)).collect();
-        files.sort_by(|a, b| b.1.max_score.partial_cmp(&a.1.max_score).unwrap());
-
-        let output: Vec<serde_json::Value> = files
-            .iter()
-            .map(|(path, risk)| {
-                serde_json::json!({
-                    "file": path,
-                    "max_risk": format!("{}", risk.max_risk),
-                    "max_score": format!("{:.2}", risk.max_score),
-                    "entity_count": risk.entity_count,
-                    "critical": risk.critical,
-                    "high": risk.high,
-                    "public_api_changes": risk.public_api_changes,
-                })
-            })
-            .collect();
-
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        let by = RiskMapBy::parse(params.group_by.as_deref().unwrap_or("file"), params.depth)
+            .map_err(invalid_params)?;
+
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let output: Vec<serde_json::Value> =
+            risk_map(&result, by).iter().map(|b| b.to_json(by)).collect();
+
+        Ok(respond(output.into(), &run))
     }
 
-    #[tool(description = "Analyze a remote GitHub PR via API (no local clone needed). Returns entity-level triage with ConGra classification, risk scoring, and logical grouping. Same output format as inspect_triage but works on any public/accessible repo.")]
-    async fn inspect_pr(
+    #[tool(description = "Ready-to-post markdown review summary, the same document as the CLI's --format markdown: verdict header, logical groups, a risk table of changed entities (riskiest first, default 50, max_entities=0 for all) and a detail section per high or critical entity. Set include_diffs to embed each high or critical entity's diff, truncated. Returned as plain markdown, followed by elapsed time and cache status.")]
+    async fn inspect_report(
         &self,
-        Parameters(params): Parameters<RemoteTriageParams>,
+        Parameters(params): Parameters<ReportParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let client = GitHubClient::new().map_err(internal_err)?;
-
-        let pr = client
-            .get_pr(&params.repo, params.pr_number)
-            .await
-            .map_err(internal_err)?;
-
-        let visible_files: Vec<_> = pr
-            .files
-            .iter()
-            .filter(|f| !is_noise_file(&f.filename))
-            .cloned()
-            .collect();
-
-        let file_pairs = client
-            .get_file_pairs(&params.repo, &visible_files, &pr.base_sha, &pr.head_sha)
-            .await;
-
-        let result = analyze_remote(&file_pairs).map_err(internal_err)?;
-        let verdict = suggest_verdict(&result);
-
-        let entities: Vec<serde_json::Value> = result
-            .entity_reviews
-            .iter()
-            .filter(|r| {
-                if let Some(ref min) = params.min_risk {
-                    r.risk_level >= parse_risk_level(min)
-                } else {
-                    true
-                }
-            })
-            .map(|r| {
-                serde_json::json!({
-                    "name": r.entity_name,
-                    "type": r.entity_type,
-                    "file": r.file_path,
-                    "risk": format!("{}", r.risk_level),
-                    "score": format!("{:.2}", r.risk_score),
-                    "classification": format!("{}", r.classification),
-                    "change_type": format!("{:?}", r.change_type).to_lowercase(),
-                    "public_api": r.is_public_api,
-                    "cosmetic": r.structural_change == Some(false),
-                    "group_id": r.group_id,
-                })
-            })
-            .collect();
+        let mut filter = entity_filter(params.min_risk.as_deref(), None, None)?;
+        self.config.lock().await.apply_defaults(&mut filter, None);
+
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let report = report_markdown(
+            &result,
+            &filter,
+            params.max_entities.unwrap_or(DEFAULT_REPORT_ENTITIES),
+            params.include_diffs.unwrap_or(false),
+        );
+        let extra = serde_json::to_string_pretty(&run_fields(&run)).unwrap_or_default();
+        Ok(CallToolResult::success(vec![Content::text(report), Content::text(extra)]))
+    }
 
-        let groups: Vec<serde_json::Value> = result
-            .groups
-            .iter()
-            .map(|g| {
-                serde_json::json!({
-                    "id": g.id,
-                    "label": g.label,
-                    "entity_count": g.entity_ids.len(),
-                })
+    #[tool(description = "Compare two analyses, e.g. before and after a fix: returns entities newly changed or no longer changed, risk level transitions, the verdict before and after, and entities that moved between logical groups. Compact (ids and transitions only); drill in with inspect_entity.")]
+    async fn inspect_compare(
+        &self,
+        Parameters(params): Parameters<CompareParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let start = Instant::now();
+        let force_refresh = params.force_refresh.unwrap_or(false);
+
+        let (base, base_run) = self
+            .get_result(&params.repo_path, &params.base_target, force_refresh, &context)
+            .await?;
+        let (head, head_run) = self
+            .get_result(&params.repo_path, &params.head_target, force_refresh, &context)
+            .await?;
+
+        let delta = compare(&base, &head);
+
+        let entity = |e: &DeltaEntity| {
+            serde_json::json!({
+                "entity_id": e.entity_id,
+                "name": e.name,
+                "file": e.file_path,
+                "risk": format!("{}", e.risk_level),
             })
-            .collect();
+        };
 
         let output = serde_json::json!({
-            "pr": {
-                "number": pr.number,
-                "title": pr.title,
-                "state": pr.state,
-                "additions": pr.additions,
-                "deletions": pr.deletions,
+            "verdict": {
+                "before": format!("{}", delta.verdict_before),
+                "after": format!("{}", delta.verdict_after),
+                "changed": delta.verdict_changed(),
             },
-            "verdict": format!("{}", verdict),
-            "stats": {
-                "total_entities": result.stats.total_entities,
-                "critical": result.stats.by_risk.critical,
-                "high": result.stats.by_risk.high,
-                "medium": result.stats.by_risk.medium,
-                "low": result.stats.by_risk.low,
-            },
-            "entities": entities,
-            "groups": groups,
-            "timing_ms": result.timing.total_ms,
+            "added": delta.added.iter().map(entity).collect::<Vec<_>>(),
+            "removed": delta.removed.iter().map(entity).collect::<Vec<_>>(),
+            "risk_transitions": delta.risk_transitions.iter().map(|t| {
+                serde_json::json!({
+                    "entity_id": t.entity_id,
+                    "name": t.name,
+                    "file": t.file_path,
+                    "from": format!("{}", t.from),
+                    "to": format!("{}", t.to),
+                })
+            }).collect::<Vec<_>>(),
+            "group_moves": delta.group_moves,
         });
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        let warning = base_run.warning.or(head_run.warning);
+        let mut run = RunInfo::new(start, warning);
+        // A hit only when neither side had to be analyzed
+        run.cache_hit = base_run.cache_hit.zip(head_run.cache_hit).map(|(base, head)| base && head);
+        Ok(respond(output, &run))
     }
 
-    #[tool(description = "Post review comments on a GitHub PR. Validates each comment against commentable diff lines before posting. Returns the review URL.")]
-    async fn inspect_post_review(
+    #[tool(description = "File-level overview of a diff: one row per changed file with status, entity count, max risk, public API changes, and whether it is a noise file (lockfile, generated, build output, OS metadata) and why. Sorted by max risk. Set fast to list files from the raw git diff without entity analysis. Cheaper than inspect_triage as a first look.")]
+    async fn inspect_changed_files(
         &self,
-        Parameters(params): Parameters<PostReviewParams>,
+        Parameters(params): Parameters<ChangedFilesParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let client = GitHubClient::new().map_err(internal_err)?;
+        let start = Instant::now();
+        check_repo_path(&params.repo_path)?;
+        let files = {
+            let repo = PathBuf::from(&params.repo_path);
+            let scope = resolve_scope(&params.repo_path, &params.target).await?;
+            // With a valid repo, git failures here are bad refs
+            tokio::task::spawn_blocking(move || list_changed_files(&repo, &scope))
+                .await
+                .map_err(internal_err)?
+                .map_err(|e| invalid_target(&params.target, e))?
+        };
 
-        let pr = client
-            .get_pr_with_patches(&params.repo, params.pr_number)
-            .await
-            .map_err(internal_err)?;
+        let fast = params.fast.unwrap_or(self.config.lock().await.fast);
+        let (rows, run) = if fast {
+            (summarize_files(&files, None), RunInfo::new(start, None))
+        } else {
+            let (result, run) = self
+                .get_result(
+                    &params.repo_path,
+                    &params.target,
+                    params.force_refresh.unwrap_or(false),
+                    &context,
+                )
+                .await?;
+            (summarize_files(&files, Some(&result.entity_reviews)), run)
+        };
 
-        let file_commentable: HashMap<String, Vec<u64>> = pr
-            .files
+        let noise_count = rows.iter().filter(|f| f.is_noise).count();
+        let rows: Vec<serde_json::Value> = rows
             .iter()
             .map(|f| {
-                let hunks = f.patch.as_deref().map(parse_patch).unwrap_or_default();
-                let cl = commentable_lines(&hunks);
-                (f.filename.clone(), cl)
-            })
-            .collect();
-
-        let mut warnings = Vec::new();
-        let mut valid_comments = Vec::new();
-
-        for c in &params.comments {
-            if let Some(cl) = file_commentable.get(&c.path) {
-                if cl.contains(&c.line) {
-                    valid_comments.push(ReviewCommentInput {
-                        path: c.path.clone(),
-                        line: c.line,
-                        body: c.body.clone(),
-                        start_line: c.start_line,
-                    });
-                } else {
-                    warnings.push(format!(
-                        "{}:{} not commentable (not in diff)",
-                        c.path, c.line
-                    ));
-                }
-            } else {
-                warnings.push(format!("{} not a changed file", c.path));
-            }
-        }
-
-        if valid_comments.is_empty() {
-            return Ok(CallToolResult::success(vec![Content::text(
                 serde_json::json!({
-                    "error": "no valid comments after validation",
-                    "warnings": warnings,
+                    "file": f.path,
+                    "status": f.status,
+                    "entity_count": f.entity_count,
+                    "max_risk": f.max_risk.map(|r| r.to_string()),
+                    "max_score": f.max_score.map(|s| format!("{:.2}", s)),
+                    "public_api_changes": f.public_api_changes,
+                    "is_noise": f.is_noise,
+                    "noise_kind": f.noise_kind,
                 })
-                .to_string(),
-            )]));
-        }
-
-        let review = CreateReview {
-            commit_id: pr.head_sha,
-            event: "COMMENT".to_string(),
-            body: params.body.unwrap_or_else(|| "Review from inspect".into()),
-            comments: valid_comments,
-        };
-
-        let resp = client
-            .create_review(&params.repo, params.pr_number, &review)
-            .await
-            .map_err(internal_err)?;
+            })
+            .collect();
 
         let output = serde_json::json!({
-            "id": resp.id,
-            "url": resp.html_url,
-            "warnings": warnings,
+            "file_count": rows.len(),
+            "noise_count": noise_count,
+            "files": rows,
         });
 
-        Ok(CallToolResult::success(vec![Content::text(
-            serde_json::to_string_pretty(&output).unwrap_or_default(),
-        )]))
+        Ok(respond(output, &run))
     }
 
-    #[tool(description = "Search PR files for a text pattern. Optionally also searches the broader codebase via GitHub Code Search. Returns grep-style matches with file, line, and context.")]
-    async fn inspect_search(
+    #[tool(description = "Search the files changed by a local target for a text or regex pattern, without network. Each match names its enclosing changed entity (null if the line is outside every changed entity). Set changed_only to search only changed entities' content. Noise files (lockfiles, generated, build output) are skipped.")]
+    async fn inspect_local_search(
         &self,
-        Parameters(params): Parameters<SearchParams>,
+        Parameters(params): Parameters<LocalSearchParams>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, rmcp::ErrorData> {
-        let client = GitHubClient::new().map_err(internal_err)?;
-        let case_sensitive = params.case_sensitive.unwrap_or(false);
-        let repo_wide = params.repo_wide.unwrap_or(false);
-
-        let pr = client
-            .get_pr(&params.repo, params.pr_number)
-            .await
-            .map_err(internal_err)?;
-
-        let file_paths: Vec<String> = pr
-            .files
-            .iter()
-            .filter(|f| !is_noise_file(&f.filename))
-            .map(|f| f.filename.clone())
-            .collect();
-
-        let pr_files = client
-            .fetch_file_contents(&params.repo, &file_paths, &pr.head_ref)
-            .await;
-
-        let mut matches = search::grep_files(&pr_files, &params.pattern, case_sensitive, 2);
-
-        if repo_wide {
-            if let Ok(search_results) = client
-                .search_code(&params.repo, &params.pattern, None)
+        let max_results = params.max_results.unwrap_or(DEFAULT_MAX_MATCHES);
+        let matcher = search::build_matcher(&params.pattern, false, params.regex.unwrap_or(false))
+            .map_err(|e| invalid_params(format!("invalid pattern: {}", e)))?;
+
+        let (result, run) = self
+            .get_result(
+                &params.repo_path,
+                &params.target,
+                params.force_refresh.unwrap_or(false),
+                &context,
+            )
+            .await?;
+
+        let mut matches = if params.changed_only.unwrap_or(false) {
+            search::grep_entities(&result.entity_reviews, &matcher, 0)
+        } else {
+            let repo = PathBuf::from(&params.repo_path);
+            let scope = resolve_scope(&params.repo_path, &params.target).await?;
+            let files = tokio::task::spawn_blocking(move || changed_file_contents(&repo, &scope))
                 .await
-            {
-                let pr_file_set: HashSet<&str> =
-                    file_paths.iter().map(|s| s.as_str()).collect();
-
-                for item in &search_results.items {
-                    if pr_file_set.contains(item.path.as_str()) || is_noise_file(&item.path) {
-                        continue;
-                    }
-                    if let Some(text_matches) = &item.text_matches {
-                        for tm in text_matches {
-                            for (line_idx, line) in tm.fragment.lines().enumerate() {
-                                let haystack = if case_sensitive {
-                                    line.to_string()
-                                } else {
-                                    line.to_lowercase()
-                                };
-                                let pat = if case_sensitive {
-                                    params.pattern.clone()
-                                } else {
-                                    params.pattern.to_lowercase()
-                                };
-                                if haystack.contains(&pat) {
-                                    matches.push(search::SearchMatch {
-                                        file: item.path.clone(),
-                                        line: line_idx + 1,
-                                        column: haystack.find(&pat).unwrap_or(0) + 1,
-                                        text: line.to_string(),
-                                        context_before: vec![],
-                                        context_after: vec![],
-                                    });
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+                .map_err(internal_err)?
+                .map_err(internal_err)?;
+            search::grep_files(&files, &matcher, 0)
+        };
+        for m in &mut matches {
+            m.source = search::MatchSource::Local;
         }
 
         let output = serde_json::json!({
             "total_matches": matches.len(),
-            "matches": matches.iter().take(100).map(|m| {
+            "truncated": matches.len() > max_results,
+            "matches": matches.iter().take(max_results).map(|m| {
+                let entity = search::enclosing_entity(&result.entity_reviews, &m.file, m.line);
                 serde_json::json!({
                     "file": m.file,
                     "line": m.line,
                     "column": m.column,
                     "text": m.text,
+                    "entity": entity.map(|r| &r.entity_name),
+                    "entity_risk": entity.map(|r| r.risk_level.to_string()),
                 })
             }).collect::<Vec<_>>(),
         });
 
+        Ok(respond(output, &run))
+    }
+
+    #[tool(description = "Set defaults for the rest of this session instead of repeating them on every call: risk weights (a JSON weights file and/or inline overrides), default min_risk, hide_tests, and fast mode. Arguments passed to other tools still win. Changing weights drops cached analyses. Returns the effective configuration.")]
+    async fn inspect_configure(
+        &self,
+        Parameters(params): Parameters<ConfigureParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        let (config, cache_cleared) = self.configure(&params).await?;
+        let mut output = config.to_json();
+        output["cache_cleared"] = cache_cleared.into();
+
         Ok(CallToolResult::success(vec![Content::text(
             serde_json::to_string_pretty(&output).unwrap_or_default(),
         )]))
     }
-}
 
-struct FileRisk {
-    max_score: f64,
-    max_risk: RiskLevel,
-    entity_count: usize,
-    critical: usize,
-    high: usize,
-    public_api_changes: usize,
+    #[tool(description = "Show the effective session configuration set with inspect_configure: risk weights and their source, default min_risk, hide_tests, and fast mode.")]
+    async fn inspect_get_config(&self) -> Result<CallToolResult, rmcp::ErrorData> {
+        let output = self.config.lock().await.to_json();
+
+        Ok(CallToolResult::success(vec![Content::text(
+            serde_json::to_string_pretty(&output).unwrap_or_default(),
+        )]))
+    }
+
+    #[tool(description = "Analyze a remote GitHub PR via API (no local clone needed). Returns entity-level triage with ConGra classification, risk scoring, and logical grouping, in the same paged format as inspect_triage, plus the PR's title and size. Works on any public/accessible repo. Results are cached per head commit, so paging and repeat calls only refetch the PR metadata. Pass since_commit to analyze only what later pushes changed.")]
+    async fn inspect_pr(
+        &self,
+        Parameters(params): Parameters<RemoteTriageParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        check_repo_slug(&params.repo)?;
+        let client = match GitHubClient::new() {
+            Ok(client) => client,
+            Err(e) => return github_error(e),
+        };
+        self.remote_triage(&client, params).await
+    }
+
+    #[tool(description = "Post review comments on a GitHub PR. Validates each comment against commentable diff lines before posting: comments a few lines off are re-anchored to the nearest diff line, the rest are moved into the review body and listed under rejected with the nearest commentable line as suggested_line. Multi-line comments need start_line < line. Comments already on the PR nearby are skipped unless allow_duplicates is set. Large reviews are posted in parts; comments GitHub still rejects are dropped and listed under rejected. The event defaults to COMMENT. Returns the review id and html_url and a per-comment disposition report. Set dry_run to preview without posting.")]
+    async fn inspect_post_review(
+        &self,
+        Parameters(params): Parameters<PostReviewParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        check_repo_slug(&params.repo)?;
+        let client = match GitHubClient::new() {
+            Ok(client) => client,
+            Err(e) => return github_error(e),
+        };
+        post_review(&client, params).await
+    }
+
+    #[tool(description = "Search PR files for a text or regex pattern. Optionally also searches the broader codebase via GitHub Code Search; for a regex, Code Search is asked for its longest literal text and the results are filtered with the full pattern. Returns grep-style matches with file, line, column, text, a few lines of context, and source ('pr' or 'codebase'), plus totals. At most 200 matches are returned; truncated is set when there were more.")]
+    async fn inspect_search(
+        &self,
+        Parameters(params): Parameters<SearchParams>,
+    ) -> Result<CallToolResult, rmcp::ErrorData> {
+        check_repo_slug(&params.repo)?;
+        let client = match GitHubClient::new() {
+            Ok(client) => client,
+            Err(e) => return github_error(e),
+        };
+        self.search_pr(&client, params).await
+    }
 }
 
 #[tool_handler]
@@ -691,9 +1822,9 @@ impl ServerHandler for InspectServer {
         ServerInfo {
             instructions: Some(
                 "Entity-level code review triage server. For local repos: use inspect_triage as \
-                 the primary entry point. For remote GitHub PRs: use inspect_pr (no clone needed). \
-                 Drill down with inspect_entity, inspect_group, or inspect_file. Post reviews with \
-                 inspect_post_review. Search PR files with inspect_search."
+                 the primary entry point, or inspect_changed_files for a quick file-level overview. For remote GitHub PRs: use inspect_pr (no clone needed). \
+                 Drill down with inspect_entity, inspect_diff, inspect_group, or inspect_file; use \
+                 inspect_dependents for callers of any entity; inspect_impact for what changing an entity would break; inspect_compare shows how risk moved between two targets; inspect_verdict explains the verdict; inspect_local_search greps the changed files. Set session defaults (weights, min_risk, hide_tests, fast) once with inspect_configure. inspect_report returns a ready-to-post markdown summary. Post reviews with inspect_post_review. Search PR files with inspect_search."
                     .into(),
             ),
             capabilities: ServerCapabilities::builder().enable_tools().build(),
@@ -701,3 +1832,907 @@ impl ServerHandler for InspectServer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inspect_core::analyze::compute_stats;
+    use inspect_core::types::{AnalysisWarning, ChangeClassification, ChangeGroup, Timing};
+    use rmcp::model::ErrorCode;
+
+    fn review(name: &str) -> EntityReview {
+        EntityReview {
+            entity_id: format!("src/lib.rs::{}", name),
+            entity_name: name.into(),
+            risk_score: 0.5,
+            risk_level: RiskLevel::Medium,
+            ..EntityReview::test_default()
+        }
+    }
+
+    fn result() -> ReviewResult {
+        let entity_reviews = vec![review("load_config"), review("save_config"), review("render")];
+        ReviewResult {
+            stats: compute_stats(&entity_reviews),
+            groups: vec![ChangeGroup {
+                id: 0,
+                label: "config".into(),
+                entity_ids: entity_reviews.iter().map(|r| r.entity_id.clone()).collect(),
+                dominant_classification: ChangeClassification::Functional,
+                max_risk: RiskLevel::Medium,
+                max_score: 0.5,
+                has_public_api: false,
+            }],
+            entity_reviews,
+            edges: vec![],
+            blast_radius_exact: true,
+            timing: Timing::default(),
+            warnings: vec![],
+            changes: vec![],
+        }
+    }
+
+    fn body(result: &CallToolResult) -> serde_json::Value {
+        serde_json::from_str(&result.content[0].as_text().unwrap().text).unwrap()
+    }
+
+    #[test]
+    fn malformed_targets_are_invalid_params() {
+        for bad in ["main..", "..main", "a..b..c", "main...", ""] {
+            let err = parse_target_param(bad).unwrap_err();
+            assert_eq!(err.code, ErrorCode::INVALID_PARAMS, "target {:?}", bad);
+        }
+        assert_eq!(parse_target_param("staged").unwrap(), Target::Staged);
+        assert!(matches!(parse_target_param("main...feature"), Ok(Target::MergeBase { .. })));
+    }
+
+    #[test]
+    fn bad_filters_are_invalid_params() {
+        let filter = entity_filter(Some("HIGH"), None, None).unwrap();
+        assert_eq!(filter.min_risk, Some(RiskLevel::High));
+        assert_eq!(entity_filter(Some("severe"), None, None).unwrap_err().code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(entity_filter(None, Some(2.0), None).unwrap_err().code, ErrorCode::INVALID_PARAMS);
+        let bad = vec!["logic".to_string()];
+        assert_eq!(
+            entity_filter(None, None, Some(&bad)).unwrap_err().code,
+            ErrorCode::INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn filters_are_echoed() {
+        let classes = vec!["functional".to_string()];
+        let filter = entity_filter(Some("high"), Some(0.5), Some(&classes)).unwrap();
+        let echoed = filters_json(&filter);
+        assert_eq!(echoed["min_risk"], "high");
+        assert_eq!(echoed["min_score"], 0.5);
+        assert_eq!(echoed["classification"][0], "functional");
+        assert!(filters_json(&EntityFilter::default())["min_score"].is_null());
+    }
+
+    fn error_code(err: &ErrorData) -> &str {
+        err.data.as_ref().and_then(|d| d["code"].as_str()).unwrap_or_default()
+    }
+
+    #[test]
+    fn repo_paths_must_be_absolute_git_work_trees() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let plain = tmp.path().join("plain");
+        let repo = tmp.path().join("repo");
+        std::fs::create_dir_all(&plain).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(repo.join("src")).unwrap();
+
+        for bad in ["/definitely/not/a/repo", "relative/repo", plain.to_str().unwrap()] {
+            let err = check_repo_path(bad).unwrap_err();
+            assert_eq!(err.code, ErrorCode::INVALID_PARAMS, "{}", bad);
+            assert_eq!(error_code(&err), "NOT_A_REPO", "{}", bad);
+            assert_eq!(err.data.as_ref().unwrap()["repo_path"], bad);
+        }
+        assert!(check_repo_path("relative/repo").unwrap_err().message.contains("absolute"));
+        assert!(check_repo_path(plain.to_str().unwrap()).unwrap_err().message.contains("git repository"));
+        assert!(check_repo_path(repo.to_str().unwrap()).is_ok());
+        assert!(check_repo_path(repo.join("src").to_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn malformed_targets_carry_invalid_target() {
+        let err = parse_target_param("main..").unwrap_err();
+        assert_eq!(error_code(&err), "INVALID_TARGET");
+        assert_eq!(err.data.unwrap()["target"], "main..");
+    }
+
+    #[tokio::test]
+    async fn unknown_refs_are_invalid_target() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git").args(args).current_dir(dir).output().unwrap();
+        };
+        git(&["init"]);
+        let err = resolve_scope(dir.to_str().unwrap(), "main...no-such-branch").await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(error_code(&err), "INVALID_TARGET");
+    }
+
+    #[tokio::test]
+    async fn analysis_failures_are_bad_params() {
+        let err = analyze_error("HEAD~3", AnalyzeError::Git("bad revision".into()));
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(error_code(&err), "INVALID_TARGET");
+        let err = analyze_error("HEAD", AnalyzeError::Io("permission denied".into()));
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(RunError::Task("panicked".into()).into_error("HEAD").code, ErrorCode::INTERNAL_ERROR);
+
+        // A broken repo config fails the (fast) run as invalid params
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(tmp.path().join(".inspect.toml"), "[risk.weights]\nblast_radius = -1.0\n").unwrap();
+        let server = InspectServer::new();
+        let repo_path = tmp.path().to_str().unwrap();
+        let key = CacheKey {
+            repo_path: repo_path.into(),
+            resolved: "abc123".into(),
+        };
+        let config = server.config.lock().await.clone();
+        let err = server
+            .fast_result(&key, repo_path, "HEAD", DiffScope::Commit { sha: "HEAD".into() }, &config)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert!(err.message.contains("config error"), "{}", err.message);
+    }
+
+    #[tokio::test]
+    async fn fast_results_are_cached_apart_from_full_ones() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let repo_path = tmp.path().to_str().unwrap();
+        let server = InspectServer::new();
+        let target = CacheKey {
+            repo_path: repo_path.into(),
+            resolved: "abc123".into(),
+        };
+        let config = server.config.lock().await.clone();
+        server
+            .fast_result(&target, repo_path, "HEAD", DiffScope::Commit { sha: "HEAD".into() }, &config)
+            .await
+            .unwrap();
+
+        let run = |generation: u64, fast: bool| RunKey {
+            target: target.clone(),
+            generation,
+            fast,
+        };
+        let mut cache = server.cache.lock().await;
+        assert!(cache.get(&run(0, true)).is_some());
+        assert!(cache.get(&run(0, false)).is_none(), "a fast result never stands in for a full one");
+        assert!(cache.get(&run(1, true)).is_none());
+    }
+
+    #[test]
+    fn unknown_entity_is_a_tool_error_with_suggestions() {
+        let result = result();
+        let err = entity_not_found(&result, "load_confg");
+        assert_eq!(err.is_error, Some(true));
+        let body = body(&err);
+        assert_eq!(body["code"], "ENTITY_NOT_FOUND");
+        assert_eq!(body["changed_entities"], 3);
+        assert_eq!(body["did_you_mean"][0], "load_config");
+        assert!(body["did_you_mean"].as_array().unwrap().len() <= SUGGESTION_COUNT);
+    }
+
+    #[test]
+    fn unknown_group_is_a_tool_error_listing_groups() {
+        let mut empty = result();
+        empty.groups.clear();
+        assert!(body(&group_not_found(&empty, 0))["valid_range"].is_null());
+
+        let err = group_not_found(&result(), 7);
+        assert_eq!(err.is_error, Some(true));
+        let body = body(&err);
+        assert_eq!(body["error"], "Group 7 not found");
+        assert_eq!(body["code"], "GROUP_NOT_FOUND");
+        assert_eq!(body["valid_range"], serde_json::json!([0, 0]));
+        assert_eq!(body["groups"][0]["label"], "config");
+    }
+
+    #[test]
+    fn next_action_per_verdict() {
+        let mut r = result();
+        r.entity_reviews[2].risk_level = RiskLevel::Critical;
+        r.entity_reviews[2].risk_score = 0.8;
+        r.stats = compute_stats(&r.entity_reviews);
+        let report = explain_verdict(&r);
+        assert_eq!(report.verdict, ReviewVerdict::RequiresCarefulReview);
+        assert_eq!(
+            next_action(&r, &report),
+            "drill into group 0 with inspect_group, starting at render (src/lib.rs)"
+        );
+
+        let r = result();
+        let report = explain_verdict(&r);
+        assert_eq!(report.verdict, ReviewVerdict::StandardReview);
+        assert!(next_action(&r, &report).starts_with("review with inspect_triage"));
+
+        let mut r = result();
+        for review in &mut r.entity_reviews {
+            review.structural_change = Some(false);
+        }
+        let report = explain_verdict(&r);
+        assert_eq!(next_action(&r, &report), "spot-check with inspect_diff, then approve");
+    }
+
+    #[test]
+    fn triage_stats_and_groups_agree_under_min_risk() {
+        let mut r = result();
+        r.entity_reviews[0].risk_level = RiskLevel::High;
+        r.entity_reviews[1].group_id = 1;
+        r.entity_reviews[1].risk_level = RiskLevel::Low;
+        r.groups[0].entity_ids.retain(|id| !id.ends_with("save_config"));
+        r.groups.push(ChangeGroup {
+            id: 1,
+            label: "io".into(),
+            entity_ids: vec![r.entity_reviews[1].entity_id.clone()],
+            dominant_classification: ChangeClassification::Functional,
+            max_risk: RiskLevel::Medium,
+            max_score: 0.5,
+            has_public_api: false,
+        });
+        r.stats = compute_stats(&r.entity_reviews);
+
+        let filter = entity_filter(Some("medium"), None, None).unwrap();
+        let output = triage_output(&r, &filter, EntitySort::Risk, EntityFields::Full, 0, 1);
+
+        assert_eq!(output["total_before_filter"], 3);
+        assert_eq!(output["total_entities_matching"], 2);
+        assert_eq!(output["returned"], 1);
+        let stats = &output["stats"];
+        assert_eq!(stats["total_entities"], 2);
+        assert_eq!(stats["high"], 1);
+        assert_eq!(stats["medium"], 1);
+        assert_eq!(stats["low"], 0);
+
+        // Group "io" only held the low-risk entity
+        let groups = output["groups"].as_array().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0]["entity_count"], 2);
+        let grouped: u64 = groups.iter().map(|g| g["entity_count"].as_u64().unwrap()).sum();
+        assert_eq!(grouped, output["total_entities_matching"].as_u64().unwrap());
+
+        // The cached result is not modified
+        assert_eq!(r.entity_reviews.len(), 3);
+        assert_eq!(r.groups.len(), 2);
+    }
+
+    #[test]
+    fn triage_pages_by_risk_and_projects_fields() {
+        let mut r = result();
+        for (review, score) in r.entity_reviews.iter_mut().zip([0.2, 0.9, 0.5]) {
+            review.risk_score = score;
+        }
+        r.stats = compute_stats(&r.entity_reviews);
+        let filter = EntityFilter::default();
+        let names = |output: &serde_json::Value| -> Vec<String> {
+            output["entities"].as_array().unwrap().iter().map(|e| e["name"].as_str().unwrap().to_string()).collect()
+        };
+
+        let first = triage_output(&r, &filter, EntitySort::Risk, EntityFields::Full, 0, 2);
+        let last = triage_output(&r, &filter, EntitySort::Risk, EntityFields::Full, 2, 2);
+        let past_end = triage_output(&r, &filter, EntitySort::Risk, EntityFields::Full, 3, 2);
+        assert_eq!((first["returned"].as_u64(), first["next_offset"].as_u64()), (Some(2), Some(2)));
+        assert_eq!((last["returned"].as_u64(), last["next_offset"].as_u64()), (Some(1), None));
+        assert_eq!((past_end["returned"].as_u64(), past_end["next_offset"].as_u64()), (Some(0), None));
+        let by_risk = [1, 2, 0].map(|i| r.entity_reviews[i].entity_name.clone());
+        assert_eq!([names(&first), names(&last)].concat(), by_risk);
+
+        // Every page describes the whole matching set
+        for page in [&first, &last, &past_end] {
+            assert_eq!(page["total_entities_matching"], 3);
+            assert_eq!(page["stats"]["total_entities"], 3);
+        }
+
+        let minimal = triage_output(&r, &filter, EntitySort::Risk, EntityFields::Minimal, 0, 1);
+        let mut keys: Vec<&str> = minimal["entities"][0].as_object().unwrap().keys().map(|k| k.as_str()).collect();
+        keys.sort();
+        assert_eq!(keys, ["file", "group_id", "name", "risk", "score"]);
+        assert_eq!(minimal["entities"][0]["score"], "0.90");
+        assert!(EntityFields::parse("everything").is_err());
+    }
+
+    #[tokio::test]
+    async fn session_config_applies_defaults_and_invalidates_cache() {
+        let server = InspectServer::new();
+        let key = RunKey {
+            target: CacheKey {
+                repo_path: "/repo".into(),
+                resolved: "abc123".into(),
+            },
+            generation: 0,
+            fast: false,
+        };
+        let mut r = result();
+        r.entity_reviews[0].risk_level = RiskLevel::High;
+        r.entity_reviews[1].file_path = "tests/config.rs".into();
+        r.entity_reviews[1].risk_level = RiskLevel::High;
+        r.stats = compute_stats(&r.entity_reviews);
+        server.cache.lock().await.insert(key.clone(), r.clone());
+
+        let params: ConfigureParams =
+            serde_json::from_value(serde_json::json!({ "min_risk": "high", "hide_tests": true })).unwrap();
+        let (_, cleared) = server.configure(&params).await.unwrap();
+        assert!(!cleared);
+        let cached = server.cache.lock().await.get(&key).unwrap();
+
+        // Triage with no filter arguments picks up the session defaults
+        let mut filter = entity_filter(None, None, None).unwrap();
+        server.config.lock().await.apply_defaults(&mut filter, None);
+        let output = triage_output(&cached, &filter, EntitySort::Risk, EntityFields::Full, 0, 50);
+        assert_eq!(output["total_entities_matching"], 1);
+        assert_eq!(output["entities"][0]["name"], "load_config");
+        assert_eq!(output["filters"]["min_risk"], "high");
+        assert_eq!(output["filters"]["hide_tests"], true);
+
+        // An explicit argument wins over the session default
+        let mut filter = entity_filter(Some("low"), None, None).unwrap();
+        server.config.lock().await.apply_defaults(&mut filter, Some(false));
+        assert_eq!(triage_output(&cached, &filter, EntitySort::Risk, EntityFields::Full, 0, 50)["total_entities_matching"], 3);
+
+        // New weights drop results scored with the old ones
+        let params: ConfigureParams =
+            serde_json::from_value(serde_json::json!({ "weights": { "public_api": 0.5 } })).unwrap();
+        let (config, cleared) = server.configure(&params).await.unwrap();
+        assert!(cleared);
+        assert_eq!(config.generation, 1);
+        assert!(server.cache.lock().await.get(&key).is_none());
+
+        // Rejected arguments are invalid params and change nothing
+        let params: ConfigureParams = serde_json::from_value(serde_json::json!({ "min_risk": "severe" })).unwrap();
+        assert_eq!(server.configure(&params).await.unwrap_err().code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(server.config.lock().await.min_risk, Some(RiskLevel::High));
+    }
+
+    #[test]
+    fn report_is_the_markdown_summary_cut_to_max_entities() {
+        let mut r = result();
+        for (review, (score, level)) in r.entity_reviews.iter_mut().zip([
+            (0.2, RiskLevel::Low),
+            (0.9, RiskLevel::Critical),
+            (0.5, RiskLevel::Medium),
+        ]) {
+            review.risk_score = score;
+            review.risk_level = level;
+            review.before_content = Some("fn f() {\n    1\n}\n".into());
+            review.after_content = Some("fn f() {\n    2\n}\n".into());
+        }
+        r.stats = compute_stats(&r.entity_reviews);
+
+        let all = report_markdown(&r, &EntityFilter::default(), 0, false);
+        assert!(all.starts_with("# inspect: 3 entities changed\n"), "{}", all);
+        let rows: Vec<&str> = all.lines().filter(|l| l.starts_with("| ") && l.contains('`')).collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].contains("`save_config`"));
+        assert!(all.contains("### `save_config` (function)"));
+        assert!(!all.contains("```diff"));
+
+        let cut = report_markdown(&r, &EntityFilter::default(), 1, true);
+        assert!(cut.contains("… and 2 more entities (raise max_entities to see them)"));
+        assert_eq!(cut.matches("```diff").count(), 1);
+
+        let high = entity_filter(Some("high"), None, None).unwrap();
+        assert!(report_markdown(&r, &high, 0, false).starts_with("# inspect: 1 entities changed\n"));
+    }
+
+    #[test]
+    fn rescoring_with_session_weights() {
+        let mut r = result();
+        r.entity_reviews[2].is_public_api = true;
+        let mut config = SessionConfig::default();
+        let params: ConfigureParams =
+            serde_json::from_value(serde_json::json!({ "weights": { "public_api": 0.6 } })).unwrap();
+        config.update(&params).unwrap();
+
+        rescore(&mut r, &config.risk_config(RiskConfig::default()));
+        assert_eq!(r.entity_reviews[0].entity_name, "render");
+        assert_eq!(r.entity_reviews[0].risk_level, RiskLevel::Critical);
+        assert_eq!(r.stats.by_risk.critical, 1);
+    }
+
+    #[test]
+    fn stats_surface_warnings_and_confidence() {
+        let mut r = result();
+        let output = stats_output(&r);
+        assert_eq!(output["confidence"], "full");
+        assert_eq!(output["warnings"].as_array().unwrap().len(), 0);
+        assert_eq!(output["timing"]["graph_entity_count"], 0);
+
+        // One unparseable file next to one analyzed file
+        r.warnings.push(AnalysisWarning {
+            kind: WarningKind::NoEntities,
+            file_path: Some("src/broken.rs".into()),
+            message: "src/broken.rs: changed but no entities extracted".into(),
+        });
+        let output = stats_output(&r);
+        assert_eq!(output["confidence"], "degraded");
+        assert_eq!(output["unanalyzed_files"], 1);
+        assert_eq!(output["warnings"][0]["kind"], "no_entities");
+        assert_eq!(output["warnings"][0]["file_path"], "src/broken.rs");
+
+        let mut r = result();
+        r.warnings.push(AnalysisWarning {
+            kind: WarningKind::GraphSkipped,
+            file_path: None,
+            message: "entity graph not built".into(),
+        });
+        assert_eq!(stats_output(&r)["confidence"], "degraded");
+    }
+
+    /// GitHub's API for one PR with one added file: GraphQL, the PR diff,
+    /// its review comments, and posting reviews. The head commit can be
+    /// moved, the PR and blob queries are counted, and posted reviews kept.
+    #[derive(Clone, Default)]
+    struct FakeGitHub {
+        head: Arc<std::sync::Mutex<String>>,
+        unauthorized: bool,
+        pr_queries: Arc<std::sync::atomic::AtomicUsize>,
+        blob_queries: Arc<std::sync::atomic::AtomicUsize>,
+        reviews: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+    }
+
+    impl FakeGitHub {
+        fn counts(&self) -> (usize, usize) {
+            use std::sync::atomic::Ordering::SeqCst;
+            (self.pr_queries.load(SeqCst), self.blob_queries.load(SeqCst))
+        }
+
+        async fn serve(self) -> GitHubClient {
+            let app = axum::Router::new()
+                .route("/graphql", axum::routing::post(fake_graphql))
+                .route("/repos/{owner}/{repo}/pulls/{number}", axum::routing::get(|| async { PR_DIFF }))
+                .route(
+                    "/repos/{owner}/{repo}/pulls/{number}/comments",
+                    axum::routing::get(|| async { axum::Json(serde_json::json!([])) }),
+                )
+                .route("/repos/{owner}/{repo}/pulls/{number}/reviews", axum::routing::post(fake_review))
+                .with_state(self);
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            GitHubClient::with_base_url("test-token", &url).unwrap()
+        }
+    }
+
+    async fn fake_graphql(
+        axum::extract::State(gh): axum::extract::State<FakeGitHub>,
+        axum::Json(req): axum::Json<serde_json::Value>,
+    ) -> axum::response::Response {
+        use axum::response::IntoResponse;
+        use std::sync::atomic::Ordering::SeqCst;
+        if gh.unauthorized {
+            let body = serde_json::json!({"message": "Bad credentials"});
+            return (axum::http::StatusCode::UNAUTHORIZED, axum::Json(body)).into_response();
+        }
+        let query = req["query"].as_str().unwrap_or_default();
+        let body = if query.contains("pullRequest(number") {
+            gh.pr_queries.fetch_add(1, SeqCst);
+            serde_json::json!({"data": {"repository": {"pullRequest": {
+                "number": req["variables"]["number"], "title": "Add a", "body": null, "state": "OPEN",
+                "additions": 1, "deletions": 0, "changedFiles": 1,
+                "headRefName": "feature", "baseRefName": "main",
+                "headRefOid": *gh.head.lock().unwrap(), "baseRefOid": "base",
+                "files": {
+                    "pageInfo": {"hasNextPage": false, "endCursor": null},
+                    "nodes": [{"path": "src/a.rs", "additions": 1, "deletions": 0, "changeType": "ADDED"}],
+                },
+            }}}})
+        } else {
+            gh.blob_queries.fetch_add(1, SeqCst);
+            serde_json::json!({"data": {"repository": {
+                "f0": {"text": "pub fn a() {}\n", "isBinary": false, "byteSize": 14},
+            }}})
+        };
+        axum::Json(body).into_response()
+    }
+
+    /// Lines 1-3 of src/a.rs are added; nothing else can take a comment.
+    const PR_DIFF: &str = "\
+diff --git a/src/a.rs b/src/a.rs
+new file mode 100644
+--- /dev/null
++++ b/src/a.rs
+@@ -0,0 +1,3 @@
++pub fn a() {
++    todo!()
++}
+";
+
+    async fn fake_review(
+        axum::extract::State(gh): axum::extract::State<FakeGitHub>,
+        axum::Json(review): axum::Json<serde_json::Value>,
+    ) -> axum::Json<serde_json::Value> {
+        let mut reviews = gh.reviews.lock().unwrap();
+        reviews.push(review);
+        let id = reviews.len();
+        axum::Json(serde_json::json!({"id": id, "html_url": format!("https://github.com/owner/repo/pull/7#review-{id}")}))
+    }
+
+    fn remote_params(args: serde_json::Value) -> RemoteTriageParams {
+        let mut params = serde_json::json!({"repo": "owner/repo", "pr_number": 7});
+        params.as_object_mut().unwrap().extend(args.as_object().unwrap().clone());
+        serde_json::from_value(params).unwrap()
+    }
+
+    #[tokio::test]
+    async fn remote_triage_is_cached_per_head_commit() {
+        let gh = FakeGitHub::default();
+        *gh.head.lock().unwrap() = "head1".into();
+        let client = gh.clone().serve().await;
+        let server = InspectServer::new();
+
+        let first = server.remote_triage(&client, remote_params(serde_json::json!({}))).await.unwrap();
+        assert_eq!(body(&first)["pr"]["head_sha"], "head1");
+        assert_eq!(body(&first)["cache"], "miss");
+        assert_eq!(gh.counts(), (1, 1));
+
+        // Same head: only the PR metadata is fetched again
+        let again = server.remote_triage(&client, remote_params(serde_json::json!({}))).await.unwrap();
+        assert_eq!(body(&again)["cache"], "hit");
+        assert_eq!(gh.counts(), (2, 1));
+
+        // A new push, or another PR, is a new analysis
+        *gh.head.lock().unwrap() = "head2".into();
+        let pushed = server.remote_triage(&client, remote_params(serde_json::json!({}))).await.unwrap();
+        assert_eq!(body(&pushed)["pr"]["head_sha"], "head2");
+        assert_eq!(gh.counts(), (3, 2));
+        server
+            .remote_triage(&client, remote_params(serde_json::json!({"pr_number": 8})))
+            .await
+            .unwrap();
+        assert_eq!(gh.counts(), (4, 3));
+    }
+
+    #[tokio::test]
+    async fn remote_triage_has_the_triage_shape() {
+        let gh = FakeGitHub::default();
+        let client = gh.clone().serve().await;
+        let server = InspectServer::new();
+
+        let args = serde_json::json!({"min_risk": "high", "limit": 5, "offset": 0, "sort": "file", "hide_tests": true});
+        let output = body(&server.remote_triage(&client, remote_params(args)).await.unwrap());
+        assert_eq!(output["filters"]["min_risk"], "high");
+        assert_eq!(output["filters"]["hide_tests"], true);
+        assert_eq!(output["pr"]["number"], 7);
+        assert_eq!(output["pr"]["title"], "Add a");
+        for field in ["verdict", "stats", "total_before_filter", "total_entities_matching", "next_offset", "entities", "groups", "elapsed_ms"] {
+            assert!(output.get(field).is_some(), "missing {field}");
+        }
+
+        // Bad arguments fail before any request
+        let err = server
+            .remote_triage(&client, remote_params(serde_json::json!({"min_risk": "severe"})))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        let err = server
+            .remote_triage(&client, remote_params(serde_json::json!({"sort": "size"})))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        let err = server
+            .remote_triage(&client, remote_params(serde_json::json!({"limit": 0})))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(gh.counts(), (1, 1));
+    }
+
+    fn review_params(args: serde_json::Value) -> PostReviewParams {
+        let mut params = serde_json::json!({"repo": "owner/repo", "pr_number": 7});
+        params.as_object_mut().unwrap().extend(args.as_object().unwrap().clone());
+        serde_json::from_value(params).unwrap()
+    }
+
+    #[tokio::test]
+    async fn post_review_posts_comments_on_the_diff() {
+        let gh = FakeGitHub::default();
+        *gh.head.lock().unwrap() = "head1".into();
+        let client = gh.clone().serve().await;
+
+        let args = serde_json::json!({
+            "event": "request_changes",
+            "body": "Needs work",
+            "comments": [
+                {"path": "src/a.rs", "line": 2, "body": "todo left in"},
+                {"path": "src/a.rs", "line": 3, "start_line": 1, "body": "whole function"},
+            ],
+        });
+        let output = body(&post_review(&client, review_params(args)).await.unwrap());
+        assert_eq!(output["id"], 1);
+        assert_eq!(output["html_url"], "https://github.com/owner/repo/pull/7#review-1");
+        assert_eq!(output["rejected"], serde_json::json!([]));
+
+        let reviews = gh.reviews.lock().unwrap();
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(reviews[0]["commit_id"], "head1");
+        assert_eq!(reviews[0]["event"], "REQUEST_CHANGES");
+        assert_eq!(reviews[0]["body"], "Needs work");
+        assert_eq!(reviews[0]["comments"][0], serde_json::json!({"path": "src/a.rs", "line": 2, "body": "todo left in"}));
+        assert_eq!(reviews[0]["comments"][1]["start_line"], 1);
+    }
+
+    #[tokio::test]
+    async fn post_review_rejects_comments_off_the_diff() {
+        let gh = FakeGitHub::default();
+        let client = gh.clone().serve().await;
+
+        let args = serde_json::json!({
+            "comments": [
+                {"path": "src/a.rs", "line": 20, "body": "far away"},
+                {"path": "src/b.rs", "line": 1, "body": "unchanged file"},
+                {"path": "src/a.rs", "line": 2, "start_line": 2, "body": "empty range"},
+                {"path": "src/a.rs", "line": 5, "body": "close enough"},
+            ],
+        });
+        let output = body(&post_review(&client, review_params(args)).await.unwrap());
+        let rejected: Vec<_> = output["rejected"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| (r["path"].as_str().unwrap(), r["line"].as_u64().unwrap(), r["reason"].as_str().unwrap(), r["suggested_line"].as_u64()))
+            .collect();
+        assert_eq!(
+            rejected,
+            [
+                ("src/a.rs", 20, "line not in diff", Some(3)),
+                ("src/b.rs", 1, "not a changed file", None),
+                ("src/a.rs", 2, "start_line must be before line", Some(2)),
+            ]
+        );
+        assert_eq!(output["comments"][3]["status"], "reanchored");
+
+        // Off-diff comments move into the body; the bad range isn't posted
+        let reviews = gh.reviews.lock().unwrap();
+        assert_eq!(reviews[0]["event"], "COMMENT");
+        assert_eq!(reviews[0]["comments"].as_array().unwrap().len(), 1);
+        assert_eq!(reviews[0]["comments"][0]["line"], 3);
+        let review_body = reviews[0]["body"].as_str().unwrap();
+        assert!(review_body.contains("far away") && review_body.contains("unchanged file"));
+        assert!(!review_body.contains("empty range"));
+    }
+
+    #[tokio::test]
+    async fn post_review_checks_the_event_before_any_request() {
+        let gh = FakeGitHub::default();
+        let client = gh.clone().serve().await;
+        let args = serde_json::json!({"event": "MERGE", "comments": []});
+        let err = post_review(&client, review_params(args)).await.unwrap_err();
+        assert_eq!(err.code, ErrorCode::INVALID_PARAMS);
+        assert_eq!(gh.counts(), (0, 0));
+        assert!(gh.reviews.lock().unwrap().is_empty());
+    }
+
+    /// The PR from [`PR_DIFF`] at head "head1" with no review comments yet,
+    /// mounted on a wiremock server. Posting a review returns id 1 at most
+    /// `reviews` times.
+    async fn mock_pr(reviews: u64) -> (wiremock::MockServer, GitHubClient) {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let pr = serde_json::json!({"data": {"repository": {"pullRequest": {
+            "number": 7, "title": "Add a", "body": null, "state": "OPEN",
+            "additions": 3, "deletions": 0, "changedFiles": 1,
+            "headRefName": "feature", "baseRefName": "main",
+            "headRefOid": "head1", "baseRefOid": "base",
+            "files": {
+                "pageInfo": {"hasNextPage": false, "endCursor": null},
+                "nodes": [{"path": "src/a.rs", "additions": 3, "deletions": 0, "changeType": "ADDED"}],
+            },
+        }}}});
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pr))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/pulls/7"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(PR_DIFF))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo/pulls/7/comments"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/repo/pulls/7/reviews"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                serde_json::json!({"id": 1, "html_url": "https://github.com/owner/repo/pull/7#review-1"}),
+            ))
+            .expect(reviews)
+            .mount(&server)
+            .await;
+        let client = GitHubClient::with_base_url("test-token", &server.uri()).unwrap();
+        (server, client)
+    }
+
+    /// Bodies of the reviews posted to `server`.
+    async fn posted_reviews(server: &wiremock::MockServer) -> Vec<serde_json::Value> {
+        server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|r| r.method.as_str() == "POST" && r.url.path().ends_with("/reviews"))
+            .map(|r| serde_json::from_slice(&r.body).unwrap())
+            .collect()
+    }
+
+    /// One comment on the diff, one a line off it, one far off it and one
+    /// with an empty range.
+    fn mixed_comments() -> serde_json::Value {
+        serde_json::json!({
+            "body": "Looks close",
+            "comments": [
+                {"path": "src/a.rs", "line": 2, "body": "todo left in"},
+                {"path": "src/a.rs", "line": 4, "body": "just past the end"},
+                {"path": "src/a.rs", "line": 40, "body": "far away"},
+                {"path": "src/a.rs", "line": 2, "start_line": 3, "body": "backwards range"},
+            ],
+        })
+    }
+
+    #[tokio::test]
+    async fn post_review_posts_the_anchored_payload() {
+        let (server, client) = mock_pr(1).await;
+        let output = body(&post_review(&client, review_params(mixed_comments())).await.unwrap());
+        let statuses: Vec<_> = output["comments"].as_array().unwrap().iter().map(|c| c["status"].as_str().unwrap()).collect();
+        assert_eq!(statuses, ["anchored", "reanchored", "demoted", "rejected"]);
+        assert_eq!(output["html_url"], "https://github.com/owner/repo/pull/7#review-1");
+
+        let reviews = posted_reviews(&server).await;
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(
+            reviews[0],
+            serde_json::json!({
+                "commit_id": "head1",
+                "event": "COMMENT",
+                "body": "Looks close\n\n**Comments outside the diff**\n\n- `src/a.rs:40` (line not in diff): far away",
+                "comments": [
+                    {"path": "src/a.rs", "line": 2, "body": "todo left in"},
+                    {"path": "src/a.rs", "line": 3, "body": "just past the end"},
+                ],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn post_review_dry_run_posts_nothing() {
+        let (server, client) = mock_pr(0).await;
+        let mut args = mixed_comments();
+        args["dry_run"] = serde_json::json!(true);
+        let output = body(&post_review(&client, review_params(args)).await.unwrap());
+        assert_eq!(output["dry_run"], true);
+        assert_eq!(output["review"]["commit_id"], "head1");
+        assert_eq!(output["review"]["comments"].as_array().unwrap().len(), 2);
+        assert_eq!(output["review"]["comments"][1]["line"], 3);
+        assert_eq!(output["rejected"].as_array().unwrap().len(), 2);
+        assert!(posted_reviews(&server).await.is_empty());
+    }
+
+    /// A PR changing src/a.rs and Cargo.lock, with src/a.rs readable at head
+    /// and Code Search finding src/a.rs and src/b.rs on the default branch.
+    async fn mock_search() -> (wiremock::MockServer, GitHubClient) {
+        use wiremock::matchers::{body_string_contains, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let pr = serde_json::json!({"data": {"repository": {"pullRequest": {
+            "number": 7, "title": "Parse args", "body": null, "state": "OPEN",
+            "additions": 2, "deletions": 0, "changedFiles": 2,
+            "headRefName": "feature", "baseRefName": "main",
+            "headRefOid": "head1", "baseRefOid": "base",
+            "files": {
+                "pageInfo": {"hasNextPage": false, "endCursor": null},
+                "nodes": [
+                    {"path": "src/a.rs", "additions": 1, "deletions": 0, "changeType": "MODIFIED"},
+                    {"path": "Cargo.lock", "additions": 1, "deletions": 0, "changeType": "MODIFIED"},
+                ],
+            },
+        }}}});
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_string_contains("pullRequest(number"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(pr))
+            .mount(&server)
+            .await;
+        let blobs = serde_json::json!({"data": {"repository": {
+            "f0": {"text": "fn parse_args() {}\n", "isBinary": false, "byteSize": 19},
+        }}});
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(blobs))
+            .mount(&server)
+            .await;
+        let results = serde_json::json!({"total_count": 2, "incomplete_results": false, "items": [
+            {"name": "a.rs", "path": "src/a.rs", "repository": {"full_name": "owner/repo"},
+             "html_url": "https://github.com/owner/repo/blob/main/src/a.rs",
+             "text_matches": [{"fragment": "fn parse_old() {}", "matches": []}]},
+            {"name": "b.rs", "path": "src/b.rs", "repository": {"full_name": "owner/repo"},
+             "html_url": "https://github.com/owner/repo/blob/main/src/b.rs",
+             "text_matches": [{"fragment": "let args = parse_args();\nrun(args);", "matches": []}]},
+        ]});
+        Mock::given(method("GET"))
+            .and(path("/search/code"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(results))
+            .mount(&server)
+            .await;
+        let client = GitHubClient::with_base_url("test-token", &server.uri()).unwrap();
+        (server, client)
+    }
+
+    /// Requests `server` received whose path is `request_path`.
+    async fn requests_to(server: &wiremock::MockServer, request_path: &str) -> Vec<wiremock::Request> {
+        let requests = server.received_requests().await.unwrap();
+        requests.into_iter().filter(|r| r.url.path() == request_path).collect()
+    }
+
+    fn search_params(args: serde_json::Value) -> SearchParams {
+        let mut params = serde_json::json!({"repo": "owner/repo", "pr_number": 7});
+        params.as_object_mut().unwrap().extend(args.as_object().unwrap().clone());
+        serde_json::from_value(params).unwrap()
+    }
+
+    #[tokio::test]
+    async fn search_merges_pr_files_with_code_search() {
+        let (server, client) = mock_search().await;
+        let inspect = InspectServer::new();
+        let args = serde_json::json!({"pattern": r"parse_\w+\(", "regex": true, "repo_wide": true});
+
+        let output = body(&inspect.search_pr(&client, search_params(args.clone())).await.unwrap());
+        let matches: Vec<_> = output["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| (m["file"].as_str().unwrap(), m["line"].as_u64().unwrap(), m["source"].as_str().unwrap()))
+            .collect();
+        // src/a.rs comes from the PR at head, not its default-branch copy
+        assert_eq!(matches, [("src/a.rs", 1, "pr"), ("src/b.rs", 1, "codebase")]);
+        assert_eq!(output["code_search"]["results"], 2);
+
+        // Code Search got the regex's literal, not the regex
+        let searches = requests_to(&server, "/search/code").await;
+        let query: Vec<_> = searches[0].url.query_pairs().filter(|(k, _)| k == "q").map(|(_, v)| v.into_owned()).collect();
+        assert_eq!(query, ["parse_ repo:owner/repo"]);
+
+        // Only src/a.rs was read; the lockfile is noise. A repeat search reuses it.
+        let graphql = requests_to(&server, "/graphql").await;
+        let blob_query = String::from_utf8_lossy(&graphql[1].body).into_owned();
+        assert!(blob_query.contains("src/a.rs") && !blob_query.contains("Cargo.lock"));
+        inspect.search_pr(&client, search_params(args)).await.unwrap();
+        assert_eq!(requests_to(&server, "/graphql").await.len(), 3, "PR metadata only");
+    }
+
+    #[tokio::test]
+    async fn search_skips_code_search_without_a_literal() {
+        let (server, client) = mock_search().await;
+        let args = serde_json::json!({"pattern": r"\w+_\w+\(", "regex": true, "repo_wide": true});
+        let output = body(&InspectServer::new().search_pr(&client, search_params(args)).await.unwrap());
+        assert_eq!(output["total_matches"], 1);
+        assert!(output["code_search"]["skipped"].is_string());
+        assert!(requests_to(&server, "/search/code").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejected_tokens_name_github_token() {
+        let gh = FakeGitHub {
+            unauthorized: true,
+            ..Default::default()
+        };
+        let client = gh.serve().await;
+        let result = InspectServer::new()
+            .remote_triage(&client, remote_params(serde_json::json!({})))
+            .await
+            .unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert!(body(&result)["error"].as_str().unwrap().contains("GITHUB_TOKEN"), "{:?}", body(&result));
+    }
+}
@@ -4,58 +4,218 @@ use serde::Deserialize;
 pub struct TriageParams {
     #[schemars(description = "Absolute path to the git repository")]
     pub repo_path: String,
-    #[schemars(description = "What to analyze: a commit ref (e.g. 'HEAD~1'), a range ('main..feature'), or 'working' for uncommitted changes")]
+    #[schemars(description = "What to analyze: a commit ref (e.g. 'HEAD~1'), a range ('main..feature', or 'main...feature' to diff from the merge base), 'working' for uncommitted changes, or 'staged' for changes in the index only")]
     pub target: String,
     #[schemars(description = "Minimum risk level to include: 'low', 'medium', 'high', or 'critical'")]
     pub min_risk: Option<String>,
+    #[schemars(description = "Minimum risk score to include, 0.0 to 1.0")]
+    pub min_score: Option<f64>,
+    #[schemars(description = "Only include these classifications: 'text', 'syntax', 'functional', 'text+syntax', 'text+functional', 'syntax+functional', 'text+syntax+functional'")]
+    pub classification: Option<Vec<String>>,
+    #[schemars(description = "Maximum number of entities to return (default: 50)")]
+    pub limit: Option<usize>,
+    #[schemars(description = "Number of entities to skip; pass the previous response's next_offset")]
+    pub offset: Option<usize>,
+    #[schemars(description = "Entity order: 'risk' (default, highest first) or 'file'")]
+    pub sort: Option<String>,
+    #[schemars(description = "Entity detail: 'full' (default) or 'minimal' (name, file, risk, score, group_id only)")]
+    pub fields: Option<String>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+    #[schemars(description = "Hide test code: entities in test files or Rust #[cfg(test)] modules (default: the session setting from inspect_configure, else false)")]
+    pub hide_tests: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct EntityParams {
     #[schemars(description = "Absolute path to the git repository")]
     pub repo_path: String,
-    #[schemars(description = "What to analyze: commit ref, range, or 'working'")]
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
     pub target: String,
     #[schemars(description = "Name of the entity to inspect")]
     pub entity_name: String,
     #[schemars(description = "File path to disambiguate entities with the same name")]
     pub file_path: Option<String>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DiffParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
+    pub target: String,
+    #[schemars(description = "Name of the entity to diff")]
+    pub entity_name: String,
+    #[schemars(description = "File path to disambiguate entities with the same name")]
+    pub file_path: Option<String>,
+    #[schemars(description = "Unchanged lines of context around each hunk (default: 3)")]
+    pub context_lines: Option<usize>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DependentsParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "Name of the entity to look up (need not be part of any diff)")]
+    pub entity_name: String,
+    #[schemars(description = "File path to disambiguate entities with the same name")]
+    pub file_path: Option<String>,
+    #[schemars(description = "Edges to follow: 'dependents' (default), 'dependencies', or 'both'")]
+    pub direction: Option<String>,
+    #[schemars(description = "How many hops to traverse (default: 1, max: 5)")]
+    pub depth: Option<usize>,
+    #[schemars(description = "Rebuild the entity graph instead of using the cached one")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ImpactParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "Name of the entity you plan to change (need not be part of any diff)")]
+    pub entity_name: String,
+    #[schemars(description = "File path to disambiguate entities with the same name")]
+    pub file_path: Option<String>,
+    #[schemars(description = "How many hops of dependents to follow (default: 3, max: 5)")]
+    pub depth: Option<usize>,
+    #[schemars(description = "Rebuild the entity graph instead of using the cached one")]
+    pub force_refresh: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct GroupParams {
     #[schemars(description = "Absolute path to the git repository")]
     pub repo_path: String,
-    #[schemars(description = "What to analyze: commit ref, range, or 'working'")]
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
     pub target: String,
     #[schemars(description = "Group ID to inspect")]
     pub group_id: usize,
+    #[schemars(description = "Minimum risk level to include: 'low', 'medium', 'high', or 'critical'")]
+    pub min_risk: Option<String>,
+    #[schemars(description = "Minimum risk score to include, 0.0 to 1.0")]
+    pub min_score: Option<f64>,
+    #[schemars(description = "Only include these classifications: 'text', 'syntax', 'functional', 'text+syntax', 'text+functional', 'syntax+functional', 'text+syntax+functional'")]
+    pub classification: Option<Vec<String>>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+    #[schemars(description = "Hide test code: entities in test files or Rust #[cfg(test)] modules (default: the session setting from inspect_configure, else false)")]
+    pub hide_tests: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct FileParams {
     #[schemars(description = "Absolute path to the git repository")]
     pub repo_path: String,
-    #[schemars(description = "What to analyze: commit ref, range, or 'working'")]
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
     pub target: String,
     #[schemars(description = "File path to scope the review to")]
     pub file_path: String,
+    #[schemars(description = "Minimum risk level to include: 'low', 'medium', 'high', or 'critical'")]
+    pub min_risk: Option<String>,
+    #[schemars(description = "Minimum risk score to include, 0.0 to 1.0")]
+    pub min_score: Option<f64>,
+    #[schemars(description = "Only include these classifications: 'text', 'syntax', 'functional', 'text+syntax', 'text+functional', 'syntax+functional', 'text+syntax+functional'")]
+    pub classification: Option<Vec<String>>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+    #[schemars(description = "Hide test code: entities in test files or Rust #[cfg(test)] modules (default: the session setting from inspect_configure, else false)")]
+    pub hide_tests: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct StatsParams {
     #[schemars(description = "Absolute path to the git repository")]
     pub repo_path: String,
-    #[schemars(description = "What to analyze: commit ref, range, or 'working'")]
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
     pub target: String,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct VerdictParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
+    pub target: String,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct RiskMapParams {
     #[schemars(description = "Absolute path to the git repository")]
     pub repo_path: String,
-    #[schemars(description = "What to analyze: commit ref, range, or 'working'")]
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
+    pub target: String,
+    #[schemars(description = "Bucket entities by 'file' (default), 'directory', or 'group' (logical change group)")]
+    pub group_by: Option<String>,
+    #[schemars(description = "Directory depth to roll up to when group_by is 'directory' (default: 1)")]
+    pub depth: Option<usize>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ReportParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
+    pub target: String,
+    #[schemars(description = "Minimum risk level to include: 'low', 'medium', 'high', or 'critical'")]
+    pub min_risk: Option<String>,
+    #[schemars(description = "Most entities to list, riskiest first (default: 50, 0 for all)")]
+    pub max_entities: Option<usize>,
+    #[schemars(description = "Embed a truncated diff under each high or critical entity")]
+    pub include_diffs: Option<bool>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CompareParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "Baseline to compare against: commit ref, range, 'working', or 'staged'")]
+    pub base_target: String,
+    #[schemars(description = "Newer state to compare: commit ref, range, 'working', or 'staged'")]
+    pub head_target: String,
+    #[schemars(description = "Bypass the analysis cache and re-run both analyses")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ChangedFilesParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
+    pub target: String,
+    #[schemars(description = "List files from the raw git diff only, skipping entity analysis (default: false)")]
+    pub fast: Option<bool>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct LocalSearchParams {
+    #[schemars(description = "Absolute path to the git repository")]
+    pub repo_path: String,
+    #[schemars(description = "What to analyze: commit ref, range, 'working', or 'staged'")]
     pub target: String,
+    #[schemars(description = "Text pattern to search for (case-insensitive)")]
+    pub pattern: String,
+    #[schemars(description = "Treat the pattern as a regular expression (default: false)")]
+    pub regex: Option<bool>,
+    #[schemars(description = "Search only the changed entities' content instead of whole changed files (default: false)")]
+    pub changed_only: Option<bool>,
+    #[schemars(description = "Maximum number of matches to return (default: 100)")]
+    pub max_results: Option<usize>,
+    #[schemars(description = "Bypass the analysis cache and re-run the analysis")]
+    pub force_refresh: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -66,6 +226,18 @@ pub struct RemoteTriageParams {
     pub pr_number: u64,
     #[schemars(description = "Minimum risk level to include: 'low', 'medium', 'high', or 'critical'")]
     pub min_risk: Option<String>,
+    #[schemars(description = "Hide test code: entities in test files or Rust #[cfg(test)] modules (default: the session setting from inspect_configure, else false)")]
+    pub hide_tests: Option<bool>,
+    #[schemars(description = "Only analyze what changed after this PR commit sha (full or abbreviated), e.g. the last reviewed push")]
+    pub since_commit: Option<String>,
+    #[schemars(description = "Maximum number of entities to return (default: 50)")]
+    pub limit: Option<usize>,
+    #[schemars(description = "Number of entities to skip; pass the previous response's next_offset")]
+    pub offset: Option<usize>,
+    #[schemars(description = "Entity order: 'risk' (default, highest first) or 'file'")]
+    pub sort: Option<String>,
+    #[schemars(description = "Entity detail: 'full' (default) or 'minimal' (name, file, risk, score, group_id only)")]
+    pub fields: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -78,6 +250,12 @@ pub struct PostReviewParams {
     pub body: Option<String>,
     #[schemars(description = "Review comments to post. Each has: path (file), line (number), body (text), start_line (optional, for multi-line)")]
     pub comments: Vec<ReviewComment>,
+    #[schemars(description = "Review event: 'COMMENT' (default), 'APPROVE', or 'REQUEST_CHANGES'")]
+    pub event: Option<String>,
+    #[schemars(description = "Validate and preview the review without posting it (default: false)")]
+    pub dry_run: Option<bool>,
+    #[schemars(description = "Post comments even if the same comment is already on the PR within a few lines (default: false)")]
+    pub allow_duplicates: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -88,7 +266,7 @@ pub struct ReviewComment {
     pub line: u64,
     #[schemars(description = "Comment body text")]
     pub body: String,
-    #[schemars(description = "Start line for multi-line comments")]
+    #[schemars(description = "Start line for multi-line comments; must be before line")]
     pub start_line: Option<u64>,
 }
 
@@ -104,4 +282,30 @@ pub struct SearchParams {
     pub repo_wide: Option<bool>,
     #[schemars(description = "Case-sensitive search (default: false)")]
     pub case_sensitive: Option<bool>,
+    #[schemars(description = "Treat the pattern as a regular expression (default: false)")]
+    pub regex: Option<bool>,
+    #[schemars(description = "Maximum number of matches to return (default: 100, max: 200)")]
+    pub max_results: Option<usize>,
+    #[schemars(description = "Lines of context around each PR match (default: 2, max: 5)")]
+    pub context_lines: Option<usize>,
+    #[schemars(description = "Only search files matching any of these globs, e.g. ['src/**/*.ts']; '*' does not cross '/'")]
+    pub include: Option<Vec<String>>,
+    #[schemars(description = "Skip files matching any of these globs, e.g. ['**/*.test.ts']")]
+    pub exclude: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ConfigureParams {
+    #[schemars(description = "Path to a JSON risk weights file (keys: public_api, blast_radius, dependents, cosmetic_discount); missing keys keep their defaults")]
+    pub weights_path: Option<String>,
+    #[schemars(description = "Inline weight overrides applied on top of the weights file or current weights, e.g. {\"public_api\": 0.2}")]
+    pub weights: Option<serde_json::Value>,
+    #[schemars(description = "Default minimum risk level for entity lists: 'low', 'medium', 'high', or 'critical'")]
+    pub min_risk: Option<String>,
+    #[schemars(description = "Hide entities in test files by default")]
+    pub hide_tests: Option<bool>,
+    #[schemars(description = "Analyze without the entity graph by default (faster; blast radius and dependents are 0)")]
+    pub fast: Option<bool>,
+    #[schemars(description = "Restore the built-in defaults before applying the other arguments")]
+    pub reset: Option<bool>,
 }